@@ -149,13 +149,13 @@ pub fn setup_remotes(
     Ok(())
 }
 
-/// Setup a remote and tracking branch for the given remote.
+/// Setup a remote and tracking branch for the given remote. Returns the name of the remote.
 pub fn setup_remote(
     setup: &project::SetupRemote,
     remote_id: &NodeId,
     remote_name: Option<git::RefString>,
     aliases: &impl AliasStore,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<git::RefString> {
     let remote_name = if let Some(name) = remote_name {
         name
     } else {
@@ -167,7 +167,7 @@ pub fn setup_remote(
         git::RefString::try_from(name.as_str())
             .map_err(|_| anyhow!("invalid remote name: '{name}'"))?
     };
-    let (remote, branch) = setup.run(remote_name, *remote_id)?;
+    let (remote, branch) = setup.run(&remote_name, *remote_id)?;
 
     term::success!("Remote {} added", term::format::tertiary(remote.name));
 
@@ -178,5 +178,5 @@ pub fn setup_remote(
             term::format::tertiary(term::format::node(remote_id))
         );
     }
-    Ok(())
+    Ok(remote_name)
 }
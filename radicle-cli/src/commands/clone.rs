@@ -1,6 +1,6 @@
 #![allow(clippy::or_fun_call)]
 use std::ffi::OsString;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time;
 
@@ -11,7 +11,7 @@ use radicle::git::raw;
 use radicle::identity::doc::{DocError, Id};
 use radicle::identity::{doc, IdentityError};
 use radicle::node;
-use radicle::node::tracking::Scope;
+use radicle::node::tracking::{Origin, Scope};
 use radicle::node::{Handle as _, Node};
 use radicle::prelude::*;
 use radicle::rad;
@@ -36,6 +36,8 @@ Usage
 Options
 
     --scope <scope>   Tracking scope (default: all)
+    --sparse <path>   Only check out the files under the given path
+    --resume          Resume an interrupted checkout, and keep it if interrupted again
     --no-announce     Do not announce our new refs to the network
     --help            Print help
 
@@ -47,6 +49,8 @@ pub struct Options {
     id: Id,
     announce: bool,
     scope: Scope,
+    sparse: Option<PathBuf>,
+    resume: bool,
 }
 
 impl Args for Options {
@@ -57,6 +61,8 @@ impl Args for Options {
         let mut id: Option<Id> = None;
         let mut announce = true;
         let mut scope = Scope::All;
+        let mut sparse = None;
+        let mut resume = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -65,6 +71,14 @@ impl Args for Options {
 
                     scope = term::args::parse_value("scope", value)?;
                 }
+                Long("sparse") => {
+                    let value = parser.value()?;
+
+                    sparse = Some(PathBuf::from(value));
+                }
+                Long("resume") => {
+                    resume = true;
+                }
                 Long("no-confirm") => {
                     // We keep this flag here for consistency though it doesn't have any effect,
                     // since the command is fully non-interactive.
@@ -96,6 +110,8 @@ impl Args for Options {
                 id,
                 scope,
                 announce,
+                sparse,
+                resume,
             },
             vec![],
         ))
@@ -104,12 +120,14 @@ impl Args for Options {
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
+    let mut node = ctx.node(&profile)?;
     let signer = term::signer(&profile)?;
-    let mut node = radicle::Node::new(profile.socket());
     let (working, doc, proj) = clone(
         options.id,
         options.announce,
         options.scope,
+        options.sparse.as_deref(),
+        options.resume,
         &mut node,
         &signer,
         &profile.storage,
@@ -170,14 +188,16 @@ pub fn clone<G: Signer>(
     id: Id,
     announce: bool,
     scope: Scope,
+    sparse: Option<&Path>,
+    resume: bool,
     node: &mut Node,
     signer: &G,
     storage: &Storage,
 ) -> Result<(raw::Repository, Doc<Verified>, Project), CloneError> {
     let me = *signer.public_key();
 
-    // Track.
-    if node.track_repo(id, scope)? {
+    // Track. The origin is updated by the node if we turn out to be a delegate.
+    if node.track_repo_origin(id, scope, Origin::Tracked)? {
         term::success!(
             "Tracking relationship established for {} with scope '{scope}'",
             term::format::tertiary(id)
@@ -233,12 +253,31 @@ pub fn clone<G: Signer>(
     }
 
     // Checkout.
-    let spinner = term::spinner(format!(
+    let message = format!(
         "Creating checkout in ./{}..",
         term::format::tertiary(path.display())
-    ));
-    let repo = rad::checkout(id, &me, path, &storage)?;
-
+    );
+    let mut spinner = term::spinner(&message);
+    let result = {
+        let mut progress = |p: rad::CheckoutProgress| {
+            spinner.message(format!("{message} ({}/{} files)", p.written, p.total));
+            Ok(())
+        };
+        let options = rad::CheckoutOptions {
+            sparse,
+            resume,
+            progress: Some(&mut progress),
+        };
+        rad::checkout_with(id, &me, path, &storage, options)
+    };
+    let repo = match result {
+        Ok(repo) => repo,
+        Err(e) => {
+            spinner.failed();
+            return Err(e.into());
+        }
+    };
+    spinner.message(message);
     spinner.finish();
 
     Ok((repo, doc, proj))
@@ -10,6 +10,7 @@ use radicle::prelude::*;
 use radicle::storage;
 
 use crate::git::Rev;
+use crate::project;
 use crate::terminal as term;
 use crate::terminal::args::string;
 use crate::terminal::args::{Args, Error, Help};
@@ -136,8 +137,7 @@ fn comment(
 }
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
-    let (_, id) = radicle::rad::cwd()
-        .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+    let (_, id) = project::cwd()?;
     let profile = ctx.profile()?;
     let repo = profile.storage.repository(id)?;
     let signer = term::signer(&profile)?;
@@ -62,7 +62,7 @@ impl Args for Options {
 }
 
 pub fn run(_options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
-    println!("Usage: rad <command> [--help]");
+    println!("Usage: rad [--offline] <command> [--help]");
 
     if ctx.profile().is_err() {
         println!();
@@ -89,6 +89,7 @@ pub fn run(_options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     }
     println!();
     println!("See `rad <command> --help` to learn about a specific command.");
+    println!("With `--offline`, commands don't connect to the node, and those that need it fail.");
     println!();
 
     Ok(())
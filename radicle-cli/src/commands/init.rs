@@ -9,7 +9,7 @@ use serde_json as json;
 
 use radicle::crypto::ssh;
 use radicle::git::RefString;
-use radicle::node::tracking::Scope;
+use radicle::node::tracking::{Origin, Scope};
 use radicle::node::{Handle, NodeId};
 use radicle::profile;
 
@@ -164,10 +164,14 @@ impl Args for Options {
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
 
-    init(options, &profile)
+    init(options, &profile, &ctx)
 }
 
-pub fn init(options: Options, profile: &profile::Profile) -> anyhow::Result<()> {
+pub fn init(
+    options: Options,
+    profile: &profile::Profile,
+    ctx: &impl term::Context,
+) -> anyhow::Result<()> {
     let cwd = std::env::current_dir()?;
     let path = options.path.unwrap_or_else(|| cwd.clone());
     let path = path.as_path().canonicalize()?;
@@ -213,7 +217,7 @@ pub fn init(options: Options, profile: &profile::Profile) -> anyhow::Result<()>
     let branch = RefString::try_from(branch.clone())
         .map_err(|e| anyhow!("invalid branch name {:?}: {}", branch, e))?;
 
-    let mut node = radicle::Node::new(profile.socket());
+    let mut node = radicle::Node::from_profile(profile);
     let mut spinner = term::spinner("Initializing...");
     let mut push_cmd = String::from("git push");
 
@@ -228,10 +232,10 @@ pub fn init(options: Options, profile: &profile::Profile) -> anyhow::Result<()>
         Ok((id, doc, _)) => {
             let proj = doc.project()?;
 
-            if options.track && node.is_running() {
+            if options.track && ctx.is_node_running(profile) {
                 // It's important to track our own repositories to make sure that our node signals
                 // interest for them. This ensures that messages relating to them are relayed to us.
-                node.track_repo(id, options.scope)?;
+                node.track_repo_origin(id, options.scope, Origin::Created)?;
             }
 
             spinner.message(format!(
@@ -261,7 +265,7 @@ pub fn init(options: Options, profile: &profile::Profile) -> anyhow::Result<()>
                 self::setup_signing(profile.id(), &repo, interactive)?;
             }
 
-            if node.is_running() {
+            if ctx.is_node_running(profile) {
                 let spinner = term::spinner("Syncing inventory..");
                 if let Err(e) = node.sync_inventory() {
                     spinner.error(e);
@@ -270,7 +274,9 @@ pub fn init(options: Options, profile: &profile::Profile) -> anyhow::Result<()>
                 }
             }
 
-            if options.announce {
+            if options.announce && ctx.offline() {
+                term::info!("Skipping inventory announcement, since we're offline");
+            } else if options.announce {
                 let spinner = term::spinner("Announcing inventory..");
                 if let Err(e) = node.announce_inventory() {
                     spinner.error(e);
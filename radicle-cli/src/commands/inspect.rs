@@ -7,16 +7,19 @@ use std::str::FromStr;
 use anyhow::{anyhow, Context as _};
 use chrono::prelude::*;
 use json_color::{Color, Colorizer};
+use localtime::LocalTime;
 
 use radicle::crypto::{Unverified, Verified};
 use radicle::identity::Untrusted;
 use radicle::identity::{Doc, Id};
 use radicle::node::tracking::Policy;
-use radicle::node::AliasStore as _;
+use radicle::node::{AliasStore, Handle as _, Node};
+use radicle::storage::git::stats::{self, RepoStats, Usage};
 use radicle::storage::{ReadRepository, ReadStorage};
 
 use crate::terminal as term;
 use crate::terminal::args::{Args, Error, Help};
+use crate::terminal::Element as _;
 
 pub const HELP: Help = Help {
     name: "inspect",
@@ -40,6 +43,8 @@ Options
     --delegates Inspect the repository's delegates
     --policy    Inspect the repository's tracking policy
     --history   Show the history of the repository identity document
+    --size      Show the size of the repository, per namespace, and its largest objects
+    --json      Output the repository size as JSON (with `--size`)
     --help      Print help
 "#,
 };
@@ -51,6 +56,7 @@ pub enum Target {
     Delegates,
     Policy,
     History,
+    Size,
     #[default]
     Id,
 }
@@ -59,6 +65,7 @@ pub enum Target {
 pub struct Options {
     pub id: Option<Id>,
     pub target: Target,
+    pub json: bool,
 }
 
 impl Args for Options {
@@ -68,6 +75,7 @@ impl Args for Options {
         let mut parser = lexopt::Parser::from_args(args);
         let mut id: Option<Id> = None;
         let mut target = Target::default();
+        let mut json = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -92,6 +100,12 @@ impl Args for Options {
                 Long("id") => {
                     target = Target::Id;
                 }
+                Long("size") => {
+                    target = Target::Size;
+                }
+                Long("json") => {
+                    json = true;
+                }
                 Value(val) if id.is_none() => {
                     let val = val.to_string_lossy();
 
@@ -109,7 +123,10 @@ impl Args for Options {
             }
         }
 
-        Ok((Options { id, target }, vec![]))
+        if json && target != Target::Size {
+            return Err(anyhow!("`--json` can only be used with `--size`"));
+        }
+        Ok((Options { id, target, json }, vec![]))
     }
 }
 
@@ -152,6 +169,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
             if let Some(repo) = tracking.repo_policy(&id)? {
                 let tracking = match repo.policy {
                     Policy::Track => term::format::positive("tracked"),
+                    Policy::Watch => term::format::tertiary("watched"),
                     Policy::Block => term::format::negative("blocked"),
                 };
                 println!(
@@ -235,6 +253,23 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
                 println!();
             }
         }
+        Target::Size => {
+            // Prefer asking the node, which caches the statistics.
+            let stats = if ctx.is_node_running(&profile) {
+                Node::from_profile(&profile)
+                    .repo_size(Some(id), true)?
+                    .pop()
+                    .ok_or_else(|| anyhow!("node returned no statistics for {id}"))?
+            } else {
+                stats::stats(&repo, LocalTime::now().as_millis())?
+            };
+
+            if options.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                size(&stats, &profile.aliases());
+            }
+        }
         Target::Id => {
             // Handled above.
         }
@@ -268,6 +303,86 @@ fn refs(repo: &radicle::storage::git::Repository) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn size(stats: &RepoStats, aliases: &impl AliasStore) {
+    let usage = |u: &Usage| {
+        term::format::default(format!(
+            "{} ({} objects)",
+            term::format::bytes(u.size),
+            u.objects
+        ))
+    };
+
+    let mut table = term::Table::<2, term::Paint<String>>::default();
+    table.push([
+        term::format::bold(String::from("Disk")),
+        term::format::bytes(stats.disk),
+    ]);
+    table.push([
+        term::format::bold(String::from("Total")),
+        usage(&stats.total),
+    ]);
+    table.push([
+        term::format::bold(String::from("Shared")),
+        usage(&stats.shared),
+    ]);
+    table.push([
+        term::format::bold(String::from("Unreachable")),
+        usage(&stats.unreachable),
+    ]);
+    table.print();
+    term::blank();
+
+    let mut table = term::Table::new(term::table::TableOptions::bordered());
+    table.push([
+        term::format::bold(String::from("Namespace")),
+        term::format::bold(String::from("Reachable")),
+        term::format::bold(String::from("Exclusive")),
+    ]);
+    table.divider();
+
+    for ns in &stats.namespaces {
+        let namespace = if let Some(alias) = aliases.alias(&ns.namespace) {
+            format!("{} ({alias})", term::format::node(&ns.namespace))
+        } else {
+            term::format::node(&ns.namespace)
+        };
+        table.push([
+            term::format::tertiary(namespace),
+            usage(&ns.reachable),
+            usage(&ns.exclusive),
+        ]);
+    }
+    table.print();
+    term::blank();
+
+    let mut table = term::Table::new(term::table::TableOptions::bordered());
+    table.push([
+        term::format::bold(String::from("Object")),
+        term::format::bold(String::from("Type")),
+        term::format::bold(String::from("Size")),
+        term::format::bold(String::from("Path")),
+    ]);
+    table.divider();
+
+    for object in &stats.largest {
+        table.push([
+            term::format::secondary(term::format::oid(object.oid).item),
+            term::format::default(object.kind.clone()),
+            term::format::bytes(object.size),
+            term::format::italic(object.path.clone().unwrap_or_default()),
+        ]);
+    }
+    table.print();
+
+    term::info!(
+        "{}",
+        term::format::dim(
+            "Sizes are uncompressed. Objects reachable from more than one namespace are \
+             counted as shared, and not in the exclusive size of any namespace."
+        )
+    );
+}
+
 /// Show the list of given git references as a newline terminated tree `String` similar to the tree command.
 fn tree(mut refs: Vec<String>) -> String {
     refs.sort();
@@ -279,7 +279,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
                 | Operation::Delete { .. }
         );
 
-    let mut node = Node::new(profile.socket());
+    let mut node = Node::from_profile(&profile);
     let mut issues = Issues::open(&repo)?;
 
     match options.op {
@@ -349,7 +349,9 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         }
     }
 
-    if announce {
+    if announce && ctx.offline() {
+        term::info!("Skipping announcement of issue refs, since we're offline");
+    } else if announce {
         match node.announce_refs(rid) {
             Ok(()) => {}
             Err(e) if e.is_connection_err() => {
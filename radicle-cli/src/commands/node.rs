@@ -1,20 +1,27 @@
 use std::ffi::OsString;
-use std::time;
+use std::{net, time};
 
 use anyhow::anyhow;
 
-use radicle::node::{Address, Node, NodeId, PeerAddr, ROUTING_DB_FILE, TRACKING_DB_FILE};
+use radicle::node::config::Validation;
+use radicle::node::{Address, Alias, Node, NodeId, PeerAddr, ROUTING_DB_FILE, TRACKING_DB_FILE};
 use radicle::prelude::Id;
 
 use crate::terminal as term;
 use crate::terminal::args::{Args, Error, Help};
 
+#[path = "node/config.rs"]
+mod config;
 #[path = "node/control.rs"]
 mod control;
+#[path = "node/doctor.rs"]
+mod doctor;
 #[path = "node/events.rs"]
 mod events;
 #[path = "node/routing.rs"]
 mod routing;
+#[path = "node/storage.rs"]
+mod storage;
 #[path = "node/tracking.rs"]
 mod tracking;
 
@@ -29,10 +36,15 @@ Usage
     rad node start [--foreground] [<option>...] [-- <node-option>...]
     rad node stop [<option>...]
     rad node logs [-n <lines>]
-    rad node connect <nid>@<addr> [<option>...]
+    rad node connect <nid>@<addr> [--persist [--alias <alias>]] [<option>...]
     rad node routing [--rid <rid>] [--nid <nid>] [--json] [<option>...]
     rad node tracking [--repos | --nodes] [<option>...]
+    rad node storage [--rid <rid> [--refresh]] [<option>...]
     rad node events [--timeout <secs>] [-n <count>] [<option>...]
+    rad node activity [--since <timestamp>] [-n <count>] [<option>...]
+    rad node doctor [--listen <addr>...] [<option>...]
+    rad node config [<option>...]
+    rad node set-limit <name> <value> [--persist] [<option>...]
 
     For `<node-option>` see `radicle-node --help`.
 
@@ -40,6 +52,13 @@ Start options
 
     --foreground         Start the node in the foreground
 
+Connect options
+
+    --timeout <secs>     How long to wait for the connection to be established
+    --persist            Also add the peer to the configuration file, so that the node
+                         stays connected to it, including after a restart
+    --alias <alias>      Alias of the peer, when added to the configuration file
+
 Routing options
 
     --rid <rid>          Show the routing table entries for the given RID
@@ -51,11 +70,37 @@ Tracking options
     --repos              Show the tracked repositories table
     --nodes              Show the tracked nodes table
 
+Storage options
+
+    --rid <rid>          Only show the storage used by the given repository
+    --refresh            Recompute the storage used by the repository, instead of showing
+                         the cached figures, which may be a few minutes old
+
 Events options
 
     --timeout <secs>     How long to wait to receive an event before giving up
     --count, -n <count>  Exit after <count> events
 
+Activity options
+
+    --since <timestamp>  Only show activity recorded at or after this time, in milliseconds
+    --count, -n <count>  Only show the <count> most recent entries
+
+Doctor options
+
+    --listen <addr>      Check external address ports against this listening address
+    --allow-port-mismatch
+                         Don't check external address ports
+
+Set-limit options
+
+    --persist            Also write the limit to the configuration file
+
+    Limits are changed without restarting the node. Sessions established before the
+    change keep their limits. The limits that can be changed are: fetchConcurrency,
+    fetchMaxWait, routingMaxSize, routingMaxAge, gossipMaxNodes, gossipMaxRefs
+    and relayRate.
+
 General options
 
     --help               Print help
@@ -67,9 +112,20 @@ pub struct Options {
 }
 
 pub enum Operation {
+    Activity {
+        since: Option<u64>,
+        limit: Option<usize>,
+    },
+    Config,
     Connect {
         addr: PeerAddr<NodeId, Address>,
         timeout: time::Duration,
+        persist: bool,
+        alias: Option<Alias>,
+    },
+    Doctor {
+        listen: Vec<net::SocketAddr>,
+        validation: Validation,
     },
     Events {
         timeout: time::Duration,
@@ -87,8 +143,17 @@ pub enum Operation {
     Logs {
         lines: usize,
     },
+    SetLimit {
+        name: String,
+        value: String,
+        persist: bool,
+    },
     Status,
     Stop,
+    Storage {
+        rid: Option<Id>,
+        refresh: bool,
+    },
     Tracking {
         mode: TrackingMode,
     },
@@ -103,14 +168,19 @@ pub enum TrackingMode {
 
 #[derive(Default, PartialEq, Eq)]
 pub enum OperationName {
+    Activity,
+    Config,
     Connect,
+    Doctor,
     Events,
     Routing,
     Logs,
+    SetLimit,
     Start,
     #[default]
     Status,
     Stop,
+    Storage,
     Tracking,
 }
 
@@ -130,6 +200,14 @@ impl Args for Options {
         let mut lines: usize = 10;
         let mut count: usize = usize::MAX;
         let mut timeout = time::Duration::MAX;
+        let mut listen = Vec::new();
+        let mut validation = Validation::default();
+        let mut limit: Option<(String, Option<String>)> = None;
+        let mut persist = false;
+        let mut since: Option<u64> = None;
+        let mut activity_limit: Option<usize> = None;
+        let mut alias: Option<Alias> = None;
+        let mut refresh = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -137,13 +215,18 @@ impl Args for Options {
                     return Err(Error::Help.into());
                 }
                 Value(val) if op.is_none() => match val.to_string_lossy().as_ref() {
+                    "activity" => op = Some(OperationName::Activity),
+                    "config" => op = Some(OperationName::Config),
                     "connect" => op = Some(OperationName::Connect),
+                    "doctor" => op = Some(OperationName::Doctor),
                     "events" => op = Some(OperationName::Events),
                     "logs" => op = Some(OperationName::Logs),
                     "routing" => op = Some(OperationName::Routing),
+                    "set-limit" => op = Some(OperationName::SetLimit),
                     "start" => op = Some(OperationName::Start),
                     "status" => op = Some(OperationName::Status),
                     "stop" => op = Some(OperationName::Stop),
+                    "storage" => op = Some(OperationName::Storage),
                     "tracking" => op = Some(OperationName::Tracking),
 
                     unknown => anyhow::bail!("unknown operation '{}'", unknown),
@@ -151,10 +234,35 @@ impl Args for Options {
                 Value(val) if matches!(op, Some(OperationName::Connect)) => {
                     addr = Some(val.parse()?);
                 }
+                Value(val) if matches!(op, Some(OperationName::SetLimit)) => {
+                    let val = val.to_string_lossy().into_owned();
+                    match &mut limit {
+                        None => limit = Some((val, None)),
+                        Some((_, value @ None)) => *value = Some(val),
+                        Some(_) => return Err(anyhow!("unexpected argument '{val}'")),
+                    }
+                }
+                Long("persist")
+                    if matches!(
+                        op,
+                        Some(OperationName::SetLimit) | Some(OperationName::Connect)
+                    ) =>
+                {
+                    persist = true
+                }
+                Long("alias") if matches!(op, Some(OperationName::Connect)) => {
+                    let val = parser.value()?;
+                    alias = Some(term::args::alias(&val)?);
+                }
                 Long("rid") if matches!(op, Some(OperationName::Routing)) => {
                     let val = parser.value()?;
                     rid = term::args::rid(&val).ok();
                 }
+                Long("rid") if matches!(op, Some(OperationName::Storage)) => {
+                    let val = parser.value()?;
+                    rid = Some(term::args::rid(&val)?);
+                }
+                Long("refresh") if matches!(op, Some(OperationName::Storage)) => refresh = true,
                 Long("nid") if matches!(op, Some(OperationName::Routing)) => {
                     let val = parser.value()?;
                     nid = term::args::nid(&val).ok();
@@ -170,12 +278,26 @@ impl Args for Options {
                     let val = parser.value()?;
                     count = term::args::number(&val)?;
                 }
+                Long("since") if matches!(op, Some(OperationName::Activity)) => {
+                    let val = parser.value()?;
+                    since = Some(term::args::number(&val)? as u64);
+                }
+                Long("count") | Short('n') if matches!(op, Some(OperationName::Activity)) => {
+                    let val = parser.value()?;
+                    activity_limit = Some(term::args::number(&val)?);
+                }
                 Long("repos") if matches!(op, Some(OperationName::Tracking)) => {
                     tracking_mode = TrackingMode::Repos
                 }
                 Long("nodes") if matches!(op, Some(OperationName::Tracking)) => {
                     tracking_mode = TrackingMode::Nodes
                 }
+                Long("listen") if matches!(op, Some(OperationName::Doctor)) => {
+                    listen.push(parser.value()?.parse()?);
+                }
+                Long("allow-port-mismatch") if matches!(op, Some(OperationName::Doctor)) => {
+                    validation.allow_port_mismatch = true;
+                }
                 Long("foreground") if matches!(op, Some(OperationName::Start)) => {
                     foreground = true;
                 }
@@ -190,21 +312,50 @@ impl Args for Options {
         }
 
         let op = match op.unwrap_or_default() {
-            OperationName::Connect => Operation::Connect {
-                addr: addr.ok_or_else(|| {
-                    anyhow!("an address of the form `<nid>@<host>:<port>` must be provided")
-                })?,
-                timeout,
+            OperationName::Activity => Operation::Activity {
+                since,
+                limit: activity_limit,
             },
+            OperationName::Config => Operation::Config,
+            OperationName::Connect => {
+                if alias.is_some() && !persist {
+                    anyhow::bail!("`--alias` can only be used with `--persist`");
+                }
+                Operation::Connect {
+                    addr: addr.ok_or_else(|| {
+                        anyhow!("an address of the form `<nid>@<host>:<port>` must be provided")
+                    })?,
+                    timeout,
+                    persist,
+                    alias,
+                }
+            }
+            OperationName::Doctor => Operation::Doctor { listen, validation },
             OperationName::Events => Operation::Events { timeout, count },
             OperationName::Routing => Operation::Routing { rid, nid, json },
             OperationName::Logs => Operation::Logs { lines },
+            OperationName::SetLimit => {
+                let Some((name, Some(value))) = limit else {
+                    anyhow::bail!("a limit name and value must be provided");
+                };
+                Operation::SetLimit {
+                    name,
+                    value,
+                    persist,
+                }
+            }
             OperationName::Start => Operation::Start {
                 foreground,
                 options,
             },
             OperationName::Status => Operation::Status,
             OperationName::Stop => Operation::Stop,
+            OperationName::Storage => {
+                if refresh && rid.is_none() {
+                    anyhow::bail!("`--refresh` can only be used with `--rid`");
+                }
+                Operation::Storage { rid, refresh }
+            }
             OperationName::Tracking => Operation::Tracking {
                 mode: tracking_mode,
             },
@@ -215,11 +366,33 @@ impl Args for Options {
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
-    let mut node = Node::new(profile.socket());
+    let mut node = match options.op {
+        // These don't connect to the node, so they also work offline.
+        Operation::Start { .. }
+        | Operation::Logs { .. }
+        | Operation::Doctor { .. }
+        | Operation::Routing { .. }
+        | Operation::Tracking { .. } => Node::from_profile(&profile),
+        _ => ctx.node(&profile)?,
+    };
 
     match options.op {
-        Operation::Connect { addr, timeout } => {
-            control::connect(&mut node, addr.id, addr.addr, timeout)?
+        Operation::Activity { since, limit } => events::activity(node, since, limit)?,
+        Operation::Config => config::show(&node)?,
+        Operation::Connect {
+            addr,
+            timeout,
+            persist,
+            alias,
+        } => {
+            control::connect(&mut node, addr.id, addr.addr.clone(), timeout)?;
+
+            if persist {
+                control::persist(&mut node, addr.id, addr.addr, alias)?;
+            }
+        }
+        Operation::Doctor { listen, validation } => {
+            doctor::run(&profile, &listen, validation)?;
         }
         Operation::Events { timeout, count } => {
             events::run(node, count, timeout)?;
@@ -230,6 +403,11 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
             routing::run(&store, rid, nid, json)?;
         }
         Operation::Logs { lines } => control::logs(lines, Some(time::Duration::MAX), &profile)?,
+        Operation::SetLimit {
+            name,
+            value,
+            persist,
+        } => config::set_limit(&mut node, &name, &value, persist, &profile)?,
         Operation::Start {
             foreground,
             options,
@@ -242,6 +420,9 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         Operation::Stop => {
             control::stop(node)?;
         }
+        Operation::Storage { rid, refresh } => {
+            storage::run(&node, rid, refresh)?;
+        }
         Operation::Tracking { mode } => {
             let store = radicle::node::tracking::store::Config::reader(
                 profile.home.node().join(TRACKING_DB_FILE),
@@ -0,0 +1,42 @@
+use radicle::node::{Handle as _, Node};
+use radicle::profile::Config;
+use radicle::Profile;
+use serde_json as json;
+
+use crate::terminal as term;
+
+/// Print the effective configuration of the running node.
+pub fn show(node: &Node) -> anyhow::Result<()> {
+    let config = node.config()?;
+
+    term::print(json::to_string_pretty(&config)?);
+
+    Ok(())
+}
+
+/// Change one of the limits of the running node, and optionally write it to the
+/// configuration file, so that it survives a restart.
+pub fn set_limit(
+    node: &mut Node,
+    name: &str,
+    value: &str,
+    persist: bool,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    let value = json::Value::String(value.to_owned());
+
+    node.set_limit(name, value.clone())?;
+    term::success!("Limit {} updated", term::format::highlight(name));
+
+    if persist {
+        let path = profile.home.config();
+        Config::set_limit(&path, name, value)?;
+
+        term::success!(
+            "Limit {} written to {}",
+            term::format::highlight(name),
+            term::format::dim(path.display())
+        );
+    }
+    Ok(())
+}
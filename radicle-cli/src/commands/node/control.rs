@@ -147,6 +147,27 @@ pub fn connect(
     Ok(())
 }
 
+/// Add a peer to the node's configuration as a persistent peer.
+pub fn persist(
+    node: &mut Node,
+    nid: NodeId,
+    addr: Address,
+    alias: Option<node::Alias>,
+) -> anyhow::Result<()> {
+    let added = node.add_peer(node::config::PeerEntry {
+        id: nid,
+        address: addr,
+        alias,
+        persistent: true,
+    })?;
+    if added {
+        term::success!("Peer {} added to configuration", term::format::node(&nid));
+    } else {
+        term::success!("Peer {} updated in configuration", term::format::node(&nid));
+    }
+    Ok(())
+}
+
 pub fn status(node: &Node, profile: &Profile) -> anyhow::Result<()> {
     if node.is_running() {
         term::success!("Node is {}.", term::format::positive("running"));
@@ -159,11 +180,70 @@ pub fn status(node: &Node, profile: &Profile) -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let sessions = sessions(node)?;
-    if let Some(table) = sessions {
+    let sessions = node.sessions()?;
+    if let Some(table) = self::sessions(&sessions) {
         term::blank();
         table.print();
     }
+    for sess in sessions.iter().filter(|s| !s.queue.is_empty()) {
+        term::blank();
+        term::info!("Fetch queue of {}:", term::format::tertiary(sess.nid));
+
+        for (i, rid) in sess.queue.iter().enumerate() {
+            term::indented(format!("{}. {}", i + 1, term::format::tertiary(rid)));
+        }
+    }
+
+    let gossip = node.gossip_stats()?;
+    term::info!(
+        "Gossip store holds announcements of {} node(s), and {} refs announcement(s).",
+        gossip.nodes,
+        gossip.refs
+    );
+    if let Some(hour) = gossip.bandwidth.last() {
+        term::info!(
+            "Gossip traffic this hour: {} sent, {} received.",
+            term::format::bytes(hour.total_sent()),
+            term::format::bytes(hour.total_received())
+        );
+    }
+    if gossip.relays_throttled > 0 {
+        term::info!(
+            "{} relayed announcement(s) dropped to stay within the relay rate.",
+            gossip.relays_throttled
+        );
+    }
+    // Nb. Nodes that predate recovery don't know the command.
+    if let Ok(Some(recovery)) = node.recovery() {
+        for db in &recovery.databases {
+            term::info!(
+                "Database {} was corrupt, and was replaced with an empty one. It was moved to {}.",
+                db.path.display(),
+                db.backup.display()
+            );
+        }
+        if recovery.unclean {
+            term::info!(
+                "Recovered from an unclean shutdown: {} routing entries added, {} removed, {} sync watermark(s) cleared.",
+                recovery.routes_added,
+                recovery.routes_removed,
+                recovery.watermarks_cleared
+            );
+            for rid in &recovery.invalid {
+                term::info!(
+                    "Repository {} failed validation.",
+                    term::format::tertiary(rid)
+                );
+            }
+            if !recovery.is_complete() {
+                term::info!(
+                    "{} repository(ies) checked, {} left to check.",
+                    recovery.checked,
+                    recovery.pending
+                );
+            }
+        }
+    }
 
     if profile.home.node().join("node.log").exists() {
         term::blank();
@@ -174,10 +254,9 @@ pub fn status(node: &Node, profile: &Profile) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn sessions(node: &Node) -> Result<Option<term::Table<4, term::Label>>, node::Error> {
-    let sessions = node.sessions()?;
+pub fn sessions(sessions: &[node::Session]) -> Option<term::Table<4, term::Label>> {
     if sessions.is_empty() {
-        return Ok(None);
+        return None;
     }
     let mut table = term::Table::new(term::table::TableOptions::bordered());
     let now = LocalTime::now();
@@ -192,7 +271,7 @@ pub fn sessions(node: &Node) -> Result<Option<term::Table<4, term::Label>>, node
 
     for sess in sessions {
         let nid = term::format::tertiary(sess.nid).into();
-        let (addr, state, time) = match sess.state {
+        let (addr, state, time) = match &sess.state {
             node::State::Initial => (
                 term::Label::blank(),
                 term::Label::from(term::format::dim("initial")),
@@ -206,15 +285,21 @@ pub fn sessions(node: &Node) -> Result<Option<term::Table<4, term::Label>>, node
             node::State::Connected { since, .. } => (
                 sess.addr.to_string().into(),
                 term::Label::from(term::format::positive("connected")),
-                term::format::dim(now - since).into(),
+                term::format::dim(now - *since).into(),
             ),
-            node::State::Disconnected { retry_at, .. } => (
+            node::State::Disconnected { since, .. } => (
                 sess.addr.to_string().into(),
-                term::Label::from(term::format::negative("disconnected")),
-                term::format::dim(retry_at - now).into(),
+                term::Label::from(term::format::negative(if sess.user_disconnected {
+                    "user-disconnected"
+                } else if sess.gave_up_until.is_some() {
+                    "unreachable"
+                } else {
+                    "disconnected"
+                })),
+                term::format::dim(now - *since).into(),
             ),
         };
         table.push([nid, addr, state, time]);
     }
-    Ok(Some(table))
+    Some(table)
 }
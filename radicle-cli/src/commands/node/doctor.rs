@@ -0,0 +1,39 @@
+use std::net;
+
+use radicle::node::config::{self, Validation};
+use radicle::Profile;
+
+use crate::terminal as term;
+
+/// Check the node configuration for problems, eg. unusable external addresses.
+pub fn run(
+    profile: &Profile,
+    listen: &[net::SocketAddr],
+    validation: Validation,
+) -> anyhow::Result<()> {
+    let config = &profile.config.node;
+
+    if config.external_addresses.is_empty() {
+        term::info!("No external addresses configured");
+        return Ok(());
+    }
+    // Nb. Without listening addresses, we can't tell which ports the node listens on.
+    let listen = (!listen.is_empty()).then_some(listen);
+    let warnings = config.validate(listen, validation, config::resolve);
+
+    if warnings.is_empty() {
+        term::success!(
+            "{} external address(es) checked, no problems found",
+            config.external_addresses.len()
+        );
+        return Ok(());
+    }
+    for warning in &warnings {
+        term::warning(&warning.to_string());
+    }
+    term::tip!(
+        "Addresses with problems can be excluded from announcements with `radicle-node --strict-config`"
+    );
+
+    Ok(())
+}
@@ -18,3 +18,13 @@ pub fn run(node: impl Handle, count: usize, timeout: time::Duration) -> anyhow::
 
     Ok(())
 }
+
+pub fn activity(node: impl Handle, since: Option<u64>, limit: Option<usize>) -> anyhow::Result<()> {
+    for entry in node.activity(since, limit)? {
+        let obj = serde_json::to_string(&entry)?;
+
+        println!("{obj}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use localtime::LocalTime;
+
+use radicle::node::Handle;
+use radicle::prelude::Id;
+use radicle::storage::git::stats::RepoStats;
+
+use crate::terminal as term;
+use crate::terminal::Element;
+
+pub fn run(node: &impl Handle, rid: Option<Id>, refresh: bool) -> anyhow::Result<()> {
+    let mut stats = node.repo_size(rid, refresh)?;
+    if stats.is_empty() {
+        term::info!("No repositories in storage.");
+        return Ok(());
+    }
+    // Largest repositories first.
+    stats.sort_by(|a, b| b.disk.cmp(&a.disk).then(a.rid.cmp(&b.rid)));
+
+    let mut t = term::Table::new(term::table::TableOptions::bordered());
+    t.push([
+        term::format::default(String::from("RID")),
+        term::format::default(String::from("Disk")),
+        term::format::default(String::from("Namespaces")),
+        term::format::default(String::from("Refs")),
+        term::format::default(String::from("Loose")),
+        term::format::default(String::from("Packed")),
+        term::format::default(String::from("Fetched")),
+        term::format::default(String::from("Announced")),
+        term::format::default(String::from("Computed")),
+    ]);
+    t.divider();
+
+    for s in &stats {
+        t.push(row(s));
+    }
+    t.print();
+
+    let total = stats.iter().map(|s| s.disk).sum();
+    term::info!(
+        "{} repository(ies) using {} on disk.",
+        stats.len(),
+        term::format::bytes(total)
+    );
+
+    Ok(())
+}
+
+fn row(stats: &RepoStats) -> [term::Paint<String>; 9] {
+    let time = |t: Option<u64>| {
+        term::format::dim(
+            t.map(|t| term::format::timestamp(&LocalTime::from_millis(t as u128)).to_string())
+                .unwrap_or_else(|| String::from("-")),
+        )
+    };
+    [
+        term::format::highlight(stats.rid.to_string()),
+        term::format::bytes(stats.disk),
+        term::format::default(stats.namespaces.len().to_string()),
+        term::format::default(stats.refs.to_string()),
+        term::format::default(stats.loose.to_string()),
+        term::format::default(stats.packed.to_string()),
+        time(stats.fetched),
+        time(stats.announced),
+        time(Some(stats.computed_at)),
+    ]
+}
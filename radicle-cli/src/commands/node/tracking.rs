@@ -20,18 +20,32 @@ fn print_repos(store: &tracking::store::ConfigReader) -> anyhow::Result<()> {
         term::format::default(String::from("RID")),
         term::format::default(String::from("Scope")),
         term::format::default(String::from("Policy")),
+        term::format::default(String::from("Origin")),
+        term::format::default(String::from("Refs")),
     ]);
     t.divider();
 
-    for tracking::Repo { id, scope, policy } in store.repo_policies()? {
+    for tracking::Repo {
+        id,
+        scope,
+        policy,
+        origin,
+        ref_filter,
+        ..
+    } in store.repo_policies()?
+    {
         let id = id.to_string();
         let scope = scope.to_string();
         let policy = policy.to_string();
+        let origin = origin.to_string();
+        let ref_filter = ref_filter.to_string();
 
         t.push([
             term::format::highlight(id),
             term::format::secondary(scope),
             term::format::secondary(policy),
+            term::format::secondary(origin),
+            term::format::secondary(ref_filter),
         ])
     }
     t.print();
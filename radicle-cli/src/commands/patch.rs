@@ -10,6 +10,8 @@ mod delete;
 mod edit;
 #[path = "patch/list.rs"]
 mod list;
+#[path = "patch/prune.rs"]
+mod prune;
 #[path = "patch/ready.rs"]
 mod ready;
 #[path = "patch/redact.rs"]
@@ -29,6 +31,7 @@ use radicle::prelude::*;
 use radicle::storage::git::transport;
 
 use crate::git::Rev;
+use crate::project;
 use crate::terminal as term;
 use crate::terminal::args::{string, Args, Error, Help};
 use crate::terminal::patch::Message;
@@ -50,6 +53,7 @@ Usage
     rad patch redact <revision-id> [<option>...]
     rad patch ready <patch-id> [--undo] [<option>...]
     rad patch edit <patch-id> [<option>...]
+    rad patch prune [--force] [<option>...]
 
 Show options
 
@@ -73,10 +77,19 @@ List options
         --open                 Show only open patches (default)
         --draft                Show only draft patches
 
+Checkout options
+
+        --no-upstream          Don't setup an upstream branch for the patch branch
+        --force                Reset the patch branch to the latest revision, even if it has diverged
+
 Ready options
 
         --undo                 Convert a patch back to a draft
 
+Prune options
+
+        --force                Delete the patch branches without asking for confirmation
+
 Other options
 
         --help                 Print help
@@ -95,6 +108,7 @@ pub enum OperationName {
     List,
     Edit,
     Redact,
+    Prune,
 }
 
 pub struct Filter(fn(&patch::State) -> bool);
@@ -141,6 +155,8 @@ pub enum Operation {
     },
     Checkout {
         patch_id: Rev,
+        upstream: bool,
+        force: bool,
     },
     List {
         filter: Filter,
@@ -152,6 +168,9 @@ pub enum Operation {
     Redact {
         revision_id: Rev,
     },
+    Prune {
+        force: bool,
+    },
 }
 
 #[derive(Debug)]
@@ -177,6 +196,8 @@ impl Args for Options {
         let mut filter = Filter::default();
         let mut diff = false;
         let mut undo = false;
+        let mut upstream = true;
+        let mut force = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -214,6 +235,18 @@ impl Args for Options {
                     undo = true;
                 }
 
+                // Checkout options.
+                Long("no-upstream") if op == Some(OperationName::Checkout) => {
+                    upstream = false;
+                }
+
+                // Prune options.
+                Long("force")
+                    if op == Some(OperationName::Prune) || op == Some(OperationName::Checkout) =>
+                {
+                    force = true;
+                }
+
                 // Update options
                 Long("revision") if op == Some(OperationName::Update) => {
                     let val = parser.value()?;
@@ -256,6 +289,7 @@ impl Args for Options {
                     "y" | "ready" => op = Some(OperationName::Ready),
                     "e" | "edit" => op = Some(OperationName::Edit),
                     "r" | "redact" => op = Some(OperationName::Redact),
+                    "prune" => op = Some(OperationName::Prune),
                     unknown => anyhow::bail!("unknown operation '{}'", unknown),
                 },
                 Value(val) if op == Some(OperationName::Redact) => {
@@ -301,6 +335,8 @@ impl Args for Options {
             },
             OperationName::Checkout => Operation::Checkout {
                 patch_id: patch_id.ok_or_else(|| anyhow!("a patch must be provided"))?,
+                upstream,
+                force,
             },
             OperationName::Ready => Operation::Ready {
                 patch_id: patch_id.ok_or_else(|| anyhow!("a patch must be provided"))?,
@@ -313,6 +349,7 @@ impl Args for Options {
             OperationName::Redact => Operation::Redact {
                 revision_id: revision_id.ok_or_else(|| anyhow!("a revision must be provided"))?,
             },
+            OperationName::Prune => Operation::Prune { force },
         };
 
         Ok((
@@ -328,8 +365,7 @@ impl Args for Options {
 }
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
-    let (workdir, id) = radicle::rad::cwd()
-        .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+    let (workdir, id) = project::cwd()?;
 
     let profile = ctx.profile()?;
     let repository = profile.storage.repository(id)?;
@@ -367,9 +403,13 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
             let patch_id = patch_id.resolve::<PatchId>(&repository.backend)?;
             delete::run(&patch_id, &profile, &repository)?;
         }
-        Operation::Checkout { patch_id } => {
+        Operation::Checkout {
+            patch_id,
+            upstream,
+            force,
+        } => {
             let patch_id = patch_id.resolve(&repository.backend)?;
-            checkout::run(&patch_id, &repository, &workdir)?;
+            checkout::run(&patch_id, upstream, force, &repository, &workdir)?;
         }
         Operation::Edit { patch_id, message } => {
             let patch_id = patch_id.resolve(&repository.backend)?;
@@ -378,6 +418,9 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         Operation::Redact { revision_id } => {
             redact::run(&revision_id, &profile, &repository)?;
         }
+        Operation::Prune { force } => {
+            prune::run(force, &repository, &workdir)?;
+        }
     }
     Ok(())
 }
@@ -1,16 +1,17 @@
 use anyhow::anyhow;
 
-use radicle::cob::patch;
-use radicle::cob::patch::{Patch, PatchId};
-use radicle::git::RefString;
-use radicle::storage::git::Repository;
-use radicle::storage::ReadRepository;
-use radicle::{git, rad};
+use radicle::api::git;
+use radicle::api::git::RefString;
+use radicle::api::patch;
+use radicle::api::patch::{Patch, PatchId};
+use radicle::api::{ReadRepository as _, Repository};
 
 use crate::terminal as term;
 
 pub fn run(
     patch_id: &PatchId,
+    upstream: bool,
+    force: bool,
     stored: &Repository,
     working: &git::raw::Repository,
 ) -> anyhow::Result<()> {
@@ -18,37 +19,336 @@ pub fn run(
     let patch = patches
         .get(patch_id)?
         .ok_or_else(|| anyhow!("Patch `{patch_id}` not found"))?;
+    let (revision_id, _) = patch.latest();
 
-    let mut spinner = term::spinner("Performing checkout...");
-    let patch_branch =
+    // If the patch was checked out before, and its branch still exists, update that branch.
+    // Otherwise, forget about the previous checkout.
+    let checkout = match Metadata::load(working, patch_id)? {
+        Some(metadata) if working.find_reference(&metadata.reference()).is_ok() => Some(metadata),
+        Some(_) => {
+            Metadata::remove(working, patch_id)?;
+            None
+        }
+        None => None,
+    };
+    let patch_branch = match &checkout {
+        Some(metadata) => RefString::try_from(metadata.branch.as_str())?,
         // SAFETY: Patch IDs are valid refstrings.
-        git::refname!("patch").join(RefString::try_from(term::format::cob(patch_id)).unwrap());
+        None => {
+            git::refname!("patch").join(RefString::try_from(term::format::cob(patch_id)).unwrap())
+        }
+    };
+    let patch_ref = git::workdir::branch(&patch_branch);
+    // Nb. A patch branch may exist without checkout metadata, eg. if it was created by hand,
+    // in which case it's updated like a branch that was checked out before.
+    let exists = checkout.is_some() || working.find_reference(&patch_ref).is_ok();
+
+    // Check everything that could go wrong before touching the working copy.
+    if !git::raw::Branch::name_is_valid(patch_branch.as_str())? {
+        anyhow::bail!("invalid branch name `{patch_branch}`");
+    }
+    if upstream {
+        check_upstream(patch_id, &patch, working)?;
+    }
     let commit = find_patch_commit(&patch, stored, working)?;
+    let target = if exists {
+        let revision = checkout.as_ref().map(|m| m.revision.as_str());
+        update_target(&patch_branch, revision, &patch, &commit, force, working)?
+    } else {
+        commit.id()
+    };
+
+    let mut spinner = term::spinner("Performing checkout...");
+    let previous = Previous::load(working, &patch_ref)?;
+
+    // Create or update the patch branch, and switch to it. If anything fails from here on,
+    // the working copy is rolled back to its previous state.
+    let result = (|| -> anyhow::Result<git::raw::Commit> {
+        let target = working.find_commit(target)?;
+        if exists {
+            working.reference(&patch_ref, target.id(), true, "Update patch branch")?;
+        } else {
+            working.branch(patch_branch.as_str(), &target, false)?;
+        }
+        working.checkout_tree(target.as_object(), None)?;
+        working.set_head(&patch_ref)?;
+
+        Metadata {
+            branch: patch_branch.to_string(),
+            revision: revision_id.to_string(),
+        }
+        .store(working, patch_id)?;
 
-    // Create patch branch and switch to it.
-    working.branch(patch_branch.as_str(), &commit, true)?;
-    working.checkout_tree(commit.as_object(), None)?;
-    working.set_head(&git::refs::workdir::branch(&patch_branch))?;
+        Ok(target)
+    })();
+    let target = match result {
+        Ok(target) => target,
+        Err(e) => {
+            spinner.failed();
+            previous.restore(working, &patch_ref)?;
+            term::notice!("Switched back to {}", previous);
 
+            return Err(e);
+        }
+    };
     spinner.message(format!(
         "Switched to branch {}",
         term::format::highlight(&patch_branch)
     ));
     spinner.finish();
 
-    if let Some(branch) = rad::setup_patch_upstream(patch_id, *patch.head(), working)? {
-        let tracking = branch
-            .name()?
-            .ok_or_else(|| anyhow!("failed to create tracking branch: invalid name"))?;
-        term::success!(
-            "Branch {} setup to track {}",
-            term::format::highlight(patch_branch),
-            term::format::tertiary(tracking)
-        );
+    if let Some(metadata) = &checkout {
+        if metadata.revision != revision_id.to_string() && target.id() == commit.id() {
+            term::success!(
+                "Branch {} updated to revision {}",
+                term::format::highlight(&patch_branch),
+                term::format::tertiary(term::format::oid(*revision_id))
+            );
+        }
+    }
+
+    if !upstream {
+        return Ok(());
+    }
+    match patch::setup_upstream(patch_id, *patch.head(), working) {
+        Ok(Some(branch)) => {
+            let tracking = branch
+                .name()?
+                .ok_or_else(|| anyhow!("failed to create tracking branch: invalid name"))?;
+            term::success!(
+                "Branch {} setup to track {}",
+                term::format::highlight(patch_branch),
+                term::format::tertiary(tracking)
+            );
+        }
+        Ok(None) => {}
+        Err(e) => {
+            previous.restore(working, &patch_ref)?;
+            term::notice!("Switched back to {}", previous);
+
+            return Err(anyhow!("failed to setup upstream for {patch_branch}: {e}"));
+        }
     }
     Ok(())
 }
 
+/// Checkout metadata, stored in the working copy's git configuration. Associates a patch
+/// with the branch it was checked out on, and the revision the branch was last set to.
+pub struct Metadata {
+    /// Branch name, eg. `patch/23080bb`.
+    pub branch: String,
+    /// Revision the branch was last set to.
+    pub revision: String,
+}
+
+impl Metadata {
+    /// Configuration key of the given metadata field.
+    fn key(patch_id: &PatchId, field: &str) -> String {
+        format!("radicle.patch.{patch_id}.{field}")
+    }
+
+    /// Reference name of the patch branch.
+    fn reference(&self) -> String {
+        format!("refs/heads/{}", self.branch)
+    }
+
+    /// Load the checkout metadata of a patch, if the patch was checked out before.
+    pub fn load(
+        working: &git::raw::Repository,
+        patch_id: &PatchId,
+    ) -> anyhow::Result<Option<Self>> {
+        let config = working.config()?;
+        let get = |field| match config.get_string(&Self::key(patch_id, field)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if git::is_not_found_err(&e) => Ok(None),
+            Err(e) => Err(e),
+        };
+        match (get("branch")?, get("revision")?) {
+            (Some(branch), Some(revision)) => Ok(Some(Self { branch, revision })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Store the checkout metadata of a patch.
+    fn store(&self, working: &git::raw::Repository, patch_id: &PatchId) -> anyhow::Result<()> {
+        let mut config = working.config()?.open_level(git::raw::ConfigLevel::Local)?;
+
+        config.set_str(&Self::key(patch_id, "branch"), &self.branch)?;
+        config.set_str(&Self::key(patch_id, "revision"), &self.revision)?;
+
+        Ok(())
+    }
+
+    /// Remove the checkout metadata of a patch, eg. once its branch is deleted.
+    pub fn remove(working: &git::raw::Repository, patch_id: &PatchId) -> anyhow::Result<()> {
+        let mut config = working.config()?.open_level(git::raw::ConfigLevel::Local)?;
+
+        for field in ["branch", "revision"] {
+            match config.remove(&Self::key(patch_id, field)) {
+                Ok(()) => {}
+                Err(e) if git::is_not_found_err(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the commit an existing patch branch should point to, given the latest patch head,
+/// and the revision the branch was last set to, if it was checked out before.
+/// The branch is only moved to the new head if that doesn't lose any local work, ie. if the
+/// branch still points to the revision it was last set to, or if the new head is a
+/// fast-forward. Otherwise, `force` is required.
+fn update_target(
+    branch: &RefString,
+    revision: Option<&str>,
+    patch: &Patch,
+    head: &git::raw::Commit,
+    force: bool,
+    working: &git::raw::Repository,
+) -> anyhow::Result<git::raw::Oid> {
+    let (revision_id, _) = patch.latest();
+    let tip = working
+        .find_reference(&git::workdir::branch(branch))?
+        .peel_to_commit()?
+        .id();
+
+    // The latest revision is already checked out: keep any local changes.
+    if revision == Some(revision_id.to_string().as_str()) || tip == head.id() {
+        return Ok(tip);
+    }
+    let previous = revision.and_then(|revision| {
+        patch
+            .revisions()
+            .find(|(id, _)| id.to_string() == revision)
+            .map(|(_, r)| git::raw::Oid::from(r.head()))
+    });
+
+    if force || previous == Some(tip) || working.graph_descendant_of(head.id(), tip)? {
+        return Ok(head.id());
+    }
+    let (ahead, behind) = working.graph_ahead_behind(tip, head.id())?;
+
+    anyhow::bail!(
+        "branch `{}` has diverged from revision {} of this patch, with {ahead} commit(s) ahead \
+        and {behind} commit(s) behind; use `--force` to reset it to the latest revision",
+        branch,
+        term::format::oid(*revision_id),
+    )
+}
+
+/// Working copy state prior to checkout, used to roll back a failed checkout.
+struct Previous {
+    /// The previous `HEAD`, if any.
+    head: Option<Head>,
+    /// Where the patch branch pointed to, if it already existed.
+    branch: Option<git::raw::Oid>,
+}
+
+/// A previous working copy `HEAD`.
+enum Head {
+    /// `HEAD` pointed to a branch.
+    Branch { name: String, oid: git::raw::Oid },
+    /// `HEAD` was detached.
+    Detached(git::raw::Oid),
+}
+
+impl Previous {
+    fn load(working: &git::raw::Repository, patch_ref: &str) -> anyhow::Result<Self> {
+        let head = match working.head() {
+            Ok(head) => {
+                let oid = head.peel_to_commit()?.id();
+                match head.name() {
+                    Some(name) if head.is_branch() => Some(Head::Branch {
+                        name: name.to_owned(),
+                        oid,
+                    }),
+                    _ => Some(Head::Detached(oid)),
+                }
+            }
+            Err(e) if e.code() == git::raw::ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        let branch = match working.find_reference(patch_ref) {
+            Ok(r) => r.target(),
+            Err(e) if git::is_not_found_err(&e) => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { head, branch })
+    }
+
+    /// Switch back to the previous `HEAD`, and reset the patch branch.
+    fn restore(&self, working: &git::raw::Repository, patch_ref: &str) -> anyhow::Result<()> {
+        let Some(head) = &self.head else {
+            return Ok(());
+        };
+        let (Head::Branch { oid, .. } | Head::Detached(oid)) = head;
+        let commit = working.find_commit(*oid)?;
+
+        working.checkout_tree(commit.as_object(), None)?;
+        match head {
+            Head::Branch { name, .. } => working.set_head(name)?,
+            Head::Detached(oid) => working.set_head_detached(*oid)?,
+        }
+        match self.branch {
+            Some(oid) => {
+                working.reference(patch_ref, oid, true, "Restore patch branch")?;
+            }
+            None => match working.find_reference(patch_ref) {
+                Ok(mut r) => r.delete()?,
+                Err(e) if git::is_not_found_err(&e) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Previous {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.head {
+            Some(Head::Branch { name, .. }) => {
+                let name = name.strip_prefix("refs/heads/").unwrap_or(name);
+                write!(f, "branch {}", term::format::highlight(name))
+            }
+            Some(Head::Detached(oid)) => {
+                write!(f, "{}", term::format::oid(*oid))
+            }
+            None => write!(f, "previous state"),
+        }
+    }
+}
+
+/// Make sure the patch upstream branch can be setup, ie. that we're not about to
+/// overwrite a remote tracking branch that points to a commit outside of the patch.
+fn check_upstream(
+    patch_id: &PatchId,
+    patch: &Patch,
+    working: &git::raw::Repository,
+) -> anyhow::Result<()> {
+    let upstream = radicle::git::refs::workdir::patch_upstream(patch_id);
+    let target = match working.find_reference(upstream.as_str()) {
+        Ok(r) => r.target(),
+        Err(e) if git::is_not_found_err(&e) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(target) = target else {
+        return Ok(());
+    };
+    if patch
+        .revisions()
+        .any(|(_, r)| git::raw::Oid::from(r.head()) == target)
+    {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "upstream branch `{}` already exists and points to {}, which is not a revision of this patch; \
+        use `--no-upstream` to checkout without setting up an upstream",
+        upstream.as_str().strip_prefix("refs/remotes/").unwrap_or(upstream.as_str()),
+        term::format::oid(target),
+    )
+}
+
 /// Try to find the patch head in our working copy, and if we don't find it,
 /// fetch it from storage first.
 fn find_patch_commit<'a>(
@@ -60,7 +360,7 @@ fn find_patch_commit<'a>(
 
     match working.find_commit(patch_head.into()) {
         Ok(commit) => Ok(commit),
-        Err(e) if git::ext::is_not_found_err(&e) => {
+        Err(e) if git::is_not_found_err(&e) => {
             let url = git::url::File::new(stored.path());
 
             working.remote_anonymous(url.to_string().as_str())?.fetch(
@@ -0,0 +1,140 @@
+use radicle::cob::patch;
+use radicle::cob::patch::{Patch, PatchId};
+use radicle::git;
+use radicle::storage::git::Repository;
+
+use crate::terminal as term;
+
+/// A patch branch of the working copy.
+struct Branch {
+    /// Branch name, eg. `patch/23080bb`.
+    name: String,
+    /// The patch checked out on this branch.
+    patch_id: PatchId,
+    /// Why the branch can be pruned.
+    state: &'static str,
+}
+
+/// Delete the patch branches of the working copy whose patches were merged or archived.
+pub fn run(
+    force: bool,
+    repository: &Repository,
+    working: &git::raw::Repository,
+) -> anyhow::Result<()> {
+    let patches = patch::Patches::open(repository)?;
+    let patches = patches
+        .all()?
+        .filter_map(Result::ok)
+        .collect::<Vec<(PatchId, Patch)>>();
+
+    let mut prunable = Vec::new();
+    for branch in working.branches(Some(git::raw::BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(name) = branch.name()? else {
+            continue;
+        };
+        let Some(short) = name.strip_prefix("patch/") else {
+            continue;
+        };
+        // Patch branches are named after the short form of the patch id, so skip branches
+        // that could refer to more than one patch.
+        let mut matching = patches
+            .iter()
+            .filter(|(id, _)| term::format::cob(id) == short);
+        let (Some((patch_id, patch)), None) = (matching.next(), matching.next()) else {
+            continue;
+        };
+        let state = match patch.state() {
+            patch::State::Merged { .. } => "merged",
+            patch::State::Archived => "archived",
+            patch::State::Draft | patch::State::Open { .. } => continue,
+        };
+
+        if branch.is_head() {
+            term::notice!(
+                "Skipping branch {}, which is checked out",
+                term::format::highlight(name)
+            );
+            continue;
+        }
+        let Some(tip) = branch.get().target() else {
+            continue;
+        };
+        if !is_published(tip, patch_id, patch, working) {
+            term::notice!(
+                "Skipping branch {}, which has commits that aren't part of patch {}",
+                term::format::highlight(name),
+                term::format::tertiary(term::format::cob(patch_id)),
+            );
+            continue;
+        }
+        prunable.push(Branch {
+            name: name.to_owned(),
+            patch_id: *patch_id,
+            state,
+        });
+    }
+
+    if prunable.is_empty() {
+        term::print(term::format::italic("Nothing to prune."));
+        return Ok(());
+    }
+    if !force {
+        for branch in &prunable {
+            term::info!(
+                "{} ({})",
+                term::format::highlight(&branch.name),
+                branch.state
+            );
+        }
+        if !term::confirm(format!("Delete {} patch branch(es)?", prunable.len())) {
+            return Ok(());
+        }
+    }
+
+    for Branch {
+        name,
+        patch_id,
+        state,
+    } in prunable
+    {
+        // Deleting the branch also removes its upstream configuration.
+        working
+            .find_branch(&name, git::raw::BranchType::Local)?
+            .delete()?;
+
+        let upstream = git::refs::workdir::patch_upstream(&patch_id);
+        match working.find_reference(upstream.as_str()) {
+            Ok(mut r) => r.delete()?,
+            Err(e) if git::is_not_found_err(&e) => {}
+            Err(e) => return Err(e.into()),
+        }
+        super::checkout::Metadata::remove(working, &patch_id)?;
+
+        term::success!("Deleted branch {} ({state})", term::format::highlight(name));
+    }
+    Ok(())
+}
+
+/// Check that all the commits of a patch branch are part of the patch, ie. that the branch
+/// tip is contained in one of the patch revisions, or in the patch upstream branch.
+fn is_published(
+    tip: git::raw::Oid,
+    patch_id: &PatchId,
+    patch: &Patch,
+    working: &git::raw::Repository,
+) -> bool {
+    let upstream = working
+        .find_reference(git::refs::workdir::patch_upstream(patch_id).as_str())
+        .ok()
+        .and_then(|r| r.target());
+    let mut heads = patch
+        .revisions()
+        .map(|(_, r)| git::raw::Oid::from(r.head()))
+        .chain(upstream);
+
+    heads.any(|head| {
+        // Commits missing from the working copy can't be checked, so they don't count.
+        head == tip || working.graph_descendant_of(head, tip).unwrap_or(false)
+    })
+}
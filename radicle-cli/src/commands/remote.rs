@@ -5,15 +5,20 @@ pub mod add;
 pub mod list;
 #[path = "remote/rm.rs"]
 pub mod rm;
+#[path = "remote/sync.rs"]
+pub mod sync;
 
+use std::env;
 use std::ffi::OsString;
 
 use anyhow::anyhow;
 
-use radicle::git::RefString;
-use radicle::prelude::NodeId;
-use radicle::storage::ReadStorage;
+use radicle::api;
+use radicle::api::git::RefString;
+use radicle::api::{Id, NodeId, Profile, ReadStorage as _, WriteRepository as _};
 
+use crate::project;
+use crate::terminal as term;
 use crate::terminal::args;
 use crate::terminal::{Args, Context, Help};
 
@@ -24,14 +29,40 @@ pub const HELP: Help = Help {
     usage: r#"
 Usage
 
-    rad remote
-    rad remote list
-    rad remote add (<did> | <nid>) [--name <string>]
-    rad remote rm <name>
+    rad remote [--repo <rid>]
+    rad remote list [--status] [--repo <rid>]
+    rad remote add (<did> | <nid>) [--name <string>] [--force] [--fetch] [--repo <rid>]
+    rad remote add --delegates [--fetch] [--repo <rid>]
+    rad remote rm <name> [--repo <rid>]
+    rad remote sync
+
+    The `sync` operation adds remotes for the tracked nodes we have refs of, and
+    removes the remotes it added for nodes that are no longer tracked. Remotes are
+    added for all tracked nodes if `cli.addRemotes` is set in the configuration,
+    otherwise only for the nodes tracked with `rad track <nid> --add-remotes`.
+    Remotes removed with `rad remote rm` are not added back.
+
+    With `--status`, the `list` operation shows how many commits the default
+    branch of each namespaced remote is ahead and behind of the canonical default
+    branch, as `+<ahead>/-<behind>`. Remotes without a default branch are shown
+    with `-`. Only refs and objects we already have are used; nothing is fetched.
+
+    With `--delegates`, a remote is added for each delegate of the project that
+    doesn't have one yet, named after the delegate's alias, if known.
+
+    With `--repo`, the remotes of the given repository in storage are managed,
+    instead of the remotes of the working copy. This is also the case when the
+    command is run from inside a storage repository. Storage repositories have a
+    namespace for each node we have refs of, which are listed alongside remotes.
 
 Options
 
     --name      Override the name of the remote that by default is set to the node alias
+    --force     Add the remote even if the peer is neither a delegate nor tracked
+    --fetch     Fetch the refs of the added remotes into the working copy
+    --delegates Add a remote for each delegate of the project
+    --status    Show the remotes' ahead/behind counts when listing them
+    --repo      Manage the remotes of the given repository in storage
     --help      Print help
 "#,
 };
@@ -40,20 +71,36 @@ Options
 pub enum OperationName {
     Add,
     Rm,
+    Sync,
     #[default]
     List,
 }
 
 #[derive(Debug)]
 pub enum Operation {
-    Add { id: NodeId, name: Option<RefString> },
-    Rm { name: RefString },
-    List,
+    Add {
+        id: NodeId,
+        name: Option<RefString>,
+        force: bool,
+        fetch: bool,
+    },
+    AddDelegates {
+        fetch: bool,
+    },
+    Rm {
+        name: RefString,
+    },
+    Sync,
+    List {
+        status: bool,
+    },
 }
 
 #[derive(Debug)]
 pub struct Options {
     pub op: Operation,
+    /// Storage repository to manage the remotes of, instead of the working copy.
+    pub repo: Option<Id>,
 }
 
 impl Args for Options {
@@ -64,6 +111,11 @@ impl Args for Options {
         let mut op: Option<OperationName> = None;
         let mut id: Option<NodeId> = None;
         let mut name: Option<RefString> = None;
+        let mut force = false;
+        let mut fetch = false;
+        let mut delegates = false;
+        let mut status = false;
+        let mut repo: Option<Id> = None;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -76,10 +128,29 @@ impl Args for Options {
 
                     name = Some(value);
                 }
+                Long("force") | Short('f') if op == Some(OperationName::Add) => {
+                    force = true;
+                }
+                Long("fetch") if op == Some(OperationName::Add) => {
+                    fetch = true;
+                }
+                Long("delegates") if op == Some(OperationName::Add) => {
+                    delegates = true;
+                }
+                Long("status") if op == Some(OperationName::List) => {
+                    status = true;
+                }
+                Long("repo") => {
+                    let value = parser.value()?;
+                    let value = args::rid(&value)?;
+
+                    repo = Some(value);
+                }
                 Value(val) if op.is_none() => match val.to_string_lossy().as_ref() {
                     "a" | "add" => op = Some(OperationName::Add),
                     "l" | "list" => op = Some(OperationName::List),
                     "r" | "rm" => op = Some(OperationName::Rm),
+                    "s" | "sync" => op = Some(OperationName::Sync),
                     unknown => anyhow::bail!("unknown operation '{}'", unknown),
                 },
                 Value(val) if op == Some(OperationName::Add) && id.is_none() => {
@@ -98,36 +169,158 @@ impl Args for Options {
         }
 
         let op = match op.unwrap_or_default() {
+            OperationName::Add if delegates => {
+                if id.is_some() || name.is_some() {
+                    anyhow::bail!("`--delegates` cannot be used with a `DID` or `--name`");
+                }
+                Operation::AddDelegates { fetch }
+            }
             OperationName::Add => Operation::Add {
                 id: id.ok_or(anyhow!(
                     "`DID` required, try running `rad remote add <did>`"
                 ))?,
                 name,
+                force,
+                fetch,
             },
-            OperationName::List => Operation::List,
+            OperationName::List => Operation::List { status },
+            OperationName::Sync => Operation::Sync,
             OperationName::Rm => Operation::Rm {
                 name: name.ok_or(anyhow!("name required, see `rad remote`"))?,
             },
         };
 
-        Ok((Options { op }, vec![]))
+        Ok((Options { op, repo }, vec![]))
     }
 }
 
 pub fn run(options: Options, ctx: impl Context) -> anyhow::Result<()> {
-    let (working, rid) = radicle::rad::cwd()
-        .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
     let profile = ctx.profile()?;
 
+    if let Some(rid) = options.repo.or_else(|| storage_repo(&profile)) {
+        return run_storage(options.op, rid, &profile);
+    }
+    let (working, rid) = project::working(".")?;
+
     match options.op {
-        Operation::Add { ref id, name } => {
-            let proj = profile.storage.repository(rid)?.project()?;
+        Operation::Add {
+            ref id,
+            name,
+            force,
+            fetch,
+        } => {
+            let stored = profile.storage.repository(rid)?;
+            let (doc, proj) = api::project(&stored)?;
             let branch = proj.default_branch();
 
-            self::add::run(rid, id, name, Some(branch.clone()), &profile, &working)?
+            self::add::run(
+                rid,
+                id,
+                name,
+                Some(branch.clone()),
+                force,
+                fetch,
+                &profile,
+                &stored,
+                &doc,
+                &working,
+            )?
+        }
+        Operation::AddDelegates { fetch } => {
+            let stored = profile.storage.repository(rid)?;
+            let (doc, proj) = api::project(&stored)?;
+            let branch = proj.default_branch();
+
+            self::add::delegates(
+                rid,
+                Some(branch.clone()),
+                fetch,
+                &profile,
+                &stored,
+                &doc,
+                &working,
+            )?
         }
         Operation::Rm { ref name } => self::rm::run(name, &working)?,
-        Operation::List => self::list::run(&working)?,
+        Operation::Sync => {
+            let stored = profile.storage.repository(rid)?;
+            let (_, proj) = api::project(&stored)?;
+            let branch = proj.default_branch();
+
+            self::sync::run(rid, Some(branch.clone()), &profile, &stored, &working)?
+        }
+        Operation::List { status } => {
+            let stored = status
+                .then(|| profile.storage.repository(rid))
+                .transpose()?;
+
+            self::list::run(&working, stored.as_ref())?
+        }
     };
     Ok(())
 }
+
+/// Manage the remotes of a repository in storage.
+fn run_storage(op: Operation, rid: Id, profile: &Profile) -> anyhow::Result<()> {
+    let stored = profile.storage.repository(rid)?;
+
+    term::info!(
+        "Remotes of storage repository {}",
+        term::format::tertiary(rid)
+    );
+
+    match op {
+        Operation::Add { fetch: true, .. } | Operation::AddDelegates { fetch: true } => {
+            return Err(args::Error::WithHint {
+                err: anyhow!("`--fetch` is not supported for storage repositories"),
+                hint: "Storage repositories already have the refs of the nodes we fetched.",
+            }
+            .into());
+        }
+        Operation::Add {
+            ref id,
+            name,
+            force,
+            fetch,
+        } => {
+            let (doc, _) = api::project(&stored)?;
+
+            self::add::run(
+                rid,
+                id,
+                name,
+                None,
+                force,
+                fetch,
+                profile,
+                &stored,
+                &doc,
+                stored.raw(),
+            )?
+        }
+        Operation::AddDelegates { fetch } => {
+            let (doc, _) = api::project(&stored)?;
+
+            self::add::delegates(rid, None, fetch, profile, &stored, &doc, stored.raw())?
+        }
+        Operation::Rm { ref name } => self::rm::run(name, stored.raw())?,
+        Operation::List { status } => self::list::run_storage(&stored, profile, status)?,
+        Operation::Sync => {
+            return Err(args::Error::WithHint {
+                err: anyhow!("`sync` is not supported for storage repositories"),
+                hint: "Run `rad remote sync` from a working copy of the repository.",
+            }
+            .into());
+        }
+    };
+    Ok(())
+}
+
+/// Get the storage repository the current directory is in, if any.
+fn storage_repo(profile: &Profile) -> Option<Id> {
+    let cwd = env::current_dir().ok()?.canonicalize().ok()?;
+    let storage = profile.storage.path().canonicalize().ok()?;
+    let dir = cwd.strip_prefix(storage).ok()?.components().next()?;
+
+    Id::from_canonical(&dir.as_os_str().to_string_lossy()).ok()
+}
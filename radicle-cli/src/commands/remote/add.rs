@@ -1,26 +1,156 @@
-use radicle::git::RefString;
-use radicle::prelude::*;
-use radicle::Profile;
-use radicle_crypto::PublicKey;
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+
+use radicle::api::git::RefString;
+use radicle::api::{BranchName, Did, Doc, Id, Profile, PublicKey, Repository, Verified};
+use radicle::node::AliasStore as _;
 
 use crate::commands::rad_checkout as checkout;
 use crate::git;
 use crate::project::SetupRemote;
+use crate::terminal as term;
+use crate::terminal::args;
 
+/// What we know locally about a peer that is added as a remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    /// The peer has refs in our copy of the repository.
+    Stored,
+    /// The peer is a delegate of the repository, but has no refs locally.
+    Delegate,
+    /// The peer is tracked, but has no refs locally.
+    Tracked,
+    /// We know nothing about this peer.
+    Unknown,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     rid: Id,
     nid: &PublicKey,
     name: Option<RefString>,
     tracking: Option<BranchName>,
+    force: bool,
+    fetch: bool,
     profile: &Profile,
+    stored: &Repository,
+    doc: &Doc<Verified>,
     repo: &git::Repository,
 ) -> anyhow::Result<()> {
+    let peer = if stored
+        .remote_ids()?
+        .any(|id| matches!(id, Ok(id) if id == *nid))
+    {
+        Peer::Stored
+    } else if doc.is_delegate(nid) {
+        Peer::Delegate
+    } else if profile.tracking()?.is_node_tracked(nid)? {
+        Peer::Tracked
+    } else {
+        Peer::Unknown
+    };
+    let did = Did::from(*nid);
+
+    match peer {
+        Peer::Stored => {}
+        Peer::Delegate | Peer::Tracked => {
+            term::tip!("This peer has no refs locally; run `rad sync` to fetch them");
+        }
+        Peer::Unknown if force => {
+            term::tip!("This peer has no refs locally; run `rad sync` or `rad track {did}` first");
+        }
+        Peer::Unknown => {
+            return Err(args::Error::WithHint {
+                err: anyhow!("{did} is neither a delegate nor a tracked peer of {rid}"),
+                hint: "This peer has no refs locally; run `rad sync` or `rad track <did>` first, \
+                    or use `--force` to add it anyway.",
+            }
+            .into());
+        }
+    }
+
     let aliases = profile.aliases();
     let setup = SetupRemote {
         rid,
         tracking,
-        fetch: false,
+        fetch,
         repo,
     };
-    checkout::setup_remote(&setup, nid, name, &aliases)
+    checkout::setup_remote(&setup, nid, name, &aliases)?;
+    // Let `rad remote sync` manage this node's remote again, if it was removed before.
+    git::skip_remote(repo, nid, false)?;
+
+    Ok(())
+}
+
+/// Add a remote for each delegate of the repository, other than ourselves, that doesn't
+/// have one already. Remotes are named after the delegate's alias if we know it, or
+/// `delegate-<suffix>` otherwise, where the suffix is the end of the delegate's node id.
+#[allow(clippy::too_many_arguments)]
+pub fn delegates(
+    rid: Id,
+    tracking: Option<BranchName>,
+    fetch: bool,
+    profile: &Profile,
+    stored: &Repository,
+    doc: &Doc<Verified>,
+    repo: &git::Repository,
+) -> anyhow::Result<()> {
+    let existing = git::rad_remotes(repo)?
+        .into_iter()
+        .filter_map(|r| r.url.namespace)
+        .collect::<HashSet<_>>();
+    let aliases = profile.aliases();
+    let (mut added, mut skipped, mut failed) = (0, 0, 0);
+
+    for did in doc.delegates.iter() {
+        let nid = did.as_key();
+
+        if *nid == profile.public_key {
+            continue;
+        }
+        if existing.contains(nid) {
+            term::info!("Skipping {did}: a remote already exists");
+            skipped += 1;
+            continue;
+        }
+        let name = match aliases.alias(nid) {
+            Some(alias) => alias.to_string(),
+            None => {
+                let nid = nid.to_human();
+                format!("delegate-{}", &nid[nid.len() - 7..])
+            }
+        };
+        let result = RefString::try_from(name.as_str())
+            .map_err(|_| anyhow!("invalid remote name: '{name}'"))
+            .and_then(|name| {
+                run(
+                    rid,
+                    nid,
+                    Some(name),
+                    tracking.clone(),
+                    false,
+                    fetch,
+                    profile,
+                    stored,
+                    doc,
+                    repo,
+                )
+            });
+
+        match result {
+            Ok(()) => added += 1,
+            Err(e) => {
+                term::error(format!("Failed to add remote for {did}: {e}"));
+                failed += 1;
+            }
+        }
+    }
+    term::info!("{added} remote(s) added, {skipped} skipped, {failed} failed");
+
+    if failed > 0 {
+        anyhow::bail!("failed to add remotes for {failed} delegate(s)");
+    }
+    Ok(())
 }
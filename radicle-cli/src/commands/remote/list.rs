@@ -1,12 +1,126 @@
+use radicle::api::{self, Profile, ReadRepository as _, Repository, WriteRepository as _};
+use radicle::node::AliasStore as _;
+use radicle::prelude::NodeId;
 use radicle_term::{Element, Table};
 
 use crate::git;
 use crate::terminal as term;
 
-pub fn run(repo: &git::Repository) -> anyhow::Result<()> {
-    let mut table = Table::default();
-    let remotes = git::rad_remotes(repo)?;
-    for r in remotes {
+/// A row of the remote list.
+struct Entry {
+    /// The name, description and kind of the entry.
+    cells: [term::Paint<String>; 3],
+    /// The namespace the entry refers to, if any.
+    namespace: Option<NodeId>,
+    /// The name of the remote, if the entry is a remote.
+    remote: Option<String>,
+}
+
+/// List the remotes of a working copy. With `status`, the remotes' default branch is
+/// compared with the canonical default branch of the stored repository.
+pub fn run(repo: &git::Repository, status: Option<&Repository>) -> anyhow::Result<()> {
+    let entries = remotes(repo)?;
+
+    print(entries, repo, status)
+}
+
+/// List the namespaces of a storage repository, and the remotes configured in it.
+pub fn run_storage(stored: &Repository, profile: &Profile, status: bool) -> anyhow::Result<()> {
+    let aliases = profile.aliases();
+    let mut entries = Vec::new();
+
+    for nid in stored.remote_ids()? {
+        let nid = nid?;
+        let alias = if &nid == profile.id() {
+            term::format::primary("(you)".to_owned())
+        } else {
+            aliases
+                .alias(&nid)
+                .map(|a| term::format::primary(a.to_string()))
+                .unwrap_or_default()
+        };
+        entries.push(Entry {
+            cells: [
+                alias,
+                term::format::tertiary(nid.to_string()),
+                term::format::parens(term::format::secondary("namespace".to_owned())),
+            ],
+            namespace: Some(nid),
+            remote: None,
+        });
+    }
+    entries.extend(remotes(stored.raw())?);
+
+    print(entries, stored.raw(), status.then_some(stored))
+}
+
+/// Print the entries, with their ahead/behind counts if a stored repository is given.
+fn print(
+    entries: Vec<Entry>,
+    working: &git::Repository,
+    status: Option<&Repository>,
+) -> anyhow::Result<()> {
+    let Some(stored) = status else {
+        let mut table = Table::<3, _>::default();
+        for entry in entries {
+            table.push(entry.cells);
+        }
+        table.print();
+
+        return Ok(());
+    };
+    let (_, project) = api::project(stored)?;
+    let branch = project.default_branch();
+    let (_, canonical) = stored.head()?;
+    let mut table = Table::<4, _>::default();
+
+    for entry in entries {
+        // The default branch of the namespace in storage, or else the remote-tracking
+        // branch of the remote in the working copy.
+        let head = entry.namespace.and_then(|nid| {
+            stored
+                .raw()
+                .refname_to_id(&format!("refs/namespaces/{nid}/refs/heads/{branch}"))
+                .ok()
+                .or_else(|| {
+                    let name = entry.remote.as_ref()?;
+                    working
+                        .refname_to_id(&format!("refs/remotes/{name}/{branch}"))
+                        .ok()
+                })
+        });
+        let status = head
+            .and_then(|head| ahead_behind(working, stored, head, canonical.into()))
+            .map(|(ahead, behind)| term::format::secondary(format!("+{ahead}/-{behind}")))
+            .unwrap_or_else(|| term::format::dim(String::from("-")));
+        let [name, description, kind] = entry.cells;
+
+        table.push([name, description, kind, status]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+/// Count the commits of `head` that aren't in `canonical`, and vice versa. The objects
+/// are looked up in the working copy first, then in storage. Nothing is fetched.
+fn ahead_behind(
+    working: &git::Repository,
+    stored: &Repository,
+    head: git::Oid,
+    canonical: git::Oid,
+) -> Option<(usize, usize)> {
+    working
+        .graph_ahead_behind(head, canonical)
+        .or_else(|_| stored.raw().graph_ahead_behind(head, canonical))
+        .ok()
+}
+
+/// Get the `rad` remotes configured in a repository.
+fn remotes(repo: &git::Repository) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for r in git::rad_remotes(repo)? {
         for (dir, url) in [("fetch", Some(r.url)), ("push", r.pushurl)] {
             let Some(url) = url else {
                 continue;
@@ -16,14 +130,16 @@ pub fn run(repo: &git::Repository) -> anyhow::Result<()> {
                 term::format::dim("(canonical upstream)".to_string()).italic(),
                 |namespace| term::format::tertiary(namespace.to_string()),
             );
-            table.push([
-                term::format::bold(r.name.clone()),
-                description,
-                term::format::parens(term::format::secondary(dir.to_owned())),
-            ]);
+            entries.push(Entry {
+                cells: [
+                    term::format::bold(r.name.clone()),
+                    description,
+                    term::format::parens(term::format::secondary(dir.to_owned())),
+                ],
+                namespace: url.namespace,
+                remote: Some(r.name.clone()),
+            });
         }
     }
-    table.print();
-
-    Ok(())
+    Ok(entries)
 }
@@ -5,7 +5,18 @@ pub fn run(name: &str, repository: &git::Repository) -> anyhow::Result<()> {
     if !git::is_remote(repository, name)? {
         anyhow::bail!("remote `{name}` not found");
     }
+    let namespace = repository
+        .find_remote(name)
+        .ok()
+        .and_then(|r| git::Remote::try_from(r).ok())
+        .and_then(|r| r.url.namespace);
+
     repository.remote_delete(name)?;
+
+    // Don't let `rad remote sync` add back a remote that was removed by the user.
+    if let Some(nid) = namespace {
+        git::skip_remote(repository, &nid, true)?;
+    }
     term::success!("Remote `{name}` removed");
     Ok(())
 }
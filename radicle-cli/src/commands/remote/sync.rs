@@ -0,0 +1,74 @@
+use std::collections::{BTreeSet, HashSet};
+
+use radicle::api::{BranchName, Id, Profile, Repository};
+use radicle::node::tracking::Policy;
+
+use crate::commands::rad_checkout as checkout;
+use crate::git;
+use crate::project::SetupRemote;
+use crate::terminal as term;
+
+/// Add remotes for the tracked nodes we have refs of, and remove the remotes we added
+/// for nodes that are no longer tracked.
+pub fn run(
+    rid: Id,
+    tracking: Option<BranchName>,
+    profile: &Profile,
+    stored: &Repository,
+    repo: &git::Repository,
+) -> anyhow::Result<()> {
+    let config = &profile.config.cli;
+    let stored_ids = stored.remote_ids()?.collect::<Result<HashSet<_>, _>>()?;
+    let skipped = git::skipped_remotes(repo)?;
+    // Tracked nodes that should have a remote in the working copy.
+    let wanted = profile
+        .tracking()?
+        .node_policies()?
+        .filter(|node| node.policy == Policy::Track)
+        .map(|node| node.id)
+        .filter(|nid| {
+            *nid != profile.public_key
+                && config.adds_remotes_for(nid)
+                && stored_ids.contains(nid)
+                && !skipped.contains(nid)
+        })
+        .collect::<BTreeSet<_>>();
+    let remotes = git::rad_remotes(repo)?;
+    let existing = remotes
+        .iter()
+        .filter_map(|r| r.url.namespace)
+        .collect::<HashSet<_>>();
+    let mut changed = false;
+
+    for remote in &remotes {
+        let Some(nid) = remote.url.namespace else {
+            continue;
+        };
+        if wanted.contains(&nid) || !git::is_synced_remote(repo, &remote.name)? {
+            continue;
+        }
+        repo.remote_delete(&remote.name)?;
+        term::success!("Remote `{}` removed", remote.name);
+
+        changed = true;
+    }
+
+    let aliases = profile.aliases();
+    let setup = SetupRemote {
+        rid,
+        tracking,
+        fetch: false,
+        repo,
+    };
+    for nid in wanted.iter().filter(|nid| !existing.contains(nid)) {
+        let name = checkout::setup_remote(&setup, nid, None, &aliases)?;
+        git::set_synced_remote(repo, name.as_str())?;
+
+        changed = true;
+    }
+
+    if !changed {
+        term::info!("Remotes are up to date");
+    }
+    Ok(())
+}
@@ -6,10 +6,11 @@ use std::ffi::OsString;
 use anyhow::{anyhow, Context};
 
 use radicle::cob::patch::{PatchId, Patches, RevisionId, Verdict};
+use radicle::git;
 use radicle::prelude::*;
-use radicle::{git, rad};
 
 use crate::git::Rev;
+use crate::project;
 use crate::terminal as term;
 use crate::terminal::args::{string, Args, Error, Help};
 use crate::terminal::patch::Message;
@@ -198,8 +199,7 @@ impl Args for Options {
 }
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
-    let (_, id) =
-        rad::cwd().map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+    let (_, id) = project::cwd()?;
     let profile = ctx.profile()?;
     let signer = term::signer(&profile)?;
     let repository = profile.storage.repository(id)?;
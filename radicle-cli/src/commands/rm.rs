@@ -80,7 +80,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     }
 
     if !options.confirm || term::confirm(format!("Remove {rid}?")) {
-        untrack(&rid, &profile)?;
+        untrack(&rid, &profile, &ctx)?;
         remove_remote(&rid)?;
         fs::remove_dir_all(path)?;
         term::success!("Successfully removed {rid} from storage");
@@ -89,10 +89,9 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn untrack(rid: &Id, profile: &Profile) -> anyhow::Result<()> {
-    let mut node = radicle::Node::new(profile.socket());
-
-    let result = if node.is_running() {
+fn untrack(rid: &Id, profile: &Profile, ctx: &impl term::Context) -> anyhow::Result<()> {
+    let result = if ctx.is_node_running(profile) {
+        let mut node = radicle::Node::from_profile(profile);
         node.untrack_repo(*rid).map_err(anyhow::Error::from)
     } else {
         let mut store =
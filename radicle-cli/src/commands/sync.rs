@@ -1,15 +1,18 @@
 use std::ffi::OsString;
-use std::path::Path;
 use std::time;
 
 use anyhow::{anyhow, Context as _};
+use localtime::LocalTime;
 
-use radicle::node;
-use radicle::node::{FetchResult, FetchResults, Handle as _, Node};
-use radicle::prelude::{Id, NodeId};
+use radicle::api::{self, error};
+use radicle::api::{AnnounceEvent, ConnectOptions, ConnectResult, ErrorCode};
+use radicle::api::{FetchOutcome, FetchRecord, SyncAllOptions, SyncProgress, SyncResult};
+use radicle::api::{FetchResult, FetchResults};
+use radicle::api::{Handle as _, Id, Node, NodeId};
 
 use crate::terminal as term;
 use crate::terminal::args::{Args, Error, Help};
+use crate::terminal::Element as _;
 
 pub const HELP: Help = Help {
     name: "sync",
@@ -21,6 +24,8 @@ Usage
     rad sync [<rid>] [<option>...]
     rad sync [<rid>] [--fetch] [<rid>] [<option>...]
     rad sync [<rid>] [--announce] [<rid>] [<option>...]
+    rad sync [<rid>] --history
+    rad sync --all [--fetch] [--announce] [<option>...]
 
     By default, the current repository is synchronized both ways.
     If an <rid> is specified, that repository is synced instead.
@@ -38,6 +43,13 @@ Usage
     When `--fetch` or `--announce` are specified on their own, this command
     will only fetch or announce.
 
+    When `--history` is specified, nothing is synced. Instead, the recent
+    fetches of the repository by the node are shown, including failed ones.
+
+    When `--all` is specified, all tracked repositories are synced by the node,
+    a few at a time. Each repository is fetched from its best connected seed,
+    and our refs are announced.
+
 Options
 
     --fetch, -f               Turn on fetching (default: true)
@@ -45,6 +57,8 @@ Options
     --timeout <secs>          How many seconds to wait while syncing
     --seed <nid>              Sync with the given node (may be specified multiple times)
     --replicas, -r <count>    Sync with a specific number of seeds
+    --history                 Show the recent fetches of the repository
+    --all                     Sync all tracked repositories
     --verbose, -v             Verbose output
     --help                    Print help
 "#,
@@ -82,6 +96,8 @@ pub struct Options {
     pub verbose: bool,
     pub timeout: time::Duration,
     pub sync: SyncOptions,
+    pub history: bool,
+    pub all: bool,
 }
 
 impl Args for Options {
@@ -93,12 +109,20 @@ impl Args for Options {
         let mut timeout = time::Duration::from_secs(9);
         let mut rid = None;
         let mut sync = SyncOptions::default();
+        let mut history = false;
+        let mut all = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
                 Long("verbose") | Short('v') => {
                     verbose = true;
                 }
+                Long("history") => {
+                    history = true;
+                }
+                Long("all") => {
+                    all = true;
+                }
                 Long("fetch") | Short('f') => {
                     sync.direction = match sync.direction {
                         SyncDirection::Both => SyncDirection::Fetch,
@@ -151,6 +175,14 @@ impl Args for Options {
             }
         }
 
+        if all {
+            if rid.is_some() {
+                anyhow::bail!("`--all` cannot be specified with a repository");
+            }
+            if sync.mode != SyncMode::default() {
+                anyhow::bail!("`--all` cannot be specified with `--seed` or `--replicas`");
+            }
+        }
         if sync.direction == SyncDirection::Announce {
             if let SyncMode::Seeds(_) = sync.mode {
                 anyhow::bail!("`--seed` is only supported when fetching.");
@@ -163,6 +195,8 @@ impl Args for Options {
                 verbose,
                 timeout,
                 sync,
+                history,
+                all,
             },
             vec![],
         ))
@@ -171,18 +205,26 @@ impl Args for Options {
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
+    let mut node = ctx.node(&profile)?;
+
+    if options.all {
+        return sync_all(options.sync.direction, &mut node);
+    }
     let rid = match options.rid {
         Some(rid) => rid,
         None => {
-            let (_, rid) = radicle::rad::repo(Path::new("."))
-                .context("Current directory is not a radicle project")?;
+            let (_, rid) =
+                api::working(".").context("Current directory is not a radicle project")?;
 
             rid
         }
     };
-    let mut node = radicle::Node::new(profile.socket());
     let mode = options.sync.mode;
 
+    if options.history {
+        return history(rid, &node);
+    }
+
     if [SyncDirection::Fetch, SyncDirection::Both].contains(&options.sync.direction) {
         if !profile.tracking()?.is_repo_tracked(&rid)? {
             anyhow::bail!("repository {rid} is not tracked");
@@ -196,6 +238,18 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         } else {
             term::success!("Fetched repository from {success} seed(s)");
         }
+        let skipped = results.skipped();
+        if !skipped.is_empty() {
+            term::blank();
+            term::info!("Not fetched:");
+            for (nid, reason) in skipped {
+                term::info!(
+                    "  {} {}",
+                    term::format::tertiary(term::format::node(&nid)),
+                    term::format::dim(reason)
+                );
+            }
+        }
     }
     if [SyncDirection::Announce, SyncDirection::Both].contains(&options.sync.direction) {
         announce(rid, mode, options.timeout, node)?;
@@ -203,6 +257,105 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn sync_all(direction: SyncDirection, node: &mut Node) -> anyhow::Result<()> {
+    let options = SyncAllOptions {
+        fetch: direction != SyncDirection::Announce,
+        announce: direction != SyncDirection::Fetch,
+        ..SyncAllOptions::default()
+    };
+
+    for progress in node.sync_all(options)? {
+        match progress? {
+            SyncProgress::Repo {
+                rid,
+                action,
+                result,
+            } => match result {
+                SyncResult::Success {
+                    seed: Some(seed), ..
+                } => {
+                    term::success!(
+                        "Fetched {} from {}",
+                        term::format::tertiary(rid),
+                        term::format::node(&seed)
+                    );
+                }
+                SyncResult::Success { seed: None, .. } => {
+                    term::success!("Announced {}", term::format::tertiary(rid));
+                }
+                SyncResult::Skipped { reason } => {
+                    term::info!(
+                        "Skipped {action} of {}: {reason}",
+                        term::format::tertiary(rid)
+                    );
+                }
+                SyncResult::Failed { reason } => {
+                    term::error(format!(
+                        "Failed to {action} {}: {reason}",
+                        term::format::tertiary(rid)
+                    ));
+                }
+            },
+            SyncProgress::Summary {
+                repos,
+                succeeded,
+                skipped,
+                failed,
+            } => {
+                term::info!(
+                    "Synced {repos} repositories: {succeeded} succeeded, {skipped} skipped, {failed} failed"
+                );
+                if failed > 0 {
+                    anyhow::bail!("failed to sync {failed} repositories");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn history(rid: Id, node: &Node) -> anyhow::Result<()> {
+    let history = node.fetch_history(rid)?;
+    if history.is_empty() {
+        term::info!("No fetches of {rid} yet.");
+        return Ok(());
+    }
+    let mut table = term::Table::default();
+
+    for FetchRecord {
+        time,
+        seed,
+        duration,
+        received,
+        outcome,
+    } in history
+    {
+        let (status, reason) = match outcome {
+            FetchOutcome::Success => (term::format::positive(String::from("ok")), None),
+            FetchOutcome::Failed { failure, reason } => {
+                (term::format::negative(failure.to_string()), Some(reason))
+            }
+        };
+        table.push([
+            term::format::tertiary(term::format::node(&seed)),
+            status,
+            term::format::dim(duration.map(|d| format!("{d}ms")).unwrap_or_default()),
+            term::format::dim(
+                received
+                    .map(|r| term::format::bytes(r).to_string())
+                    .unwrap_or_default(),
+            ),
+            term::format::dim(
+                term::format::timestamp(&LocalTime::from_millis(time as u128)).to_string(),
+            ),
+            term::format::default(reason.unwrap_or_default()),
+        ]);
+    }
+    table.print();
+
+    Ok(())
+}
+
 fn announce(
     rid: Id,
     _mode: SyncMode,
@@ -218,11 +371,25 @@ fn announce(
 
     let mut spinner = term::spinner(format!("Syncing with {} node(s)..", connected.len()));
     let result = node.announce(rid, connected, timeout, |event| match event {
-        node::AnnounceEvent::Announced => {}
-        node::AnnounceEvent::RefsSynced { remote } => {
+        AnnounceEvent::Announced => {}
+        AnnounceEvent::RefsSynced { remote } => {
             spinner.message(format!("Synced with {remote}.."));
         }
-    })?;
+    });
+    let result = match result {
+        Ok(result) => result,
+        Err(error::Node::Node {
+            code: ErrorCode::NothingToAnnounce,
+            ..
+        }) => {
+            spinner.message("Nothing to announce");
+            spinner.warn();
+            term::tip!("You have no refs in {rid}. To contribute to it, run `rad fork` first.");
+
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
 
     if result.synced.is_empty() {
         spinner.failed();
@@ -244,7 +411,7 @@ pub fn fetch(
     mode: SyncMode,
     timeout: time::Duration,
     node: &mut Node,
-) -> Result<FetchResults, node::Error> {
+) -> Result<FetchResults, error::Node> {
     match mode {
         SyncMode::Seeds(seeds) => {
             let mut results = FetchResults::default();
@@ -263,14 +430,16 @@ fn fetch_all(
     count: usize,
     timeout: time::Duration,
     node: &mut Node,
-) -> Result<FetchResults, node::Error> {
+) -> Result<FetchResults, error::Node> {
     // Get seeds. This consults the local routing table only.
     let seeds = node.seeds(rid)?;
     let mut results = FetchResults::default();
-    let (connected, mut disconnected) = seeds.partition();
+    let (_, mut disconnected) = seeds.partition();
+    // Seeds are popped off the end, so try the ones that announced refs we don't have first.
+    disconnected.sort_by_key(|s| s.is_fresh());
 
     // Fetch from connected seeds.
-    for seed in connected.iter().take(count) {
+    for seed in seeds.select(count) {
         let result = fetch_from(rid, &seed.nid, node)?;
         results.push(seed.nid, result);
     }
@@ -290,20 +459,20 @@ fn fetch_all(
             let cr = node.connect(
                 seed.nid,
                 ka.addr,
-                node::ConnectOptions {
+                ConnectOptions {
                     persistent: false,
                     timeout,
                 },
             )?;
 
             match cr {
-                node::ConnectResult::Connected => {
+                ConnectResult::Connected => {
                     spinner.finish();
                     let result = fetch_from(rid, &seed.nid, node)?;
                     results.push(seed.nid, result);
                     break;
                 }
-                node::ConnectResult::Disconnected { .. } => {
+                ConnectResult::Disconnected { .. } => {
                     spinner.failed();
                     continue;
                 }
@@ -314,7 +483,7 @@ fn fetch_all(
     Ok(results)
 }
 
-fn fetch_from(rid: Id, seed: &NodeId, node: &mut Node) -> Result<FetchResult, node::Error> {
+fn fetch_from(rid: Id, seed: &NodeId, node: &mut Node) -> Result<FetchResult, error::Node> {
     let spinner = term::spinner(format!(
         "Fetching {} from {}..",
         term::format::tertiary(rid),
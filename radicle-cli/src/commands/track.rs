@@ -3,8 +3,9 @@ use std::time;
 
 use anyhow::anyhow;
 
-use radicle::node::tracking::{Alias, Scope};
-use radicle::node::{Handle, NodeId};
+use radicle::node::tracking::{Alias, RefFilter, Scope};
+use radicle::node::{Handle, NodeId, TrackFetch};
+use radicle::profile::Config;
 use radicle::{prelude::*, Node};
 
 use crate::commands::rad_sync as sync;
@@ -18,8 +19,9 @@ pub const HELP: Help = Help {
     usage: r#"
 Usage
 
-    rad track <nid> [--alias <name>] [<option>...]
-    rad track <rid> [--[no-]fetch] [--scope <scope>] [<option>...]
+    rad track <nid> [--alias <name>] [--add-remotes] [<option>...]
+    rad track <rid> [--[no-]fetch] [--scope <scope>] [--dry-run] [<option>...]
+              [--include <pattern>...] [--exclude <pattern>...]
 
     The `track` command takes either an NID or an RID. Based on the argument, it will
     either update the tracking policy of a node (NID), or a repository (RID).
@@ -29,11 +31,26 @@ Usage
     On the other hand, with `trusted`, only the repository delegates will be tracked,
     plus any remote that is explicitly tracked via `rad track <nid>`.
 
+    With `--dry-run`, the tracking policy is left unchanged, and a connected seed is
+    asked for its refs instead, to show what would be fetched.
+
+    With `--include` and `--exclude`, only the refs matching the given patterns are
+    fetched from the repository's remotes, eg. `--exclude 'refs/heads/artifacts/*'`.
+    Patterns are matched against ref names without the namespace prefix. When any
+    pattern is given, the repository's previous patterns are replaced.
+
+    With `--add-remotes`, `rad remote sync` will add a remote for the tracked node
+    to the working copies of repositories we have the node's refs of.
+
 Options
 
     --alias <name>         Associate an alias to a tracked node
-    --[no-]fetch           Fetch refs after tracking
+    --add-remotes          Have `rad remote sync` add remotes for the tracked node
+    --[no-]fetch           Fetch the repository from known seeds after tracking (default: on)
     --scope <scope>        Node (remote) tracking scope for a repository
+    --dry-run              Show what would be fetched, without tracking the repository
+    --include <pattern>    Only fetch refs matching the pattern (may be repeated)
+    --exclude <pattern>    Don't fetch refs matching the pattern (may be repeated)
     --verbose, -v          Verbose output
     --help                 Print help
 "#,
@@ -41,14 +58,23 @@ Options
 
 #[derive(Debug)]
 pub enum Operation {
-    TrackNode { nid: NodeId, alias: Option<Alias> },
-    TrackRepo { rid: Id, scope: Scope },
+    TrackNode {
+        nid: NodeId,
+        alias: Option<Alias>,
+        add_remotes: bool,
+    },
+    TrackRepo {
+        rid: Id,
+        scope: Scope,
+        filter: Option<RefFilter>,
+    },
 }
 
 #[derive(Debug)]
 pub struct Options {
     pub op: Operation,
     pub fetch: bool,
+    pub dry_run: bool,
     pub verbose: bool,
 }
 
@@ -59,6 +85,7 @@ impl Args for Options {
         let mut parser = lexopt::Parser::from_args(args);
         let mut op: Option<Operation> = None;
         let mut fetch = true;
+        let mut dry_run = false;
         let mut verbose = false;
 
         while let Some(arg) = parser.next()? {
@@ -68,14 +95,20 @@ impl Args for Options {
                         op = Some(Operation::TrackRepo {
                             rid,
                             scope: Scope::default(),
+                            filter: None,
                         });
                     } else if let Ok(did) = term::args::did(val) {
                         op = Some(Operation::TrackNode {
                             nid: did.into(),
                             alias: None,
+                            add_remotes: false,
                         });
                     } else if let Ok(nid) = term::args::nid(val) {
-                        op = Some(Operation::TrackNode { nid, alias: None });
+                        op = Some(Operation::TrackNode {
+                            nid,
+                            alias: None,
+                            add_remotes: false,
+                        });
                     }
                 }
                 (Long("alias"), Some(Operation::TrackNode { alias, .. })) => {
@@ -84,13 +117,35 @@ impl Args for Options {
 
                     *alias = Some(name.to_owned());
                 }
+                (Long("add-remotes"), Some(Operation::TrackNode { add_remotes, .. })) => {
+                    *add_remotes = true;
+                }
                 (Long("scope"), Some(Operation::TrackRepo { scope, .. })) => {
                     let val = parser.value()?;
 
                     *scope = term::args::parse_value("scope", val)?;
                 }
+                (Long("include"), Some(Operation::TrackRepo { filter, .. })) => {
+                    let val = parser.value()?;
+                    let pattern = term::args::pattern("include", val)?;
+
+                    filter
+                        .get_or_insert_with(RefFilter::default)
+                        .include
+                        .push(pattern);
+                }
+                (Long("exclude"), Some(Operation::TrackRepo { filter, .. })) => {
+                    let val = parser.value()?;
+                    let pattern = term::args::pattern("exclude", val)?;
+
+                    filter
+                        .get_or_insert_with(RefFilter::default)
+                        .exclude
+                        .push(pattern);
+                }
                 (Long("fetch"), Some(Operation::TrackRepo { .. })) => fetch = true,
                 (Long("no-fetch"), Some(Operation::TrackRepo { .. })) => fetch = false,
+                (Long("dry-run"), Some(Operation::TrackRepo { .. })) => dry_run = true,
                 (Long("verbose") | Short('v'), _) => verbose = true,
                 (Long("help") | Short('h'), _) => {
                     return Err(Error::Help.into());
@@ -105,6 +160,7 @@ impl Args for Options {
             Options {
                 op: op.ok_or_else(|| anyhow!("either a NID or an RID must be specified"))?,
                 fetch,
+                dry_run,
                 verbose,
             },
             vec![],
@@ -114,22 +170,39 @@ impl Args for Options {
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
-    let mut node = radicle::Node::new(profile.socket());
+    let mut node = ctx.node(&profile)?;
 
     match options.op {
-        Operation::TrackNode { nid, alias } => {
+        Operation::TrackNode {
+            nid,
+            alias,
+            add_remotes,
+        } => {
             track_node(nid, alias, &mut node)?;
-        }
-        Operation::TrackRepo { rid, scope } => {
-            track_repo(rid, scope, &mut node)?;
 
+            if add_remotes {
+                let path = profile.home.config();
+                let mut config = Config::load(&path)?;
+                if config.cli.remotes.insert(nid) {
+                    config.write(&path)?;
+                }
+                term::success!(
+                    "Remotes for {} will be added by `rad remote sync`",
+                    term::format::tertiary(term::format::node(&nid)),
+                );
+            }
+        }
+        Operation::TrackRepo { rid, scope, .. } if options.dry_run => {
+            track_repo_dry_run(rid, scope, &mut node)?;
+        }
+        Operation::TrackRepo { rid, scope, filter } => {
+            if let Some(filter) = filter {
+                track_repo_filter(rid, scope, filter, &mut node)?;
+            }
             if options.fetch {
-                sync::fetch(
-                    rid,
-                    sync::SyncMode::default(),
-                    time::Duration::from_secs(6),
-                    &mut node,
-                )?;
+                track_repo_fetch(rid, scope, &mut node)?;
+            } else {
+                track_repo(rid, scope, &mut node)?;
             }
         }
     }
@@ -148,6 +221,79 @@ pub fn track_repo(rid: Id, scope: Scope, node: &mut Node) -> anyhow::Result<()>
     Ok(())
 }
 
+pub fn track_repo_filter(
+    rid: Id,
+    scope: Scope,
+    filter: RefFilter,
+    node: &mut Node,
+) -> anyhow::Result<()> {
+    let updated = node.track_repo_filter(rid, scope, filter.clone())?;
+    let outcome = if updated { "updated" } else { "exists" };
+
+    term::success!(
+        "Ref filter {outcome} for {}: {filter}",
+        term::format::tertiary(rid),
+    );
+
+    Ok(())
+}
+
+pub fn track_repo_fetch(rid: Id, scope: Scope, node: &mut Node) -> anyhow::Result<()> {
+    let tracked = node.track_repo_fetch(rid, scope)?;
+    let outcome = if tracked.updated { "updated" } else { "exists" };
+
+    term::success!(
+        "Tracking policy {outcome} for {} with scope '{scope}'",
+        term::format::tertiary(rid),
+    );
+
+    match tracked.fetch {
+        // Wait for the fetch started by the node, which also covers the time it takes to
+        // connect to the seed, if the fetch is queued.
+        TrackFetch::Started { seed } | TrackFetch::Queued { seed } => {
+            sync::fetch(
+                rid,
+                sync::SyncMode::Seeds(vec![seed]),
+                time::Duration::from_secs(6),
+                node,
+            )?;
+        }
+        TrackFetch::NoSeeds => {
+            term::warning(&format!(
+                "No seeds found for {rid}; it will be fetched once a seed is known"
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn track_repo_dry_run(rid: Id, scope: Scope, node: &mut Node) -> anyhow::Result<()> {
+    let summary = node.track_repo_dry_run(rid, scope)?;
+    let connected = summary.seeds.iter().filter(|s| s.is_connected()).count();
+
+    term::info!(
+        "Tracking {} with scope '{scope}' (dry-run)",
+        term::format::tertiary(rid)
+    );
+    term::info!(
+        "Found {} seed(s), {connected} of which connected",
+        summary.seeds.len()
+    );
+
+    if let (Some(seed), Some(namespaces), Some(refs)) =
+        (summary.seed, summary.namespaces, summary.refs)
+    {
+        term::info!(
+            "Would fetch {refs} ref(s) in {namespaces} namespace(s) from {}",
+            term::format::node(&seed)
+        );
+    }
+    if let Some(note) = summary.note {
+        term::info!("{}", term::format::dim(format!("Note: {note}")));
+    }
+    Ok(())
+}
+
 pub fn track_node(nid: NodeId, alias: Option<Alias>, node: &mut Node) -> anyhow::Result<()> {
     let tracked = node.track_node(nid, alias.clone())?;
     let outcome = if tracked { "updated" } else { "exists" };
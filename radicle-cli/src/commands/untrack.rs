@@ -81,7 +81,7 @@ impl Args for Options {
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
-    let mut node = radicle::Node::new(profile.socket());
+    let mut node = ctx.node(&profile)?;
 
     match options.op {
         Operation::UntrackNode { nid } => untrack_node(nid, &mut node),
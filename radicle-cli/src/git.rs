@@ -34,6 +34,8 @@ pub const CONFIG_SIGNING_KEY: &str = "user.signingkey";
 pub const CONFIG_GPG_FORMAT: &str = "gpg.format";
 pub const CONFIG_GPG_SSH_PROGRAM: &str = "gpg.ssh.program";
 pub const CONFIG_GPG_SSH_ALLOWED_SIGNERS: &str = "gpg.ssh.allowedSignersFile";
+/// Nodes whose remote was removed by the user, and shouldn't be re-added by `rad remote sync`.
+pub const CONFIG_SKIP_REMOTE: &str = "rad.skipRemote";
 
 /// Git revision parameter. Supports extended SHA-1 syntax.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -258,6 +260,58 @@ pub fn is_remote(repo: &git2::Repository, alias: &str) -> anyhow::Result<bool> {
     }
 }
 
+/// Get the nodes `rad remote sync` shouldn't add remotes for.
+pub fn skipped_remotes(repo: &git2::Repository) -> anyhow::Result<HashSet<NodeId>> {
+    let cfg = repo.config()?;
+    let mut skipped = HashSet::new();
+    let mut entries = cfg.multivar(CONFIG_SKIP_REMOTE, None)?;
+
+    while let Some(entry) = entries.next() {
+        if let Some(nid) = entry?.value().and_then(|v| NodeId::from_str(v).ok()) {
+            skipped.insert(nid);
+        }
+    }
+    Ok(skipped)
+}
+
+/// Set whether `rad remote sync` should skip adding a remote for the given node.
+pub fn skip_remote(repo: &git2::Repository, nid: &NodeId, skip: bool) -> anyhow::Result<()> {
+    let mut cfg = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    // Nb. Node ids are base-58 encoded, and therefore safe to use as a regular expression.
+    let regexp = format!("^{nid}$");
+
+    if skip {
+        cfg.set_multivar(CONFIG_SKIP_REMOTE, &regexp, &nid.to_string())?;
+    } else {
+        match cfg.remove_multivar(CONFIG_SKIP_REMOTE, &regexp) {
+            Ok(()) => {}
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Check whether the given remote was added by `rad remote sync`.
+pub fn is_synced_remote(repo: &git2::Repository, name: &str) -> anyhow::Result<bool> {
+    let cfg = repo.config()?;
+
+    match cfg.get_bool(&format!("remote.{name}.radSync")) {
+        Ok(synced) => Ok(synced),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Mark the given remote as added by `rad remote sync`.
+pub fn set_synced_remote(repo: &git2::Repository, name: &str) -> anyhow::Result<()> {
+    repo.config()?
+        .open_level(git2::ConfigLevel::Local)?
+        .set_bool(&format!("remote.{name}.radSync"), true)?;
+
+    Ok(())
+}
+
 /// Get the repository's "rad" remote.
 pub fn rad_remote(repo: &Repository) -> anyhow::Result<(git2::Remote, Id)> {
     match radicle::rad::remote(repo) {
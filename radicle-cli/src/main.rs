@@ -4,6 +4,7 @@ use std::{io::ErrorKind, iter, process};
 
 use anyhow::anyhow;
 
+use radicle::profile::env;
 use radicle::version;
 use radicle_cli::commands::*;
 use radicle_cli::terminal as term;
@@ -46,6 +47,11 @@ fn parse_args() -> anyhow::Result<Command> {
             Long("version") => {
                 command = Some(Command::Version);
             }
+            Long("offline") if command.is_none() => {
+                // Nb. Set in the environment, so that it is inherited by the processes we
+                // spawn, eg. git and its remote helper.
+                env::set_var(env::RAD_OFFLINE, "true");
+            }
             Value(val) if command.is_none() => {
                 if val == *"." {
                     command = Some(Command::Other(vec![OsString::from("inspect")]));
@@ -69,7 +75,7 @@ fn print_help() -> anyhow::Result<()> {
     println!("{DESCRIPTION}");
     println!();
 
-    rad_help::run(Default::default(), term::profile)
+    rad_help::run(Default::default(), term::DefaultContext::from_env())
 }
 
 fn run(command: Command) -> Result<(), Option<anyhow::Error>> {
@@ -1,8 +1,73 @@
+use std::path::Path;
+
+use radicle::git::raw::{ErrorClass, ErrorCode};
 use radicle::prelude::*;
+use radicle::rad::RemoteError;
 
 use crate::git;
+use crate::terminal::args;
 use radicle::git::RefStr;
 
+/// Why a directory could not be resolved to a project working copy.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NotAProject {
+    /// The directory is not in a git repository.
+    NotARepository,
+    /// The repository has no `rad` remote.
+    NoRemote,
+    /// The `rad` remote's URL doesn't point to a repository.
+    InvalidRemote { url: String },
+    /// Any other failure, eg. an unreadable git configuration.
+    Other,
+}
+
+impl NotAProject {
+    /// Classify an error returned when resolving a working copy.
+    pub fn classify(err: &RemoteError) -> Self {
+        match err {
+            RemoteError::Git(e)
+                if e.code() == ErrorCode::NotFound && e.class() == ErrorClass::Repository =>
+            {
+                Self::NotARepository
+            }
+            RemoteError::NotFound(_) => Self::NoRemote,
+            RemoteError::Url { url, .. } => Self::InvalidRemote { url: url.clone() },
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Get the working copy at the given path, and the project it belongs to.
+/// Fails with a hint if the path is not inside a project working copy.
+pub fn working(path: impl AsRef<Path>) -> anyhow::Result<(git::Repository, Id)> {
+    let err = match radicle::rad::repo(path) {
+        Ok(working) => return Ok(working),
+        Err(err) => err,
+    };
+    let err = match NotAProject::classify(&err) {
+        NotAProject::NotARepository => args::Error::WithHint {
+            err: anyhow::anyhow!("this command must be run in the context of a project"),
+            hint: "The current directory is not a git repository.",
+        },
+        NotAProject::NoRemote => args::Error::WithHint {
+            err: anyhow::anyhow!("this repository is not a radicle project"),
+            hint:
+                "To publish it, run `rad init`, or use `rad clone` to checkout an existing project.",
+        },
+        NotAProject::InvalidRemote { url } => args::Error::WithHint {
+            err: anyhow::anyhow!("the `rad` remote has an invalid url '{url}'"),
+            hint: "The url is expected to be of the form `rad://<rid>`.",
+        },
+        NotAProject::Other => return Err(err.into()),
+    };
+    Err(err.into())
+}
+
+/// Get the working copy in the current directory, and the project it belongs to.
+pub fn cwd() -> anyhow::Result<(git::Repository, Id)> {
+    self::working(".")
+}
+
 /// Setup a project remote and tracking branch.
 pub struct SetupRemote<'a> {
     /// The project id.
@@ -48,3 +113,51 @@ impl<'a> SetupRemote<'a> {
         Ok((remote, None))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use radicle::git::raw as git2;
+
+    #[test]
+    fn test_classify() {
+        let err = RemoteError::Git(git2::Error::new(
+            ErrorCode::NotFound,
+            ErrorClass::Repository,
+            "could not find repository at '.'",
+        ));
+        assert_eq!(NotAProject::classify(&err), NotAProject::NotARepository);
+
+        let err = RemoteError::NotFound(String::from("rad"));
+        assert_eq!(NotAProject::classify(&err), NotAProject::NoRemote);
+
+        let err = RemoteError::Url {
+            url: String::from("rad://nope"),
+            err: "rad://nope".parse::<radicle::git::Url>().unwrap_err(),
+        };
+        assert_eq!(
+            NotAProject::classify(&err),
+            NotAProject::InvalidRemote {
+                url: String::from("rad://nope")
+            }
+        );
+
+        let err = RemoteError::Git(git2::Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Config,
+            "failed to parse config file",
+        ));
+        assert_eq!(NotAProject::classify(&err), NotAProject::Other);
+
+        let err = RemoteError::Git(git2::Error::new(
+            ErrorCode::NotFound,
+            ErrorClass::Config,
+            "config value not found",
+        ));
+        assert_eq!(
+            NotAProject::classify(&err),
+            NotAProject::Other,
+            "Only a missing repository means we're not in one"
+        );
+    }
+}
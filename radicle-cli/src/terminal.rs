@@ -5,12 +5,14 @@ pub mod io;
 pub use io::{proposal, signer};
 pub mod patch;
 
+use std::cell::Cell;
 use std::ffi::OsString;
 use std::process;
 
 pub use radicle_term::*;
 
-use radicle::profile::Profile;
+use radicle::node::{Handle as _, Node};
+use radicle::profile::{self, Profile};
 
 use crate::terminal;
 
@@ -18,6 +20,72 @@ use crate::terminal;
 pub trait Context {
     /// Return the currently active profile, or an error if no profile is active.
     fn profile(&self) -> Result<Profile, anyhow::Error>;
+
+    /// Whether we're offline, in which case commands must not connect to the node.
+    fn offline(&self) -> bool {
+        false
+    }
+
+    /// Check whether the node is running. Always `false` when offline.
+    fn is_node_running(&self, profile: &Profile) -> bool {
+        !self.offline() && Node::from_profile(profile).is_running()
+    }
+
+    /// Get a handle to the node, for commands that can't do without it.
+    /// Fails when offline, so that such commands fail before doing any work.
+    fn node(&self, profile: &Profile) -> Result<Node, anyhow::Error> {
+        if self.offline() {
+            return Err(Error::WithHint {
+                err: anyhow::anyhow!("this command requires a running node"),
+                hint: "Run it again without `--offline`.",
+            }
+            .into());
+        }
+        Ok(Node::from_profile(profile))
+    }
+}
+
+/// Context of commands run from the command line.
+#[derive(Debug, Default)]
+pub struct DefaultContext {
+    /// Whether we're offline.
+    offline: bool,
+    /// Whether the node is running, once checked.
+    running: Cell<Option<bool>>,
+}
+
+impl DefaultContext {
+    /// Create a context from the environment.
+    pub fn from_env() -> Self {
+        Self {
+            offline: profile::env::offline(),
+            running: Cell::default(),
+        }
+    }
+}
+
+impl Context for DefaultContext {
+    fn profile(&self) -> Result<Profile, anyhow::Error> {
+        self::profile()
+    }
+
+    fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Nb. The result is memoized, so that commands only ever pay for the socket timeout once.
+    fn is_node_running(&self, profile: &Profile) -> bool {
+        if self.offline {
+            return false;
+        }
+        if let Some(running) = self.running.get() {
+            return running;
+        }
+        let running = Node::from_profile(profile).is_running();
+        self.running.set(Some(running));
+
+        running
+    }
 }
 
 impl Context for Profile {
@@ -53,7 +121,7 @@ where
 pub fn run_command<A, C>(help: Help, action: &str, cmd: C) -> !
 where
     A: Args,
-    C: Command<A, fn() -> anyhow::Result<Profile>>,
+    C: Command<A, DefaultContext>,
 {
     let args = std::env::args_os().skip(1).collect();
 
@@ -63,7 +131,7 @@ where
 pub fn run_command_args<A, C>(help: Help, action: &str, cmd: C, args: Vec<OsString>) -> !
 where
     A: Args,
-    C: Command<A, fn() -> anyhow::Result<Profile>>,
+    C: Command<A, DefaultContext>,
 {
     use io as term;
 
@@ -102,7 +170,7 @@ where
         }
     };
 
-    match cmd.run(options, self::profile) {
+    match cmd.run(options, DefaultContext::from_env()) {
         Ok(()) => process::exit(0),
         Err(err) => {
             terminal::fail(&format!("{action} failed"), &err);
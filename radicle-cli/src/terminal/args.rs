@@ -6,7 +6,7 @@ use anyhow::anyhow;
 
 use radicle::cob::{self, issue, patch};
 use radicle::crypto;
-use radicle::git::RefString;
+use radicle::git::{PatternString, RefString};
 use radicle::node::{Address, Alias};
 use radicle::prelude::{Did, Id, NodeId};
 
@@ -93,6 +93,20 @@ pub fn refstring(flag: &str, value: OsString) -> anyhow::Result<RefString> {
     })
 }
 
+pub fn pattern(flag: &str, value: OsString) -> anyhow::Result<PatternString> {
+    PatternString::try_from(
+        value
+            .into_string()
+            .map_err(|_| anyhow!("the value specified for '--{}' is not valid UTF-8", flag))?,
+    )
+    .map_err(|_| {
+        anyhow!(
+            "the value specified for '--{}' is not a valid ref pattern",
+            flag
+        )
+    })
+}
+
 pub fn did(val: &OsString) -> anyhow::Result<Did> {
     let val = val.to_string_lossy();
     let Ok(peer) = Did::from_str(&val) else {
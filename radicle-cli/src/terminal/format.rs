@@ -75,6 +75,23 @@ pub fn strip_comments(s: &str) -> String {
     w.to_string()
 }
 
+/// Format a size in bytes, eg. `1.5 MiB`.
+pub fn bytes(size: u64) -> Paint<String> {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        Paint::new(format!("{size} {}", UNITS[unit]))
+    } else {
+        Paint::new(format!("{value:.1} {}", UNITS[unit]))
+    }
+}
+
 /// Format a timestamp.
 pub fn timestamp(time: &Timestamp) -> Paint<String> {
     let fmt = timeago::Formatter::new();
@@ -229,6 +246,14 @@ impl<'a> IntoIterator for Author<'a> {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_bytes() {
+        assert_eq!(bytes(0).to_string(), "0 B");
+        assert_eq!(bytes(1023).to_string(), "1023 B");
+        assert_eq!(bytes(1024).to_string(), "1.0 KiB");
+        assert_eq!(bytes(1536 * 1024).to_string(), "1.5 MiB");
+    }
+
     #[test]
     fn test_strip_comments() {
         let test = "\
@@ -297,6 +297,32 @@ fn rad_node() {
     .unwrap();
 }
 
+#[test]
+fn rad_node_doctor() {
+    let mut environment = Environment::new();
+    let mut profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    profile.config.node.external_addresses = [
+        "192.168.1.2:8776",
+        "seed.radicle.invalid:8776",
+        "1.1.1.1:443",
+    ]
+    .into_iter()
+    .map(|a| a.parse().unwrap())
+    .collect();
+    profile.config.write(&home.config()).unwrap();
+
+    test(
+        "examples/rad-node-doctor.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
 #[test]
 fn rad_patch() {
     let mut environment = Environment::new();
@@ -312,6 +338,66 @@ fn rad_patch() {
     test("examples/rad-patch.md", working.path(), Some(home), []).unwrap();
 }
 
+#[test]
+fn rad_patch_checkout() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test(
+        "examples/rad-patch-checkout.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
+#[test]
+fn rad_patch_checkout_update() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test(
+        "examples/rad-patch-checkout-update.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
+#[test]
+fn rad_patch_prune() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test(
+        "examples/rad-patch-prune.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
 #[test]
 fn rad_patch_update() {
     let mut environment = Environment::new();
@@ -1046,6 +1132,164 @@ fn rad_remote() {
     .unwrap();
 }
 
+#[test]
+fn rad_remote_sync() {
+    let mut environment = Environment::new();
+    let alice = environment.node(Config::test(Alias::new("alice")));
+    let bob = environment.node(Config::test(Alias::new("bob")));
+    let eve = environment.node(Config::test(Alias::new("eve")));
+    let working = environment.tmp().join("working");
+    let home = alice.home.clone();
+    let rid = Id::from_str("z42hL2jL4XNk6K8oHQaSWfMgCL7ji").unwrap();
+    // Setup a test repository.
+    fixtures::repository(working.join("alice"));
+
+    test(
+        "examples/rad-init.md",
+        working.join("alice"),
+        Some(&home),
+        [],
+    )
+    .unwrap();
+
+    let alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.connect(&alice);
+    eve.connect(&alice);
+    bob.routes_to(&[(rid, alice.id)]);
+    eve.routes_to(&[(rid, alice.id)]);
+    bob.rad("clone", &[rid.to_string().as_str()], working.join("bob"))
+        .unwrap();
+    eve.rad("clone", &[rid.to_string().as_str()], working.join("eve"))
+        .unwrap();
+
+    alice.routes_to(&[(rid, alice.id), (rid, bob.id), (rid, eve.id)]);
+
+    test(
+        "examples/rad-remote-sync.md",
+        working.join("alice"),
+        Some(&home),
+        [],
+    )
+    .unwrap();
+}
+
+#[test]
+fn rad_remote_status() {
+    logger::init(log::Level::Debug);
+
+    let mut environment = Environment::new();
+    let alice = environment.node(Config::test(Alias::new("alice")));
+    let bob = environment.node(Config::test(Alias::new("bob")));
+    let eve = environment.node(Config::test(Alias::new("eve")));
+    let working = environment.tmp().join("working");
+
+    fixtures::repository(working.join("alice"));
+
+    let alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.connect(&alice).converge([&alice]);
+    eve.connect(&alice).converge([&alice]);
+
+    formula(&environment.tmp(), "examples/rad-remote-status.md")
+        .unwrap()
+        .home(
+            "alice",
+            working.join("alice"),
+            [("RAD_HOME", alice.home.path().display())],
+        )
+        .home(
+            "bob",
+            bob.home.path(),
+            [("RAD_HOME", bob.home.path().display())],
+        )
+        .home(
+            "eve",
+            eve.home.path(),
+            [("RAD_HOME", eve.home.path().display())],
+        )
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn rad_remote_add_unknown() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test(
+        "examples/rad-remote-add-unknown.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
+#[test]
+fn rad_offline() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test("examples/rad-offline.md", working.path(), Some(home), []).unwrap();
+}
+
+#[test]
+fn rad_remote_add_delegates() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test(
+        "examples/rad-remote-add-delegates.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
+#[test]
+fn rad_remote_storage() {
+    let mut environment = Environment::new();
+    let profile = environment.profile("alice");
+    let working = tempfile::tempdir().unwrap();
+    let home = &profile.home;
+
+    // Setup a test repository.
+    fixtures::repository(working.path());
+
+    test("examples/rad-init.md", working.path(), Some(home), []).unwrap();
+    test(
+        "examples/rad-remote-storage.md",
+        working.path(),
+        Some(home),
+        [],
+    )
+    .unwrap();
+}
+
 #[test]
 fn rad_merge_via_push() {
     logger::init(log::Level::Debug);
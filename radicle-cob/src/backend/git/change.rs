@@ -1,6 +1,6 @@
 // Copyright © 2022 The Radicle Link Contributors
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::convert::TryFrom;
 use std::path::PathBuf;
 
@@ -15,7 +15,7 @@ use crate::change::store::Version;
 use crate::signatures;
 use crate::{
     change,
-    change::{store, Contents, Entry, Timestamp},
+    change::{store, Contents, Entry, Limits, Timestamp},
     signatures::{ExtendedSignature, Signatures},
     trailers, Embed,
 };
@@ -33,10 +33,13 @@ pub mod error {
     use git_ext::Oid;
     use thiserror::Error;
 
+    use crate::change::LimitExceeded;
     use crate::signatures::error::Signatures;
 
     #[derive(Debug, Error)]
     pub enum Create {
+        #[error(transparent)]
+        Limit(#[from] LimitExceeded),
         #[error(transparent)]
         WriteCommit(#[from] commit::error::Write),
         #[error(transparent)]
@@ -82,6 +85,20 @@ pub mod error {
         #[error("non utf-8 characters in commit message")]
         Utf8(#[from] FromUtf8Error),
     }
+
+    #[derive(Debug, Error)]
+    pub enum Check {
+        #[error(transparent)]
+        Read(#[from] commit::error::Read),
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+        #[error("the change '{id}' exceeds the entry limits: {err}")]
+        Limit {
+            id: Oid,
+            #[source]
+            err: LimitExceeded,
+        },
+    }
 }
 
 impl change::Storage for git2::Repository {
@@ -109,6 +126,8 @@ impl change::Storage for git2::Repository {
             embeds,
             contents,
         } = spec;
+        Limits::default().check(&contents)?;
+
         let manifest = store::Manifest::new(type_name, Version::default());
         let revision = write_manifest(self, &manifest, embeds, &contents)?;
         let tree = self.find_tree(revision)?;
@@ -184,6 +203,51 @@ impl change::Storage for git2::Repository {
     }
 }
 
+/// Check the contents of the change with the given id, and of the changes it depends on,
+/// against the given limits. Only the headers of the content blobs are read, so oversized
+/// contents are never loaded into memory.
+///
+/// Changes for which `known` returns `true` are assumed to be valid, and neither checked
+/// nor traversed.
+pub fn check_limits(
+    repo: &git2::Repository,
+    id: Oid,
+    limits: &Limits,
+    known: impl Fn(&Oid) -> bool,
+) -> Result<(), error::Check> {
+    let odb = repo.odb()?;
+    let mut visited = HashSet::new();
+    let mut queue = vec![id];
+
+    while let Some(id) = queue.pop() {
+        if known(&id) || !visited.insert(id) {
+            continue;
+        }
+        let commit = Commit::read(repo, id.into())?;
+        // Commits without a resource trailer, eg. code commits referenced by a change,
+        // aren't changes.
+        let Ok(resource) = parse_resource_trailer(commit.trailers()) else {
+            continue;
+        };
+        let tree = repo.find_tree(commit.tree())?;
+        let sizes = tree
+            .iter()
+            .filter(|entry| {
+                entry.kind() == Some(git2::ObjectType::Blob)
+                    && entry.name().map_or(false, |n| n.parse::<usize>().is_ok())
+            })
+            .map(|entry| odb.read_header(entry.id()).map(|(size, _)| size))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        limits
+            .check_sizes(sizes)
+            .map_err(|err| error::Check::Limit { id, err })?;
+
+        queue.extend(commit.parents().map(Oid::from).filter(|p| *p != resource));
+    }
+    Ok(())
+}
+
 fn parse_resource_trailer<'a>(
     trailers: impl Iterator<Item = &'a OwnedTrailer>,
 ) -> Result<Oid, error::Load> {
@@ -3,7 +3,7 @@
 use git_ext::Oid;
 
 pub mod store;
-pub use store::{Contents, EntryId, Storage, Template, Timestamp};
+pub use store::{Contents, EntryId, LimitExceeded, Limits, Storage, Template, Timestamp};
 
 use crate::signatures::ExtendedSignature;
 
@@ -5,6 +5,7 @@ use std::{error::Error, fmt, num::NonZeroUsize};
 use nonempty::NonEmpty;
 use radicle_git_ext::Oid;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::{signatures, TypeName};
 
@@ -53,6 +54,91 @@ pub struct Template<Id> {
 /// This is the change payload.
 pub type Contents = NonEmpty<Vec<u8>>;
 
+/// Maximum size of a single entry blob, in bytes.
+pub const MAX_BLOB_SIZE: usize = 4 * 1024 * 1024;
+/// Maximum total size of the blobs of an entry, in bytes.
+pub const MAX_ENTRY_SIZE: usize = 16 * 1024 * 1024;
+/// Maximum number of blobs in an entry. Blobs are named after their index, which is
+/// loaded as an `i8`, so entries can't have more blobs than this anyway.
+pub const MAX_ENTRY_BLOBS: usize = 128;
+
+/// Limits on the contents of an entry.
+///
+/// Entries are replicated to every node tracking a repository, and loaded into memory
+/// when the history of their object is evaluated, so their size must be bounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum size of a single blob, in bytes.
+    pub max_blob_size: usize,
+    /// Maximum total size of the blobs of an entry, in bytes.
+    pub max_entry_size: usize,
+    /// Maximum number of blobs in an entry.
+    pub max_blobs: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_blob_size: MAX_BLOB_SIZE,
+            max_entry_size: MAX_ENTRY_SIZE,
+            max_blobs: MAX_ENTRY_BLOBS,
+        }
+    }
+}
+
+impl Limits {
+    /// Check entry contents against these limits.
+    pub fn check(&self, contents: &Contents) -> Result<(), LimitExceeded> {
+        self.check_sizes(contents.iter().map(|blob| blob.len()))
+    }
+
+    /// Check the sizes of the blobs of an entry against these limits.
+    pub fn check_sizes(&self, sizes: impl IntoIterator<Item = usize>) -> Result<(), LimitExceeded> {
+        let mut blobs = 0;
+        let mut total = 0;
+
+        for (index, size) in sizes.into_iter().enumerate() {
+            if size > self.max_blob_size {
+                return Err(LimitExceeded::BlobSize {
+                    index,
+                    size,
+                    max: self.max_blob_size,
+                });
+            }
+            blobs += 1;
+            total += size;
+        }
+        if blobs > self.max_blobs {
+            return Err(LimitExceeded::Blobs {
+                count: blobs,
+                max: self.max_blobs,
+            });
+        }
+        if total > self.max_entry_size {
+            return Err(LimitExceeded::EntrySize {
+                size: total,
+                max: self.max_entry_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// An entry [`Limits`] was exceeded.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LimitExceeded {
+    #[error("entry blob {index} is {size} bytes, the maximum is {max} bytes")]
+    BlobSize {
+        index: usize,
+        size: usize,
+        max: usize,
+    },
+    #[error("entry is {size} bytes, the maximum is {max} bytes")]
+    EntrySize { size: usize, max: usize },
+    #[error("entry has {count} blobs, the maximum is {max}")]
+    Blobs { count: usize, max: usize },
+}
+
 /// Local time in seconds since epoch.
 pub type Timestamp = u64;
 
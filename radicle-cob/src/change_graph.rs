@@ -7,8 +7,8 @@ use git_ext::Oid;
 use radicle_dag::Dag;
 
 use crate::{
-    change, history::EntryId, object, signatures::ExtendedSignature, CollaborativeObject, Entry,
-    History, ObjectId, TypeName,
+    change, history::EntryId, object, object::Rejected, object::Verification,
+    signatures::ExtendedSignature, CollaborativeObject, Entry, History, ObjectId, TypeName,
 };
 
 /// The graph of changes for a particular collaborative object
@@ -79,9 +79,12 @@ impl ChangeGraph {
         builder.build(*oid)
     }
 
-    /// Given a graph evaluate it to produce a collaborative object. This will
-    /// filter out branches of the graph which do not have valid signatures.
-    pub(crate) fn evaluate(self) -> CollaborativeObject {
+    /// Given a graph evaluate it to produce a collaborative object. Unless verification
+    /// is skipped, this will filter out branches of the graph which do not have valid
+    /// signatures, and record the offending entries in [`CollaborativeObject::rejected`].
+    ///
+    /// Returns `None` if the root of the object itself doesn't have a valid signature.
+    pub(crate) fn evaluate(self, verification: Verification) -> Option<CollaborativeObject> {
         let root = *self.object_id;
         let root_node = self
             .graph
@@ -89,11 +92,22 @@ impl ChangeGraph {
             .expect("ChangeGraph::evaluate: root must be part of change graph");
 
         let manifest = root_node.manifest.clone();
+        let mut rejected = Vec::new();
         let graph = self
             .graph
             .fold(&root, Dag::new(), |mut graph, _, change, _| {
                 // Check the change signatures are valid.
-                if !change.valid_signatures() {
+                if verification == Verification::Verify && !change.valid_signatures() {
+                    log::warn!(
+                        "rejecting change '{}' of object '{}': invalid signature for author {}",
+                        change.id,
+                        self.object_id,
+                        change.author()
+                    );
+                    rejected.push(Rejected {
+                        id: change.id,
+                        author: *change.author(),
+                    });
                     return ControlFlow::Break(graph);
                 }
                 let entry = change.value.clone();
@@ -110,11 +124,15 @@ impl ChangeGraph {
                 ControlFlow::Continue(graph)
             });
 
-        CollaborativeObject {
+        if !graph.contains(&root) {
+            return None;
+        }
+        Some(CollaborativeObject {
             manifest,
             history: History::new((*root).into(), graph),
             id: self.object_id,
-        }
+            rejected,
+        })
     }
 
     /// Get the tips of the collaborative object
@@ -4,9 +4,21 @@ use std::{cmp::Ordering, collections::BTreeSet, ops::ControlFlow};
 
 use git_ext::Oid;
 use radicle_dag::Dag;
+use thiserror::Error;
 
 pub use crate::change::{Contents, Entry, EntryId, Timestamp};
 
+/// Error computing the difference between two history entries.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DiffError {
+    /// The entry is not part of the history.
+    #[error("entry {0} not found in history")]
+    NotFound(EntryId),
+    /// The entries are on divergent branches of the history.
+    #[error("entry {from} is not an ancestor of {to}")]
+    Diverged { from: EntryId, to: EntryId },
+}
+
 /// The DAG of changes making up the history of a collaborative object.
 #[derive(Clone, Debug)]
 pub struct History {
@@ -80,6 +92,61 @@ impl History {
             .map(|node| &node.value)
     }
 
+    /// Return the entries reachable from `to` but not from `from`, ie. the entries
+    /// added to the history since `from` was the latest entry.
+    ///
+    /// Entries are returned in topological order (parents before children), with
+    /// concurrent entries ordered by timestamp. Entries merged in from other branches
+    /// are included, as long as they aren't reachable from `from`.
+    ///
+    /// If `from` is not an ancestor of `to`, the histories have diverged and
+    /// [`DiffError::Diverged`] is returned; diffing from the common ancestor is then
+    /// left to the caller.
+    pub fn diff(&self, from: &EntryId, to: &EntryId) -> Result<Vec<&Entry>, DiffError> {
+        for id in [from, to] {
+            if !self.graph.contains(id) {
+                return Err(DiffError::NotFound(*id));
+            }
+        }
+        let known = self.graph.ancestors(from);
+        let ancestors = self.graph.ancestors(to);
+
+        if !ancestors.contains(from) {
+            return Err(DiffError::Diverged {
+                from: *from,
+                to: *to,
+            });
+        }
+        Ok(self
+            .by_time()
+            .filter(|e| ancestors.contains(e.id()) && !known.contains(e.id()))
+            .collect())
+    }
+
+    /// Return all the entries that are not reachable from `from`, ie. the entries
+    /// added to the history since `from` was the latest entry, across all tips.
+    ///
+    /// Entries are ordered like in [`History::diff`]. Unlike [`History::diff`], this
+    /// never fails with [`DiffError::Diverged`].
+    pub fn since(&self, from: &EntryId) -> Result<Vec<&Entry>, DiffError> {
+        if !self.graph.contains(from) {
+            return Err(DiffError::NotFound(*from));
+        }
+        let known = self.graph.ancestors(from);
+
+        Ok(self.by_time().filter(|e| !known.contains(e.id())).collect())
+    }
+
+    /// Topologically sorted entries, with concurrent entries ordered by timestamp.
+    fn by_time(&self) -> impl Iterator<Item = &Entry> {
+        self.sorted(|a, b| {
+            self.graph[a]
+                .timestamp
+                .cmp(&self.graph[b].timestamp)
+                .then(a.cmp(b))
+        })
+    }
+
     /// Extend this history with a new entry.
     pub fn extend(&mut self, change: Entry) {
         let tips = self.tips();
@@ -115,3 +182,127 @@ impl History {
             .expect("History::root: the root entry must be present in the graph")
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use nonempty::NonEmpty;
+    use radicle_crypto::test::signer::MockSigner;
+    use radicle_crypto::Signer as _;
+
+    use super::*;
+    use crate::change::store::{Manifest, Version};
+    use crate::signatures::ExtendedSignature;
+
+    fn entry(id: u8, timestamp: Timestamp) -> Entry {
+        let signer = MockSigner::from_seed([id + 1; 32]);
+        let oid = Oid::try_from([id; 20].as_slice()).unwrap();
+
+        Entry {
+            id: oid,
+            revision: oid,
+            signature: ExtendedSignature::new(*signer.public_key(), signer.sign(&[id])),
+            resource: oid,
+            parents: vec![],
+            manifest: Manifest::new("xyz.test".parse().unwrap(), Version::default()),
+            contents: NonEmpty::new(vec![id]),
+            timestamp,
+        }
+    }
+
+    fn oid(id: u8) -> EntryId {
+        Oid::try_from([id; 20].as_slice()).unwrap()
+    }
+
+    /// Builds the following history, where `D` forks from `A`, and `E` merges both branches:
+    ///
+    /// ```text
+    /// R <- A <- B <- C <- E
+    ///       \            /
+    ///        `-- D <----'
+    /// ```
+    fn history() -> History {
+        let mut graph = Dag::new();
+
+        for (id, timestamp) in [(0, 0), (1, 1), (2, 2), (3, 5), (4, 3), (5, 6)] {
+            graph.node(oid(id), entry(id, timestamp));
+        }
+        for (from, to) in [(1, 0), (2, 1), (3, 2), (4, 1), (5, 3), (5, 4)] {
+            graph.dependency(oid(from), oid(to));
+        }
+        History::new(oid(0), graph)
+    }
+
+    fn ids(entries: Vec<&Entry>) -> Vec<EntryId> {
+        entries.into_iter().map(|e| *e.id()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let h = history();
+
+        assert_eq!(ids(h.diff(&oid(0), &oid(0)).unwrap()), vec![]);
+        assert_eq!(ids(h.diff(&oid(0), &oid(2)).unwrap()), vec![oid(1), oid(2)]);
+        assert_eq!(
+            ids(h.diff(&oid(1), &oid(5)).unwrap()),
+            vec![oid(2), oid(4), oid(3), oid(5)],
+            "Concurrent entries are ordered by timestamp"
+        );
+        assert_eq!(
+            ids(h.diff(&oid(3), &oid(5)).unwrap()),
+            vec![oid(4), oid(5)],
+            "Merged branches are included"
+        );
+        assert_eq!(
+            ids(h.diff(&oid(4), &oid(5)).unwrap()),
+            vec![oid(2), oid(3), oid(5)]
+        );
+    }
+
+    #[test]
+    fn test_since() {
+        let h = history();
+
+        assert_eq!(
+            h.since(&oid(3)).map(ids),
+            Ok(vec![oid(4), oid(5)]),
+            "Entries on other branches are included"
+        );
+        assert_eq!(h.since(&oid(5)).map(ids), Ok(vec![]));
+        assert_eq!(h.since(&oid(9)).map(ids), Err(DiffError::NotFound(oid(9))));
+    }
+
+    #[test]
+    fn test_diff_diverged() {
+        let h = history();
+
+        assert_eq!(
+            h.diff(&oid(3), &oid(4)).map(ids),
+            Err(DiffError::Diverged {
+                from: oid(3),
+                to: oid(4)
+            })
+        );
+        assert_eq!(
+            h.diff(&oid(5), &oid(1)).map(ids),
+            Err(DiffError::Diverged {
+                from: oid(5),
+                to: oid(1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff_not_found() {
+        let h = history();
+
+        assert_eq!(
+            h.diff(&oid(9), &oid(5)).map(ids),
+            Err(DiffError::NotFound(oid(9)))
+        );
+        assert_eq!(
+            h.diff(&oid(0), &oid(9)).map(ids),
+            Err(DiffError::NotFound(oid(9)))
+        );
+    }
+}
@@ -91,7 +91,8 @@ pub use type_name::TypeName;
 
 pub mod object;
 pub use object::{
-    create, get, info, list, remove, update, CollaborativeObject, Create, ObjectId, Update, Updated,
+    create, get, info, list, remove, update, CollaborativeObject, Create, ObjectId, Rejected,
+    Update, Updated, Verification,
 };
 
 #[cfg(test)]
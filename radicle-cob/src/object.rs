@@ -9,8 +9,8 @@ use thiserror::Error;
 
 pub mod collaboration;
 pub use collaboration::{
-    create, get, info, list, parse_refstr, remove, update, CollaborativeObject, Create, Update,
-    Updated,
+    create, get, info, list, parse_refstr, remove, update, CollaborativeObject, Create, Rejected,
+    Update, Updated, Verification,
 };
 
 pub mod storage;
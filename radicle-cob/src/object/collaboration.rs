@@ -5,6 +5,7 @@ use std::collections::BTreeSet;
 use git_ext::Oid;
 
 use crate::change::store::{Manifest, Version};
+use crate::history::EntryId;
 use crate::{change, History, ObjectId, TypeName};
 
 pub mod error;
@@ -35,6 +36,30 @@ pub struct CollaborativeObject {
     pub(crate) history: History,
     /// The id of the object
     pub(crate) id: ObjectId,
+    /// Entries that were excluded from the history, because they failed verification
+    pub(crate) rejected: Vec<Rejected>,
+}
+
+/// Whether the history entries of an object are verified when it is loaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// Verify that each entry is signed by its author. Entries that aren't are excluded
+    /// from the history, along with the entries that depend on them.
+    #[default]
+    Verify,
+    /// Don't verify entry signatures. This is faster, but should only be used when the
+    /// authorship of entries doesn't matter, eg. when counting objects.
+    Skip,
+}
+
+/// A history entry that was excluded from an object, because its signature
+/// could not be verified against its author's key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejected {
+    /// The entry that was rejected.
+    pub id: EntryId,
+    /// The author the entry claims to be from.
+    pub author: crypto::PublicKey,
 }
 
 impl CollaborativeObject {
@@ -54,6 +79,12 @@ impl CollaborativeObject {
         &self.manifest
     }
 
+    /// Entries that were excluded from the history because they failed verification.
+    /// Entries that depend on these are also excluded, but not listed here.
+    pub fn rejected(&self) -> &[Rejected] {
+        &self.rejected
+    }
+
     fn tips(&self) -> BTreeSet<Oid> {
         self.history.tips().into_iter().map(Oid::from).collect()
     }
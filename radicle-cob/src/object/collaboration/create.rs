@@ -80,5 +80,6 @@ where
         manifest: Manifest::new(type_name, version),
         history,
         id: object_id,
+        rejected: Vec::new(),
     })
 }
@@ -2,6 +2,8 @@
 
 use crate::{change_graph::ChangeGraph, CollaborativeObject, ObjectId, Store, TypeName};
 
+use super::Verification;
+
 use super::error;
 
 /// Get a [`CollaborativeObject`], if it exists.
@@ -13,10 +15,14 @@ use super::error;
 /// The `typename` is the type of object to be found, while the
 /// `object_id` is the identifier for the particular object under that
 /// type.
+///
+/// The `verification` determines whether entries that aren't signed by
+/// their author are excluded. See [`CollaborativeObject::rejected`].
 pub fn get<S, I>(
     storage: &S,
     typename: &TypeName,
     oid: &ObjectId,
+    verification: Verification,
 ) -> Result<Option<CollaborativeObject>, error::Retrieve>
 where
     S: Store<I>,
@@ -24,5 +30,6 @@ where
     let tip_refs = storage
         .objects(typename, oid)
         .map_err(|err| error::Retrieve::Refs { err: Box::new(err) })?;
-    Ok(ChangeGraph::load(storage, tip_refs.iter(), typename, oid).map(|graph| graph.evaluate()))
+    Ok(ChangeGraph::load(storage, tip_refs.iter(), typename, oid)
+        .and_then(|graph| graph.evaluate(verification)))
 }
@@ -2,6 +2,8 @@
 
 use crate::{change_graph::ChangeGraph, CollaborativeObject, Store, TypeName};
 
+use super::Verification;
+
 use super::error;
 
 /// List a set of [`CollaborativeObject`].
@@ -11,9 +13,13 @@ use super::error;
 /// [`Store`] for further information.
 ///
 /// The `typename` is the type of objects to be listed.
+///
+/// The `verification` determines whether entries that aren't signed by
+/// their author are excluded. See [`CollaborativeObject::rejected`].
 pub fn list<S, I>(
     storage: &S,
     typename: &TypeName,
+    verification: Verification,
 ) -> Result<Vec<CollaborativeObject>, error::Retrieve>
 where
     S: Store<I>,
@@ -26,7 +32,7 @@ where
     for (oid, tip_refs) in references {
         log::trace!("loading object '{}'", oid);
         let loaded = ChangeGraph::load(storage, tip_refs.iter(), typename, &oid)
-            .map(|graph| graph.evaluate());
+            .and_then(|graph| graph.evaluate(verification));
 
         match loaded {
             Some(obj) => {
@@ -8,7 +8,7 @@ use crate::{
     Store, TypeName,
 };
 
-use super::error;
+use super::{error, Verification};
 
 /// Result of an `update` operation.
 #[derive(Debug)]
@@ -79,7 +79,7 @@ where
         .map_err(|err| error::Update::Refs { err: Box::new(err) })?;
 
     let mut object = ChangeGraph::load(storage, existing_refs.iter(), typename, &object_id)
-        .map(|graph| graph.evaluate())
+        .and_then(|graph| graph.evaluate(Verification::Verify))
         .ok_or(error::Update::NoSuchObject)?;
 
     let change = storage.store(
@@ -134,11 +134,12 @@ impl object::Storage for Storage {
             let r = r?;
             let name = r.name().unwrap();
             println!("NAME: {name}");
-            let oid = r
-                .target()
-                .map(ObjectId::from)
-                .expect("BUG: the cob references should be direct");
             if name.contains(typename.as_str()) {
+                let oid = name
+                    .rsplit('/')
+                    .next()
+                    .and_then(|oid| oid.parse::<ObjectId>().ok())
+                    .expect("BUG: the cob references should end with the object id");
                 let reference = Reference::try_from(r)?;
                 objects
                     .entry(oid)
@@ -1,14 +1,15 @@
 use std::ops::ControlFlow;
 
-use crypto::test::signer::MockSigner;
+use crypto::test::signer::{Forger, MockSigner};
 use git_ext::ref_format::{refname, Component, RefString};
-use nonempty::nonempty;
+use nonempty::{nonempty, NonEmpty};
 use qcheck::Arbitrary;
 use radicle_crypto::Signer;
 
+use crate::change::{store, LimitExceeded, Limits};
 use crate::{
-    create, get, list, object, test::arbitrary::Invalid, update, Create, ObjectId, TypeName,
-    Update, Updated, Version,
+    create, get, git, list, object, test::arbitrary::Invalid, update, Create, ObjectId, Rejected,
+    TypeName, Update, Updated, Verification, Version,
 };
 
 use super::test;
@@ -40,7 +41,7 @@ fn roundtrip() {
     )
     .unwrap();
 
-    let expected = get(&storage, &typename, cob.id())
+    let expected = get(&storage, &typename, cob.id(), Verification::Verify)
         .unwrap()
         .expect("BUG: cob was missing");
 
@@ -90,7 +91,7 @@ fn list_cobs() {
     )
     .unwrap();
 
-    let mut expected = list(&storage, &typename).unwrap();
+    let mut expected = list(&storage, &typename, Verification::Verify).unwrap();
     expected.sort_by(|x, y| x.id().cmp(y.id()));
 
     let mut actual = vec![issue_1, issue_2];
@@ -126,7 +127,7 @@ fn update_cob() {
     )
     .unwrap();
 
-    let not_expected = get(&storage, &typename, cob.id())
+    let not_expected = get(&storage, &typename, cob.id(), Verification::Verify)
         .unwrap()
         .expect("BUG: cob was missing");
 
@@ -146,7 +147,7 @@ fn update_cob() {
     )
     .unwrap();
 
-    let expected = get(&storage, &typename, object.id())
+    let expected = get(&storage, &typename, object.id(), Verification::Verify)
         .unwrap()
         .expect("BUG: cob was missing");
 
@@ -154,6 +155,142 @@ fn update_cob() {
     assert_eq!(object, expected);
 }
 
+#[test]
+fn create_exceeding_limits() {
+    let storage = test::Storage::new();
+    let signer = gen::<MockSigner>(1);
+    let terry = test::Person::new(&storage, "terry", *signer.public_key()).unwrap();
+    let proj = test::Project::new(&storage, "discworld", *signer.public_key()).unwrap();
+    let proj = test::RemoteProject {
+        project: proj,
+        person: terry,
+    };
+    let typename = "xyz.rad.issue".parse::<TypeName>().unwrap();
+    let create = |contents| {
+        create(
+            &storage,
+            &signer,
+            proj.project.content_id,
+            vec![],
+            &proj.identifier(),
+            Create {
+                contents,
+                type_name: typename.clone(),
+                message: "creating xyz.rad.issue".to_string(),
+                embeds: vec![],
+                version: Version::default(),
+            },
+        )
+    };
+
+    let err = create(nonempty![vec![0; store::MAX_BLOB_SIZE + 1]]).unwrap_err();
+    assert!(matches!(
+        err,
+        object::collaboration::error::Create::CreateChange(git::change::error::Create::Limit(
+            LimitExceeded::BlobSize { index: 0, .. }
+        ))
+    ));
+
+    let blobs = NonEmpty::from_vec(vec![vec![]; store::MAX_ENTRY_BLOBS + 1]).unwrap();
+    let err = create(blobs).unwrap_err();
+    assert!(matches!(
+        err,
+        object::collaboration::error::Create::CreateChange(git::change::error::Create::Limit(
+            LimitExceeded::Blobs { .. }
+        ))
+    ));
+    assert!(list(&storage, &typename, Verification::Verify)
+        .unwrap()
+        .is_empty());
+
+    let blobs = NonEmpty::from_vec(vec![vec![]; store::MAX_ENTRY_BLOBS]).unwrap();
+    assert!(create(blobs).is_ok());
+}
+
+#[test]
+fn check_limits() {
+    let storage = test::Storage::new();
+    let signer = gen::<MockSigner>(1);
+    let terry = test::Person::new(&storage, "terry", *signer.public_key()).unwrap();
+    let proj = test::Project::new(&storage, "discworld", *signer.public_key()).unwrap();
+    let proj = test::RemoteProject {
+        project: proj,
+        person: terry,
+    };
+    let typename = "xyz.rad.issue".parse::<TypeName>().unwrap();
+    let cob = create(
+        &storage,
+        &signer,
+        proj.project.content_id,
+        vec![],
+        &proj.identifier(),
+        Create {
+            contents: nonempty!(b"issue 1".to_vec()),
+            type_name: typename,
+            message: "creating xyz.rad.issue".to_string(),
+            embeds: vec![],
+            version: Version::default(),
+        },
+    )
+    .unwrap();
+    let repo = storage.as_raw();
+    let root = **cob.id();
+
+    git::change::check_limits(repo, root, &Limits::default(), |_| false).unwrap();
+    assert!(matches!(
+        git::change::check_limits(
+            repo,
+            root,
+            &Limits {
+                max_blob_size: 1,
+                ..Limits::default()
+            },
+            |_| false
+        ),
+        Err(git::change::error::Check::Limit {
+            err: LimitExceeded::BlobSize { size: 7, .. },
+            ..
+        })
+    ));
+
+    // Write a change with too many blobs, bypassing the checks done when storing changes.
+    let oversized = {
+        let commit = repo.find_commit(*root).unwrap();
+        let mut tree = repo.treebuilder(Some(&commit.tree().unwrap())).unwrap();
+        let blob = repo.blob(b"").unwrap();
+
+        for ix in 0..=store::MAX_ENTRY_BLOBS {
+            tree.insert(ix.to_string(), blob, git2::FileMode::Blob.into())
+                .unwrap();
+        }
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        let author = commit.author();
+
+        repo.commit(
+            None,
+            &author,
+            &author,
+            commit.message().unwrap(),
+            &tree,
+            &[&commit],
+        )
+        .unwrap()
+    };
+    let err = git::change::check_limits(repo, oversized.into(), &Limits::default(), |_| false)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        git::change::error::Check::Limit { id, err: LimitExceeded::Blobs { .. } }
+            if *id == oversized
+    ));
+
+    // Known changes aren't checked.
+    git::change::check_limits(repo, oversized.into(), &Limits::default(), |id| {
+        **id == oversized
+    })
+    .unwrap();
+}
+
 #[test]
 fn traverse_cobs() {
     let storage = test::Storage::new();
@@ -230,6 +367,89 @@ fn traverse_cobs() {
     assert_eq!(contents, vec![b"issue 1".to_vec(), b"issue 2".to_vec()]);
 }
 
+#[test]
+fn forged_entries_are_rejected() {
+    let storage = test::Storage::new();
+    let mallory_signer = gen::<MockSigner>(2);
+    let mallory = test::Person::new(&storage, "mallory", *mallory_signer.public_key()).unwrap();
+    let terry_signer = gen::<MockSigner>(1);
+    let terry = test::Person::new(&storage, "pratchett", *terry_signer.public_key()).unwrap();
+    let proj = test::Project::new(&storage, "discworld", *terry_signer.public_key()).unwrap();
+    let terry_proj = test::RemoteProject {
+        project: proj.clone(),
+        person: terry,
+    };
+    let mallory_proj = test::RemoteProject {
+        project: proj,
+        person: mallory,
+    };
+    let typename = "xyz.rad.issue".parse::<TypeName>().unwrap();
+    let cob = create(
+        &storage,
+        &terry_signer,
+        terry_proj.project.content_id,
+        vec![],
+        &terry_proj.identifier(),
+        Create {
+            contents: nonempty!(b"issue 1".to_vec()),
+            type_name: typename.clone(),
+            message: "creating xyz.rad.issue".to_string(),
+            embeds: vec![],
+            version: Version::default(),
+        },
+    )
+    .unwrap();
+    copy_to(
+        storage.as_raw(),
+        &terry_proj,
+        &mallory_proj,
+        &typename,
+        *cob.id(),
+    )
+    .unwrap();
+
+    // Mallory writes a change in her namespace, attributed to Terry.
+    let forger = Forger::new(*terry_signer.public_key(), mallory_signer);
+    let Updated { head: forged, .. } = update(
+        &storage,
+        &forger,
+        mallory_proj.project.content_id,
+        vec![],
+        &mallory_proj.identifier(),
+        Update {
+            changes: nonempty!(b"issue 2".to_vec()),
+            object_id: *cob.id(),
+            type_name: typename.clone(),
+            embeds: vec![],
+            message: "commenting on xyz.rad.issue".to_string(),
+        },
+    )
+    .unwrap();
+
+    let object = get(&storage, &typename, cob.id(), Verification::Verify)
+        .unwrap()
+        .unwrap();
+    assert_eq!(object.history().len(), 1);
+    assert_eq!(
+        object.rejected(),
+        &[Rejected {
+            id: forged,
+            author: *terry_signer.public_key(),
+        }]
+    );
+
+    let objects = list(&storage, &typename, Verification::Verify).unwrap();
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].rejected(), object.rejected());
+
+    // Without verification, the forged change is part of the history.
+    let object = get(&storage, &typename, cob.id(), Verification::Skip)
+        .unwrap()
+        .unwrap();
+    assert_eq!(object.history().len(), 2);
+    assert!(object.rejected().is_empty());
+}
+
 #[quickcheck]
 fn parse_refstr(oid: ObjectId, typename: TypeName) {
     let suffix = refname!("refs/cobs")
@@ -72,6 +72,35 @@ impl Signer for MockSigner {
     }
 }
 
+/// Signs with its own key, while claiming to be someone else. Used to check that forged
+/// signatures are rejected.
+#[derive(Debug, Clone)]
+pub struct Forger {
+    claimed: PublicKey,
+    signer: MockSigner,
+}
+
+impl Forger {
+    /// Create a forger that signs with the given signer, while claiming the given key.
+    pub fn new(claimed: PublicKey, signer: MockSigner) -> Self {
+        Self { claimed, signer }
+    }
+}
+
+impl Signer for Forger {
+    fn public_key(&self) -> &PublicKey {
+        &self.claimed
+    }
+
+    fn sign(&self, msg: &[u8]) -> Signature {
+        self.signer.sign(msg)
+    }
+
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature, SignerError> {
+        self.signer.try_sign(msg)
+    }
+}
+
 #[cfg(feature = "cyphernet")]
 impl cyphernet::EcSk for MockSigner {
     type Pk = PublicKey;
@@ -124,6 +124,22 @@ impl<K: Ord + Copy, V> Dag<K, V> {
             .unwrap_or_default()
     }
 
+    /// Get the keys of all nodes the given node transitively depends on, including itself.
+    /// Returns an empty set if the node isn't in the graph.
+    pub fn ancestors(&self, key: &K) -> BTreeSet<K> {
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![*key];
+
+        while let Some(next) = stack.pop() {
+            if let Some(node) = self.graph.get(&next) {
+                if visited.insert(next) {
+                    stack.extend(node.dependencies.iter());
+                }
+            }
+        }
+        visited
+    }
+
     /// Get the graph's root nodes, ie. nodes which don't depend on other nodes.
     pub fn roots(&self) -> impl Iterator<Item = (&K, &Node<K, V>)> + '_ {
         self.roots
@@ -300,6 +316,25 @@ mod tests {
         assert!(!dag.has_dependency(&1, &0));
     }
 
+    #[test]
+    fn test_ancestors() {
+        let mut dag = Dag::new();
+
+        dag.node("R", ());
+        dag.node("A", ());
+        dag.node("B", ());
+        dag.node("C", ());
+        dag.dependency("A", "R");
+        dag.dependency("B", "R");
+        dag.dependency("C", "A");
+        dag.dependency("C", "B");
+
+        assert_eq!(dag.ancestors(&"R"), BTreeSet::from(["R"]));
+        assert_eq!(dag.ancestors(&"A"), BTreeSet::from(["A", "R"]));
+        assert_eq!(dag.ancestors(&"C"), BTreeSet::from(["A", "B", "C", "R"]));
+        assert!(dag.ancestors(&"D").is_empty());
+    }
+
     #[test]
     fn test_get() {
         let mut dag = Dag::new();
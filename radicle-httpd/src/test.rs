@@ -65,6 +65,7 @@ pub fn profile(home: &Path, seed: [u8; 32]) -> radicle::Profile {
         public_key: keypair.pk.into(),
         config: profile::Config {
             node: node::Config::new(node::Alias::new("seed")),
+            cli: profile::CliConfig::default(),
         },
     }
 }
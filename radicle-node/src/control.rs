@@ -2,22 +2,26 @@
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::LineWriter;
+#[cfg(unix)]
 use std::os::unix::net::UnixListener;
-use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{io, net, time};
 
-use radicle::node::Handle;
+use radicle::cob;
+use radicle::node::control::{Address, Endpoint, OperatorToken, Permission, Stream};
+use radicle::node::{Handle, Node};
 use serde_json as json;
 
 use crate::identity::Id;
 use crate::node::NodeId;
-use crate::node::{Command, CommandResult};
+use crate::node::{Command, CommandResult, ErrorCode, RequestError};
 use crate::runtime;
 use crate::runtime::thread;
+use crate::service;
 
-/// Maximum timeout for waiting for node events.
-const MAX_TIMEOUT: time::Duration = time::Duration::MAX;
+/// Maximum time a TCP control client has to authenticate.
+const AUTH_TIMEOUT: time::Duration = time::Duration::from_secs(3);
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -25,47 +29,202 @@ pub enum Error {
     Bind(io::Error),
     #[error("invalid socket path specified: {0}")]
     InvalidPath(PathBuf),
+    #[error("failed to write control endpoint file: {0}")]
+    Endpoint(io::Error),
+    #[error("control endpoint {0} is in use by a running node")]
+    AlreadyRunning(PathBuf),
     #[error("node: {0}")]
     Node(#[from] runtime::HandleError),
 }
 
-/// Listen for commands on the control socket, and process them.
-pub fn listen<H: Handle<Error = runtime::HandleError> + 'static>(
-    listener: UnixListener,
+/// A listener for control connections.
+pub trait Listener: Send + Sync {
+    /// Accept the next control connection.
+    fn accept(&self) -> io::Result<Box<dyn Stream>>;
+
+    /// Authenticate an accepted connection, before any command is read. This is called on
+    /// the connection's own thread, so that slow clients don't hold up other connections.
+    /// Connections that fail authentication are rejected with an error of kind
+    /// [`io::ErrorKind::PermissionDenied`].
+    fn authenticate(&self, _stream: &mut dyn Stream) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    fn accept(&self) -> io::Result<Box<dyn Stream>> {
+        UnixListener::accept(self).map(|(s, _)| Box::new(s) as Box<dyn Stream>)
+    }
+}
+
+impl<L: Listener + ?Sized> Listener for Box<L> {
+    fn accept(&self) -> io::Result<Box<dyn Stream>> {
+        self.as_ref().accept()
+    }
+
+    fn authenticate(&self, stream: &mut dyn Stream) -> io::Result<()> {
+        self.as_ref().authenticate(stream)
+    }
+}
+
+/// A control listener bound to a localhost TCP port. Clients must send the
+/// endpoint token on the first line, before any command.
+pub struct TcpListener {
+    listener: net::TcpListener,
+    token: String,
+}
+
+impl TcpListener {
+    /// Bind to an ephemeral localhost port, and write the resulting endpoint
+    /// to the given path, for clients to read. Fails if the endpoint at that path
+    /// belongs to a running node.
+    pub fn bind(path: &Path) -> Result<Self, Error> {
+        if path.exists() && Node::with_address(Address::Tcp(path.to_owned())).is_running() {
+            return Err(Error::AlreadyRunning(path.to_owned()));
+        }
+        let listener =
+            net::TcpListener::bind((net::Ipv4Addr::LOCALHOST, 0)).map_err(Error::Bind)?;
+        let addr = listener.local_addr().map_err(Error::Bind)?;
+        let endpoint = Endpoint::new(addr);
+
+        endpoint.write(path).map_err(Error::Endpoint)?;
+
+        Ok(Self {
+            listener,
+            token: endpoint.token,
+        })
+    }
+
+    /// Get the local address of the listener.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept(&self) -> io::Result<Box<dyn Stream>> {
+        let (stream, _) = self.listener.accept()?;
+
+        Ok(Box::new(stream))
+    }
+
+    fn authenticate(&self, stream: &mut dyn Stream) -> io::Result<()> {
+        let deadline = time::Instant::now() + AUTH_TIMEOUT;
+
+        // Nb. We read the token byte by byte, to not consume any of the command that follows.
+        // Each read is bounded by the time left, so that clients can't keep us waiting by
+        // trickling bytes in.
+        let mut token = Vec::new();
+        let mut byte = [0u8; 1];
+        while token.len() <= self.token.len() {
+            let remaining = deadline.saturating_duration_since(time::Instant::now());
+            if remaining.is_zero() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            stream.set_read_timeout(Some(remaining))?;
+            stream.read_exact(&mut byte)?;
+
+            if byte[0] == b'\n' {
+                break;
+            }
+            token.push(byte[0]);
+        }
+        if !constant_time_eq(&token, self.token.as_bytes()) {
+            CommandResult::error_code(
+                ErrorCode::Unauthorized,
+                io::Error::new(io::ErrorKind::PermissionDenied, "invalid control token"),
+            )
+            .to_writer(&mut *stream)
+            .ok();
+            // Nb. Drain what the client sent before closing, since closing a socket with
+            // unread data resets the connection, and the client may not see our response.
+            drain(stream, deadline);
+
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        stream.set_read_timeout(None)?;
+
+        Ok(())
+    }
+}
+
+/// Compare two byte strings in time that only depends on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Read and discard what's left to read on a stream, until the deadline.
+fn drain(stream: &mut dyn Stream, deadline: time::Instant) {
+    let mut buf = [0u8; 256];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(time::Instant::now());
+        if remaining.is_zero() || stream.set_read_timeout(Some(remaining)).is_err() {
+            break;
+        }
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => continue,
+        }
+    }
+}
+
+/// Listen for commands on the control socket, and process them. Event subscriptions
+/// are sent a heartbeat after being idle for the given duration. Clients that send the
+/// given operator token are granted [`Permission::Operator`], others [`Permission::User`].
+pub fn listen<L: Listener + 'static, H: Handle<Error = runtime::HandleError> + 'static>(
+    listener: L,
     handle: H,
+    heartbeat: time::Duration,
+    operator: Option<OperatorToken>,
 ) -> Result<(), Error>
 where
     H::Sessions: serde::Serialize,
 {
     log::debug!(target: "control", "Control thread listening on socket..");
     let nid = handle.nid()?;
+    let listener = Arc::new(listener);
 
-    for incoming in listener.incoming() {
-        match incoming {
+    loop {
+        match listener.accept() {
             Ok(mut stream) => {
                 let handle = handle.clone();
+                let listener = listener.clone();
+                let operator = operator.clone();
 
                 thread::spawn(&nid, "control", move || {
-                    if let Err(e) = command(&stream, handle) {
+                    if let Err(e) = listener.authenticate(stream.as_mut()) {
+                        if e.kind() == io::ErrorKind::PermissionDenied {
+                            log::warn!(target: "control", "Rejected unauthenticated control connection");
+                        } else {
+                            log::debug!(target: "control", "Failed to authenticate control connection: {e}");
+                        }
+                        stream.shutdown().ok();
+
+                        return;
+                    }
+                    if let Err(e) = command(stream.as_mut(), handle, heartbeat, operator.as_ref()) {
                         log::error!(target: "control", "Command returned error: {e}");
 
-                        CommandResult::error(e).to_writer(&mut stream).ok();
+                        CommandResult::error_code(e.code(), e)
+                            .to_writer(&mut stream)
+                            .ok();
 
                         stream.flush().ok();
-                        stream.shutdown(net::Shutdown::Both).ok();
+                        stream.shutdown().ok();
                     }
                 });
             }
             Err(e) => log::error!(target: "control", "Failed to accept incoming connection: {}", e),
         }
     }
-    log::debug!(target: "control", "Exiting control loop..");
-
-    Ok(())
 }
 
 #[derive(thiserror::Error, Debug)]
 enum CommandError {
+    #[error(transparent)]
+    Request(#[from] RequestError),
     #[error("(de)serialization failed: {0}")]
     Serialization(#[from] json::Error),
     #[error("runtime error: {0}")]
@@ -74,22 +233,77 @@ enum CommandError {
     Io(#[from] io::Error),
 }
 
+impl CommandError {
+    /// Get the error code to respond with.
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Request(e) => e.code,
+            Self::Runtime(runtime::HandleError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                ErrorCode::NotFound
+            }
+            Self::Runtime(runtime::HandleError::Command(service::CommandError::Limit(_))) => {
+                ErrorCode::InvalidArgument
+            }
+            Self::Runtime(runtime::HandleError::Command(
+                service::CommandError::NothingToAnnounce(_),
+            )) => ErrorCode::NothingToAnnounce,
+            Self::Runtime(runtime::HandleError::Command(
+                service::CommandError::TooManyFetches(_),
+            )) => ErrorCode::TooManyFetches,
+            Self::Runtime(runtime::HandleError::Command(service::CommandError::NotTracked(_))) => {
+                ErrorCode::NotTracked
+            }
+            Self::Runtime(runtime::HandleError::Command(
+                service::CommandError::InvalidAddress(_),
+            )) => ErrorCode::InvalidAddress,
+            Self::Runtime(runtime::HandleError::Cob(cob::store::Error::NotFound(..))) => {
+                ErrorCode::NotFound
+            }
+            Self::Runtime(runtime::HandleError::Cob(
+                cob::store::Error::InvalidAction(_)
+                | cob::store::Error::NoActions
+                | cob::store::Error::UnsupportedType(_)
+                | cob::store::Error::Apply(_),
+            )) => ErrorCode::InvalidArgument,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
 fn command<H: Handle<Error = runtime::HandleError> + 'static>(
-    stream: &UnixStream,
+    stream: &mut dyn Stream,
     mut handle: H,
+    heartbeat: time::Duration,
+    operator: Option<&OperatorToken>,
 ) -> Result<(), CommandError>
 where
     H::Sessions: serde::Serialize,
 {
-    let mut reader = BufReader::new(stream);
+    let mut reader = BufReader::new(stream.try_clone()?);
     let mut writer = LineWriter::new(stream);
     let mut line = String::new();
+    let mut permission = Permission::User;
 
     reader.read_line(&mut line)?;
+
+    // Commands are JSON objects, so any other line is an operator token.
+    if !line.starts_with('{') {
+        let token = line.trim_end().as_bytes();
+        if !operator.map_or(false, |o| constant_time_eq(token, o.as_str().as_bytes())) {
+            return Err(
+                RequestError::new(ErrorCode::Unauthorized, "invalid operator token").into(),
+            );
+        }
+        permission = Permission::Operator;
+
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
     let input = line.trim_end();
 
     log::debug!(target: "control", "Received `{input}` on control socket");
-    let cmd: Command = json::from_str(input)?;
+    let cmd = Command::parse(input)?;
+    cmd.authorize(permission)?;
 
     match cmd {
         Command::Connect { addr, opts } => {
@@ -102,20 +316,138 @@ where
                 }
             }
         }
-        Command::Fetch { rid, nid } => {
-            fetch(rid, nid, writer, &mut handle)?;
+        Command::Fetch { rid, nid, minimal } => {
+            fetch(rid, nid, minimal, writer, &mut handle)?;
         }
         Command::Seeds { rid } => {
             let seeds = handle.seeds(rid)?;
 
             json::to_writer(writer, &seeds)?;
         }
+        Command::RepoSize { rid, refresh } => {
+            let stats = handle.repo_size(rid, refresh)?;
+
+            json::to_writer(writer, &stats)?;
+        }
+        Command::GossipStats => {
+            let stats = handle.gossip_stats()?;
+
+            json::to_writer(writer, &stats)?;
+        }
+        Command::Metrics => {
+            let metrics = handle.metrics()?;
+
+            json::to_writer(writer, &metrics)?;
+        }
+        Command::Recovery => {
+            let recovery = handle.recovery()?;
+
+            json::to_writer(writer, &recovery)?;
+        }
+        Command::Explain { rid, nid } => {
+            let explanation = handle.explain(rid, nid)?;
+
+            json::to_writer(writer, &explanation)?;
+        }
+        Command::FetchHistory { rid } => {
+            let history = handle.fetch_history(rid)?;
+
+            json::to_writer(writer, &history)?;
+        }
+        Command::EventsSince { seq } => {
+            let page = handle.events_since(seq)?;
+
+            json::to_writer(writer, &page)?;
+        }
+        Command::Activity { since, limit } => {
+            for entry in handle.activity(since, limit)? {
+                let entry = json::to_string(&entry)?;
+
+                writeln!(&mut writer, "{entry}")?;
+            }
+        }
+        Command::Inspect { rid } => {
+            let inspection = handle.inspect(rid)?;
+
+            json::to_writer(writer, &inspection)?;
+        }
+        Command::GetConfig => {
+            let config = handle.config()?;
+
+            json::to_writer(writer, &config)?;
+        }
+        Command::SetLimit { name, value } => {
+            handle.set_limit(&name, value)?;
+
+            CommandResult::ok().to_writer(writer)?;
+        }
+        Command::AddPeer { peer } => {
+            let updated = handle.add_peer(peer)?;
+
+            CommandResult::Okay { updated }.to_writer(writer)?;
+        }
+        Command::RemovePeer { nid } => {
+            let updated = handle.remove_peer(nid)?;
+
+            CommandResult::Okay { updated }.to_writer(writer)?;
+        }
         Command::Sessions => {
             let sessions = handle.sessions()?;
 
             json::to_writer(writer, &sessions)?;
         }
-        Command::TrackRepo { rid, scope } => match handle.track_repo(rid, scope) {
+        Command::TrackRepo {
+            rid,
+            scope,
+            dry_run: true,
+            ..
+        } => {
+            let summary = handle.track_repo_dry_run(rid, scope)?;
+
+            json::to_writer(writer, &summary)?;
+        }
+        Command::TrackRepo {
+            rid,
+            scope,
+            dry_run: false,
+            fetch: true,
+            filter,
+            ..
+        } => {
+            // Set the filter first, so that it applies to the initial fetch.
+            let filtered = match filter {
+                Some(filter) => handle.track_repo_filter(rid, scope, filter)?,
+                None => false,
+            };
+            let mut tracked = handle.track_repo_fetch(rid, scope)?;
+            tracked.updated |= filtered;
+
+            json::to_writer(writer, &tracked)?;
+        }
+        Command::TrackRepo {
+            rid,
+            scope,
+            dry_run: false,
+            fetch: false,
+            origin,
+            filter,
+        } => {
+            let result = handle
+                .track_repo_origin(rid, scope, origin)
+                .and_then(|updated| match filter {
+                    Some(filter) => Ok(handle.track_repo_filter(rid, scope, filter)? || updated),
+                    None => Ok(updated),
+                });
+            match result {
+                Ok(updated) => {
+                    CommandResult::Okay { updated }.to_writer(writer)?;
+                }
+                Err(e) => {
+                    return Err(CommandError::Runtime(e));
+                }
+            }
+        }
+        Command::UntrackRepo { rid } => match handle.untrack_repo(rid) {
             Ok(updated) => {
                 CommandResult::Okay { updated }.to_writer(writer)?;
             }
@@ -123,7 +455,15 @@ where
                 return Err(CommandError::Runtime(e));
             }
         },
-        Command::UntrackRepo { rid } => match handle.untrack_repo(rid) {
+        Command::WatchRepo { rid } => match handle.watch_repo(rid) {
+            Ok(updated) => {
+                CommandResult::Okay { updated }.to_writer(writer)?;
+            }
+            Err(e) => {
+                return Err(CommandError::Runtime(e));
+            }
+        },
+        Command::UnwatchRepo { rid } => match handle.unwatch_repo(rid) {
             Ok(updated) => {
                 CommandResult::Okay { updated }.to_writer(writer)?;
             }
@@ -147,6 +487,16 @@ where
                 return Err(CommandError::Runtime(e));
             }
         },
+        Command::ExportPolicies => {
+            let policies = handle.export_policies()?;
+
+            json::to_writer(writer, &policies)?;
+        }
+        Command::ImportPolicies { policies } => {
+            let summary = handle.import_policies(policies)?;
+
+            json::to_writer(writer, &summary)?;
+        }
         Command::AnnounceRefs { rid } => {
             if let Err(e) = handle.announce_refs(rid) {
                 return Err(CommandError::Runtime(e));
@@ -167,17 +517,45 @@ where
                 return Err(CommandError::Runtime(e));
             }
         },
-        Command::Subscribe => match handle.subscribe(MAX_TIMEOUT) {
+        Command::Subscribe => match handle.subscribe(heartbeat) {
             Ok(events) => {
                 for e in events {
-                    let event = e?;
-                    let event = serde_json::to_string(&event)?;
-
-                    writeln!(&mut writer, "{event}")?;
+                    match e {
+                        Ok(event) => {
+                            let event = serde_json::to_string(&event)?;
+
+                            writeln!(&mut writer, "{event}")?;
+                        }
+                        // Let the client know we're still here with an empty line. This also
+                        // lets us notice clients that went away while the node is quiet.
+                        Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                            writeln!(&mut writer)?;
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
                 }
             }
             Err(e) => log::error!(target: "control", "Error subscribing to events: {e}"),
         },
+        Command::ValidateRepo { rid } => {
+            for finding in handle.validate(rid)? {
+                let finding = json::to_string(&finding?)?;
+
+                writeln!(&mut writer, "{finding}")?;
+            }
+        }
+        Command::SyncAll { options } => {
+            for progress in handle.sync_all(options)? {
+                let progress = json::to_string(&progress?)?;
+
+                writeln!(&mut writer, "{progress}")?;
+            }
+        }
+        Command::CobUpdate { update } => {
+            let updated = handle.cob_update(update)?;
+
+            json::to_writer(writer, &updated)?;
+        }
         Command::Status => {
             CommandResult::ok().to_writer(writer).ok();
         }
@@ -201,10 +579,16 @@ where
 fn fetch<W: Write, H: Handle<Error = runtime::HandleError>>(
     id: Id,
     node: NodeId,
+    minimal: bool,
     mut writer: W,
     handle: &mut H,
 ) -> Result<(), CommandError> {
-    match handle.fetch(id, node) {
+    let result = if minimal {
+        handle.fetch_minimal(id, node)
+    } else {
+        handle.fetch(id, node)
+    };
+    match result {
         Ok(result) => {
             json::to_writer(&mut writer, &result)?;
         }
@@ -218,17 +602,25 @@ fn fetch<W: Write, H: Handle<Error = runtime::HandleError>>(
 #[cfg(test)]
 mod tests {
     use std::io::prelude::*;
+    #[cfg(unix)]
     use std::os::unix::net::UnixStream;
     use std::thread;
 
     use super::*;
     use crate::identity::Id;
+    use crate::node::control::{Address, Endpoint};
     use crate::node::Handle;
-    use crate::node::{Alias, Node, NodeId};
+    use crate::node::Node;
+    #[cfg(unix)]
+    use crate::node::{Alias, CobObject, CobUpdate, NodeId};
     use crate::service::tracking::Scope;
     use crate::test;
+    use crate::test::assert_matches;
+
+    const HEARTBEAT: time::Duration = time::Duration::from_secs(1);
 
     #[test]
+    #[cfg(unix)]
     fn test_control_socket() {
         let tmp = tempfile::tempdir().unwrap();
         let handle = test::handle::Handle::default();
@@ -239,7 +631,7 @@ mod tests {
         thread::spawn({
             let handle = handle.clone();
 
-            move || listen(listener, handle)
+            move || listen(listener, handle, HEARTBEAT, None)
         });
 
         for rid in &rids {
@@ -270,6 +662,60 @@ mod tests {
     }
 
     #[test]
+    #[cfg(unix)]
+    fn test_control_socket_legacy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let handle = test::handle::Handle::default();
+        let socket = tmp.path().join("alice.sock");
+        let rid = test::arbitrary::gen::<Id>(1);
+        let nid = test::arbitrary::gen::<NodeId>(1);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        thread::spawn({
+            let handle = handle.clone();
+
+            move || listen(listener, handle, HEARTBEAT, None)
+        });
+
+        let requests = [
+            (
+                json::json!({ "cmd": "announce-refs", "args": [rid.urn()] }),
+                json::json!({ "status": "ok" }),
+            ),
+            (
+                json::json!({ "cmd": "announce-refs", "args": ["rad:invalid"] }),
+                json::json!({ "status": "Error", "code": "invalid-rid" }),
+            ),
+            (
+                json::json!({ "type": "frobnicate" }),
+                json::json!({ "status": "Error", "code": "unknown-command" }),
+            ),
+            (
+                json::json!({ "type": "trackNode", "nid": nid, "alias": "cloud head" }),
+                json::json!({ "status": "Error", "code": "invalid-alias" }),
+            ),
+        ];
+        for (request, expected) in requests {
+            let stream = loop {
+                if let Ok(stream) = UnixStream::connect(&socket) {
+                    break stream;
+                }
+            };
+            writeln!(&stream, "{request}").unwrap();
+
+            let stream = BufReader::new(stream);
+            let line = stream.lines().next().unwrap().unwrap();
+            let mut response: json::Value = json::from_str(&line).unwrap();
+
+            // The reason is meant for humans, so we don't match on it.
+            response.as_object_mut().unwrap().remove("reason");
+            assert_eq!(response, expected);
+        }
+        assert!(handle.updates.lock().unwrap().contains(&rid));
+    }
+
+    #[test]
+    #[cfg(unix)]
     fn test_track_untrack() {
         let tmp = tempfile::tempdir().unwrap();
         let socket = tmp.path().join("node.sock");
@@ -281,7 +727,7 @@ mod tests {
         thread::spawn({
             let handle = crate::test::handle::Handle::default();
 
-            move || crate::control::listen(listener, handle)
+            move || crate::control::listen(listener, handle, HEARTBEAT, None)
         });
 
         // Wait for node to be online.
@@ -297,4 +743,158 @@ mod tests {
         assert!(handle.untrack_node(peer).unwrap());
         assert!(!handle.untrack_node(peer).unwrap());
     }
+
+    #[test]
+    fn test_control_tcp() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("control.json");
+        let rid = test::arbitrary::gen::<Id>(1);
+        let listener = TcpListener::bind(&path).unwrap();
+        let handle = test::handle::Handle::default();
+        let mut node = Node::with_address(Address::Tcp(path));
+
+        thread::spawn({
+            let handle = handle.clone();
+            move || listen(listener, handle, HEARTBEAT, None)
+        });
+
+        assert!(node.is_running());
+        assert!(node.track_repo(rid, Scope::default()).unwrap());
+        assert!(!node.track_repo(rid, Scope::default()).unwrap());
+
+        node.announce_refs(rid).unwrap();
+        assert!(handle.updates.lock().unwrap().contains(&rid));
+    }
+
+    #[test]
+    fn test_control_tcp_invalid_token() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("control.json");
+        let listener = TcpListener::bind(&path).unwrap();
+        let endpoint = Endpoint::read(&path).unwrap();
+
+        thread::spawn(move || listen(listener, test::handle::Handle::default(), HEARTBEAT, None));
+
+        // Connect with the right address, but the wrong token.
+        let forged = tmp.path().join("forged.json");
+        Endpoint::new(endpoint.addr).write(&forged).unwrap();
+
+        let node = Node::with_address(Address::Tcp(forged));
+        let mut lines = node
+            .call::<CommandResult>(Command::Status, time::Duration::from_secs(3))
+            .unwrap();
+
+        assert_matches!(
+            lines.next(),
+            Some(Ok(CommandResult::Error { reason, code: ErrorCode::Unauthorized }))
+                if reason.contains("invalid control token")
+        );
+        assert!(!node.is_running());
+    }
+
+    #[test]
+    fn test_control_tcp_slow_client() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("control.json");
+        let rid = test::arbitrary::gen::<Id>(1);
+        let listener = TcpListener::bind(&path).unwrap();
+        let endpoint = Endpoint::read(&path).unwrap();
+        let handle = test::handle::Handle::default();
+        let mut node = Node::with_address(Address::Tcp(path));
+
+        thread::spawn({
+            let handle = handle.clone();
+            move || listen(listener, handle, HEARTBEAT, None)
+        });
+
+        // A client that connects, and never authenticates, doesn't hold up others.
+        let mut slow = net::TcpStream::connect(endpoint.addr).unwrap();
+        slow.write_all(&endpoint.token.as_bytes()[..1]).unwrap();
+
+        assert!(node.track_repo(rid, Scope::default()).unwrap());
+
+        // The slow client is dropped once the deadline passes.
+        slow.set_read_timeout(Some(AUTH_TIMEOUT * 2)).unwrap();
+        assert_eq!(slow.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_control_tcp_endpoint_in_use() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("control.json");
+
+        // The endpoint left behind by a node that isn't running anymore is replaced.
+        let stale = net::TcpListener::bind((net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+        Endpoint::new(stale.local_addr().unwrap())
+            .write(&path)
+            .unwrap();
+        drop(stale);
+
+        let listener = TcpListener::bind(&path).unwrap();
+        let endpoint = Endpoint::read(&path).unwrap();
+
+        thread::spawn(move || listen(listener, test::handle::Handle::default(), HEARTBEAT, None));
+
+        // The endpoint of a running node is left alone.
+        assert_matches!(
+            TcpListener::bind(&path),
+            Err(Error::AlreadyRunning(p)) if p == path
+        );
+        assert_eq!(Endpoint::read(&path).unwrap(), endpoint);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_control_permission() {
+        let tmp = tempfile::tempdir().unwrap();
+        let socket = tmp.path().join("alice.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let token = OperatorToken::generate();
+        let update = CobUpdate {
+            rid: test::arbitrary::gen::<Id>(1),
+            type_name: cob::issue::TYPENAME.clone(),
+            object: CobObject::New,
+            message: String::from("Open issue"),
+            actions: vec![json::json!({ "type": "edit", "title": "Bug" })],
+        };
+
+        thread::spawn({
+            let token = token.clone();
+            move || {
+                listen(
+                    listener,
+                    test::handle::Handle::default(),
+                    HEARTBEAT,
+                    Some(token),
+                )
+            }
+        });
+
+        // Clients without the operator token are only granted user permission.
+        for mut node in [
+            Node::new(&socket),
+            Node::new(&socket).with_operator_token(OperatorToken::generate()),
+        ] {
+            let err = node.cob_update(update.clone()).unwrap_err();
+            assert_matches!(
+                err,
+                radicle::node::Error::Node {
+                    code: ErrorCode::Unauthorized,
+                    ..
+                }
+            );
+        }
+        // Operators are authorized, and told the repository doesn't exist.
+        let err = Node::new(&socket)
+            .with_operator_token(token)
+            .cob_update(update)
+            .unwrap_err();
+        assert_matches!(
+            err,
+            radicle::node::Error::Node {
+                code: ErrorCode::NotFound,
+                ..
+            }
+        );
+    }
 }
@@ -63,6 +63,11 @@ impl<D: wire::Decode> Deserializer<D> {
         self.unparsed.drain(..)
     }
 
+    /// Return the number of unparsed bytes.
+    pub fn len(&self) -> usize {
+        self.unparsed.len()
+    }
+
     /// Return whether there are unparsed bytes.
     pub fn is_empty(&self) -> bool {
         self.unparsed.is_empty()
@@ -0,0 +1,371 @@
+//! Local network peer discovery, for development setups where nodes share a LAN.
+//!
+//! When enabled, the node periodically sends a signed beacon with its node id, listen
+//! port and features to a UDP multicast group, and listens for the beacons of other
+//! nodes. Nodes we hear from are added to the address book, from where they are dialed
+//! like any other known peer.
+//!
+//! Beacons are only accepted if their signature is valid, they are recent, and they were
+//! sent by a node on the same network. The address of a discovered node is made of the
+//! beacon's source IP and advertised port. Since connections are authenticated, a
+//! replayed beacon can at worst cause a failed dial.
+use std::collections::HashMap;
+use std::{io, mem, net, time};
+
+use localtime::LocalTime;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use radicle::node::config::{self, Network};
+use radicle::node::{Address, Alias, Features};
+
+use crate::crypto::{PublicKey, Signature, Signer};
+use crate::node::NodeId;
+use crate::runtime;
+use crate::service;
+use crate::wire::{self, Decode, Encode};
+use crate::{LocalDuration, Timestamp};
+
+/// Identifies radicle discovery beacons among other multicast traffic.
+pub const MAGIC: u32 = 0x7261_6431; // "rad1"
+/// Maximum age of an accepted beacon. Also bounds how far ahead of our clock it can be.
+pub const MAX_BEACON_AGE: LocalDuration = LocalDuration::from_secs(60);
+/// Maximum size of a beacon, in bytes.
+pub const MAX_BEACON_SIZE: usize = 512;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to bind discovery socket: {0}")]
+    Bind(io::Error),
+    #[error("discovery group {0} is not a multicast address")]
+    InvalidGroup(net::Ipv4Addr),
+    #[error("failed to send discovered node to service: {0}")]
+    Service(io::Error),
+}
+
+/// Why a beacon was rejected.
+#[derive(thiserror::Error, Debug)]
+pub enum BeaconError {
+    #[error("invalid beacon: {0}")]
+    Decode(#[from] wire::Error),
+    #[error("invalid beacon signature")]
+    Signature,
+    #[error("beacon is for another network")]
+    Network,
+    #[error("beacon timestamp {0} is too far from our clock")]
+    Timestamp(Timestamp),
+}
+
+/// What a node says about itself on the local network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Beacon {
+    /// Node sending the beacon.
+    pub nid: NodeId,
+    /// Network the node is on.
+    pub network: Network,
+    /// Port the node accepts peer connections on.
+    pub port: u16,
+    /// Features advertised by the node.
+    pub features: Features,
+    /// Node alias.
+    pub alias: Alias,
+    /// Time at which the beacon was sent.
+    pub timestamp: Timestamp,
+}
+
+impl Beacon {
+    /// Sign the beacon, and return it as bytes ready to be sent.
+    pub fn signed<G: Signer + ?Sized>(&self, signer: &G) -> Vec<u8> {
+        let mut bytes = wire::serialize(self);
+        let signature = signer.sign(&bytes);
+
+        signature
+            .encode(&mut bytes)
+            .expect("in-memory writes don't error");
+
+        bytes
+    }
+
+    /// Decode and verify a signed beacon received at the given time.
+    pub fn verified(bytes: &[u8], network: Network, now: LocalTime) -> Result<Self, BeaconError> {
+        let len = bytes
+            .len()
+            .checked_sub(mem::size_of::<[u8; 64]>())
+            .ok_or(wire::Error::UnexpectedEnd)?;
+        let (msg, sig) = bytes.split_at(len);
+        let beacon = wire::deserialize::<Self>(msg)?;
+        let signature = wire::deserialize::<Signature>(sig)?;
+
+        if beacon.nid.verify(msg, &signature).is_err() {
+            return Err(BeaconError::Signature);
+        }
+        if beacon.network != network {
+            return Err(BeaconError::Network);
+        }
+        let skew = now.as_millis().abs_diff(beacon.timestamp);
+        if skew > MAX_BEACON_AGE.as_millis() as u64 {
+            return Err(BeaconError::Timestamp(beacon.timestamp));
+        }
+        Ok(beacon)
+    }
+}
+
+impl Encode for Beacon {
+    fn encode<W: io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        let network: u8 = match self.network {
+            Network::Main => 1,
+            Network::Test => 2,
+        };
+        let mut n = 0;
+
+        n += MAGIC.encode(writer)?;
+        n += network.encode(writer)?;
+        n += self.nid.encode(writer)?;
+        n += self.port.encode(writer)?;
+        n += self.features.encode(writer)?;
+        n += self.alias.encode(writer)?;
+        n += self.timestamp.encode(writer)?;
+
+        Ok(n)
+    }
+}
+
+impl Decode for Beacon {
+    fn decode<R: io::Read + ?Sized>(reader: &mut R) -> Result<Self, wire::Error> {
+        let magic = u32::decode(reader)?;
+        if magic != MAGIC {
+            return Err(wire::Error::UnexpectedBytes);
+        }
+        let network = match u8::decode(reader)? {
+            1 => Network::Main,
+            2 => Network::Test,
+            _ => return Err(wire::Error::UnexpectedBytes),
+        };
+        let nid = PublicKey::decode(reader)?;
+        let port = u16::decode(reader)?;
+        let features = Features::decode(reader)?;
+        let alias = Alias::decode(reader)?;
+        let timestamp = Timestamp::decode(reader)?;
+
+        Ok(Self {
+            nid,
+            network,
+            port,
+            features,
+            alias,
+            timestamp,
+        })
+    }
+}
+
+/// A socket joined to the discovery multicast group.
+pub struct Listener {
+    socket: net::UdpSocket,
+    config: config::Discovery,
+    network: Network,
+    features: Features,
+    alias: Alias,
+    port: u16,
+    signer: Box<dyn Signer>,
+}
+
+impl Listener {
+    /// Join the configured multicast group. Returns `None` if discovery is disabled.
+    ///
+    /// Our beacon advertises the given port, which should be the one we accept peer
+    /// connections on.
+    pub fn bind<G: Signer + 'static>(
+        config: &service::Config,
+        port: u16,
+        signer: G,
+    ) -> Result<Option<Self>, Error> {
+        let discovery = config.discovery.clone();
+        if !discovery.enabled {
+            return Ok(None);
+        }
+        let group = discovery.group;
+        if !group.ip().is_multicast() {
+            return Err(Error::InvalidGroup(*group.ip()));
+        }
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))
+            .and_then(|socket| {
+                // Nb. Other nodes on this host share the group's port.
+                socket.set_reuse_address(true)?;
+                #[cfg(unix)]
+                socket.set_reuse_port(true)?;
+                socket.bind(
+                    &net::SocketAddrV4::new(net::Ipv4Addr::UNSPECIFIED, group.port()).into(),
+                )?;
+                socket.join_multicast_v4(group.ip(), &discovery.interface)?;
+                socket.set_multicast_if_v4(&discovery.interface)?;
+                socket.set_multicast_loop_v4(true)?;
+
+                Ok(socket)
+            })
+            .map_err(Error::Bind)?;
+
+        Ok(Some(Self {
+            socket: socket.into(),
+            config: discovery,
+            network: config.network,
+            features: config.features(),
+            alias: config.alias.clone(),
+            port,
+            signer: Box::new(signer),
+        }))
+    }
+
+    /// Get the multicast group the listener is joined to.
+    pub fn group(&self) -> net::SocketAddrV4 {
+        self.config.group
+    }
+
+    /// Send our beacon to the group.
+    fn announce(&self) -> io::Result<()> {
+        let beacon = Beacon {
+            nid: *self.signer.public_key(),
+            network: self.network,
+            port: self.port,
+            features: self.features,
+            alias: self.alias.clone(),
+            timestamp: LocalTime::now().as_millis(),
+        };
+        self.socket
+            .send_to(&beacon.signed(&self.signer), self.config.group)?;
+
+        Ok(())
+    }
+}
+
+/// Send our beacon periodically, and pass the nodes we discover on to the service.
+pub fn listen(listener: Listener, handle: runtime::Handle) -> Result<(), Error> {
+    log::debug!(target: "discovery", "Joined multicast group {}..", listener.group());
+
+    let interval = time::Duration::from_millis(listener.config.interval.as_millis() as u64);
+    let nid = *listener.signer.public_key();
+    let mut discovered = HashMap::<NodeId, net::SocketAddr>::new();
+    let mut last_announce: Option<time::Instant> = None;
+    let mut buf = [0; MAX_BEACON_SIZE];
+
+    loop {
+        let elapsed = last_announce.map(|t| t.elapsed());
+        if elapsed.map_or(true, |e| e >= interval) {
+            if let Err(e) = listener.announce() {
+                log::error!(target: "discovery", "Failed to send beacon: {e}");
+            }
+            last_announce = Some(time::Instant::now());
+        }
+        let timeout = interval.saturating_sub(elapsed.unwrap_or_default());
+        listener
+            .socket
+            .set_read_timeout(Some(timeout.max(time::Duration::from_millis(1))))
+            .ok();
+
+        let (n, from) = match listener.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue;
+            }
+            Err(e) => {
+                log::error!(target: "discovery", "Failed to receive beacon: {e}");
+                continue;
+            }
+        };
+        let beacon = match Beacon::verified(&buf[..n], listener.network, LocalTime::now()) {
+            Ok(beacon) => beacon,
+            Err(e) => {
+                log::debug!(target: "discovery", "Ignoring beacon from {from}: {e}");
+                continue;
+            }
+        };
+        if beacon.nid == nid {
+            continue;
+        }
+        let addr = net::SocketAddr::new(from.ip(), beacon.port);
+        if discovered.insert(beacon.nid, addr) == Some(addr) {
+            continue;
+        }
+        log::debug!(target: "discovery", "Discovered node {} at {addr}", beacon.nid);
+
+        handle
+            .command(service::Command::Discovered(
+                beacon.nid,
+                Address::from(addr),
+                beacon.features,
+                beacon.alias,
+            ))
+            .map_err(Error::Service)?;
+
+        // Let the new node know about us right away, instead of at the next interval.
+        last_announce = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use radicle::crypto::test::signer::MockSigner;
+
+    fn beacon(signer: &MockSigner, timestamp: Timestamp) -> Beacon {
+        Beacon {
+            nid: *signer.public_key(),
+            network: Network::Test,
+            port: 8776,
+            features: Features::SEED,
+            alias: Alias::new("alice"),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_beacon_verified() {
+        let signer = MockSigner::default();
+        let now = LocalTime::now();
+        let beacon = beacon(&signer, now.as_millis());
+        let bytes = beacon.signed(&signer);
+
+        assert_eq!(
+            Beacon::verified(&bytes, Network::Test, now).unwrap(),
+            beacon
+        );
+        assert!(matches!(
+            Beacon::verified(&bytes, Network::Main, now),
+            Err(BeaconError::Network)
+        ));
+        assert!(matches!(
+            Beacon::verified(
+                &bytes,
+                Network::Test,
+                now + MAX_BEACON_AGE + LocalDuration::from_secs(1)
+            ),
+            Err(BeaconError::Timestamp(_))
+        ));
+        assert!(Beacon::verified(&bytes[..bytes.len() - 1], Network::Test, now).is_err());
+    }
+
+    #[test]
+    fn test_beacon_forged() {
+        let alice = MockSigner::default();
+        let eve = MockSigner::default();
+        let now = LocalTime::now();
+
+        // A beacon claiming to be from Alice, signed by Eve.
+        let bytes = beacon(&alice, now.as_millis()).signed(&eve);
+        assert!(matches!(
+            Beacon::verified(&bytes, Network::Test, now),
+            Err(BeaconError::Signature)
+        ));
+
+        // A beacon from Alice, tampered with.
+        let mut bytes = beacon(&alice, now.as_millis()).signed(&alice);
+        bytes[37] ^= 0xff;
+        assert!(matches!(
+            Beacon::verified(&bytes, Network::Test, now),
+            Err(BeaconError::Signature)
+        ));
+    }
+}
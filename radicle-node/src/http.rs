@@ -0,0 +1,365 @@
+//! Read-only HTTP interface, for lightweight integrations such as status badges.
+//!
+//! Only `GET` requests are served, and no endpoint changes the state of the node:
+//!
+//! * `/status`: the node's id, version and number of connected peers.
+//! * `/repos/{rid}`: whether the node seeds the repository, how many other seeds it
+//!   knows of, and when the repository was last fetched and announced.
+//! * `/peers`: the number of connected peers, and if configured, the peers themselves.
+//! * `/events`: a stream of node events, as [Server-Sent Events][sse]. Each event is
+//!   sent as JSON, the same way as on the control socket subscription.
+//!
+//! [sse]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+use std::io::prelude::*;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{io, net, time};
+
+use localtime::{LocalDuration, LocalTime};
+use serde_json as json;
+
+use radicle::node::config;
+use radicle::node::{Handle as _, HostName, State};
+
+use crate::identity::Id;
+use crate::runtime;
+use crate::runtime::thread;
+use crate::service::limitter::{AsTokens, RateLimiter};
+
+/// Maximum time a client has to send its request.
+const READ_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+/// Maximum time a write to a client can block, eg. when it doesn't read its response.
+const WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+/// Maximum number of connections served concurrently, including event streams.
+const MAX_CONNECTIONS: usize = 32;
+/// How often the rate limits of idle clients are forgotten.
+const LIMITER_PRUNE_INTERVAL: LocalDuration = LocalDuration::from_mins(1);
+/// Maximum size of a request head. Requests don't have a body.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Maximum number of concurrent event streams.
+const MAX_EVENT_STREAMS: usize = 8;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to bind HTTP listener: {0}")]
+    Bind(io::Error),
+    #[error("failed to query node: {0}")]
+    Runtime(#[from] runtime::HandleError),
+}
+
+impl AsTokens for config::Http {
+    fn capacity(&self) -> usize {
+        self.rate_limit
+    }
+
+    fn rate(&self) -> f64 {
+        self.rate_limit as f64 / 60.
+    }
+}
+
+/// A listener for HTTP connections.
+pub struct Listener {
+    listener: net::TcpListener,
+    config: config::Http,
+}
+
+impl Listener {
+    /// Bind to the configured address. Returns `None` if the interface is disabled.
+    pub fn bind(config: config::Http) -> Result<Option<Self>, Error> {
+        let Some(addr) = config.listen else {
+            return Ok(None);
+        };
+        let listener = net::TcpListener::bind(addr).map_err(Error::Bind)?;
+
+        Ok(Some(Self { listener, config }))
+    }
+
+    /// Get the local address of the listener.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+/// An HTTP response, with a JSON body.
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    body: json::Value,
+}
+
+impl Response {
+    fn ok(body: json::Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, msg: impl ToString) -> Self {
+        Self {
+            status,
+            body: json::json!({ "error": msg.to_string() }),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            429 => "Too Many Requests",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn to_writer(&self, head: bool, mut w: impl Write) -> io::Result<()> {
+        let body = self.body.to_string();
+
+        write!(
+            w,
+            "HTTP/1.1 {} {}\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: {}\r\n\
+            Connection: close\r\n",
+            self.status,
+            self.reason(),
+            body.len()
+        )?;
+        if self.status == 405 {
+            write!(w, "Allow: GET, HEAD\r\n")?;
+        }
+        write!(w, "\r\n")?;
+
+        if !head {
+            w.write_all(body.as_bytes())?;
+        }
+        w.flush()
+    }
+}
+
+/// Listen for HTTP requests, and answer them. Each connection is served from its own
+/// thread, up to [`MAX_CONNECTIONS`] at a time. Idle event streams are sent a heartbeat
+/// comment after the given duration.
+pub fn listen(
+    listener: Listener,
+    handle: runtime::Handle,
+    heartbeat: time::Duration,
+) -> Result<(), Error> {
+    log::debug!(target: "http", "Listening on {:?}..", listener.local_addr());
+
+    let nid = handle.nid()?;
+    let mut limiter = RateLimiter::default();
+    let mut pruned = LocalTime::now();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let streams = Arc::new(AtomicUsize::new(0));
+
+    for incoming in listener.listener.incoming() {
+        let mut stream = match incoming {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::error!(target: "http", "Failed to accept incoming connection: {e}");
+                continue;
+            }
+        };
+        let Ok(peer) = stream.peer_addr() else {
+            continue;
+        };
+        let now = LocalTime::now();
+        if now - pruned >= LIMITER_PRUNE_INTERVAL {
+            limiter.prune(now);
+            pruned = now;
+        }
+        let limited = limiter.limit(HostName::Ip(peer.ip()), &listener.config, now);
+
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            log::debug!(target: "http", "Dropping connection from {peer}: too many connections");
+
+            continue;
+        }
+        let guard = CountGuard(connections.clone());
+        let handle = handle.clone();
+        let config = listener.config.clone();
+        let streams = streams.clone();
+
+        thread::spawn(&nid, "http", move || {
+            let _guard = guard;
+
+            if let Err(e) = serve(&mut stream, limited, &handle, &config, heartbeat, &streams) {
+                log::debug!(target: "http", "Failed to serve request from {peer}: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Read a request from the stream, and write the response. The request is read even if
+/// the client is rate limited, so that it gets the response instead of a reset connection.
+fn serve(
+    stream: &mut net::TcpStream,
+    limited: bool,
+    handle: &runtime::Handle,
+    config: &config::Http,
+    heartbeat: time::Duration,
+    streams: &Arc<AtomicUsize>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let mut buf = Vec::new();
+    let mut chunk = [0; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Response::error(400, "request too large").to_writer(false, stream);
+        }
+        match stream.read(&mut chunk)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+    if limited {
+        return Response::error(429, "rate limit exceeded").to_writer(false, stream);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut line = head.lines().next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (line.next(), line.next()) else {
+        return Response::error(400, "invalid request line").to_writer(false, stream);
+    };
+    let path = target.split('?').next().unwrap_or_default();
+    let response = match method {
+        "GET" if path.trim_end_matches('/') == "/events" => {
+            return events(stream, handle, heartbeat, streams);
+        }
+        "GET" | "HEAD" => respond(path, handle, config),
+        _ => Response::error(405, format!("method {method} not allowed")),
+    };
+    response.to_writer(method == "HEAD", stream)
+}
+
+/// Stream events to the client. The stream ends when the client goes away, or the node
+/// shuts down.
+fn events(
+    stream: &mut net::TcpStream,
+    handle: &runtime::Handle,
+    heartbeat: time::Duration,
+    streams: &Arc<AtomicUsize>,
+) -> io::Result<()> {
+    if streams.fetch_add(1, Ordering::SeqCst) >= MAX_EVENT_STREAMS {
+        streams.fetch_sub(1, Ordering::SeqCst);
+
+        return Response::error(503, "too many event streams").to_writer(false, stream);
+    }
+    let _guard = CountGuard(streams.clone());
+    let events = match handle.subscribe(heartbeat) {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!(target: "http", "Error subscribing to events: {e}");
+            return Response::error(500, "node query failed").to_writer(false, stream);
+        }
+    };
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/event-stream\r\n\
+        Cache-Control: no-cache\r\n\
+        Connection: close\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    for e in events {
+        let result = match e {
+            Ok(event) => {
+                let event = json::to_string(&event)?;
+
+                write!(stream, "data: {event}\n\n")
+            }
+            // Comments are ignored by clients, but let us notice clients that went away
+            // while the node is quiet.
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => write!(stream, ":\n\n"),
+            Err(e) => return Err(e),
+        };
+        // Writes time out if the client stops reading. Either way, the client is gone, and
+        // returning drops the subscription, so that events are no longer queued for it.
+        result.and_then(|()| stream.flush())?;
+    }
+    Ok(())
+}
+
+/// Decrements a number of connections when dropped.
+struct CountGuard(Arc<AtomicUsize>);
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Get the response to a request for the given path.
+fn respond(path: &str, handle: &runtime::Handle, config: &config::Http) -> Response {
+    let result = match path.trim_end_matches('/').split('/').collect::<Vec<_>>()[..] {
+        ["", "status"] => status(handle),
+        ["", "peers"] => peers(handle, config),
+        ["", "repos", rid] => match Id::from_str(rid) {
+            Ok(rid) => repo(rid, handle),
+            Err(e) => return Response::error(400, format!("invalid repository id: {e}")),
+        },
+        _ => return Response::error(404, format!("{path} not found")),
+    };
+    match result {
+        Ok(body) => Response::ok(body),
+        Err(e) => {
+            log::error!(target: "http", "Failed to query node for {path}: {e}");
+            Response::error(500, "node query failed")
+        }
+    }
+}
+
+fn status(handle: &runtime::Handle) -> Result<json::Value, runtime::HandleError> {
+    let peers = connected(handle)?.len();
+
+    Ok(json::json!({
+        "nid": handle.nid()?,
+        "version": env!("CARGO_PKG_VERSION"),
+        "peers": peers,
+    }))
+}
+
+fn peers(
+    handle: &runtime::Handle,
+    config: &config::Http,
+) -> Result<json::Value, runtime::HandleError> {
+    let peers = connected(handle)?;
+
+    if config.list_peers {
+        let list = peers
+            .iter()
+            .map(|s| json::json!({ "nid": s.nid, "addr": s.addr }))
+            .collect::<Vec<_>>();
+
+        Ok(json::json!({ "count": peers.len(), "peers": list }))
+    } else {
+        Ok(json::json!({ "count": peers.len() }))
+    }
+}
+
+fn repo(rid: Id, handle: &runtime::Handle) -> Result<json::Value, runtime::HandleError> {
+    let activity = handle.repo_activity(rid)?;
+
+    Ok(json::json!({
+        "rid": rid,
+        "seeding": activity.seeding,
+        "seeds": activity.seeds,
+        "lastFetch": activity.fetched.map(|t| t.as_millis()),
+        "lastAnnounce": activity.announced.map(|t| t.as_millis()),
+    }))
+}
+
+fn connected(
+    handle: &runtime::Handle,
+) -> Result<Vec<radicle::node::Session>, runtime::HandleError> {
+    Ok(handle
+        .sessions()?
+        .into_iter()
+        .filter(|s| matches!(s.state, State::Connected { .. }))
+        .collect())
+}
@@ -1,6 +1,8 @@
 pub mod bounded;
 pub mod control;
 pub mod deserializer;
+pub mod discovery;
+pub mod http;
 pub mod logger;
 pub mod runtime;
 pub mod service;
@@ -4,6 +4,7 @@ use std::{env, fs, net, path::PathBuf, process};
 use anyhow::Context;
 use crossbeam_channel as chan;
 
+use radicle::node::config;
 use radicle::prelude::Signer;
 use radicle::profile;
 use radicle::version;
@@ -29,6 +30,8 @@ Options
     --git-daemon         <address>      Address to bind git-daemon to (default 0.0.0.0:9418)
     --force                             Force start even if an existing control socket is found
     --listen             <address>      Address to listen on
+    --strict-config                     Exclude external addresses that fail validation
+    --allow-port-mismatch               Allow external addresses with a port that no listener is bound to
     --version                           Print program version
     --help                              Print help
 "#;
@@ -39,6 +42,7 @@ struct Options {
     config: Option<PathBuf>,
     listen: Vec<net::SocketAddr>,
     force: bool,
+    validation: config::Validation,
 }
 
 impl Options {
@@ -50,6 +54,7 @@ impl Options {
         let mut daemon = None;
         let mut config = None;
         let mut force = false;
+        let mut validation = config::Validation::default();
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -69,6 +74,12 @@ impl Options {
                     let addr = parser.value()?.parse()?;
                     listen.push(addr);
                 }
+                Long("strict-config") => {
+                    validation.strict = true;
+                }
+                Long("allow-port-mismatch") => {
+                    validation.allow_port_mismatch = true;
+                }
                 Long("help") | Short('h') => {
                     println!("{HELP_MSG}");
                     process::exit(0);
@@ -86,6 +97,7 @@ impl Options {
             force,
             listen,
             config,
+            validation,
         })
     }
 }
@@ -108,7 +120,11 @@ fn execute() -> anyhow::Result<()> {
 
     let config = options.config.unwrap_or_else(|| home.config());
     let config = profile::Config::load(&config)?.node;
-    let proxy = net::SocketAddr::new(net::Ipv4Addr::LOCALHOST.into(), 9050);
+    // Nb. The proxy is only dialed for addresses that require one, which are skipped if
+    // no proxy is configured.
+    let proxy = config
+        .proxy
+        .unwrap_or_else(|| net::SocketAddr::new(net::Ipv4Addr::LOCALHOST.into(), 9050));
     let daemon = options.daemon.unwrap_or_else(|| {
         net::SocketAddr::new(net::Ipv4Addr::LOCALHOST.into(), radicle::git::PROTOCOL_PORT)
     });
@@ -120,7 +136,17 @@ fn execute() -> anyhow::Result<()> {
         log::debug!(target: "node", "Removing existing control socket..");
         fs::remove_file(home.socket()).ok();
     }
-    Runtime::init(home, config, options.listen, proxy, daemon, signals, signer)?.run()?;
+    Runtime::init(
+        home,
+        config,
+        options.listen,
+        options.validation,
+        proxy,
+        daemon,
+        signals,
+        signer,
+    )?
+    .run()?;
 
     Ok(())
 }
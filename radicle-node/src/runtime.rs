@@ -1,8 +1,10 @@
 pub mod handle;
+pub mod recovery;
+pub mod setup;
+pub mod stats;
 pub mod thread;
 
 use std::io::{BufRead, BufReader};
-use std::os::unix::net::UnixListener;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::{fs, io, net, time};
@@ -18,13 +20,24 @@ use radicle::git;
 use radicle::node;
 use radicle::node::address;
 use radicle::node::address::Store as _;
+use radicle::node::config;
+use radicle::node::control::{OperatorToken, Permission, Transport};
+use radicle::node::events;
+use radicle::node::watermark;
 use radicle::node::Handle as _;
-use radicle::node::{ADDRESS_DB_FILE, NODE_ANNOUNCEMENT_FILE, ROUTING_DB_FILE, TRACKING_DB_FILE};
+use radicle::node::{
+    ADDRESS_DB_FILE, EVENTS_DB_FILE, NODE_ANNOUNCEMENT_FILE, ROUTING_DB_FILE, TRACKING_DB_FILE,
+    WATERMARK_DB_FILE,
+};
 use radicle::profile::Home;
+use radicle::storage::ReadStorage as _;
 use radicle::Storage;
 
 use crate::control;
 use crate::crypto::Signer;
+use crate::discovery;
+use crate::http;
+use crate::identity::Id;
 use crate::node::{routing, NodeId};
 use crate::service::message::NodeAnnouncement;
 use crate::service::{tracking, Event};
@@ -35,6 +48,7 @@ use crate::{service, LocalTime};
 
 pub use handle::Error as HandleError;
 pub use handle::Handle;
+pub use stats::Stats;
 
 /// A client error.
 #[derive(Error, Debug)]
@@ -48,12 +62,24 @@ pub enum Error {
     /// A tracking database error.
     #[error("tracking database error: {0}")]
     Tracking(#[from] tracking::Error),
+    /// An event log database error.
+    #[error("event log database error: {0}")]
+    Events(#[from] events::store::Error),
+    /// A sync watermark database error.
+    #[error("watermark database error: {0}")]
+    Watermarks(#[from] watermark::Error),
     /// An I/O error.
     #[error("i/o error: {0}")]
     Io(#[from] io::Error),
     /// A control socket error.
     #[error("control socket error: {0}")]
     Control(#[from] control::Error),
+    /// An HTTP interface error.
+    #[error("http interface error: {0}")]
+    Http(#[from] http::Error),
+    /// A local network discovery error.
+    #[error("local network discovery error: {0}")]
+    Discovery(#[from] discovery::Error),
     /// Another node is already running.
     #[error(
         "another node appears to be running; \
@@ -103,13 +129,27 @@ impl<T: Clone> Emitter<T> {
 pub struct Runtime {
     pub id: NodeId,
     pub home: Home,
-    pub control: UnixListener,
+    pub control: Box<dyn control::Listener>,
+    /// How long an event subscription on the control socket can be idle before a
+    /// heartbeat is sent.
+    pub heartbeat: time::Duration,
+    /// Token granting operator permission to control clients, if it can be granted.
+    pub operator: Option<OperatorToken>,
+    pub http: Option<http::Listener>,
+    pub discovery: Option<discovery::Listener>,
     pub handle: Handle,
     pub storage: Storage,
     pub reactor: Reactor<wire::Control, popol::Poller>,
     pub daemon: net::SocketAddr,
     pub pool: worker::Pool,
+    /// Repositories to check in the background, after an unclean shutdown.
+    pub recovering: Vec<Id>,
     pub local_addrs: Vec<net::SocketAddr>,
+    /// External addresses announced to the network, once validated.
+    pub external_addrs: Vec<node::Address>,
+    /// Nodes we don't establish sessions with, to simulate network partitions.
+    #[cfg(any(test, feature = "test"))]
+    pub blocklist: crate::test::environment::Blocklist,
     pub signals: chan::Receiver<()>,
 }
 
@@ -117,10 +157,12 @@ impl Runtime {
     /// Initialize the runtime.
     ///
     /// This function spawns threads.
+    #[allow(clippy::too_many_arguments)]
     pub fn init<G: Signer + Ecdh + 'static>(
         home: Home,
-        config: service::Config,
+        mut config: service::Config,
         listen: Vec<net::SocketAddr>,
+        validation: config::Validation,
         proxy: net::SocketAddr,
         daemon: net::SocketAddr,
         signals: chan::Receiver<()>,
@@ -131,6 +173,9 @@ impl Runtime {
     {
         let id = *signer.public_key();
         let node_dir = home.node();
+        let transport = config.control;
+        let permission = config.control_permission;
+        let http = http::Listener::bind(config.http.clone())?;
         let network = config.network;
         let rng = fastrand::Rng::new();
         let clock = LocalTime::now();
@@ -138,17 +183,116 @@ impl Runtime {
         let address_db = node_dir.join(ADDRESS_DB_FILE);
         let routing_db = node_dir.join(ROUTING_DB_FILE);
         let tracking_db = node_dir.join(TRACKING_DB_FILE);
+        let events_db = node_dir.join(EVENTS_DB_FILE);
+        let watermarks_db = node_dir.join(WATERMARK_DB_FILE);
+
+        if setup::prepare(&node_dir)? == setup::State::Fresh {
+            log::info!(target: "node", "Initializing node directory {}..", node_dir.display());
+        }
+        let unclean = setup::running(&node_dir)?;
+        if unclean {
+            log::warn!(target: "node", "Node didn't shut down cleanly; recovering on startup..");
+        }
+
+        // Corrupt databases that were moved aside, and where they were moved to.
+        let mut recovered = Vec::new();
 
         log::info!(target: "node", "Opening address book {}..", address_db.display());
-        let mut addresses = address::Book::open(address_db)?;
+        let mut addresses = if config.recover_databases {
+            let (book, backup) = address::Book::open_or_recover(&address_db)?;
+            recovered.extend(backup.map(|b| (address_db, b)));
+            book
+        } else {
+            address::Book::open(address_db)?
+        };
 
         log::info!(target: "node", "Opening routing table {}..", routing_db.display());
-        let routing = routing::Table::open(routing_db)?;
+        let routing = if config.recover_databases {
+            let (table, backup) = routing::Table::open_or_recover(&routing_db)?;
+            recovered.extend(backup.map(|b| (routing_db, b)));
+            table
+        } else {
+            routing::Table::open(routing_db)?
+        };
 
         log::info!(target: "node", "Opening tracking policy table {}..", tracking_db.display());
-        let tracking = tracking::Store::open(tracking_db)?;
+        let tracking = if config.recover_databases {
+            let (store, backup) = tracking::Store::open_or_recover(&tracking_db)?;
+            if let Some(backup) = &backup {
+                log::warn!(
+                    target: "node",
+                    "Tracking policies were lost! They may be salvaged from {}, and restored with the `importPolicies` command",
+                    backup.display()
+                );
+            }
+            recovered.extend(backup.map(|b| (tracking_db, b)));
+            store
+        } else {
+            tracking::Store::open(tracking_db)?
+        };
         let tracking = tracking::Config::new(config.policy, config.scope, tracking);
 
+        log::info!(target: "node", "Opening event log {}..", events_db.display());
+        let event_log = if config.recover_databases {
+            let (log, backup) = events::Log::open_or_recover(&events_db)?;
+            recovered.extend(backup.map(|b| (events_db, b)));
+            log
+        } else {
+            events::Log::open(events_db)?
+        };
+
+        log::info!(target: "node", "Opening sync watermarks {}..", watermarks_db.display());
+        let watermarks = if config.recover_databases {
+            let (table, backup) = watermark::Table::open_or_recover(&watermarks_db)?;
+            recovered.extend(backup.map(|b| (watermarks_db, b)));
+            table
+        } else {
+            watermark::Table::open(watermarks_db)?
+        };
+
+        for (path, backup) in &recovered {
+            log::warn!(
+                target: "node",
+                "Database {} is corrupt! It was moved to {}, and an empty database was created in its place",
+                path.display(),
+                backup.display()
+            );
+        }
+
+        let mut listeners = Vec::new();
+        let mut local_addrs = Vec::new();
+
+        for addr in listen {
+            let listener = NetAccept::bind(&addr)?;
+            let local_addr = listener.local_addr();
+
+            local_addrs.push(local_addr);
+            listeners.push(listener);
+
+            log::info!(target: "node", "Listening on {local_addr}..");
+        }
+
+        // Nb. This has to happen before the node announcement is created, since it may
+        // change the external addresses.
+        for warning in config.validate(Some(&local_addrs), validation, config::resolve) {
+            if validation.strict {
+                log::error!(target: "node", "Excluding external address: {warning}");
+                config.external_addresses.retain(|a| a != warning.address());
+            } else {
+                log::warn!(target: "node", "Invalid configuration: {warning}");
+            }
+        }
+        let external_addrs = config.external_addresses.clone();
+        let heartbeat =
+            time::Duration::from_millis(config.limits.subscribe_heartbeat.as_millis() as u64);
+        let shape = config.limits.fetch_shape;
+
+        // Nb. Discovery is only available if we're listening for peer connections.
+        let discovery = match local_addrs.first() {
+            Some(addr) => discovery::Listener::bind(&config, addr.port(), signer.clone())?,
+            None => None,
+        };
+
         log::info!(target: "node", "Default tracking policy set to '{}'", &config.policy);
         log::info!(target: "node", "Initializing service ({:?})..", network);
 
@@ -173,12 +317,12 @@ impl Runtime {
             );
             ann
         } else {
-            service::gossip::node(&config, clock.as_secs())
+            service::gossip::node(&config, clock.as_millis())
                 .solve(Default::default())
                 .expect("Runtime::init: unable to solve proof-of-work puzzle")
         };
 
-        if config.connect.is_empty() && addresses.is_empty()? {
+        if config.startup_peers().next().is_none() && addresses.is_empty()? {
             log::info!(target: "node", "Address book is empty. Adding bootstrap nodes..");
 
             for (alias, addr) in config.network.bootstrap() {
@@ -195,36 +339,55 @@ impl Runtime {
             }
             log::info!(target: "node", "{} nodes added to address book", addresses.len()?);
         }
+        setup::complete(&node_dir)?;
 
         let emitter: Emitter<Event> = Default::default();
-        let service = service::Service::new(
+        let mut service = service::Service::new(
             config,
             clock,
             routing,
             storage.clone(),
             addresses,
             tracking,
+            event_log,
+            watermarks,
             signer.clone(),
             rng,
             announcement,
             emitter.clone(),
         );
+        service.set_node_announcement_file(node_dir.join(NODE_ANNOUNCEMENT_FILE));
 
-        let (worker_send, worker_recv) = chan::unbounded::<worker::Task>();
-        let mut wire = Wire::new(service, worker_send, signer, proxy, clock);
-        let mut local_addrs = Vec::new();
+        for (path, backup) in recovered {
+            service.database_recovered(path, backup);
+        }
+        // Nb. Checking repositories can take a while, so it is done by a separate thread.
+        let mut recovering = Vec::new();
+        if unclean {
+            match storage.inventory() {
+                Ok(inventory) => recovering = inventory,
+                Err(e) => log::error!(target: "node", "Error listing repositories to check: {e}"),
+            }
+            service.unclean_shutdown(recovering.iter().copied());
+        }
 
-        for addr in listen {
-            let listener = NetAccept::bind(&addr)?;
-            let local_addr = listener.local_addr();
+        let (worker_send, worker_recv) = chan::unbounded::<worker::Task>();
+        let mut wire = Wire::new(service, worker_send, signer.clone(), proxy, clock);
 
-            local_addrs.push(local_addr);
+        for listener in listeners {
             wire.listen(listener);
-
-            log::info!(target: "node", "Listening on {local_addr}..");
         }
+        #[cfg(any(test, feature = "test"))]
+        let blocklist = wire.blocklist();
         let reactor = Reactor::named(wire, popol::Poller::new(), thread::name(&id, "service"))?;
-        let handle = Handle::new(home.clone(), reactor.controller(), emitter);
+        let handle = Handle::new(
+            home.clone(),
+            home.control(transport),
+            reactor.controller(),
+            emitter,
+            Stats::spawn(&id, storage.clone(), stats::STATS_TTL),
+            Arc::new(signer),
+        );
         let atomic = git::version()? >= git::VERSION_REQUIRED;
 
         if !atomic {
@@ -244,42 +407,121 @@ impl Runtime {
                 storage: storage.clone(),
                 daemon,
                 atomic,
+                shape,
             },
         );
-        let control = match UnixListener::bind(home.socket()) {
-            Ok(sock) => sock,
-            Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
-                return Err(Error::AlreadyRunning(home.socket()));
+        let control: Box<dyn control::Listener> = match transport.resolve() {
+            Transport::Tcp => {
+                let listener = match control::TcpListener::bind(&home.control_endpoint()) {
+                    Ok(listener) => listener,
+                    Err(control::Error::AlreadyRunning(path)) => {
+                        return Err(Error::AlreadyRunning(path));
+                    }
+                    Err(err) => {
+                        return Err(err.into());
+                    }
+                };
+                log::info!(target: "node", "Control listener bound to {}..", listener.local_addr()?);
+
+                Box::new(listener)
+            }
+            #[cfg(unix)]
+            Transport::Unix | Transport::Auto => {
+                match std::os::unix::net::UnixListener::bind(home.socket()) {
+                    Ok(sock) => Box::new(sock),
+                    Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+                        return Err(Error::AlreadyRunning(home.socket()));
+                    }
+                    Err(err) => {
+                        return Err(err.into());
+                    }
+                }
             }
-            Err(err) => {
-                return Err(err.into());
+            #[cfg(not(unix))]
+            Transport::Unix | Transport::Auto => {
+                return Err(Error::Io(io::ErrorKind::Unsupported.into()));
+            }
+        };
+        // Nb. The token is only written once we know no other node is running, since it
+        // would replace the token of that node.
+        let operator = match permission {
+            Permission::Operator => {
+                let token = OperatorToken::generate();
+                token.write(&home.control_operator())?;
+
+                Some(token)
+            }
+            Permission::User => {
+                match fs::remove_file(home.control_operator()) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+                None
             }
         };
 
+        if let Some(http) = &http {
+            log::info!(target: "node", "HTTP listener bound to {}..", http.local_addr()?);
+        }
+        if let Some(discovery) = &discovery {
+            log::info!(target: "node", "Local network discovery enabled on {}..", discovery.group());
+        }
+
         Ok(Runtime {
             id,
             home,
             control,
+            heartbeat,
+            operator,
+            http,
+            discovery,
             storage,
             reactor,
             daemon,
             handle,
             pool,
+            recovering,
             signals,
             local_addrs,
+            external_addrs,
+            #[cfg(any(test, feature = "test"))]
+            blocklist,
         })
     }
 
     pub fn run(self) -> Result<(), Error> {
         let home = self.home;
+        let control = self.handle.control.clone();
 
         log::info!(target: "node", "Running node {} in {}..", self.id, home.path().display());
-        log::info!(target: "node", "Binding control socket {}..", home.socket().display());
+        log::info!(target: "node", "Binding control socket {}..", control);
 
         thread::spawn(&self.id, "control", {
             let handle = self.handle.clone();
-            || control::listen(self.control, handle)
+            let heartbeat = self.heartbeat;
+            let operator = self.operator;
+            move || control::listen(self.control, handle, heartbeat, operator)
         });
+        if let Some(http) = self.http {
+            thread::spawn(&self.id, "http", {
+                let handle = self.handle.clone();
+                let heartbeat = self.heartbeat;
+                move || http::listen(http, handle, heartbeat)
+            });
+        }
+        if let Some(discovery) = self.discovery {
+            thread::spawn(&self.id, "discovery", {
+                let handle = self.handle.clone();
+                || discovery::listen(discovery, handle)
+            });
+        }
+        if !self.recovering.is_empty() {
+            thread::spawn(&self.id, "recovery", {
+                let handle = self.handle.clone();
+                let storage = self.storage.clone();
+                || recovery::check(storage, self.recovering, handle)
+            });
+        }
         let _signals = thread::spawn(&self.id, "signals", move || {
             if let Ok(()) = self.signals.recv() {
                 log::info!(target: "node", "Termination signal received; shutting down..");
@@ -306,14 +548,19 @@ impl Runtime {
         self.pool.run().unwrap();
         self.reactor.join().unwrap();
 
+        // The service stopped cleanly, so there's nothing to recover from on the next start.
+        if let Err(e) = setup::stopped(&home.node()) {
+            log::error!(target: "node", "Failed to mark node as stopped: {e}");
+        }
+
         daemon::kill(&daemon).ok(); // Ignore error if daemon has already exited, for whatever reason.
         daemon.wait()?;
 
-        // Nb. We don't join the control thread here, as we have no way of notifying it that the
-        // node is shutting down.
+        // Nb. We don't join the control, HTTP and discovery threads here, as we have no way of
+        // notifying them that the node is shutting down.
 
-        // Remove control socket file, but don't freak out if it's not there anymore.
-        fs::remove_file(home.socket()).ok();
+        // Remove control socket or endpoint file, but don't freak out if it's not there anymore.
+        fs::remove_file(control.path()).ok();
 
         log::debug!(target: "node", "Node shutdown completed for {}", self.id);
 
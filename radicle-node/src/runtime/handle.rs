@@ -1,22 +1,35 @@
-use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::{fmt, io, time};
 
 use crossbeam_channel as chan;
-use radicle::node::{ConnectOptions, ConnectResult, Seeds};
+use radicle::cob;
+use radicle::node::{
+    activity, config, control, events, CobUpdate, CobUpdated, ConnectOptions, ConnectResult, Seeds,
+};
 use reactor::poller::popol::PopolWaker;
 use thiserror::Error;
 
+use crate::crypto::Signer;
 use crate::identity::Id;
-use crate::node::{Alias, Command, FetchResult};
+use crate::node::{
+    Alias, Command, Explanation, FetchRecord, FetchResult, GossipStats, Inspection, Metrics,
+    Recovery, SyncAction, SyncAllOptions, SyncProgress, SyncResult, Timestamp, TrackDryRun,
+    Tracked,
+};
+use crate::profile;
 use crate::profile::Home;
+use crate::runtime::thread;
 use crate::runtime::Emitter;
+use crate::runtime::Stats;
 use crate::service;
 use crate::service::tracking;
 use crate::service::NodeId;
-use crate::service::{CommandError, QueryState};
+use crate::service::{CommandError, QueryState, RepoActivity};
 use crate::service::{Event, Events};
+use crate::storage::git::stats;
+use crate::storage::git::Storage;
+use crate::storage::{Finding, ReadStorage, WriteStorage};
 use crate::wire;
 use crate::wire::StreamId;
 use crate::worker::TaskResult;
@@ -36,6 +49,15 @@ pub enum Error {
     /// An I/O error occured.
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    /// A storage error occured.
+    #[error("storage: {0}")]
+    Storage(#[from] radicle::storage::Error),
+    /// The configuration file couldn't be updated.
+    #[error(transparent)]
+    Config(#[from] profile::ConfigError),
+    /// A collaborative object couldn't be updated.
+    #[error("cob: {0}")]
+    Cob(#[from] cob::store::Error),
 }
 
 impl From<chan::RecvError> for Error {
@@ -61,12 +83,18 @@ impl<T> From<chan::SendError<T>> for Error {
 
 pub struct Handle {
     pub(crate) home: Home,
+    /// Address of our own control interface.
+    pub(crate) control: control::Address,
     pub(crate) controller: reactor::Controller<wire::Control, PopolWaker>,
 
     /// Whether a shutdown was initiated or not. Prevents attempting to shutdown twice.
     shutdown: Arc<AtomicBool>,
     /// Publishes events to subscribers.
     emitter: Emitter<Event>,
+    /// Cached repository statistics.
+    stats: Stats,
+    /// Our signer, used to sign changes made on behalf of control clients.
+    signer: Arc<dyn Signer>,
 }
 
 impl Handle {
@@ -74,6 +102,23 @@ impl Handle {
     pub fn events(&self) -> Events {
         Events::from(self.emitter.subscribe())
     }
+
+    /// Get what the service knows about a repository.
+    pub fn repo_activity(&self, rid: Id) -> Result<RepoActivity, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.repo_activity(&rid)).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let activity = receiver.recv()?;
+
+        Ok(activity)
+    }
 }
 
 impl fmt::Debug for Handle {
@@ -86,9 +131,12 @@ impl Clone for Handle {
     fn clone(&self) -> Self {
         Self {
             home: self.home.clone(),
+            control: self.control.clone(),
             controller: self.controller.clone(),
             shutdown: self.shutdown.clone(),
             emitter: self.emitter.clone(),
+            stats: self.stats.clone(),
+            signer: self.signer.clone(),
         }
     }
 }
@@ -96,14 +144,20 @@ impl Clone for Handle {
 impl Handle {
     pub fn new(
         home: Home,
+        control: control::Address,
         controller: reactor::Controller<wire::Control, PopolWaker>,
         emitter: Emitter<Event>,
+        stats: Stats,
+        signer: Arc<dyn Signer>,
     ) -> Self {
         Self {
             home,
+            control,
             controller,
             shutdown: Arc::default(),
             emitter,
+            stats,
+            signer,
         }
     }
 
@@ -118,6 +172,90 @@ impl Handle {
     pub(crate) fn command(&self, cmd: service::Command) -> Result<(), io::Error> {
         self.controller.cmd(wire::Control::User(cmd))
     }
+
+    /// Update the peers in the configuration file. If the node runs without a configuration
+    /// file, there is nothing to update.
+    fn update_peers(&self, f: impl FnOnce(&mut config::Config)) -> Result<(), Error> {
+        let path = self.home.config();
+        if !path.exists() {
+            log::warn!(target: "node", "Configuration file {} not found, peers are only updated in memory", path.display());
+            return Ok(());
+        }
+        profile::Config::update_peers(&path, f)?;
+
+        Ok(())
+    }
+}
+
+/// Sync a repository, as part of [`radicle::node::Handle::sync_all`]. The repository is
+/// fetched from its best connected seed, and then our refs are announced.
+fn sync_repo(
+    rid: Id,
+    options: &SyncAllOptions,
+    storage: &Storage,
+    handle: &mut Handle,
+) -> Vec<SyncProgress> {
+    use radicle::node::Handle as _;
+
+    let mut lines = Vec::new();
+
+    if options.fetch {
+        let result = match handle.seeds(rid) {
+            Ok(seeds) => match seeds.select(1).first() {
+                Some(seed) => match handle.fetch(rid, seed.nid) {
+                    Ok(FetchResult::Success { updated, .. }) => SyncResult::Success {
+                        seed: Some(seed.nid),
+                        updated: updated.len(),
+                    },
+                    Ok(FetchResult::Failed { reason }) => SyncResult::Failed { reason },
+                    Err(e) => SyncResult::Failed {
+                        reason: e.to_string(),
+                    },
+                },
+                None => SyncResult::Skipped {
+                    reason: String::from("no connected seeds"),
+                },
+            },
+            Err(e) => SyncResult::Failed {
+                reason: e.to_string(),
+            },
+        };
+        lines.push(SyncProgress::Repo {
+            rid,
+            action: SyncAction::Fetch,
+            result,
+        });
+    }
+    if options.announce {
+        let result = match storage.contains(&rid) {
+            Ok(true) => match handle.announce_refs(rid) {
+                Ok(()) => SyncResult::Success {
+                    seed: None,
+                    updated: 0,
+                },
+                Err(Error::Command(e @ service::CommandError::NothingToAnnounce(_))) => {
+                    SyncResult::Skipped {
+                        reason: e.to_string(),
+                    }
+                }
+                Err(e) => SyncResult::Failed {
+                    reason: e.to_string(),
+                },
+            },
+            Ok(false) => SyncResult::Skipped {
+                reason: String::from("repository is not in storage"),
+            },
+            Err(e) => SyncResult::Failed {
+                reason: e.to_string(),
+            },
+        };
+        lines.push(SyncProgress::Repo {
+            rid,
+            action: SyncAction::Announce,
+            result,
+        });
+    }
+    lines
 }
 
 impl radicle::node::Handle for Handle {
@@ -159,6 +297,19 @@ impl radicle::node::Handle for Handle {
                 return Ok(ConnectResult::Connected);
             }
         }
+        // Reject addresses that can't be dialed right away, instead of timing out.
+        let query: Arc<QueryState> = {
+            let addr = addr.clone();
+            Arc::new(move |state| {
+                addr.validate(&state.config().address_context())?;
+
+                Ok(())
+            })
+        };
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, sender))?;
+        receiver.recv()??;
+
         self.command(service::Command::Connect(node, addr, opts))?;
 
         events
@@ -186,36 +337,284 @@ impl radicle::node::Handle for Handle {
     fn fetch(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Error> {
         let (sender, receiver) = chan::bounded(1);
         self.command(service::Command::Fetch(id, from, sender))?;
-        receiver.recv().map_err(Error::from)
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn fetch_minimal(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::FetchMinimal(id, from, sender))?;
+        receiver.recv()?.map_err(Error::from)
     }
 
     fn track_node(&mut self, id: NodeId, alias: Option<Alias>) -> Result<bool, Error> {
         let (sender, receiver) = chan::bounded(1);
         self.command(service::Command::TrackNode(id, alias, sender))?;
-        receiver.recv().map_err(Error::from)
+        receiver.recv()?.map_err(Error::from)
     }
 
     fn untrack_node(&mut self, id: NodeId) -> Result<bool, Error> {
         let (sender, receiver) = chan::bounded(1);
         self.command(service::Command::UntrackNode(id, sender))?;
-        receiver.recv().map_err(Error::from)
+        receiver.recv()?.map_err(Error::from)
     }
 
     fn track_repo(&mut self, id: Id, scope: tracking::Scope) -> Result<bool, Error> {
+        self.track_repo_origin(id, scope, tracking::Origin::default())
+    }
+
+    fn track_repo_origin(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+        origin: tracking::Origin,
+    ) -> Result<bool, Error> {
         let (sender, receiver) = chan::bounded(1);
-        self.command(service::Command::TrackRepo(id, scope, sender))?;
+        self.command(service::Command::TrackRepo(id, scope, origin, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn track_repo_filter(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+        filter: tracking::RefFilter,
+    ) -> Result<bool, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::TrackRepoFilter(id, scope, filter, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn track_repo_fetch(&mut self, id: Id, scope: tracking::Scope) -> Result<Tracked, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::TrackRepoFetch(id, scope, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn track_repo_dry_run(&mut self, id: Id, scope: tracking::Scope) -> Result<TrackDryRun, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::TrackRepoDryRun(id, scope, sender))?;
         receiver.recv().map_err(Error::from)
     }
 
+    fn inspect(&mut self, id: Id) -> Result<Inspection, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::Inspect(id, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn config(&self) -> Result<config::Config, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.config().clone()).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let config = receiver.recv()?;
+
+        Ok(config)
+    }
+
+    fn set_limit(&mut self, name: &str, value: serde_json::Value) -> Result<(), Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::SetLimit(name.to_owned(), value, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn add_peer(&mut self, peer: config::PeerEntry) -> Result<bool, Error> {
+        // The configuration file is updated first, so that the node's configuration isn't
+        // changed if the file can't be.
+        self.update_peers(|config| {
+            config.add_peer(peer.clone());
+        })?;
+
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::AddPeer(peer, sender))?;
+        receiver.recv().map_err(Error::from)
+    }
+
+    fn remove_peer(&mut self, nid: NodeId) -> Result<bool, Error> {
+        self.update_peers(|config| {
+            config.remove_peer(&nid);
+        })?;
+
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::RemovePeer(nid, sender))?;
+        receiver.recv().map_err(Error::from)
+    }
+
+    fn sync_all(
+        &mut self,
+        options: SyncAllOptions,
+    ) -> Result<Box<dyn Iterator<Item = Result<SyncProgress, io::Error>>>, Error> {
+        let rids = self
+            .export_policies()?
+            .repos
+            .into_iter()
+            .filter(|r| r.policy == tracking::Policy::Track)
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+        let storage = Storage::open(self.home.storage())?;
+        let nid = self.nid()?;
+        let handle = self.clone();
+        let (sender, receiver) = chan::unbounded();
+
+        // Syncing waits on fetches to complete, so it's orchestrated from its own threads,
+        // to not block the service. Each worker syncs one repository at a time, which bounds
+        // the number of fetches we start, on top of the service's own fetch limits.
+        thread::spawn(&nid, "sync", move || {
+            let repos = rids.len();
+            let workers = options.parallelism.clamp(1, repos.max(1));
+            let (queue, jobs) = chan::unbounded();
+            let (results, progress) = chan::unbounded();
+            let (mut succeeded, mut skipped, mut failed) = (0, 0, 0);
+
+            for rid in rids {
+                queue.send(rid).ok();
+            }
+            drop(queue);
+
+            thread::scope(|scope| {
+                for i in 0..workers {
+                    let jobs = jobs.clone();
+                    let results = results.clone();
+                    let storage = storage.clone();
+                    let mut handle = handle.clone();
+
+                    thread::spawn_scoped(&nid, format!("sync#{i}"), scope, move || {
+                        for rid in jobs {
+                            let lines = sync_repo(rid, &options, &storage, &mut handle);
+                            // The receiver is gone if the client disconnected.
+                            if results.send(lines).is_err() {
+                                return;
+                            }
+                        }
+                    });
+                }
+                drop(results);
+
+                // Each repository is counted once: as failed if any of its actions failed,
+                // as succeeded if any succeeded otherwise, and as skipped if all were skipped.
+                for lines in progress {
+                    let results = lines
+                        .iter()
+                        .filter_map(|line| match line {
+                            SyncProgress::Repo { result, .. } => Some(result),
+                            SyncProgress::Summary { .. } => None,
+                        })
+                        .collect::<Vec<_>>();
+
+                    if results
+                        .iter()
+                        .any(|r| matches!(r, SyncResult::Failed { .. }))
+                    {
+                        failed += 1;
+                    } else if results
+                        .iter()
+                        .any(|r| matches!(r, SyncResult::Success { .. }))
+                    {
+                        succeeded += 1;
+                    } else {
+                        skipped += 1;
+                    }
+
+                    for line in lines {
+                        if sender.send(Ok(line)).is_err() {
+                            // Nb. Dropping the progress receiver stops the workers.
+                            return;
+                        }
+                    }
+                }
+                sender
+                    .send(Ok(SyncProgress::Summary {
+                        repos,
+                        succeeded,
+                        skipped,
+                        failed,
+                    }))
+                    .ok();
+            });
+        });
+
+        Ok(Box::new(receiver.into_iter()))
+    }
+
+    fn cob_update(&mut self, update: CobUpdate) -> Result<CobUpdated, Error> {
+        let storage = Storage::open(self.home.storage())?;
+        if !storage.path_of(&update.rid).exists() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("repository {} was not found in storage", update.rid),
+            )));
+        }
+        let repo = storage.repository_mut(update.rid)?;
+        let (object, entry) = cob::update_json(
+            &repo,
+            &update.type_name,
+            update.object.into(),
+            &update.message,
+            update.actions,
+            &self.signer,
+        )?;
+        log::debug!(
+            target: "node",
+            "Updated {} {object} in {} with entry {entry}", update.type_name, update.rid
+        );
+        self.announce_refs(update.rid)?;
+
+        Ok(CobUpdated { object, entry })
+    }
+
     fn untrack_repo(&mut self, id: Id) -> Result<bool, Error> {
         let (sender, receiver) = chan::bounded(1);
         self.command(service::Command::UntrackRepo(id, sender))?;
-        receiver.recv().map_err(Error::from)
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn watch_repo(&mut self, id: Id) -> Result<bool, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::WatchRepo(id, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn unwatch_repo(&mut self, id: Id) -> Result<bool, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::UnwatchRepo(id, sender))?;
+        receiver.recv()?.map_err(Error::from)
+    }
+
+    fn export_policies(&self) -> Result<tracking::Policies, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.export_policies()?).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let policies = receiver.recv()?;
+
+        Ok(policies)
+    }
+
+    fn import_policies(
+        &mut self,
+        policies: tracking::Policies,
+    ) -> Result<tracking::ImportSummary, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::ImportPolicies(policies, sender))?;
+        receiver.recv()?.map_err(Error::from)
     }
 
     fn announce_refs(&mut self, id: Id) -> Result<(), Error> {
-        self.command(service::Command::AnnounceRefs(id))
-            .map_err(Error::from)
+        let (sender, receiver) = chan::bounded(1);
+        self.command(service::Command::AnnounceRefs(id, sender))?;
+        receiver.recv()?.map_err(Error::from)
     }
 
     fn announce_inventory(&mut self) -> Result<(), Error> {
@@ -226,19 +625,226 @@ impl radicle::node::Handle for Handle {
     fn sync_inventory(&mut self) -> Result<bool, Error> {
         let (sender, receiver) = chan::bounded(1);
         self.command(service::Command::SyncInventory(sender))?;
-        receiver.recv().map_err(Error::from)
+        receiver.recv()?.map_err(Error::from)
     }
 
     fn subscribe(
         &self,
-        _timeout: time::Duration,
+        timeout: time::Duration,
     ) -> Result<Box<dyn Iterator<Item = Result<Event, io::Error>>>, Error> {
-        Ok(Box::new(self.events().into_iter().map(Ok)))
+        let events = self.events();
+
+        Ok(Box::new(std::iter::from_fn(move || {
+            match events.recv_timeout(timeout) {
+                Ok(event) => Some(Ok(event)),
+                Err(chan::RecvTimeoutError::Timeout) => {
+                    Some(Err(io::Error::from(io::ErrorKind::TimedOut)))
+                }
+                Err(chan::RecvTimeoutError::Disconnected) => None,
+            }
+        })))
+    }
+
+    fn validate(
+        &self,
+        rid: Option<Id>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Finding, io::Error>>>, Error> {
+        let storage = Storage::open(self.home.storage())?;
+        if let Some(rid) = rid {
+            if !storage.path_of(&rid).exists() {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("repository {rid} was not found in storage"),
+                )));
+            }
+        }
+        let nid = self.nid()?;
+        let (sender, receiver) = chan::unbounded();
+
+        // Validation only reads from storage, and can take a while for large repositories,
+        // so we don't involve the service, and run it on its own thread.
+        thread::spawn(&nid, "validate", move || {
+            let rids = match rid {
+                Some(rid) => vec![rid],
+                None => match storage.ids() {
+                    Ok(rids) => rids,
+                    Err(e) => {
+                        sender
+                            .send(Err(io::Error::new(io::ErrorKind::Other, e.to_string())))
+                            .ok();
+                        return;
+                    }
+                },
+            };
+            for rid in rids {
+                let findings = match storage.repository(rid).and_then(|repo| repo.findings()) {
+                    Ok(findings) => findings,
+                    Err(e) => vec![Finding::error(
+                        rid,
+                        None,
+                        format!("failed to validate repository: {e}"),
+                    )],
+                };
+                for finding in findings {
+                    // The receiver is gone if the client disconnected.
+                    if sender.send(Ok(finding)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Box::new(receiver.into_iter()))
+    }
+
+    fn repo_size(&self, rid: Option<Id>, refresh: bool) -> Result<Vec<stats::RepoStats>, Error> {
+        let storage = Storage::open(self.home.storage())?;
+        let mut stats = self.stats.get(&storage, rid, refresh)?;
+
+        // Fetch and announcement times are kept by the service, and are never stale.
+        let rids = stats.iter().map(|s| s.rid).collect::<Vec<_>>();
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            let activity = rids
+                .iter()
+                .map(|rid| state.repo_activity(rid))
+                .collect::<Vec<_>>();
+            sender.send(activity).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        for (s, activity) in stats.iter_mut().zip(receiver.recv()?) {
+            s.fetched = activity.fetched.map(|t| t.as_millis());
+            s.announced = activity.announced.map(|t| t.as_millis());
+        }
+        Ok(stats)
+    }
+
+    fn gossip_stats(&self) -> Result<GossipStats, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.gossip()).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let stats = receiver.recv()?;
+
+        Ok(stats)
+    }
+
+    fn metrics(&self) -> Result<Metrics, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.metrics().snapshot()).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let metrics = receiver.recv()?;
+
+        Ok(metrics)
+    }
+
+    fn recovery(&self) -> Result<Option<Recovery>, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.recovery().cloned()).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let recovery = receiver.recv()?;
+
+        Ok(recovery)
+    }
+
+    fn explain(&self, rid: Id, nid: NodeId) -> Result<Explanation, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.explain(&rid, &nid)?).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let explanation = receiver.recv()?;
+
+        Ok(explanation)
+    }
+
+    fn fetch_history(&self, rid: Id) -> Result<Vec<FetchRecord>, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.fetch_history(&rid)?).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let history = receiver.recv()?;
+
+        Ok(history)
+    }
+
+    fn events_since(&self, seq: u64) -> Result<events::Page, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.events_since(seq)?).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let page = receiver.recv()?;
+
+        Ok(page)
+    }
+
+    fn activity(
+        &self,
+        since: Option<Timestamp>,
+        limit: Option<usize>,
+    ) -> Result<Vec<activity::Entry>, Error> {
+        let (sender, receiver) = chan::bounded(1);
+        let query: Arc<QueryState> = Arc::new(move |state| {
+            sender.send(state.activity().since(since, limit)).ok();
+
+            Ok(())
+        });
+        let (err_sender, err_receiver) = chan::bounded(1);
+        self.command(service::Command::QueryState(query, err_sender))?;
+        err_receiver.recv()??;
+
+        let entries = receiver.recv()?;
+
+        Ok(entries)
     }
 
     fn sessions(&self) -> Result<Self::Sessions, Error> {
         let (sender, receiver) = chan::unbounded();
         let query: Arc<QueryState> = Arc::new(move |state| {
+            let now = *state.clock();
+            let timeout = state.config().limits.stale_timeout;
             let sessions = state
                 .sessions()
                 .iter()
@@ -246,6 +852,17 @@ impl radicle::node::Handle for Handle {
                     nid: *nid,
                     addr: s.addr.clone(),
                     state: s.state.clone(),
+                    version: s.version,
+                    queue: s.queued(now).into_iter().map(|q| q.rid).collect(),
+                    sent: s.sent,
+                    received: s.received,
+                    rtt: s.rtt.map(|rtt| rtt.as_millis() as u64),
+                    timeout: s
+                        .is_connected()
+                        .then(|| s.timeout(timeout).as_millis() as u64),
+                    user_disconnected: s.user_disconnected,
+                    attempts: s.attempts(),
+                    gave_up_until: s.gave_up_until,
                 })
                 .collect();
             sender.send(sessions).ok();
@@ -273,7 +890,8 @@ impl radicle::node::Handle for Handle {
         // Send a shutdown request to our own control socket. This is the only way to kill the
         // control thread gracefully. Since the control thread may have called this function,
         // the control socket may already be disconnected. Ignore errors.
-        UnixStream::connect(self.home.socket())
+        self.control
+            .connect()
             .and_then(|sock| Command::Shutdown.to_writer(sock))
             .ok();
 
@@ -0,0 +1,27 @@
+//! Checking repositories in storage after an unclean shutdown.
+//!
+//! Validating every repository can take a while on seeds, so it is done in the background,
+//! and each outcome is reported to the service.
+use crate::identity::Id;
+use crate::runtime::Handle;
+use crate::service;
+use crate::storage::git::Storage;
+use crate::storage::{ReadRepository, ReadStorage};
+
+/// Validate the given repositories, reporting each outcome with
+/// [`service::Command::RepoValidated`].
+pub fn check(storage: Storage, rids: Vec<Id>, handle: Handle) {
+    for rid in rids {
+        let result = match storage.repository(rid) {
+            Ok(repo) => repo.validate().map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        // Nb. This only fails if the node is shutting down.
+        if handle
+            .command(service::Command::RepoValidated(rid, result))
+            .is_err()
+        {
+            break;
+        }
+    }
+}
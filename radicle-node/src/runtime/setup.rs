@@ -0,0 +1,234 @@
+//! First-run setup of the node directory.
+//!
+//! Once all node stores are created, a marker file is written to the node directory. If the
+//! node is interrupted before that, the next start finds the marker missing, along with a
+//! mix of present, absent or empty stores, and redoes the setup.
+//!
+//! Another marker is kept in the node directory while the node runs, and removed when it
+//! shuts down cleanly. If it's found on startup, the node's stores are checked for
+//! consistency, as they may be out of date with storage.
+use std::path::Path;
+use std::{fs, io};
+
+use radicle::node::{
+    ADDRESS_DB_FILE, NODE_ANNOUNCEMENT_FILE, NODE_INITIALIZED_FILE, NODE_RUNNING_FILE,
+    ROUTING_DB_FILE, TRACKING_DB_FILE,
+};
+
+/// Databases created under the node directory on first run.
+pub const DATABASES: [&str; 3] = [ADDRESS_DB_FILE, ROUTING_DB_FILE, TRACKING_DB_FILE];
+
+/// Every SQLite database file starts with this header.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// State of the node directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Nothing was set up yet.
+    Fresh,
+    /// Setup was completed.
+    Initialized,
+    /// A previous setup didn't complete.
+    Incomplete,
+}
+
+/// Find out the state of the given node directory.
+pub fn state(dir: &Path) -> io::Result<State> {
+    if dir.join(NODE_INITIALIZED_FILE).exists() {
+        return Ok(State::Initialized);
+    }
+    let mut missing = 0;
+    for db in DATABASES {
+        match is_database(&dir.join(db)) {
+            Ok(true) => {}
+            Ok(false) => return Ok(State::Incomplete),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => missing += 1,
+            Err(e) => return Err(e),
+        }
+    }
+    if missing == DATABASES.len() {
+        Ok(State::Fresh)
+    } else if missing == 0 {
+        // Setup of nodes that predate the marker.
+        Ok(State::Initialized)
+    } else {
+        Ok(State::Incomplete)
+    }
+}
+
+/// Prepare the node directory for its stores to be opened, and return the state it was
+/// found in. Leftovers of an incomplete setup are removed, so that setup can be redone.
+///
+/// Databases that are intact are always kept, as they may have been written to by other
+/// processes, eg. to add tracking policies.
+pub fn prepare(dir: &Path) -> io::Result<State> {
+    let state = state(dir)?;
+    if state != State::Incomplete {
+        return Ok(state);
+    }
+    log::warn!(target: "node", "Incomplete previous initialization detected; reinitializing..");
+
+    for db in DATABASES {
+        let path = dir.join(db);
+
+        if let Ok(false) = is_database(&path) {
+            log::warn!(target: "node", "Removing incomplete database {}..", path.display());
+
+            for suffix in ["", "-journal", "-wal", "-shm"] {
+                remove(&dir.join(format!("{db}{suffix}")))?;
+            }
+        }
+    }
+    remove(&dir.join(NODE_ANNOUNCEMENT_FILE))?;
+
+    Ok(state)
+}
+
+/// Mark the setup of the node directory as complete. To be called once all stores are
+/// opened.
+pub fn complete(dir: &Path) -> io::Result<()> {
+    let marker = dir.join(NODE_INITIALIZED_FILE);
+    if marker.exists() {
+        return Ok(());
+    }
+    let tmp = dir.join(format!("{NODE_INITIALIZED_FILE}.tmp"));
+
+    fs::write(&tmp, [])?;
+    fs::rename(tmp, marker)
+}
+
+/// Mark the node as running, and return whether it was already marked as such, ie. whether
+/// it didn't shut down cleanly the last time it ran.
+pub fn running(dir: &Path) -> io::Result<bool> {
+    let marker = dir.join(NODE_RUNNING_FILE);
+    if marker.exists() {
+        return Ok(true);
+    }
+    // Nb. The marker must reach the disk before anything else does, or we won't know
+    // about a power loss.
+    fs::File::create(marker)?.sync_all()?;
+
+    Ok(false)
+}
+
+/// Mark the node as stopped. To be called once the node shut down cleanly.
+pub fn stopped(dir: &Path) -> io::Result<()> {
+    remove(&dir.join(NODE_RUNNING_FILE))
+}
+
+/// Check whether the file at the given path is a database with at least a header.
+fn is_database(path: &Path) -> io::Result<bool> {
+    let mut header = [0; SQLITE_HEADER.len()];
+    let mut file = fs::File::open(path)?;
+
+    match io::Read::read_exact(&mut file, &mut header) {
+        Ok(()) => Ok(header == SQLITE_HEADER),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remove a file, if it exists.
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use radicle::node::address;
+    use radicle::node::routing;
+    use radicle::node::tracking::{self, store::Config as Tracking};
+    use radicle::test::arbitrary;
+
+    /// Setup the node directory the way the runtime does.
+    fn init(dir: &Path) -> State {
+        let state = prepare(dir).unwrap();
+
+        address::Book::open(dir.join(ADDRESS_DB_FILE)).unwrap();
+        routing::Table::open(dir.join(ROUTING_DB_FILE)).unwrap();
+        Tracking::open(dir.join(TRACKING_DB_FILE)).unwrap();
+        complete(dir).unwrap();
+
+        state
+    }
+
+    #[test]
+    fn test_setup_fresh() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        assert_eq!(init(tmp.path()), State::Fresh);
+        assert_eq!(state(tmp.path()).unwrap(), State::Initialized);
+    }
+
+    #[test]
+    fn test_setup_incomplete() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        init(dir);
+
+        // Simulate a crash halfway through setup.
+        fs::write(dir.join(NODE_ANNOUNCEMENT_FILE), b"announcement").unwrap();
+        fs::remove_file(dir.join(ROUTING_DB_FILE)).unwrap();
+        fs::write(dir.join(ADDRESS_DB_FILE), []).unwrap();
+        fs::remove_file(dir.join(NODE_INITIALIZED_FILE)).unwrap();
+
+        assert_eq!(state(dir).unwrap(), State::Incomplete);
+        assert_eq!(init(dir), State::Incomplete);
+        assert_eq!(state(dir).unwrap(), State::Initialized);
+        assert!(!dir.join(NODE_ANNOUNCEMENT_FILE).exists());
+
+        for db in DATABASES {
+            assert!(is_database(&dir.join(db)).unwrap(), "{db}");
+        }
+    }
+
+    #[test]
+    fn test_unclean_shutdown() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        init(dir);
+
+        assert!(!running(dir).unwrap(), "first start");
+        stopped(dir).unwrap();
+        assert!(!running(dir).unwrap(), "clean restart");
+        // The node is killed, and started again.
+        assert!(running(dir).unwrap(), "unclean restart");
+        stopped(dir).unwrap();
+        assert!(!dir.join(NODE_RUNNING_FILE).exists());
+    }
+
+    #[test]
+    fn test_setup_initialized_untouched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let rid = arbitrary::gen(1);
+        init(dir);
+
+        Tracking::open(dir.join(TRACKING_DB_FILE))
+            .unwrap()
+            .track_repo(&rid, tracking::Scope::All)
+            .unwrap();
+        fs::write(dir.join(NODE_ANNOUNCEMENT_FILE), b"announcement").unwrap();
+
+        // Homes set up before the marker existed are left alone too.
+        for marker in [true, false] {
+            if !marker {
+                fs::remove_file(dir.join(NODE_INITIALIZED_FILE)).unwrap();
+            }
+            assert_eq!(init(dir), State::Initialized);
+            assert_eq!(
+                fs::read(dir.join(NODE_ANNOUNCEMENT_FILE)).unwrap(),
+                b"announcement"
+            );
+            assert!(Tracking::open(dir.join(TRACKING_DB_FILE))
+                .unwrap()
+                .is_repo_tracked(&rid)
+                .unwrap());
+        }
+    }
+}
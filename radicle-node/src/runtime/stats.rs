@@ -0,0 +1,137 @@
+//! Background refresh of cached repository statistics.
+//!
+//! Statistics are used by seed operators to find out which repositories use up disk space.
+//! Computing them for all repositories is slow, so they are computed by a background
+//! worker, and possibly stale statistics are returned while they are being refreshed.
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel as chan;
+use localtime::{LocalDuration, LocalTime};
+
+use crate::identity::Id;
+use crate::runtime::thread;
+use crate::service::NodeId;
+use crate::storage::git::stats::{self, RepoStats};
+use crate::storage::git::Storage;
+use crate::storage::{self, ReadStorage};
+
+/// Time after which cached statistics are checked again.
+pub const STATS_TTL: LocalDuration = LocalDuration::from_mins(10);
+
+/// Repository statistics cache, refreshed in the background.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    cache: Arc<stats::Cache>,
+    /// Repositories queued for a refresh by the worker.
+    queued: Arc<Mutex<HashSet<Id>>>,
+    /// Sends repositories to refresh to the worker. The worker stops when all senders are
+    /// dropped.
+    queue: chan::Sender<Id>,
+    ttl: LocalDuration,
+}
+
+impl Stats {
+    /// Spawn the worker, and queue all repositories in storage, so that statistics are
+    /// usually available by the time they are asked for.
+    pub fn spawn(nid: &NodeId, storage: Storage, ttl: LocalDuration) -> Self {
+        let (queue, work) = chan::unbounded::<Id>();
+        let cache = Arc::new(stats::Cache::default());
+        let queued = Arc::new(Mutex::new(HashSet::new()));
+        let stats = Self {
+            cache: cache.clone(),
+            queued: queued.clone(),
+            queue,
+            ttl,
+        };
+
+        match storage.inventory() {
+            Ok(inventory) => {
+                for rid in inventory {
+                    stats.refresh(rid);
+                }
+            }
+            Err(e) => log::error!(target: "node", "Error listing repositories for stats: {e}"),
+        }
+        thread::spawn(nid, "stats", move || {
+            for rid in work {
+                queued.lock().unwrap().remove(&rid);
+
+                let result = storage
+                    .repository(rid)
+                    .and_then(|repo| cache.get(&repo, LocalTime::now().as_millis()));
+
+                match result {
+                    Ok(_) => {}
+                    Err(e) if e.is_not_found() => cache.retain(|id| *id != rid),
+                    Err(e) => {
+                        log::warn!(target: "node", "Failed to compute stats of repository {rid}: {e}")
+                    }
+                }
+            }
+        });
+        stats
+    }
+
+    /// Get the statistics of a repository, or of all repositories in storage if `None` is
+    /// given. Stale statistics are returned as they are, and refreshed in the background.
+    /// Statistics that aren't available yet, or for which a refresh is requested, are
+    /// brought up to date before returning.
+    pub fn get(
+        &self,
+        storage: &Storage,
+        rid: Option<Id>,
+        refresh: bool,
+    ) -> Result<Vec<RepoStats>, storage::Error> {
+        if let Some(rid) = rid {
+            return Ok(vec![self.repo(storage, rid, refresh)?]);
+        }
+        let inventory = storage.inventory()?.into_iter().collect::<HashSet<_>>();
+        let mut stats = Vec::with_capacity(inventory.len());
+
+        // Repositories removed from storage are evicted here and by the worker.
+        self.cache.retain(|rid| inventory.contains(rid));
+
+        for rid in inventory {
+            // Nb. A single broken repository shouldn't hide the others.
+            match self.repo(storage, rid, false) {
+                Ok(s) => stats.push(s),
+                Err(e) => {
+                    log::warn!(target: "node", "Failed to compute stats of repository {rid}: {e}")
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Get the statistics of a single repository.
+    fn repo(&self, storage: &Storage, rid: Id, refresh: bool) -> Result<RepoStats, storage::Error> {
+        let now = LocalTime::now().as_millis();
+
+        if !refresh {
+            if let Some((stats, checked_at)) = self.cache.cached(&rid) {
+                if now.saturating_sub(checked_at) as u128 >= self.ttl.as_millis() {
+                    self.refresh(rid);
+                }
+                return Ok(stats);
+            }
+        }
+        let repo = match storage.repository(rid) {
+            Ok(repo) => repo,
+            Err(e) => {
+                if e.is_not_found() {
+                    self.cache.retain(|id| *id != rid);
+                }
+                return Err(e);
+            }
+        };
+        self.cache.get(&repo, now)
+    }
+
+    /// Queue a repository for a refresh, unless it's already queued.
+    fn refresh(&self, rid: Id) {
+        if self.queued.lock().unwrap().insert(rid) {
+            self.queue.send(rid).ok();
+        }
+    }
+}
@@ -1,46 +1,66 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::collapsible_match)]
 #![allow(clippy::collapsible_if)]
+pub mod announcer;
+pub mod backoff;
+pub mod bandwidth;
 pub mod filter;
 pub mod io;
 pub mod limitter;
 pub mod message;
+pub mod metrics;
 pub mod session;
 pub mod tracking;
 
 use std::collections::hash_map::Entry;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crossbeam_channel as chan;
+use cyphernet::addr::Host as _;
 use fastrand::Rng;
 use localtime::{LocalDuration, LocalTime};
 use log::*;
-use nonempty::NonEmpty;
 
 use radicle::node::address;
 use radicle::node::address::{AddressBook, KnownAddress};
+use radicle::node::config;
 use radicle::node::config::PeerConfig;
 use radicle::node::ConnectOptions;
 
 use crate::crypto;
 use crate::crypto::{Signer, Verified};
-use crate::identity::IdentityError;
+use crate::git;
 use crate::identity::{Doc, Id};
+use crate::identity::{Identity, IdentityError};
+use crate::node::activity::{self, Activity};
+use crate::node::events;
 use crate::node::routing;
 use crate::node::routing::InsertResult;
-use crate::node::{Address, Alias, Features, FetchResult, HostName, Seed, Seeds};
+use crate::node::watermark;
+use crate::node::watermark::Store as _;
+use crate::node::{
+    Address, Alias, Explanation, Features, FetchAttempt, FetchOutcome, FetchRecord, FetchResult,
+    GossipStats, HostName, InspectSource, Inspection, MessageKind, RecoveredDatabase, Recovery,
+    Seed, Seeds, TrackDryRun, TrackFetch, Tracked,
+};
 use crate::prelude::*;
 use crate::runtime::Emitter;
+use crate::service::backoff::Backoff;
 use crate::service::message::{Announcement, AnnouncementMessage, Ping};
-use crate::service::message::{NodeAnnouncement, RefsAnnouncement};
+use crate::service::message::{AnnouncementKinds, AnnouncementRequest};
+use crate::service::message::{CobsAnnouncement, NodeAnnouncement, RefsAnnouncement};
+use crate::service::metrics::DropReason;
 use crate::service::tracking::{store::Write, Scope};
 use crate::storage;
 use crate::storage::{Namespaces, ReadStorage};
 use crate::storage::{ReadRepository, RefUpdate};
-use crate::worker::FetchError;
+use crate::wire;
+use crate::worker::{FetchError, Transferred};
 use crate::Link;
 
 pub use crate::node::events::{Event, Events};
@@ -48,9 +68,10 @@ pub use crate::node::{config::Network, Config, NodeId};
 pub use crate::service::message::{Message, ZeroBytes};
 pub use crate::service::session::Session;
 
+use self::announcer::{Announcer, Job};
 use self::gossip::Gossip;
 use self::io::Outbox;
-use self::limitter::RateLimiter;
+use self::limitter::{RateLimit, RateLimiter};
 use self::message::InventoryAnnouncement;
 use self::tracking::NamespacesError;
 
@@ -62,10 +83,6 @@ pub const ANNOUNCE_INTERVAL: LocalDuration = LocalDuration::from_mins(60);
 pub const SYNC_INTERVAL: LocalDuration = LocalDuration::from_secs(60);
 /// How often to run the "prune" task.
 pub const PRUNE_INTERVAL: LocalDuration = LocalDuration::from_mins(30);
-/// Duration to wait on an unresponsive peer before dropping its connection.
-pub const STALE_CONNECTION_TIMEOUT: LocalDuration = LocalDuration::from_mins(2);
-/// How much time should pass after a peer was last active for a *ping* to be sent.
-pub const KEEP_ALIVE_DELTA: LocalDuration = LocalDuration::from_mins(1);
 /// Maximum time difference between the local time, and an announcement timestamp.
 pub const MAX_TIME_DELTA: LocalDuration = LocalDuration::from_mins(60);
 /// Maximum attempts to connect to a peer before we give up.
@@ -76,11 +93,71 @@ pub const SUBSCRIBE_BACKLOG_DELTA: LocalDuration = LocalDuration::from_mins(60);
 pub const MIN_RECONNECTION_DELTA: LocalDuration = LocalDuration::from_secs(3);
 /// Maximum amount of time to wait before reconnecting to a peer.
 pub const MAX_RECONNECTION_DELTA: LocalDuration = LocalDuration::from_mins(60);
+/// Number of attempts to reconnect to a persistent peer, after which we give up on it until
+/// the session retention period passes.
+pub const MAX_RECONNECTION_ATTEMPTS: usize = 16;
 /// Connection retry delta used for ephemeral peers that failed to connect previously.
 pub const CONNECTION_RETRY_DELTA: LocalDuration = LocalDuration::from_mins(10);
+/// Maximum number of times a fetch that failed for a transient reason is retried.
+pub const MAX_FETCH_RETRIES: usize = 3;
+/// Minimum amount of time to wait before retrying a failed fetch.
+pub const MIN_FETCH_RETRY_DELTA: LocalDuration = LocalDuration::from_secs(3);
+/// Maximum amount of time to wait before retrying a failed fetch.
+pub const MAX_FETCH_RETRY_DELTA: LocalDuration = LocalDuration::from_mins(10);
+/// Maximum amount of time a user-requested fetch waits for its seed to be connected.
+pub const FETCH_CONNECT_TIMEOUT: LocalDuration = LocalDuration::from_secs(30);
+/// Number of refs announcement entries with an invalid signature a node can announce
+/// before its refs announcements are dropped.
+pub const MAX_INVALID_REFS: usize = 16;
+/// Time after which a refs announcement entry with an invalid signature is forgiven.
+pub const INVALID_REFS_DECAY: LocalDuration = LocalDuration::from_mins(15);
+/// Minimum amount of time a misbehaving seed is avoided for.
+pub const MIN_MISBEHAVIOR_BACKOFF: LocalDuration = LocalDuration::from_mins(60);
+/// Maximum amount of time a misbehaving seed is avoided for.
+pub const MAX_MISBEHAVIOR_BACKOFF: LocalDuration = LocalDuration::from_mins(24 * 60);
+/// Maximum number of announcements of a subscription backlog sent at once. The rest is
+/// sent in further chunks, so that a large backlog doesn't hold up other peers.
+pub const BACKLOG_CHUNK_SIZE: usize = 64;
+/// Time to wait before sending the next chunk of a subscription backlog.
+pub const BACKLOG_CHUNK_INTERVAL: LocalDuration = LocalDuration::from_millis(10);
+/// Rate at which announcement requests are answered, per peer host.
+pub const ANNOUNCEMENT_REQUEST_RATE: RateLimit = RateLimit {
+    fill_rate: 0.1,
+    capacity: 8,
+};
+/// Delay before reconnecting to a persistent peer, by number of connection attempts.
+pub const RECONNECTION_BACKOFF: Backoff = Backoff {
+    base: LocalDuration::from_secs(1),
+    factor: 2,
+    jitter: 0.,
+    min: MIN_RECONNECTION_DELTA,
+    max: MAX_RECONNECTION_DELTA,
+    max_attempts: Some(MAX_RECONNECTION_ATTEMPTS),
+};
+/// Delay before retrying a fetch that failed for a transient reason, by number of
+/// failed attempts.
+pub const FETCH_RETRY_BACKOFF: Backoff = Backoff {
+    base: LocalDuration::from_secs(1),
+    factor: 2,
+    jitter: 0.,
+    min: MIN_FETCH_RETRY_DELTA,
+    max: MAX_FETCH_RETRY_DELTA,
+    max_attempts: Some(MAX_FETCH_RETRIES),
+};
+/// How long a misbehaving seed is avoided for, by number of previous offenses.
+pub const MISBEHAVIOR_BACKOFF: Backoff = Backoff {
+    base: MIN_MISBEHAVIOR_BACKOFF,
+    factor: 2,
+    jitter: 0.,
+    min: MIN_MISBEHAVIOR_BACKOFF,
+    max: MAX_MISBEHAVIOR_BACKOFF,
+    max_attempts: None,
+};
 
 /// Maximum external address limit imposed by message size limits.
 pub use message::ADDRESS_LIMIT;
+/// Maximum number of collaborative object types imposed by message size limits.
+pub use message::COB_LIMIT;
 /// Maximum inventory limit imposed by message size limits.
 pub use message::INVENTORY_LIMIT;
 /// Maximum number of project git references imposed by message size limits.
@@ -103,6 +180,130 @@ impl SyncedRouting {
     }
 }
 
+/// A fetch that failed for a transient reason, and is scheduled to be retried.
+#[derive(Debug, Clone)]
+struct FetchRetry {
+    /// Node we last attempted to fetch from.
+    from: NodeId,
+    /// Number of failed attempts so far.
+    attempts: usize,
+    /// When to retry the fetch, or to check back on a retry in progress.
+    retry_at: Option<LocalTime>,
+}
+
+/// A tracking dry-run waiting for the refs of a repository to be listed by a seed.
+#[derive(Debug)]
+struct DryRunRequest {
+    /// Summary to send back, once completed with the listed refs.
+    summary: TrackDryRun,
+    /// Namespaces that would be fetched with the requested scope.
+    namespaces: Namespaces,
+    /// Channel on which the summary is sent.
+    resp: chan::Sender<TrackDryRun>,
+}
+
+/// Channel on which the result of a user-requested fetch is sent.
+type FetchResponder = chan::Sender<Result<FetchResult, CommandError>>;
+
+/// Fetches requested by users, eg. via the control socket, which are waiting for results.
+/// Several users may wait on the same fetch.
+#[derive(Debug, Default)]
+struct FetchRequests(HashMap<(Id, NodeId), Vec<FetchResponder>>);
+
+impl FetchRequests {
+    /// Number of outstanding requests, counting each waiting user.
+    fn len(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    /// Check whether a user is waiting on a fetch.
+    fn contains(&self, rid: &Id, nid: &NodeId) -> bool {
+        self.0.contains_key(&(*rid, *nid))
+    }
+
+    /// Add a user waiting on a fetch. Returns `false` if another user was already waiting
+    /// on the same fetch, in which case they share the result.
+    fn insert(&mut self, rid: Id, nid: NodeId, resp: FetchResponder) -> bool {
+        match self.0.entry((rid, nid)) {
+            Entry::Occupied(mut e) => {
+                e.get_mut().push(resp);
+                false
+            }
+            Entry::Vacant(e) => {
+                e.insert(vec![resp]);
+                true
+            }
+        }
+    }
+
+    /// Send the result of a fetch to all users waiting on it. Returns `false` if no one
+    /// was waiting.
+    fn reply(&mut self, rid: &Id, nid: &NodeId, result: FetchResult) -> bool {
+        let Some(resps) = self.0.remove(&(*rid, *nid)) else {
+            return false;
+        };
+        for resp in resps {
+            if resp.send(Ok(result.clone())).is_err() {
+                debug!(target: "service", "Fetch requester for {rid} is gone; dropping result..");
+            }
+        }
+        true
+    }
+
+    /// Fail a fetch for all users waiting on it, before it was initiated.
+    fn fail(&mut self, rid: &Id, nid: &NodeId, err: impl Fn() -> CommandError) -> bool {
+        let Some(resps) = self.0.remove(&(*rid, *nid)) else {
+            return false;
+        };
+        for resp in resps {
+            resp.send(Err(err())).ok();
+        }
+        true
+    }
+}
+
+/// A seed that misbehaved, eg. by going over the fetch size limit, and is avoided.
+#[derive(Debug, Clone)]
+struct Misbehavior {
+    /// Number of offenses so far.
+    offenses: usize,
+    /// Until when the seed is avoided.
+    until: LocalTime,
+}
+
+/// Timestamps of our last announcements. Used to keep the timestamps of our announcements
+/// strictly increasing, even if the clock didn't advance since the previous announcement,
+/// since peers discard announcements that aren't newer than the last one they received.
+#[derive(Debug, Default)]
+struct AnnouncementTimestamps {
+    /// Timestamp of our last inventory announcement.
+    inventory: Timestamp,
+    /// Timestamp of our last refs announcement, per repository.
+    refs: HashMap<Id, Timestamp>,
+}
+
+impl AnnouncementTimestamps {
+    /// Get the timestamp of a new inventory announcement.
+    fn inventory(&mut self, now: Timestamp) -> Timestamp {
+        Self::next(&mut self.inventory, now)
+    }
+
+    /// Get the timestamp of a new refs announcement of the given repository.
+    fn refs(&mut self, rid: Id, now: Timestamp) -> Timestamp {
+        Self::next(self.refs.entry(rid).or_default(), now)
+    }
+
+    /// Forget timestamps that are in the past: new timestamps will be greater anyway.
+    fn prune(&mut self, now: Timestamp) {
+        self.refs.retain(|_, t| *t >= now);
+    }
+
+    fn next(last: &mut Timestamp, now: Timestamp) -> Timestamp {
+        *last = now.max(*last + 1);
+        *last
+    }
+}
+
 /// General service error.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -114,6 +315,12 @@ pub enum Error {
     Tracking(#[from] tracking::Error),
     #[error("namespaces error: {0}")]
     Namespaces(#[from] NamespacesError),
+    #[error(transparent)]
+    Address(#[from] address::Error),
+    #[error(transparent)]
+    Watermark(#[from] watermark::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
 }
 
 /// Function used to query internal service state.
@@ -122,27 +329,75 @@ pub type QueryState = dyn Fn(&dyn ServiceState) -> Result<(), CommandError> + Se
 /// Commands sent to the service by the operator.
 pub enum Command {
     /// Announce repository references for given repository to peers.
-    AnnounceRefs(Id),
+    AnnounceRefs(Id, chan::Sender<Result<(), CommandError>>),
     /// Announce local repositories to peers.
     AnnounceInventory,
     /// Announce local inventory to peers.
-    SyncInventory(chan::Sender<bool>),
+    SyncInventory(chan::Sender<Result<bool, CommandError>>),
     /// Connect to node with the given address.
     Connect(NodeId, Address, ConnectOptions),
     /// Disconnect from node.
     Disconnect(NodeId),
+    /// Add a node discovered on the local network to the address book.
+    Discovered(NodeId, Address, Features, Alias),
+    /// Report the outcome of checking a repository in storage after an unclean shutdown.
+    RepoValidated(Id, Result<(), String>),
     /// Lookup seeds for the given repository in the routing table.
     Seeds(Id, chan::Sender<Seeds>),
     /// Fetch the given repository from the network.
-    Fetch(Id, NodeId, chan::Sender<FetchResult>),
-    /// Track the given repository.
-    TrackRepo(Id, Scope, chan::Sender<bool>),
+    Fetch(Id, NodeId, chan::Sender<Result<FetchResult, CommandError>>),
+    /// Fetch only the identity branch and the delegates' namespaces of the given
+    /// repository, and mark it as partially replicated.
+    FetchMinimal(Id, NodeId, chan::Sender<Result<FetchResult, CommandError>>),
+    /// Track the given repository, recording why it is in our inventory.
+    TrackRepo(
+        Id,
+        Scope,
+        tracking::Origin,
+        chan::Sender<Result<bool, CommandError>>,
+    ),
+    /// Track the given repository, only fetching the refs matched by the given filter.
+    TrackRepoFilter(
+        Id,
+        Scope,
+        tracking::RefFilter,
+        chan::Sender<Result<bool, CommandError>>,
+    ),
+    /// Track the given repository, and fetch it from known seeds right away.
+    TrackRepoFetch(Id, Scope, chan::Sender<Result<Tracked, CommandError>>),
+    /// Find out what would be fetched if the given repository was tracked.
+    TrackRepoDryRun(Id, Scope, chan::Sender<TrackDryRun>),
     /// Untrack the given repository.
-    UntrackRepo(Id, chan::Sender<bool>),
+    UntrackRepo(Id, chan::Sender<Result<bool, CommandError>>),
+    /// Watch the given repository.
+    WatchRepo(Id, chan::Sender<Result<bool, CommandError>>),
+    /// Stop watching the given repository.
+    UnwatchRepo(Id, chan::Sender<Result<bool, CommandError>>),
     /// Track the given node.
-    TrackNode(NodeId, Option<Alias>, chan::Sender<bool>),
+    TrackNode(
+        NodeId,
+        Option<Alias>,
+        chan::Sender<Result<bool, CommandError>>,
+    ),
     /// Untrack the given node.
-    UntrackNode(NodeId, chan::Sender<bool>),
+    UntrackNode(NodeId, chan::Sender<Result<bool, CommandError>>),
+    /// Import the given tracking policies.
+    ImportPolicies(
+        tracking::Policies,
+        chan::Sender<Result<tracking::ImportSummary, CommandError>>,
+    ),
+    /// Get the verified identity of the given repository.
+    Inspect(Id, chan::Sender<Result<Inspection, CommandError>>),
+    /// Change one of the runtime limits.
+    SetLimit(
+        String,
+        serde_json::Value,
+        chan::Sender<Result<(), CommandError>>,
+    ),
+    /// Add a peer to the configuration, replacing its previous entry.
+    AddPeer(config::PeerEntry, chan::Sender<bool>),
+    /// Remove a peer from the configuration.
+    RemovePeer(NodeId, chan::Sender<bool>),
     /// Query the internal service state.
     QueryState(Arc<QueryState>, chan::Sender<Result<(), CommandError>>),
 }
@@ -150,17 +405,39 @@ pub enum Command {
 impl fmt::Debug for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::AnnounceRefs(id) => write!(f, "AnnounceRefs({id})"),
+            Self::AnnounceRefs(id, _) => write!(f, "AnnounceRefs({id})"),
             Self::AnnounceInventory => write!(f, "AnnounceInventory"),
             Self::SyncInventory(_) => write!(f, "SyncInventory(..)"),
             Self::Connect(id, addr, opts) => write!(f, "Connect({id}, {addr}, {opts:?})"),
             Self::Disconnect(id) => write!(f, "Disconnect({id})"),
+            Self::Discovered(id, addr, _, _) => write!(f, "Discovered({id}, {addr})"),
+            Self::RepoValidated(rid, result) => write!(f, "RepoValidated({rid}, {result:?})"),
             Self::Seeds(id, _) => write!(f, "Seeds({id})"),
             Self::Fetch(id, node, _) => write!(f, "Fetch({id}, {node})"),
-            Self::TrackRepo(id, scope, _) => write!(f, "TrackRepo({id}, {scope})"),
+            Self::FetchMinimal(id, node, _) => write!(f, "FetchMinimal({id}, {node})"),
+            Self::TrackRepo(id, scope, origin, _) => {
+                write!(f, "TrackRepo({id}, {scope}, {origin})")
+            }
+            Self::TrackRepoFilter(id, scope, filter, _) => {
+                write!(f, "TrackRepoFilter({id}, {scope}, {filter})")
+            }
+            Self::TrackRepoFetch(id, scope, _) => write!(f, "TrackRepoFetch({id}, {scope})"),
+            Self::TrackRepoDryRun(id, scope, _) => write!(f, "TrackRepoDryRun({id}, {scope})"),
             Self::UntrackRepo(id, _) => write!(f, "UntrackRepo({id})"),
+            Self::WatchRepo(id, _) => write!(f, "WatchRepo({id})"),
+            Self::UnwatchRepo(id, _) => write!(f, "UnwatchRepo({id})"),
             Self::TrackNode(id, _, _) => write!(f, "TrackNode({id})"),
             Self::UntrackNode(id, _) => write!(f, "UntrackNode({id})"),
+            Self::ImportPolicies(policies, _) => write!(
+                f,
+                "ImportPolicies({} repo(s), {} node(s))",
+                policies.repos.len(),
+                policies.nodes.len()
+            ),
+            Self::Inspect(id, _) => write!(f, "Inspect({id})"),
+            Self::SetLimit(name, value, _) => write!(f, "SetLimit({name}, {value})"),
+            Self::AddPeer(peer, _) => write!(f, "AddPeer({}, {})", peer.id, peer.address),
+            Self::RemovePeer(id, _) => write!(f, "RemovePeer({id})"),
             Self::QueryState { .. } => write!(f, "QueryState(..)"),
         }
     }
@@ -175,6 +452,30 @@ pub enum CommandError {
     Routing(#[from] routing::Error),
     #[error(transparent)]
     Tracking(#[from] tracking::Error),
+    #[error(transparent)]
+    Events(#[from] events::store::Error),
+    #[error("identity invalid: {0}")]
+    InvalidIdentity(#[from] IdentityError),
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+    #[error("repository {0} was not found in storage, or on any connected seed")]
+    NotFound(Id),
+    #[error("disconnected from {0}: {1}")]
+    Disconnected(NodeId, String),
+    #[error("failed to fetch identity from {0}: {1}")]
+    InspectFailed(NodeId, String),
+    #[error(transparent)]
+    Limit(#[from] config::LimitError),
+    #[error("nothing to announce for {0}: we have no refs in this repository, eg. because it was never forked")]
+    NothingToAnnounce(Id),
+    #[error("too many fetches in progress: at most {0} fetch request(s) can be outstanding")]
+    TooManyFetches(usize),
+    #[error("invalid address: {0}")]
+    InvalidAddress(#[from] address::AddressError),
+    #[error("repository {0} is not tracked")]
+    NotTracked(Id),
+    #[error(transparent)]
+    Service(#[from] Error),
 }
 
 #[derive(Debug)]
@@ -191,6 +492,20 @@ pub struct Service<R, A, S, G> {
     addresses: A,
     /// Tracking policy configuration.
     tracking: tracking::Config<Write>,
+    /// Durable log of the events of the kinds configured in [`Config::event_log`].
+    event_log: events::Log,
+    /// In-memory log of recent activity, bounded by [`Config::activity_log`].
+    activity_log: activity::Log,
+    /// Gossip announcements sent since they were last recorded in the activity log.
+    announcements_sent: usize,
+    /// Gossip announcements received since they were last recorded in the activity log.
+    announcements_received: usize,
+    /// Service metrics, recorded if enabled in [`Config::metrics`].
+    metrics: metrics::Metrics,
+    /// Signed refs we last fetched and announced, per repository namespace.
+    watermarks: watermark::Table,
+    /// Our collaborative object types announcement.
+    cobs: CobsAnnouncement,
     /// State relating to gossip.
     gossip: Gossip,
     /// Peer sessions, currently or recently connected.
@@ -199,14 +514,72 @@ pub struct Service<R, A, S, G> {
     clock: LocalTime,
     /// I/O outbox.
     outbox: Outbox,
+    /// Gossip bandwidth used, per message kind.
+    bandwidth: bandwidth::Accounting,
     /// Cached local node announcement.
     node: NodeAnnouncement,
     /// Source of entropy.
     rng: Rng,
     /// Fetch requests initiated by user, which are waiting for results.
-    fetch_reqs: HashMap<(Id, NodeId), chan::Sender<FetchResult>>,
+    fetch_reqs: FetchRequests,
+    /// User-requested fetches that should only fetch the delegates' namespaces, and
+    /// haven't started yet.
+    minimal_reqs: HashSet<(Id, NodeId)>,
+    /// Minimal fetches in progress.
+    minimal_fetches: HashSet<(Id, NodeId)>,
+    /// Tracking dry-runs initiated by the user, which are waiting for the remote's refs.
+    dry_run_reqs: HashMap<(Id, NodeId), Vec<DryRunRequest>>,
+    /// Identity inspections initiated by the user, which are waiting for the remote's
+    /// identity branch. Several users may wait on the same inspection.
+    inspect_reqs: HashMap<(Id, NodeId), Vec<chan::Sender<Result<Inspection, CommandError>>>>,
+    /// Fetches of newly tracked repositories, which are waiting for us to connect to a seed.
+    pending_fetches: HashMap<NodeId, HashSet<Id>>,
+    /// Deadlines of user-requested fetches waiting for us to connect to their seed. If the
+    /// seed isn't connected by then, the fetch fails.
+    fetch_deadlines: HashMap<(Id, NodeId), LocalTime>,
+    /// Timestamps of our last announcements.
+    timestamps: AnnouncementTimestamps,
+    /// Our last signed refs announcement of each repository. Reused to answer announcement
+    /// requests, instead of signing a new announcement each time.
+    announced_refs: HashMap<Id, Announcement>,
+    /// Fetches not initiated by the user, which failed for a transient reason
+    /// and should be retried.
+    fetch_retries: HashMap<Id, FetchRetry>,
+    /// Seeds that misbehaved, which we don't reconnect to until their backoff expires.
+    backoffs: HashMap<NodeId, Misbehavior>,
+    /// Peers disconnected by the user, which we don't automatically reconnect to until
+    /// the given time, unless the user connects to them again.
+    user_disconnects: HashMap<NodeId, LocalTime>,
+    /// Mirrored repositories missing from the inventories of the nodes we mirror, and
+    /// since when.
+    mirror_missing: HashMap<Id, LocalTime>,
+    /// Missing repositories left over by the last inventory sync, because of the fetch
+    /// limit. They are fetched first by the next sync.
+    missing_backlog: Vec<Id>,
+    /// How long the last fetch of each tracked repository took. Used to order queued
+    /// fetches.
+    fetch_estimates: HashMap<Id, LocalDuration>,
+    /// When repositories were last fetched and announced.
+    activity: HashMap<Id, RepoActivity>,
+    /// Last fetch of each repository from each node.
+    fetches: HashMap<(Id, NodeId), FetchAttempt>,
+    /// Signature of our refs each connected peer last synced, per tracked repository.
+    /// Used to only emit [`Event::RefsSynced`] when a peer syncs refs it hadn't synced.
+    refs_synced: HashMap<(Id, NodeId), crypto::Signature>,
+    /// Seeds of tracked repositories we emitted [`Event::SeedDiscovered`] for.
+    seeds_discovered: HashSet<(Id, NodeId)>,
+    /// Repositories in storage that were reported as corrupt.
+    corrupted: HashSet<Id>,
+    /// Repairs made on startup, if the node didn't shut down cleanly.
+    recovery: Option<Recovery>,
+    /// Repositories being checked in the background after an unclean shutdown.
+    recovering: HashSet<Id>,
     /// Request/connection rate limitter.
     limiter: RateLimiter,
+    /// Announcement request rate limitter.
+    requests_limiter: RateLimiter,
+    /// Spreads periodic announcements over time.
+    announcer: Announcer,
     /// Current tracked repository bloom filter.
     filter: Filter,
     /// Last time the service was idle.
@@ -217,6 +590,10 @@ pub struct Service<R, A, S, G> {
     last_prune: LocalTime,
     /// Last time the service announced its inventory.
     last_announce: LocalTime,
+    /// Last time our node announcement was refreshed.
+    last_node_announce: LocalTime,
+    /// File our node announcement is persisted to, if any.
+    node_announcement_file: Option<PathBuf>,
     /// Time when the service was initialized.
     start_time: LocalTime,
     /// Publishes events to subscribers.
@@ -252,38 +629,185 @@ where
         storage: S,
         addresses: A,
         tracking: tracking::Config<Write>,
+        event_log: events::Log,
+        watermarks: watermark::Table,
         signer: G,
         rng: Rng,
         node: NodeAnnouncement,
         emitter: Emitter<Event>,
     ) -> Self {
         let sessions = Sessions::new(rng.clone());
+        let outbox = Outbox::new(config.limits.relay_rate);
+        let activity_log = activity::Log::new(config.activity_log.capacity());
+        let metrics = metrics::Metrics::new(config.metrics);
+        let cobs = gossip::cobs(&config, clock.as_millis());
 
         Self {
             config,
             storage,
             addresses,
             tracking,
+            event_log,
+            activity_log,
+            announcements_sent: 0,
+            announcements_received: 0,
+            metrics,
+            watermarks,
+            cobs,
             signer,
             rng,
             node,
             clock,
             routing,
             gossip: Gossip::default(),
-            outbox: Outbox::default(),
+            outbox,
+            bandwidth: bandwidth::Accounting::default(),
             limiter: RateLimiter::default(),
+            requests_limiter: RateLimiter::default(),
+            announcer: Announcer::default(),
             sessions,
-            fetch_reqs: HashMap::new(),
+            fetch_reqs: FetchRequests::default(),
+            minimal_reqs: HashSet::new(),
+            minimal_fetches: HashSet::new(),
+            dry_run_reqs: HashMap::new(),
+            inspect_reqs: HashMap::new(),
+            pending_fetches: HashMap::new(),
+            fetch_deadlines: HashMap::new(),
+            timestamps: AnnouncementTimestamps::default(),
+            announced_refs: HashMap::new(),
+            fetch_retries: HashMap::new(),
+            backoffs: HashMap::new(),
+            user_disconnects: HashMap::new(),
+            mirror_missing: HashMap::new(),
+            missing_backlog: Vec::new(),
+            fetch_estimates: HashMap::new(),
+            activity: HashMap::new(),
+            fetches: HashMap::new(),
+            refs_synced: HashMap::new(),
+            seeds_discovered: HashSet::new(),
+            corrupted: HashSet::new(),
+            recovery: None,
+            recovering: HashSet::new(),
             filter: Filter::empty(),
             last_idle: LocalTime::default(),
             last_sync: LocalTime::default(),
             last_prune: LocalTime::default(),
             last_announce: LocalTime::default(),
+            last_node_announce: LocalTime::default(),
+            node_announcement_file: None,
             start_time: LocalTime::default(),
             emitter,
         }
     }
 
+    /// Persist our node announcement to the given file whenever it changes.
+    pub fn set_node_announcement_file(&mut self, path: PathBuf) {
+        self.node_announcement_file = Some(path);
+    }
+
+    /// Record that the corrupt database at `path` was moved to `backup` and replaced
+    /// with an empty one on startup.
+    pub fn database_recovered(&mut self, path: PathBuf, backup: PathBuf) {
+        // Nb. There are no event subscribers this early, so it is kept for `recovery()`.
+        self.recovery
+            .get_or_insert_with(Recovery::default)
+            .databases
+            .push(RecoveredDatabase {
+                path: path.clone(),
+                backup: backup.clone(),
+            });
+        self.emit(Event::DatabaseRecovered { path, backup });
+    }
+
+    /// Record that the node didn't shut down cleanly the last time it ran, so that our
+    /// stores are checked against storage on initialization. The given repositories are
+    /// checked in the background, and reported with [`Command::RepoValidated`].
+    pub fn unclean_shutdown(&mut self, rids: impl IntoIterator<Item = Id>) {
+        self.recovering = rids.into_iter().collect();
+
+        let recovery = self.recovery.get_or_insert_with(Recovery::default);
+        recovery.unclean = true;
+        recovery.pending = self.recovering.len();
+    }
+
+    /// Add a node discovered on the local network to the address book, from where it is
+    /// dialed if we need more peers.
+    ///
+    /// Nb. The address is stored with a zero timestamp, so that it never takes precedence
+    /// over what the node announces about itself.
+    pub fn discovered(&mut self, nid: NodeId, addr: Address, features: Features, alias: Alias) {
+        if nid == self.node_id() {
+            return;
+        }
+        match self.addresses.insert(
+            &nid,
+            features,
+            alias,
+            0,
+            0,
+            [KnownAddress::new(addr.clone(), address::Source::Local)],
+        ) {
+            Ok(true) => {
+                info!(target: "service", "Discovered node {nid} at {addr} on the local network");
+
+                // Nodes usually discover each other at the same time, and would dial each
+                // other simultaneously. Only the node with the lower id dials right away,
+                // the other one waits for its next round of connection maintenance.
+                if self.node_id() < nid {
+                    self.maintain_connections();
+                }
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!(target: "service", "Error adding discovered node {nid} to address book: {e}");
+            }
+        }
+    }
+
+    /// Add a configured peer to the address book, under its configured alias.
+    ///
+    /// Nb. Like discovered nodes, the peer is stored with a zero timestamp, so that what
+    /// the node announces about itself takes precedence.
+    fn import_peer(&mut self, peer: &config::PeerEntry) {
+        let Some(alias) = peer.alias.clone() else {
+            return;
+        };
+        if let Err(e) = self.addresses.insert(
+            &peer.id,
+            Features::default(),
+            alias,
+            0,
+            0,
+            [KnownAddress::new(
+                peer.address.clone(),
+                address::Source::Imported,
+            )],
+        ) {
+            error!(target: "service", "Error adding peer {} to address book: {e}", peer.id);
+        }
+    }
+
+    /// Add a peer to the configuration, and connect to it if it's persistent.
+    /// Returns `true` if the peer wasn't configured.
+    pub fn add_peer(&mut self, peer: config::PeerEntry) -> bool {
+        let (nid, addr, persistent) = (peer.id, peer.address.clone(), peer.persistent);
+
+        self.import_peer(&peer);
+        let added = self.config.add_peer(peer);
+
+        if persistent && !self.sessions.contains_key(&nid) {
+            self.connect(nid, addr);
+        }
+        added
+    }
+
+    /// Remove a peer from the configuration. The peer isn't disconnected from, but isn't
+    /// reconnected to either.
+    /// Returns `true` if the peer was configured.
+    pub fn remove_peer(&mut self, nid: &NodeId) -> bool {
+        self.config.remove_peer(nid)
+    }
+
     /// Return the next i/o action to execute.
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<io::Io> {
@@ -291,31 +815,225 @@ where
     }
 
     /// Track a repository.
-    /// Returns whether or not the tracking policy was updated.
+    /// Returns whether or not the tracking policy was updated. If it was, emits
+    /// [`Event::RepoTracked`], after the policy is stored.
     pub fn track_repo(&mut self, id: &Id, scope: Scope) -> Result<bool, tracking::Error> {
+        self.track_repo_origin(id, scope, tracking::Origin::Tracked)
+    }
+
+    /// Track a repository, and record why it is in our inventory. A repository's origin is
+    /// never reset to [`tracking::Origin::Tracked`] this way, eg. when a repository we
+    /// created is tracked again.
+    pub fn track_repo_origin(
+        &mut self,
+        id: &Id,
+        scope: Scope,
+        origin: tracking::Origin,
+    ) -> Result<bool, tracking::Error> {
         let updated = self.tracking.track_repo(id, scope)?;
+        let origin_updated =
+            origin != tracking::Origin::Tracked && self.tracking.set_repo_origin(id, origin)?;
         self.filter.insert(id);
 
-        Ok(updated)
+        if updated {
+            self.emit(Event::RepoTracked {
+                rid: *id,
+                scope,
+                origin,
+            });
+        }
+        Ok(origin_updated || updated)
+    }
+
+    /// Track a repository announced by the given node, if it is one of the nodes we mirror
+    /// and we have no policy for the repository yet. Returns whether the repository was
+    /// tracked.
+    fn mirror_repo(&mut self, rid: &Id, announcer: &NodeId) -> bool {
+        if !self.config.mirror.contains(announcer) {
+            return false;
+        }
+        match tracking::Store::repo_policy(&self.tracking, rid) {
+            Ok(Some(_)) => return false,
+            Ok(None) => {}
+            Err(e) => {
+                self.store_error(*announcer, e);
+                return false;
+            }
+        }
+        match self.track_repo_origin(rid, self.config.mirror_scope, tracking::Origin::Mirror) {
+            Ok(tracked) => {
+                if tracked {
+                    info!(target: "service", "Mirroring repository {rid} announced by {announcer}");
+                }
+                tracked
+            }
+            Err(e) => {
+                self.store_error(*announcer, e);
+                false
+            }
+        }
+    }
+
+    /// Untrack mirrored repositories which have been missing from the inventories of the
+    /// nodes we mirror for longer than the configured expiry.
+    fn expire_mirrored(&mut self, now: LocalTime) -> Result<(), Error> {
+        let Some(expiry) = self.config.mirror_expiry else {
+            return Ok(());
+        };
+        let mirrored = self
+            .tracking
+            .repo_policies()?
+            .filter(|r| r.origin == tracking::Origin::Mirror && r.policy == tracking::Policy::Track)
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+
+        self.mirror_missing.retain(|rid, _| mirrored.contains(rid));
+
+        for rid in mirrored {
+            let seeds = self.routing.get(&rid)?;
+            if self.config.mirror.iter().any(|nid| seeds.contains(nid)) {
+                self.mirror_missing.remove(&rid);
+                continue;
+            }
+            let since = *self.mirror_missing.entry(rid).or_insert(now);
+
+            if now - since >= expiry {
+                info!(target: "service", "Untracking mirrored repository {rid}: no longer announced");
+
+                self.mirror_missing.remove(&rid);
+                if self.untrack_repo(&rid)? {
+                    self.announce_untracked(rid)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Track a repository, and set which refs are fetched from its remotes.
+    pub fn track_repo_filter(
+        &mut self,
+        id: &Id,
+        scope: Scope,
+        filter: &tracking::RefFilter,
+    ) -> Result<bool, tracking::Error> {
+        let updated = self.track_repo(id, scope)?;
+
+        Ok(self.tracking.set_repo_ref_filter(id, filter)? || updated)
+    }
+
+    /// Record in a repository's origin whether we are one of its delegates. Repositories
+    /// that were created locally keep their origin, as do mirrored ones we aren't a
+    /// delegate of.
+    fn update_origin(&mut self, rid: &Id) {
+        let repo = match tracking::Store::repo_policy(&self.tracking, rid) {
+            Ok(Some(repo)) => repo,
+            Ok(None) => return,
+            Err(e) => {
+                error!(target: "service", "Error getting tracking policy of {rid}: {e}");
+                return;
+            }
+        };
+        if repo.origin == tracking::Origin::Created {
+            return;
+        }
+        let is_delegate = match self.storage.repository(*rid).map(|r| r.delegates()) {
+            Ok(Ok(delegates)) => delegates.contains(&self.node_id().into()),
+            Ok(Err(e)) => {
+                error!(target: "service", "Error getting delegates of {rid}: {e}");
+                return;
+            }
+            Err(e) => {
+                error!(target: "service", "Error opening repository {rid}: {e}");
+                return;
+            }
+        };
+        let origin = if is_delegate {
+            tracking::Origin::Delegate
+        } else if repo.origin == tracking::Origin::Mirror {
+            return;
+        } else {
+            tracking::Origin::Tracked
+        };
+        match self.tracking.set_repo_origin(rid, origin) {
+            Ok(true) => {
+                debug!(target: "service", "Origin of {rid} updated to '{origin}'");
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!(target: "service", "Error updating origin of {rid}: {e}");
+            }
+        }
     }
 
     /// Untrack a repository.
-    /// Returns whether or not the tracking policy was updated.
-    /// Note that when untracking, we don't announce anything to the network. This is because by
-    /// simply not announcing it anymore, it will eventually be pruned by nodes.
+    /// Returns whether or not the tracking policy was updated. If it was, emits
+    /// [`Event::RepoUntracked`], after the policy is stored.
     pub fn untrack_repo(&mut self, id: &Id) -> Result<bool, tracking::Error> {
         let updated = self.tracking.untrack_repo(id)?;
+        if updated {
+            self.emit(Event::RepoUntracked { rid: *id });
+        }
+        if let Err(e) = self.watermarks.remove(id) {
+            error!(target: "service", "Error removing sync watermarks of {id}: {e}");
+        }
+        self.refs_synced.retain(|(rid, _), _| rid != id);
+        self.seeds_discovered.retain(|(rid, _)| rid != id);
+        self.fetch_estimates.remove(id);
         // Nb. This is potentially slow if we have lots of projects. We should probably
         // only re-compute the filter when we've untracked a certain amount of projects
         // and the filter is really out of date.
-        //
-        // TODO: Share this code with initialization code.
+        self.rebuild_filter()?;
+
+        Ok(updated)
+    }
+
+    /// Watch a repository, ie. subscribe to its announcements without ever fetching it.
+    /// Returns whether or not the tracking policy was updated. Tracked repositories are
+    /// left as-is.
+    pub fn watch_repo(&mut self, id: &Id) -> Result<bool, tracking::Error> {
+        let updated = self.tracking.watch_repo(id)?;
+        self.filter.insert(id);
+
+        Ok(updated)
+    }
+
+    /// Stop watching a repository. Returns whether or not the tracking policy was updated.
+    /// Tracked repositories are left as-is.
+    pub fn unwatch_repo(&mut self, id: &Id) -> Result<bool, tracking::Error> {
+        let updated = self.tracking.unwatch_repo(id)?;
+        if updated {
+            self.seeds_discovered.retain(|(rid, _)| rid != id);
+            self.rebuild_filter()?;
+        }
+        Ok(updated)
+    }
+
+    /// Re-compute our subscription filter from our tracked and watched repositories.
+    fn rebuild_filter(&mut self) -> Result<(), tracking::Error> {
         self.filter = Filter::new(
             self.tracking
                 .repo_policies()?
-                .filter_map(|t| (t.policy == tracking::Policy::Track).then_some(t.id)),
+                .filter_map(|t| (t.policy != tracking::Policy::Block).then_some(t.id)),
         );
-        Ok(updated)
+        Ok(())
+    }
+
+    /// Import tracking policies, and update our tracked repository filter accordingly.
+    pub fn import_policies(
+        &mut self,
+        policies: &tracking::Policies,
+    ) -> Result<tracking::ImportSummary, tracking::Error> {
+        let summary = self.tracking.import(policies)?;
+
+        for repo in &policies.repos {
+            if repo.policy == tracking::Policy::Block {
+                self.refs_synced.retain(|(rid, _), _| *rid != repo.id);
+                self.seeds_discovered.retain(|(rid, _)| *rid != repo.id);
+            }
+        }
+        self.rebuild_filter()?;
+
+        Ok(summary)
     }
 
     /// Check whether we are tracking a certain repository.
@@ -346,6 +1064,11 @@ where
         &self.routing
     }
 
+    /// Get the mutable routing store.
+    pub fn routing_mut(&mut self) -> &mut R {
+        &mut self.routing
+    }
+
     /// Get the storage instance.
     pub fn storage(&self) -> &S {
         &self.storage
@@ -361,6 +1084,16 @@ where
         &self.tracking
     }
 
+    /// Get the sync watermarks.
+    pub fn watermarks(&self) -> &watermark::Table {
+        &self.watermarks
+    }
+
+    /// Get the mutable sync watermarks.
+    pub fn watermarks_mut(&mut self) -> &mut watermark::Table {
+        &mut self.watermarks
+    }
+
     /// Get the local signer.
     pub fn signer(&self) -> &G {
         &self.signer
@@ -371,6 +1104,22 @@ where
         Events::from(self.emitter.subscribe())
     }
 
+    /// Emit an event to subscribers, and append it to the event log if its kind is
+    /// configured to be logged.
+    fn emit(&mut self, event: Event) {
+        if self.config.event_log.events.contains(&event.kind()) {
+            if let Err(e) = self.event_log.append(&event, self.time()) {
+                error!(target: "service", "Error appending {} event to log: {e}", event.kind().as_str());
+            }
+        }
+        self.emitter.emit(event);
+    }
+
+    /// Record an activity in the activity log.
+    fn log_activity(&mut self, activity: Activity) {
+        self.activity_log.record(self.time(), activity);
+    }
+
     /// Get I/O outbox.
     pub fn outbox(&mut self) -> &mut Outbox {
         &mut self.outbox
@@ -392,16 +1141,44 @@ where
         self.start_time = time;
 
         // Connect to configured peers.
-        let addrs = self.config.connect.clone();
-        for (id, addr) in addrs.into_iter().map(|ca| ca.into()) {
+        for peer in self.config.peer_entries.clone() {
+            self.import_peer(&peer);
+        }
+        let addrs = self.config.startup_peers().collect::<Vec<_>>();
+        for (id, addr) in addrs {
             self.connect(id, addr);
         }
+        // Remove routing entries claiming that we seed repositories we don't have, eg.
+        // left behind by a bug, a manual import or an unclean shutdown. We would otherwise
+        // advertise them.
+        let mut removed = 0;
+        for rid in self.routing.get_resources(&self.node_id())? {
+            if let Ok(false) = self.storage.contains(&rid) {
+                warn!(target: "service", "Removing routing entry for {rid}: not in local storage");
+                self.routing.remove(&rid, &self.node_id())?;
+                removed += 1;
+            }
+        }
         // Ensure that our inventory is recorded in our routing table, and we are tracking
         // all of it. It can happen that inventory is not properly tracked if for eg. the
         // user creates a new repository while the node is stopped.
-        let rids = self.storage.inventory()?;
-        self.routing
-            .insert(&rids, self.node_id(), time.as_millis())?;
+        let rids = self.storage_inventory()?;
+        let added = self
+            .route(&rids, self.node_id(), self.node_id(), time.as_millis())?
+            .into_iter()
+            .filter(|(_, result)| *result == InsertResult::SeedAdded)
+            .count();
+
+        if let Some(recovery) = self.recovery.as_mut().filter(|r| r.unclean) {
+            recovery.routes_added = added;
+            recovery.routes_removed = removed;
+
+            info!(
+                target: "service",
+                "Checking {} repositories against storage after unclean shutdown..",
+                recovery.pending
+            );
+        }
 
         for rid in rids {
             if !self.is_tracking(&rid)? {
@@ -412,28 +1189,15 @@ where
                     info!(target: "service", "Tracking local repository {rid}");
                 }
             }
+            self.update_origin(&rid);
         }
         // Ensure that our local node is in our address database.
-        self.addresses
-            .insert(
-                &self.node_id(),
-                self.node.features,
-                self.node.alias.clone(),
-                self.node.work(),
-                self.node.timestamp,
-                self.node
-                    .addresses
-                    .iter()
-                    .map(|a| KnownAddress::new(a.clone(), address::Source::Peer)),
-            )
-            .expect("Service::initialize: error adding local node to address database");
+        self.last_node_announce = LocalTime::from_millis(self.node.timestamp as u128);
+        self.store_node_announcement()
+            .expect("Service::initialize: error storing local node announcement");
 
-        // Setup subscription filter for tracked repos.
-        self.filter = Filter::new(
-            self.tracking
-                .repo_policies()?
-                .filter_map(|t| (t.policy == tracking::Policy::Track).then_some(t.id)),
-        );
+        // Setup subscription filter for tracked and watched repos.
+        self.rebuild_filter()?;
         // Try to establish some connections.
         self.maintain_connections();
         // Start periodic tasks.
@@ -445,55 +1209,155 @@ where
     pub fn tick(&mut self, now: LocalTime) {
         trace!(target: "service", "Tick +{}", now - self.start_time);
 
+        if now < self.clock {
+            let jump = self.clock - now;
+            warn!(target: "service", "Clock jumped backwards by {jump}, adjusting timers..");
+
+            self.rewind(jump);
+        }
         self.clock = now;
     }
 
+    /// Move all our timers back by the given duration, after the clock jumped backwards.
+    /// This keeps the time left until each timer is due unchanged, so that periodic tasks
+    /// neither stall until the clock catches up, nor all run at once.
+    fn rewind(&mut self, by: LocalDuration) {
+        for time in [
+            &mut self.last_idle,
+            &mut self.last_sync,
+            &mut self.last_prune,
+            &mut self.last_announce,
+            &mut self.last_node_announce,
+            &mut self.start_time,
+        ] {
+            *time = rewind(*time, by);
+        }
+        for (_, session) in self.sessions.iter_mut() {
+            session.rewind(by);
+        }
+        for retry in self.fetch_retries.values_mut() {
+            retry.retry_at = retry.retry_at.map(|t| rewind(t, by));
+        }
+        for backoff in self.backoffs.values_mut() {
+            backoff.until = rewind(backoff.until, by);
+        }
+        for until in self.user_disconnects.values_mut() {
+            *until = rewind(*until, by);
+        }
+        for deadline in self.fetch_deadlines.values_mut() {
+            *deadline = rewind(*deadline, by);
+        }
+        for since in self.mirror_missing.values_mut() {
+            *since = rewind(*since, by);
+        }
+        for activity in self.activity.values_mut() {
+            activity.fetched = activity.fetched.map(|t| rewind(t, by));
+            activity.announced = activity.announced.map(|t| rewind(t, by));
+        }
+        self.announcer.rewind(by);
+    }
+
     pub fn wake(&mut self) {
         let now = self.clock;
 
         trace!(target: "service", "Wake +{}", now - self.start_time);
 
+        self.send_backlogs();
+
         if now - self.last_idle >= IDLE_INTERVAL {
             trace!(target: "service", "Running 'idle' task...");
 
+            let timer = self.metrics.timer();
             self.keep_alive(&now);
             self.disconnect_unresponsive_peers(&now);
+            self.retry_fetches(&now);
             self.maintain_connections();
             self.outbox.wakeup(IDLE_INTERVAL);
             self.last_idle = now;
+            self.metrics.task(metrics::Task::Idle, timer);
         }
         if now - self.last_sync >= SYNC_INTERVAL {
             trace!(target: "service", "Running 'sync' task...");
 
+            let timer = self.metrics.timer();
             if let Err(e) = self.fetch_missing_inventory() {
                 error!(target: "service", "Error fetching missing inventory: {e}");
             }
             self.outbox.wakeup(SYNC_INTERVAL);
             self.last_sync = now;
+            self.metrics.task(metrics::Task::Sync, timer);
         }
         if now - self.last_announce >= ANNOUNCE_INTERVAL {
-            if let Err(err) = self
-                .storage
-                .inventory()
-                .and_then(|i| self.announce_inventory(i))
-            {
-                error!(target: "service", "Error announcing inventory: {}", err);
-            }
+            let timer = self.metrics.timer();
+            self.announcer
+                .schedule(now, ANNOUNCE_INTERVAL, &mut self.rng);
             self.outbox.wakeup(ANNOUNCE_INTERVAL);
             self.last_announce = now;
+            self.metrics.task(metrics::Task::Announce, timer);
+        }
+        self.announce_periodic(&now);
+        if now - self.last_node_announce >= self.config.limits.node_announce_interval {
+            trace!(target: "service", "Running 'node announce' task...");
+
+            let timer = self.metrics.timer();
+            self.refresh_node_announcement(&now);
+            self.outbox
+                .wakeup(self.config.limits.node_announce_interval);
+            self.metrics.task(metrics::Task::NodeAnnounce, timer);
         }
         if now - self.last_prune >= PRUNE_INTERVAL {
             trace!(target: "service", "Running 'prune' task...");
 
-            if let Err(err) = self.prune_routing_entries(&now) {
+            let timer = self.metrics.timer();
+            let routes = self.prune_routing_entries(&now).unwrap_or_else(|err| {
                 error!("Error pruning routing entries: {}", err);
+                0
+            });
+            let fetches = self
+                .routing
+                .prune_fetches((now - self.config.limits.routing_max_age).as_millis())
+                .unwrap_or_else(|err| {
+                    error!("Error pruning fetch history: {}", err);
+                    0
+                });
+            if routes > 0 || fetches > 0 {
+                self.log_activity(Activity::Pruned { routes, fetches });
+            }
+            let event_log = &self.config.event_log;
+            if let Err(err) = self
+                .event_log
+                .prune((now - event_log.max_age).as_millis(), event_log.max_size)
+            {
+                error!("Error pruning event log: {}", err);
+            }
+            if let Err(err) = self.prune_watermarks() {
+                error!("Error pruning sync watermarks: {}", err);
+            }
+            self.user_disconnects.retain(|_, until| *until > now);
+            self.prune_sessions(&now);
+            self.prune_gossip();
+            self.prune_fetch_estimates();
+            self.timestamps.prune(now.as_millis());
+            if let Err(err) = self.expire_mirrored(now) {
+                error!("Error expiring mirrored repositories: {}", err);
+            }
+            if self.announcements_sent > 0 || self.announcements_received > 0 {
+                self.log_activity(Activity::Announcements {
+                    sent: self.announcements_sent,
+                    received: self.announcements_received,
+                });
+                self.announcements_sent = 0;
+                self.announcements_received = 0;
             }
             self.outbox.wakeup(PRUNE_INTERVAL);
             self.last_prune = now;
+            self.metrics.task(metrics::Task::Prune, timer);
         }
 
-        // Always check whether there are persistent peers that need reconnecting.
+        // Always check whether there are persistent peers that need reconnecting, and
+        // fetches that waited too long for their seed to be connected.
         self.maintain_persistent();
+        self.expire_pending_fetches(&now);
     }
 
     pub fn command(&mut self, cmd: Command) {
@@ -504,13 +1368,27 @@ where
                 if opts.persistent {
                     self.config.connect.insert((nid, addr.clone()).into());
                 }
-                if !self.connect(nid, addr) {
+                // Connecting explicitly lifts the cool-off of a previous disconnect.
+                if self.user_disconnects.remove(&nid).is_some()
+                    && self
+                        .sessions
+                        .get(&nid)
+                        .map_or(false, |s| s.is_disconnected())
+                {
+                    self.reconnect(nid, addr);
+                } else if !self.connect(nid, addr) {
                     // TODO: Return error to command.
                 }
             }
             Command::Disconnect(nid) => {
                 self.outbox.disconnect(nid, DisconnectReason::Command);
             }
+            Command::Discovered(nid, addr, features, alias) => {
+                self.discovered(nid, addr, features, alias);
+            }
+            Command::RepoValidated(rid, result) => {
+                self.repo_validated(rid, result);
+            }
             Command::Seeds(rid, resp) => match self.seeds(&rid) {
                 Ok(seeds) => {
                     let (connected, disconnected) = seeds.partition();
@@ -519,6 +1397,11 @@ where
                         "Found {} connected seed(s) and {} disconnected seed(s) for {}",
                         connected.len(), disconnected.len(),  rid
                     );
+                    // Fill the gaps in what we know of the connected seeds' refs, so that
+                    // the next query is more complete.
+                    for seed in connected.iter().filter(|s| s.announced.is_none()) {
+                        self.request_announcements(&seed.nid, rid, AnnouncementKinds::REFS);
+                    }
                     resp.send(seeds).ok();
                 }
                 Err(e) => {
@@ -526,64 +1409,169 @@ where
                 }
             },
             Command::Fetch(rid, seed, resp) => {
-                // TODO: Establish connections to unconnected seeds, and retry.
-                self.fetch_reqs.insert((rid, seed), resp);
-                self.fetch(rid, &seed);
+                self.request_fetch(rid, seed, false, resp);
+            }
+            Command::FetchMinimal(rid, seed, resp) => {
+                self.request_fetch(rid, seed, true, resp);
             }
-            Command::TrackRepo(rid, scope, resp) => {
+            Command::TrackRepo(rid, scope, origin, resp) => {
                 // Update our tracking policy.
-                let tracked = self
-                    .track_repo(&rid, scope)
-                    .expect("Service::command: error tracking repository");
-                resp.send(tracked).ok();
-
+                match self.track_repo_origin(&rid, scope, origin) {
+                    Ok(tracked) => {
+                        resp.send(Ok(tracked)).ok();
+                    }
+                    Err(e) => {
+                        error!(target: "service", "Error tracking repository {rid}: {e}");
+                        resp.send(Err(e.into())).ok();
+                        return;
+                    }
+                }
                 // Let all our peers know that we're interested in this repo from now on.
-                self.outbox.broadcast(
-                    Message::subscribe(self.filter(), self.time(), Timestamp::MAX),
-                    self.sessions.connected().map(|(_, s)| s),
-                );
+                self.resubscribe();
             }
-            Command::UntrackRepo(id, resp) => {
-                let untracked = self
-                    .untrack_repo(&id)
-                    .expect("Service::command: error untracking repository");
-                resp.send(untracked).ok();
+            Command::TrackRepoFilter(rid, scope, filter, resp) => {
+                match self.track_repo_filter(&rid, scope, &filter) {
+                    Ok(tracked) => {
+                        resp.send(Ok(tracked)).ok();
+                    }
+                    Err(e) => {
+                        error!(target: "service", "Error tracking repository {rid}: {e}");
+                        resp.send(Err(e.into())).ok();
+                        return;
+                    }
+                }
+                self.resubscribe();
             }
-            Command::TrackNode(id, alias, resp) => {
-                let tracked = self
-                    .tracking
-                    .track_node(&id, alias.as_deref())
-                    .expect("Service::command: error tracking node");
-                resp.send(tracked).ok();
+            Command::TrackRepoFetch(rid, scope, resp) => {
+                let updated = match self.track_repo(&rid, scope) {
+                    Ok(updated) => updated,
+                    Err(e) => {
+                        error!(target: "service", "Error tracking repository {rid}: {e}");
+                        resp.send(Err(e.into())).ok();
+                        return;
+                    }
+                };
+                let fetch = self.fetch_tracked(rid);
+
+                resp.send(Ok(Tracked { updated, fetch })).ok();
+                self.resubscribe();
             }
-            Command::UntrackNode(id, resp) => {
-                let untracked = self
-                    .tracking
-                    .untrack_node(&id)
-                    .expect("Service::command: error untracking node");
-                resp.send(untracked).ok();
-            }
-            Command::AnnounceRefs(id) => {
-                if let Err(err) = self.announce_refs(id, [self.node_id()]) {
-                    error!("Error announcing refs: {}", err);
-                }
+            Command::TrackRepoDryRun(rid, scope, resp) => {
+                self.track_repo_dry_run(rid, scope, resp);
             }
-            Command::AnnounceInventory => {
-                if let Err(err) = self
-                    .storage
-                    .inventory()
-                    .and_then(|i| self.announce_inventory(i))
-                {
-                    error!("Error announcing inventory: {}", err);
+            Command::Inspect(rid, resp) => {
+                self.inspect(rid, resp);
+            }
+            Command::SetLimit(name, value, resp) => {
+                resp.send(self.set_limit(&name, value)).ok();
+            }
+            Command::AddPeer(peer, resp) => {
+                resp.send(self.add_peer(peer)).ok();
+            }
+            Command::RemovePeer(nid, resp) => {
+                resp.send(self.remove_peer(&nid)).ok();
+            }
+            Command::UntrackRepo(id, resp) => {
+                let untracked = match self.untrack_repo(&id) {
+                    Ok(untracked) => untracked,
+                    Err(e) => {
+                        error!(target: "service", "Error untracking repository {id}: {e}");
+                        resp.send(Err(e.into())).ok();
+                        return;
+                    }
+                };
+                resp.send(Ok(untracked)).ok();
+
+                if untracked {
+                    if let Err(e) = self.announce_untracked(id) {
+                        error!(target: "service", "Error announcing inventory: {e}");
+                    }
                 }
             }
-            Command::SyncInventory(resp) => {
-                let synced = self
-                    .sync_inventory()
-                    .expect("Service::command: error syncing inventory");
-                resp.send(synced.added.len() + synced.removed.len() > 0)
-                    .ok();
+            Command::WatchRepo(id, resp) => {
+                match self.watch_repo(&id) {
+                    Ok(watched) => {
+                        resp.send(Ok(watched)).ok();
+                    }
+                    Err(e) => {
+                        error!(target: "service", "Error watching repository {id}: {e}");
+                        resp.send(Err(e.into())).ok();
+                        return;
+                    }
+                }
+                // Let all our peers know that we're interested in this repo from now on.
+                self.resubscribe();
+            }
+            Command::UnwatchRepo(id, resp) => match self.unwatch_repo(&id) {
+                Ok(unwatched) => {
+                    resp.send(Ok(unwatched)).ok();
+                }
+                Err(e) => {
+                    error!(target: "service", "Error unwatching repository {id}: {e}");
+                    resp.send(Err(e.into())).ok();
+                }
+            },
+            Command::TrackNode(id, alias, resp) => {
+                let tracked = self.tracking.track_node(&id, alias.as_ref());
+                match &tracked {
+                    Ok(true) => self.emit(Event::NodeTracked { nid: id, alias }),
+                    Ok(false) => {}
+                    Err(e) => error!(target: "service", "Error tracking node {id}: {e}"),
+                }
+                resp.send(tracked.map_err(CommandError::from)).ok();
+            }
+            Command::UntrackNode(id, resp) => {
+                let untracked = self.tracking.untrack_node(&id);
+                match &untracked {
+                    Ok(true) => self.emit(Event::NodeUntracked { nid: id }),
+                    Ok(false) => {}
+                    Err(e) => error!(target: "service", "Error untracking node {id}: {e}"),
+                }
+                resp.send(untracked.map_err(CommandError::from)).ok();
+            }
+            Command::ImportPolicies(policies, resp) => {
+                let summary = match self.import_policies(&policies) {
+                    Ok(summary) => summary,
+                    Err(e) => {
+                        error!(target: "service", "Error importing tracking policies: {e}");
+                        resp.send(Err(e.into())).ok();
+                        return;
+                    }
+                };
+                resp.send(Ok(summary)).ok();
+
+                // Imported repository policies may have changed what we're interested in.
+                if summary.added + summary.updated > 0 {
+                    self.resubscribe();
+                }
+            }
+            Command::AnnounceRefs(id, resp) => match self.announce_refs(id, [self.node_id()]) {
+                Ok(true) => {
+                    resp.send(Ok(())).ok();
+                }
+                Ok(false) => {
+                    resp.send(Err(CommandError::NothingToAnnounce(id))).ok();
+                }
+                Err(err) => {
+                    error!("Error announcing refs: {}", err);
+                    resp.send(Err(err.into())).ok();
+                }
+            },
+            Command::AnnounceInventory => {
+                if let Err(err) = self.inventory().and_then(|i| self.announce_inventory(i)) {
+                    error!("Error announcing inventory: {}", err);
+                }
             }
+            Command::SyncInventory(resp) => match self.sync_inventory() {
+                Ok(synced) => {
+                    resp.send(Ok(synced.added.len() + synced.removed.len() > 0))
+                        .ok();
+                }
+                Err(e) => {
+                    error!(target: "service", "Error syncing inventory: {e}");
+                    resp.send(Err(e.into())).ok();
+                }
+            },
             Command::QueryState(query, sender) => {
                 sender.send(query(self)).ok();
             }
@@ -591,38 +1579,100 @@ where
     }
 
     pub fn fetch(&mut self, rid: Id, from: &NodeId) {
+        if *from == self.node_id() {
+            // This can happen if our own node id made its way into the routing table, or
+            // was given by the user.
+            error!(target: "service", "Attempted to fetch {rid} from self");
+
+            self.fetch_reqs.reply(
+                &rid,
+                from,
+                FetchResult::Failed {
+                    reason: String::from("cannot fetch from the local node"),
+                },
+            );
+            return;
+        }
+        let limit = self.fetch_limit(&rid);
+        let filter = self.ref_filter(&rid);
         let Some(session) = self.sessions.get_mut(from) else {
             error!(target: "service", "Session {from} does not exist; cannot initiate fetch");
+
+            self.fetch_reqs.reply(
+                &rid,
+                from,
+                FetchResult::Failed {
+                    reason: format!("not connected to {from}"),
+                },
+            );
             return;
         };
+        if session.is_initial() || session.is_connecting() {
+            // This can happen if a fetch is requested right after connecting to the seed,
+            // eg. when cloning. We fetch once the handshake completes.
+            debug!(target: "service", "Fetch of {rid} deferred until we're connected to {from}..");
+
+            self.defer_fetch(rid, *from);
+            return;
+        }
         if !session.is_connected() {
             // This can happen if a session disconnects in the time between asking for seeds to
             // fetch from, and initiating the fetch from one of those seeds.
             error!(target: "service", "Session {from} is not connected; cannot initiate fetch");
+
+            self.fetch_reqs.reply(
+                &rid,
+                from,
+                FetchResult::Failed {
+                    reason: format!("not connected to {from}"),
+                },
+            );
             return;
         }
         let seed = session.id;
+        let estimate = self.fetch_estimates.get(&rid).copied();
 
-        match session.fetch(rid) {
+        match session.fetch(rid, self.clock, estimate) {
             session::FetchResult::Queued => {
                 debug!(target: "service", "Fetch queued for {rid} with {seed}..");
             }
             session::FetchResult::Ready => {
                 debug!(target: "service", "Fetch initiated for {rid} with {seed}..");
 
-                match self.tracking.namespaces_for(&self.storage, &rid) {
+                // Only fetches still awaited by the user are minimal.
+                let minimal =
+                    self.fetch_reqs.contains(&rid, &seed) && self.minimal_reqs.remove(&(rid, seed));
+                let namespaces = if minimal {
+                    self.tracking.delegate_namespaces_for(&self.storage, &rid)
+                } else {
+                    self.tracking.namespaces_for(&self.storage, &rid)
+                };
+                match namespaces {
                     Ok(namespaces) => {
-                        self.outbox.fetch(session, rid, namespaces);
+                        if minimal {
+                            self.minimal_fetches.insert((rid, seed));
+                        }
+                        self.outbox.fetch(session, rid, namespaces, limit, filter);
+                        self.log_activity(Activity::FetchStarted { rid, nid: seed });
+                        self.metrics.fetch_initiated();
                     }
                     Err(err) => {
                         error!(target: "service", "Error getting namespaces for {rid}: {err}");
 
-                        if let Some(resp) = self.fetch_reqs.remove(&(rid, seed)) {
-                            resp.send(FetchResult::Failed {
-                                reason: err.to_string(),
-                            })
-                            .ok();
+                        self.fetch_retries.remove(&rid);
+
+                        if let NamespacesError::BlockedPolicy { rid } = err {
+                            self.fetch_reqs
+                                .fail(&rid, &seed, || CommandError::NotTracked(rid));
+                            return;
                         }
+                        self.fetch_reqs.reply(
+                            &rid,
+                            &seed,
+                            FetchResult::Failed {
+                                reason: err.to_string(),
+                            },
+                        );
                     }
                 };
             }
@@ -631,55 +1681,521 @@ where
             }
             session::FetchResult::NotConnected => {
                 error!(target: "service", "Unable to fetch {rid} from peer {seed}: peer is not connected");
+
+                self.fetch_reqs.reply(
+                    &rid,
+                    &seed,
+                    FetchResult::Failed {
+                        reason: format!("not connected to {seed}"),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Handle a user's fetch request. In `minimal` mode, only the delegates' namespaces
+    /// are fetched.
+    fn request_fetch(
+        &mut self,
+        rid: Id,
+        seed: NodeId,
+        minimal: bool,
+        resp: chan::Sender<Result<FetchResult, CommandError>>,
+    ) {
+        let max = self.config.limits.fetch_max_requests;
+        if self.fetch_reqs.len() >= max {
+            warn!(target: "service", "Rejecting fetch of {rid} from {seed}: too many fetches in progress");
+            resp.send(Err(CommandError::TooManyFetches(max))).ok();
+            return;
+        }
+        // If the same fetch was already requested, wait for its result instead.
+        if !self.fetch_reqs.insert(rid, seed, resp) {
+            debug!(target: "service", "Fetch of {rid} from {seed} already requested; waiting for result..");
+            return;
+        }
+        if minimal {
+            self.minimal_reqs.insert((rid, seed));
+        } else {
+            self.minimal_reqs.remove(&(rid, seed));
+        }
+        // TODO: Establish connections to unconnected seeds, and retry.
+        self.fetch(rid, &seed);
+    }
+
+    /// Fetch a repository once we're connected to the given seed. If the fetch was requested
+    /// by a user, it fails if the seed isn't connected within [`FETCH_CONNECT_TIMEOUT`].
+    fn defer_fetch(&mut self, rid: Id, seed: NodeId) {
+        self.pending_fetches.entry(seed).or_default().insert(rid);
+
+        if self.fetch_reqs.contains(&rid, &seed) {
+            self.fetch_deadlines
+                .insert((rid, seed), self.clock + FETCH_CONNECT_TIMEOUT);
+            self.outbox.wakeup(FETCH_CONNECT_TIMEOUT);
+        }
+    }
+
+    /// Fail the user-requested fetches whose seed wasn't connected in time.
+    fn expire_pending_fetches(&mut self, now: &LocalTime) {
+        let expired = self
+            .fetch_deadlines
+            .iter()
+            .filter(|(_, deadline)| *deadline <= now)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        for (rid, seed) in expired {
+            debug!(target: "service", "Fetch of {rid} timed out waiting for {seed} to be connected");
+
+            self.fetch_deadlines.remove(&(rid, seed));
+            if let Some(rids) = self.pending_fetches.get_mut(&seed) {
+                rids.remove(&rid);
+            }
+            self.fetch_reqs.reply(
+                &rid,
+                &seed,
+                FetchResult::Failed {
+                    reason: format!("timed out connecting to {seed}"),
+                },
+            );
+        }
+    }
+
+    /// Fetch a newly tracked repository from its preferred connected seed. If none of its
+    /// seeds are connected, connect to the preferred one we can reach, and fetch once connected.
+    fn fetch_tracked(&mut self, rid: Id) -> TrackFetch {
+        let (connected, disconnected) = match self.seeds(&rid) {
+            Ok(seeds) => seeds.partition(),
+            Err(e) => {
+                error!(target: "service", "Error reading routing table for {rid}: {e}");
+                return TrackFetch::NoSeeds;
+            }
+        };
+        if let Some(seed) = connected.first().map(|s| s.nid) {
+            self.fetch(rid, &seed);
+
+            let started = self
+                .sessions
+                .get(&seed)
+                .map_or(false, |s| s.fetching().contains(&rid));
+
+            return if started {
+                TrackFetch::Started { seed }
+            } else {
+                TrackFetch::Queued { seed }
+            };
+        }
+        let now = self.clock;
+
+        for seed in disconnected {
+            // Seeds that misbehaved are avoided until their backoff expires.
+            if self
+                .backoffs
+                .get(&seed.nid)
+                .map_or(false, |b| b.until > now)
+            {
+                continue;
+            }
+            let connecting = match self.sessions.get(&seed.nid) {
+                // We're either connecting to the seed already, or it's a persistent peer
+                // that we'll re-connect to.
+                Some(_) => true,
+                None => seed
+                    .addrs
+                    .first()
+                    .map_or(false, |ka| self.connect(seed.nid, ka.addr.clone())),
+            };
+            if connecting {
+                debug!(target: "service", "Fetch of {rid} queued until we're connected to {}..", seed.nid);
+
+                self.pending_fetches
+                    .entry(seed.nid)
+                    .or_default()
+                    .insert(rid);
+
+                return TrackFetch::Queued { seed: seed.nid };
+            }
+        }
+        debug!(target: "service", "No seeds to fetch {rid} from..");
+
+        TrackFetch::NoSeeds
+    }
+
+    /// Find out what would be fetched if the given repository was tracked, by asking
+    /// a connected seed for its refs. The tracking policy is left untouched.
+    pub fn track_repo_dry_run(&mut self, rid: Id, scope: Scope, resp: chan::Sender<TrackDryRun>) {
+        let mut summary = TrackDryRun {
+            rid,
+            seeds: Vec::new(),
+            seed: None,
+            namespaces: None,
+            refs: None,
+            note: None,
+        };
+        let (connected, disconnected) = match self.seeds(&rid) {
+            Ok(seeds) => seeds.partition(),
+            Err(e) => {
+                error!(target: "service", "Error reading routing table for {rid}: {e}");
+
+                summary.note = Some(format!("error reading routing table: {e}"));
+                resp.send(summary).ok();
+
+                return;
+            }
+        };
+        summary.seeds = connected
+            .iter()
+            .chain(disconnected.iter())
+            .cloned()
+            .collect();
+
+        let Some(seed) = connected.first().map(|s| s.nid) else {
+            summary.note = Some(String::from(
+                "no connected seeds; only routing information is available",
+            ));
+            resp.send(summary).ok();
+
+            return;
+        };
+        let Some(session) = self.sessions.get(&seed) else {
+            // Nb. A seed can only be connected if it has a session.
+            error!(target: "service", "Session {seed} does not exist; cannot list refs");
+
+            summary.note = Some(format!("not connected to {seed}"));
+            resp.send(summary).ok();
+
+            return;
+        };
+        let namespaces = match self
+            .tracking
+            .namespaces_for_scope(&self.storage, &rid, scope)
+        {
+            Ok(namespaces) => namespaces,
+            Err(e) => {
+                error!(target: "service", "Error getting namespaces for {rid}: {e}");
+
+                summary.note = Some(e.to_string());
+                resp.send(summary).ok();
+
+                return;
+            }
+        };
+        if scope == Scope::Trusted && namespaces == Namespaces::All {
+            summary.note = Some(String::from(
+                "trusted peers are not yet known; all namespaces were counted",
+            ));
+        }
+        summary.seed = Some(seed);
+
+        // Refs are only listed once for all dry-runs waiting on the same seed. Since these
+        // can have different scopes, all namespaces are listed, and each dry-run only counts
+        // its own.
+        let reqs = self.dry_run_reqs.entry((rid, seed)).or_default();
+        if reqs.is_empty() {
+            debug!(target: "service", "Listing refs of {rid} on {seed} (dry-run)..");
+
+            self.outbox.ls_refs(session, rid, Namespaces::All);
+        }
+        reqs.push(DryRunRequest {
+            summary,
+            namespaces,
+            resp,
+        });
+    }
+
+    /// Refs were listed by a peer, as part of a tracking dry-run.
+    pub fn listed(
+        &mut self,
+        rid: Id,
+        remote: NodeId,
+        result: Result<BTreeSet<git::Namespaced<'static>>, FetchError>,
+    ) {
+        let Some(reqs) = self.dry_run_reqs.remove(&(rid, remote)) else {
+            debug!(target: "service", "No dry-run requests found for {rid}..");
+            return;
+        };
+        if let Err(e) = &result {
+            error!(target: "service", "Listing refs of {rid} on {remote} failed: {e}");
+        }
+        let local = self.node_id();
+
+        for DryRunRequest {
+            mut summary,
+            namespaces: wanted,
+            resp,
+        } in reqs
+        {
+            match &result {
+                Ok(refs) => {
+                    let mut namespaces = HashSet::new();
+                    let mut count = 0;
+
+                    for r in refs {
+                        let Ok(nid) = NodeId::from_str(r.namespace().as_str()) else {
+                            continue;
+                        };
+                        // Our own refs are never fetched.
+                        if nid == local {
+                            continue;
+                        }
+                        if let Namespaces::Trusted(trusted) = &wanted {
+                            if !trusted.contains(&nid) {
+                                continue;
+                            }
+                        }
+                        namespaces.insert(nid);
+                        count += 1;
+                    }
+                    summary.namespaces = Some(namespaces.len());
+                    summary.refs = Some(count);
+                }
+                Err(e) => {
+                    summary.note = Some(format!("error listing refs on {remote}: {e}"));
+                }
+            }
+            resp.send(summary).ok();
+        }
+    }
+
+    /// Change one of the [`config::Limits::RUNTIME`] limits. Existing sessions keep the
+    /// limits they were established with, while new sessions get the new limits.
+    pub fn set_limit(&mut self, name: &str, value: serde_json::Value) -> Result<(), CommandError> {
+        if let Err(e) = self.config.limits.set(name, value) {
+            warn!(target: "service", "Error setting limit: {e}");
+            return Err(e.into());
+        }
+        let limits = &self.config.limits;
+        let value = serde_json::to_value(limits)
+            .ok()
+            .and_then(|l| l.get(name).cloned())
+            .unwrap_or_default();
+
+        self.outbox.set_relay_rate(limits.relay_rate);
+
+        info!(target: "service", "Limit `{name}` set to {value}");
+        self.emit(Event::ConfigChanged {
+            name: name.to_owned(),
+            value,
+        });
+        Ok(())
+    }
+
+    /// Get the verified identity of a repository. If the repository isn't in storage, its
+    /// identity branch is fetched from a connected seed, and the response is sent once the
+    /// fetch completes. Nothing is stored, and the tracking policy is left untouched.
+    pub fn inspect(&mut self, rid: Id, resp: chan::Sender<Result<Inspection, CommandError>>) {
+        match self.storage.contains(&rid) {
+            Ok(true) => {
+                let result = self
+                    .storage
+                    .repository(rid)
+                    .map_err(CommandError::from)
+                    .and_then(|repo| Ok(repo.identity()?.verified(rid)?))
+                    .map(|identity| Inspection {
+                        rid,
+                        source: InspectSource::Local,
+                        head: identity.head,
+                        doc: identity.doc,
+                    });
+                resp.send(result).ok();
+
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!(target: "service", "Error checking storage for {rid}: {e}");
+                resp.send(Err(e.into())).ok();
+
+                return;
+            }
+        }
+        let seeds = match self.routing.get(&rid) {
+            Ok(seeds) => seeds,
+            Err(e) => {
+                error!(target: "service", "Error reading routing table for {rid}: {e}");
+                resp.send(Err(e.into())).ok();
+
+                return;
+            }
+        };
+        let Some(session) = seeds
+            .iter()
+            .filter_map(|nid| self.sessions.get(nid))
+            .find(|s| s.is_connected())
+        else {
+            resp.send(Err(CommandError::NotFound(rid))).ok();
+            return;
+        };
+        match self.inspect_reqs.entry((rid, session.id)) {
+            Entry::Occupied(mut e) => {
+                debug!(target: "service", "Identity of {rid} already being fetched from {}; waiting for result..", session.id);
+                e.get_mut().push(resp);
+            }
+            Entry::Vacant(e) => {
+                debug!(target: "service", "Fetching identity of {rid} from {}..", session.id);
+
+                self.outbox.fetch_identity(session, rid);
+                e.insert(vec![resp]);
             }
         }
     }
 
+    /// The identity branch of a repository was fetched from a peer, as part of an inspection.
+    pub fn inspected(&mut self, rid: Id, remote: NodeId, result: Result<Identity<Id>, FetchError>) {
+        let Some(resps) = self.inspect_reqs.remove(&(rid, remote)) else {
+            debug!(target: "service", "No inspect requests found for {rid}..");
+            return;
+        };
+        let result = match result {
+            Ok(identity) => Ok(Inspection {
+                rid,
+                source: InspectSource::Seed { nid: remote },
+                head: identity.head,
+                doc: identity.doc,
+            }),
+            Err(e) => {
+                error!(target: "service", "Fetching identity of {rid} from {remote} failed: {e}");
+                Err(e.to_string())
+            }
+        };
+        for resp in resps {
+            let result = result
+                .clone()
+                .map_err(|reason| CommandError::InspectFailed(remote, reason));
+            resp.send(result).ok();
+        }
+    }
+
     pub fn fetched(
         &mut self,
         rid: Id,
         remote: NodeId,
-        result: Result<(Vec<RefUpdate>, HashSet<NodeId>), FetchError>,
+        result: Result<Transferred, FetchError>,
+        received: u64,
     ) {
+        let user_requested = self.fetch_reqs.contains(&rid, &remote);
+        let minimal = self.minimal_fetches.remove(&(rid, remote));
+        let succeeded = result.is_ok();
+        self.metrics.fetched(succeeded);
+        let took = self
+            .sessions
+            .get_mut(&remote)
+            .and_then(|s| s.fetched(rid, self.clock));
+        let record = FetchRecord {
+            time: self.time(),
+            seed: remote,
+            duration: took.map(|d| d.as_millis() as u64),
+            received: Some(received),
+            outcome: match &result {
+                Ok(_) => FetchOutcome::Success,
+                Err(e) => FetchOutcome::Failed {
+                    failure: e.failure(),
+                    reason: e.to_string(),
+                },
+            },
+        };
+        if let Err(e) = self.routing.fetched(&rid, &record) {
+            error!(target: "service", "Error recording fetch of {rid} in fetch history: {e}");
+        }
+
+        self.fetches.insert(
+            (rid, remote),
+            FetchAttempt {
+                time: self.time(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                attempts: 0,
+                retry_at: None,
+            },
+        );
+        let mut delegates_added = Vec::new();
         let result = match result {
-            Ok((updated, namespaces)) => {
+            Ok((updated, namespaces, fetched, skipped)) => {
                 debug!(target: "service", "Fetched {rid} from {remote} successfully");
 
+                for (nid, refs) in &fetched {
+                    debug!(target: "service", "Fetched {refs} ref(s) of namespace {nid} of {rid} from {remote}");
+                }
+                for (nid, reason) in &skipped {
+                    info!(target: "service", "Namespace {nid} of {rid} not fetched from {remote}: {reason}");
+                }
+
+                if self.fetch_retries.remove(&rid).is_some() {
+                    debug!(target: "service", "Fetch retry of {rid} from {remote} succeeded");
+                }
+                self.activity.entry(rid).or_default().fetched = Some(self.clock);
+                self.record_fetched(rid, &namespaces);
+                // A regular fetch completes the replication of a minimal one.
+                match self.watermarks.set_partial(&rid, minimal) {
+                    Ok(true) if minimal => {
+                        info!(target: "service", "Fetched the delegates of {rid} from {remote}; the rest is fetched on the next sync");
+                    }
+                    Ok(true) => {
+                        info!(target: "service", "Replication of {rid} completed with fetch from {remote}");
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!(target: "service", "Error recording replication state of {rid}: {e}");
+                    }
+                }
+                // The fetch may have updated the repository's delegates.
+                self.update_origin(&rid);
+
                 for update in &updated {
                     debug!(target: "service", "Ref updated: {update} for {rid}");
                 }
-                self.emitter.emit(Event::RefsFetched {
+                self.emit(Event::RefsFetched {
                     remote,
                     rid,
                     updated: updated.clone(),
                 });
+                delegates_added = self.delegates_changed(rid, &updated);
+                self.log_activity(Activity::FetchCompleted {
+                    rid,
+                    nid: remote,
+                    updated: updated
+                        .iter()
+                        .filter(|u| !matches!(u, RefUpdate::Skipped { .. }))
+                        .count(),
+                });
 
                 FetchResult::Success {
                     updated,
                     namespaces,
+                    fetched,
+                    skipped,
                 }
             }
             Err(err) => {
                 let reason = err.to_string();
                 error!(target: "service", "Fetch failed for {rid} from {remote}: {reason}");
+                self.log_activity(Activity::FetchFailed {
+                    rid,
+                    nid: remote,
+                    reason: reason.clone(),
+                });
 
                 // For now, we only disconnect the remote in case of timeout. In the future,
                 // there may be other reasons to disconnect.
-                if err.is_timeout() {
+                if !user_requested && err.is_transient() {
+                    self.schedule_fetch_retry(rid, remote);
+                } else {
+                    self.fetch_retries.remove(&rid);
+                }
+                if let FetchError::SizeLimitExceeded { .. } = err {
+                    // The seed is either misbehaving, or serving a repository too large for
+                    // us to fetch; either way, we avoid it for a while.
+                    self.back_off(remote);
+                    self.outbox.disconnect(remote, DisconnectReason::Fetch(err));
+                } else if err.is_timeout() {
                     self.outbox.disconnect(remote, DisconnectReason::Fetch(err));
                 }
                 FetchResult::Failed { reason }
             }
         };
 
-        if let Some(results) = self.fetch_reqs.remove(&(rid, remote)) {
+        if user_requested {
             debug!(target: "service", "Found existing fetch request, sending result..");
 
-            if results.send(result).is_err() {
-                error!(target: "service", "Error sending fetch result for {rid}..");
-            } else {
-                debug!(target: "service", "Sent fetch result for {rid}..");
-            }
+            self.fetch_reqs.reply(&rid, &remote, result);
         } else {
             debug!(target: "service", "No fetch requests found for {rid}..");
 
@@ -690,8 +2206,12 @@ where
                 FetchResult::Success {
                     updated,
                     namespaces,
-                } if !updated.is_empty() => {
-                    if let Err(e) = self.announce_refs(rid, namespaces) {
+                    ..
+                } if updated
+                    .iter()
+                    .any(|u| !matches!(u, RefUpdate::Skipped { .. })) =>
+                {
+                    if let Err(e) = self.announce_new_refs(rid, namespaces) {
                         error!(target: "service", "Failed to announce new refs: {e}");
                     }
                 }
@@ -707,13 +2227,148 @@ where
         // network, if necessary.
         self.sync_and_announce();
 
+        // Only successful fetches tell us how long fetching this repository takes.
+        if let Some(took) = took.filter(|_| succeeded) {
+            self.fetch_estimates.insert(rid, took);
+        }
         if let Some(s) = self.sessions.get_mut(&remote) {
-            if let Some(dequeued) = s.fetched(rid) {
+            if let Some(dequeued) = s.dequeue(self.clock) {
                 debug!(target: "service", "Dequeued fetch {dequeued} from session {remote}..");
 
                 self.fetch(dequeued, &remote);
             }
         }
+        if !delegates_added.is_empty() {
+            self.fetch_delegates(rid, remote);
+        }
+    }
+
+    /// Check whether a fetch changed the delegates of a repository, by updating the
+    /// identity branch of one of its delegates. If so, emits [`Event::DelegatesChanged`],
+    /// and returns the added delegates.
+    fn delegates_changed(&mut self, rid: Id, updated: &[RefUpdate]) -> Vec<NodeId> {
+        let identities = updated
+            .iter()
+            .filter_map(|update| {
+                let RefUpdate::Updated { name, old, .. } = update else {
+                    return None;
+                };
+                let name = name.to_namespaced()?;
+                let nid = NodeId::from_namespaced(&name).ok()?;
+
+                (name.strip_namespace().as_str() == git::refs::storage::IDENTITY_BRANCH.as_str())
+                    .then_some((nid, *old))
+            })
+            .collect::<Vec<_>>();
+        if identities.is_empty() {
+            return vec![];
+        }
+        let repo = match self.storage.repository(rid) {
+            Ok(repo) => repo,
+            Err(e) => {
+                error!(target: "service", "Error opening repository {rid}: {e}");
+                return vec![];
+            }
+        };
+        // Find the identity revision that a delegate was on before the fetch.
+        let previous = identities.into_iter().find_map(|(nid, old)| {
+            let previous = Doc::<Verified>::load_at(old, &repo).ok()?.doc;
+
+            previous.is_delegate(&nid).then_some(previous.delegates)
+        });
+        let Some(previous) = previous else {
+            return vec![];
+        };
+        let current = match repo.delegates() {
+            Ok(delegates) => delegates,
+            Err(e) => {
+                error!(target: "service", "Error getting delegates of {rid}: {e}");
+                return vec![];
+            }
+        };
+        let previous = previous
+            .into_iter()
+            .map(NodeId::from)
+            .collect::<BTreeSet<_>>();
+        let current = current
+            .into_iter()
+            .map(NodeId::from)
+            .collect::<BTreeSet<_>>();
+        let added = current.difference(&previous).copied().collect::<Vec<_>>();
+        let removed = previous.difference(&current).copied().collect::<Vec<_>>();
+
+        if added.is_empty() && removed.is_empty() {
+            return vec![];
+        }
+        info!(
+            target: "service",
+            "Delegates of {rid} changed: {} added, {} removed",
+            added.len(),
+            removed.len()
+        );
+        // Nb. The namespaces of removed delegates are kept: they may still be tracked.
+        self.emit(Event::DelegatesChanged {
+            rid,
+            added: added.clone(),
+            removed,
+        });
+
+        added
+    }
+
+    /// Fetch a repository from its connected seeds, after delegates were added to it.
+    /// The namespaces of the new delegates weren't fetched along with the identity update,
+    /// if only trusted nodes are tracked.
+    fn fetch_delegates(&mut self, rid: Id, remote: NodeId) {
+        match self.tracking.repo_policy(&rid) {
+            Ok(repo) if repo.policy == tracking::Policy::Track && repo.scope == Scope::Trusted => {}
+            // When tracking all nodes, every namespace was fetched already.
+            Ok(_) => return,
+            Err(e) => {
+                error!(target: "service", "Error getting tracking policy for {rid}: {e}");
+                return;
+            }
+        }
+        let connected = match self.seeds(&rid) {
+            Ok(seeds) => seeds.partition().0,
+            Err(e) => {
+                error!(target: "service", "Error reading routing table for {rid}: {e}");
+                vec![]
+            }
+        };
+        // The seed that served the new identity likely has the new delegates' refs too.
+        let mut seeds = vec![remote];
+        seeds.extend(
+            connected
+                .into_iter()
+                .map(|s| s.nid)
+                .filter(|nid| *nid != remote),
+        );
+
+        for seed in seeds {
+            if !self.sessions.get(&seed).map_or(false, |s| s.is_connected()) {
+                continue;
+            }
+            debug!(target: "service", "Fetching new delegates of {rid} from {seed}..");
+
+            self.fetch(rid, &seed);
+        }
+    }
+
+    /// A data transfer (fetch or upload) with the given peer was handed to a worker.
+    /// While a transfer is active, the peer isn't pinged or considered unresponsive.
+    pub fn transfer_started(&mut self, remote: &NodeId) {
+        if let Some(session) = self.sessions.get_mut(remote) {
+            session.transfer_started(self.clock);
+        }
+    }
+
+    /// A data transfer with the given peer was completed by a worker. The peer isn't
+    /// pinged until it has been idle for the keep-alive delta again.
+    pub fn transfer_finished(&mut self, remote: &NodeId) {
+        if let Some(session) = self.sessions.get_mut(remote) {
+            session.transfer_finished(self.clock);
+        }
     }
 
     /// Inbound connection attempt.
@@ -744,7 +2399,13 @@ where
 
     pub fn connected(&mut self, remote: NodeId, addr: Address, link: Link) {
         info!(target: "service", "Connected to {} ({:?})", remote, link);
-        self.emitter.emit(Event::PeerConnected { nid: remote });
+        self.emit(Event::PeerConnected { nid: remote });
+        self.metrics.session_opened();
+        self.log_activity(Activity::Connected {
+            nid: remote,
+            addr: addr.clone(),
+            inbound: link.is_inbound(),
+        });
 
         let msgs = self.initial(link);
         let now = self.time();
@@ -758,6 +2419,11 @@ where
                     error!(target: "service", "Error updating address book with connection: {e}");
                 }
             }
+            self.fetch_deadlines.retain(|(_, nid), _| *nid != remote);
+
+            for rid in self.pending_fetches.remove(&remote).unwrap_or_default() {
+                self.fetch(rid, &remote);
+            }
         } else {
             match self.sessions.entry(remote) {
                 Entry::Occupied(e) => {
@@ -785,10 +2451,30 @@ where
         let since = self.local_time();
 
         debug!(target: "service", "Disconnected from {} ({})", remote, reason);
-        self.emitter.emit(Event::PeerDisconnected {
+        self.emit(Event::PeerDisconnected {
             nid: remote,
             reason: reason.to_string(),
         });
+        self.log_activity(Activity::Disconnected {
+            nid: remote,
+            reason: reason.to_string(),
+        });
+        self.metrics.session_closed();
+        self.refs_synced.retain(|(_, nid), _| *nid != remote);
+        self.minimal_reqs.retain(|(_, nid)| *nid != remote);
+        // Repositories that are still missing are fetched with the rest of our missing
+        // inventory, so there's no need to keep these around. Users waiting on these
+        // fetches are told that they failed.
+        for rid in self.pending_fetches.remove(&remote).unwrap_or_default() {
+            self.fetch_deadlines.remove(&(rid, remote));
+            self.fetch_reqs.reply(
+                &rid,
+                &remote,
+                FetchResult::Failed {
+                    reason: format!("disconnected from {remote}"),
+                },
+            );
+        }
 
         let Some(session) = self.sessions.get_mut(&remote) else {
             if cfg!(debug_assertions) {
@@ -799,21 +2485,88 @@ where
         };
         let link = session.link;
 
+        // Peers disconnected by the user aren't reconnected to until the cool-off passes.
+        // Nb. Since the disconnect isn't the peer's fault, it doesn't count as a failed
+        // connection attempt either.
+        if reason.is_command() {
+            self.user_disconnects
+                .insert(remote, since + self.config.limits.disconnect_cooloff);
+        }
+
         // If the peer disconnected while we were fetching, return a failure to any
         // potential fetcher.
+        let mut retries = Vec::new();
         for rid in session.fetching() {
-            if let Some(resp) = self.fetch_reqs.remove(&(rid, remote)) {
-                resp.send(FetchResult::Failed {
+            let replied = self.fetch_reqs.reply(
+                &rid,
+                &remote,
+                FetchResult::Failed {
                     reason: format!("disconnected: {reason}"),
-                })
-                .ok();
+                },
+            );
+            if !replied && reason.is_transient() {
+                // Fetches that weren't requested by the user are retried later, since
+                // there is no one to report the failure to.
+                retries.push(rid);
+            }
+        }
+        let pending = self
+            .dry_run_reqs
+            .keys()
+            .filter(|(_, nid)| *nid == remote)
+            .copied()
+            .collect::<Vec<_>>();
+        for key in pending {
+            for DryRunRequest {
+                mut summary, resp, ..
+            } in self.dry_run_reqs.remove(&key).unwrap_or_default()
+            {
+                summary.note = Some(format!("disconnected: {reason}"));
+                resp.send(summary).ok();
+            }
+        }
+        let pending = self
+            .inspect_reqs
+            .keys()
+            .filter(|(_, nid)| *nid == remote)
+            .copied()
+            .collect::<Vec<_>>();
+        for key in pending {
+            for resp in self.inspect_reqs.remove(&key).unwrap_or_default() {
+                resp.send(Err(CommandError::Disconnected(remote, reason.to_string())))
+                    .ok();
             }
         }
 
         // Attempt to re-connect to persistent peers.
         if self.config.peer(&remote).is_some() {
-            let delay = LocalDuration::from_secs(2u64.saturating_pow(session.attempts() as u32))
-                .clamp(MIN_RECONNECTION_DELTA, MAX_RECONNECTION_DELTA);
+            let delay = RECONNECTION_BACKOFF.next_delay(session.attempts(), &mut self.rng);
+            // Peers that misbehaved are only re-connected to once their backoff expires.
+            let delay = match self.backoffs.get(&remote) {
+                Some(backoff) if backoff.until > since => delay.max(backoff.until - since),
+                _ => delay,
+            };
+            let delay = match self.user_disconnects.get(&remote) {
+                Some(until) if *until > since => {
+                    session.user_disconnected = true;
+                    delay.max(*until - since)
+                }
+                _ => delay,
+            };
+            // Peers that can't be reached are only retried once in a while.
+            let delay = if RECONNECTION_BACKOFF.should_give_up(session.attempts()) {
+                let retention = self.config.limits.session_retention;
+                session.gave_up_until = Some(since + retention);
+
+                info!(
+                    target: "service",
+                    "Giving up on {remote} after {} attempts, until {retention} from now",
+                    session.attempts()
+                );
+                delay.max(retention)
+            } else {
+                delay
+            };
 
             // Nb. We always try to reconnect to persistent peers, even when the error appears
             // to not be transient.
@@ -830,6 +2583,33 @@ where
                 self.maintain_connections();
             }
         }
+        for rid in retries {
+            self.schedule_fetch_retry(rid, remote);
+        }
+    }
+
+    /// Account for gossip bytes sent to a peer.
+    pub fn bytes_sent(&mut self, remote: &NodeId, kind: MessageKind, bytes: usize) {
+        self.bandwidth.sent(kind, bytes, self.clock);
+        if kind.is_announcement() {
+            self.announcements_sent += 1;
+        }
+
+        if let Some(session) = self.sessions.get_mut(remote) {
+            session.sent += bytes as u64;
+        }
+    }
+
+    /// Account for gossip bytes received from a peer.
+    pub fn bytes_received(&mut self, remote: &NodeId, kind: MessageKind, bytes: usize) {
+        self.bandwidth.received(kind, bytes, self.clock);
+        if kind.is_announcement() {
+            self.announcements_received += 1;
+        }
+
+        if let Some(session) = self.sessions.get_mut(remote) {
+            session.received += bytes as u64;
+        }
     }
 
     pub fn received_message(&mut self, remote: NodeId, message: Message) {
@@ -844,6 +2624,51 @@ where
         }
     }
 
+    /// Record the protocol version spoken by a peer, as found in the frames it sends us.
+    pub fn received_version(&mut self, remote: &NodeId, version: u8) {
+        let Some(session) = self.sessions.get_mut(remote) else {
+            return;
+        };
+        if session.version != Some(version) {
+            debug!(target: "service", "Peer {remote} speaks protocol version {version}");
+
+            let supported = session.supports(wire::PROTOCOL_VERSION);
+            session.version = Some(version);
+
+            // Now that we know the peer skips messages it doesn't know, we can send it
+            // our cobs announcement, which older nodes may not know.
+            if !supported && session.supports(wire::PROTOCOL_VERSION) && session.is_connected() {
+                self.outbox
+                    .write(session, Message::cobs(self.cobs.clone(), &self.signer));
+            }
+        }
+    }
+
+    /// Handle a well-framed message of a type we don't know.
+    ///
+    /// Peers speaking our protocol version or a newer one may send messages we don't know
+    /// about, eg. when they run a newer release, which are skipped. Other peers are
+    /// misbehaving, and are disconnected from.
+    pub fn received_unknown(&mut self, remote: NodeId, kind: u16) {
+        let Some(session) = self.sessions.get(&remote) else {
+            warn!(target: "service", "Session not found for {remote}");
+            return;
+        };
+        if session.supports(wire::PROTOCOL_VERSION) {
+            debug!(
+                target: "service",
+                "Skipping message of unknown type {kind} from {remote} (version={:?})", session.version
+            );
+        } else {
+            warn!(target: "service", "Received message of unknown type {kind} from {remote}");
+
+            self.outbox.disconnect(
+                remote,
+                DisconnectReason::Session(session::Error::Misbehavior),
+            );
+        }
+    }
+
     /// Handle an announcement message.
     ///
     /// Returns `true` if this announcement should be stored and relayed to connected peers,
@@ -865,21 +2690,38 @@ where
 
         // Ignore our own announcements, in case the relayer sent one by mistake.
         if *announcer == self.node_id() {
+            self.metrics.dropped(DropReason::Own);
             return Ok(false);
         }
         let now = self.clock;
         let timestamp = message.timestamp();
         let relay = self.config.relay;
-        let peer = self
-            .gossip
-            .nodes
-            .entry(*announcer)
-            .or_insert_with(Node::default);
 
+        // Announcements must carry a timestamp; a zero timestamp is never valid.
+        if timestamp == 0 {
+            warn!(target: "service", "Peer {relayer} relayed announcement from {announcer} with zero timestamp");
+            return Err(session::Error::InvalidTimestamp(timestamp));
+        }
         // Don't allow messages from too far in the future.
         if timestamp.saturating_sub(now.as_millis()) > MAX_TIME_DELTA.as_millis() as u64 {
             return Err(session::Error::InvalidTimestamp(timestamp));
         }
+        // Ignore messages that are older than what we keep in our routing table, since they
+        // would be pruned right away. The session is still considered alive.
+        if timestamp
+            < now
+                .as_millis()
+                .saturating_sub(self.config.limits.routing_max_age.as_millis() as u64)
+        {
+            trace!(target: "service", "Ignoring expired announcement from {announcer} (time={timestamp})");
+            self.metrics.dropped(DropReason::Expired);
+            return Ok(false);
+        }
+        let peer = self
+            .gossip
+            .nodes
+            .entry(*announcer)
+            .or_insert_with(Node::default);
 
         match message {
             AnnouncementMessage::Inventory(message) => {
@@ -887,22 +2729,37 @@ where
                 // out last seen time.
                 if !peer.inventory_announced(announcement.clone()) {
                     trace!(target: "service", "Ignoring stale inventory announcement from {announcer} (t={})", self.time());
+                    self.metrics.dropped(DropReason::Stale);
                     return Ok(false);
                 }
+                peer.last_updated = now;
 
-                match self.sync_routing(&message.inventory, *announcer, message.timestamp) {
+                match self.sync_routing(&message.inventory, *announcer, *relayer, message.timestamp)
+                {
                     Ok(synced) => {
                         if synced.is_empty() {
                             trace!(target: "service", "No routes updated by inventory announcement from {announcer}");
+                            self.metrics.dropped(DropReason::Unchanged);
                             return Ok(false);
                         }
                     }
                     Err(e) => {
                         error!(target: "service", "Error processing inventory from {}: {}", announcer, e);
+                        self.metrics.dropped(DropReason::Error);
                         return Ok(false);
                     }
                 }
 
+                // Track the repositories of the nodes we mirror. Since announcements are
+                // signed by their announcer, relaying nodes can't make us mirror anything.
+                let mut mirrored = false;
+                for id in message.inventory.as_slice() {
+                    mirrored |= self.mirror_repo(id, announcer);
+                }
+                if mirrored {
+                    self.resubscribe();
+                }
+
                 for id in message.inventory.as_slice() {
                     // TODO: Move this out (good luck with the borrow checker).
                     if let Some(sess) = self.sessions.get_mut(announcer) {
@@ -915,14 +2772,27 @@ where
 
                         // If we're tracking and connected to the announcer, and we don't have
                         // the inventory, fetch it from the announcer.
-                        if self.tracking.is_repo_tracked(id).expect(
-                            "Service::handle_announcement: error accessing tracking configuration",
-                        ) {
+                        let tracked = match self.tracking.is_repo_tracked(id) {
+                            Ok(tracked) => tracked,
+                            Err(e) => {
+                                self.store_error(*announcer, e);
+                                continue;
+                            }
+                        };
+                        if tracked {
                             // Only if we do not have the repository locally do we fetch here.
                             // If we do have it, only fetch after receiving a ref announcement.
                             match self.storage.contains(id) {
                                 Ok(true) => {
-                                    // Do nothing.
+                                    // If we don't know the announcer's refs, ask for them
+                                    // instead of fetching blindly.
+                                    if self.announced(announcer, id).is_none() {
+                                        self.request_announcements(
+                                            announcer,
+                                            *id,
+                                            AnnouncementKinds::REFS,
+                                        );
+                                    }
                                 }
                                 Ok(false) => {
                                     debug!(target: "service", "Missing tracked inventory {id}; initiating fetch..");
@@ -941,11 +2811,31 @@ where
             }
             // Process a peer inventory update announcement by (maybe) fetching.
             AnnouncementMessage::Refs(message) => {
-                for theirs in message.refs.iter() {
-                    if theirs.verify(&theirs.id).is_err() {
-                        warn!(target: "service", "Peer {relayer} relayed refs announcement with invalid signature for {}", theirs.id);
-                        return Err(session::Error::Misbehavior);
-                    }
+                // Each entry is signed by its remote. An entry with an invalid signature,
+                // eg. of stale or corrupt signed refs, doesn't invalidate the others, which
+                // are still processed.
+                let (valid, invalid): (Vec<_>, Vec<_>) = message
+                    .refs
+                    .iter()
+                    .partition(|theirs| theirs.verify(&theirs.id).is_ok());
+
+                for theirs in &invalid {
+                    warn!(target: "service", "Peer {relayer} relayed refs announcement from {announcer} with invalid signature for {}", theirs.id);
+                }
+                if valid.is_empty() && !invalid.is_empty() {
+                    return Err(session::Error::Misbehavior);
+                }
+                // Nb. Only the announcer is penalized for its invalid entries, since the
+                // relayer may well be honest.
+                if self
+                    .gossip
+                    .nodes
+                    .get_mut(announcer)
+                    .map_or(false, |node| node.invalid_refs(now) > MAX_INVALID_REFS)
+                {
+                    debug!(target: "service", "Dropping refs announcement from {announcer}: too many invalid refs announced");
+                    self.metrics.dropped(DropReason::Invalid);
+                    return Ok(false);
                 }
 
                 // We update inventories when receiving ref announcements, as these could come
@@ -954,30 +2844,64 @@ where
                     self.routing
                         .insert([&message.rid], *announcer, message.timestamp)
                 {
+                    if let Err(e) = self.routing.relayed(
+                        [&message.rid],
+                        *announcer,
+                        *relayer,
+                        message.timestamp,
+                        now.as_millis(),
+                    ) {
+                        error!(target: "service", "Error recording provenance of routing entry: {e}");
+                    }
                     if let &[(_, InsertResult::SeedAdded)] = result.as_slice() {
-                        self.emitter.emit(Event::SeedDiscovered {
-                            rid: message.rid,
-                            nid: *relayer,
-                        });
+                        self.metrics.routes_inserted(1);
+                        self.seed_discovered(message.rid, *announcer);
                         info!(target: "service", "Routing table updated for {} with seed {announcer}", message.rid);
                     }
                 }
                 // Discard announcement messages we've already seen, otherwise update
                 // our last seen time.
-                if !peer.refs_announced(message.rid, announcement.clone()) {
+                let peer = self
+                    .gossip
+                    .nodes
+                    .entry(*announcer)
+                    .or_insert_with(Node::default);
+                if !peer.refs_announced(message.rid, announcement.clone(), now) {
                     trace!(target: "service", "Ignoring stale refs announcement from {announcer} (time={timestamp})");
+                    self.metrics.dropped(DropReason::Stale);
                     return Ok(false);
                 }
+                peer.last_updated = now;
+
+                let filtered;
+                let message = if invalid.is_empty() {
+                    message
+                } else {
+                    // Nb. Only counted once the announcement is known to be new, so that
+                    // the same announcement relayed by several peers is only counted once.
+                    if peer.count_invalid_refs(invalid.len(), now) > MAX_INVALID_REFS {
+                        warn!(target: "service", "Node {announcer} announced too many refs with invalid signatures; dropping its refs announcements");
+                        self.metrics.dropped(DropReason::Invalid);
+                        return Ok(false);
+                    }
+                    filtered = RefsAnnouncement {
+                        rid: message.rid,
+                        refs: BoundedVec::collect_from(&mut valid.into_iter().cloned()),
+                        timestamp: message.timestamp,
+                    };
+                    &filtered
+                };
+                // The announcer's signature covers all entries, so the announcement can't be
+                // relayed without the invalid ones. It isn't relayed at all, since peers
+                // may consider it misbehavior.
+                let relay = relay && invalid.is_empty();
 
                 // Check if the announcer is in sync with our own refs, and if so emit an event.
                 // This event is used for showing sync progress to users.
                 match message.is_synced(&self.node_id(), &self.storage) {
                     Ok(synced) => {
                         if synced {
-                            self.emitter.emit(Event::RefsSynced {
-                                rid: message.rid,
-                                remote: *announcer,
-                            });
+                            self.refs_synced(message, *announcer);
                         }
                     }
                     Err(e) => {
@@ -985,10 +2909,19 @@ where
                     }
                 }
 
+                if self.mirror_repo(&message.rid, announcer) {
+                    self.resubscribe();
+                }
+
                 // TODO: Buffer/throttle fetches.
-                let repo_entry = self.tracking.repo_policy(&message.rid).expect(
-                    "Service::handle_announcement: error accessing repo tracking configuration",
-                );
+                let repo_entry = match self.tracking.repo_policy(&message.rid) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        self.store_error(*announcer, e);
+                        self.metrics.dropped(DropReason::Error);
+                        return Ok(false);
+                    }
+                };
 
                 if repo_entry.policy == tracking::Policy::Track {
                     // Refs can be relayed by peers who don't have the data in storage,
@@ -1011,12 +2944,22 @@ where
                         );
                     }
                     return Ok(relay);
+                } else if repo_entry.policy == tracking::Policy::Watch {
+                    // Watched repositories are never fetched, but their announcements are
+                    // relayed, and surfaced to subscribers.
+                    self.emit(Event::RefsAnnounced {
+                        remote: *announcer,
+                        rid: message.rid,
+                        timestamp: message.timestamp,
+                    });
+                    return Ok(relay);
                 } else {
                     debug!(
                         target: "service",
                         "Ignoring refs announcement from {announcer}: repository {} isn't tracked",
                         message.rid
                     );
+                    self.metrics.dropped(DropReason::Untracked);
                 }
             }
             AnnouncementMessage::Node(
@@ -1030,8 +2973,10 @@ where
                 // our last seen time.
                 if !peer.node_announced(announcement.clone()) {
                     trace!(target: "service", "Ignoring stale node announcement from {announcer}");
+                    self.metrics.dropped(DropReason::Stale);
                     return Ok(false);
                 }
+                peer.last_updated = now;
 
                 // If this node isn't a seed, we're not interested in adding it
                 // to our address book, but other nodes may be, so we relay the message anyway.
@@ -1059,13 +3004,34 @@ where
                             );
                             return Ok(relay);
                         }
+                        self.metrics.dropped(DropReason::Unchanged);
                     }
                     Err(err) => {
                         // An error here is due to a fault in our address store.
                         error!(target: "service", "Error processing node announcement from {announcer}: {err}");
+                        self.metrics.dropped(DropReason::Error);
                     }
                 }
             }
+            AnnouncementMessage::Cobs(message) => {
+                // Discard cobs messages we've already seen, otherwise update
+                // our last seen time.
+                if !peer.cobs_announced(announcement.clone()) {
+                    trace!(target: "service", "Ignoring stale cobs announcement from {announcer}");
+                    self.metrics.dropped(DropReason::Stale);
+                    return Ok(false);
+                }
+                peer.last_updated = now;
+
+                if let Err(e) = self
+                    .addresses
+                    .set_cobs(announcer, message.cobs.iter().cloned())
+                {
+                    // An error here is due to a fault in our address store.
+                    error!(target: "service", "Error processing cobs announcement from {announcer}: {e}");
+                }
+                return Ok(relay);
+            }
         }
         Ok(false)
     }
@@ -1077,10 +3043,20 @@ where
         message: &RefsAnnouncement,
         scope: &tracking::Scope,
     ) -> Result<bool, Error> {
-        // First, check the freshness.
-        if !message.is_fresh(&self.storage)? {
-            debug!(target: "service", "All refs of {} are already in local storage", &message.rid);
-            return Ok(false);
+        // Repositories bootstrapped with a minimal fetch are missing namespaces that the
+        // announcement may not mention, so we take the chance to complete them.
+        if self.is_partial(&message.rid) {
+            debug!(target: "service", "Repository {} is partially replicated", &message.rid);
+        } else {
+            // First, check the freshness, starting with what we last fetched.
+            if self.is_fetched(message) {
+                debug!(target: "service", "All refs of {} were already fetched", &message.rid);
+                return Ok(false);
+            }
+            if !message.is_fresh(&self.storage)? {
+                debug!(target: "service", "All refs of {} are already in local storage", &message.rid);
+                return Ok(false);
+            }
         }
 
         // Second, check the scope.
@@ -1125,8 +3101,8 @@ where
             trace!(target: "service", "Rate limiting message from {remote} ({})", peer.addr);
             return Ok(());
         }
-        peer.last_active = self.clock;
         message.log(log::Level::Debug, remote, Link::Inbound);
+        self.metrics.message(message.kind());
 
         trace!(target: "service", "Received message {:?} from {}", &message, peer.id);
 
@@ -1137,33 +3113,97 @@ where
                 let relayer_addr = peer.addr.clone();
                 let announcer = ann.node;
 
+                let relay = self.handle_announcement(&relayer, &relayer_addr, &ann);
+
+                if relay.is_err() {
+                    self.metrics.dropped(DropReason::Invalid);
+                }
                 // Returning true here means that the message should be relayed.
-                if self.handle_announcement(&relayer, &relayer_addr, &ann)? {
+                if relay? {
+                    self.metrics.relayed();
                     // Choose peers we should relay this message to.
                     // 1. Don't relay to the peer who sent us this message.
                     // 2. Don't relay to the peer who signed this announcement.
-                    let relay_to = self
+                    // 3. Don't relay to peers whose own announcements show they are up to date.
+                    // 4. Don't relay cobs announcements to peers that may not skip them.
+                    let mut relay_to: Vec<&Session> = Vec::new();
+                    for (_, p) in self
                         .sessions
-                        .connected()
+                        .connected_mut()
                         .filter(|(id, _)| *id != remote && *id != &announcer)
-                        .map(|(_, p)| p);
-
-                    self.outbox.relay(ann, relay_to);
+                        .filter(|(id, _)| !self.gossip.is_known_by(id, &ann))
+                        .filter(|(_, p)| gossip::is_supported(p, &ann.message))
+                    {
+                        // Peers still being sent their subscription backlog get the
+                        // announcement queued in their backlog, so that it isn't sent
+                        // ahead of older announcements.
+                        if p.has_backlog() {
+                            if p.subscribe
+                                .as_ref()
+                                .map_or(false, |s| ann.matches(&s.filter))
+                            {
+                                p.queue_backlog([ann.clone()]);
+                            }
+                        } else {
+                            relay_to.push(p);
+                        }
+                    }
 
+                    if let io::Relay::Throttled { started: true } =
+                        self.outbox.relay(ann, relay_to, self.clock)
+                    {
+                        self.emit(Event::BandwidthThrottled {
+                            rate: self.config.limits.relay_rate.unwrap_or_default(),
+                        });
+                    }
                     return Ok(());
                 }
             }
+            // Process an announcement we asked for. It isn't relayed.
+            (session::State::Connected { .. }, Message::AnnouncementResponse(ann)) => {
+                peer.last_protocol_response_at = self.clock;
+
+                let relayer = peer.id;
+                let relayer_addr = peer.addr.clone();
+                let result = self.handle_announcement(&relayer, &relayer_addr, &ann);
+
+                if result.is_err() {
+                    self.metrics.dropped(DropReason::Invalid);
+                }
+                result?;
+            }
             (session::State::Connected { .. }, Message::Subscribe(subscribe)) => {
-                for ann in self
+                // If the remote was already subscribed, announcements matching its previous
+                // filter and time range were already sent, either as backlog or as they were
+                // relayed.
+                let previous = peer.subscribe.take();
+                let backlog = self
                     .gossip
                     // Filter announcements by interest.
                     .filtered(&subscribe.filter, subscribe.since, subscribe.until)
                     // Don't send announcements authored by the remote, back to the remote.
                     .filter(|ann| &ann.node != remote)
-                {
-                    self.outbox.write(peer, ann.into());
-                }
+                    // Only send announcements newly covered by the filter or time range.
+                    .filter(|ann| {
+                        previous.as_ref().map_or(true, |p| {
+                            !ann.matches(&p.filter)
+                                || ann.timestamp() < p.since
+                                || ann.timestamp() >= p.until
+                        })
+                    });
+
+                peer.queue_backlog(backlog);
                 peer.subscribe = Some(subscribe);
+
+                // Large backlogs are sent in chunks, on subsequent wakeups.
+                if self.outbox.write_backlog(peer, BACKLOG_CHUNK_SIZE) {
+                    self.outbox.wakeup(BACKLOG_CHUNK_INTERVAL);
+                }
+            }
+            (session::State::Connected { .. }, Message::AnnouncementRequest(req)) => {
+                let addr = peer.addr.clone();
+
+                self.announcements_requested(remote, addr, req);
             }
             (session::State::Connected { .. }, Message::Ping(Ping { ponglen, .. })) => {
                 // Ignore pings which ask for too much data.
@@ -1181,6 +3221,7 @@ where
                 if let session::PingState::AwaitingResponse(ponglen) = *ping {
                     if (ponglen as usize) == zeroes.len() {
                         *ping = session::PingState::Ok;
+                        peer.ponged(self.clock);
                     }
                 }
             }
@@ -1194,26 +3235,212 @@ where
         Ok(())
     }
 
-    /// Set of initial messages to send to a peer.
-    fn initial(&self, _link: Link) -> Vec<Message> {
-        let filter = self.filter();
+    /// Answer an announcement request with the matching announcements from our gossip store,
+    /// and our own if we seed the repository.
+    fn announcements_requested(
+        &mut self,
+        remote: &NodeId,
+        addr: Address,
+        req: AnnouncementRequest,
+    ) {
+        let AnnouncementRequest { rid, kinds } = req;
+
+        if self
+            .requests_limiter
+            .limit(addr.clone().into(), &ANNOUNCEMENT_REQUEST_RATE, self.clock)
+        {
+            debug!(target: "service", "Rate limiting announcement request from {remote} ({addr})");
+            return;
+        }
+        let mut msgs = Vec::new();
+
+        // Don't send the remote its own announcements.
+        for (_, node) in self.gossip.nodes.iter().filter(|(nid, _)| *nid != remote) {
+            if kinds.has(AnnouncementKinds::REFS) {
+                if let Some(ann) = node.last_refs.get(&rid) {
+                    msgs.push(ann.clone());
+                }
+            }
+            if kinds.has(AnnouncementKinds::INVENTORY) {
+                if let Some(ann) = &node.last_inventory {
+                    if let AnnouncementMessage::Inventory(inv) = &ann.message {
+                        if inv.inventory.contains(&rid) {
+                            msgs.push(ann.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // If we seed the repository, we're the origin of its announcements.
+        let seeding = match self.tracking.is_repo_tracked(&rid) {
+            Ok(true) => self.storage.contains(&rid).unwrap_or_default(),
+            Ok(false) => false,
+            Err(e) => {
+                error!(target: "service", "Error getting repository policy for {rid}: {e}");
+                false
+            }
+        };
+        if seeding {
+            if kinds.has(AnnouncementKinds::REFS) {
+                match self.own_refs_announcement(rid) {
+                    Ok(Some(ann)) => msgs.push(ann),
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!(target: "service", "Error creating refs announcement for {rid}: {e}")
+                    }
+                }
+            }
+            if kinds.has(AnnouncementKinds::INVENTORY) {
+                let time = self.timestamps.inventory(self.time());
+
+                match self.inventory() {
+                    Ok(inventory) => msgs.push(
+                        AnnouncementMessage::from(gossip::inventory(time, inventory))
+                            .signed(&self.signer),
+                    ),
+                    Err(e) => error!(target: "service", "Error getting local inventory: {e}"),
+                }
+            }
+        }
+
+        if msgs.is_empty() {
+            debug!(target: "service", "No announcements of {rid} to send to {remote}");
+            return;
+        }
+        if let Some(peer) = self.sessions.get(remote) {
+            self.outbox
+                .write_all(peer, msgs.into_iter().map(Message::AnnouncementResponse));
+        }
+    }
+
+    /// Get a refs announcement of our own refs in a repository, if we have any. The last one
+    /// we signed is reused if it's up to date with our refs.
+    fn own_refs_announcement(&mut self, rid: Id) -> Result<Option<Announcement>, storage::Error> {
+        let nid = self.node_id();
+        let signature = match self.storage.repository(rid)?.remote(&nid) {
+            Ok(remote) => remote.refs.signature,
+            Err(e) if e.is_not_found() => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(ann) = self.announced_refs.get(&rid) {
+            if let AnnouncementMessage::Refs(refs) = &ann.message {
+                if refs
+                    .refs
+                    .iter()
+                    .any(|r| r.id == nid && r.signature == signature)
+                {
+                    return Ok(Some(ann.clone()));
+                }
+            }
+        }
+        let ann = self.refs_announcement(rid, [nid])?;
+        self.announced_refs.insert(rid, ann.clone());
+
+        Ok(Some(ann))
+    }
+
+    /// Ask a connected peer for its stored announcements about a repository. Nothing is sent
+    /// if the peer doesn't advertize support for [`Features::PULL`].
+    fn request_announcements(&mut self, nid: &NodeId, rid: Id, kinds: AnnouncementKinds) {
+        let Some(peer) = self.sessions.get(nid).filter(|s| s.is_connected()) else {
+            return;
+        };
+        match self.addresses.get(nid) {
+            Ok(Some(node)) if node.features.has(Features::PULL) => {
+                debug!(target: "service", "Requesting announcements of {rid} from {nid}..");
 
-        // TODO: Only subscribe to outbound connections, otherwise we will consume too
-        // much bandwidth.
+                self.outbox
+                    .write(peer, Message::announcement_request(rid, kinds));
+            }
+            Ok(_) => {}
+            Err(e) => error!(target: "service", "Error getting features of {nid}: {e}"),
+        }
+    }
 
+    /// Set of initial messages to send to a peer.
+    fn initial(&mut self, link: Link) -> Vec<Message> {
+        let filter = self.is_subscribing(link).then(|| self.filter());
+        let inventory = match self.inventory() {
+            Ok(i) => i,
+            Err(e) => {
+                error!("Error getting local inventory for handshake: {}", e);
+                // Other than crashing the node completely, there's nothing we can do
+                // here besides returning an empty inventory and logging an error.
+                vec![]
+            }
+        };
         gossip::handshake(
             self.node.clone(),
-            self.clock.as_millis(),
-            &self.storage,
+            self.timestamps.inventory(self.time()),
+            inventory,
             &self.signer,
             filter,
         )
     }
 
+    /// Our inventory, ie. the repositories we seed. These are the tracked repositories
+    /// that we have in storage.
+    fn inventory(&self) -> Result<Vec<Id>, Error> {
+        self.tracked(self.storage.inventory()?)
+    }
+
+    /// Keep only the repositories we track.
+    fn tracked(&self, rids: Vec<Id>) -> Result<Vec<Id>, Error> {
+        let mut inventory = Vec::new();
+
+        for rid in rids {
+            if self.tracking.is_repo_tracked(&rid)? {
+                inventory.push(rid);
+            }
+        }
+        Ok(inventory)
+    }
+
+    /// The repositories in storage. Repositories that are corrupt are left out, so that
+    /// they don't take the others off the network, and are reported once with
+    /// [`Event::RepoCorrupted`].
+    fn storage_inventory(&mut self) -> Result<Vec<Id>, Error> {
+        let report = self.storage.inventory_report()?;
+        // Forget about repositories that were repaired or removed, so that they are
+        // reported again if they get corrupted again.
+        self.corrupted
+            .retain(|rid| report.skipped.iter().any(|(skipped, _)| skipped == rid));
+
+        for (rid, error) in report.skipped {
+            if self.corrupted.insert(rid) {
+                warn!(target: "service", "Skipping corrupt repository {rid}: {error}");
+
+                self.emit(Event::RepoCorrupted {
+                    rid,
+                    error: error.to_string(),
+                });
+            }
+        }
+        Ok(report.inventory)
+    }
+
+    /// Let our peers know right away that we no longer seed the given repository, so that
+    /// they can update their routing tables, instead of waiting for our next announcement.
+    fn announce_untracked(&mut self, rid: Id) -> Result<(), Error> {
+        let synced = self.sync_inventory()?;
+        if !synced.removed.contains(&rid) {
+            return Ok(());
+        }
+        let peers = self.inventory().and_then(|i| self.announce_inventory(i))?;
+
+        debug!(target: "service", "Announced removal of {rid} from inventory to {peers} peer(s)");
+
+        self.emit(Event::InventoryRemoved { rid, peers });
+
+        Ok(())
+    }
+
     /// Update our routing table with our local node's inventory.
     fn sync_inventory(&mut self) -> Result<SyncedRouting, Error> {
-        let inventory = self.storage.inventory()?;
-        let result = self.sync_routing(&inventory, self.node_id(), self.time())?;
+        let inventory = self.inventory()?;
+        let nid = self.node_id();
+        let result = self.sync_routing(&inventory, nid, nid, self.time())?;
 
         Ok(result)
     }
@@ -1225,23 +3452,21 @@ where
         &mut self,
         inventory: &[Id],
         from: NodeId,
+        relayer: NodeId,
         timestamp: Timestamp,
     ) -> Result<SyncedRouting, Error> {
         let mut synced = SyncedRouting::default();
         let included: HashSet<&Id> = HashSet::from_iter(inventory);
 
-        for (rid, result) in self.routing.insert(inventory, from, timestamp)? {
+        for (rid, result) in self.route(inventory, from, relayer, timestamp)? {
             match result {
                 InsertResult::SeedAdded => {
                     info!(target: "service", "Routing table updated for {rid} with seed {from}");
-                    self.emitter.emit(Event::SeedDiscovered { rid, nid: from });
+                    self.seed_discovered(rid, from);
+                    self.metrics.routes_inserted(1);
 
-                    if self.tracking.is_repo_tracked(&rid).expect(
-                        "Service::process_inventory: error accessing tracking configuration",
-                    ) {
-                        // TODO: We should fetch here if we're already connected, case this seed has
-                        // refs we don't have.
-                    }
+                    // TODO: If we're tracking the repository, we should fetch here if we're
+                    // already connected, case this seed has refs we don't have.
                     synced.added.push(rid);
                 }
                 InsertResult::TimeUpdated => {
@@ -1253,30 +3478,248 @@ where
         for rid in self.routing.get_resources(&from)?.into_iter() {
             if !included.contains(&rid) {
                 if self.routing.remove(&rid, &from)? {
+                    self.metrics.routes_removed(1);
                     synced.removed.push(rid);
-                    self.emitter.emit(Event::SeedDropped { rid, nid: from });
+                    self.seeds_discovered.remove(&(rid, from));
+                    self.emit(Event::SeedDropped { rid, nid: from });
+                }
+            }
+        }
+        Ok(synced)
+    }
+
+    /// Emit an [`Event::SeedDiscovered`] event, unless one was already emitted for this
+    /// seed of a tracked repository, eg. before its routing entry was pruned and re-added.
+    fn seed_discovered(&mut self, rid: Id, nid: NodeId) {
+        if matches!(self.tracking.is_repo_tracked(&rid), Ok(true))
+            && !self.seeds_discovered.insert((rid, nid))
+        {
+            return;
+        }
+        self.emit(Event::SeedDiscovered { rid, nid });
+    }
+
+    /// Emit an [`Event::RefsSynced`] event for a peer that announced it is in sync with our
+    /// refs, unless one was already emitted for these refs. Peers keep announcing the same
+    /// refs, which would otherwise flood subscribers with identical events.
+    fn refs_synced(&mut self, message: &RefsAnnouncement, remote: NodeId) {
+        let rid = message.rid;
+        let nid = self.node_id();
+        let signature = message
+            .refs
+            .iter()
+            .find(|refs| refs.id == nid)
+            .map(|refs| refs.signature);
+
+        // We only keep track of connected peers and tracked repositories.
+        if let Some(signature) = signature {
+            if self.sessions.is_connected(&remote)
+                && matches!(self.tracking.is_repo_tracked(&rid), Ok(true))
+                && self.refs_synced.insert((rid, remote), signature) == Some(signature)
+            {
+                return;
+            }
+        }
+        self.emit(Event::RefsSynced { rid, remote });
+    }
+
+    /// Add routing entries for the given node, announced at the given time, and relayed
+    /// to us by the given peer. Records where the entries were learned from.
+    ///
+    /// Entries for our own node are only added from our local inventory, ie. when we're
+    /// both the node and the relayer.
+    fn route<'a>(
+        &mut self,
+        ids: impl IntoIterator<Item = &'a Id> + Clone,
+        node: NodeId,
+        relayer: NodeId,
+        timestamp: Timestamp,
+    ) -> Result<Vec<(Id, InsertResult)>, routing::Error> {
+        if node == self.node_id() && relayer != node {
+            warn!(target: "service", "Ignoring routing entries for the local node relayed by {relayer}");
+            return Ok(vec![]);
+        }
+        let results = self.routing.insert(ids.clone(), node, timestamp)?;
+        self.routing
+            .relayed(ids, node, relayer, timestamp, self.time())?;
+
+        Ok(results)
+    }
+
+    /// Announce local refs for given id. Remotes that aren't in storage, eg. our own if we
+    /// never forked the repository, are skipped. Returns whether anything was announced.
+    fn announce_refs(
+        &mut self,
+        rid: Id,
+        remotes: impl IntoIterator<Item = NodeId>,
+    ) -> Result<bool, storage::Error> {
+        let ann = self.refs_announcement(rid, remotes)?;
+        if let AnnouncementMessage::Refs(refs) = &ann.message {
+            if refs.refs.is_empty() {
+                debug!(target: "service", "No refs of {rid} to announce..");
+                return Ok(false);
+            }
+            for r in refs.refs.iter() {
+                if let Err(e) = self.watermarks.announced(&rid, &r.id, &r.signature) {
+                    error!(target: "service", "Error recording announced refs of {rid}: {e}");
+                }
+            }
+        }
+        self.announced_refs.insert(rid, ann.clone());
+
+        let peers = self.sessions.connected().map(|(_, p)| p);
+
+        self.outbox.broadcast(ann, peers);
+        self.activity.entry(rid).or_default().announced = Some(self.clock);
+
+        Ok(true)
+    }
+
+    /// Announce the refs of the given remotes, unless we've already announced them as they
+    /// are, eg. before a restart.
+    fn announce_new_refs(
+        &mut self,
+        rid: Id,
+        remotes: impl IntoIterator<Item = NodeId>,
+    ) -> Result<(), storage::Error> {
+        let repo = self.storage.repository(rid)?;
+        let mut remotes = remotes.into_iter().collect::<Vec<_>>();
+
+        remotes.retain(|nid| {
+            let Ok(remote) = repo.remote(nid) else {
+                return true;
+            };
+            match self.watermarks.get(&rid, nid) {
+                Ok(Some(w)) => w.announced != Some(remote.refs.signature),
+                Ok(None) => true,
+                Err(e) => {
+                    error!(target: "service", "Error reading sync watermark of {rid}/{nid}: {e}");
+                    true
+                }
+            }
+        });
+        drop(repo);
+
+        if remotes.is_empty() {
+            debug!(target: "service", "Refs of {rid} were already announced..");
+            return Ok(());
+        }
+        self.announce_refs(rid, remotes).map(|_| ())
+    }
+
+    /// Record the signed refs of the given namespaces, which we just fetched.
+    fn record_fetched(&mut self, rid: Id, namespaces: &HashSet<NodeId>) {
+        let repo = match self.storage.repository(rid) {
+            Ok(repo) => repo,
+            Err(e) => {
+                error!(target: "service", "Error opening {rid} to record fetched refs: {e}");
+                return;
+            }
+        };
+        for nid in namespaces {
+            let Ok(remote) = repo.remote(nid) else {
+                continue;
+            };
+            if let Err(e) = self.watermarks.fetched(&rid, nid, &remote.refs.signature) {
+                error!(target: "service", "Error recording fetched refs of {rid}/{nid}: {e}");
+            }
+        }
+    }
+
+    /// Check whether we already fetched all the refs of an announcement. This is cheaper than
+    /// checking our storage, but only valid if we still have the repository.
+    fn is_fetched(&self, message: &RefsAnnouncement) -> bool {
+        let rid = &message.rid;
+
+        if message.refs.is_empty() || !self.storage.contains(rid).unwrap_or(false) {
+            return false;
+        }
+        message.refs.iter().all(|r| match self.watermarks.get(rid, &r.id) {
+            Ok(Some(w)) => w.fetched == Some(r.signature),
+            Ok(None) => false,
+            Err(e) => {
+                error!(target: "service", "Error reading sync watermark of {rid}/{}: {e}", r.id);
+                false
+            }
+        })
+    }
+
+    /// Check whether a repository was only partially replicated, by a minimal fetch.
+    fn is_partial(&self, rid: &Id) -> bool {
+        self.watermarks.is_partial(rid).unwrap_or_else(|e| {
+            error!(target: "service", "Error reading replication state of {rid}: {e}");
+            false
+        })
+    }
+
+    /// Record the outcome of checking a repository after an unclean shutdown. The sync
+    /// watermarks of repositories that fail to validate are cleared, since they may record
+    /// fetches that didn't reach the disk. Such repositories are otherwise only reported:
+    /// if they don't open, inventory handling already leaves them out.
+    fn repo_validated(&mut self, rid: Id, result: Result<(), String>) {
+        let Some(recovery) = &mut self.recovery else {
+            return;
+        };
+        if !self.recovering.remove(&rid) {
+            return;
+        }
+        if let Err(e) = result {
+            warn!(target: "service", "Repository {rid} failed validation: {e}");
+            recovery.invalid.push(rid);
+
+            match self.watermarks.remove(&rid) {
+                Ok(cleared) => recovery.watermarks_cleared += cleared,
+                Err(e) => {
+                    error!(target: "service", "Error clearing sync watermarks of {rid}: {e}")
                 }
             }
         }
-        Ok(synced)
+        recovery.checked += 1;
+        recovery.pending = self.recovering.len();
+
+        if recovery.is_complete() {
+            info!(
+                target: "service",
+                "Recovery completed: {} routing entries added, {} removed, {} sync watermarks cleared, {}/{} repositories invalid",
+                recovery.routes_added,
+                recovery.routes_removed,
+                recovery.watermarks_cleared,
+                recovery.invalid.len(),
+                recovery.checked,
+            );
+        }
+    }
+
+    /// Remove the sync watermarks of repositories we no longer track.
+    fn prune_watermarks(&mut self) -> Result<(), Error> {
+        for rid in self.watermarks.repos()? {
+            if !self.tracking.is_repo_tracked(&rid)? {
+                self.watermarks.remove(&rid)?;
+            }
+        }
+        Ok(())
     }
 
-    /// Announce local refs for given id.
-    fn announce_refs(
+    /// Create a signed refs announcement of the given remotes, for the given id.
+    fn refs_announcement(
         &mut self,
         rid: Id,
         remotes: impl IntoIterator<Item = NodeId>,
-    ) -> Result<(), storage::Error> {
+    ) -> Result<Announcement, storage::Error> {
         let repo = self.storage.repository(rid)?;
-        let peers = self.sessions.connected().map(|(_, p)| p);
-        let timestamp = self.time();
+        let timestamp = self.timestamps.refs(rid, self.time());
         let mut refs = BoundedVec::<_, REF_REMOTE_LIMIT>::new();
 
         for remote_id in remotes.into_iter() {
-            if refs
-                .push(repo.remote(&remote_id)?.refs.unverified())
-                .is_err()
-            {
+            let remote = match repo.remote(&remote_id) {
+                Ok(remote) => remote,
+                Err(e) if e.is_not_found() => {
+                    debug!(target: "service", "Skipping {remote_id}: not in storage of {rid}");
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if refs.push(remote.refs.unverified()).is_err() {
                 warn!(
                     target: "service",
                     "refs announcement limit ({}) exceeded, peers will see only some of your repository references",
@@ -1291,11 +3734,8 @@ where
             refs,
             timestamp,
         });
-        let ann = msg.signed(&self.signer);
-
-        self.outbox.broadcast(ann, peers);
 
-        Ok(())
+        Ok(msg.signed(&self.signer))
     }
 
     fn sync_and_announce(&mut self) {
@@ -1303,11 +3743,7 @@ where
             Ok(synced) => {
                 // Only announce if our inventory changed.
                 if synced.added.len() + synced.removed.len() > 0 {
-                    if let Err(e) = self
-                        .storage
-                        .inventory()
-                        .and_then(|i| self.announce_inventory(i))
-                    {
+                    if let Err(e) = self.inventory().and_then(|i| self.announce_inventory(i)) {
                         error!(target: "service", "Failed to announce inventory: {e}");
                     }
                 }
@@ -1337,6 +3773,21 @@ where
             error!(target: "service", "Attempted connection to self");
             return false;
         }
+        if let Err(e) = addr.validate(&self.config.address_context()) {
+            warn!(target: "service", "Refusing to connect to {nid}: {e}");
+            return false;
+        }
+        if addr.is_local() || addr.is_trusted() {
+            warn!(target: "service", "Connecting to {nid} at local address {addr}..");
+        }
+        if !self.is_dialable(&addr) {
+            warn!(target: "service", "Skipping connection to {nid} at {addr}: no proxy configured");
+
+            if let Err(e) = self.addresses.unsupported(&nid, &addr) {
+                error!(target: "service", "Error updating address book with unsupported address: {e}");
+            }
+            return false;
+        }
         let persistent = self.config.is_persistent(&nid);
 
         if let Err(e) = self.addresses.attempted(&nid, &addr, self.time()) {
@@ -1357,34 +3808,70 @@ where
         true
     }
 
-    fn seeds(&self, rid: &Id) -> Result<Seeds, Error> {
+    fn seeds(&mut self, rid: &Id) -> Result<Seeds, Error> {
+        // Nb. The RNG is forked, and not cloned, so that seeds are returned in a different
+        // order every time.
         match self.routing.get(rid) {
             Ok(seeds) => {
-                Ok(seeds
-                    .into_iter()
-                    .fold(Seeds::new(self.rng.clone()), |mut seeds, node| {
-                        if node != self.node_id() {
-                            let addrs: Vec<KnownAddress> = self
-                                .addresses
-                                .get(&node)
-                                .ok()
-                                .flatten()
-                                .map(|n| n.addrs)
-                                .unwrap_or(vec![]);
-
-                            if let Some(s) = self.sessions.get(&node) {
-                                seeds.insert(Seed::new(node, addrs, Some(s.state.clone())));
-                            } else {
-                                seeds.insert(Seed::new(node, addrs, None));
+                let mut seeds =
+                    seeds
+                        .into_iter()
+                        .fold(Seeds::new(self.rng.fork()), |mut seeds, node| {
+                            if node != self.node_id() {
+                                let (addrs, cobs): (Vec<KnownAddress>, _) = self
+                                    .addresses
+                                    .get(&node)
+                                    .ok()
+                                    .flatten()
+                                    .map(|n| (n.addrs, n.cobs))
+                                    .unwrap_or_default();
+
+                                let mut seed = if let Some(s) = self.sessions.get(&node) {
+                                    Seed::new(node, addrs, Some(s.state.clone()))
+                                } else {
+                                    Seed::new(node, addrs, None)
+                                }
+                                .with_cobs(cobs);
+                                if let Some((timestamp, fresh)) = self.announced(&node, rid) {
+                                    seed = seed.with_announced(timestamp, fresh);
+                                }
+                                seeds.insert(seed);
                             }
-                        }
-                        seeds
-                    }))
+                            seeds
+                        });
+                // Prefer the seeds that announced refs for the repository most recently.
+                seeds.rank_by_key(|s| s.announced.map(std::cmp::Reverse));
+                // Then, prefer the seeds that serve the collaborative object types we
+                // replicate, with the first configured type weighing the most.
+                for cob in self.config.cobs.iter().rev() {
+                    seeds.prefer(cob);
+                }
+
+                Ok(seeds)
             }
             Err(err) => Err(Error::Routing(err)),
         }
     }
 
+    /// Get the timestamp of the latest refs announcement of a node for a repository, and
+    /// whether it has refs we'd fetch, according to our gossip store.
+    fn announced(&self, nid: &NodeId, rid: &Id) -> Option<(Timestamp, bool)> {
+        let ann = self.gossip.nodes.get(nid)?.last_refs.get(rid)?;
+        let AnnouncementMessage::Refs(refs) = &ann.message else {
+            return None;
+        };
+        let fresh = match self.tracking.repo_policy(rid) {
+            Ok(entry) => self
+                .should_fetch_refs_announcement(refs, &entry.scope)
+                .unwrap_or_default(),
+            Err(e) => {
+                error!(target: "service", "Error getting repository policy for {rid}: {e}");
+                false
+            }
+        };
+        Some((refs.timestamp, fresh))
+    }
+
     /// Return a new filter object, based on our tracking policy.
     fn filter(&self) -> Filter {
         if self.config.policy == tracking::Policy::Track {
@@ -1395,6 +3882,39 @@ where
         }
     }
 
+    /// Whether we subscribe to gossip from peers on the given link, according to our
+    /// subscription policy.
+    fn is_subscribing(&self, link: Link) -> bool {
+        match self.config.subscribe {
+            config::SubscribePolicy::All => true,
+            config::SubscribePolicy::OutboundOnly => {
+                // When tracking selectively, our filter is narrow enough to subscribe to
+                // inbound peers, which may be the only ones with the repositories we track.
+                link.is_outbound() || self.config.policy != tracking::Policy::Track
+            }
+            config::SubscribePolicy::None => false,
+        }
+    }
+
+    /// Update our subscription with the peers we subscribe to.
+    fn resubscribe(&mut self) {
+        let msg = Message::subscribe(self.filter(), self.time(), Timestamp::MAX);
+        let (inbound, outbound) = (
+            self.is_subscribing(Link::Inbound),
+            self.is_subscribing(Link::Outbound),
+        );
+        let peers = self
+            .sessions
+            .connected()
+            .filter(|(_, s)| match s.link {
+                Link::Inbound => inbound,
+                Link::Outbound => outbound,
+            })
+            .map(|(_, s)| s);
+
+        self.outbox.broadcast(msg, peers);
+    }
+
     /// Get the current time.
     fn time(&self) -> Timestamp {
         self.clock.as_millis()
@@ -1405,34 +3925,179 @@ where
     ////////////////////////////////////////////////////////////////////////////
 
     /// Announce our inventory to all connected peers.
-    fn announce_inventory(&mut self, inventory: Vec<Id>) -> Result<(), storage::Error> {
-        let time = self.time();
+    /// Returns the number of peers the inventory was sent to.
+    fn announce_inventory(&mut self, inventory: Vec<Id>) -> Result<usize, Error> {
+        let time = self.timestamps.inventory(self.time());
         let inv = Message::inventory(gossip::inventory(time, inventory), &self.signer);
+        let mut peers = 0;
+
         for (_, sess) in self.sessions.connected() {
             self.outbox.write(sess, inv.clone());
+            peers += 1;
+        }
+        Ok(peers)
+    }
+
+    /// Refresh our node announcement with a new timestamp, and broadcast it to our peers, so
+    /// that it isn't pruned by nodes which have been online for longer than we have.
+    fn refresh_node_announcement(&mut self, now: &LocalTime) {
+        let Some(ann) = gossip::node(&self.config, now.as_millis()).solve(Default::default()) else {
+            error!(target: "service", "Unable to solve proof-of-work puzzle for node announcement");
+            return;
+        };
+        self.node = ann;
+        self.last_node_announce = *now;
+
+        if let Err(e) = self.store_node_announcement() {
+            error!(target: "service", "Error storing node announcement: {e}");
+        }
+        self.cobs = gossip::cobs(&self.config, now.as_millis());
+
+        let node = Message::node(self.node.clone(), &self.signer);
+        let cobs = Message::cobs(self.cobs.clone(), &self.signer);
+
+        for (_, sess) in self.sessions.connected() {
+            self.outbox.write(sess, node.clone());
+
+            if sess.supports(wire::PROTOCOL_VERSION) {
+                self.outbox.write(sess, cobs.clone());
+            }
+        }
+    }
+
+    /// Record our node announcement in our address database, and persist it to file.
+    fn store_node_announcement(&mut self) -> Result<(), Error> {
+        self.addresses.insert(
+            &self.node_id(),
+            self.node.features,
+            self.node.alias.clone(),
+            self.node.work(),
+            self.node.timestamp,
+            self.node
+                .addresses
+                .iter()
+                .map(|a| KnownAddress::new(a.clone(), address::Source::Peer)),
+        )?;
+        if let Some(path) = &self.node_announcement_file {
+            std::fs::write(path, wire::serialize(&self.node))?;
         }
         Ok(())
     }
 
-    fn prune_routing_entries(&mut self, now: &LocalTime) -> Result<(), routing::Error> {
+    /// Emit the periodic announcements that are due.
+    fn announce_periodic(&mut self, now: &LocalTime) {
+        while let Some(job) = self.announcer.next(*now) {
+            let msg = match job {
+                Job::Inventory => {
+                    let time = self.timestamps.inventory(self.time());
+
+                    self.inventory()
+                        .map(|i| Message::inventory(gossip::inventory(time, i), &self.signer))
+                }
+            };
+            let msg = match msg {
+                Ok(msg) => msg,
+                Err(e) => {
+                    error!(target: "service", "Error creating periodic announcement: {e}");
+                    continue;
+                }
+            };
+            self.outbox
+                .broadcast(msg, self.sessions.connected().map(|(_, s)| s));
+        }
+        if let Some(delay) = self.announcer.wakeup(*now) {
+            self.outbox.wakeup(delay);
+        }
+    }
+
+    /// Prune the routing table down to its maximum size.
+    /// Returns the number of entries pruned.
+    fn prune_routing_entries(&mut self, now: &LocalTime) -> Result<usize, routing::Error> {
         let count = self.routing.len()?;
         if count <= self.config.limits.routing_max_size {
-            return Ok(());
+            return Ok(0);
         }
 
         let delta = count - self.config.limits.routing_max_size;
-        self.routing.prune(
+        let pruned = self.routing.prune(
             (*now - self.config.limits.routing_max_age).as_millis(),
             Some(delta),
         )?;
-        Ok(())
+        self.metrics.routes_removed(pruned);
+        Ok(pruned)
+    }
+
+    /// Keep the gossip store within its configured limits. Announcements of connected or
+    /// persistent peers, and refs announcements of tracked repositories are never evicted.
+    fn prune_gossip(&mut self) {
+        let limits = &self.config.limits;
+        let tracking = &self.tracking;
+        // Each repository is only looked up once, since many announcers may announce it.
+        let mut tracked = HashMap::new();
+        // When in doubt, consider the repository tracked, so that it is kept.
+        let mut is_tracked = |rid: &Id| {
+            *tracked
+                .entry(*rid)
+                .or_insert_with(|| !matches!(tracking.is_repo_tracked(rid), Ok(false)))
+        };
+
+        for (announcer, node) in self.gossip.nodes.iter_mut() {
+            for rid in node.prune_refs(limits.gossip_max_refs, &mut is_tracked) {
+                trace!(target: "service", "Evicted refs announcement of {announcer} for {rid} from gossip store");
+            }
+        }
+
+        let sessions = &self.sessions;
+        let config = &self.config;
+        let evicted = self.gossip.prune(limits.gossip_max_nodes, |nid, node| {
+            sessions.is_connected(nid)
+                || config.is_persistent(nid)
+                || node.last_refs.keys().any(&mut is_tracked)
+        });
+        for nid in evicted {
+            trace!(target: "service", "Evicted announcements of {nid} from gossip store");
+        }
     }
 
+    /// Remove the fetch estimates of repositories that are no longer tracked, eg. because
+    /// their policy was changed without going through the service.
+    fn prune_fetch_estimates(&mut self) {
+        let tracking = &self.tracking;
+
+        // When in doubt, consider the repository tracked, so that its estimate is kept.
+        self.fetch_estimates
+            .retain(|rid, _| !matches!(tracking.is_repo_tracked(rid), Ok(false)));
+    }
+
+    /// Remove the sessions of peers that have been disconnected for longer than the session
+    /// retention period. The sessions of persistent peers are kept.
+    fn prune_sessions(&mut self, now: &LocalTime) {
+        let retention = self.config.limits.session_retention;
+        let expired = self
+            .sessions
+            .iter()
+            .filter(|(nid, _)| !self.config.is_persistent(nid))
+            .filter_map(|(nid, session)| match session.state {
+                session::State::Disconnected { since, .. } if *now - since >= retention => {
+                    Some(*nid)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for nid in expired {
+            debug!(target: "service", "Removing session of {nid}, disconnected for over {retention}");
+            self.sessions.remove(&nid);
+        }
+    }
+
+    /// Disconnect peers that haven't answered our pings in time.
     fn disconnect_unresponsive_peers(&mut self, now: &LocalTime) {
+        let timeout = self.config.limits.stale_timeout;
         let stale = self
             .sessions
             .connected()
-            .filter(|(_, session)| *now - session.last_active >= STALE_CONNECTION_TIMEOUT);
+            .filter(|(_, session)| session.is_unresponsive(*now, timeout));
 
         for (_, session) in stale {
             self.outbox.disconnect(
@@ -1442,15 +4107,28 @@ where
         }
     }
 
+    /// Send the next chunk of every pending subscription backlog.
+    fn send_backlogs(&mut self) {
+        let mut pending = false;
+
+        for (_, session) in self.sessions.connected_mut() {
+            pending |= self.outbox.write_backlog(session, BACKLOG_CHUNK_SIZE);
+        }
+        if pending {
+            self.outbox.wakeup(BACKLOG_CHUNK_INTERVAL);
+        }
+    }
+
     /// Ensure connection health by pinging connected peers.
     fn keep_alive(&mut self, now: &LocalTime) {
+        let delta = self.config.limits.keep_alive;
         let inactive_sessions = self
             .sessions
             .connected_mut()
-            .filter(|(_, session)| *now - session.last_active >= KEEP_ALIVE_DELTA)
+            .filter(|(_, session)| session.is_ping_due(*now, delta))
             .map(|(_, session)| session);
         for session in inactive_sessions {
-            session.ping(&mut self.outbox).ok();
+            session.ping(*now, &mut self.outbox).ok();
         }
     }
 
@@ -1463,6 +4141,8 @@ where
                 entries
                     .filter(|(nid, _)| !self.sessions.contains_key(nid))
                     .filter(|(nid, _)| nid != &self.node_id())
+                    .filter(|(nid, _)| !self.is_backed_off(nid))
+                    .filter(|(nid, _)| !self.is_user_disconnected(nid))
                     .fold(HashMap::new(), |mut acc, (nid, addr)| {
                         acc.entry(nid).or_insert_with(Vec::new).push(addr);
                         acc
@@ -1475,22 +4155,187 @@ where
         }
     }
 
-    /// Fetch all repositories that are tracked but missing from our inventory.
+    /// Get the maximum number of bytes that can be received in a fetch of a repository.
+    /// The repository's tracking policy takes precedence over the configured limit.
+    fn fetch_limit(&self, rid: &Id) -> u64 {
+        match self.tracking.repo_policy(rid) {
+            Ok(tracking::Repo {
+                fetch_limit: Some(limit),
+                ..
+            }) => limit,
+            Ok(_) => {
+                let cloning = !self.storage.contains(rid).unwrap_or(false);
+                self.config.limits.fetch_pack_max_bytes.get(cloning)
+            }
+            Err(e) => {
+                error!(target: "service", "Error getting tracking policy for {rid}: {e}");
+                self.config.limits.fetch_pack_max_bytes.get(true)
+            }
+        }
+    }
+
+    /// Get the filter of the refs to fetch from the remotes of a repository.
+    fn ref_filter(&self, rid: &Id) -> tracking::RefFilter {
+        match self.tracking.repo_policy(rid) {
+            Ok(repo) => repo.ref_filter,
+            Err(e) => {
+                error!(target: "service", "Error getting tracking policy for {rid}: {e}");
+                tracking::RefFilter::default()
+            }
+        }
+    }
+
+    /// Avoid a misbehaving seed for a while. The backoff doubles with each offense.
+    fn back_off(&mut self, nid: NodeId) {
+        let now = self.clock;
+        let backoff = self.backoffs.entry(nid).or_insert(Misbehavior {
+            offenses: 0,
+            until: now,
+        });
+        let delay = MISBEHAVIOR_BACKOFF.next_delay(backoff.offenses, &mut self.rng);
+
+        backoff.offenses += 1;
+        backoff.until = now + delay;
+
+        warn!(target: "service", "Avoiding seed {nid} for {delay}, after {} offense(s)", backoff.offenses);
+    }
+
+    /// Check whether a seed is being avoided.
+    fn is_backed_off(&self, nid: &NodeId) -> bool {
+        self.backoffs
+            .get(nid)
+            .map_or(false, |backoff| backoff.until > self.clock)
+    }
+
+    /// Report a store error which caused a message from the given peer to be dropped.
+    fn store_error(&mut self, remote: NodeId, err: impl std::error::Error) {
+        error!(target: "service", "Dropping message from {remote} after store error: {err}");
+        self.emit(Event::StoreError {
+            remote,
+            error: err.to_string(),
+        });
+    }
+
+    /// Check whether a peer was disconnected by the user, and is still cooling off.
+    fn is_user_disconnected(&self, nid: &NodeId) -> bool {
+        self.user_disconnects
+            .get(nid)
+            .map_or(false, |until| *until > self.clock)
+    }
+
+    /// Schedule a fetch that failed for a transient reason to be retried later.
+    fn schedule_fetch_retry(&mut self, rid: Id, from: NodeId) {
+        let now = self.clock;
+        let retry = self.fetch_retries.entry(rid).or_insert(FetchRetry {
+            from,
+            attempts: 0,
+            retry_at: None,
+        });
+        retry.attempts += 1;
+        retry.from = from;
+
+        if FETCH_RETRY_BACKOFF.should_give_up(retry.attempts) {
+            warn!(
+                target: "service",
+                "Giving up on fetching {rid} after {} failed attempt(s)", retry.attempts
+            );
+            self.fetch_retries.remove(&rid);
+            return;
+        }
+        let delay = FETCH_RETRY_BACKOFF.next_delay(retry.attempts, &mut self.rng);
+        retry.retry_at = Some(now + delay);
+
+        debug!(target: "service", "Retrying fetch of {rid} in {delay}..");
+    }
+
+    /// Retry fetches that previously failed for a transient reason, and are due.
+    /// We prefer fetching from a different seed than the one that failed, but fall back
+    /// to the same seed if it has reconnected.
+    fn retry_fetches(&mut self, now: &LocalTime) {
+        let due = self
+            .fetch_retries
+            .iter()
+            .filter(|(_, r)| r.retry_at.map_or(false, |t| *now >= t))
+            .map(|(rid, r)| (*rid, r.from))
+            .collect::<Vec<_>>();
+
+        for (rid, from) in due {
+            let seeds = match self.seeds(&rid) {
+                Ok(seeds) => seeds,
+                Err(e) => {
+                    error!(target: "service", "Couldn't retry fetch of {rid}: failed to lookup seeds: {e}");
+                    continue;
+                }
+            };
+            let connected = seeds.connected().map(|s| s.nid).collect::<Vec<_>>();
+            let Some(seed) = connected
+                .iter()
+                .find(|nid| **nid != from)
+                .or_else(|| connected.first())
+                .copied()
+            else {
+                debug!(target: "service", "No connected seeds to retry fetch of {rid}..");
+                continue;
+            };
+            let fetching = self.sessions.values().any(|s| s.is_fetching(&rid));
+            let Some(retry) = self.fetch_retries.get_mut(&rid) else {
+                continue;
+            };
+            // The outcome of the fetch isn't always reported back, eg. if it's merged with
+            // a fetch that is already under way, or if the session goes away. In that case,
+            // we check back later.
+            retry.retry_at =
+                Some(*now + FETCH_RETRY_BACKOFF.next_delay(retry.attempts, &mut self.rng));
+
+            if fetching {
+                debug!(target: "service", "Fetch of {rid} already under way, not retrying..");
+                continue;
+            }
+            retry.from = seed;
+
+            debug!(target: "service", "Retrying fetch of {rid} from {seed}..");
+
+            self.fetch(rid, &seed);
+        }
+    }
+
+    /// Fetch all repositories that are tracked but missing from our inventory, or only
+    /// partially replicated.
     fn fetch_missing_inventory(&mut self) -> Result<(), Error> {
         let inventory = self.storage().inventory()?;
-        let missing = self
+        let mut missing = self
             .tracking
             .repo_policies()?
             .filter_map(|t| (t.policy == tracking::Policy::Track).then_some(t.id))
-            .filter(|rid| !inventory.contains(rid));
+            .filter(|rid| !inventory.contains(rid) || self.is_partial(rid))
+            .collect::<Vec<_>>();
+
+        // Repositories left over by the last sync go first, so that they aren't starved
+        // by the ones before them. Nb. The sort is stable.
+        let backlog = std::mem::take(&mut self.missing_backlog);
+        missing.sort_by_key(|rid| !backlog.contains(rid));
+
+        let max = self.config.limits.fetch_missing_max;
+        let mut fetched = 0;
 
         for rid in missing {
+            // Repositories being fetched, or waiting for a retry, are left alone.
+            if self.fetch_retries.contains_key(&rid)
+                || self.sessions.values().any(|s| s.is_fetching(&rid))
+            {
+                continue;
+            }
+            if fetched >= max {
+                self.missing_backlog.push(rid);
+                continue;
+            }
             match self.seeds(&rid) {
                 Ok(seeds) => {
-                    if let Some(connected) = NonEmpty::from_vec(seeds.connected().collect()) {
-                        for seed in connected {
-                            self.fetch(rid, &seed.nid);
-                        }
+                    // Each repository is only fetched from one seed at a time. If the fetch
+                    // fails, another seed may be picked by the retry, or by the next sync.
+                    if let Some(seed) = seeds.select(1).first().map(|s| s.nid) {
+                        self.fetch(rid, &seed);
+                        fetched += 1;
                     } else {
                         // TODO: We should make sure that this fetch is retried later, either
                         // when we connect to a seed, or when we discover a new seed.
@@ -1532,10 +4377,20 @@ where
             return;
         }
 
-        for (id, ka) in self
+        let (dialable, undialable): (Vec<_>, Vec<_>) = self
             .available_peers()
             .into_iter()
             .flat_map(|(nid, kas)| kas.into_iter().map(move |ka| (nid, ka)))
+            .partition(|(_, ka)| self.is_dialable(&ka.addr));
+
+        // Addresses we can't dial aren't attempted, so that they aren't treated as failing.
+        for (nid, ka) in undialable.iter().filter(|(_, ka)| !ka.unsupported) {
+            if let Err(e) = self.addresses.unsupported(nid, &ka.addr) {
+                error!(target: "service", "Error updating address book with unsupported address: {e}");
+            }
+        }
+        let mut candidates = dialable
+            .into_iter()
             .filter(|(_, ka)| match (ka.last_success, ka.last_attempt) {
                 // If we succeeded the last time we tried, this is a good address.
                 (Some(success), attempt) => success >= attempt.unwrap_or_default(),
@@ -1544,12 +4399,25 @@ where
                 // If we've never tried this address, it's worth a try.
                 (None, None) => true,
             })
-            .take(wanted)
-        {
+            .collect::<Vec<_>>();
+
+        // Prefer direct addresses over proxied ones, and only try one address per peer.
+        // Nb. The sort is stable, so peers remain in random order otherwise.
+        candidates.sort_by_key(|(_, ka)| ka.addr.requires_proxy());
+        let mut seen = HashSet::new();
+        candidates.retain(|(nid, _)| seen.insert(*nid));
+
+        for (id, ka) in candidates.into_iter().take(wanted) {
             self.connect(id, ka.addr.clone());
         }
     }
 
+    /// Check whether we're able to dial an address. Addresses that require a proxy, eg.
+    /// Tor onion addresses, can only be dialed if a proxy is configured.
+    fn is_dialable(&self, addr: &Address) -> bool {
+        !addr.requires_proxy() || self.config.proxy.is_some()
+    }
+
     /// Maintain persistent peer connections.
     fn maintain_persistent(&mut self) {
         trace!(target: "service", "Maintaining persistent peers..");
@@ -1578,6 +4446,11 @@ where
     }
 }
 
+/// Move a time back by the given duration, stopping at the epoch.
+pub(crate) fn rewind(time: LocalTime, by: LocalDuration) -> LocalTime {
+    LocalTime::from_millis((time.as_millis() as u128).saturating_sub(by.as_millis()))
+}
+
 /// Gives read access to the service state.
 pub trait ServiceState {
     /// Get the Node ID.
@@ -1592,6 +4465,37 @@ pub trait ServiceState {
     fn clock_mut(&mut self) -> &mut LocalTime;
     /// Get service configuration.
     fn config(&self) -> &Config;
+    /// Get the size of the gossip store.
+    fn gossip(&self) -> GossipStats;
+    /// Get what the service knows about a repository.
+    fn repo_activity(&self, rid: &Id) -> RepoActivity;
+    /// Explain why the routing table says the given node seeds the given repository.
+    fn explain(&self, rid: &Id, nid: &NodeId) -> Result<Explanation, routing::Error>;
+    /// Get the recent fetches of the given repository, oldest first.
+    fn fetch_history(&self, rid: &Id) -> Result<Vec<FetchRecord>, routing::Error>;
+    /// Get a page of the logged events following the given sequence number.
+    fn events_since(&self, seq: u64) -> Result<events::Page, events::store::Error>;
+    /// Get the in-memory activity log.
+    fn activity(&self) -> &activity::Log;
+    /// Get the service metrics.
+    fn metrics(&self) -> &metrics::Metrics;
+    /// Get the repairs made on startup, if the node didn't shut down cleanly.
+    fn recovery(&self) -> Option<&Recovery>;
+    /// Get all tracking policies.
+    fn export_policies(&self) -> Result<tracking::Policies, tracking::Error>;
+}
+
+/// What the service knows about a repository, beyond what's in storage.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepoActivity {
+    /// Whether we are a seed of the repository, according to our routing table.
+    pub seeding: bool,
+    /// Number of other seeds of the repository, according to our routing table.
+    pub seeds: usize,
+    /// Last time the repository was fetched successfully.
+    pub fetched: Option<LocalTime>,
+    /// Last time we announced refs of the repository.
+    pub announced: Option<LocalTime>,
 }
 
 impl<R, A, S, G> ServiceState for Service<R, A, S, G>
@@ -1623,6 +4527,73 @@ where
     fn config(&self) -> &Config {
         &self.config
     }
+
+    fn repo_activity(&self, rid: &Id) -> RepoActivity {
+        let nid = self.node_id();
+        let seeds = self.routing.get(rid).unwrap_or_default();
+
+        RepoActivity {
+            seeding: seeds.contains(&nid),
+            seeds: seeds.iter().filter(|s| **s != nid).count(),
+            ..self.activity.get(rid).cloned().unwrap_or_default()
+        }
+    }
+
+    fn explain(&self, rid: &Id, nid: &NodeId) -> Result<Explanation, routing::Error> {
+        let limits = &self.config.limits;
+        let time = self.routing.entry(rid, nid)?;
+        let oldest = (self.clock - limits.routing_max_age).as_millis();
+        let prunable =
+            time.map_or(false, |t| t < oldest) && self.routing.len()? > limits.routing_max_size;
+        let fetch = self.fetches.get(&(*rid, *nid)).cloned().map(|mut fetch| {
+            if let Some(retry) = self.fetch_retries.get(rid).filter(|r| r.from == *nid) {
+                fetch.attempts = retry.attempts;
+                fetch.retry_at = retry.retry_at.map(|t| t.as_millis());
+            }
+            fetch
+        });
+
+        Ok(Explanation {
+            rid: *rid,
+            nid: *nid,
+            time,
+            provenance: self.routing.provenance(rid, nid)?,
+            prunable,
+            fetch,
+        })
+    }
+
+    fn fetch_history(&self, rid: &Id) -> Result<Vec<FetchRecord>, routing::Error> {
+        self.routing.fetch_history(rid)
+    }
+
+    fn events_since(&self, seq: u64) -> Result<events::Page, events::store::Error> {
+        self.event_log.since(seq, events::PAGE_SIZE)
+    }
+
+    fn activity(&self) -> &activity::Log {
+        &self.activity_log
+    }
+
+    fn metrics(&self) -> &metrics::Metrics {
+        &self.metrics
+    }
+
+    fn recovery(&self) -> Option<&Recovery> {
+        self.recovery.as_ref()
+    }
+
+    fn export_policies(&self) -> Result<tracking::Policies, tracking::Error> {
+        self.tracking.export()
+    }
+
+    fn gossip(&self) -> GossipStats {
+        GossipStats {
+            relays_throttled: self.outbox.throttled(),
+            bandwidth: self.bandwidth.hours(),
+            ..self.gossip.stats()
+        }
+    }
 }
 
 /// Disconnect reason.
@@ -1651,6 +4622,10 @@ impl DisconnectReason {
         matches!(self, Self::Connection(_))
     }
 
+    pub fn is_command(&self) -> bool {
+        matches!(self, Self::Command)
+    }
+
     // TODO: These aren't quite correct, since dial errors *can* be transient, eg.
     // temporary DNS issue.
     pub fn is_transient(&self) -> bool {
@@ -1658,7 +4633,7 @@ impl DisconnectReason {
             Self::Dial(_) => false,
             Self::Connection(_) => true,
             Self::Command => false,
-            Self::Fetch(_) => true,
+            Self::Fetch(err) => err.is_transient(),
             Self::Session(err) => err.is_transient(),
         }
     }
@@ -1700,26 +4675,67 @@ pub enum LookupError {
 pub struct Node {
     /// Last ref announcements (per project).
     pub last_refs: HashMap<Id, Announcement>,
+    /// Local time at which the last refs announcement of each project was stored.
+    refs_updated: HashMap<Id, LocalTime>,
     /// Last inventory announcement.
     pub last_inventory: Option<Announcement>,
     /// Last node announcement.
     pub last_node: Option<Announcement>,
+    /// Last cobs announcement.
+    pub last_cobs: Option<Announcement>,
+    /// Local time at which an announcement of this node was last stored.
+    pub last_updated: LocalTime,
+    /// Number of refs announcement entries of this node with an invalid signature, not
+    /// yet forgiven.
+    invalid_refs: usize,
+    /// Local time from which invalid entries are forgiven.
+    invalid_refs_since: LocalTime,
 }
 
 impl Node {
-    /// Process a refs announcement for the given node.
+    /// Get the number of refs announcement entries of this node with an invalid signature,
+    /// after forgiving one for each [`INVALID_REFS_DECAY`] elapsed.
+    pub fn invalid_refs(&mut self, now: LocalTime) -> usize {
+        let elapsed = now - self.invalid_refs_since;
+        let forgiven = elapsed.as_millis() / INVALID_REFS_DECAY.as_millis();
+
+        if forgiven > 0 {
+            self.invalid_refs = self.invalid_refs.saturating_sub(forgiven as usize);
+            self.invalid_refs_since = now;
+        }
+        self.invalid_refs
+    }
+
+    /// Count refs announcement entries of this node with an invalid signature. Returns the
+    /// number of such entries not yet forgiven.
+    pub fn count_invalid_refs(&mut self, count: usize, now: LocalTime) -> usize {
+        if self.invalid_refs(now) == 0 {
+            self.invalid_refs_since = now;
+        }
+        self.invalid_refs += count;
+        self.invalid_refs
+    }
+
+    /// Process a refs announcement for the given node, received at the given local time.
     /// Returns `true` if the timestamp was updated.
-    pub fn refs_announced(&mut self, id: Id, ann: Announcement) -> bool {
+    pub fn refs_announced(&mut self, id: Id, ann: Announcement, now: LocalTime) -> bool {
         match self.last_refs.entry(id) {
             Entry::Vacant(e) => {
                 e.insert(ann);
+                self.refs_updated.insert(id, now);
                 return true;
             }
             Entry::Occupied(mut e) => {
                 let last = e.get_mut();
 
-                if ann.timestamp() > last.timestamp() {
+                // Announcements with the same timestamp are ordered by content, so that
+                // changed refs aren't discarded, and all nodes keep the same announcement.
+                if ann.timestamp() > last.timestamp()
+                    || (ann.timestamp() == last.timestamp()
+                        && wire::serialize(&ann.message) > wire::serialize(&last.message))
+                {
                     *last = ann;
+                    self.refs_updated.insert(id, now);
                     return true;
                 }
             }
@@ -1762,6 +4778,62 @@ impl Node {
         }
         false
     }
+
+    /// Process a cobs announcement for the given node.
+    /// Returns `true` if the timestamp was updated.
+    pub fn cobs_announced(&mut self, ann: Announcement) -> bool {
+        match &mut self.last_cobs {
+            Some(last) => {
+                if ann.timestamp() > last.timestamp() {
+                    *last = ann;
+                    return true;
+                }
+            }
+            None => {
+                self.last_cobs = Some(ann);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Evict the least recently stored refs announcements, until at most `max` are left.
+    /// Refs announcements of repositories for which `is_protected` returns `true` are
+    /// never evicted. Returns the repositories of the evicted announcements.
+    pub fn prune_refs(&mut self, max: usize, mut is_protected: impl FnMut(&Id) -> bool) -> Vec<Id> {
+        let mut excess = self.last_refs.len().saturating_sub(max);
+        if excess == 0 {
+            return vec![];
+        }
+        // Nb. Announcement timestamps are chosen by the announcer, so we use the local time
+        // at which they were stored instead.
+        let mut candidates = self
+            .last_refs
+            .keys()
+            .map(|rid| {
+                (
+                    self.refs_updated.get(rid).copied().unwrap_or_default(),
+                    *rid,
+                )
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        let mut evicted = Vec::new();
+        for (_, rid) in candidates {
+            if excess == 0 {
+                break;
+            }
+            if is_protected(&rid) {
+                continue;
+            }
+            self.last_refs.remove(&rid);
+            self.refs_updated.remove(&rid);
+            evicted.push(rid);
+            excess -= 1;
+        }
+        evicted
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1830,53 +4902,140 @@ pub mod gossip {
     }
 
     impl Gossip {
-        pub fn filtered<'a>(
-            &'a self,
-            filter: &'a Filter,
+        /// Get the announcements matching the given filter, with a timestamp in the given
+        /// range, in ascending timestamp order. Announcements with the same timestamp are
+        /// ordered by announcer.
+        ///
+        /// Subscription backlogs are sent in this order. Hence, a peer that remembers the
+        /// timestamp of the last announcement it processed can resume by subscribing with
+        /// that timestamp as `since`, without missing any of the announcements we had.
+        /// Announcements with that exact timestamp are sent again.
+        pub fn filtered(
+            &self,
+            filter: &Filter,
             start: Timestamp,
             end: Timestamp,
-        ) -> impl Iterator<Item = Announcement> + '_ {
-            self.nodes
+        ) -> impl Iterator<Item = Announcement> {
+            let mut anns = self
+                .nodes
                 .values()
                 .flat_map(|n| {
-                    [&n.last_node, &n.last_inventory]
+                    [&n.last_node, &n.last_inventory, &n.last_cobs]
                         .into_iter()
                         .flatten()
                         .chain(n.last_refs.values())
-                        .cloned()
-                        .collect::<Vec<_>>()
                 })
-                .filter(move |ann| ann.timestamp() >= start && ann.timestamp() < end)
-                .filter(move |ann| ann.matches(filter))
+                .filter(|ann| ann.timestamp() >= start && ann.timestamp() < end)
+                .filter(|ann| ann.matches(filter))
+                .cloned()
+                .collect::<Vec<_>>();
+            // Nb. The sort is stable, and the refs announcements of an announcer are
+            // ordered by repository, so that the order is fully deterministic.
+            anns.sort_by_key(|ann| {
+                let rid = match &ann.message {
+                    AnnouncementMessage::Refs(refs) => Some(refs.rid),
+                    _ => None,
+                };
+                (ann.timestamp(), ann.node, rid)
+            });
+            anns.into_iter()
+        }
+
+        /// Check whether, according to its own last refs announcement for the same repository,
+        /// the given peer already has the refs of a refs announcement. A peer's other
+        /// announcements are about itself, and say nothing about the announcer's, so only
+        /// refs announcements are ever considered known. This is a heuristic, since the gossip
+        /// state may be stale: it returns `false` for peers we know nothing about.
+        pub fn is_known_by(&self, peer: &NodeId, ann: &Announcement) -> bool {
+            let AnnouncementMessage::Refs(refs) = &ann.message else {
+                return false;
+            };
+            let Some(last) = self
+                .nodes
+                .get(peer)
+                .and_then(|node| node.last_refs.get(&refs.rid))
+            else {
+                return false;
+            };
+            let AnnouncementMessage::Refs(known) = &last.message else {
+                return false;
+            };
+            refs.refs.iter().all(|r| known.refs.contains(r))
+        }
+
+        /// Get the size of the store.
+        pub fn stats(&self) -> GossipStats {
+            GossipStats {
+                nodes: self.nodes.len(),
+                refs: self.nodes.values().map(|n| n.last_refs.len()).sum(),
+                ..GossipStats::default()
+            }
+        }
+
+        /// Evict the least recently updated announcers, until at most `max` are left.
+        /// Announcers for which `is_protected` returns `true` are never evicted, hence
+        /// the store may stay above `max`. Returns the evicted announcers.
+        pub fn prune(
+            &mut self,
+            max: usize,
+            mut is_protected: impl FnMut(&NodeId, &Node) -> bool,
+        ) -> Vec<NodeId> {
+            let mut excess = self.nodes.len().saturating_sub(max);
+            if excess == 0 {
+                return vec![];
+            }
+            let mut candidates = self
+                .nodes
+                .iter()
+                .map(|(nid, n)| (n.last_updated, *nid))
+                .collect::<Vec<_>>();
+            candidates.sort();
+
+            let mut evicted = Vec::new();
+            for (_, nid) in candidates {
+                if excess == 0 {
+                    break;
+                }
+                if self.nodes.get(&nid).map_or(true, |n| is_protected(&nid, n)) {
+                    continue;
+                }
+                self.nodes.remove(&nid);
+                evicted.push(nid);
+                excess -= 1;
+            }
+            evicted
+        }
+    }
+
+    /// Check whether an announcement can be sent to the given peer. Cobs announcements
+    /// are only sent to peers we know speak our protocol version, since nodes that don't
+    /// know them only skip them in that case.
+    pub fn is_supported(session: &Session, message: &AnnouncementMessage) -> bool {
+        match message {
+            AnnouncementMessage::Cobs(_) => session.supports(wire::PROTOCOL_VERSION),
+            _ => true,
         }
     }
 
-    pub fn handshake<G: Signer, S: ReadStorage>(
+    pub fn handshake<G: Signer>(
         node: NodeAnnouncement,
         now: Timestamp,
-        storage: &S,
+        inventory: Vec<Id>,
         signer: &G,
-        filter: Filter,
+        filter: Option<Filter>,
     ) -> Vec<Message> {
-        let inventory = match storage.inventory() {
-            Ok(i) => i,
-            Err(e) => {
-                error!("Error getting local inventory for handshake: {}", e);
-                // Other than crashing the node completely, there's nothing we can do
-                // here besides returning an empty inventory and logging an error.
-                vec![]
-            }
-        };
-
-        vec![
+        let mut msgs = vec![
             Message::node(node, signer),
             Message::inventory(gossip::inventory(now, inventory), signer),
-            Message::subscribe(
+        ];
+        if let Some(filter) = filter {
+            msgs.push(Message::subscribe(
                 filter,
                 now - SUBSCRIBE_BACKLOG_DELTA.as_millis() as u64,
                 Timestamp::MAX,
-            ),
-        ]
+            ));
+        }
+        msgs
     }
 
     pub fn node(config: &Config, timestamp: Timestamp) -> NodeAnnouncement {
@@ -1897,6 +5056,29 @@ pub mod gossip {
         }
     }
 
+    pub fn cobs(config: &Config, timestamp: Timestamp) -> CobsAnnouncement {
+        let mut cobs = config
+            .cobs
+            .iter()
+            .filter(|cob| cob.as_str().len() <= u8::MAX as usize)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if cobs.len() > COB_LIMIT {
+            error!(
+                target: "service",
+                "cobs announcement limit ({COB_LIMIT}) exceeded, other nodes will see only some of your collaborative object types"
+            );
+            cobs.truncate(COB_LIMIT);
+        }
+        CobsAnnouncement {
+            cobs: cobs
+                .try_into()
+                .expect("collaborative object types are within the limit"),
+            timestamp,
+        }
+    }
+
     pub fn inventory(timestamp: Timestamp, inventory: Vec<Id>) -> InventoryAnnouncement {
         type Inventory = BoundedVec<Id, INVENTORY_LIMIT>;
 
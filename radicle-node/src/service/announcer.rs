@@ -0,0 +1,120 @@
+use fastrand::Rng;
+use localtime::{LocalDuration, LocalTime};
+
+/// Periodic announcement.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Job {
+    /// Announce our inventory.
+    Inventory,
+}
+
+/// Periodic announcer.
+///
+/// Instead of announcing at the start of every announce interval, announcements are due
+/// at a jittered offset into the interval, so that nodes started at the same time don't
+/// synchronize their announcements.
+///
+/// Our refs are not re-announced periodically: they are announced when they change.
+/// Announcements triggered by the user or by events are not delayed.
+#[derive(Debug, Default)]
+pub struct Announcer {
+    /// Announcement that is not yet due, and when it is due.
+    scheduled: Option<(LocalTime, Job)>,
+    /// Time of the next wakeup we asked for, if any.
+    wakeup: Option<LocalTime>,
+}
+
+impl Announcer {
+    /// Schedule a round of announcements, due within the first half of the interval
+    /// starting now. An announcement of the previous round which is not yet due is
+    /// dropped, since it is announced as part of this round.
+    pub fn schedule(&mut self, now: LocalTime, interval: LocalDuration, rng: &mut Rng) {
+        let jitter = LocalDuration::from_millis(rng.u128(0..(interval.as_millis() / 2).max(1)));
+
+        self.scheduled = Some((now + jitter, Job::Inventory));
+    }
+
+    /// Get the next due announcement, if any.
+    pub fn next(&mut self, now: LocalTime) -> Option<Job> {
+        match self.scheduled {
+            Some((due, job)) if due <= now => {
+                self.scheduled = None;
+                Some(job)
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of announcements scheduled, but not yet due.
+    pub fn scheduled(&self) -> usize {
+        self.scheduled.iter().count()
+    }
+
+    /// Move the schedule back by the given duration, after the clock jumped backwards.
+    pub fn rewind(&mut self, by: LocalDuration) {
+        if let Some((due, _)) = &mut self.scheduled {
+            *due = super::rewind(*due, by);
+        }
+        self.wakeup = self.wakeup.map(|t| super::rewind(t, by));
+    }
+
+    /// Get the delay after which the announcer should be woken up, if it needs to be,
+    /// and isn't already going to be.
+    pub fn wakeup(&mut self, now: LocalTime) -> Option<LocalDuration> {
+        let (next, _) = self.scheduled?;
+        let next = next.max(now);
+
+        if matches!(self.wakeup, Some(t) if t > now && t <= next) {
+            return None;
+        }
+        self.wakeup = Some(next);
+
+        Some(next - now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_jitter() {
+        let mut rng = Rng::with_seed(42);
+        let mut announcer = Announcer::default();
+        let interval = LocalDuration::from_mins(60);
+        let now = LocalTime::from_secs(3600);
+
+        announcer.schedule(now, interval, &mut rng);
+        assert_eq!(announcer.scheduled(), 1);
+
+        // The inventory is announced once, within the first half of the interval.
+        let mut emitted = Vec::new();
+        let mut t = now;
+        while t < now + interval {
+            while let Some(job) = announcer.next(t) {
+                emitted.push((t, job));
+            }
+            t = t + LocalDuration::from_mins(1);
+        }
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(emitted[0].1, Job::Inventory);
+        assert!(emitted[0].0 <= now + LocalDuration::from_mins(30));
+        assert_eq!(announcer.scheduled(), 0);
+    }
+
+    #[test]
+    fn test_wakeup() {
+        let mut rng = Rng::with_seed(42);
+        let mut announcer = Announcer::default();
+        let now = LocalTime::from_secs(3600);
+
+        assert_eq!(announcer.wakeup(now), None);
+        announcer.schedule(now, LocalDuration::from_mins(60), &mut rng);
+
+        // We're woken up once when the announcement is due.
+        let delay = announcer.wakeup(now).unwrap();
+        assert_eq!(announcer.wakeup(now), None);
+        assert!(announcer.next(now + delay).is_some());
+        assert_eq!(announcer.wakeup(now + delay), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! Exponential backoff, used to space out retries, eg. of connections and fetches.
+use fastrand::Rng;
+use localtime::LocalDuration;
+
+/// Exponential backoff schedule.
+///
+/// The delay before attempt `n` is `base * factor^n`, plus a random jitter of up to
+/// `jitter` times that delay, clamped between `min` and `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first attempt, before clamping.
+    pub base: LocalDuration,
+    /// Factor the delay is multiplied by with each attempt.
+    pub factor: u64,
+    /// Maximum random jitter added to the delay, as a fraction of the delay.
+    /// No randomness is used if zero.
+    pub jitter: f64,
+    /// Minimum delay.
+    pub min: LocalDuration,
+    /// Maximum delay.
+    pub max: LocalDuration,
+    /// Number of attempts after which we give up. We never give up if not set.
+    pub max_attempts: Option<usize>,
+}
+
+impl Backoff {
+    /// Get the delay to wait for, given the number of attempts made so far.
+    pub fn next_delay(&self, attempts: usize, rng: &mut Rng) -> LocalDuration {
+        let exp = u32::try_from(attempts).unwrap_or(u32::MAX);
+        let delay = self
+            .base
+            .as_millis()
+            .saturating_mul(self.factor.saturating_pow(exp) as u128);
+        let jitter = if self.jitter > 0. {
+            let max = (delay as f64 * self.jitter) as u64;
+            rng.u64(0..=max) as u128
+        } else {
+            0
+        };
+
+        LocalDuration::from_millis(delay.saturating_add(jitter)).clamp(self.min, self.max)
+    }
+
+    /// Check whether we should give up, given the number of attempts made so far.
+    pub fn should_give_up(&self, attempts: usize) -> bool {
+        self.max_attempts.map_or(false, |max| attempts > max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use qcheck_macros::quickcheck;
+
+    fn backoff(base: u16, factor: u8, jitter: u8, min: u16, max: u16) -> Backoff {
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+
+        Backoff {
+            base: LocalDuration::from_secs(base as u64),
+            factor: factor as u64 % 8 + 1,
+            jitter: jitter as f64 / u8::MAX as f64,
+            min: LocalDuration::from_secs(min as u64),
+            max: LocalDuration::from_secs(max as u64),
+            max_attempts: None,
+        }
+    }
+
+    #[quickcheck]
+    fn prop_monotonic(base: u16, factor: u8, min: u16, max: u16, attempts: u8) {
+        let backoff = backoff(base, factor, 0, min, max);
+        let mut rng = Rng::with_seed(0);
+        let attempts = attempts as usize;
+
+        assert!(
+            backoff.next_delay(attempts, &mut rng) <= backoff.next_delay(attempts + 1, &mut rng)
+        );
+    }
+
+    #[quickcheck]
+    fn prop_clamped(base: u16, factor: u8, jitter: u8, min: u16, max: u16, attempts: u8) {
+        let backoff = backoff(base, factor, jitter, min, max);
+        let mut rng = Rng::with_seed(attempts as u64);
+        let delay = backoff.next_delay(attempts as usize, &mut rng);
+
+        assert!(delay >= backoff.min);
+        assert!(delay <= backoff.max);
+    }
+
+    #[quickcheck]
+    fn prop_jitter(base: u16, factor: u8, jitter: u8, attempts: u8, seed: u64) {
+        let backoff = backoff(base, factor, jitter, 0, u16::MAX);
+        let exact = Backoff {
+            jitter: 0.,
+            ..backoff
+        };
+        let mut rng = Rng::with_seed(seed);
+        let attempts = attempts as usize;
+        let delay = exact.next_delay(attempts, &mut rng);
+        let jittered = backoff.next_delay(attempts, &mut rng);
+        let bound = delay.as_millis() + (delay.as_millis() as f64 * backoff.jitter) as u128;
+
+        assert!(jittered >= delay);
+        assert!(jittered.as_millis() <= bound.min(backoff.max.as_millis()));
+    }
+
+    #[test]
+    fn test_give_up() {
+        let backoff = Backoff {
+            max_attempts: Some(3),
+            ..backoff(1, 1, 0, 1, 1)
+        };
+        assert!(!backoff.should_give_up(3));
+        assert!(backoff.should_give_up(4));
+        assert!(!Backoff {
+            max_attempts: None,
+            ..backoff
+        }
+        .should_give_up(usize::MAX));
+    }
+
+    #[test]
+    fn test_reconnection_delays() {
+        use crate::service::{
+            MAX_RECONNECTION_DELTA, MIN_RECONNECTION_DELTA, RECONNECTION_BACKOFF,
+        };
+
+        let mut rng = Rng::with_seed(0);
+
+        for attempts in 0..16 {
+            let expected = LocalDuration::from_secs(2u64.saturating_pow(attempts as u32))
+                .clamp(MIN_RECONNECTION_DELTA, MAX_RECONNECTION_DELTA);
+
+            assert_eq!(
+                RECONNECTION_BACKOFF.next_delay(attempts, &mut rng),
+                expected,
+                "delay of attempt {attempts}"
+            );
+        }
+        assert_eq!(
+            RECONNECTION_BACKOFF.next_delay(2, &mut rng),
+            MIN_RECONNECTION_DELTA
+        );
+        assert_eq!(
+            RECONNECTION_BACKOFF.next_delay(12, &mut rng),
+            MAX_RECONNECTION_DELTA
+        );
+    }
+
+    #[test]
+    fn test_fetch_retry_delays() {
+        use crate::service::{
+            FETCH_RETRY_BACKOFF, MAX_FETCH_RETRIES, MAX_FETCH_RETRY_DELTA, MIN_FETCH_RETRY_DELTA,
+        };
+
+        let mut rng = Rng::with_seed(0);
+
+        for attempts in 1..=MAX_FETCH_RETRIES {
+            let expected = LocalDuration::from_secs(2u64.saturating_pow(attempts as u32))
+                .clamp(MIN_FETCH_RETRY_DELTA, MAX_FETCH_RETRY_DELTA);
+
+            assert_eq!(FETCH_RETRY_BACKOFF.next_delay(attempts, &mut rng), expected);
+            assert!(!FETCH_RETRY_BACKOFF.should_give_up(attempts));
+        }
+        assert!(FETCH_RETRY_BACKOFF.should_give_up(MAX_FETCH_RETRIES + 1));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+use localtime::{LocalDuration, LocalTime};
+use radicle::node::{Bandwidth, MessageKind};
+
+/// Number of hours of gossip traffic kept.
+pub const MAX_HOURS: usize = 24;
+/// Length of the sliding window over which the relay rate applies.
+pub const THROTTLE_WINDOW: LocalDuration = LocalDuration::from_secs(10);
+
+/// Length of an hour, in milliseconds.
+const HOUR: u64 = 60 * 60 * 1000;
+
+/// Gossip traffic accounting, aggregated per hour and per message kind.
+#[derive(Debug, Default)]
+pub struct Accounting {
+    /// Traffic of the last [`MAX_HOURS`] hours, oldest first.
+    hours: VecDeque<Bandwidth>,
+}
+
+impl Accounting {
+    /// Account for bytes sent.
+    pub fn sent(&mut self, kind: MessageKind, bytes: usize, now: LocalTime) {
+        *self.hour(now).sent.entry(kind).or_default() += bytes as u64;
+    }
+
+    /// Account for bytes received.
+    pub fn received(&mut self, kind: MessageKind, bytes: usize, now: LocalTime) {
+        *self.hour(now).received.entry(kind).or_default() += bytes as u64;
+    }
+
+    /// Get the traffic of the last hours, oldest first.
+    pub fn hours(&self) -> Vec<Bandwidth> {
+        self.hours.iter().cloned().collect()
+    }
+
+    fn hour(&mut self, now: LocalTime) -> &mut Bandwidth {
+        let hour = now.as_millis() / HOUR * HOUR;
+
+        if self.hours.back().map_or(true, |b| b.hour < hour) {
+            self.hours.push_back(Bandwidth {
+                hour,
+                ..Bandwidth::default()
+            });
+            if self.hours.len() > MAX_HOURS {
+                self.hours.pop_front();
+            }
+        }
+        self.hours
+            .back_mut()
+            .expect("Accounting::hour: there is at least one entry")
+    }
+}
+
+/// Outbound gossip throttle.
+///
+/// Bytes spent are tracked over a sliding [`THROTTLE_WINDOW`], and spending is refused
+/// once the rate would be exceeded over the window. The first spend of an empty window
+/// is always allowed, so that messages larger than the window budget still get through.
+#[derive(Debug)]
+pub struct Throttle {
+    /// Maximum number of bytes per second.
+    rate: usize,
+    /// Bytes spent during the window, and when.
+    spent: VecDeque<(LocalTime, usize)>,
+    /// Sum of the bytes spent during the window.
+    total: usize,
+    /// Whether the last spend was refused.
+    throttled: bool,
+}
+
+impl Throttle {
+    pub fn new(rate: usize) -> Self {
+        Self {
+            rate,
+            spent: VecDeque::new(),
+            total: 0,
+            throttled: false,
+        }
+    }
+
+    /// Maximum number of bytes per second.
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    /// Change the maximum number of bytes per second. Bytes already spent still count.
+    pub fn set_rate(&mut self, rate: usize) {
+        self.rate = rate;
+    }
+
+    /// Whether the last spend was refused.
+    pub fn is_throttled(&self) -> bool {
+        self.throttled
+    }
+
+    /// Try to spend the given number of bytes. Returns `false` if this would exceed the
+    /// rate over the current window.
+    pub fn spend(&mut self, bytes: usize, now: LocalTime) -> bool {
+        while let Some((t, b)) = self.spent.front() {
+            if now - *t < THROTTLE_WINDOW {
+                break;
+            }
+            self.total -= b;
+            self.spent.pop_front();
+        }
+        let budget = self.rate * THROTTLE_WINDOW.as_secs() as usize;
+
+        if !self.spent.is_empty() && self.total + bytes > budget {
+            self.throttled = true;
+            return false;
+        }
+        self.spent.push_back((now, bytes));
+        self.total += bytes;
+        self.throttled = false;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounting() {
+        let mut accounting = Accounting::default();
+        let now = LocalTime::from_secs(3600 * 100);
+
+        accounting.sent(MessageKind::Ping, 10, now);
+        accounting.sent(MessageKind::Ping, 10, now + LocalDuration::from_mins(59));
+        accounting.received(MessageKind::Pong, 5, now);
+        accounting.sent(
+            MessageKind::RefsAnnouncement,
+            100,
+            now + LocalDuration::from_mins(60),
+        );
+
+        let hours = accounting.hours();
+        assert_eq!(hours.len(), 2);
+        assert_eq!(hours[0].hour, now.as_millis());
+        assert_eq!(hours[0].sent[&MessageKind::Ping], 20);
+        assert_eq!(hours[0].total_received(), 5);
+        assert_eq!(hours[1].total_sent(), 100);
+
+        // Only the last hours are kept.
+        for h in 0..MAX_HOURS as u64 * 2 {
+            accounting.sent(MessageKind::Ping, 1, now + LocalDuration::from_mins(60 * h));
+        }
+        assert_eq!(accounting.hours().len(), MAX_HOURS);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let mut throttle = Throttle::new(10);
+        let now = LocalTime::from_secs(3600);
+
+        // The first spend of a window is allowed, even if over budget.
+        assert!(throttle.spend(150, now));
+        assert!(!throttle.spend(1, now));
+        assert!(throttle.is_throttled());
+
+        // The window slides.
+        let now = now + THROTTLE_WINDOW;
+        assert!(throttle.spend(50, now));
+        assert!(!throttle.is_throttled());
+        assert!(throttle.spend(50, now + LocalDuration::from_secs(5)));
+        assert!(!throttle.spend(1, now + LocalDuration::from_secs(5)));
+
+        // Only the first spend has left the window.
+        let now = now + LocalDuration::from_secs(10);
+        assert!(throttle.spend(50, now));
+        assert!(!throttle.spend(1, now));
+    }
+}
@@ -1,11 +1,16 @@
-use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
 
 use log::*;
 
 use crate::prelude::*;
+use crate::service::bandwidth::Throttle;
 use crate::service::session::Session;
+use crate::service::tracking::RefFilter;
 use crate::service::Link;
 use crate::storage::Namespaces;
+use crate::wire;
 
 use super::message::{Announcement, AnnouncementMessage};
 
@@ -26,19 +31,77 @@ pub enum Io {
         remote: NodeId,
         /// Namespaces being fetched.
         namespaces: Namespaces,
+        /// Maximum number of bytes that can be received before the fetch is aborted.
+        limit: u64,
+        /// Which refs are fetched from the remotes.
+        filter: RefFilter,
+    },
+    /// List the refs a peer has for a repository, without fetching anything.
+    LsRefs {
+        /// Repo being listed.
+        rid: Id,
+        /// Remote node being asked.
+        remote: NodeId,
+        /// Namespaces being listed.
+        namespaces: Namespaces,
+    },
+    /// Fetch the identity branch of a repository from a peer, without storing it.
+    FetchIdentity {
+        /// Repo whose identity is being fetched.
+        rid: Id,
+        /// Remote node being fetched from.
+        remote: NodeId,
     },
     /// Ask for a wakeup in a specified amount of time.
     Wakeup(LocalDuration),
 }
 
+/// Outcome of relaying an announcement.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Relay {
+    /// The announcement was relayed to interested peers, if any.
+    Relayed,
+    /// The announcement was dropped, to stay within the relay rate. `started` is `true`
+    /// if this is the first announcement dropped since relaying was last allowed.
+    Throttled { started: bool },
+}
+
 /// Interface to the network.
 #[derive(Debug, Default)]
 pub struct Outbox {
     /// Outgoing I/O queue.
     io: VecDeque<Io>,
+    /// Relay rate throttle, if there is a relay rate.
+    throttle: Option<Throttle>,
+    /// Number of announcements dropped to stay within the relay rate.
+    throttled: usize,
+    /// Digests of the announcements queued for each peer, that weren't handed over to the
+    /// wire yet. Used to skip queuing the same announcement twice.
+    queued: HashMap<NodeId, HashSet<u64>>,
 }
 
 impl Outbox {
+    /// Create a new outbox, relaying at most `relay_rate` bytes per second, if set.
+    pub fn new(relay_rate: Option<usize>) -> Self {
+        Self {
+            throttle: relay_rate.map(Throttle::new),
+            ..Self::default()
+        }
+    }
+
+    /// Change the relay rate. Relaying is unthrottled if `None` is given.
+    pub fn set_relay_rate(&mut self, relay_rate: Option<usize>) {
+        match (&mut self.throttle, relay_rate) {
+            (Some(throttle), Some(rate)) => throttle.set_rate(rate),
+            (throttle, rate) => *throttle = rate.map(Throttle::new),
+        }
+    }
+
+    /// Number of announcements dropped to stay within the relay rate.
+    pub fn throttled(&self) -> usize {
+        self.throttled
+    }
+
     /// Connect to a peer.
     pub fn connect(&mut self, id: NodeId, addr: Address) {
         self.io.push_back(Io::Connect(id, addr));
@@ -50,6 +113,9 @@ impl Outbox {
     }
 
     pub fn write(&mut self, remote: &Session, msg: Message) {
+        let Some(msg) = self.dedup(remote.id, msg) else {
+            return;
+        };
         msg.log(log::Level::Debug, &remote.id, Link::Outbound);
         trace!(target: "service", "Write {:?} to {}", &msg, remote);
 
@@ -57,7 +123,21 @@ impl Outbox {
     }
 
     pub fn write_all(&mut self, remote: &Session, msgs: impl IntoIterator<Item = Message>) {
-        let msgs = msgs.into_iter().collect::<Vec<_>>();
+        let mut batch = Vec::new();
+        for msg in msgs {
+            let Some(msg) = self.dedup(remote.id, msg) else {
+                continue;
+            };
+            // Announcements of this batch aren't queued yet, so we check them here.
+            if matches!(msg, Message::Announcement(_)) && batch.contains(&msg) {
+                continue;
+            }
+            batch.push(msg);
+        }
+        let msgs = batch;
+        if msgs.is_empty() {
+            return;
+        }
 
         for (ix, msg) in msgs.iter().enumerate() {
             trace!(
@@ -73,15 +153,49 @@ impl Outbox {
         self.io.push_back(Io::Write(remote.id, msgs));
     }
 
+    /// Write up to `max` announcements from the peer's subscription backlog.
+    /// Returns whether there are announcements left to write.
+    pub fn write_backlog(&mut self, remote: &mut Session, max: usize) -> bool {
+        let chunk = remote.next_backlog(max);
+        if !chunk.is_empty() {
+            self.write_all(remote, chunk.into_iter().map(Message::from));
+        }
+        remote.has_backlog()
+    }
+
     pub fn wakeup(&mut self, after: LocalDuration) {
         self.io.push_back(Io::Wakeup(after));
     }
 
-    pub fn fetch(&mut self, remote: &mut Session, rid: Id, namespaces: Namespaces) {
+    pub fn fetch(
+        &mut self,
+        remote: &mut Session,
+        rid: Id,
+        namespaces: Namespaces,
+        limit: u64,
+        filter: RefFilter,
+    ) {
         self.io.push_back(Io::Fetch {
             rid,
             namespaces,
             remote: remote.id,
+            limit,
+            filter,
+        });
+    }
+
+    pub fn ls_refs(&mut self, remote: &Session, rid: Id, namespaces: Namespaces) {
+        self.io.push_back(Io::LsRefs {
+            rid,
+            namespaces,
+            remote: remote.id,
+        });
+    }
+
+    pub fn fetch_identity(&mut self, remote: &Session, rid: Id) {
+        self.io.push_back(Io::FetchIdentity {
+            rid,
+            remote: remote.id,
         });
     }
 
@@ -97,22 +211,104 @@ impl Outbox {
         }
     }
 
-    /// Relay a message to interested peers.
-    pub fn relay<'a>(&mut self, ann: Announcement, peers: impl IntoIterator<Item = &'a Session>) {
-        if let AnnouncementMessage::Refs(msg) = &ann.message {
+    /// Relay a message to interested peers, unless this would exceed the relay rate.
+    pub fn relay<'a>(
+        &mut self,
+        ann: Announcement,
+        peers: impl IntoIterator<Item = &'a Session>,
+        now: LocalTime,
+    ) -> Relay {
+        let peers = if let AnnouncementMessage::Refs(msg) = &ann.message {
             let id = msg.rid;
-            let peers = peers.into_iter().filter(|p| {
-                if let Some(subscribe) = &p.subscribe {
-                    subscribe.filter.contains(&id)
+            peers
+                .into_iter()
+                .filter(|p| {
+                    if let Some(subscribe) = &p.subscribe {
+                        subscribe.filter.contains(&id)
+                    } else {
+                        // If the peer did not send us a `subscribe` message, we don'the
+                        // relay any messages to them.
+                        false
+                    }
+                })
+                .collect::<Vec<_>>()
+        } else {
+            peers.into_iter().collect()
+        };
+        if peers.is_empty() {
+            return Relay::Relayed;
+        }
+        let msg = Message::from(ann);
+
+        if let Some(throttle) = &mut self.throttle {
+            let started = !throttle.is_throttled();
+            let bytes = wire::serialize(&msg).len() * peers.len();
+
+            if !throttle.spend(bytes, now) {
+                self.throttled += 1;
+
+                return Relay::Throttled { started };
+            }
+        }
+        self.broadcast(msg, peers);
+
+        Relay::Relayed
+    }
+
+    /// Deduplicate an idempotent message against the messages queued for the same peer,
+    /// that weren't handed over to the wire yet. Returns the message to queue, if any.
+    ///
+    /// Identical announcements are only queued once, and a subscription replaces the
+    /// queued ones, since only the latest filter matters. Pings and pongs are never
+    /// deduplicated.
+    fn dedup(&mut self, remote: NodeId, msg: Message) -> Option<Message> {
+        match msg {
+            Message::Subscribe(mut subscribe) => {
+                for io in self.io.iter_mut() {
+                    let Io::Write(nid, msgs) = io else {
+                        continue;
+                    };
+                    if *nid != remote {
+                        continue;
+                    }
+                    msgs.retain(|m| {
+                        let Message::Subscribe(queued) = m else {
+                            return true;
+                        };
+                        // Keep the widest time range, so that we don't miss out on
+                        // messages requested by the replaced subscription.
+                        subscribe.since = subscribe.since.min(queued.since);
+                        subscribe.until = subscribe.until.max(queued.until);
+
+                        false
+                    });
+                }
+                self.io
+                    .retain(|io| !matches!(io, Io::Write(_, msgs) if msgs.is_empty()));
+
+                Some(Message::Subscribe(subscribe))
+            }
+            Message::Announcement(_) => {
+                let digest = digest(&msg);
+                let queued = self.queued.entry(remote).or_default();
+
+                if queued.contains(&digest) {
+                    let duplicate = self.io.iter().any(|io| {
+                        matches!(io, Io::Write(nid, msgs) if *nid == remote && msgs.contains(&msg))
+                    });
+                    if duplicate {
+                        debug!(target: "service", "Skipping duplicate announcement to {remote}");
+                        return None;
+                    }
                 } else {
-                    // If the peer did not send us a `subscribe` message, we don'the
-                    // relay any messages to them.
-                    false
+                    queued.insert(digest);
                 }
-            });
-            self.broadcast(ann, peers);
-        } else {
-            self.broadcast(ann, peers);
+                Some(msg)
+            }
+            Message::AnnouncementRequest(_)
+            | Message::AnnouncementResponse(_)
+            | Message::Ping(_)
+            | Message::Pong { .. } => Some(msg),
         }
     }
 
@@ -126,6 +322,111 @@ impl Iterator for Outbox {
     type Item = Io;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.io.pop_front()
+        let io = self.io.pop_front()?;
+
+        if let Io::Write(remote, msgs) = &io {
+            if let Some(queued) = self.queued.get_mut(remote) {
+                for msg in msgs {
+                    if let Message::Announcement(_) = msg {
+                        queued.remove(&digest(msg));
+                    }
+                }
+                if queued.is_empty() {
+                    self.queued.remove(remote);
+                }
+            }
+        }
+        Some(io)
+    }
+}
+
+/// Digest of a message's wire encoding.
+fn digest(msg: &Message) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    wire::serialize(msg).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::test::signer::MockSigner;
+    use crate::node::config::Limits;
+    use crate::service::filter::Filter;
+    use crate::service::message::{InventoryAnnouncement, Ping};
+    use crate::test::arbitrary;
+
+    fn session() -> Session {
+        let addr = Address::from(std::net::SocketAddr::from(([8, 8, 8, 8], 8776)));
+        Session::outbound(
+            arbitrary::gen(1),
+            addr,
+            false,
+            fastrand::Rng::new(),
+            Limits::default(),
+        )
+    }
+
+    fn writes(outbox: &mut Outbox) -> Vec<Message> {
+        outbox
+            .filter_map(|io| match io {
+                Io::Write(_, msgs) => Some(msgs),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup_subscribe() {
+        let mut outbox = Outbox::default();
+        let alice = session();
+        let bob = session();
+        let rids = arbitrary::vec::<Id>(3);
+        let mut filter = Filter::empty();
+
+        for (i, rid) in rids.iter().enumerate() {
+            filter.insert(rid);
+            outbox.broadcast(
+                Message::subscribe(filter.clone(), 100 - i as u64, Timestamp::MAX),
+                [&alice, &bob],
+            );
+        }
+        let msgs = writes(&mut outbox);
+        assert_eq!(msgs.len(), 2, "a single subscribe is written to each peer");
+
+        for msg in msgs {
+            let Message::Subscribe(subscribe) = msg else {
+                panic!("unexpected message {msg:?}");
+            };
+            assert_eq!(subscribe.filter, filter, "the latest filter is kept");
+            assert_eq!(subscribe.since, 98, "the widest time range is kept");
+        }
+    }
+
+    #[test]
+    fn test_dedup_announcements() {
+        let mut outbox = Outbox::default();
+        let mut rng = fastrand::Rng::new();
+        let peer = session();
+        let signer = MockSigner::default();
+        let ann = Message::inventory(
+            InventoryAnnouncement {
+                inventory: arbitrary::vec::<Id>(2).try_into().unwrap(),
+                timestamp: 42,
+            },
+            &signer,
+        );
+        let ping = Message::Ping(Ping::new(&mut rng));
+
+        outbox.write(&peer, ann.clone());
+        outbox.write(&peer, ann.clone());
+        outbox.write(&peer, ping.clone());
+        outbox.write(&peer, ping.clone());
+        assert_eq!(writes(&mut outbox), vec![ann.clone(), ping.clone(), ping]);
+
+        // Once written, the same announcement can be queued again.
+        outbox.write_all(&peer, [ann.clone(), ann.clone()]);
+        assert_eq!(writes(&mut outbox), vec![ann]);
     }
 }
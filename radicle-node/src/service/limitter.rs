@@ -27,6 +27,22 @@ impl RateLimiter {
             .or_insert_with(|| TokenBucket::new(tokens.capacity(), tokens.rate(), now))
             .take(now)
     }
+
+    /// Remove the buckets of addresses that have been idle long enough for their bucket
+    /// to be full again. These are no different from the buckets of new addresses.
+    pub fn prune(&mut self, now: LocalTime) {
+        self.buckets.retain(|_, bucket| !bucket.is_full(now));
+    }
+
+    /// Number of addresses tracked.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Whether no addresses are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
 }
 
 /// Any type that can be assigned a number of rate-limit tokens.
@@ -54,6 +70,25 @@ impl AsTokens for crate::Link {
     }
 }
 
+/// A fixed token capacity and refill rate.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Number of tokens refilled per second.
+    pub fill_rate: f64,
+    /// Maximum number of tokens, ie. the allowed burst.
+    pub capacity: usize,
+}
+
+impl AsTokens for RateLimit {
+    fn rate(&self) -> f64 {
+        self.fill_rate
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
 #[derive(Debug)]
 pub struct TokenBucket {
     /// Token refill rate per second.
@@ -77,13 +112,21 @@ impl TokenBucket {
     }
 
     fn refill(&mut self, now: LocalTime) {
-        let elapsed = now.duration_since(self.refilled_at);
+        // Nb. If the clock jumped backwards, nothing is refilled until it catches up.
+        let elapsed = now - self.refilled_at;
         let tokens = elapsed.as_secs() as f64 * self.rate;
 
         self.tokens = (self.tokens + tokens).min(self.capacity);
         self.refilled_at = now;
     }
 
+    /// Whether the bucket would be full if refilled now.
+    fn is_full(&self, now: LocalTime) -> bool {
+        let elapsed = now - self.refilled_at;
+
+        self.tokens + elapsed.as_secs() as f64 * self.rate >= self.capacity
+    }
+
     fn take(&mut self, now: LocalTime) -> bool {
         self.refill(now);
 
@@ -176,4 +219,32 @@ mod test {
         assert_eq!(r.limit(addr2.clone(), &t2, LocalTime::from_secs(1)), false);
         assert_eq!(r.limit(addr2.clone(), &t2, LocalTime::from_secs(1)), true);
     }
+
+    #[test]
+    fn test_limitter_prune() {
+        let t = (2, 0.5); // One token every 2 seconds. Two tokens burst.
+        let mut r = RateLimiter::default();
+        let addr1 = HostName::Dns(String::from("seed.radicle.xyz"));
+        let addr2 = HostName::Dns(String::from("seed.radicle.net"));
+
+        assert_eq!(r.limit(addr1.clone(), &t, LocalTime::from_secs(0)), false);
+        assert_eq!(r.limit(addr1.clone(), &t, LocalTime::from_secs(0)), false);
+        assert_eq!(r.limit(addr2.clone(), &t, LocalTime::from_secs(3)), false);
+        assert_eq!(r.len(), 2);
+
+        // Neither bucket is full yet.
+        r.prune(LocalTime::from_secs(3));
+        assert_eq!(r.len(), 2);
+
+        // The first bucket is full again, and is pruned, while the second isn't.
+        r.prune(LocalTime::from_secs(4));
+        assert_eq!(r.len(), 1);
+        assert_eq!(r.limit(addr2.clone(), &t, LocalTime::from_secs(4)), false);
+        assert_eq!(r.limit(addr2.clone(), &t, LocalTime::from_secs(4)), true);
+
+        // A pruned address starts over with a full bucket.
+        assert_eq!(r.limit(addr1.clone(), &t, LocalTime::from_secs(4)), false);
+        assert_eq!(r.limit(addr1.clone(), &t, LocalTime::from_secs(4)), false);
+        assert_eq!(r.limit(addr1.clone(), &t, LocalTime::from_secs(4)), true);
+    }
 }
@@ -1,10 +1,12 @@
 use std::{fmt, io, mem};
 
+use radicle::cob::TypeName;
+
 use crate::crypto;
 use crate::crypto::Unverified;
 use crate::identity::Id;
 use crate::node;
-use crate::node::{Address, Alias};
+use crate::node::{Address, Alias, MessageKind};
 use crate::prelude::BoundedVec;
 use crate::service::filter::Filter;
 use crate::service::{Link, NodeId, Timestamp};
@@ -19,6 +21,8 @@ pub const ADDRESS_LIMIT: usize = 16;
 pub const REF_REMOTE_LIMIT: usize = 512;
 /// Maximum number of inventory which can be announced to other nodes.
 pub const INVENTORY_LIMIT: usize = 2973;
+/// Maximum number of collaborative object types which can be announced to other nodes.
+pub const COB_LIMIT: usize = 16;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 // TODO: We should check the length and charset when deserializing.
@@ -34,9 +38,14 @@ impl fmt::Display for Hostname {
 pub struct Subscribe {
     /// Subscribe to events matching this filter.
     pub filter: Filter,
-    /// Request messages since this time.
+    /// Request messages since this time, inclusive.
+    ///
+    /// Stored announcements are sent oldest first, ordered by announcer among those with
+    /// the same timestamp. To resume an interrupted subscription, subscribe again with the
+    /// timestamp of the last announcement received: announcements with that timestamp are
+    /// sent again, and should be ignored if already processed.
     pub since: Timestamp,
-    /// Request messages until this time.
+    /// Request messages until this time, exclusive.
     pub until: Timestamp,
 }
 
@@ -50,6 +59,55 @@ impl Subscribe {
     }
 }
 
+/// Kinds of announcements that can be requested from a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AnnouncementKinds(u8);
+
+impl AnnouncementKinds {
+    /// Refs announcements.
+    pub const REFS: AnnouncementKinds = AnnouncementKinds(0b00000001);
+    /// Inventory announcements.
+    pub const INVENTORY: AnnouncementKinds = AnnouncementKinds(0b00000010);
+    /// All announcement kinds.
+    pub const ALL: AnnouncementKinds = AnnouncementKinds(0b00000011);
+
+    /// Returns [`AnnouncementKinds`] with the other kinds added.
+    #[must_use]
+    pub fn with(self, other: AnnouncementKinds) -> AnnouncementKinds {
+        Self(self.0 | other.0)
+    }
+
+    /// Check whether the given kinds are included.
+    pub fn has(self, other: AnnouncementKinds) -> bool {
+        (self.0 | other.0) == self.0
+    }
+}
+
+impl From<u8> for AnnouncementKinds {
+    fn from(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<AnnouncementKinds> for u8 {
+    fn from(kinds: AnnouncementKinds) -> Self {
+        kinds.0
+    }
+}
+
+/// Request for the latest announcements a peer has stored about a repository.
+///
+/// Peers supporting [`node::Features::PULL`] answer with the matching announcements they
+/// have in their gossip store, including their own if they seed the repository. Each is
+/// sent as a [`Message::AnnouncementResponse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnouncementRequest {
+    /// Repository the announcements are about.
+    pub rid: Id,
+    /// Kinds of announcements requested.
+    pub kinds: AnnouncementKinds,
+}
+
 /// Node announcing itself to the network.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NodeAnnouncement {
@@ -217,6 +275,18 @@ pub struct InventoryAnnouncement {
     pub timestamp: Timestamp,
 }
 
+/// Node announcing the collaborative object types it replicates and serves.
+/// This should be the whole list every time.
+///
+/// Introduced in protocol version 2. Nodes speaking an older version skip it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CobsAnnouncement {
+    /// Collaborative object types.
+    pub cobs: BoundedVec<TypeName, COB_LIMIT>,
+    /// Time of announcement.
+    pub timestamp: Timestamp,
+}
+
 /// Announcement messages are messages that are relayed between peers.
 #[derive(Clone, PartialEq, Eq)]
 pub enum AnnouncementMessage {
@@ -226,6 +296,8 @@ pub enum AnnouncementMessage {
     Node(NodeAnnouncement),
     /// Refs announcement.
     Refs(RefsAnnouncement),
+    /// Collaborative object types announcement.
+    Cobs(CobsAnnouncement),
 }
 
 impl AnnouncementMessage {
@@ -246,6 +318,7 @@ impl AnnouncementMessage {
             Self::Inventory(InventoryAnnouncement { timestamp, .. }) => *timestamp,
             Self::Refs(RefsAnnouncement { timestamp, .. }) => *timestamp,
             Self::Node(NodeAnnouncement { timestamp, .. }) => *timestamp,
+            Self::Cobs(CobsAnnouncement { timestamp, .. }) => *timestamp,
         }
     }
 }
@@ -268,6 +341,12 @@ impl From<RefsAnnouncement> for AnnouncementMessage {
     }
 }
 
+impl From<CobsAnnouncement> for AnnouncementMessage {
+    fn from(ann: CobsAnnouncement) -> Self {
+        Self::Cobs(ann)
+    }
+}
+
 impl fmt::Debug for AnnouncementMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -292,6 +371,19 @@ impl fmt::Debug for AnnouncementMessage {
                     message.rid, message.timestamp, message.refs
                 )
             }
+            Self::Cobs(message) => {
+                write!(
+                    f,
+                    "Cobs([{}], {})",
+                    message
+                        .cobs
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    message.timestamp
+                )
+            }
         }
     }
 }
@@ -335,6 +427,7 @@ impl Announcement {
         match &self.message {
             AnnouncementMessage::Inventory(_) => true,
             AnnouncementMessage::Node(_) => true,
+            AnnouncementMessage::Cobs(_) => true,
             AnnouncementMessage::Refs(RefsAnnouncement { rid, .. }) => filter.contains(rid),
         }
     }
@@ -361,6 +454,14 @@ pub enum Message {
     /// using [`Message::Subscribe`].
     Announcement(Announcement),
 
+    /// Ask a connected peer to send its stored announcements about a repository.
+    /// Only sent to peers advertizing [`node::Features::PULL`].
+    AnnouncementRequest(AnnouncementRequest),
+
+    /// Announcement sent in answer to an [`AnnouncementRequest`]. It is handled like a
+    /// gossip announcement, but isn't relayed: the peers of the requester didn't ask for it.
+    AnnouncementResponse(Announcement),
+
     /// Ask a connected peer for a Pong.
     ///
     /// Used to check if the remote peer is responsive, or a side-effect free way to keep a
@@ -390,6 +491,22 @@ impl Ord for Message {
 }
 
 impl Message {
+    /// Get the kind of message this is.
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Self::Subscribe(_) => MessageKind::Subscribe,
+            Self::Announcement(ann) | Self::AnnouncementResponse(ann) => match &ann.message {
+                AnnouncementMessage::Node(_) => MessageKind::NodeAnnouncement,
+                AnnouncementMessage::Inventory(_) => MessageKind::InventoryAnnouncement,
+                AnnouncementMessage::Refs(_) => MessageKind::RefsAnnouncement,
+                AnnouncementMessage::Cobs(_) => MessageKind::CobsAnnouncement,
+            },
+            Self::AnnouncementRequest(_) => MessageKind::AnnouncementRequest,
+            Self::Ping(_) => MessageKind::Ping,
+            Self::Pong { .. } => MessageKind::Pong,
+        }
+    }
+
     pub fn announcement(
         node: NodeId,
         message: impl Into<AnnouncementMessage>,
@@ -411,6 +528,10 @@ impl Message {
         AnnouncementMessage::from(message).signed(signer).into()
     }
 
+    pub fn cobs<G: crypto::Signer>(message: CobsAnnouncement, signer: &G) -> Self {
+        AnnouncementMessage::from(message).signed(signer).into()
+    }
+
     pub fn subscribe(filter: Filter, since: Timestamp, until: Timestamp) -> Self {
         Self::Subscribe(Subscribe {
             filter,
@@ -419,6 +540,10 @@ impl Message {
         })
     }
 
+    pub fn announcement_request(rid: Id, kinds: AnnouncementKinds) -> Self {
+        Self::AnnouncementRequest(AnnouncementRequest { rid, kinds })
+    }
+
     pub fn log(&self, level: log::Level, remote: &NodeId, link: Link) {
         if !log::log_enabled!(level) {
             return;
@@ -444,7 +569,17 @@ impl Message {
                         inventory.len()
                     )
                 }
+                AnnouncementMessage::Cobs(CobsAnnouncement { cobs, .. }) => format!(
+                    "{verb} cobs announcement of {node} with {} type(s) {prep} {remote}",
+                    cobs.len()
+                ),
             },
+            Self::AnnouncementRequest(AnnouncementRequest { rid, .. }) => {
+                format!("{verb} announcement request for {rid} {prep} {remote}")
+            }
+            Self::AnnouncementResponse(Announcement { node, .. }) => {
+                format!("{verb} announcement response of {node} {prep} {remote}")
+            }
             Self::Ping { .. } => format!("{verb} ping {prep} {remote}"),
             Self::Pong { .. } => format!("{verb} pong {prep} {remote}"),
             Self::Subscribe(Subscribe { .. }) => {
@@ -500,6 +635,12 @@ impl fmt::Debug for Message {
             Self::Announcement(Announcement { node, message, .. }) => {
                 write!(f, "Announcement({node}, {message:?})")
             }
+            Self::AnnouncementRequest(AnnouncementRequest { rid, kinds }) => {
+                write!(f, "AnnouncementRequest({rid}, {:#04b})", u8::from(*kinds))
+            }
+            Self::AnnouncementResponse(Announcement { node, message, .. }) => {
+                write!(f, "AnnouncementResponse({node}, {message:?})")
+            }
             Self::Ping(Ping { ponglen, zeroes }) => write!(f, "Ping({ponglen}, {zeroes:?})"),
             Self::Pong { zeroes } => write!(f, "Pong({zeroes:?})"),
         }
@@ -0,0 +1,238 @@
+//! Service metrics.
+//!
+//! Counters of what the service did since it started, for operators to scrape through the
+//! control socket. Nothing is recorded when metrics are disabled in the configuration.
+use std::collections::BTreeMap;
+use std::time;
+
+use radicle::node;
+use radicle::node::MessageKind;
+
+/// Why a received announcement was dropped, ie. neither stored nor relayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DropReason {
+    /// The announcement was signed by us.
+    Own,
+    /// The announcement is older than the entries kept in the routing table.
+    Expired,
+    /// The announcement, or a newer one, was already received.
+    Stale,
+    /// The announcement didn't carry any new information.
+    Unchanged,
+    /// The announcement is about a repository we don't track.
+    Untracked,
+    /// The announcement couldn't be processed, due to a local error.
+    Error,
+    /// The announcement was invalid, eg. its signature or timestamp.
+    Invalid,
+}
+
+impl DropReason {
+    /// All drop reasons.
+    pub const ALL: [DropReason; 7] = [
+        Self::Own,
+        Self::Expired,
+        Self::Stale,
+        Self::Unchanged,
+        Self::Untracked,
+        Self::Error,
+        Self::Invalid,
+    ];
+
+    /// Get the drop reason name, as it appears in metric names.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Own => "own",
+            Self::Expired => "expired",
+            Self::Stale => "stale",
+            Self::Unchanged => "unchanged",
+            Self::Untracked => "untracked",
+            Self::Error => "error",
+            Self::Invalid => "invalid",
+        }
+    }
+}
+
+/// Periodic task run when the service wakes up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Task {
+    Idle,
+    Sync,
+    Announce,
+    NodeAnnounce,
+    Prune,
+}
+
+impl Task {
+    /// Get the task name, as it appears in metric names.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Sync => "sync",
+            Self::Announce => "announce",
+            Self::NodeAnnounce => "nodeAnnounce",
+            Self::Prune => "prune",
+        }
+    }
+}
+
+/// Service metrics. Counters are never reset while the service is running.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Whether metrics are recorded.
+    enabled: bool,
+    /// Messages handled, per message kind.
+    messages: BTreeMap<MessageKind, u64>,
+    /// Announcements relayed to other peers.
+    announcements_relayed: u64,
+    /// Announcements dropped, per reason.
+    announcements_dropped: BTreeMap<DropReason, u64>,
+    /// Fetches initiated.
+    fetches_initiated: u64,
+    /// Fetches that succeeded.
+    fetches_succeeded: u64,
+    /// Fetches that failed.
+    fetches_failed: u64,
+    /// Sessions established.
+    sessions_opened: u64,
+    /// Sessions closed, including sessions that failed to be established.
+    sessions_closed: u64,
+    /// Routing table entries inserted.
+    routes_inserted: u64,
+    /// Routing table entries removed, including pruned entries.
+    routes_removed: u64,
+    /// How long each periodic task took, the last time it ran.
+    tasks: BTreeMap<Task, time::Duration>,
+}
+
+impl Metrics {
+    /// Create new metrics. Nothing is recorded unless `enabled` is set.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    /// Start timing a periodic task, if metrics are enabled.
+    pub fn timer(&self) -> Option<time::Instant> {
+        self.enabled.then(time::Instant::now)
+    }
+
+    /// Record a handled message.
+    pub fn message(&mut self, kind: MessageKind) {
+        if self.enabled {
+            *self.messages.entry(kind).or_default() += 1;
+        }
+    }
+
+    /// Record a relayed announcement.
+    pub fn relayed(&mut self) {
+        if self.enabled {
+            self.announcements_relayed += 1;
+        }
+    }
+
+    /// Record a dropped announcement.
+    pub fn dropped(&mut self, reason: DropReason) {
+        if self.enabled {
+            *self.announcements_dropped.entry(reason).or_default() += 1;
+        }
+    }
+
+    /// Record an initiated fetch.
+    pub fn fetch_initiated(&mut self) {
+        if self.enabled {
+            self.fetches_initiated += 1;
+        }
+    }
+
+    /// Record a completed fetch.
+    pub fn fetched(&mut self, success: bool) {
+        if !self.enabled {
+            return;
+        }
+        if success {
+            self.fetches_succeeded += 1;
+        } else {
+            self.fetches_failed += 1;
+        }
+    }
+
+    /// Record an established session.
+    pub fn session_opened(&mut self) {
+        if self.enabled {
+            self.sessions_opened += 1;
+        }
+    }
+
+    /// Record a closed session.
+    pub fn session_closed(&mut self) {
+        if self.enabled {
+            self.sessions_closed += 1;
+        }
+    }
+
+    /// Record routing table entries inserted.
+    pub fn routes_inserted(&mut self, count: usize) {
+        if self.enabled {
+            self.routes_inserted += count as u64;
+        }
+    }
+
+    /// Record routing table entries removed.
+    pub fn routes_removed(&mut self, count: usize) {
+        if self.enabled {
+            self.routes_removed += count as u64;
+        }
+    }
+
+    /// Record how long a periodic task took, given the timer started before it ran.
+    pub fn task(&mut self, task: Task, timer: Option<time::Instant>) {
+        if let Some(started) = timer {
+            self.tasks.insert(task, started.elapsed());
+        }
+    }
+
+    /// Get a snapshot of the metrics, as a flat map. Empty if metrics are disabled.
+    pub fn snapshot(&self) -> node::Metrics {
+        let mut metrics = node::Metrics::new();
+        if !self.enabled {
+            return metrics;
+        }
+        for (kind, count) in &self.messages {
+            metrics.insert(format!("messages.{}", kind.as_str()), *count);
+        }
+        metrics.insert(
+            String::from("announcements.relayed"),
+            self.announcements_relayed,
+        );
+        for reason in DropReason::ALL {
+            metrics.insert(
+                format!("announcements.dropped.{}", reason.as_str()),
+                self.announcements_dropped
+                    .get(&reason)
+                    .copied()
+                    .unwrap_or_default(),
+            );
+        }
+        for (name, value) in [
+            ("fetches.initiated", self.fetches_initiated),
+            ("fetches.succeeded", self.fetches_succeeded),
+            ("fetches.failed", self.fetches_failed),
+            ("sessions.opened", self.sessions_opened),
+            ("sessions.closed", self.sessions_closed),
+            ("routing.inserted", self.routes_inserted),
+            ("routing.removed", self.routes_removed),
+        ] {
+            metrics.insert(String::from(name), value);
+        }
+        for (task, duration) in &self.tasks {
+            metrics.insert(
+                format!("tasks.{}.lastDurationMicros", task.as_str()),
+                duration.as_micros() as u64,
+            );
+        }
+        metrics
+    }
+}
@@ -1,14 +1,20 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 
-use crate::node::config::Limits;
+use crate::node::config::{FetchOrder, Limits};
+use crate::service;
 use crate::service::message;
 use crate::service::message::Message;
-use crate::service::{Address, Id, LocalTime, NodeId, Outbox, Rng};
+use crate::service::{Address, Id, LocalDuration, LocalTime, NodeId, Outbox, Rng};
+use crate::wire;
 use crate::Link;
 
 pub use crate::node::{PingState, State};
 
+/// Factor by which the round-trip time of a session is multiplied, to get the time
+/// the peer has to answer a ping, when that is longer than the configured timeout.
+pub const STALE_RTT_FACTOR: u128 = 10;
+
 /// Return value of [`Session::fetch`].
 #[derive(Debug)]
 pub enum FetchResult {
@@ -22,6 +28,17 @@ pub enum FetchResult {
     NotConnected,
 }
 
+/// A fetch waiting in a session's queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedFetch {
+    /// Repository to fetch.
+    pub rid: Id,
+    /// Time at which the fetch was queued.
+    pub since: LocalTime,
+    /// How long the fetch is expected to take, if known.
+    pub estimate: Option<LocalDuration>,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     /// The remote peer sent an invalid announcement timestamp,
@@ -66,13 +83,38 @@ pub struct Session {
     pub persistent: bool,
     /// Peer connection state.
     pub state: State,
+    /// Protocol version spoken by the peer, as found in the frames it sends us.
+    /// Unknown until we receive a frame from the peer.
+    pub version: Option<u8>,
     /// Peer subscription.
     pub subscribe: Option<message::Subscribe>,
-    /// Last time a message was received from the peer.
-    pub last_active: LocalTime,
+    /// Announcements matching the peer subscription that are yet to be sent, oldest first.
+    pub backlog: VecDeque<message::Announcement>,
+    /// Last time the peer responded to something we asked of it, eg. a ping.
+    /// This is what tells us whether the peer is still responsive.
+    pub last_protocol_response_at: LocalTime,
     /// Fetch queue.
-    pub queue: VecDeque<Id>,
+    pub queue: VecDeque<QueuedFetch>,
+    /// Gossip bytes sent to the peer.
+    pub sent: u64,
+    /// Gossip bytes received from the peer.
+    pub received: u64,
+    /// Round-trip time to the peer, as a moving average of the time it takes to answer
+    /// our pings. Unknown until the peer answers a ping.
+    pub rtt: Option<LocalDuration>,
+    /// Whether the peer was disconnected by the user. Cleared when we reconnect.
+    pub user_disconnected: bool,
+    /// Time until which we don't try to reconnect, after giving up on a persistent peer.
+    /// Cleared when we connect.
+    pub gave_up_until: Option<LocalTime>,
 
+    /// Time at which the last ping was sent.
+    ping_sent_at: LocalTime,
+    /// Start time of the fetches in progress with this peer.
+    started: HashMap<Id, LocalTime>,
+    /// Number of data transfers (fetches or uploads) currently in progress
+    /// with this peer. Set and cleared around worker tasks.
+    transfers: usize,
     /// Connection attempts. For persistent peers, Tracks
     /// how many times we've attempted to connect. We reset this to zero
     /// upon successful connection.
@@ -109,10 +151,20 @@ impl Session {
             addr,
             state: State::Initial,
             link: Link::Outbound,
+            version: None,
             subscribe: None,
+            backlog: VecDeque::default(),
             persistent,
-            last_active: LocalTime::default(),
+            last_protocol_response_at: LocalTime::default(),
             queue: VecDeque::default(),
+            sent: 0,
+            received: 0,
+            rtt: None,
+            user_disconnected: false,
+            gave_up_until: None,
+            ping_sent_at: LocalTime::default(),
+            started: HashMap::default(),
+            transfers: 0,
             attempts: 1,
             rng,
             limits,
@@ -136,10 +188,20 @@ impl Session {
                 fetching: HashSet::default(),
             },
             link: Link::Inbound,
+            version: None,
             subscribe: None,
+            backlog: VecDeque::default(),
             persistent,
-            last_active: LocalTime::default(),
+            last_protocol_response_at: time,
             queue: VecDeque::default(),
+            sent: 0,
+            received: 0,
+            rtt: None,
+            user_disconnected: false,
+            gave_up_until: None,
+            ping_sent_at: LocalTime::default(),
+            started: HashMap::default(),
+            transfers: 0,
             attempts: 0,
             rng,
             limits,
@@ -166,16 +228,90 @@ impl Session {
         self.attempts
     }
 
-    pub fn fetch(&mut self, rid: Id) -> FetchResult {
+    /// Whether a data transfer is currently in progress with this peer.
+    pub fn transfer_active(&self) -> bool {
+        self.transfers > 0
+    }
+
+    /// Mark the start of a data transfer with this peer. Transfers count as the peer
+    /// responding to us, so that it isn't pinged right after one.
+    pub fn transfer_started(&mut self, now: LocalTime) {
+        self.transfers += 1;
+        self.last_protocol_response_at = now;
+    }
+
+    /// Mark the end of a data transfer with this peer.
+    pub fn transfer_finished(&mut self, now: LocalTime) {
+        self.transfers = self.transfers.saturating_sub(1);
+        self.last_protocol_response_at = now;
+    }
+
+    /// Whether this peer should be pinged, given the current time.
+    /// We only ping peers that haven't responded to us in a while, and which
+    /// aren't busy transferring data.
+    pub fn is_ping_due(&self, now: LocalTime, delta: LocalDuration) -> bool {
+        !self.transfer_active() && now - self.last_protocol_response_at >= delta
+    }
+
+    /// How long this peer has to answer our ping, given the configured timeout. Peers on
+    /// slow links get more time, according to their round-trip time.
+    pub fn timeout(&self, base: LocalDuration) -> LocalDuration {
+        match self.rtt {
+            Some(rtt) => base.max(LocalDuration::from_millis(
+                rtt.as_millis() * STALE_RTT_FACTOR,
+            )),
+            None => base,
+        }
+    }
+
+    /// Whether this peer is unresponsive, ie. it hasn't answered our ping in time.
+    pub fn is_unresponsive(&self, now: LocalTime, timeout: LocalDuration) -> bool {
+        if self.transfer_active() {
+            return false;
+        }
+        match &self.state {
+            State::Connected {
+                ping: PingState::AwaitingResponse(_),
+                ..
+            } => now - self.last_protocol_response_at >= self.timeout(timeout),
+            _ => false,
+        }
+    }
+
+    /// Record the peer's answer to our ping, and update its round-trip time.
+    pub fn ponged(&mut self, now: LocalTime) {
+        let sample = now - self.ping_sent_at;
+
+        self.rtt = Some(match self.rtt {
+            // Nb. Like TCP, we give new samples a weight of 1/8.
+            Some(rtt) => LocalDuration::from_millis((rtt.as_millis() * 7 + sample.as_millis()) / 8),
+            None => sample,
+        });
+        self.last_protocol_response_at = now;
+    }
+
+    /// Fetch the given repository, or queue the fetch if the maximum number of concurrent
+    /// fetches is reached. The estimate is used to order queued fetches.
+    pub fn fetch(
+        &mut self,
+        rid: Id,
+        now: LocalTime,
+        estimate: Option<LocalDuration>,
+    ) -> FetchResult {
         if let State::Connected { fetching, .. } = &mut self.state {
-            if fetching.contains(&rid) || self.queue.contains(&rid) {
+            if fetching.contains(&rid) || self.queue.iter().any(|q| q.rid == rid) {
                 return FetchResult::AlreadyFetching;
             }
             if fetching.len() >= self.limits.fetch_concurrency {
-                self.queue.push_back(rid);
+                self.queue.push_back(QueuedFetch {
+                    rid,
+                    since: now,
+                    estimate,
+                });
                 return FetchResult::Queued;
             }
             fetching.insert(rid);
+            self.started.insert(rid, now);
 
             FetchResult::Ready
         } else {
@@ -183,17 +319,57 @@ impl Session {
         }
     }
 
-    pub fn fetched(&mut self, rid: Id) -> Option<Id> {
+    /// Mark the fetch of the given repository as done.
+    /// Returns how long the fetch took, if it was in progress.
+    pub fn fetched(&mut self, rid: Id, now: LocalTime) -> Option<LocalDuration> {
         if let State::Connected { fetching, .. } = &mut self.state {
             if !fetching.remove(&rid) {
                 log::error!(target: "service", "Fetched unknown repository {rid}");
             }
-            // Dequeue the next fetch, if any.
-            if let Some(rid) = self.queue.pop_front() {
-                return Some(rid);
-            }
         }
-        None
+        self.started.remove(&rid).map(|since| now - since)
+    }
+
+    /// Remove the next fetch to carry out from the queue, if any.
+    pub fn dequeue(&mut self, now: LocalTime) -> Option<Id> {
+        if !self.is_connected() {
+            return None;
+        }
+        let next = self.queued(now).first()?.rid;
+        self.queue.retain(|q| q.rid != next);
+
+        Some(next)
+    }
+
+    /// Queued fetches, in the order they will be carried out.
+    pub fn queued(&self, now: LocalTime) -> Vec<&QueuedFetch> {
+        let mut queued = self.queue.iter().collect::<Vec<_>>();
+
+        if self.limits.fetch_order == FetchOrder::Fair {
+            let max_wait = self.limits.fetch_max_wait;
+
+            // Fetches with no estimate are assumed to take as long as the average queued
+            // fetch, so that they are neither stuck behind every other fetch, nor put ahead
+            // of the small ones.
+            let known = queued.iter().filter_map(|q| q.estimate).collect::<Vec<_>>();
+            let default = if known.is_empty() {
+                LocalDuration::from_secs(0)
+            } else {
+                let total = known.iter().map(|e| e.as_millis()).sum::<u128>();
+                LocalDuration::from_millis(total / known.len() as u128)
+            };
+
+            // Fetches that waited for too long go first, then the shortest estimated ones.
+            // The sort is stable, so ties are carried out in the order they were queued.
+            queued.sort_by_key(|q| {
+                if now - q.since >= max_wait {
+                    (0, LocalDuration::from_secs(0))
+                } else {
+                    (1, q.estimate.unwrap_or(default))
+                }
+            });
+        }
+        queued
     }
 
     pub fn to_attempted(&mut self) {
@@ -207,6 +383,7 @@ impl Session {
 
     pub fn to_connected(&mut self, since: LocalTime) {
         self.attempts = 0;
+        self.gave_up_until = None;
 
         let State::Attempted = &self.state else {
             panic!("Session::to_connected: can only transition to 'connected' state from 'attempted' state");
@@ -216,14 +393,58 @@ impl Session {
             ping: PingState::default(),
             fetching: HashSet::default(),
         };
+        self.last_protocol_response_at = since;
+    }
+
+    /// Queue announcements to be sent to the peer, keeping the backlog sorted by
+    /// timestamp, then announcer.
+    pub fn queue_backlog(&mut self, anns: impl IntoIterator<Item = message::Announcement>) {
+        self.backlog.extend(anns);
+        self.backlog
+            .make_contiguous()
+            .sort_by_key(|ann| (ann.timestamp(), ann.node));
+    }
+
+    /// Take up to `max` announcements from the front of the backlog.
+    pub fn next_backlog(&mut self, max: usize) -> Vec<message::Announcement> {
+        let n = max.min(self.backlog.len());
+        self.backlog.drain(..n).collect()
+    }
+
+    /// Whether there are announcements left to send from the backlog.
+    pub fn has_backlog(&self) -> bool {
+        !self.backlog.is_empty()
     }
 
     /// Move the session state to "disconnected". Returns any pending RID
     /// that was requested.
     pub fn to_disconnected(&mut self, since: LocalTime, retry_at: LocalTime) {
+        self.transfers = 0;
+        self.started.clear();
+        self.backlog.clear();
         self.state = State::Disconnected { since, retry_at };
     }
 
+    /// Move the times of this session back by the given duration, after the clock
+    /// jumped backwards.
+    pub fn rewind(&mut self, by: LocalDuration) {
+        let rewind = |t: &mut LocalTime| *t = service::rewind(*t, by);
+
+        rewind(&mut self.last_protocol_response_at);
+        rewind(&mut self.ping_sent_at);
+        self.started.values_mut().for_each(rewind);
+        self.queue.iter_mut().for_each(|q| rewind(&mut q.since));
+
+        match &mut self.state {
+            State::Connected { since, .. } => rewind(since),
+            State::Disconnected { since, retry_at } => {
+                rewind(since);
+                rewind(retry_at);
+            }
+            State::Initial | State::Attempted => {}
+        }
+    }
+
     /// Return to initial state from disconnected state. This state transition
     /// happens when we attempt to re-connect to a disconnected peer.
     pub fn to_initial(&mut self) {
@@ -232,6 +453,7 @@ impl Session {
             "Can only transition to 'initial' state from 'disconnected' state"
         );
         self.state = State::Initial;
+        self.user_disconnected = false;
     }
 
     pub fn fetching(&self) -> HashSet<Id> {
@@ -242,10 +464,32 @@ impl Session {
         }
     }
 
-    pub fn ping(&mut self, reactor: &mut Outbox) -> Result<(), Error> {
+    /// Check whether the given repository is being fetched from this peer, or is queued
+    /// to be.
+    pub fn is_fetching(&self, rid: &Id) -> bool {
+        let fetching = match &self.state {
+            State::Connected { fetching, .. } => fetching.contains(rid),
+            _ => false,
+        };
+        fetching || self.queue.iter().any(|q| q.rid == *rid)
+    }
+
+    /// Check whether the peer speaks the given protocol version, or a newer one.
+    pub fn supports(&self, version: wire::Version) -> bool {
+        self.version.map_or(false, |v| v >= version.number())
+    }
+
+    pub fn ping(&mut self, now: LocalTime, reactor: &mut Outbox) -> Result<(), Error> {
+        let supported = self.supports(wire::PROTOCOL_VERSION);
+
         if let State::Connected { ping, .. } = &mut self.state {
-            let msg = message::Ping::new(&mut self.rng);
+            let mut msg = message::Ping::new(&mut self.rng);
+            // Only peers speaking the current protocol version are asked for a non-empty pong.
+            if !supported {
+                msg.ponglen = 0;
+            }
             *ping = PingState::AwaitingResponse(msg.ponglen);
+            self.ping_sent_at = now;
 
             reactor.write(self, Message::Ping(msg));
         }
@@ -15,7 +15,9 @@ use crate::service::NodeId;
 pub use crate::node::tracking::store;
 pub use crate::node::tracking::store::Config as Store;
 pub use crate::node::tracking::store::Error;
-pub use crate::node::tracking::{Alias, Node, Policy, Repo, Scope};
+pub use crate::node::tracking::{
+    Alias, ImportSummary, Node, Origin, Policies, Policy, RefFilter, Repo, Scope,
+};
 
 #[derive(Debug, Error)]
 pub enum NamespacesError {
@@ -104,6 +106,9 @@ impl<T> Config<T> {
             id: *id,
             scope: self.scope,
             policy: self.policy,
+            fetch_limit: None,
+            origin: Origin::default(),
+            ref_filter: RefFilter::default(),
         }))
     }
 
@@ -117,37 +122,88 @@ impl<T> Config<T> {
             .repo_policy(rid)
             .map_err(|err| FailedPolicy { rid: *rid, err })?;
         match entry.policy {
-            Policy::Block => {
+            Policy::Block | Policy::Watch => {
                 error!(target: "service", "Attempted to fetch untracked repo {rid}");
                 Err(NamespacesError::BlockedPolicy { rid: *rid })
             }
-            Policy::Track => match entry.scope {
-                Scope::All => Ok(Namespaces::All),
-                Scope::Trusted => {
-                    let nodes = self
-                        .node_policies()
-                        .map_err(|err| FailedNodes { rid: *rid, err })?;
-                    let mut trusted: HashSet<_> = nodes
-                        .filter_map(|node| (node.policy == Policy::Track).then_some(node.id))
-                        .collect();
-
-                    if let Ok(repo) = storage.repository(*rid) {
-                        let delegates = repo
-                            .delegates()
+            Policy::Track => self.namespaces_for_scope(storage, rid, entry.scope),
+        }
+    }
+
+    /// Get the namespaces to fetch for a repository tracked with the given scope,
+    /// regardless of the repository's current policy.
+    pub fn namespaces_for_scope<S>(
+        &self,
+        storage: &S,
+        rid: &Id,
+        scope: Scope,
+    ) -> Result<Namespaces, NamespacesError>
+    where
+        S: ReadStorage,
+    {
+        use NamespacesError::*;
+
+        match scope {
+            Scope::All => Ok(Namespaces::All),
+            Scope::Trusted => {
+                let nodes = self
+                    .node_policies()
+                    .map_err(|err| FailedNodes { rid: *rid, err })?;
+                let mut trusted: HashSet<_> = nodes
+                    .filter_map(|node| (node.policy == Policy::Track).then_some(node.id))
+                    .collect();
+
+                if let Ok(repo) = storage.repository(*rid) {
+                    let delegates = repo
+                        .delegates()
+                        .map_err(|err| FailedDelegates { rid: *rid, err })?
+                        .map(PublicKey::from);
+                    trusted.extend(delegates);
+                };
+                if trusted.is_empty() {
+                    // Nb. returning All here because the
+                    // fetching logic will correctly determine
+                    // trusted and delegate remotes.
+                    Ok(Namespaces::All)
+                } else {
+                    Ok(Namespaces::Trusted(trusted))
+                }
+            }
+        }
+    }
+
+    /// Get the namespaces to fetch for a minimal fetch of a repository, ie. only those of
+    /// its delegates, regardless of the scope. If we don't have the repository yet, its
+    /// delegates are only known once its identity branch is fetched, and the set is empty.
+    pub fn delegate_namespaces_for<S>(
+        &self,
+        storage: &S,
+        rid: &Id,
+    ) -> Result<Namespaces, NamespacesError>
+    where
+        S: ReadStorage,
+    {
+        use NamespacesError::*;
+
+        let entry = self
+            .repo_policy(rid)
+            .map_err(|err| FailedPolicy { rid: *rid, err })?;
+        match entry.policy {
+            Policy::Block | Policy::Watch => {
+                error!(target: "service", "Attempted to fetch untracked repo {rid}");
+                Err(NamespacesError::BlockedPolicy { rid: *rid })
+            }
+            Policy::Track => {
+                let mut delegates = HashSet::new();
+                if let Ok(repo) = storage.repository(*rid) {
+                    delegates.extend(
+                        repo.delegates()
                             .map_err(|err| FailedDelegates { rid: *rid, err })?
-                            .map(PublicKey::from);
-                        trusted.extend(delegates);
-                    };
-                    if trusted.is_empty() {
-                        // Nb. returning All here because the
-                        // fetching logic will correctly determine
-                        // trusted and delegate remotes.
-                        Ok(Namespaces::All)
-                    } else {
-                        Ok(Namespaces::Trusted(trusted))
-                    }
+                            .map(PublicKey::from),
+                    );
                 }
-            },
+                Ok(Namespaces::Trusted(delegates))
+            }
         }
     }
 }
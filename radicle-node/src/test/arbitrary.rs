@@ -1,13 +1,14 @@
 use bloomy::BloomFilter;
 use qcheck::Arbitrary;
+use radicle::cob::TypeName;
 
 use crate::crypto;
 use crate::node::Alias;
 use crate::prelude::{BoundedVec, Id, NodeId, Timestamp};
 use crate::service::filter::{Filter, FILTER_SIZE_L, FILTER_SIZE_M, FILTER_SIZE_S};
 use crate::service::message::{
-    Announcement, InventoryAnnouncement, Message, NodeAnnouncement, Ping, RefsAnnouncement,
-    Subscribe, ZeroBytes,
+    Announcement, AnnouncementKinds, AnnouncementRequest, CobsAnnouncement, InventoryAnnouncement,
+    Message, NodeAnnouncement, Ping, RefsAnnouncement, Subscribe, ZeroBytes,
 };
 use crate::wire::MessageType;
 
@@ -34,7 +35,10 @@ impl Arbitrary for Message {
                 MessageType::InventoryAnnouncement,
                 MessageType::NodeAnnouncement,
                 MessageType::RefsAnnouncement,
+                MessageType::CobsAnnouncement,
                 MessageType::Subscribe,
+                MessageType::AnnouncementRequest,
+                MessageType::AnnouncementResponse,
                 MessageType::Ping,
                 MessageType::Pong,
             ])
@@ -81,11 +85,39 @@ impl Arbitrary for Message {
                 }
                 .into()
             }
+            MessageType::CobsAnnouncement => {
+                let names = ["xyz.radicle.issue", "xyz.radicle.patch", "com.example.task"];
+                let cobs = names
+                    .iter()
+                    .filter(|_| bool::arbitrary(g))
+                    .map(|n| n.parse::<TypeName>().unwrap())
+                    .collect::<Vec<_>>();
+
+                Announcement {
+                    node: NodeId::arbitrary(g),
+                    message: CobsAnnouncement {
+                        cobs: cobs.try_into().expect("size within bounds"),
+                        timestamp: Timestamp::arbitrary(g),
+                    }
+                    .into(),
+                    signature: crypto::Signature::from(<[u8; 64]>::arbitrary(g)),
+                }
+                .into()
+            }
             MessageType::Subscribe => Self::Subscribe(Subscribe {
                 filter: Filter::arbitrary(g),
                 since: Timestamp::arbitrary(g),
                 until: Timestamp::arbitrary(g),
             }),
+            MessageType::AnnouncementRequest => Self::AnnouncementRequest(AnnouncementRequest {
+                rid: Id::arbitrary(g),
+                kinds: AnnouncementKinds::from(u8::arbitrary(g)),
+            }),
+            MessageType::AnnouncementResponse => loop {
+                if let Self::Announcement(ann) = Self::arbitrary(g) {
+                    break Self::AnnouncementResponse(ann);
+                }
+            },
             MessageType::Ping => {
                 let mut rng = fastrand::Rng::with_seed(u64::arbitrary(g));
 
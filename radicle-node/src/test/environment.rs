@@ -2,8 +2,9 @@ use std::io::BufRead as _;
 use std::mem::ManuallyDrop;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     env, fs, io, iter, net, process, thread, time,
     time::Duration,
 };
@@ -19,6 +20,7 @@ use radicle::git;
 use radicle::git::refname;
 use radicle::identity::Id;
 use radicle::node::address::Book;
+use radicle::node::config;
 use radicle::node::routing;
 use radicle::node::routing::Store;
 use radicle::node::tracking::store as tracking;
@@ -39,10 +41,49 @@ use crate::{runtime, runtime::Handle, service, Runtime};
 
 pub use service::Config;
 
+/// How long to wait for the nodes of a partition to disconnect, or reconnect once healed.
+const PARTITION_TIMEOUT: time::Duration = time::Duration::from_secs(6);
+
+/// Nodes with which no sessions are established: dials to them are refused, and sessions
+/// with them are closed as soon as they are established. Used to simulate network
+/// partitions in tests.
+#[derive(Debug, Default, Clone)]
+pub struct Blocklist(Arc<RwLock<HashSet<NodeId>>>);
+
+impl Blocklist {
+    /// Block the given node.
+    pub fn block(&self, nid: NodeId) {
+        self.0
+            .write()
+            .expect("Blocklist::block: lock is poisoned")
+            .insert(nid);
+    }
+
+    /// Unblock all nodes.
+    pub fn clear(&self) {
+        self.0
+            .write()
+            .expect("Blocklist::clear: lock is poisoned")
+            .clear();
+    }
+
+    /// Check whether the given node is blocked.
+    pub fn contains(&self, nid: &NodeId) -> bool {
+        self.0
+            .read()
+            .expect("Blocklist::contains: lock is poisoned")
+            .contains(nid)
+    }
+}
+
 /// Test environment.
 pub struct Environment {
     tempdir: tempfile::TempDir,
     users: usize,
+    /// Blocklists of the nodes on either side of a partition.
+    partitioned: Vec<Blocklist>,
+    /// Connections severed by a partition, to re-establish once healed.
+    severed: Vec<(Handle, NodeId, net::SocketAddr)>,
 }
 
 impl Default for Environment {
@@ -50,6 +91,8 @@ impl Default for Environment {
         Self {
             tempdir: tempfile::tempdir().unwrap(),
             users: 0,
+            partitioned: Vec::new(),
+            severed: Vec::new(),
         }
     }
 }
@@ -102,6 +145,89 @@ impl Environment {
         }
     }
 
+    /// Partition the network in two: all sessions between the two groups of nodes are
+    /// closed, and no new sessions are established between them until [`Environment::heal`]
+    /// is called. Nodes within a group stay connected.
+    #[track_caller]
+    pub fn partition<'a, G: Signer + cyphernet::Ecdh + 'static>(
+        &mut self,
+        a: impl IntoIterator<Item = &'a NodeHandle<G>>,
+        b: impl IntoIterator<Item = &'a NodeHandle<G>>,
+    ) {
+        let a = a.into_iter().collect::<Vec<_>>();
+        let b = b.into_iter().collect::<Vec<_>>();
+
+        for x in &a {
+            for y in &b {
+                x.blocklist.block(y.id);
+                y.blocklist.block(x.id);
+            }
+        }
+        for x in &a {
+            let sessions = x.handle.sessions().unwrap();
+
+            for y in &b {
+                if sessions
+                    .iter()
+                    .any(|s| s.nid == y.id && s.state.is_connected())
+                {
+                    self.severed.push(((*x.handle).clone(), y.id, y.addr));
+                }
+                x.handle.command(service::Command::Disconnect(y.id)).ok();
+                y.handle.command(service::Command::Disconnect(x.id)).ok();
+            }
+        }
+        self.partitioned
+            .extend(a.iter().chain(b.iter()).map(|n| n.blocklist.clone()));
+
+        // Wait for all sessions across the partition to be closed.
+        let start = time::Instant::now();
+        for x in a.iter().chain(b.iter()) {
+            let others = if a.iter().any(|n| n.id == x.id) {
+                &b
+            } else {
+                &a
+            };
+
+            while x
+                .handle
+                .sessions()
+                .unwrap()
+                .iter()
+                .any(|s| s.state.is_connected() && others.iter().any(|n| n.id == s.nid))
+            {
+                assert!(
+                    start.elapsed() < PARTITION_TIMEOUT,
+                    "Environment::partition: {} is still connected across the partition",
+                    x.id
+                );
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+
+    /// Heal all partitions: nodes can connect to each other again, and the connections
+    /// that were severed by the partitions are re-established.
+    #[track_caller]
+    pub fn heal(&mut self) {
+        for blocklist in self.partitioned.drain(..) {
+            blocklist.clear();
+        }
+        for (mut handle, nid, addr) in self.severed.drain(..) {
+            let events = handle.events();
+
+            handle
+                .connect(nid, addr.into(), ConnectOptions::default())
+                .ok();
+            events
+                .wait(
+                    |e| matches!(e, Event::PeerConnected { nid: n } if n == &nid).then_some(()),
+                    PARTITION_TIMEOUT,
+                )
+                .unwrap();
+        }
+    }
+
     /// Create a new profile in this environment.
     /// This should be used when a running node is not required.
     pub fn profile(&mut self, alias: &str) -> Profile {
@@ -152,8 +278,12 @@ pub struct NodeHandle<G: Signer + cyphernet::Ecdh + 'static> {
     pub signer: G,
     pub home: Home,
     pub addr: net::SocketAddr,
+    /// Address of the read-only HTTP interface, if enabled.
+    pub http: Option<net::SocketAddr>,
     pub thread: ManuallyDrop<thread::JoinHandle<Result<(), runtime::Error>>>,
     pub handle: ManuallyDrop<Handle>,
+    /// Nodes this node doesn't establish sessions with.
+    pub blocklist: Blocklist,
 }
 
 impl<G: Signer + cyphernet::Ecdh + 'static> Drop for NodeHandle<G> {
@@ -266,6 +396,36 @@ impl<G: Signer + cyphernet::Ecdh> NodeHandle<G> {
         }
     }
 
+    /// Wait until this node has the latest refs of a remote node, in that node's namespace.
+    #[track_caller]
+    pub fn has_refs_of(&self, rid: &Id, remote: &NodeHandle<G>) {
+        log::debug!(target: "test", "Waiting for {} to have the refs of {rid}/{}", self.id, remote.id);
+        let events = self.handle.events();
+        let latest = remote
+            .storage
+            .repository(*rid)
+            .unwrap()
+            .remote(&remote.id)
+            .unwrap();
+
+        loop {
+            if let Ok(repo) = self.storage.repository(*rid) {
+                if repo
+                    .remote(&remote.id)
+                    .map_or(false, |r| r.refs.signature == latest.refs.signature)
+                {
+                    break;
+                }
+            }
+            events
+                .wait(
+                    |e| matches!(e, Event::RefsFetched { .. }).then_some(()),
+                    time::Duration::from_secs(6),
+                )
+                .unwrap();
+        }
+    }
+
     /// Run a `rad` CLI command.
     pub fn rad<P: AsRef<Path>>(&self, cmd: &str, args: &[&str], cwd: P) -> io::Result<()> {
         let cwd = cwd.as_ref();
@@ -321,6 +481,32 @@ impl<G: Signer + cyphernet::Ecdh> NodeHandle<G> {
     }
 }
 
+impl<G: cyphernet::Ecdh<Pk = NodeId> + Signer + Clone> NodeHandle<G> {
+    /// Shut the node down, and spawn it again with the given configuration. The node's
+    /// home directory, and hence its storage and databases, are kept.
+    pub fn restart(self, config: Config) -> NodeHandle<G> {
+        self.shutdown(config).spawn()
+    }
+
+    /// Shut the node down. The returned node can be spawned again with the given
+    /// configuration, and the same home directory.
+    pub fn shutdown(self, config: Config) -> Node<G> {
+        let node = Node {
+            id: self.id,
+            home: self.home.clone(),
+            signer: self.signer.clone(),
+            storage: self.storage.clone(),
+            config,
+            addresses: Book::memory().unwrap(),
+            routing: routing::Table::memory().unwrap(),
+            tracking: tracking::Config::<tracking::Write>::memory().unwrap(),
+        };
+        drop(self);
+
+        node
+    }
+}
+
 impl Node<MockSigner> {
     /// Create a new node.
     pub fn init(base: &Path, config: Config) -> Self {
@@ -366,15 +552,24 @@ impl<G: cyphernet::Ecdh<Pk = NodeId> + Signer + Clone> Node<G> {
             self.home.clone(),
             self.config,
             listen,
+            config::Validation::default(),
             proxy,
             daemon,
             signals,
             self.signer.clone(),
         )
         .unwrap();
-        let addr = *rt.local_addrs.first().unwrap();
+        let addr = {
+            // We listen on all interfaces, but peers dial us on the loopback interface,
+            // since unspecified addresses can't be dialed.
+            let mut addr = *rt.local_addrs.first().unwrap();
+            addr.set_ip(net::Ipv4Addr::LOCALHOST.into());
+            addr
+        };
+        let http = rt.http.as_ref().map(|l| l.local_addr().unwrap());
         let id = *self.signer.public_key();
         let handle = ManuallyDrop::new(rt.handle.clone());
+        let blocklist = rt.blocklist.clone();
         let thread = ManuallyDrop::new(runtime::thread::spawn(&id, "runtime", move || rt.run()));
 
         NodeHandle {
@@ -383,8 +578,10 @@ impl<G: cyphernet::Ecdh<Pk = NodeId> + Signer + Clone> Node<G> {
             signer: self.signer,
             home: self.home,
             addr,
+            http,
             handle,
             thread,
+            blocklist,
         }
     }
 
@@ -449,6 +646,22 @@ impl<G: cyphernet::Ecdh<Pk = NodeId> + Signer + Clone> Node<G> {
 pub fn converge<'a, G: Signer + cyphernet::Ecdh + 'static>(
     nodes: impl IntoIterator<Item = &'a NodeHandle<G>>,
 ) -> BTreeSet<(Id, NodeId)> {
+    converge_until(nodes, None).unwrap()
+}
+
+/// Waits for the nodes to converge in their routing tables, like [`converge`], but only
+/// until the timeout has elapsed. Returns the nodes that haven't converged on timeout.
+pub fn converge_within<'a, G: Signer + cyphernet::Ecdh + 'static>(
+    nodes: impl IntoIterator<Item = &'a NodeHandle<G>>,
+    timeout: time::Duration,
+) -> Result<BTreeSet<(Id, NodeId)>, BTreeSet<NodeId>> {
+    converge_until(nodes, Some(time::Instant::now() + timeout))
+}
+
+fn converge_until<'a, G: Signer + cyphernet::Ecdh + 'static>(
+    nodes: impl IntoIterator<Item = &'a NodeHandle<G>>,
+    deadline: Option<time::Instant>,
+) -> Result<BTreeSet<(Id, NodeId)>, BTreeSet<NodeId>> {
     let nodes = nodes.into_iter().collect::<Vec<_>>();
 
     let mut all_routes = BTreeSet::<(Id, NodeId)>::new();
@@ -481,7 +694,13 @@ pub fn converge<'a, G: Signer + cyphernet::Ecdh + 'static>(
             }
             true
         });
+        if remaining.is_empty() {
+            break;
+        }
+        if deadline.map_or(false, |d| time::Instant::now() >= d) {
+            return Err(remaining.into_keys().collect());
+        }
         thread::sleep(Duration::from_millis(100));
     }
-    all_routes
+    Ok(all_routes)
 }
@@ -4,15 +4,22 @@ use std::sync::{Arc, Mutex};
 use std::{io, time};
 
 use crate::identity::Id;
-use crate::node::{Alias, ConnectOptions, ConnectResult, Event, FetchResult, Seeds};
+use crate::node::{
+    activity, config, events, Alias, CobUpdate, CobUpdated, ConnectOptions, ConnectResult, Event,
+    Explanation, FetchRecord, FetchResult, GossipStats, Inspection, Metrics, Recovery, Seeds,
+    SyncAllOptions, SyncProgress, Timestamp, TrackDryRun, TrackFetch, Tracked,
+};
 use crate::runtime::HandleError;
 use crate::service::tracking;
 use crate::service::NodeId;
+use crate::storage::git::stats::RepoStats;
+use crate::storage::Finding;
 
 #[derive(Default, Clone)]
 pub struct Handle {
     pub updates: Arc<Mutex<Vec<Id>>>,
     pub tracking_repos: Arc<Mutex<HashSet<Id>>>,
+    pub watching_repos: Arc<Mutex<HashSet<Id>>>,
     pub tracking_nodes: Arc<Mutex<HashSet<NodeId>>>,
 }
 
@@ -45,17 +52,70 @@ impl radicle::node::Handle for Handle {
         Ok(FetchResult::Success {
             updated: vec![],
             namespaces: HashSet::new(),
+            fetched: vec![],
+            skipped: vec![],
         })
     }
 
+    fn fetch_minimal(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Self::Error> {
+        self.fetch(id, from)
+    }
+
     fn track_repo(&mut self, id: Id, _scope: tracking::Scope) -> Result<bool, Self::Error> {
         Ok(self.tracking_repos.lock().unwrap().insert(id))
     }
 
+    fn track_repo_origin(
+        &mut self,
+        id: Id,
+        _scope: tracking::Scope,
+        _origin: tracking::Origin,
+    ) -> Result<bool, Self::Error> {
+        Ok(self.tracking_repos.lock().unwrap().insert(id))
+    }
+
+    fn track_repo_filter(
+        &mut self,
+        id: Id,
+        _scope: tracking::Scope,
+        _filter: tracking::RefFilter,
+    ) -> Result<bool, Self::Error> {
+        Ok(self.tracking_repos.lock().unwrap().insert(id))
+    }
+
+    fn track_repo_fetch(
+        &mut self,
+        id: Id,
+        _scope: tracking::Scope,
+    ) -> Result<Tracked, Self::Error> {
+        let updated = self.tracking_repos.lock().unwrap().insert(id);
+
+        Ok(Tracked {
+            updated,
+            fetch: TrackFetch::NoSeeds,
+        })
+    }
+
+    fn track_repo_dry_run(
+        &mut self,
+        _id: Id,
+        _scope: tracking::Scope,
+    ) -> Result<TrackDryRun, Self::Error> {
+        unimplemented!()
+    }
+
     fn untrack_repo(&mut self, id: Id) -> Result<bool, Self::Error> {
         Ok(self.tracking_repos.lock().unwrap().remove(&id))
     }
 
+    fn watch_repo(&mut self, id: Id) -> Result<bool, Self::Error> {
+        Ok(self.watching_repos.lock().unwrap().insert(id))
+    }
+
+    fn unwatch_repo(&mut self, id: Id) -> Result<bool, Self::Error> {
+        Ok(self.watching_repos.lock().unwrap().remove(&id))
+    }
+
     fn track_node(&mut self, id: NodeId, _alias: Option<Alias>) -> Result<bool, Self::Error> {
         Ok(self.tracking_nodes.lock().unwrap().insert(id))
     }
@@ -67,10 +127,32 @@ impl radicle::node::Handle for Handle {
         Ok(Box::new(std::iter::empty()))
     }
 
+    fn validate(
+        &self,
+        _rid: Option<Id>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Finding, io::Error>>>, Self::Error> {
+        Ok(Box::new(std::iter::empty()))
+    }
+
+    fn repo_size(&self, _rid: Option<Id>, _refresh: bool) -> Result<Vec<RepoStats>, Self::Error> {
+        unimplemented!()
+    }
+
     fn untrack_node(&mut self, id: NodeId) -> Result<bool, Self::Error> {
         Ok(self.tracking_nodes.lock().unwrap().remove(&id))
     }
 
+    fn export_policies(&self) -> Result<tracking::Policies, Self::Error> {
+        unimplemented!()
+    }
+
+    fn import_policies(
+        &mut self,
+        _policies: tracking::Policies,
+    ) -> Result<tracking::ImportSummary, Self::Error> {
+        unimplemented!()
+    }
+
     fn announce_refs(&mut self, id: Id) -> Result<(), Self::Error> {
         self.updates.lock().unwrap().push(id);
 
@@ -85,6 +167,72 @@ impl radicle::node::Handle for Handle {
         unimplemented!()
     }
 
+    fn gossip_stats(&self) -> Result<GossipStats, Self::Error> {
+        unimplemented!()
+    }
+
+    fn metrics(&self) -> Result<Metrics, Self::Error> {
+        unimplemented!()
+    }
+
+    fn recovery(&self) -> Result<Option<Recovery>, Self::Error> {
+        unimplemented!()
+    }
+
+    fn explain(&self, _rid: Id, _nid: NodeId) -> Result<Explanation, Self::Error> {
+        unimplemented!()
+    }
+
+    fn fetch_history(&self, _rid: Id) -> Result<Vec<FetchRecord>, Self::Error> {
+        unimplemented!()
+    }
+
+    fn events_since(&self, _seq: u64) -> Result<events::Page, Self::Error> {
+        unimplemented!()
+    }
+
+    fn activity(
+        &self,
+        _since: Option<Timestamp>,
+        _limit: Option<usize>,
+    ) -> Result<Vec<activity::Entry>, Self::Error> {
+        unimplemented!()
+    }
+
+    fn inspect(&mut self, _rid: Id) -> Result<Inspection, Self::Error> {
+        unimplemented!()
+    }
+
+    fn config(&self) -> Result<config::Config, Self::Error> {
+        unimplemented!()
+    }
+
+    fn set_limit(&mut self, _name: &str, _value: serde_json::Value) -> Result<(), Self::Error> {
+        unimplemented!()
+    }
+
+    fn add_peer(&mut self, _peer: config::PeerEntry) -> Result<bool, Self::Error> {
+        unimplemented!()
+    }
+
+    fn remove_peer(&mut self, _nid: NodeId) -> Result<bool, Self::Error> {
+        unimplemented!()
+    }
+
+    fn sync_all(
+        &mut self,
+        _options: SyncAllOptions,
+    ) -> Result<Box<dyn Iterator<Item = Result<SyncProgress, io::Error>>>, Self::Error> {
+        unimplemented!()
+    }
+
+    fn cob_update(&mut self, update: CobUpdate) -> Result<CobUpdated, Self::Error> {
+        Err(HandleError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("repository {} was not found in storage", update.rid),
+        )))
+    }
+
     fn sessions(&self) -> Result<Self::Sessions, Self::Error> {
         unimplemented!();
     }
@@ -6,8 +6,9 @@ use std::str::FromStr;
 
 use log::*;
 
+use radicle::cob::TypeName;
 use radicle::node::address::Store;
-use radicle::node::{address, Alias, ConnectOptions};
+use radicle::node::{address, events, watermark, Alias, ConnectOptions};
 use radicle::rad;
 use radicle::storage::ReadRepository;
 use radicle::Storage;
@@ -98,6 +99,7 @@ where
 pub struct Config<G: Signer + 'static> {
     pub config: service::Config,
     pub addrs: address::Book,
+    pub tracking: tracking::Store<tracking::store::Write>,
     pub local_time: LocalTime,
     pub policy: Policy,
     pub scope: Scope,
@@ -113,6 +115,7 @@ impl Default for Config<MockSigner> {
         Config {
             config: service::Config::test(Alias::from_str("mocky").unwrap()),
             addrs: address::Book::memory().unwrap(),
+            tracking: tracking::Store::<tracking::store::Write>::memory().unwrap(),
             local_time: LocalTime::now(),
             policy: Policy::default(),
             scope: Scope::default(),
@@ -153,8 +156,7 @@ where
         mut config: Config<G>,
     ) -> Self {
         let routing = routing::Table::memory().unwrap();
-        let tracking = tracking::Store::<tracking::store::Write>::memory().unwrap();
-        let tracking = tracking::Config::new(config.policy, config.scope, tracking);
+        let tracking = tracking::Config::new(config.policy, config.scope, config.tracking);
         let tempdir = tempfile::tempdir().unwrap();
         let id = *config.signer.public_key();
         let ip = ip.into();
@@ -163,7 +165,7 @@ where
         // Make sure the peer address is advertized.
         config.config.external_addresses.push(local_addr.into());
 
-        let announcement = service::gossip::node(&config.config, config.local_time.as_secs());
+        let announcement = service::gossip::node(&config.config, config.local_time.as_millis());
         let emitter: Emitter<Event> = Default::default();
         let service = Service::new(
             config.config,
@@ -172,6 +174,8 @@ where
             storage,
             config.addrs,
             tracking,
+            events::Log::memory().unwrap(),
+            watermark::Table::memory().unwrap(),
             config.signer,
             config.rng.clone(),
             announcement,
@@ -273,6 +277,16 @@ where
         )
     }
 
+    pub fn cobs_announcement(&self, cobs: Vec<TypeName>) -> Message {
+        Message::cobs(
+            CobsAnnouncement {
+                cobs: cobs.try_into().unwrap(),
+                timestamp: self.timestamp(),
+            },
+            self.signer(),
+        )
+    }
+
     pub fn refs_announcement(&self, rid: Id) -> Message {
         let mut refs = BoundedVec::new();
         if let Ok(repo) = self.storage().repository(rid) {
@@ -390,6 +404,7 @@ where
                 rid,
                 remote,
                 namespaces,
+                ..
             } = io
             {
                 Some((rid, remote, namespaces))
@@ -18,10 +18,10 @@ use crate::crypto::Signer;
 use crate::prelude::{Address, Id};
 use crate::service::io::Io;
 use crate::service::{DisconnectReason, Event, Message, NodeId};
+use crate::storage::Namespaces;
 use crate::storage::WriteStorage;
-use crate::storage::{Namespaces, RefUpdate};
 use crate::test::peer::Service;
-use crate::worker::FetchError;
+use crate::worker::{FetchError, Transferred};
 use crate::Link;
 
 /// Minimum latency between peers.
@@ -66,11 +66,7 @@ pub enum Input {
     /// Received a message from a remote peer.
     Received(NodeId, Vec<Message>),
     /// Fetch completed for a node.
-    Fetched(
-        Id,
-        NodeId,
-        Rc<Result<(Vec<RefUpdate>, HashSet<NodeId>), FetchError>>,
-    ),
+    Fetched(Id, NodeId, Rc<Result<Transferred, FetchError>>),
     /// Used to advance the state machine after some wall time has passed.
     Wake,
 }
@@ -422,7 +418,7 @@ impl<S: WriteStorage + 'static, G: Signer> Simulation<S, G> {
                             Err(e) => panic!("Failed to open repository: {e}"),
                         };
                         match &result {
-                            Ok((_, remotes)) => {
+                            Ok((_, remotes, _, _)) => {
                                 radicle::test::fetch(
                                     &repo,
                                     &nid,
@@ -432,7 +428,7 @@ impl<S: WriteStorage + 'static, G: Signer> Simulation<S, G> {
                             }
                             Err(err) => panic!("Error fetching: {err}"),
                         }
-                        p.fetched(rid, nid, result);
+                        p.fetched(rid, nid, result, 0);
                     }
                 }
                 while let Some(o) = p.next() {
@@ -622,10 +618,29 @@ impl<S: WriteStorage + 'static, G: Signer> Simulation<S, G> {
                     );
                 }
             }
+            Io::LsRefs { rid, remote, .. } => {
+                // Nb. Listing refs doesn't affect the state of the service, so we don't
+                // simulate it.
+                log::info!(
+                    target: "sim",
+                    "{:05} {} ~> {} ({}): LsRefs outgoing (ignored)",
+                    self.elapsed().as_millis(), node, remote, rid
+                );
+            }
+            Io::FetchIdentity { rid, remote } => {
+                // Nb. Fetching an identity doesn't affect the state of the service, so we
+                // don't simulate it.
+                log::info!(
+                    target: "sim",
+                    "{:05} {} ~> {} ({}): FetchIdentity outgoing (ignored)",
+                    self.elapsed().as_millis(), node, remote, rid
+                );
+            }
             Io::Fetch {
                 rid,
                 remote,
                 namespaces,
+                ..
             } => {
                 log::info!(
                     target: "sim",
@@ -661,6 +676,8 @@ impl<S: WriteStorage + 'static, G: Signer> Simulation<S, G> {
                                         Namespaces::Trusted(hs) => hs,
                                         Namespaces::All => HashSet::new(),
                                     },
+                                    vec![],
+                                    vec![],
                                 ))),
                             ),
                         },
@@ -1,13 +1,19 @@
 mod e2e;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::default::*;
 use std::io;
+use std::net;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time;
 
 use crossbeam_channel as chan;
+use cyphernet::addr::Host as _;
 use netservices::Direction as Link;
+use radicle::cob::TypeName;
+use radicle::node::address::Store as _;
 use radicle::node::routing::Store as _;
 use radicle::node::ConnectOptions;
 use radicle::storage::ReadRepository;
@@ -26,7 +32,7 @@ use crate::service::ServiceState as _;
 use crate::service::*;
 use crate::storage::git::transport::{local, remote};
 use crate::storage::git::Storage;
-use crate::storage::ReadStorage;
+use crate::storage::{Namespaces, ReadStorage};
 use crate::test::arbitrary;
 use crate::test::assert_matches;
 use crate::test::fixtures;
@@ -37,6 +43,7 @@ use crate::test::peer::Peer;
 use crate::test::simulator;
 use crate::test::simulator::{Peer as _, Simulation};
 use crate::test::storage::MockStorage;
+use crate::wire;
 use crate::wire::Decode;
 use crate::wire::Encode;
 use crate::LocalTime;
@@ -106,13 +113,346 @@ fn test_disconnecting_unresponsive_peer() {
 
     alice.connect_to(&bob);
     assert_eq!(1, alice.sessions().connected().count(), "bob connects");
-    alice.elapse(STALE_CONNECTION_TIMEOUT + LocalDuration::from_secs(1));
+    alice.elapse(Limits::default().stale_timeout + LocalDuration::from_secs(1));
     alice
         .outbox()
         .find(|m| matches!(m, &Io::Disconnect(addr, _) if addr == bob.id()))
         .expect("disconnect an unresponsive bob");
 }
 
+#[test]
+fn test_keep_alive_idle_ping() {
+    let mut alice = Peer::new("alice", [8, 8, 8, 8]);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+
+    let mut pings = 0;
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed < Limits::default().stale_timeout * 2 {
+        alice.elapse(IDLE_INTERVAL);
+        elapsed = elapsed + IDLE_INTERVAL;
+
+        // Bob answers every ping we send him.
+        let ping = alice.messages(bob.id()).find_map(|m| match m {
+            Message::Ping(ping) => Some(ping),
+            _ => None,
+        });
+        if let Some(ping) = ping {
+            pings += 1;
+            alice.receive(
+                bob.id(),
+                Message::Pong {
+                    zeroes: ZeroBytes::new(ping.ponglen),
+                },
+            );
+        }
+    }
+    assert!(pings > 1, "alice pings an idle bob periodically");
+    assert!(
+        !alice
+            .outbox()
+            .any(|m| matches!(m, Io::Disconnect(addr, _) if addr == bob.id())),
+        "bob answers our pings and is kept connected"
+    );
+}
+
+#[test]
+fn test_ping_protocol_version() {
+    let mut alice = Peer::new("alice", [8, 8, 8, 8]);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+    let ping = |alice: &mut Peer<MockStorage, MockSigner>| {
+        let ping = (0..16)
+            .find_map(|_| {
+                alice.elapse(IDLE_INTERVAL);
+                alice.messages(bob.id()).find_map(|m| match m {
+                    Message::Ping(ping) => Some(ping),
+                    _ => None,
+                })
+            })
+            .expect("alice pings bob");
+        alice.receive(
+            bob.id(),
+            Message::Pong {
+                zeroes: ZeroBytes::new(ping.ponglen),
+            },
+        );
+        ping
+    };
+
+    alice.connect_to(&bob);
+    assert_eq!(ping(&mut alice).ponglen, 0, "bob's version is unknown");
+
+    alice.received_version(&bob.id(), wire::PROTOCOL_VERSION.number());
+    assert_eq!(
+        alice.sessions().get(&bob.id()).unwrap().version,
+        Some(wire::PROTOCOL_VERSION.number())
+    );
+    assert!(ping(&mut alice).ponglen > 0, "bob speaks our version");
+}
+
+#[test]
+fn test_unknown_message_protocol_version() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let carol = Peer::new("carol", [10, 10, 10, 10]);
+    let version = wire::PROTOCOL_VERSION.number();
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.connect_to(&carol);
+    alice.outbox().for_each(drop);
+
+    // Bob speaks a newer version, and may send messages we don't know about.
+    alice.received_version(&bob.id(), version + 1);
+    alice.received_unknown(bob.id(), 0xfffe);
+    assert!(
+        !alice
+            .outbox()
+            .any(|o| matches!(o, Io::Disconnect(nid, _) if nid == bob.id())),
+        "unknown messages from bob are skipped"
+    );
+    assert!(alice.sessions().get(&bob.id()).unwrap().is_connected());
+
+    // Eve speaks our version, and may run a newer release.
+    alice.received_version(&eve.id(), version);
+    alice.received_unknown(eve.id(), 0xfffe);
+    assert!(
+        !alice
+            .outbox()
+            .any(|o| matches!(o, Io::Disconnect(nid, _) if nid == eve.id())),
+        "unknown messages from eve are skipped"
+    );
+
+    // Carol's version is unknown, and should know better.
+    alice.received_unknown(carol.id(), 0xfffe);
+    assert_matches!(
+        alice
+            .outbox()
+            .find(|o| matches!(o, Io::Disconnect(nid, _) if nid == &carol.id())),
+        Some(Io::Disconnect(
+            _,
+            DisconnectReason::Session(session::Error::Misbehavior)
+        ))
+    );
+}
+
+#[test]
+fn test_keep_alive_transfer_suppresses_ping() {
+    let mut alice = Peer::new("alice", [8, 8, 8, 8]);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+    alice.transfer_started(&bob.id());
+
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed < Limits::default().stale_timeout * 2 {
+        alice.elapse(IDLE_INTERVAL);
+        elapsed = elapsed + IDLE_INTERVAL;
+    }
+    assert!(
+        !alice
+            .messages(bob.id())
+            .any(|m| matches!(m, Message::Ping(_))),
+        "bob isn't pinged during a transfer"
+    );
+    assert!(
+        !alice
+            .outbox()
+            .any(|m| matches!(m, Io::Disconnect(addr, _) if addr == bob.id())),
+        "bob isn't disconnected during a transfer"
+    );
+
+    alice.transfer_finished(&bob.id());
+    alice.elapse(IDLE_INTERVAL);
+
+    assert!(
+        !alice
+            .messages(bob.id())
+            .any(|m| matches!(m, Message::Ping(_))),
+        "bob isn't pinged right after the transfer"
+    );
+
+    alice.elapse(Limits::default().keep_alive);
+
+    assert!(
+        alice
+            .messages(bob.id())
+            .any(|m| matches!(m, Message::Ping(_))),
+        "bob is pinged once idle after the transfer"
+    );
+}
+
+#[test]
+fn test_keep_alive_slow_peer() {
+    let limits = Limits::default();
+    let mut alice = Peer::new("alice", [8, 8, 8, 8]);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+    alice.elapse(limits.keep_alive);
+
+    let ping = alice
+        .messages(bob.id())
+        .find_map(|m| match m {
+            Message::Ping(ping) => Some(ping),
+            _ => None,
+        })
+        .expect("bob is pinged");
+
+    // Bob takes a minute to answer our ping.
+    alice.clock_mut().elapse(LocalDuration::from_mins(1));
+    alice.receive(
+        bob.id(),
+        Message::Pong {
+            zeroes: ZeroBytes::new(ping.ponglen),
+        },
+    );
+    let session = alice.sessions().get(&bob.id()).unwrap();
+    assert_eq!(session.rtt, Some(LocalDuration::from_mins(1)));
+    assert_eq!(
+        session.timeout(limits.stale_timeout),
+        LocalDuration::from_mins(10)
+    );
+
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed <= limits.stale_timeout + limits.keep_alive {
+        alice.elapse(IDLE_INTERVAL);
+        elapsed = elapsed + IDLE_INTERVAL;
+    }
+    assert!(
+        alice
+            .messages(bob.id())
+            .any(|m| matches!(m, Message::Ping(_))),
+        "bob is pinged again"
+    );
+    assert!(
+        !alice
+            .outbox()
+            .any(|m| matches!(m, Io::Disconnect(addr, _) if addr == bob.id())),
+        "bob isn't disconnected at the base timeout"
+    );
+
+    alice.elapse(LocalDuration::from_mins(10));
+    alice
+        .outbox()
+        .find(|m| matches!(m, &Io::Disconnect(addr, _) if addr == bob.id()))
+        .expect("bob is disconnected once his timeout is reached");
+}
+
+#[test]
+fn test_clock_jump_backwards() {
+    let storage = arbitrary::nonempty_storage(3);
+    let mut alice = Peer::with_storage("alice", [8, 8, 8, 8], storage);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+
+    // Run for an interval, answering Alice's pings as Bob, and return what was sent to Bob.
+    let run = |alice: &mut Peer<MockStorage, MockSigner>, interval: LocalDuration| {
+        let mut sent = Vec::new();
+        let mut elapsed = LocalDuration::from_secs(0);
+
+        while elapsed < interval {
+            alice.elapse(IDLE_INTERVAL);
+            elapsed = elapsed + IDLE_INTERVAL;
+
+            let msgs = alice.messages(bob.id()).collect::<Vec<_>>();
+            for msg in &msgs {
+                if let Message::Ping(ping) = msg {
+                    alice.receive(
+                        bob.id(),
+                        Message::Pong {
+                            zeroes: ZeroBytes::new(ping.ponglen),
+                        },
+                    );
+                }
+            }
+            sent.extend(msgs);
+        }
+        sent
+    };
+    let pings = |msgs: &[Message]| {
+        msgs.iter()
+            .filter(|m| matches!(m, Message::Ping(_)))
+            .count()
+    };
+    let inventories = |msgs: &[Message]| {
+        msgs.iter()
+            .filter(|m| {
+                matches!(
+                    m,
+                    Message::Announcement(Announcement {
+                        message: AnnouncementMessage::Inventory(_),
+                        ..
+                    })
+                )
+            })
+            .count()
+    };
+
+    alice.connect_to(&bob);
+    run(&mut alice, ANNOUNCE_INTERVAL * 2);
+
+    // The clock jumps back by an hour.
+    let now = *alice.clock();
+    alice.tick(now - LocalDuration::from_mins(60));
+    alice.wake();
+
+    // Messages from Bob are still handled.
+    alice.receive(
+        bob.id(),
+        Message::Ping(Ping {
+            ponglen: 1,
+            zeroes: ZeroBytes::new(1),
+        }),
+    );
+    assert_matches!(
+        alice.messages(bob.id()).collect::<Vec<_>>().as_slice(),
+        [Message::Pong { .. }],
+        "no periodic task runs right after the jump"
+    );
+
+    // Periodic tasks keep running at their usual pace.
+    let sent = run(&mut alice, ANNOUNCE_INTERVAL);
+    assert!(pings(&sent) > 1, "bob is still pinged after the jump");
+    assert!(
+        inventories(&sent) <= 1,
+        "inventory is announced at most once"
+    );
+    assert!(
+        !alice
+            .outbox()
+            .any(|m| matches!(m, Io::Disconnect(addr, _) if addr == bob.id())),
+        "bob is kept connected"
+    );
+}
+
+#[test]
+fn test_disconnecting_chatty_unresponsive_peer() {
+    let mut alice = Peer::new("alice", [8, 8, 8, 8]);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed <= Limits::default().stale_timeout + Limits::default().keep_alive {
+        // Bob keeps sending us messages, but never answers our pings.
+        alice.receive(
+            bob.id(),
+            Message::Ping(Ping {
+                ponglen: 1,
+                zeroes: ZeroBytes::new(1),
+            }),
+        );
+        alice.elapse(IDLE_INTERVAL);
+        elapsed = elapsed + IDLE_INTERVAL;
+    }
+    alice
+        .outbox()
+        .find(|m| matches!(m, &Io::Disconnect(addr, _) if addr == bob.id()))
+        .expect("disconnect a chatty but unresponsive bob");
+}
+
 #[test]
 fn test_redundant_connect() {
     let mut alice = Peer::new("alice", [8, 8, 8, 8]);
@@ -131,6 +471,62 @@ fn test_redundant_connect() {
     );
 }
 
+#[test]
+fn test_connect_invalid_address() {
+    let mut alice = Peer::new("alice", [8, 8, 8, 8]);
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+    let own = Address::from(alice.local_addr);
+    let opts = ConnectOptions::default();
+
+    for addr in [
+        own,
+        Address::from(net::SocketAddr::from(([0, 0, 0, 0], 8776))),
+        Address::from(net::SocketAddr::from(([224, 0, 0, 1], 8776))),
+        Address::from(net::SocketAddr::from(([9, 9, 9, 9], 0))),
+    ] {
+        alice.command(Command::Connect(bob.id(), addr, opts.clone()));
+    }
+    assert_matches!(alice.outbox().next(), None, "no connection is attempted");
+    assert!(alice.sessions().is_empty());
+
+    // Loopback addresses are allowed by default.
+    let local = Address::from(net::SocketAddr::from(([127, 0, 0, 1], 8776)));
+    alice.command(Command::Connect(bob.id(), local.clone(), opts));
+    assert_matches!(
+        alice.outbox().next(),
+        Some(Io::Connect(id, addr)) if id == bob.id() && addr == local
+    );
+}
+
+#[test]
+fn test_connect_local_address_rejected() {
+    let mut alice = Peer::config(
+        "alice",
+        [8, 8, 8, 8],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                allow_local_addresses: false,
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+
+    for ip in [[127, 0, 0, 1], [192, 168, 1, 2]] {
+        alice.command(Command::Connect(
+            bob.id(),
+            Address::from(net::SocketAddr::from((ip, 8776))),
+            ConnectOptions::default(),
+        ));
+    }
+    assert_matches!(alice.outbox().next(), None);
+
+    alice.connect_to(&bob);
+    assert_eq!(alice.sessions().connected().count(), 1);
+}
+
 #[test]
 fn test_connection_kept_alive() {
     let mut alice = Peer::new("alice", [8, 8, 8, 8]);
@@ -152,8 +548,8 @@ fn test_connection_kept_alive() {
     assert_eq!(1, alice.sessions().connected().count(), "bob connects");
 
     let mut elapsed: LocalDuration = LocalDuration::from_secs(0);
-    let step: LocalDuration = STALE_CONNECTION_TIMEOUT / 10;
-    while elapsed < STALE_CONNECTION_TIMEOUT + step {
+    let step: LocalDuration = Limits::default().stale_timeout / 10;
+    while elapsed < Limits::default().stale_timeout + step {
         alice.elapse(step);
         bob.elapse(step);
         sim.run_while([&mut alice, &mut bob], |s| !s.is_settled());
@@ -274,6 +670,53 @@ fn test_inventory_sync() {
     }
 }
 
+#[test]
+fn test_inventory_corrupt_repo() {
+    let tmp = tempfile::tempdir().unwrap();
+    let signer = MockSigner::default();
+    let storage = fixtures::storage(tmp.path().join("bob"), &signer).unwrap();
+    let projs = storage.inventory().unwrap();
+    let corrupt = arbitrary::gen::<Id>(1);
+
+    // A repository directory that isn't a git repository.
+    std::fs::create_dir(storage.path_of(&corrupt)).unwrap();
+
+    let mut bob = Peer::config("bob", [8, 8, 8, 8], storage, peer::Config::default());
+    let alice = Peer::new("alice", [7, 7, 7, 7]);
+    let events = bob.events();
+
+    bob.initialize();
+    bob.service
+        .connected(alice.id(), alice.address(), Link::Inbound);
+
+    let inventory = bob
+        .messages(alice.id())
+        .find_map(|m| match m {
+            Message::Announcement(Announcement {
+                message: AnnouncementMessage::Inventory(i),
+                ..
+            }) => Some(i.inventory),
+            _ => None,
+        })
+        .expect("`inventory-announcement` must be sent");
+
+    assert_eq!(inventory.len(), projs.len());
+    assert!(projs.iter().all(|rid| inventory.contains(rid)));
+    assert!(!inventory.contains(&corrupt));
+
+    // The corrupt repository is only reported once.
+    bob.elapse(ANNOUNCE_INTERVAL);
+
+    let corrupted = events
+        .try_iter()
+        .filter_map(|e| match e {
+            Event::RepoCorrupted { rid, .. } => Some(rid),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(corrupted, vec![corrupt]);
+}
+
 #[test]
 fn test_inventory_pruning() {
     struct Test {
@@ -389,47 +832,403 @@ fn test_tracking() {
     alice.command(Command::TrackRepo(
         proj_id,
         tracking::Scope::default(),
+        tracking::Origin::default(),
         sender,
     ));
-    let policy_change = receiver.recv().map_err(runtime::HandleError::from).unwrap();
+    let policy_change = receiver
+        .recv()
+        .map_err(runtime::HandleError::from)
+        .unwrap()
+        .unwrap();
     assert!(policy_change);
     assert!(alice.tracking().is_repo_tracked(&proj_id).unwrap());
 
     let (sender, receiver) = chan::bounded(1);
     alice.command(Command::UntrackRepo(proj_id, sender));
-    let policy_change = receiver.recv().map_err(runtime::HandleError::from).unwrap();
+    let policy_change = receiver
+        .recv()
+        .map_err(runtime::HandleError::from)
+        .unwrap()
+        .unwrap();
     assert!(policy_change);
     assert!(!alice.tracking().is_repo_tracked(&proj_id).unwrap());
 }
 
 #[test]
-fn test_inventory_relay_bad_timestamp() {
+fn test_tracking_store_error() {
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            tracking: tracking::Store::<tracking::store::Write>::faulty().unwrap(),
+            ..peer::Config::default()
+        },
+    );
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let rid: identity::Id = test::arbitrary::gen(1);
+
+    alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
+
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::TrackRepo(
+        rid,
+        tracking::Scope::All,
+        tracking::Origin::default(),
+        sender,
+    ));
+    assert_matches!(
+        receiver.recv().unwrap(),
+        Err(CommandError::Tracking(tracking::Error::Internal(_)))
+    );
+    // Peers aren't told about the failed policy change.
+    assert_matches!(alice.outbox().next(), None);
+
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::TrackNode(bob.id, None, sender));
+    assert_matches!(receiver.recv().unwrap(), Err(CommandError::Tracking(_)));
+
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::ImportPolicies(
+        tracking::Policies {
+            repos: vec![tracking::Repo {
+                id: rid,
+                scope: tracking::Scope::All,
+                policy: tracking::Policy::Track,
+                fetch_limit: None,
+                origin: tracking::Origin::default(),
+                ref_filter: tracking::RefFilter::default(),
+            }],
+            nodes: vec![],
+        },
+        sender,
+    ));
+    assert_matches!(receiver.recv().unwrap(), Err(CommandError::Tracking(_)));
+
+    // The node is still up, and nothing was tracked.
+    assert!(!alice.tracking().is_repo_tracked(&rid).unwrap());
+    assert!(alice.sessions().is_connected(&bob.id));
+
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::UntrackNode(bob.id, sender));
+    assert!(!receiver.recv().unwrap().unwrap());
+}
+
+#[test]
+fn test_tracking_store_read_error() {
     let mut alice = Peer::new("alice", [7, 7, 7, 7]);
     let bob = Peer::new("bob", [8, 8, 8, 8]);
-    let two_hours = 3600 * 1000 * 2;
-    let timestamp = alice.timestamp() + two_hours;
+    let rid: identity::Id = test::arbitrary::gen(1);
+    let events = alice.events();
 
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
     alice.connect_to(&bob);
+    alice.tracking().corrupt().unwrap();
+
+    // Announcements that need a tracking policy lookup are dropped, instead of crashing
+    // the service.
     alice.receive(
         bob.id(),
         Message::inventory(
             InventoryAnnouncement {
-                inventory: BoundedVec::new(),
-                timestamp,
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: bob.timestamp(),
             },
             bob.signer(),
         ),
     );
-    assert_matches!(
-        alice.outbox().next(),
-        Some(Io::Disconnect(addr, DisconnectReason::Session(session::Error::InvalidTimestamp(t))))
-        if addr == bob.id() && t == timestamp
-    );
+    alice.receive(bob.id(), bob.refs_announcement(rid));
+
+    let errors = events
+        .try_iter()
+        .filter(|e| matches!(e, Event::StoreError { remote, .. } if remote == &bob.id()))
+        .count();
+    assert_eq!(errors, 2);
+    assert!(alice.sessions().is_connected(&bob.id()));
+    assert_matches!(alice.fetches().next(), None);
+
+    // Commands still work.
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::SyncInventory(sender));
+    assert!(receiver.recv().unwrap().is_ok());
 }
 
 #[test]
-fn test_announcement_rebroadcast() {
-    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+fn test_mirror_inventory() {
+    let alice = Peer::new("alice", [7, 7, 7, 7]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let mut bob = Peer::config(
+        "bob",
+        [8, 8, 8, 8],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                mirror: vec![alice.id()],
+                ..Config::new(node::Alias::new("bob"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let (ours, theirs) = (
+        test::arbitrary::gen::<identity::Id>(1),
+        test::arbitrary::gen::<identity::Id>(2),
+    );
+    let inventory = |peer: &Peer<MockStorage, MockSigner>, rid: identity::Id| {
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: peer.timestamp(),
+            },
+            peer.signer(),
+        )
+    };
+
+    bob.connect_to(&alice);
+    bob.connect_to(&eve);
+
+    // Eve's inventory, relayed by alice, isn't mirrored.
+    bob.receive(alice.id(), inventory(&eve, theirs));
+    assert!(!bob.tracking().is_repo_tracked(&theirs).unwrap());
+
+    // Alice's inventory is mirrored, even when relayed by eve.
+    bob.receive(eve.id(), inventory(&alice, ours));
+    assert!(bob.tracking().is_repo_tracked(&ours).unwrap());
+    assert_eq!(
+        tracking::Store::repo_policy(bob.tracking(), &ours)
+            .unwrap()
+            .map(|r| (r.scope, r.origin)),
+        Some((tracking::Scope::All, tracking::Origin::Mirror))
+    );
+}
+
+#[test]
+fn test_mirror_expiry() {
+    let alice = Peer::new("alice", [7, 7, 7, 7]);
+    let mut bob = Peer::config(
+        "bob",
+        [8, 8, 8, 8],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                mirror: vec![alice.id()],
+                mirror_expiry: Some(PRUNE_INTERVAL),
+                ..Config::new(node::Alias::new("bob"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let (kept, removed) = (
+        test::arbitrary::gen::<identity::Id>(1),
+        test::arbitrary::gen::<identity::Id>(2),
+    );
+    let inventory = |rids: Vec<identity::Id>, delta: u64| {
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: rids.try_into().unwrap(),
+                timestamp: alice.timestamp() + delta,
+            },
+            alice.signer(),
+        )
+    };
+
+    bob.connect_to(&alice);
+    bob.receive(alice.id(), inventory(vec![kept, removed], 0));
+    assert!(bob.tracking().is_repo_tracked(&kept).unwrap());
+    assert!(bob.tracking().is_repo_tracked(&removed).unwrap());
+
+    // Alice no longer has one of the repositories.
+    bob.receive(alice.id(), inventory(vec![kept], 1));
+
+    // The repository has to be missing for the whole expiry before it is untracked.
+    bob.elapse(PRUNE_INTERVAL);
+    assert!(bob.tracking().is_repo_tracked(&removed).unwrap());
+
+    bob.elapse(PRUNE_INTERVAL);
+    assert!(!bob.tracking().is_repo_tracked(&removed).unwrap());
+    assert!(bob.tracking().is_repo_tracked(&kept).unwrap());
+}
+
+#[test]
+fn test_import_policies() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let rids = test::arbitrary::vec::<Id>(2);
+
+    alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
+
+    let policies = tracking::Policies {
+        repos: vec![
+            tracking::Repo {
+                id: rids[0],
+                scope: tracking::Scope::All,
+                policy: tracking::Policy::Track,
+                fetch_limit: None,
+                origin: tracking::Origin::default(),
+                ref_filter: tracking::RefFilter::default(),
+            },
+            tracking::Repo {
+                id: rids[1],
+                scope: tracking::Scope::Trusted,
+                policy: tracking::Policy::Block,
+                fetch_limit: None,
+                origin: tracking::Origin::default(),
+                ref_filter: tracking::RefFilter::default(),
+            },
+        ],
+        nodes: vec![tracking::Node {
+            id: bob.id,
+            alias: Some(node::Alias::new("bob")),
+            policy: tracking::Policy::Track,
+        }],
+    };
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::ImportPolicies(policies.clone(), sender));
+
+    let summary = receiver.recv().unwrap().unwrap();
+    assert_eq!(summary.added, 3);
+    assert_eq!(alice.export_policies().unwrap(), policies);
+
+    // Our peers are told about the repositories we're now interested in.
+    assert_matches!(
+        alice.messages(bob.id).next(),
+        Some(Message::Subscribe(Subscribe { filter, .. })) if filter.contains(&rids[0])
+    );
+}
+
+#[test]
+fn test_inventory_relay_bad_timestamp() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let two_hours = 3600 * 1000 * 2;
+    let timestamp = alice.timestamp() + two_hours;
+
+    alice.connect_to(&bob);
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: BoundedVec::new(),
+                timestamp,
+            },
+            bob.signer(),
+        ),
+    );
+    assert_matches!(
+        alice.outbox().next(),
+        Some(Io::Disconnect(addr, DisconnectReason::Session(session::Error::InvalidTimestamp(t))))
+        if addr == bob.id() && t == timestamp
+    );
+}
+
+#[test]
+fn test_announcement_expired_timestamp() {
+    use radicle::node::address::Store as _;
+
+    let rid = arbitrary::gen::<Id>(1);
+    let now = LocalTime::now();
+    let max_age = Limits::default().routing_max_age.as_millis() as u64;
+    let new_alice = || {
+        Peer::config(
+            "alice",
+            [7, 7, 7, 7],
+            MockStorage::empty(),
+            peer::Config {
+                local_time: now,
+                ..peer::Config::default()
+            },
+        )
+    };
+    let announcements = |bob: &Peer<MockStorage, MockSigner>, timestamp: Timestamp| {
+        [
+            Message::inventory(
+                InventoryAnnouncement {
+                    inventory: vec![rid].try_into().unwrap(),
+                    timestamp,
+                },
+                bob.signer(),
+            ),
+            Message::Announcement(
+                AnnouncementMessage::from(RefsAnnouncement {
+                    rid,
+                    refs: BoundedVec::new(),
+                    timestamp,
+                })
+                .signed(bob.signer()),
+            ),
+            Message::node(
+                NodeAnnouncement {
+                    features: node::Features::SEED,
+                    timestamp,
+                    alias: node::Alias::new("bob"),
+                    addresses: Some(
+                        std::net::SocketAddr::from(([8, 8, 8, 8], node::DEFAULT_PORT)).into(),
+                    )
+                    .into(),
+                    nonce: 0,
+                }
+                .solve(0)
+                .unwrap(),
+                bob.signer(),
+            ),
+        ]
+    };
+    // Whether the announcement was stored, for each announcement type.
+    let stored = |alice: &Peer<MockStorage, MockSigner>, bob: &Peer<MockStorage, MockSigner>| {
+        [
+            alice.routing().get(&rid).unwrap().contains(&bob.id),
+            alice.routing().get(&rid).unwrap().contains(&bob.id),
+            alice.addresses().get(&bob.id).unwrap().is_some(),
+        ]
+    };
+
+    for (i, kind) in ["inventory", "refs", "node"].into_iter().enumerate() {
+        // Zero timestamps are invalid, and cause a disconnect.
+        let bob = Peer::new("bob", [8, 8, 8, 8]);
+        let msg = announcements(&bob, 0)[i].clone();
+        let mut alice = new_alice();
+
+        alice.connect_to(&bob);
+        alice.receive(bob.id(), msg);
+
+        assert_matches!(
+            alice.outbox().next(),
+            Some(Io::Disconnect(addr, DisconnectReason::Session(session::Error::InvalidTimestamp(0))))
+            if addr == bob.id(),
+            "{kind} announcement with zero timestamp"
+        );
+        assert!(!stored(&alice, &bob)[i]);
+
+        // Announcements older than the routing table max-age are ignored, but the session
+        // stays up.
+        for (timestamp, expected) in [
+            (1, false),
+            (now.as_millis() - max_age - 1, false),
+            (now.as_millis() - max_age, true),
+        ] {
+            let mut alice = new_alice();
+            let msg = announcements(&bob, timestamp)[i].clone();
+
+            alice.connect_to(&bob);
+            alice.receive(bob.id(), msg);
+
+            assert!(
+                alice.outbox().all(|o| !matches!(o, Io::Disconnect(..))),
+                "{kind} announcement at {timestamp} caused a disconnect"
+            );
+            assert_eq!(
+                stored(&alice, &bob)[i],
+                expected,
+                "{kind} announcement at {timestamp}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_announcement_rebroadcast() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
     let bob = Peer::new("bob", [8, 8, 8, 8]);
     let eve = Peer::new("eve", [9, 9, 9, 9]);
 
@@ -562,594 +1361,3100 @@ fn test_announcement_rebroadcast_timestamp_filtered() {
 }
 
 #[test]
-fn test_announcement_relay() {
+fn test_announcement_rebroadcast_ordered() {
     let mut alice = Peer::new("alice", [7, 7, 7, 7]);
-    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
-    let mut eve = Peer::new("eve", [9, 9, 9, 9]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let frank = Peer::new("frank", [10, 10, 10, 10]);
+    let mut carol = Peer::new("carol", [4, 4, 4, 4]);
+    let mut dave = Peer::new("dave", [5, 5, 5, 5]);
+    let mut erin = Peer::new("erin", [6, 6, 6, 6]);
+    let rid = arbitrary::gen::<Id>(1);
+    let key = |msg: &Message| match msg {
+        Message::Announcement(ann) => (ann.timestamp(), ann.node),
+        other => panic!("unexpected message {other:?}"),
+    };
+    let subscribe = |since| {
+        Message::Subscribe(Subscribe {
+            filter: Filter::default(),
+            since,
+            until: Timestamp::MAX,
+        })
+    };
 
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
     alice.connect_to(&bob);
-    alice.connect_to(&eve);
-    alice.receive(bob.id(), bob.inventory_announcement());
 
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(_))
-    );
+    // Each node announces at its own pace, so that their timestamps interleave.
+    let mut received = Vec::new();
+    for (peer, secs) in [(&mut carol, 3), (&mut dave, 4), (&mut erin, 5)] {
+        peer.elapse(LocalDuration::from_secs(secs));
+        received.push(peer.node_announcement());
+        peer.elapse(LocalDuration::from_secs(secs));
+        received.push(peer.inventory_announcement());
+        peer.elapse(LocalDuration::from_secs(secs));
+        received.push(peer.refs_announcement(rid));
+    }
+    // Alice receives the announcements grouped by node.
+    for msg in received.iter().cloned() {
+        alice.receive(bob.id(), msg);
+    }
 
-    alice.receive(bob.id(), bob.inventory_announcement());
-    assert!(
-        alice.messages(eve.id()).next().is_none(),
-        "Another inventory with the same timestamp is ignored"
-    );
+    alice.connect_from(&eve);
+    alice.receive(eve.id(), subscribe(Timestamp::MIN));
 
-    bob.elapse(LocalDuration::from_mins(1));
-    alice.receive(bob.id(), bob.inventory_announcement());
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(_)),
-        "Another inventory with a fresher timestamp is relayed"
-    );
+    let delivered = alice.messages(eve.id()).collect::<Vec<_>>();
+    let mut expected = received;
+    expected.sort_by_key(key);
 
-    alice.receive(bob.id(), bob.node_announcement());
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(_)),
-        "A node announcement with the same timestamp as the inventory is relayed"
-    );
+    assert_eq!(delivered.len(), 9);
+    assert_eq!(delivered, expected);
 
-    alice.receive(bob.id(), bob.node_announcement());
-    assert!(alice.messages(eve.id()).next().is_none(), "Only once");
+    // A subscriber resuming from the timestamp of an announcement receives exactly that
+    // announcement and the ones after it.
+    let k = 4;
+    let (since, _) = key(&delivered[k]);
 
-    alice.receive(eve.id(), eve.node_announcement());
-    assert_matches!(
-        alice.messages(bob.id()).next(),
-        Some(Message::Announcement(_)),
-        "A node announcement from Eve is relayed to Bob"
-    );
-    assert!(
-        alice.messages(eve.id()).next().is_none(),
-        "But not back to Eve"
-    );
+    alice.connect_from(&frank);
+    alice.receive(frank.id(), subscribe(since));
 
-    eve.elapse(LocalDuration::from_mins(1));
-    alice.receive(bob.id(), eve.node_announcement());
-    assert!(
-        alice.messages(bob.id()).next().is_none(),
-        "Bob already know about this message, since he sent it"
-    );
-    assert!(
-        alice.messages(eve.id()).next().is_none(),
-        "Eve already know about this message, since she signed it"
+    assert_eq!(
+        alice.messages(frank.id()).collect::<Vec<_>>(),
+        delivered[k..].to_vec()
     );
 }
 
 #[test]
-fn test_refs_announcement_relay() {
-    let tmp = tempfile::tempdir().unwrap();
-    let mut alice = Peer::config(
-        "alice",
-        [7, 7, 7, 7],
-        Storage::open(tmp.path().join("alice")).unwrap(),
-        peer::Config::default(),
-    );
-    let eve = Peer::config(
-        "eve",
-        [8, 8, 8, 8],
-        Storage::open(tmp.path().join("eve")).unwrap(),
-        peer::Config::default(),
-    );
-
-    let bob = {
-        let mut rng = fastrand::Rng::new();
-        let signer = MockSigner::new(&mut rng);
-        let storage = fixtures::storage(tmp.path().join("bob"), &signer).unwrap();
-
-        Peer::config(
-            "bob",
-            [9, 9, 9, 9],
-            storage,
-            peer::Config {
-                signer,
-                rng,
-                ..peer::Config::default()
-            },
-        )
-    };
-    let bob_inv = bob.storage().inventory().unwrap();
+fn test_announcement_rebroadcast_chunked() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
 
-    alice.track_repo(&bob_inv[0], tracking::Scope::All).unwrap();
-    alice.track_repo(&bob_inv[1], tracking::Scope::All).unwrap();
-    alice.track_repo(&bob_inv[2], tracking::Scope::All).unwrap();
     alice.connect_to(&bob);
-    alice.connect_to(&eve);
-    alice.receive(eve.id(), Message::Subscribe(Subscribe::all()));
-    alice.receive(bob.id(), bob.refs_announcement(bob_inv[0]));
 
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(_)),
-        "A refs announcement from Bob is relayed to Eve"
+    let received =
+        test::gossip::messages(BACKLOG_CHUNK_SIZE + 10, alice.local_time(), MAX_TIME_DELTA);
+    for msg in received.iter().cloned() {
+        alice.receive(bob.id(), msg);
+    }
+
+    alice.connect_from(&eve);
+    alice.receive(
+        eve.id(),
+        Message::Subscribe(Subscribe {
+            filter: Filter::default(),
+            since: Timestamp::MIN,
+            until: Timestamp::MAX,
+        }),
     );
 
-    alice.receive(bob.id(), bob.refs_announcement(bob_inv[0]));
-    assert!(
-        alice.messages(eve.id()).next().is_none(),
-        "The same ref announement is not relayed"
+    // Only the first chunk of the backlog is sent right away.
+    let mut relayed = alice.messages(eve.id()).collect::<Vec<_>>();
+    assert_eq!(relayed.len(), BACKLOG_CHUNK_SIZE);
+
+    // The rest is sent on the next wakeup.
+    alice.elapse(BACKLOG_CHUNK_INTERVAL);
+    relayed.extend(alice.messages(eve.id()));
+
+    assert_eq!(relayed.len(), received.len());
+    assert_eq!(
+        relayed.into_iter().collect::<BTreeSet<_>>(),
+        received.into_iter().collect::<BTreeSet<_>>()
     );
+}
 
-    alice.receive(bob.id(), bob.refs_announcement(bob_inv[1]));
+#[test]
+fn test_announcement_relay_chunked_backlog() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let timestamp = |msg: &Message| match msg {
+        Message::Announcement(ann) => ann.timestamp(),
+        _ => panic!("unexpected message {msg:?}"),
+    };
+
+    alice.connect_to(&bob);
+
+    let received =
+        test::gossip::messages(BACKLOG_CHUNK_SIZE + 10, alice.local_time(), MAX_TIME_DELTA);
+    for msg in received.iter().cloned() {
+        alice.receive(bob.id(), msg);
+    }
+
+    alice.connect_from(&eve);
+    alice.receive(
+        eve.id(),
+        Message::Subscribe(Subscribe {
+            filter: Filter::default(),
+            since: Timestamp::MIN,
+            until: Timestamp::MAX,
+        }),
+    );
+    assert_eq!(alice.messages(eve.id()).count(), BACKLOG_CHUNK_SIZE);
+
+    // An announcement received while the backlog is being sent isn't relayed right away.
+    let msg = test::gossip::messages(1, alice.local_time(), LocalDuration::from_secs(0))
+        .pop()
+        .unwrap();
+    alice.receive(bob.id(), msg.clone());
+    assert_eq!(alice.messages(eve.id()).count(), 0);
+
+    // It's sent with the rest of the backlog, in order.
+    alice.elapse(BACKLOG_CHUNK_INTERVAL);
+    let relayed = alice.messages(eve.id()).collect::<Vec<_>>();
+
+    assert_eq!(relayed.len(), received.len() - BACKLOG_CHUNK_SIZE + 1);
+    assert!(relayed.contains(&msg));
+    assert!(relayed
+        .windows(2)
+        .all(|w| timestamp(&w[0]) <= timestamp(&w[1])));
+}
+
+#[test]
+fn test_announcement_resubscribe() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let [a, b] = arbitrary::gen::<[Id; 2]>(1);
+    let subscribe = |filter| {
+        Message::Subscribe(Subscribe {
+            filter,
+            since: Timestamp::MIN,
+            until: Timestamp::MAX,
+        })
+    };
+    let refs = |msgs: Vec<Message>| {
+        msgs.into_iter()
+            .filter_map(|m| match m {
+                Message::Announcement(Announcement {
+                    message: AnnouncementMessage::Refs(refs),
+                    ..
+                }) => Some(refs.rid),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    alice.track_repo(&a, tracking::Scope::All).unwrap();
+    alice.track_repo(&b, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.receive(bob.id(), bob.node_announcement());
+    alice.connect_from(&eve);
+    alice.messages(eve.id()).for_each(drop);
+
+    // Eve subscribes to `a` only, and receives the node announcement as backlog.
+    alice.receive(eve.id(), subscribe(Filter::new([a])));
+    assert_eq!(alice.messages(eve.id()).count(), 1);
+
+    // Announcements for both repositories arrive, only the one for `a` is relayed.
+    bob.elapse(LocalDuration::from_secs(1));
+    alice.receive(bob.id(), bob.refs_announcement(a));
+    alice.receive(bob.id(), bob.refs_announcement(b));
+    assert_eq!(refs(alice.messages(eve.id()).collect()), vec![a]);
+
+    // Eve subscribes to `b` as well, and receives the stored announcement for `b` only.
+    alice.receive(eve.id(), subscribe(Filter::new([a, b])));
+    let msgs = alice.messages(eve.id()).collect::<Vec<_>>();
+
+    assert_eq!(msgs.len(), 1);
+    assert_eq!(refs(msgs), vec![b]);
+
+    // Subscribing again with the same filter sends nothing.
+    alice.receive(eve.id(), subscribe(Filter::new([a, b])));
+    assert_eq!(alice.messages(eve.id()).count(), 0);
+}
+
+#[test]
+fn test_announcement_resubscribe_since() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let [a, b] = arbitrary::gen::<[Id; 2]>(1);
+    let subscribe = |since| {
+        Message::Subscribe(Subscribe {
+            filter: Filter::new([a, b]),
+            since,
+            until: Timestamp::MAX,
+        })
+    };
+
+    alice.track_repo(&a, tracking::Scope::All).unwrap();
+    alice.track_repo(&b, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.connect_from(&eve);
+
+    let node = bob.node_announcement();
+    alice.receive(bob.id(), node.clone());
+
+    // Bob announces `a`, and later `b`.
+    bob.elapse(LocalDuration::from_secs(1));
+    let ann_a = bob.refs_announcement(a);
+    alice.receive(bob.id(), ann_a.clone());
+    bob.elapse(LocalDuration::from_secs(1));
+    let ann_b = bob.refs_announcement(b);
+    alice.receive(bob.id(), ann_b.clone());
+    alice.messages(eve.id()).for_each(drop);
+
+    // Eve subscribes to announcements since `b` was announced.
+    alice.receive(eve.id(), subscribe(bob.timestamp()));
+    assert_eq!(alice.messages(eve.id()).collect::<Vec<_>>(), vec![ann_b]);
+
+    // Eve subscribes again with an earlier start, and receives the announcements which
+    // weren't covered by the previous subscription.
+    alice.receive(eve.id(), subscribe(Timestamp::MIN));
+    assert_eq!(
+        alice.messages(eve.id()).collect::<Vec<_>>(),
+        vec![node, ann_a]
+    );
+}
+
+#[test]
+fn test_gossip_eviction() {
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let carol = Peer::new("carol", [4, 4, 4, 4]);
+    let dave = Peer::new("dave", [5, 5, 5, 5]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let others = [
+        Peer::new("frank", [10, 10, 10, 10]),
+        Peer::new("grace", [11, 11, 11, 11]),
+        Peer::new("heidi", [12, 12, 12, 12]),
+    ];
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                connect: [(carol.id(), carol.address()).into()].into_iter().collect(),
+                limits: Limits {
+                    gossip_max_nodes: 4,
+                    ..Limits::default()
+                },
+                ..Config::test(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+
+    // Bob is connected, Carol is a persistent peer, and Dave announces a repository
+    // we track: these are never evicted, even though they are the least recently updated.
+    alice.receive(bob.id(), bob.node_announcement());
+    alice.elapse(LocalDuration::from_secs(1));
+    alice.receive(bob.id(), carol.node_announcement());
+    alice.elapse(LocalDuration::from_secs(1));
+    alice.receive(bob.id(), dave.refs_announcement(rid));
+
+    for peer in &others {
+        alice.elapse(LocalDuration::from_secs(1));
+        alice.receive(bob.id(), peer.node_announcement());
+    }
+    assert_eq!(alice.gossip().nodes, 6);
+
+    // The store is pruned periodically.
+    alice.elapse(PRUNE_INTERVAL);
+    assert_eq!(alice.gossip().nodes, 4);
+
+    // Only the most recent unprotected announcer is kept, and evicted announcements
+    // are no longer served to subscribers.
+    alice.connect_from(&eve);
+    alice.receive(
+        eve.id(),
+        Message::Subscribe(Subscribe {
+            filter: Filter::default(),
+            since: Timestamp::MIN,
+            until: Timestamp::MAX,
+        }),
+    );
+    let relayed = alice
+        .messages(eve.id())
+        .filter_map(|m| match m {
+            Message::Announcement(ann) => Some(ann.node),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    assert_eq!(
+        relayed,
+        BTreeSet::from_iter([bob.id(), carol.id(), dave.id(), others[2].id()])
+    );
+}
+
+#[test]
+fn test_gossip_refs_eviction() {
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let rids = arbitrary::vec::<Id>(4);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                limits: Limits {
+                    gossip_max_refs: 2,
+                    ..Limits::default()
+                },
+                ..Config::test(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    alice.track_repo(&rids[0], tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+
+    let anns = rids
+        .iter()
+        .map(|rid| {
+            bob.elapse(LocalDuration::from_secs(1));
+            bob.refs_announcement(*rid)
+        })
+        .collect::<Vec<_>>();
+
+    // The announcements are received in the reverse order of their timestamps. The first
+    // repository is tracked, so its refs announcement is never evicted.
+    for ann in anns.into_iter().rev() {
+        alice.elapse(LocalDuration::from_secs(1));
+        alice.receive(bob.id(), ann);
+    }
+    assert_eq!(alice.gossip().refs, 4);
+
+    // The store is pruned periodically, keeping the most recently received announcement,
+    // rather than the one with the most recent timestamp.
+    alice.elapse(PRUNE_INTERVAL);
+    assert_eq!(alice.gossip().refs, 2);
+
+    alice.connect_from(&eve);
+    alice.receive(
+        eve.id(),
+        Message::Subscribe(Subscribe {
+            filter: Filter::default(),
+            since: Timestamp::MIN,
+            until: Timestamp::MAX,
+        }),
+    );
+    let relayed = alice
+        .messages(eve.id())
+        .filter_map(|m| match m {
+            Message::Announcement(Announcement {
+                message: AnnouncementMessage::Refs(refs),
+                ..
+            }) => Some(refs.rid),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    assert_eq!(relayed, BTreeSet::from_iter([rids[0], rids[1]]));
+}
+
+#[test]
+fn test_periodic_announcements() {
+    let storage = arbitrary::nonempty_storage(200);
+    let rids = storage.inventory.keys().copied().collect::<Vec<_>>();
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let refs = arbitrary::gen::<Refs>(1).signed(alice.signer()).unwrap();
+    let nid = alice.id;
+    for rid in &rids {
+        alice.storage_mut().insert_remote(*rid, nid, refs.clone());
+    }
+    alice.connect_to(&bob);
+    alice.messages(bob.id()).for_each(drop);
+
+    // The first wake schedules the periodic announcements over the next interval.
+    alice.elapse(LocalDuration::from_secs(1));
+
+    let mut inventories = 0;
+    for _ in 0..ANNOUNCE_INTERVAL.as_secs() / 60 {
+        for msg in alice.messages(bob.id()) {
+            let Message::Announcement(ann) = &msg else {
+                continue;
+            };
+            match &ann.message {
+                AnnouncementMessage::Refs(refs) => {
+                    panic!("{} was re-announced periodically", refs.rid);
+                }
+                AnnouncementMessage::Inventory(_) => inventories += 1,
+                AnnouncementMessage::Node(_) | AnnouncementMessage::Cobs(_) => continue,
+            }
+        }
+        alice.elapse(LocalDuration::from_mins(1));
+    }
+    // Only the inventory is announced periodically, once per interval.
+    assert_eq!(inventories, 1);
+}
+
+#[test]
+fn test_cobs_announcement() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let carol = Peer::new("carol", [10, 10, 10, 10]);
+    let cobs = vec![
+        TypeName::from_str("xyz.radicle.issue").unwrap(),
+        TypeName::from_str("org.example.task").unwrap(),
+    ];
+    let is_cobs = |m: &Message| {
+        matches!(
+            m,
+            Message::Announcement(Announcement {
+                message: AnnouncementMessage::Cobs(_),
+                ..
+            })
+        )
+    };
+
+    alice.connect_to(&bob);
+    alice.connect_from(&eve);
+    alice.connect_from(&carol);
+    assert!(
+        !alice.messages(eve.id()).any(|m| is_cobs(&m)),
+        "our cobs announcement isn't sent until eve's version is known"
+    );
+    alice.received_version(&eve.id(), wire::PROTOCOL_VERSION.number());
+    assert!(alice.messages(eve.id()).any(|m| is_cobs(&m)));
+
+    alice.receive(bob.id(), bob.node_announcement());
+    alice.receive(bob.id(), bob.inventory_announcement());
+    alice.messages(eve.id()).for_each(drop);
+    alice.messages(carol.id()).for_each(drop);
+    alice.receive(bob.id(), bob.cobs_announcement(cobs.clone()));
+
+    // Bob's collaborative object types are recorded in the address book.
+    let node = alice.addresses().get(&bob.id()).unwrap().unwrap();
+    assert_eq!(node.cobs, cobs);
+
+    // The announcement is relayed, but only to peers we know speak our version.
+    assert!(alice.messages(eve.id()).any(|m| is_cobs(&m)));
+    assert!(!alice.messages(carol.id()).any(|m| is_cobs(&m)));
+
+    // Receiving the same announcement again changes nothing.
+    alice.receive(bob.id(), bob.cobs_announcement(cobs.clone()));
+    assert_eq!(alice.messages(eve.id()).count(), 0);
+}
+
+#[test]
+fn test_seeds_prefer_cobs() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+
+    for peer in [&bob, &eve] {
+        alice.connect_to(peer);
+        alice.receive(
+            peer.id(),
+            Message::inventory(
+                InventoryAnnouncement {
+                    inventory: vec![rid].try_into().unwrap(),
+                    timestamp: peer.timestamp(),
+                },
+                peer.signer(),
+            ),
+        );
+    }
+    // Eve serves the issues we replicate, Bob doesn't.
+    alice.receive(
+        eve.id(),
+        eve.cobs_announcement(vec![TypeName::from_str("xyz.radicle.issue").unwrap()]),
+    );
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::Seeds(rid, send));
+
+    let (connected, _) = recv.recv().unwrap().partition();
+    let order = connected.iter().map(|s| s.nid).collect::<Vec<_>>();
+    assert_eq!(order, vec![eve.id(), bob.id()]);
+}
+
+#[test]
+fn test_node_announcement_refresh() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let half = LocalDuration::from_millis(Limits::default().node_announce_interval.as_millis() / 2);
+
+    alice.connect_to(&bob);
+
+    // Our node announcement sent during the handshake is recorded in our address book.
+    let handshake = alice.addresses().get(&alice.id()).unwrap().unwrap();
+
+    let is_node_announcement = |m: &Message| {
+        matches!(
+            m,
+            Message::Announcement(Announcement {
+                message: AnnouncementMessage::Node(_),
+                ..
+            })
+        )
+    };
+    alice.elapse(half);
+    assert!(
+        !alice.messages(bob.id()).any(|m| is_node_announcement(&m)),
+        "The node announcement isn't refreshed before the interval has passed"
+    );
+
+    alice.elapse(half);
+    let refreshed = alice
+        .messages(bob.id())
+        .find_map(|m| match m {
+            Message::Announcement(Announcement {
+                message: AnnouncementMessage::Node(ann),
+                ..
+            }) => Some(ann),
+            _ => None,
+        })
+        .expect("the node announcement is refreshed");
+
+    assert!(refreshed.timestamp > handshake.timestamp);
+    assert_eq!(refreshed.alias, handshake.alias);
+    assert_eq!(
+        alice
+            .addresses()
+            .get(&alice.id())
+            .unwrap()
+            .unwrap()
+            .timestamp,
+        refreshed.timestamp,
+        "Our own entry in the address book is updated"
+    );
+}
+
+#[test]
+fn test_announcement_relay() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let mut eve = Peer::new("eve", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.receive(bob.id(), bob.inventory_announcement());
+
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_))
+    );
+
+    alice.receive(bob.id(), bob.inventory_announcement());
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "Another inventory with the same timestamp is ignored"
+    );
+
+    bob.elapse(LocalDuration::from_mins(1));
+    alice.receive(bob.id(), bob.inventory_announcement());
     assert_matches!(
         alice.messages(eve.id()).next(),
         Some(Message::Announcement(_)),
-        "But a different one is"
+        "Another inventory with a fresher timestamp is relayed"
+    );
+
+    alice.receive(bob.id(), bob.node_announcement());
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_)),
+        "A node announcement with the same timestamp as the inventory is relayed"
+    );
+
+    alice.receive(bob.id(), bob.node_announcement());
+    assert!(alice.messages(eve.id()).next().is_none(), "Only once");
+
+    alice.receive(eve.id(), eve.node_announcement());
+    assert_matches!(
+        alice.messages(bob.id()).next(),
+        Some(Message::Announcement(_)),
+        "A node announcement from Eve is relayed to Bob"
+    );
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "But not back to Eve"
+    );
+
+    eve.elapse(LocalDuration::from_mins(1));
+    alice.receive(bob.id(), eve.node_announcement());
+    assert!(
+        alice.messages(bob.id()).next().is_none(),
+        "Bob already know about this message, since he sent it"
+    );
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "Eve already know about this message, since she signed it"
+    );
+}
+
+#[test]
+fn test_announcement_relay_known() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let carol = Peer::new("carol", [10, 10, 10, 10]);
+    let refs = arbitrary::gen::<Refs>(8)
+        .signed(bob.signer())
+        .unwrap()
+        .unverified();
+    let announcement = |peer: &Peer<MockStorage, MockSigner>, refs: Vec<_>| {
+        let ann = AnnouncementMessage::from(RefsAnnouncement {
+            rid,
+            refs: refs.try_into().unwrap(),
+            timestamp: peer.timestamp(),
+        });
+        Message::Announcement(ann.signed(peer.signer()))
+    };
+
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    for peer in [&bob, &eve, &carol] {
+        alice.connect_to(peer);
+        alice.receive(peer.id(), Message::Subscribe(Subscribe::all()));
+    }
+    // Eve already has Bob's refs, and announced them as part of her own.
+    let own = arbitrary::gen::<Refs>(8)
+        .signed(eve.signer())
+        .unwrap()
+        .unverified();
+    alice.receive(eve.id(), announcement(&eve, vec![own, refs.clone()]));
+    alice.outbox().for_each(drop);
+
+    alice.receive(bob.id(), announcement(&bob, vec![refs]));
+    assert_matches!(
+        alice.messages(carol.id()).next(),
+        Some(Message::Announcement(_)),
+        "Carol's state is unknown, so the announcement is relayed to her"
+    );
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "Eve already announced Bob's refs"
+    );
+}
+
+#[test]
+fn test_announcement_relay_throttled() {
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                limits: Limits {
+                    relay_rate: Some(1),
+                    ..Limits::default()
+                },
+                ..Config::test(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let events = alice.events();
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.receive(bob.id(), bob.inventory_announcement());
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_)),
+        "The first relay is within the rate"
+    );
+
+    alice.receive(bob.id(), bob.node_announcement());
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "Relays beyond the rate are dropped"
+    );
+    assert_eq!(alice.gossip().relays_throttled, 1);
+    assert_eq!(
+        events
+            .try_iter()
+            .filter(|e| matches!(e, Event::BandwidthThrottled { rate: 1 }))
+            .count(),
+        1
+    );
+
+    alice.receive(
+        eve.id(),
+        Message::Ping(Ping {
+            ponglen: 1,
+            zeroes: ZeroBytes::new(1),
+        }),
+    );
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Pong { .. }),
+        "Direct messages are not throttled"
+    );
+
+    alice.elapse(bandwidth::THROTTLE_WINDOW);
+    alice.messages(eve.id()).for_each(drop);
+    bob.elapse(LocalDuration::from_mins(1));
+    alice.receive(bob.id(), bob.inventory_announcement());
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_)),
+        "Relays resume once the window has passed"
+    );
+}
+
+#[test]
+fn test_announcement_timestamps_increasing() {
+    let storage = arbitrary::nonempty_storage(1);
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+    bob.connect_from(&alice);
+    bob.connect_to(&eve);
+    alice.messages(bob.id()).for_each(drop);
+    bob.messages(eve.id()).for_each(drop);
+
+    // Two inventory announcements are made within the same clock tick.
+    alice.command(Command::AnnounceInventory);
+    alice.command(Command::AnnounceInventory);
+
+    let anns = alice.messages(bob.id()).collect::<Vec<_>>();
+    let timestamps = anns
+        .iter()
+        .map(|m| match m {
+            Message::Announcement(ann) => ann.timestamp(),
+            _ => panic!("unexpected message {m:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(timestamps.len(), 2);
+    assert!(timestamps[0] < timestamps[1]);
+
+    // Neither is discarded as stale by the receiving node, which relays both.
+    for ann in anns {
+        bob.receive(alice.id(), ann);
+    }
+    assert_eq!(bob.messages(eve.id()).count(), 2);
+}
+
+#[test]
+fn test_refs_announcement_timestamps_increasing() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let alice_id = alice.id;
+    let (send, recv) = chan::bounded(2);
+
+    alice.connect_to(&bob);
+    bob.track_repo(&rid, tracking::Scope::All).unwrap();
+    bob.connect_from(&alice);
+    bob.connect_to(&eve);
+    bob.receive(eve.id(), Message::Subscribe(Subscribe::all()));
+    alice.messages(bob.id()).for_each(drop);
+    bob.messages(eve.id()).for_each(drop);
+
+    // Alice's refs change twice, and are announced within the same clock tick.
+    for _ in 0..2 {
+        let refs = arbitrary::gen::<Refs>(8).signed(alice.signer()).unwrap();
+
+        alice.storage_mut().insert_remote(rid, alice_id, refs);
+        alice.command(Command::AnnounceRefs(rid, send.clone()));
+        assert!(recv.recv().unwrap().is_ok());
+    }
+
+    let anns = alice.messages(bob.id()).collect::<Vec<_>>();
+    let timestamps = anns
+        .iter()
+        .map(|m| match m {
+            Message::Announcement(ann) => ann.timestamp(),
+            _ => panic!("unexpected message {m:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(timestamps.len(), 2);
+    assert!(timestamps[0] < timestamps[1]);
+
+    // Neither is discarded as stale by the receiving node, which relays both.
+    for ann in anns {
+        bob.receive(alice.id(), ann);
+    }
+    assert_eq!(bob.messages(eve.id()).count(), 2);
+}
+
+#[test]
+fn test_metrics() {
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                metrics: true,
+                ..Config::test(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+    alice.connect_from(&eve);
+    alice.receive(bob.id(), bob.node_announcement());
+    alice.receive(bob.id(), bob.node_announcement());
+    alice.elapse(IDLE_INTERVAL);
+
+    let metrics = alice.metrics().snapshot();
+    assert_eq!(metrics["sessions.opened"], 2);
+    assert_eq!(metrics["sessions.closed"], 0);
+    assert_eq!(metrics["messages.nodeAnnouncement"], 2);
+    assert_eq!(metrics["announcements.relayed"], 1);
+    assert_eq!(metrics["announcements.dropped.stale"], 1);
+    assert_eq!(metrics["announcements.dropped.invalid"], 0);
+    assert!(metrics.contains_key("tasks.idle.lastDurationMicros"));
+
+    // Counters only increase.
+    alice.disconnected(eve.id(), &DisconnectReason::Command);
+    let after = alice.metrics().snapshot();
+    assert_eq!(after["sessions.closed"], 1);
+    assert!(metrics
+        .iter()
+        .filter(|(name, _)| !name.starts_with("tasks."))
+        .all(|(name, value)| after[name] >= *value));
+
+    // Nothing is recorded when metrics are disabled.
+    bob.connect_to(&eve);
+    assert!(bob.metrics().snapshot().is_empty());
+}
+
+#[test]
+fn test_refs_announcement_relay() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        Storage::open(tmp.path().join("alice")).unwrap(),
+        peer::Config::default(),
+    );
+    let eve = Peer::config(
+        "eve",
+        [8, 8, 8, 8],
+        Storage::open(tmp.path().join("eve")).unwrap(),
+        peer::Config::default(),
+    );
+
+    let bob = {
+        let mut rng = fastrand::Rng::new();
+        let signer = MockSigner::new(&mut rng);
+        let storage = fixtures::storage(tmp.path().join("bob"), &signer).unwrap();
+
+        Peer::config(
+            "bob",
+            [9, 9, 9, 9],
+            storage,
+            peer::Config {
+                signer,
+                rng,
+                ..peer::Config::default()
+            },
+        )
+    };
+    let bob_inv = bob.storage().inventory().unwrap();
+
+    alice.track_repo(&bob_inv[0], tracking::Scope::All).unwrap();
+    alice.track_repo(&bob_inv[1], tracking::Scope::All).unwrap();
+    alice.track_repo(&bob_inv[2], tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.receive(eve.id(), Message::Subscribe(Subscribe::all()));
+    alice.receive(bob.id(), bob.refs_announcement(bob_inv[0]));
+
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_)),
+        "A refs announcement from Bob is relayed to Eve"
+    );
+
+    alice.receive(bob.id(), bob.refs_announcement(bob_inv[0]));
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "The same ref announement is not relayed"
+    );
+
+    alice.receive(bob.id(), bob.refs_announcement(bob_inv[1]));
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_)),
+        "But a different one is"
+    );
+
+    alice.receive(bob.id(), bob.refs_announcement(bob_inv[2]));
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(_)),
+        "And a third one is as well"
+    );
+}
+
+#[test]
+fn test_refs_announcement_invalid_entry() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let valid = arbitrary::gen::<Refs>(8)
+        .signed(bob.signer())
+        .unwrap()
+        .unverified();
+    let mut invalid = arbitrary::gen::<Refs>(8)
+        .signed(eve.signer())
+        .unwrap()
+        .unverified();
+    invalid.signature = valid.signature;
+
+    let announcement = |peer: &Peer<MockStorage, MockSigner>, refs: Vec<_>, delta: u64| {
+        let ann = AnnouncementMessage::from(RefsAnnouncement {
+            rid,
+            refs: refs.try_into().unwrap(),
+            timestamp: peer.timestamp() + delta,
+        });
+        Message::Announcement(ann.signed(peer.signer()))
+    };
+    let disconnected = |alice: &mut Peer<MockStorage, MockSigner>, nid: NodeId| {
+        alice.outbox().any(|io| {
+            matches!(
+                io,
+                Io::Disconnect(n, DisconnectReason::Session(session::Error::Misbehavior))
+                if n == nid
+            )
+        })
+    };
+
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.receive(eve.id(), Message::Subscribe(Subscribe::all()));
+    alice.outbox().for_each(drop);
+
+    // The valid entry is fetched, and the invalid one is dropped.
+    alice.receive(
+        bob.id(),
+        announcement(&bob, vec![valid.clone(), invalid.clone()], 0),
+    );
+    assert!(
+        alice.messages(eve.id()).next().is_none(),
+        "The announcement can't be relayed without its invalid entry"
+    );
+    assert!(alice.outbox().any(|io| matches!(
+        io,
+        Io::Fetch { rid: r, remote, .. } if r == rid && remote == bob.id()
+    )));
+    assert!(alice.sessions().get(&bob.id()).unwrap().is_connected());
+
+    // An announcement with only invalid entries is misbehavior.
+    alice.receive(eve.id(), announcement(&eve, vec![invalid.clone()], 0));
+    assert!(disconnected(&mut alice, eve.id()));
+
+    // Too many invalid entries from the same announcer get its refs announcements dropped,
+    // without disconnecting the relayer.
+    let max = MAX_INVALID_REFS as u64;
+    for delta in 1..=max {
+        alice.receive(
+            bob.id(),
+            announcement(&bob, vec![valid.clone(), invalid.clone()], delta),
+        );
+    }
+    assert!(!disconnected(&mut alice, bob.id()));
+
+    let announced = |alice: &mut Peer<MockStorage, MockSigner>| {
+        let (send, recv) = chan::bounded(1);
+        alice.command(Command::Seeds(rid, send));
+
+        let seeds = recv.recv().unwrap();
+        let seed = seeds.connected().find(|s| s.nid == bob.id()).unwrap();
+        seed.announced
+    };
+    alice.receive(bob.id(), announcement(&bob, vec![valid.clone()], max + 1));
+    assert_eq!(announced(&mut alice), Some(bob.timestamp() + max));
+
+    // Invalid entries are forgiven over time.
+    alice.elapse(INVALID_REFS_DECAY);
+    alice.receive(bob.id(), announcement(&bob, vec![valid], max + 2));
+    assert_eq!(announced(&mut alice), Some(bob.timestamp() + max + 2));
+    assert!(!disconnected(&mut alice, bob.id()));
+}
+
+/// Even if Alice is not tracking Bob, Alice will fetch Bob's refs for a repo she doesn't have.
+#[test]
+fn test_refs_announcement_fetch_trusted_no_inventory() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        Storage::open(tmp.path().join("alice")).unwrap(),
+        peer::Config::default(),
+    );
+    let bob = {
+        let mut rng = fastrand::Rng::new();
+        let signer = MockSigner::new(&mut rng);
+        let storage = fixtures::storage(tmp.path().join("bob"), &signer).unwrap();
+
+        Peer::config(
+            "bob",
+            [9, 9, 9, 9],
+            storage,
+            peer::Config {
+                signer,
+                rng,
+                ..peer::Config::default()
+            },
+        )
+    };
+    let bob_inv = bob.storage().inventory().unwrap();
+    let rid = bob_inv[0];
+
+    alice.track_repo(&rid, tracking::Scope::Trusted).unwrap();
+    alice.connect_to(&bob);
+
+    // Alice receives Bob's refs.
+    alice.receive(bob.id(), bob.refs_announcement(rid));
+
+    // Alice fetches Bob's refs as this is a new repo.
+    assert_matches!(alice.outbox().next(), Some(Io::Fetch { .. }));
+}
+
+/// Alice and Bob both have the same repo.
+///
+/// First, Alice will not fetch from Bob's `RefsAnnouncement` as Alice does not
+/// track Bob as `Trusted`.
+///
+/// Later Alice tracks Bob, and will be able to fetch Bob's refs.
+#[test]
+fn test_refs_announcement_trusted() {
+    logger::init(log::Level::Debug);
+
+    // Create MockStorage for Alice and Bob. Both will have repo with `rid`.
+    let storage_alice = arbitrary::nonempty_storage(1);
+    let rid = *storage_alice.inventory.keys().next().unwrap();
+    let storage_bob = storage_alice.clone();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage_alice);
+    let mut bob = Peer::with_storage("bob", [8, 8, 8, 8], storage_bob);
+
+    // Generate some refs for Bob under their own node_id.
+    let refs = arbitrary::gen::<Refs>(8);
+    let signed_refs = refs.signed(bob.signer()).unwrap();
+    let node_id = bob.id;
+    bob.storage_mut().insert_remote(rid, node_id, signed_refs);
+
+    // Alice uses Scope::Trusted, and did not track Bob yet.
+    alice.connect_to(&bob);
+    alice.track_repo(&rid, tracking::Scope::Trusted).unwrap();
+
+    // Alice receives Bob's refs
+    alice.receive(bob.id(), bob.refs_announcement(rid));
+
+    // Alice does not fetch as Alice is not tracking Bob.
+    assert!(
+        alice.messages(bob.id()).next().is_none(),
+        "Alice is not tracking bob yet."
+    );
+
+    // Alice starts to track Bob.
+    let (sender, receiver) = chan::bounded(1);
+    alice.command(Command::TrackNode(
+        bob.id,
+        Some(node::Alias::new("bob")),
+        sender,
+    ));
+    let policy_change = receiver
+        .recv()
+        .map_err(runtime::HandleError::from)
+        .unwrap()
+        .unwrap();
+    assert!(policy_change);
+
+    // Bob announces refs again.
+    bob.elapse(LocalDuration::from_mins(1)); // Make sure our announcement is fresh.
+    alice.receive(bob.id(), bob.refs_announcement(rid));
+    assert_matches!(alice.outbox().next(), Some(Io::Fetch { .. }));
+}
+
+#[test]
+fn test_refs_announcement_no_subscribe() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let id = arbitrary::gen(1);
+
+    alice.track_repo(&id, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.receive(bob.id(), bob.refs_announcement(rid));
+
+    assert!(alice.messages(eve.id()).next().is_none());
+}
+
+#[test]
+fn test_inventory_relay() {
+    // Topology is eve <-> alice <-> bob
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let inv = BoundedVec::try_from(arbitrary::vec(1)).unwrap();
+    let now = LocalTime::now().as_millis();
+
+    // Inventory from Bob relayed to Eve.
+    alice.connect_to(&bob);
+    alice.connect_from(&eve);
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: inv.clone(),
+                timestamp: now,
+            },
+            bob.signer(),
+        ),
+    );
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(Announcement {
+            node,
+            message: AnnouncementMessage::Inventory(InventoryAnnouncement { timestamp, .. }),
+            ..
+        }))
+        if node == bob.node_id() && timestamp == now
+    );
+    assert_matches!(
+        alice.messages(bob.id()).next(),
+        None,
+        "The inventory is not sent back to Bob"
+    );
+
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: inv.clone(),
+                timestamp: now,
+            },
+            bob.signer(),
+        ),
+    );
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        None,
+        "Sending the same inventory again doesn't trigger a relay"
+    );
+
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: inv.clone(),
+                timestamp: now + 1,
+            },
+            bob.signer(),
+        ),
+    );
+    assert_matches!(
+        alice.messages(eve.id()).next(),
+        Some(Message::Announcement(Announcement {
+            node,
+            message: AnnouncementMessage::Inventory(InventoryAnnouncement { timestamp, .. }),
+            ..
+        }))
+        if node == bob.node_id() && timestamp == now + 1,
+        "Sending a new inventory does trigger the relay"
+    );
+
+    // Inventory from Eve relayed to Bob.
+    alice.receive(
+        eve.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: inv,
+                timestamp: now,
+            },
+            eve.signer(),
+        ),
+    );
+    assert_matches!(
+        alice.messages(bob.id()).next(),
+        Some(Message::Announcement(Announcement {
+            node,
+            message: AnnouncementMessage::Inventory(InventoryAnnouncement { timestamp, .. }),
+            ..
+        }))
+        if node == eve.node_id() && timestamp == now
+    );
+}
+
+#[test]
+fn test_routing_provenance() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let carol = Peer::new("carol", [10, 10, 10, 10]);
+    let rid = arbitrary::gen::<Id>(1);
+    let inv = BoundedVec::try_from(vec![rid]).unwrap();
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+
+    // Carol's inventory is first relayed by Bob..
+    let first = alice.timestamp();
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: inv.clone(),
+                timestamp: carol.timestamp(),
+            },
+            carol.signer(),
+        ),
+    );
+    let explanation = alice.explain(&rid, &carol.id()).unwrap();
+    let provenance = explanation.provenance.unwrap();
+    assert_eq!(provenance.announcer, carol.id());
+    assert_eq!(provenance.first_relayer, bob.id());
+    assert_eq!(provenance.relayer, bob.id());
+    assert_eq!(provenance.first_received, first);
+    assert!(!explanation.prunable);
+    assert!(explanation.fetch.is_none());
+
+    // .. then refreshed by Eve.
+    alice.elapse(LocalDuration::from_mins(1));
+    let last = alice.timestamp();
+    alice.receive(
+        eve.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: inv,
+                timestamp: last,
+            },
+            carol.signer(),
+        ),
+    );
+    let explanation = alice.explain(&rid, &carol.id()).unwrap();
+    let provenance = explanation.provenance.unwrap();
+    assert_eq!(explanation.time, Some(last));
+    assert_eq!(provenance.timestamp, last);
+    assert_eq!(provenance.first_relayer, bob.id());
+    assert_eq!(provenance.first_received, first);
+    assert_eq!(provenance.relayer, eve.id());
+    assert_eq!(provenance.received, last);
+
+    // A failed fetch from Carol is scheduled to be retried.
+    alice.fetched(
+        rid,
+        carol.id(),
+        Err(crate::worker::FetchError::Io(
+            io::ErrorKind::ConnectionReset.into(),
+        )),
+        0,
+    );
+    let fetch = alice.explain(&rid, &carol.id()).unwrap().fetch.unwrap();
+    assert_eq!(fetch.time, last);
+    assert!(fetch.error.is_some());
+    assert_eq!(fetch.attempts, 1);
+    assert!(fetch.retry_at.is_some());
+
+    // Entries that are not in the routing table have no provenance.
+    let explanation = alice.explain(&rid, &bob.id()).unwrap();
+    assert_eq!(explanation.time, None);
+    assert_eq!(explanation.provenance, None);
+}
+
+#[test]
+fn test_fetch_history() {
+    use crate::worker::FetchError;
+
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+
+    let errors = [
+        (bob.id(), FetchError::Io(io::ErrorKind::TimedOut.into())),
+        // Eg. Eve disconnected in the middle of the fetch.
+        (
+            eve.id(),
+            FetchError::Io(io::ErrorKind::ConnectionReset.into()),
+        ),
+        (bob.id(), FetchError::NotFound),
+    ];
+    for (seed, err) in errors {
+        alice.fetched(rid, seed, Err(err), 0);
+        alice.elapse(LocalDuration::from_secs(1));
+    }
+    alice.fetched(rid, eve.id(), Ok(Default::default()), 0);
+
+    let history = alice.fetch_history(&rid).unwrap();
+    let history = history
+        .iter()
+        .map(|r| {
+            let failure = match &r.outcome {
+                node::FetchOutcome::Success => None,
+                node::FetchOutcome::Failed { failure, .. } => Some(*failure),
+            };
+            (r.seed, failure)
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        history,
+        vec![
+            (bob.id(), Some(node::FetchFailure::Timeout)),
+            (eve.id(), Some(node::FetchFailure::Connection)),
+            (bob.id(), Some(node::FetchFailure::NotFound)),
+            (eve.id(), None),
+        ]
+    );
+}
+
+#[test]
+fn test_fetch_size_limit() {
+    use crate::worker::FetchError;
+    use std::collections::HashSet;
+
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let missing = arbitrary::gen::<Id>(1);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        storage,
+        peer::Config {
+            config: Config {
+                connect: HashSet::from_iter([(bob.id(), bob.address()).into()]),
+                ..Config::test(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let limits = FetchPackLimit::default();
+
+    alice.connect_to(&bob);
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.track_repo(&missing, tracking::Scope::All).unwrap();
+
+    // Fetches of repositories we have are allowed more data than clones.
+    alice.fetch(rid, &bob.id());
+    assert_matches!(
+        alice.outbox().find(|io| matches!(io, Io::Fetch { .. })),
+        Some(Io::Fetch { limit, .. }) if limit == limits.update
+    );
+    alice.fetched(rid, bob.id(), Ok(Default::default()), 0);
+
+    alice.fetch(missing, &bob.id());
+    assert_matches!(
+        alice.outbox().find(|io| matches!(io, Io::Fetch { .. })),
+        Some(Io::Fetch { limit, .. }) if limit == limits.clone
+    );
+
+    // Going over the limit gets the seed disconnected, and avoided for a while,
+    // even though it's a persistent peer.
+    let err = || FetchError::SizeLimitExceeded {
+        limit: limits.clone,
+    };
+    alice.fetched(missing, bob.id(), Err(err()), limits.clone + 1);
+    assert_matches!(
+        alice.outbox().find(|io| matches!(io, Io::Disconnect(..))),
+        Some(Io::Disconnect(nid, DisconnectReason::Fetch(FetchError::SizeLimitExceeded { .. })))
+        if nid == bob.id()
+    );
+    alice.disconnected(bob.id(), &DisconnectReason::Fetch(err()));
+    alice.elapse(LocalDuration::from_mins(30));
+    assert!(!alice
+        .outbox()
+        .any(|io| matches!(io, Io::Connect(nid, _) if nid == bob.id())));
+
+    alice.elapse(LocalDuration::from_mins(30));
+    assert!(alice
+        .outbox()
+        .any(|io| matches!(io, Io::Connect(nid, _) if nid == bob.id())));
+}
+
+#[test]
+fn test_sync_watermarks() {
+    use radicle::node::watermark::Store as _;
+    use radicle::storage::RefUpdate;
+    use std::collections::HashSet;
+
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let refs = arbitrary::gen::<Refs>(8).signed(bob.signer()).unwrap();
+    let updated = vec![RefUpdate::Created {
+        name: git::refname!("refs/heads/master"),
+        oid: arbitrary::oid(),
+    }];
+    let announced = |alice: &mut Peer<MockStorage, MockSigner>| {
+        alice.messages(eve.id()).any(|m| {
+            matches!(
+                m,
+                Message::Announcement(Announcement {
+                    message: AnnouncementMessage::Refs(_),
+                    ..
+                })
+            )
+        })
+    };
+
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+
+    // Alice fetches Bob's refs from Eve, and announces them.
+    alice
+        .storage_mut()
+        .insert_remote(rid, bob.id(), refs.clone());
+    alice.fetched(
+        rid,
+        eve.id(),
+        Ok((updated.clone(), HashSet::from([bob.id()]), vec![], vec![])),
+        0,
+    );
+    assert!(announced(&mut alice));
+    assert_eq!(
+        alice.watermarks().get(&rid, &bob.id()).unwrap(),
+        Some(node::watermark::Watermark {
+            fetched: Some(refs.signature),
+            announced: Some(refs.signature),
+        })
+    );
+
+    // Refs that were already announced aren't announced again.
+    alice.fetched(
+        rid,
+        eve.id(),
+        Ok((updated, HashSet::from([bob.id()]), vec![], vec![])),
+        0,
+    );
+    assert!(!announced(&mut alice));
+
+    // An announcement of refs we already fetched isn't fetched.
+    let ann = AnnouncementMessage::from(RefsAnnouncement {
+        rid,
+        refs: vec![refs.unverified()].try_into().unwrap(),
+        timestamp: bob.timestamp(),
+    });
+    alice.receive(bob.id(), Message::Announcement(ann.signed(bob.signer())));
+    assert!(!alice.outbox().any(|io| matches!(io, Io::Fetch { .. })));
+
+    // Untracking the repository removes its watermarks.
+    alice.untrack_repo(&rid).unwrap();
+    assert!(alice.watermarks().watermarks(&rid).unwrap().is_empty());
+}
+
+#[test]
+fn test_fetch_minimal() {
+    use radicle::node::watermark::Store as _;
+    use std::collections::HashSet;
+
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let refs = arbitrary::gen::<Refs>(8).signed(bob.signer()).unwrap();
+    let (send, recv) = chan::bounded(1);
+
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
+
+    // Only the delegates are fetched, even though the repository is tracked with `all`.
+    alice.command(Command::FetchMinimal(rid, bob.id(), send));
+    assert_matches!(
+        alice.fetches().next(),
+        Some((r, _, Namespaces::Trusted(_))) if r == rid
+    );
+    alice
+        .storage_mut()
+        .insert_remote(rid, bob.id(), refs.clone());
+    alice.fetched(
+        rid,
+        bob.id(),
+        Ok((vec![], HashSet::from([bob.id()]), vec![], vec![])),
+        0,
+    );
+    assert_matches!(recv.recv(), Ok(Ok(node::FetchResult::Success { .. })));
+    assert!(alice.watermarks().is_partial(&rid).unwrap());
+
+    // An announcement of refs we already fetched completes the repository.
+    let ann = AnnouncementMessage::from(RefsAnnouncement {
+        rid,
+        refs: vec![refs.unverified()].try_into().unwrap(),
+        timestamp: bob.timestamp(),
+    });
+    alice.receive(bob.id(), Message::Announcement(ann.signed(bob.signer())));
+    assert_matches!(
+        alice.fetches().next(),
+        Some((r, _, Namespaces::All)) if r == rid
+    );
+    alice.fetched(
+        rid,
+        bob.id(),
+        Ok((vec![], HashSet::new(), vec![], vec![])),
+        0,
+    );
+    assert!(!alice.watermarks().is_partial(&rid).unwrap());
+}
+
+#[test]
+fn test_persistent_peer_reconnect_attempt() {
+    use std::collections::HashSet;
+
+    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
+    let mut eve = Peer::new("eve", [9, 9, 9, 9]);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                connect: HashSet::from_iter([
+                    (bob.id(), bob.address()).into(),
+                    (eve.id(), eve.address()).into(),
+                ]),
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+
+    let mut sim = Simulation::new(
+        LocalTime::now(),
+        alice.rng.clone(),
+        simulator::Options::default(),
+    )
+    .initialize([&mut alice, &mut bob, &mut eve]);
+
+    sim.run_while([&mut alice, &mut bob, &mut eve], |s| !s.is_settled());
+
+    let ips = alice
+        .sessions()
+        .connected()
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    assert!(ips.contains(&bob.id()));
+    assert!(ips.contains(&eve.id()));
+
+    // ... Negotiated ...
+    //
+    // Now let's disconnect a peer.
+
+    // A non-transient disconnect, such as one due to peer misbehavior will still trigger a
+    // a reconnection, since this is a persistent peer.
+    let reason = DisconnectReason::Session(session::Error::Misbehavior);
+
+    for _ in 0..3 {
+        alice.disconnected(bob.id(), &reason);
+        alice.elapse(service::MAX_RECONNECTION_DELTA);
+        alice
+            .outbox()
+            .find(|io| matches!(io, Io::Connect(a, _) if a == &bob.id()))
+            .unwrap();
+
+        alice.attempted(bob.id(), bob.address());
+    }
+}
+
+#[test]
+fn test_persistent_peer_user_disconnect() {
+    use std::collections::HashSet;
+
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                connect: HashSet::from_iter([(bob.id, bob.addr()).into()]),
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let cooloff = Limits::default().disconnect_cooloff;
+    let is_connect = |o: &Io| matches!(o, Io::Connect(id, _) if id == &bob.id());
+
+    alice.connect_to(&bob);
+    alice.command(Command::Disconnect(bob.id()));
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+
+    let session = alice.sessions().get(&bob.id()).unwrap();
+    assert!(session.is_disconnected());
+    assert!(session.user_disconnected);
+
+    // Bob isn't reconnected to during the cool-off, even though he's a persistent peer.
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed + service::MAX_RECONNECTION_DELTA < cooloff {
+        alice.elapse(service::MAX_RECONNECTION_DELTA);
+        elapsed = elapsed + service::MAX_RECONNECTION_DELTA;
+
+        assert!(!alice.outbox().any(|o| is_connect(&o)));
+    }
+
+    // Once the cool-off has passed, Bob is reconnected to.
+    alice.elapse(service::MAX_RECONNECTION_DELTA);
+    assert!(alice.outbox().any(|o| is_connect(&o)));
+
+    alice.attempted(bob.id(), bob.addr());
+    alice.connected(bob.id(), bob.addr(), Link::Outbound);
+    assert!(!alice.sessions().get(&bob.id()).unwrap().user_disconnected);
+
+    // Connecting explicitly lifts the cool-off.
+    alice.command(Command::Disconnect(bob.id()));
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+    alice.outbox().for_each(drop);
+    alice.command(Command::Connect(
+        bob.id(),
+        bob.addr(),
+        ConnectOptions::default(),
+    ));
+    assert!(alice.outbox().any(|o| is_connect(&o)));
+}
+
+#[test]
+fn test_persistent_peer_reconnect_success() {
+    use std::collections::HashSet;
+
+    let bob = Peer::config(
+        "bob",
+        [9, 9, 9, 9],
+        MockStorage::empty(),
+        peer::Config::default(),
+    );
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                connect: HashSet::from_iter([(bob.id, bob.addr()).into()]),
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    alice.connect_to(&bob);
+
+    // A transient error such as this will cause Alice to attempt a reconnection.
+    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
+    alice.disconnected(bob.id(), &DisconnectReason::Connection(error));
+    alice.elapse(service::MIN_RECONNECTION_DELTA);
+    alice.elapse(service::MIN_RECONNECTION_DELTA); // Trigger a second wakeup to test idempotence.
+
+    alice
+        .outbox()
+        .find_map(|o| match o {
+            Io::Connect(id, _) => Some(id),
+            _ => None,
+        })
+        .expect("Alice attempts a re-connection");
+
+    alice.attempted(bob.id(), bob.addr());
+    alice.connected(bob.id(), bob.addr(), Link::Outbound);
+}
+
+#[test]
+fn test_persistent_peer_give_up() {
+    use std::collections::HashSet;
+
+    let bob = Peer::new("bob", [9, 9, 9, 9]);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                connect: HashSet::from_iter([(bob.id, bob.addr()).into()]),
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let retention = Limits::default().session_retention;
+    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionRefused));
+    let is_connect = |o: &Io| matches!(o, Io::Connect(id, _) if id == &bob.id());
+
+    alice.connect_to(&bob);
+
+    // Bob can't be reached anymore, and is retried until we give up on him.
+    for _ in 0..=service::MAX_RECONNECTION_ATTEMPTS + 1 {
+        alice.disconnected(bob.id(), &DisconnectReason::Connection(error.clone()));
+
+        if alice
+            .sessions()
+            .get(&bob.id())
+            .unwrap()
+            .gave_up_until
+            .is_some()
+        {
+            break;
+        }
+        alice.elapse(service::MAX_RECONNECTION_DELTA);
+        assert!(alice.outbox().any(|o| is_connect(&o)));
+
+        alice.attempted(bob.id(), bob.addr());
+    }
+    let session = alice.sessions().get(&bob.id()).unwrap();
+    assert!(session.is_disconnected());
+    assert!(session.attempts() > service::MAX_RECONNECTION_ATTEMPTS);
+    assert_eq!(session.gave_up_until, Some(alice.local_time() + retention));
+
+    // His session is kept, but he isn't retried until the retention period passes.
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed + service::MAX_RECONNECTION_DELTA < retention {
+        alice.elapse(service::MAX_RECONNECTION_DELTA);
+        elapsed = elapsed + service::MAX_RECONNECTION_DELTA;
+
+        assert!(!alice.outbox().any(|o| is_connect(&o)));
+        assert!(alice.sessions().contains_key(&bob.id()));
+    }
+    alice.elapse(service::MAX_RECONNECTION_DELTA);
+    assert!(alice.outbox().any(|o| is_connect(&o)));
+
+    alice.attempted(bob.id(), bob.addr());
+    alice.connected(bob.id(), bob.addr(), Link::Outbound);
+
+    let session = alice.sessions().get(&bob.id()).unwrap();
+    assert_eq!(session.attempts(), 0);
+    assert_eq!(session.gave_up_until, None);
+}
+
+#[test]
+fn test_session_retention() {
+    use std::collections::HashSet;
+
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                connect: HashSet::from_iter([
+                    (bob.id, bob.addr()).into(),
+                    (eve.id, eve.addr()).into(),
+                ]),
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let retention = Limits::default().session_retention;
+    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+
+    alice.disconnected(bob.id(), &DisconnectReason::Connection(error.clone()));
+    alice.disconnected(eve.id(), &DisconnectReason::Connection(error));
+
+    // Eve is no longer a configured peer once disconnected, so she isn't reconnected to,
+    // but her session is kept.
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::RemovePeer(eve.id(), send));
+    assert!(recv.recv().unwrap());
+    assert!(alice.sessions().get(&eve.id()).unwrap().is_disconnected());
+
+    // Until the retention period passes.
+    let mut elapsed = LocalDuration::from_secs(0);
+    while elapsed + PRUNE_INTERVAL < retention {
+        alice.elapse(PRUNE_INTERVAL);
+        elapsed = elapsed + PRUNE_INTERVAL;
+
+        assert!(alice.sessions().contains_key(&eve.id()));
+    }
+    alice.elapse(PRUNE_INTERVAL);
+
+    assert!(!alice.sessions().contains_key(&eve.id()));
+    assert!(
+        alice.sessions().contains_key(&bob.id()),
+        "The sessions of persistent peers are kept"
+    );
+}
+
+#[test]
+fn test_add_peer() {
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let is_connect = |o: &Io| matches!(o, Io::Connect(id, _) if id == &bob.id());
+    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
+
+    alice.initialize();
+    alice.outbox().for_each(drop);
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::AddPeer(
+        PeerEntry {
+            id: bob.id(),
+            address: bob.addr(),
+            alias: Some(node::Alias::new("bob")),
+            persistent: true,
+        },
+        send,
+    ));
+    assert!(recv.recv().unwrap());
+    assert!(
+        alice.outbox().any(|o| is_connect(&o)),
+        "Persistent peers are connected to right away"
+    );
+    assert_eq!(alice.config().peer(&bob.id()), Some(&bob.addr()));
+
+    alice.attempted(bob.id(), bob.addr());
+    alice.connected(bob.id(), bob.addr(), Link::Outbound);
+
+    // The added peer is reconnected to, like peers configured on startup.
+    alice.disconnected(bob.id(), &DisconnectReason::Connection(error.clone()));
+    alice.elapse(service::MAX_RECONNECTION_DELTA);
+    assert!(alice.outbox().any(|o| is_connect(&o)));
+
+    alice.attempted(bob.id(), bob.addr());
+    alice.connected(bob.id(), bob.addr(), Link::Outbound);
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::RemovePeer(bob.id(), send));
+    assert!(recv.recv().unwrap());
+    assert!(!alice.config().is_persistent(&bob.id()));
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::RemovePeer(bob.id(), send));
+    assert!(!recv.recv().unwrap(), "Bob was already removed");
+}
+
+#[test]
+fn test_maintain_connections() {
+    // Peers alice starts out connected to.
+    let connected = vec![
+        Peer::new("connected", [8, 8, 8, 1]),
+        Peer::new("connected", [8, 8, 8, 2]),
+        Peer::new("connected", [8, 8, 8, 3]),
+    ];
+    // Peers alice will connect to once the others disconnect.
+    let mut unconnected = vec![
+        Peer::new("unconnected", [9, 9, 9, 1]),
+        Peer::new("unconnected", [9, 9, 9, 2]),
+        Peer::new("unconnected", [9, 9, 9, 3]),
+    ];
+
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+
+    for peer in connected.iter() {
+        alice.connect_to(peer);
+    }
+    assert_eq!(
+        connected.len(),
+        alice.sessions().len(),
+        "alice should be connected to the first set of peers"
+    );
+    // We now import the other addresses.
+    alice.import_addresses(&unconnected);
+
+    // A transient error such as this will cause Alice to attempt a reconnection.
+    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
+    for peer in connected.iter() {
+        alice.disconnected(peer.id(), &DisconnectReason::Connection(error.clone()));
+
+        let id = alice
+            .outbox()
+            .find_map(|o| match o {
+                Io::Connect(id, _) => Some(id),
+                _ => None,
+            })
+            .expect("Alice connects to a new peer");
+        assert!(id != peer.id());
+        unconnected.retain(|p| p.id() != id);
+    }
+    assert!(
+        unconnected.is_empty(),
+        "alice should connect to all unconnected peers"
+    );
+}
+
+#[test]
+fn test_maintain_connections_failed_attempt() {
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+
+    alice.connect_to(&bob);
+    // Make sure Alice knows about Eve.
+    alice.receive(bob.id, eve.node_announcement());
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+    alice
+        .outbox()
+        .find(|o| matches!(o, Io::Connect(id, _) if id == &eve.id))
+        .expect("Alice attempts Eve");
+    alice.attempted(eve.id, eve.addr());
+
+    // Disconnect Eve and make sure Alice doesn't try to re-connect immediately.
+    alice.disconnected(eve.id(), &DisconnectReason::Command);
+    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(_, _))));
+
+    // Now pass some time and try again.
+    alice.elapse(MAX_RECONNECTION_DELTA);
+    alice
+        .outbox()
+        .find(|o| matches!(o, Io::Connect(id, _) if id == &eve.id))
+        .expect("Alice attempts Eve again");
+
+    // Disconnect Eve and make sure Alice doesn't try to re-connect immediately.
+    alice.disconnected(eve.id(), &DisconnectReason::Command);
+    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(_, _))));
+    // Or even after some short time..
+    alice.elapse(MIN_RECONNECTION_DELTA);
+    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(_, _))));
+}
+
+#[test]
+fn test_maintain_connections_proxy() {
+    let onion = |port: u16| {
+        Address::from_str(&format!(
+            "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion:{port}"
+        ))
+        .unwrap()
+    };
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    // Carol is only reachable over Tor.
+    let carol = Peer::new("carol", [9, 9, 9, 9]);
+    // Dave is reachable both over Tor, and directly.
+    let dave = Peer::new("dave", [10, 10, 10, 10]);
+    let addresses = [
+        (carol.id(), vec![onion(8776)]),
+        (dave.id(), vec![onion(8777), dave.address()]),
+    ];
+    let import = |alice: &mut Peer<MockStorage, MockSigner>| {
+        let timestamp = alice.timestamp();
+
+        for (nid, addrs) in &addresses {
+            alice
+                .addresses_mut()
+                .insert(
+                    nid,
+                    node::Features::SEED,
+                    node::Alias::new("peer"),
+                    0,
+                    timestamp,
+                    addrs
+                        .iter()
+                        .map(|a| node::KnownAddress::new(a.clone(), node::address::Source::Peer)),
+                )
+                .unwrap();
+        }
+    };
+
+    // Without a proxy, Alice connects to Dave's direct address, and doesn't dial Carol.
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    alice.connect_to(&bob);
+    import(&mut alice);
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+
+    let dialed = alice
+        .outbox()
+        .filter_map(|o| match o {
+            Io::Connect(nid, addr) => Some((nid, addr)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(dialed, vec![(dave.id(), dave.address())]);
+
+    // Carol's address is unsupported, but wasn't attempted, so that it's not backed off.
+    let entries = alice.addresses().entries().unwrap().collect::<Vec<_>>();
+    for (nid, ka) in &entries {
+        assert_eq!(ka.unsupported, ka.addr.requires_proxy(), "{nid}: {ka:?}");
+    }
+    let (_, ka) = entries.iter().find(|(nid, _)| *nid == carol.id()).unwrap();
+    assert_eq!(ka.last_attempt, None);
+
+    // Connecting explicitly doesn't dial either.
+    alice.command(Command::Connect(
+        carol.id(),
+        onion(8776),
+        ConnectOptions::default(),
+    ));
+    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(..))));
+
+    // With a proxy, Alice connects to Carol's onion address, and still prefers Dave's
+    // direct address.
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                proxy: Some(([127, 0, 0, 1], 9050).into()),
+                ..Config::test(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    alice.connect_to(&bob);
+    import(&mut alice);
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+
+    let mut dialed = alice
+        .outbox()
+        .filter_map(|o| match o {
+            Io::Connect(nid, addr) => Some((nid, addr)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    dialed.sort_by_key(|(nid, _)| *nid == dave.id());
+    assert_eq!(
+        dialed,
+        vec![(carol.id(), onion(8776)), (dave.id(), dave.address())]
+    );
+}
+
+#[test]
+fn test_track_repo_subscribe() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let rid = arbitrary::gen::<Id>(1);
+    let (send, recv) = chan::bounded(1);
+
+    alice.connect_to(&bob);
+    alice.command(Command::TrackRepo(
+        rid,
+        tracking::Scope::default(),
+        tracking::Origin::default(),
+        send,
+    ));
+    assert!(recv.recv().unwrap().unwrap());
+
+    assert_matches!(
+        alice.messages(bob.id).next(),
+        Some(Message::Subscribe(Subscribe {
+            filter,
+            since,
+            ..
+        })) if since == alice.clock().as_millis() && filter.contains(&rid)
+    );
+}
+
+#[test]
+fn test_track_repo_fetch() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let rid = arbitrary::gen::<Id>(1);
+
+    // Without any known seeds, there's nothing to fetch from.
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::TrackRepoFetch(rid, tracking::Scope::All, send));
+    assert_matches!(
+        recv.recv().unwrap(),
+        Ok(node::Tracked {
+            updated: true,
+            fetch: node::TrackFetch::NoSeeds
+        })
+    );
+
+    alice.import_addresses([&bob]);
+    alice.connect_to(&bob);
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: bob.timestamp(),
+            },
+            bob.signer(),
+        ),
+    );
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+    alice.outbox().for_each(drop);
+
+    // Bob is a known seed, but isn't connected: we connect to Bob, and fetch once connected.
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::TrackRepoFetch(rid, tracking::Scope::All, send));
+    assert_matches!(
+        recv.recv().unwrap(),
+        Ok(node::Tracked { updated: false, fetch: node::TrackFetch::Queued { seed } }) if seed == bob.id
+    );
+    assert_matches!(alice.fetches().next(), None);
+
+    alice.attempted(bob.id(), bob.address());
+    alice.connected(bob.id(), bob.address(), Link::Outbound);
+    assert_matches!(alice.fetches().next(), Some((r, nid, _)) if r == rid && nid == bob.id);
+}
+
+#[test]
+fn test_subscribe_policy() {
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let rid = arbitrary::gen::<Id>(1);
+    let subscription = |alice: &mut Peer<MockStorage, MockSigner>, nid: NodeId| {
+        alice.messages(nid).find_map(|m| match m {
+            Message::Subscribe(Subscribe { filter, .. }) => Some(filter),
+            _ => None,
+        })
+    };
+
+    for (subscribe, policy, outbound, inbound) in [
+        (
+            node::config::SubscribePolicy::All,
+            tracking::Policy::Track,
+            true,
+            true,
+        ),
+        (
+            node::config::SubscribePolicy::All,
+            tracking::Policy::Block,
+            true,
+            true,
+        ),
+        (
+            node::config::SubscribePolicy::OutboundOnly,
+            tracking::Policy::Track,
+            true,
+            false,
+        ),
+        (
+            node::config::SubscribePolicy::OutboundOnly,
+            tracking::Policy::Block,
+            true,
+            true,
+        ),
+        (
+            node::config::SubscribePolicy::None,
+            tracking::Policy::Track,
+            false,
+            false,
+        ),
+        (
+            node::config::SubscribePolicy::None,
+            tracking::Policy::Block,
+            false,
+            false,
+        ),
+    ] {
+        let mut alice = Peer::config(
+            "alice",
+            [7, 7, 7, 7],
+            MockStorage::empty(),
+            peer::Config {
+                config: Config {
+                    subscribe,
+                    policy,
+                    ..Config::test(node::Alias::new("alice"))
+                },
+                ..peer::Config::default()
+            },
+        );
+        alice.initialize();
+        alice.track_repo(&rid, tracking::Scope::All).unwrap();
+
+        // Bob is connected to by Alice.
+        alice.command(Command::Connect(
+            bob.id(),
+            bob.address(),
+            ConnectOptions::default(),
+        ));
+        alice.attempted(bob.id(), bob.address());
+        alice.connected(bob.id(), bob.address(), Link::Outbound);
+
+        // Eve connects to Alice.
+        alice.connected(eve.id(), eve.address(), Link::Inbound);
+
+        let case = format!("{subscribe:?} with {policy:?} policy");
+        let outbound_filter = subscription(&mut alice, bob.id());
+        let inbound_filter = subscription(&mut alice, eve.id());
+
+        assert_eq!(outbound_filter.is_some(), outbound, "{case}: outbound");
+        assert_eq!(inbound_filter.is_some(), inbound, "{case}: inbound");
+
+        // When tracking selectively, we only subscribe to the repositories we track.
+        if policy == tracking::Policy::Block {
+            for filter in outbound_filter.iter().chain(inbound_filter.iter()) {
+                assert!(filter.contains(&rid), "{case}");
+                assert_ne!(*filter, Filter::default(), "{case}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fetch_missing_inventory() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let (send, recv) = chan::bounded(1);
+    let now = LocalTime::now();
+
+    alice.connect_to(&bob);
+    alice.connect_to(&eve);
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: now.as_millis(),
+            },
+            bob.signer(),
+        ),
+    );
+    alice.receive(
+        eve.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: now.as_millis(),
+            },
+            eve.signer(),
+        ),
     );
+    alice.command(Command::TrackRepo(
+        rid,
+        node::tracking::Scope::All,
+        tracking::Origin::default(),
+        send,
+    ));
+    alice.outbox().for_each(drop);
 
-    alice.receive(bob.id(), bob.refs_announcement(bob_inv[2]));
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(_)),
-        "And a third one is as well"
+    assert!(recv.recv().unwrap().unwrap());
+
+    alice.elapse(service::SYNC_INTERVAL);
+    assert_eq!(
+        alice
+            .outbox()
+            .filter(|m| matches!(m, Io::Fetch { .. }))
+            .count(),
+        1,
+        "The repository is only fetched from one of its seeds"
     );
 }
 
-/// Even if Alice is not tracking Bob, Alice will fetch Bob's refs for a repo she doesn't have.
 #[test]
-fn test_refs_announcement_fetch_trusted_no_inventory() {
-    logger::init(log::Level::Debug);
-
-    let tmp = tempfile::tempdir().unwrap();
+fn test_fetch_missing_inventory_max() {
+    let rids = arbitrary::set::<Id>(5..=5);
+    let seeds = (0..3)
+        .map(|i| Peer::new("seed", [8, 8, 8, i + 1]))
+        .collect::<Vec<_>>();
     let mut alice = Peer::config(
         "alice",
         [7, 7, 7, 7],
-        Storage::open(tmp.path().join("alice")).unwrap(),
-        peer::Config::default(),
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                limits: Limits {
+                    fetch_missing_max: 2,
+                    ..Limits::default()
+                },
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
     );
-    let bob = {
-        let mut rng = fastrand::Rng::new();
-        let signer = MockSigner::new(&mut rng);
-        let storage = fixtures::storage(tmp.path().join("bob"), &signer).unwrap();
+    let now = LocalTime::now();
 
-        Peer::config(
-            "bob",
-            [9, 9, 9, 9],
-            storage,
-            peer::Config {
-                signer,
-                rng,
-                ..peer::Config::default()
-            },
-        )
+    for seed in &seeds {
+        alice.connect_to(seed);
+        alice.receive(
+            seed.id(),
+            Message::inventory(
+                InventoryAnnouncement {
+                    inventory: rids.iter().cloned().collect::<Vec<_>>().try_into().unwrap(),
+                    timestamp: now.as_millis(),
+                },
+                seed.signer(),
+            ),
+        );
+    }
+    for rid in &rids {
+        alice.track_repo(rid, tracking::Scope::All).unwrap();
+    }
+    alice.outbox().for_each(drop);
+
+    // Number of seeds each repository is being fetched from.
+    let fetching = |alice: &Peer<MockStorage, MockSigner>| {
+        rids.iter()
+            .map(|rid| {
+                alice
+                    .sessions()
+                    .values()
+                    .filter(|s| s.is_fetching(rid))
+                    .count()
+            })
+            .filter(|n| *n > 0)
+            .collect::<Vec<_>>()
     };
-    let bob_inv = bob.storage().inventory().unwrap();
-    let rid = bob_inv[0];
 
-    alice.track_repo(&rid, tracking::Scope::Trusted).unwrap();
-    alice.connect_to(&bob);
+    // Each sync fetches up to the maximum, each repository from a single seed, and leaves
+    // the rest for the next sync.
+    for expected in [2, 4, 5] {
+        alice.elapse(service::SYNC_INTERVAL);
+        assert_eq!(fetching(&alice), vec![1; expected]);
+    }
+}
+#[test]
+fn test_seeds_spread() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let seeds = (0..4)
+        .map(|i| Peer::new("seed", [8, 8, 8, i + 1]))
+        .collect::<Vec<_>>();
+    let now = LocalTime::now();
 
-    // Alice receives Bob's refs.
-    alice.receive(bob.id(), bob.refs_announcement(rid));
+    for seed in &seeds {
+        alice.connect_to(seed);
+        alice.receive(
+            seed.id(),
+            Message::inventory(
+                InventoryAnnouncement {
+                    inventory: vec![rid].try_into().unwrap(),
+                    timestamp: now.as_millis(),
+                },
+                seed.signer(),
+            ),
+        );
+    }
 
-    // Alice fetches Bob's refs as this is a new repo.
-    assert_matches!(alice.outbox().next(), Some(Io::Fetch { .. }));
+    let mut selected = HashMap::<NodeId, usize>::new();
+    for _ in 0..100 {
+        let (send, recv) = chan::bounded(1);
+        alice.command(Command::Seeds(rid, send));
+
+        let seeds = recv.recv().unwrap();
+        let seed = seeds.select(1).pop().unwrap();
+
+        *selected.entry(seed.nid).or_default() += 1;
+    }
+    // Every lookup returns seeds in a different order, so that the same seed isn't
+    // always the one fetched from.
+    assert_eq!(selected.len(), seeds.len(), "{selected:?}");
 }
 
-/// Alice and Bob both have the same repo.
-///
-/// First, Alice will not fetch from Bob's `RefsAnnouncement` as Alice does not
-/// track Bob as `Trusted`.
-///
-/// Later Alice tracks Bob, and will be able to fetch Bob's refs.
 #[test]
-fn test_refs_announcement_trusted() {
-    logger::init(log::Level::Debug);
-
-    // Create MockStorage for Alice and Bob. Both will have repo with `rid`.
-    let storage_alice = arbitrary::nonempty_storage(1);
-    let rid = *storage_alice.inventory.keys().next().unwrap();
-    let storage_bob = storage_alice.clone();
-    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage_alice);
-    let mut bob = Peer::with_storage("bob", [8, 8, 8, 8], storage_bob);
+fn test_seeds_fresh() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage.clone());
+    let mut bob = Peer::with_storage("bob", [8, 8, 8, 8], storage.clone());
+    let carol = Peer::with_storage("carol", [9, 9, 9, 9], storage);
 
-    // Generate some refs for Bob under their own node_id.
-    let refs = arbitrary::gen::<Refs>(8);
-    let signed_refs = refs.signed(bob.signer()).unwrap();
-    let node_id = bob.id;
-    bob.storage_mut().insert_remote(rid, node_id, signed_refs);
+    // Bob has refs that Alice doesn't have, while Carol is in sync with Alice.
+    let refs = arbitrary::gen::<Refs>(8).signed(bob.signer()).unwrap();
+    let bob_id = bob.id;
+    bob.storage_mut().insert_remote(rid, bob_id, refs);
 
-    // Alice uses Scope::Trusted, and did not track Bob yet.
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
     alice.connect_to(&bob);
-    alice.track_repo(&rid, tracking::Scope::Trusted).unwrap();
+    alice.connect_to(&carol);
+
+    for peer in [&bob, &carol] {
+        alice.receive(
+            peer.id(),
+            Message::inventory(
+                InventoryAnnouncement {
+                    inventory: vec![rid].try_into().unwrap(),
+                    timestamp: peer.timestamp(),
+                },
+                peer.signer(),
+            ),
+        );
+        alice.receive(peer.id(), peer.refs_announcement(rid));
+    }
 
-    // Alice receives Bob's refs
-    alice.receive(bob.id(), bob.refs_announcement(rid));
+    for _ in 0..10 {
+        let (send, recv) = chan::bounded(1);
+        alice.command(Command::Seeds(rid, send));
 
-    // Alice does not fetch as Alice is not tracking Bob.
-    assert!(
-        alice.messages(bob.id()).next().is_none(),
-        "Alice is not tracking bob yet."
+        let seeds = recv.recv().unwrap();
+        let selected = seeds.select(2);
+
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].nid, bob.id);
+        assert_eq!(selected[0].announced, Some(bob.timestamp()));
+        assert_eq!(selected[0].fresh, Some(true));
+        assert_eq!(selected[1].nid, carol.id);
+        assert_eq!(selected[1].announced, Some(carol.timestamp()));
+        assert_eq!(selected[1].fresh, Some(false));
+    }
+}
+
+#[test]
+fn test_announcement_request() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage.clone());
+    let mut bob = Peer::with_storage("bob", [8, 8, 8, 8], storage.clone());
+    let carol = Peer::with_storage("carol", [9, 9, 9, 9], storage);
+    let ann = carol.refs_announcement(rid);
+    let Message::Announcement(carols) = ann.clone() else {
+        unreachable!()
+    };
+    let response = Message::AnnouncementResponse(carols);
+
+    // Bob has Carol's refs announcement in his gossip store.
+    bob.track_repo(&rid, tracking::Scope::All).unwrap();
+    bob.connect_to(&carol);
+    bob.receive(carol.id(), ann.clone());
+    bob.connect_from(&alice);
+
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.receive(
+        bob.id(),
+        Message::node(
+            NodeAnnouncement {
+                features: node::Features::SEED.with(node::Features::PULL),
+                timestamp: bob.timestamp(),
+                alias: node::Alias::new("bob"),
+                addresses: Some(
+                    std::net::SocketAddr::from(([8, 8, 8, 8], node::DEFAULT_PORT)).into(),
+                )
+                .into(),
+                nonce: 0,
+            },
+            bob.signer(),
+        ),
+    );
+    // Alice learns that Bob seeds the repository, but not which refs he has.
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: bob.timestamp(),
+            },
+            bob.signer(),
+        ),
+    );
+    let req = alice
+        .messages(bob.id())
+        .find_map(|m| match m {
+            Message::AnnouncementRequest(req) => Some(req),
+            _ => None,
+        })
+        .expect("Alice requests Bob's announcements");
+    assert_eq!(req.rid, rid);
+    assert!(req.kinds.has(AnnouncementKinds::REFS));
+
+    // Bob answers from his gossip store.
+    bob.receive(alice.id(), Message::AnnouncementRequest(req.clone()));
+    let msgs = bob.messages(alice.id()).collect::<Vec<_>>();
+    assert!(msgs.contains(&response), "{msgs:?}");
+
+    // Bob's answer counts as a response from him, like a pong.
+    alice.clock_mut().elapse(LocalDuration::from_secs(1));
+    for msg in msgs {
+        alice.receive(bob.id(), msg);
+    }
+    assert_eq!(
+        alice
+            .sessions()
+            .get(&bob.id)
+            .unwrap()
+            .last_protocol_response_at,
+        alice.local_time()
     );
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::Seeds(rid, send));
 
-    // Alice starts to track Bob.
-    let (sender, receiver) = chan::bounded(1);
-    alice.command(Command::TrackNode(
-        bob.id,
-        Some(node::Alias::new("bob")),
-        sender,
-    ));
-    let policy_change = receiver.recv().map_err(runtime::HandleError::from).unwrap();
-    assert!(policy_change);
+    let (_, disconnected) = recv.recv().unwrap().partition();
+    let seed = disconnected.iter().find(|s| s.nid == carol.id()).unwrap();
+    assert_eq!(seed.announced, Some(carol.timestamp()));
 
-    // Bob announces refs again.
-    bob.elapse(LocalDuration::from_mins(1)); // Make sure our announcement is fresh.
-    alice.receive(bob.id(), bob.refs_announcement(rid));
-    assert_matches!(alice.outbox().next(), Some(Io::Fetch { .. }));
+    // Requests beyond the rate limit aren't answered.
+    for _ in 1..ANNOUNCEMENT_REQUEST_RATE.capacity {
+        bob.receive(alice.id(), Message::AnnouncementRequest(req.clone()));
+        assert!(bob.messages(alice.id()).any(|m| m == response));
+    }
+    bob.receive(alice.id(), Message::AnnouncementRequest(req));
+    assert_eq!(bob.messages(alice.id()).count(), 0);
 }
 
 #[test]
-fn test_refs_announcement_no_subscribe() {
+fn test_announcement_response() {
     let storage = arbitrary::nonempty_storage(1);
     let rid = *storage.inventory.keys().next().unwrap();
-    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
-    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage.clone());
+    let mut bob = Peer::with_storage("bob", [8, 8, 8, 8], storage);
     let eve = Peer::new("eve", [9, 9, 9, 9]);
-    let id = arbitrary::gen(1);
+    let refs = arbitrary::gen::<Refs>(8).signed(bob.signer()).unwrap();
+    let bob_id = bob.id;
+
+    // Bob seeds the repository, and has refs of his own.
+    bob.storage_mut().insert_remote(rid, bob_id, refs);
+    bob.track_repo(&rid, tracking::Scope::All).unwrap();
+    bob.connect_from(&alice);
+
+    // Bob answers with the same signed announcement of his refs each time.
+    let req = AnnouncementRequest {
+        rid,
+        kinds: AnnouncementKinds::REFS,
+    };
+    bob.receive(alice.id(), Message::AnnouncementRequest(req.clone()));
+    let responses = bob.messages(alice.id()).collect::<Vec<_>>();
+    assert_matches!(
+        responses.as_slice(),
+        [Message::AnnouncementResponse(ann)] if ann.node == bob_id
+    );
+    bob.receive(alice.id(), Message::AnnouncementRequest(req));
+    assert_eq!(bob.messages(alice.id()).collect::<Vec<_>>(), responses);
 
-    alice.track_repo(&id, tracking::Scope::All).unwrap();
+    // Alice handles the response, but doesn't relay it to her other peers.
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
     alice.connect_to(&bob);
     alice.connect_to(&eve);
-    alice.receive(bob.id(), bob.refs_announcement(rid));
+    alice.receive(eve.id(), Message::Subscribe(Subscribe::all()));
+    alice.messages(eve.id()).for_each(drop);
 
+    for msg in responses {
+        alice.receive(bob.id(), msg);
+    }
     assert!(alice.messages(eve.id()).next().is_none());
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::Seeds(rid, send));
+
+    let (connected, _) = recv.recv().unwrap().partition();
+    let seed = connected.iter().find(|s| s.nid == bob_id).unwrap();
+    assert!(seed.announced.is_some());
 }
 
 #[test]
-fn test_inventory_relay() {
-    // Topology is eve <-> alice <-> bob
+fn test_track_repo_dry_run_concurrent() {
+    let rid = arbitrary::gen::<Id>(1);
+    let eve = arbitrary::gen::<NodeId>(1);
     let mut alice = Peer::new("alice", [7, 7, 7, 7]);
     let bob = Peer::new("bob", [8, 8, 8, 8]);
-    let eve = Peer::new("eve", [9, 9, 9, 9]);
-    let inv = BoundedVec::try_from(arbitrary::vec(1)).unwrap();
-    let now = LocalTime::now().as_millis();
 
-    // Inventory from Bob relayed to Eve.
     alice.connect_to(&bob);
-    alice.connect_from(&eve);
     alice.receive(
         bob.id(),
         Message::inventory(
             InventoryAnnouncement {
-                inventory: inv.clone(),
-                timestamp: now,
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: alice.timestamp(),
             },
             bob.signer(),
         ),
     );
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(Announcement {
-            node,
-            message: AnnouncementMessage::Inventory(InventoryAnnouncement { timestamp, .. }),
-            ..
-        }))
-        if node == bob.node_id() && timestamp == now
+    alice.outbox().for_each(drop);
+
+    let (send1, recv1) = chan::bounded(1);
+    let (send2, recv2) = chan::bounded(1);
+    alice.track_repo_dry_run(rid, tracking::Scope::All, send1);
+    alice.track_repo_dry_run(rid, tracking::Scope::All, send2);
+
+    assert_eq!(
+        alice
+            .outbox()
+            .filter(|io| matches!(io, Io::LsRefs { .. }))
+            .count(),
+        1,
+        "Refs are only listed once"
     );
-    assert_matches!(
-        alice.messages(bob.id()).next(),
-        None,
-        "The inventory is not sent back to Bob"
+
+    let refs = [bob.id(), eve]
+        .iter()
+        .map(|nid| git::refs::storage::branch_of(nid, &git::refname!("master")))
+        .collect();
+    alice.listed(rid, bob.id(), Ok(refs));
+
+    for recv in [recv1, recv2] {
+        let summary = recv.try_recv().unwrap();
+
+        assert_eq!(summary.seed, Some(bob.id()));
+        assert_eq!(summary.namespaces, Some(2));
+        assert_eq!(summary.refs, Some(2));
+    }
+}
+
+#[test]
+fn test_inspect_concurrent() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+
+    alice.connect_to(&bob);
+    alice.receive(
+        bob.id(),
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: alice.timestamp(),
+            },
+            bob.signer(),
+        ),
+    );
+    alice.outbox().for_each(drop);
+
+    let (send1, recv1) = chan::bounded(1);
+    let (send2, recv2) = chan::bounded(1);
+    alice.command(Command::Inspect(rid, send1));
+    alice.command(Command::Inspect(rid, send2));
+
+    assert_eq!(
+        alice
+            .outbox()
+            .filter(|io| matches!(io, Io::FetchIdentity { .. }))
+            .count(),
+        1,
+        "The identity is only fetched once"
+    );
+    alice.inspected(
+        rid,
+        bob.id(),
+        Err(crate::worker::FetchError::Io(
+            io::ErrorKind::ConnectionReset.into(),
+        )),
     );
 
+    for recv in [recv1, recv2] {
+        assert_matches!(
+            recv.try_recv(),
+            Ok(Err(CommandError::InspectFailed(nid, _))) if nid == bob.id()
+        );
+    }
+}
+
+#[test]
+fn test_fetch_retry_after_disconnect() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (send, recv) = chan::bounded(1);
+
+    alice.connect_to(&bob);
+    // Run the periodic tasks once, so that they don't interfere with the retry below.
+    alice.elapse(LocalDuration::from_secs(1));
+    alice.command(Command::TrackRepo(
+        rid,
+        node::tracking::Scope::All,
+        tracking::Origin::default(),
+        send,
+    ));
+    assert!(recv.recv().unwrap().unwrap());
+    alice.outbox().for_each(drop);
+
     alice.receive(
         bob.id(),
         Message::inventory(
             InventoryAnnouncement {
-                inventory: inv.clone(),
-                timestamp: now,
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: alice.timestamp(),
             },
             bob.signer(),
         ),
     );
+    assert_matches!(alice.fetches().next(), Some((r, remote, _)) if r == rid && remote == bob.id());
+
+    // Bob's session dies in the middle of the fetch.
+    alice.disconnected(
+        bob.id(),
+        &DisconnectReason::Connection(Arc::new(io::Error::from(io::ErrorKind::ConnectionReset))),
+    );
+    alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
+
+    alice.elapse(IDLE_INTERVAL);
     assert_matches!(
-        alice.messages(eve.id()).next(),
-        None,
-        "Sending the same inventory again doesn't trigger a relay"
+        alice.fetches().next(),
+        Some((r, remote, _)) if r == rid && remote == bob.id(),
+        "the fetch is retried once bob reconnects"
     );
+}
+
+#[test]
+fn test_fetch_retry_lost() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (send, recv) = chan::bounded(1);
+
+    alice.connect_to(&bob);
+    alice.elapse(LocalDuration::from_secs(1));
+    alice.command(Command::TrackRepo(
+        rid,
+        node::tracking::Scope::All,
+        tracking::Origin::default(),
+        send,
+    ));
+    assert!(recv.recv().unwrap().unwrap());
+    alice.outbox().for_each(drop);
 
     alice.receive(
         bob.id(),
         Message::inventory(
             InventoryAnnouncement {
-                inventory: inv.clone(),
-                timestamp: now + 1,
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: alice.timestamp(),
             },
             bob.signer(),
         ),
     );
-    assert_matches!(
-        alice.messages(eve.id()).next(),
-        Some(Message::Announcement(Announcement {
-            node,
-            message: AnnouncementMessage::Inventory(InventoryAnnouncement { timestamp, .. }),
-            ..
-        }))
-        if node == bob.node_id() && timestamp == now + 1,
-        "Sending a new inventory does trigger the relay"
-    );
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid);
 
-    // Inventory from Eve relayed to Bob.
-    alice.receive(
-        eve.id(),
-        Message::inventory(
-            InventoryAnnouncement {
-                inventory: inv,
-                timestamp: now,
-            },
-            eve.signer(),
-        ),
+    alice.fetched(
+        rid,
+        bob.id(),
+        Err(crate::worker::FetchError::Io(
+            io::ErrorKind::ConnectionReset.into(),
+        )),
+        0,
     );
+    alice.elapse(IDLE_INTERVAL);
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid);
+
+    // The retry is cut short, without its outcome being reported.
+    alice.disconnected(bob.id(), &DisconnectReason::Command);
+    alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
+
+    alice.elapse(MAX_FETCH_RETRY_DELTA);
     assert_matches!(
-        alice.messages(bob.id()).next(),
-        Some(Message::Announcement(Announcement {
-            node,
-            message: AnnouncementMessage::Inventory(InventoryAnnouncement { timestamp, .. }),
-            ..
-        }))
-        if node == eve.node_id() && timestamp == now
+        alice.fetches().next(),
+        Some((r, remote, _)) if r == rid && remote == bob.id(),
+        "the fetch is retried again"
     );
 }
 
 #[test]
-fn test_persistent_peer_reconnect_attempt() {
-    use std::collections::HashSet;
-
-    let mut bob = Peer::new("bob", [8, 8, 8, 8]);
-    let mut eve = Peer::new("eve", [9, 9, 9, 9]);
-    let mut alice = Peer::config(
-        "alice",
-        [7, 7, 7, 7],
-        MockStorage::empty(),
-        peer::Config {
-            config: Config {
-                connect: HashSet::from_iter([
-                    (bob.id(), bob.address()).into(),
-                    (eve.id(), eve.address()).into(),
-                ]),
-                ..Config::new(node::Alias::new("alice"))
-            },
-            ..peer::Config::default()
-        },
-    );
+fn test_fetch_retry_user_requested() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (send, recv) = chan::bounded(1);
 
-    let mut sim = Simulation::new(
-        LocalTime::now(),
-        alice.rng.clone(),
-        simulator::Options::default(),
-    )
-    .initialize([&mut alice, &mut bob, &mut eve]);
+    alice.connect_to(&bob);
+    alice.elapse(LocalDuration::from_secs(1));
+    alice.outbox().for_each(drop);
 
-    sim.run_while([&mut alice, &mut bob, &mut eve], |s| !s.is_settled());
+    alice.command(Command::Fetch(rid, bob.id(), send));
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid);
 
-    let ips = alice
-        .sessions()
-        .connected()
-        .map(|(id, _)| *id)
-        .collect::<Vec<_>>();
-    assert!(ips.contains(&bob.id()));
-    assert!(ips.contains(&eve.id()));
+    alice.fetched(
+        rid,
+        bob.id(),
+        Err(crate::worker::FetchError::Io(
+            io::ErrorKind::ConnectionReset.into(),
+        )),
+        0,
+    );
+    assert_matches!(recv.recv(), Ok(Ok(node::FetchResult::Failed { .. })));
 
-    // ... Negotiated ...
-    //
-    // Now let's disconnect a peer.
+    alice.elapse(IDLE_INTERVAL);
+    assert_matches!(
+        alice.fetches().next(),
+        None,
+        "user-requested fetches are not retried"
+    );
+}
 
-    // A non-transient disconnect, such as one due to peer misbehavior will still trigger a
-    // a reconnection, since this is a persistent peer.
-    let reason = DisconnectReason::Session(session::Error::Misbehavior);
+#[test]
+fn test_announce_refs_not_forked() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (send, recv) = chan::bounded(1);
 
-    for _ in 0..3 {
-        alice.disconnected(bob.id(), &reason);
-        alice.elapse(service::MAX_RECONNECTION_DELTA);
-        alice
-            .outbox()
-            .find(|io| matches!(io, Io::Connect(a, _) if a == &bob.id()))
-            .unwrap();
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
 
-        alice.attempted(bob.id(), bob.address());
-    }
+    // Alice has the repository in storage, but never forked it.
+    alice.command(Command::AnnounceRefs(rid, send));
+    assert_matches!(
+        recv.try_recv(),
+        Ok(Err(CommandError::NothingToAnnounce(id))) if id == rid
+    );
+    assert_matches!(alice.messages(bob.id()).next(), None);
 }
 
 #[test]
-fn test_persistent_peer_reconnect_success() {
-    use std::collections::HashSet;
+fn test_fetch_self() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (send, recv) = chan::bounded(1);
 
-    let bob = Peer::config(
-        "bob",
-        [9, 9, 9, 9],
-        MockStorage::empty(),
-        peer::Config::default(),
-    );
-    let mut alice = Peer::config(
-        "alice",
-        [7, 7, 7, 7],
-        MockStorage::empty(),
-        peer::Config {
-            config: Config {
-                connect: HashSet::from_iter([(bob.id, bob.addr()).into()]),
-                ..Config::new(node::Alias::new("alice"))
-            },
-            ..peer::Config::default()
-        },
-    );
     alice.connect_to(&bob);
+    alice.outbox().for_each(drop);
 
-    // A transient error such as this will cause Alice to attempt a reconnection.
-    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
-    alice.disconnected(bob.id(), &DisconnectReason::Connection(error));
-    alice.elapse(service::MIN_RECONNECTION_DELTA);
-    alice.elapse(service::MIN_RECONNECTION_DELTA); // Trigger a second wakeup to test idempotence.
-
-    alice
-        .outbox()
-        .find_map(|o| match o {
-            Io::Connect(id, _) => Some(id),
-            _ => None,
-        })
-        .expect("Alice attempts a re-connection");
+    let nid = alice.id();
 
-    alice.attempted(bob.id(), bob.addr());
-    alice.connected(bob.id(), bob.addr(), Link::Outbound);
+    alice.command(Command::Fetch(rid, nid, send));
+    assert_matches!(
+        recv.try_recv(),
+        Ok(Ok(node::FetchResult::Failed { reason })) if reason.contains("local node")
+    );
+    assert_matches!(alice.fetches().next(), None);
 }
 
 #[test]
-fn test_maintain_connections() {
-    // Peers alice starts out connected to.
-    let connected = vec![
-        Peer::new("connected", [8, 8, 8, 1]),
-        Peer::new("connected", [8, 8, 8, 2]),
-        Peer::new("connected", [8, 8, 8, 3]),
-    ];
-    // Peers alice will connect to once the others disconnect.
-    let mut unconnected = vec![
-        Peer::new("unconnected", [9, 9, 9, 1]),
-        Peer::new("unconnected", [9, 9, 9, 2]),
-        Peer::new("unconnected", [9, 9, 9, 3]),
-    ];
-
+fn test_fetch_not_tracked() {
+    let rid = arbitrary::gen::<Id>(1);
     let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (send, recv) = chan::bounded(1);
 
-    for peer in connected.iter() {
-        alice.connect_to(peer);
-    }
-    assert_eq!(
-        connected.len(),
-        alice.sessions().len(),
-        "alice should be connected to the first set of peers"
+    alice.connect_to(&bob);
+    alice.elapse(LocalDuration::from_secs(1));
+    alice.outbox().for_each(drop);
+
+    alice.command(Command::Fetch(rid, bob.id(), send));
+    assert_matches!(
+        recv.try_recv(),
+        Ok(Err(CommandError::NotTracked(id))) if id == rid
     );
-    // We now import the other addresses.
-    alice.import_addresses(&unconnected);
+    assert_matches!(alice.fetches().next(), None);
+}
 
-    // A transient error such as this will cause Alice to attempt a reconnection.
-    let error = Arc::new(io::Error::from(io::ErrorKind::ConnectionReset));
-    for peer in connected.iter() {
-        alice.disconnected(peer.id(), &DisconnectReason::Connection(error.clone()));
+#[test]
+fn test_fetch_while_connecting() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let (send, recv) = chan::bounded(1);
 
-        let id = alice
-            .outbox()
-            .find_map(|o| match o {
-                Io::Connect(id, _) => Some(id),
-                _ => None,
-            })
-            .expect("Alice connects to a new peer");
-        assert!(id != peer.id());
-        unconnected.retain(|p| p.id() != id);
-    }
-    assert!(
-        unconnected.is_empty(),
-        "alice should connect to all unconnected peers"
+    alice.initialize();
+    alice.command(Command::Connect(
+        bob.id(),
+        bob.address(),
+        ConnectOptions::default(),
+    ));
+    alice.command(Command::Fetch(rid, bob.id(), send));
+    assert_matches!(alice.fetches().next(), None, "the fetch is deferred");
+    assert!(recv.try_recv().is_err());
+
+    // Once the handshake completes, the fetch is initiated.
+    alice.attempted(bob.id(), bob.address());
+    alice.connected(bob.id(), bob.address(), Link::Outbound);
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid);
+
+    alice.fetched(rid, bob.id(), Ok(Default::default()), 0);
+    assert_matches!(recv.try_recv(), Ok(Ok(node::FetchResult::Success { .. })));
+
+    // If the seed isn't connected in time, the fetch fails.
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::Connect(
+        eve.id(),
+        eve.address(),
+        ConnectOptions::default(),
+    ));
+    alice.command(Command::Fetch(rid, eve.id(), send));
+    assert!(recv.try_recv().is_err());
+
+    alice.elapse(FETCH_CONNECT_TIMEOUT);
+    assert_matches!(
+        recv.try_recv(),
+        Ok(Ok(node::FetchResult::Failed { reason })) if reason.contains("timed out")
     );
 }
 
 #[test]
-fn test_maintain_connections_failed_attempt() {
+fn test_fetch_while_connecting_disconnected() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
     let bob = Peer::new("bob", [8, 8, 8, 8]);
-    let eve = Peer::new("eve", [9, 9, 9, 9]);
-    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
-
-    alice.connect_to(&bob);
-    // Make sure Alice knows about Eve.
-    alice.receive(bob.id, eve.node_announcement());
-    alice.disconnected(bob.id(), &DisconnectReason::Command);
-    alice
-        .outbox()
-        .find(|o| matches!(o, Io::Connect(id, _) if id == &eve.id))
-        .expect("Alice attempts Eve");
-    alice.attempted(eve.id, eve.addr());
-
-    // Disconnect Eve and make sure Alice doesn't try to re-connect immediately.
-    alice.disconnected(eve.id(), &DisconnectReason::Command);
-    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(_, _))));
-
-    // Now pass some time and try again.
-    alice.elapse(MAX_RECONNECTION_DELTA);
-    alice
-        .outbox()
-        .find(|o| matches!(o, Io::Connect(id, _) if id == &eve.id))
-        .expect("Alice attempts Eve again");
+    let (send, recv) = chan::bounded(1);
 
-    // Disconnect Eve and make sure Alice doesn't try to re-connect immediately.
-    alice.disconnected(eve.id(), &DisconnectReason::Command);
-    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(_, _))));
-    // Or even after some short time..
-    alice.elapse(MIN_RECONNECTION_DELTA);
-    assert!(!alice.outbox().any(|o| matches!(o, Io::Connect(_, _))));
+    alice.initialize();
+    alice.command(Command::Connect(
+        bob.id(),
+        bob.address(),
+        ConnectOptions::default(),
+    ));
+    alice.command(Command::Fetch(rid, bob.id(), send));
+    alice.attempted(bob.id(), bob.address());
+    alice.disconnected(
+        bob.id(),
+        &DisconnectReason::Connection(Arc::new(io::Error::from(io::ErrorKind::ConnectionRefused))),
+    );
+    assert_matches!(
+        recv.try_recv(),
+        Ok(Ok(node::FetchResult::Failed { reason })) if reason.contains("disconnected")
+    );
 }
 
 #[test]
-fn test_track_repo_subscribe() {
-    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+fn test_fetch_duplicate_requests() {
+    let storage = arbitrary::nonempty_storage(1);
+    let rid = *storage.inventory.keys().next().unwrap();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
     let bob = Peer::new("bob", [8, 8, 8, 8]);
-    let rid = arbitrary::gen::<Id>(1);
-    let (send, recv) = chan::bounded(1);
+    let (send1, recv1) = chan::bounded(1);
+    let (send2, recv2) = chan::bounded(1);
 
     alice.connect_to(&bob);
-    alice.command(Command::TrackRepo(rid, tracking::Scope::default(), send));
-    assert!(recv.recv().unwrap());
+    alice.outbox().for_each(drop);
 
+    alice.command(Command::Fetch(rid, bob.id(), send1));
+    alice.command(Command::Fetch(rid, bob.id(), send2));
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid);
     assert_matches!(
-        alice.messages(bob.id).next(),
-        Some(Message::Subscribe(Subscribe {
-            filter,
-            since,
-            ..
-        })) if since == alice.clock().as_millis() && filter.contains(&rid)
+        alice.fetches().next(),
+        None,
+        "the fetch is only initiated once"
     );
+
+    alice.fetched(rid, bob.id(), Ok(Default::default()), 0);
+
+    assert_matches!(recv1.try_recv(), Ok(Ok(node::FetchResult::Success { .. })));
+    assert_matches!(recv2.try_recv(), Ok(Ok(node::FetchResult::Success { .. })));
 }
 
 #[test]
-fn test_fetch_missing_inventory() {
-    let rid = arbitrary::gen::<Id>(1);
-    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+fn test_fetch_max_requests() {
+    let storage = arbitrary::nonempty_storage(2);
+    let mut rids = storage.inventory.keys();
+    let (rid1, rid2) = (*rids.next().unwrap(), *rids.next().unwrap());
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        storage,
+        peer::Config {
+            config: Config {
+                limits: Limits {
+                    fetch_max_requests: 1,
+                    ..Limits::default()
+                },
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
     let bob = Peer::new("bob", [8, 8, 8, 8]);
-    let eve = Peer::new("eve", [9, 9, 9, 9]);
-    let (send, recv) = chan::bounded::<bool>(1);
-    let now = LocalTime::now();
+    let (send1, recv1) = chan::bounded(1);
+    let (send2, recv2) = chan::bounded(1);
+    let (send3, recv3) = chan::bounded(1);
 
     alice.connect_to(&bob);
-    alice.connect_to(&eve);
-    alice.receive(
-        bob.id(),
-        Message::inventory(
-            InventoryAnnouncement {
-                inventory: vec![rid].try_into().unwrap(),
-                timestamp: now.as_millis(),
-            },
-            bob.signer(),
-        ),
-    );
-    alice.receive(
-        eve.id(),
-        Message::inventory(
-            InventoryAnnouncement {
-                inventory: vec![rid].try_into().unwrap(),
-                timestamp: now.as_millis(),
-            },
-            eve.signer(),
-        ),
-    );
-    alice.command(Command::TrackRepo(rid, node::tracking::Scope::All, send));
     alice.outbox().for_each(drop);
 
-    assert!(recv.recv().unwrap());
+    alice.command(Command::Fetch(rid1, bob.id(), send1));
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid1);
 
-    alice.elapse(service::SYNC_INTERVAL);
-    alice
-        .outbox()
-        .find(|m| matches!(m, Io::Fetch { .. }))
-        .unwrap();
+    alice.command(Command::Fetch(rid2, bob.id(), send2));
+    assert_matches!(
+        recv2.try_recv(),
+        Ok(Err(CommandError::TooManyFetches(1))),
+        "requests beyond the limit are rejected"
+    );
+
+    alice.fetched(rid1, bob.id(), Ok(Default::default()), 0);
+    assert_matches!(recv1.try_recv(), Ok(Ok(node::FetchResult::Success { .. })));
+
+    // Once the outstanding request completes, new requests are accepted again.
+    alice.command(Command::Fetch(rid2, bob.id(), send3));
+    assert_matches!(recv3.try_recv(), Err(chan::TryRecvError::Empty));
+    assert_matches!(alice.fetches().next(), Some((r, _, _)) if r == rid2);
+}
+
+#[test]
+fn test_init_removes_phantom_routes() {
+    let storage = arbitrary::nonempty_storage(1);
+    let local = *storage.inventory.keys().next().unwrap();
+    let phantom = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let (nid, now) = (alice.id(), alice.timestamp());
+
+    // Our routing table says we seed a repository we don't have.
+    alice.routing_mut().insert([&phantom], nid, now).unwrap();
     alice
-        .outbox()
-        .find(|m| matches!(m, Io::Fetch { .. }))
+        .routing_mut()
+        .insert([&phantom], bob.id(), now)
         .unwrap();
+    alice.initialize();
+
+    let seeds = alice.routing().get(&phantom).unwrap();
+    assert!(!seeds.contains(&nid), "the phantom entry is removed");
+    assert!(seeds.contains(&bob.id()), "other seeds are kept");
+    assert!(alice.routing().get(&local).unwrap().contains(&nid));
+}
+
+#[test]
+fn test_init_recovery() {
+    use radicle::node::watermark::Store as _;
+
+    let storage = arbitrary::nonempty_storage(2);
+    let rids = storage.inventory.keys().copied().collect::<Vec<_>>();
+    let phantom = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage.clone());
+    let mut bob = Peer::with_storage("bob", [8, 8, 8, 8], storage);
+    let (nid, now) = (alice.id(), alice.timestamp());
+    let signature = radicle::crypto::Signature::from([1; 64]);
+
+    // Alice didn't shut down cleanly, and her routing table is out of date with storage.
+    alice.routing_mut().insert([&phantom], nid, now).unwrap();
+    for rid in &rids {
+        alice
+            .watermarks_mut()
+            .fetched(rid, &nid, &signature)
+            .unwrap();
+    }
+    alice.unclean_shutdown(rids.clone());
+    alice.initialize();
+
+    let recovery = alice.recovery().cloned().expect("recovery ran");
+    assert_eq!(recovery.routes_added, 2);
+    assert_eq!(recovery.routes_removed, 1);
+    assert_eq!(recovery.checked, 0);
+    assert_eq!(recovery.pending, 2);
+
+    // Repositories are checked in the background. Only the watermarks of those that
+    // fail to validate are cleared.
+    alice.command(Command::RepoValidated(rids[0], Ok(())));
+    alice.command(Command::RepoValidated(
+        rids[1],
+        Err(String::from("invalid")),
+    ));
+
+    let recovery = alice.recovery().cloned().unwrap();
+    assert_eq!(recovery.checked, 2);
+    assert_eq!(recovery.invalid, vec![rids[1]]);
+    assert_eq!(recovery.watermarks_cleared, 1);
+    assert!(recovery.is_complete());
+    assert!(alice.watermarks().get(&rids[0], &nid).unwrap().is_some());
+    assert!(alice.watermarks().get(&rids[1], &nid).unwrap().is_none());
+
+    // Bob shut down cleanly.
+    bob.initialize();
+    assert_eq!(bob.recovery(), None);
+
+    // Eve shut down cleanly, but one of her databases was corrupt. Her stores aren't
+    // checked against storage, but the recovery is reported.
+    let mut eve = Peer::with_storage("eve", [9, 9, 9, 9], arbitrary::nonempty_storage(1));
+    let (path, backup) = (
+        PathBuf::from("routing.db"),
+        PathBuf::from("routing.db.corrupt"),
+    );
+    eve.database_recovered(path.clone(), backup.clone());
+    eve.initialize();
+
+    let recovery = eve.recovery().cloned().expect("recovery is reported");
+    assert!(!recovery.unclean);
+    assert_eq!(
+        recovery.databases,
+        vec![node::RecoveredDatabase { path, backup }]
+    );
+    assert_eq!(recovery.checked, 0);
 }
+
 #[test]
 fn test_queued_fetch() {
     let storage = arbitrary::nonempty_storage(3);
@@ -1165,15 +4470,15 @@ fn test_queued_fetch() {
     alice.connect_to(&bob);
 
     // Send the first fetch.
-    let (send, _recv1) = chan::bounded::<node::FetchResult>(1);
+    let (send, _recv1) = chan::bounded(1);
     alice.command(Command::Fetch(rid1, bob.id, send));
 
     // Send the 2nd fetch that will be queued.
-    let (send2, _recv2) = chan::bounded::<node::FetchResult>(1);
+    let (send2, _recv2) = chan::bounded(1);
     alice.command(Command::Fetch(rid2, bob.id, send2));
 
     // Send the 3rd fetch that will be queued.
-    let (send3, _recv3) = chan::bounded::<node::FetchResult>(1);
+    let (send3, _recv3) = chan::bounded(1);
     alice.command(Command::Fetch(rid3, bob.id, send3));
 
     // The first fetch is initiated.
@@ -1182,21 +4487,201 @@ fn test_queued_fetch() {
     assert_matches!(alice.outbox().next(), None);
 
     // Have enough time pass that Alice sends a "ping" to Bob.
-    alice.elapse(KEEP_ALIVE_DELTA);
+    alice.elapse(Limits::default().keep_alive);
 
     // Finish the 1st fetch.
-    alice.fetched(rid1, bob.id, Ok((vec![], Default::default())));
+    alice.fetched(rid1, bob.id, Ok(Default::default()), 0);
     // Now the 1st fetch is done, the 2nd fetch is dequeued.
     assert_matches!(alice.fetches().next(), Some((rid, _, _)) if rid == rid2);
     // ... but not the third.
     assert_matches!(alice.fetches().next(), None);
 
     // Finish the 2nd fetch.
-    alice.fetched(rid2, bob.id, Ok((vec![], Default::default())));
+    alice.fetched(rid2, bob.id, Ok(Default::default()), 0);
     // Now the 2nd fetch is done, the 3rd fetch is dequeued.
     assert_matches!(alice.fetches().next(), Some((rid, _, _)) if rid == rid3);
 }
 
+#[test]
+fn test_queued_fetch_order() {
+    let nid = arbitrary::gen::<NodeId>(1);
+    let addr = arbitrary::gen::<Address>(1);
+    let [rid, huge, small1, small2, small3, unknown] = arbitrary::gen::<[Id; 6]>(1);
+    let t0 = LocalTime::now();
+    let queue = |order| {
+        let mut session = session::Session::inbound(
+            nid,
+            addr.clone(),
+            false,
+            fastrand::Rng::new(),
+            t0,
+            Limits {
+                fetch_order: order,
+                ..Limits::default()
+            },
+        );
+        assert_matches!(session.fetch(rid, t0, None), session::FetchResult::Ready);
+
+        for (rid, estimate) in [
+            (huge, Some(LocalDuration::from_mins(60))),
+            (small1, Some(LocalDuration::from_secs(1))),
+            (unknown, None),
+            (small2, Some(LocalDuration::from_secs(2))),
+        ] {
+            assert_matches!(
+                session.fetch(rid, t0, estimate),
+                session::FetchResult::Queued
+            );
+        }
+        session
+    };
+    let order = |session: &session::Session, now| {
+        session
+            .queued(now)
+            .into_iter()
+            .map(|q| q.rid)
+            .collect::<Vec<_>>()
+    };
+
+    // Fetches are carried out in the order they were queued.
+    let session = queue(FetchOrder::Fifo);
+    assert_eq!(order(&session, t0), vec![huge, small1, unknown, small2]);
+
+    // Small repositories aren't stuck behind the huge one.
+    // Fetches with no estimate are assumed to take as long as the average one.
+    let mut session = queue(FetchOrder::Fair);
+    assert_eq!(order(&session, t0), vec![small1, small2, unknown, huge]);
+
+    session.fetched(rid, t0 + LocalDuration::from_secs(1));
+    assert_eq!(session.dequeue(t0), Some(small1));
+    assert_matches!(session.fetch(small1, t0, None), session::FetchResult::Ready);
+
+    // Smaller fetches keep being queued...
+    let t1 = t0 + LocalDuration::from_mins(1);
+    assert_matches!(
+        session.fetch(small3, t1, Some(LocalDuration::from_secs(1))),
+        session::FetchResult::Queued
+    );
+    assert_eq!(order(&session, t1), vec![small3, small2, unknown, huge]);
+
+    // ... but once the huge fetch waited for long enough, it goes first. Fetches that
+    // waited as long follow, in the order they were queued.
+    let t2 = t0 + Limits::default().fetch_max_wait;
+    assert_eq!(order(&session, t2), vec![huge, unknown, small2, small3]);
+    assert_eq!(session.dequeue(t2), Some(huge));
+}
+
+#[test]
+fn test_set_limit() {
+    let storage = arbitrary::nonempty_storage(4);
+    let rids = storage.inventory.keys().copied().collect::<Vec<_>>();
+    let mut alice = Peer::with_storage("alice", [7, 7, 7, 7], storage);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let eve = Peer::new("eve", [9, 9, 9, 9]);
+    let events = alice.events();
+
+    // Bob's session is established with the default concurrency of one fetch.
+    alice.connect_to(&bob);
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::SetLimit(
+        String::from("fetchConcurrency"),
+        serde_json::json!(0),
+        send,
+    ));
+    assert_matches!(recv.recv().unwrap(), Err(CommandError::Limit(_)));
+
+    let (send, recv) = chan::bounded(1);
+    alice.command(Command::SetLimit(
+        String::from("fetchConcurrency"),
+        serde_json::json!(2),
+        send,
+    ));
+    recv.recv().unwrap().unwrap();
+    assert_eq!(alice.config().limits.fetch_concurrency, 2);
+    events
+        .wait(
+            |e| match e {
+                Event::ConfigChanged { name, value } => {
+                    assert_eq!(name, "fetchConcurrency");
+                    assert_eq!(value, &serde_json::json!(2));
+                    Some(())
+                }
+                _ => None,
+            },
+            time::Duration::from_secs(3),
+        )
+        .unwrap();
+
+    // Eve's session is established after the change, and gets the new limit.
+    alice.connect_to(&eve);
+
+    for (rid, seed) in [(rids[0], bob.id), (rids[1], bob.id)] {
+        let (send, _) = chan::bounded(1);
+        alice.command(Command::Fetch(rid, seed, send));
+    }
+    assert_matches!(alice.fetches().next(), Some((rid, _, _)) if rid == rids[0]);
+    assert_matches!(
+        alice.fetches().next(),
+        None,
+        "Bob's session keeps its limit"
+    );
+
+    for (rid, seed) in [(rids[2], eve.id), (rids[3], eve.id)] {
+        let (send, _) = chan::bounded(1);
+        alice.command(Command::Fetch(rid, seed, send));
+    }
+    assert_matches!(alice.fetches().next(), Some((rid, _, _)) if rid == rids[2]);
+    assert_matches!(alice.fetches().next(), Some((rid, _, _)) if rid == rids[3]);
+}
+
+#[test]
+fn test_set_limit_timeouts() {
+    let mut alice = Peer::new("alice", [7, 7, 7, 7]);
+    let bob = Peer::new("bob", [8, 8, 8, 8]);
+    let set = |alice: &mut Peer<MockStorage, MockSigner>, name: &str, secs: u64| {
+        let (send, recv) = chan::bounded(1);
+        alice.command(Command::SetLimit(
+            String::from(name),
+            serde_json::json!(secs),
+            send,
+        ));
+        recv.recv().unwrap().unwrap();
+    };
+    let disconnected = |alice: &mut Peer<MockStorage, MockSigner>| {
+        alice
+            .outbox()
+            .any(|m| matches!(m, Io::Disconnect(addr, _) if addr == bob.id()))
+    };
+    alice.connect_to(&bob);
+
+    // Pings are sent, and unresponsive peers disconnected, according to the limits in
+    // effect, including for sessions established before the change.
+    set(&mut alice, "keepAlive", 3600);
+    set(&mut alice, "staleTimeout", 7200);
+    alice.elapse(Limits::default().stale_timeout + LocalDuration::from_secs(1));
+    assert!(
+        !alice
+            .messages(bob.id())
+            .any(|m| matches!(m, Message::Ping(_))),
+        "no pings are due yet"
+    );
+    assert!(!disconnected(&mut alice), "bob isn't stale yet");
+
+    set(&mut alice, "keepAlive", 30);
+    alice.elapse(IDLE_INTERVAL);
+    assert!(alice
+        .messages(bob.id())
+        .any(|m| matches!(m, Message::Ping(_))));
+
+    set(&mut alice, "staleTimeout", 30);
+    alice.elapse(LocalDuration::from_secs(31));
+    assert!(
+        disconnected(&mut alice),
+        "bob didn't answer our ping in time"
+    );
+}
+
 #[test]
 fn test_refs_synced_event() {
     let temp = tempfile::tempdir().unwrap();
@@ -1240,6 +4725,130 @@ fn test_refs_synced_event() {
         .unwrap();
 }
 
+#[test]
+fn test_refs_synced_event_dedup() {
+    use radicle::storage::{SignRepository as _, WriteRepository as _};
+
+    let temp = tempfile::tempdir().unwrap();
+    let storage = Storage::open(temp.path()).unwrap();
+    let mut alice = Peer::with_storage("alice", [8, 8, 8, 8], storage);
+    let mut bob = Peer::new("bob", [9, 9, 9, 9]);
+    let acme = alice.project("acme", "");
+    let events = alice.events();
+    let announcement = |alice: &Peer<Storage, MockSigner>, bob: &Peer<MockStorage, MockSigner>| {
+        let refs = alice
+            .storage()
+            .repository(acme)
+            .unwrap()
+            .remote(&alice.id)
+            .unwrap()
+            .refs
+            .unverified();
+        let ann = AnnouncementMessage::from(RefsAnnouncement {
+            rid: acme,
+            refs: vec![refs].try_into().unwrap(),
+            timestamp: bob.timestamp(),
+        });
+        Message::Announcement(ann.signed(bob.signer()))
+    };
+    let synced = |events: &node::Events| {
+        events
+            .try_iter()
+            .filter(|e| matches!(e, Event::RefsSynced { rid, .. } if *rid == acme))
+            .count()
+    };
+
+    alice.connect_to(&bob);
+    alice.receive(bob.id, announcement(&alice, &bob));
+    assert_eq!(synced(&events), 1);
+
+    // The same announcement is ignored.
+    alice.receive(bob.id, announcement(&alice, &bob));
+    assert_eq!(synced(&events), 0);
+
+    // A newer announcement of the same refs doesn't produce another event.
+    bob.elapse(LocalDuration::from_secs(1));
+    alice.receive(bob.id, announcement(&alice, &bob));
+    assert_eq!(synced(&events), 0);
+
+    // Once our refs change, and the peer syncs them, we get another event.
+    let repo = alice.storage().repository(acme).unwrap();
+    let (_, head) = repo.head().unwrap();
+    repo.raw()
+        .reference(
+            &format!("refs/namespaces/{}/refs/heads/feature", alice.id),
+            *head,
+            false,
+            "test",
+        )
+        .unwrap();
+    repo.sign_refs(alice.signer()).unwrap();
+
+    bob.elapse(LocalDuration::from_secs(1));
+    alice.receive(bob.id, announcement(&alice, &bob));
+    assert_eq!(synced(&events), 1);
+}
+
+#[test]
+fn test_seed_discovered_event_dedup() {
+    let rid = arbitrary::gen::<Id>(1);
+    let mut alice = Peer::config(
+        "alice",
+        [7, 7, 7, 7],
+        MockStorage::empty(),
+        peer::Config {
+            config: Config {
+                limits: Limits {
+                    routing_max_size: 0,
+                    routing_max_age: LocalDuration::from_mins(1),
+                    ..Limits::default()
+                },
+                ..Config::new(node::Alias::new("alice"))
+            },
+            ..peer::Config::default()
+        },
+    );
+    let mut bob = Peer::config(
+        "bob",
+        [8, 8, 8, 8],
+        MockStorage::empty(),
+        peer::Config {
+            local_time: alice.local_time(),
+            ..peer::Config::default()
+        },
+    );
+    let events = alice.events();
+    let announcement = |bob: &Peer<MockStorage, MockSigner>| {
+        Message::inventory(
+            InventoryAnnouncement {
+                inventory: vec![rid].try_into().unwrap(),
+                timestamp: bob.timestamp(),
+            },
+            bob.signer(),
+        )
+    };
+    let discovered = |events: &node::Events| {
+        events
+            .try_iter()
+            .filter(|e| matches!(e, Event::SeedDiscovered { rid: r, .. } if *r == rid))
+            .count()
+    };
+
+    alice.track_repo(&rid, tracking::Scope::All).unwrap();
+    alice.connect_to(&bob);
+    alice.receive(bob.id, announcement(&bob));
+    assert_eq!(discovered(&events), 1);
+
+    // The routing entry is pruned, and re-added when Bob announces his inventory again.
+    alice.elapse(PRUNE_INTERVAL + LocalDuration::from_secs(1));
+    assert!(alice.routing().get(&rid).unwrap().is_empty());
+
+    bob.elapse(PRUNE_INTERVAL + LocalDuration::from_secs(1));
+    alice.receive(bob.id, announcement(&bob));
+    assert!(alice.routing().get(&rid).unwrap().contains(&bob.id));
+    assert_eq!(discovered(&events), 0);
+}
+
 #[test]
 fn test_push_and_pull() {
     let tempdir = tempfile::tempdir().unwrap();
@@ -1292,6 +4901,7 @@ fn test_push_and_pull() {
     bob.command(service::Command::TrackRepo(
         proj_id,
         tracking::Scope::default(),
+        tracking::Origin::default(),
         sender,
     ));
 
@@ -1300,6 +4910,7 @@ fn test_push_and_pull() {
     eve.command(service::Command::TrackRepo(
         proj_id,
         tracking::Scope::default(),
+        tracking::Origin::default(),
         sender,
     ));
 
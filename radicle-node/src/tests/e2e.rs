@@ -1,19 +1,35 @@
-use std::{collections::HashSet, thread, time};
+use std::io::{BufRead as _, Read as _, Write as _};
+use std::{collections::HashSet, net, thread, time};
 
+use radicle::cob::issue;
 use radicle::crypto::{test::signer::MockSigner, Signer};
 use radicle::git;
-use radicle::node::{Alias, FetchResult, Handle as _};
-use radicle::storage::{ReadRepository, ReadStorage, WriteRepository, WriteStorage};
+use radicle::node::control::{OperatorToken, Permission};
+use radicle::node::tracking::store as tracking;
+use radicle::node::tracking::RefFilter;
+use radicle::node::{
+    Address, Alias, CobObject, CobUpdate, ConnectResult, ErrorCode, Event, FetchFailure,
+    FetchOutcome, FetchRecord, FetchResult, Handle as _, InspectSource, NodeId, SkipReason,
+    SyncAction, SyncAllOptions, SyncProgress, SyncResult, TrackFetch, TRACKING_DB_FILE,
+};
+use radicle::storage::{
+    Finding, ReadRepository, ReadStorage, RefUpdate, SignRepository, WriteRepository, WriteStorage,
+};
 use radicle::test::fixtures;
 use radicle::{assert_matches, rad};
 
-use crate::node::config::Limits;
+use crate::node::config::{
+    Discovery, EventLog, FetchPackLimit, Http, Limits, Network, PeerEntry, ShapeLimits, Validation,
+    Warning,
+};
+use crate::node::events::EventKind;
 use crate::node::{Config, ConnectOptions};
 use crate::service;
 use crate::service::tracking::Scope;
 use crate::storage::git::transport;
-use crate::test::environment::{converge, Environment, Node};
+use crate::test::environment::{converge, converge_within, Environment, Node, NodeHandle};
 use crate::test::logger;
+use crate::{LocalDuration, Runtime};
 
 #[test]
 //
@@ -235,265 +251,311 @@ fn test_replication_no_delegates() {
 }
 
 #[test]
-fn test_replication_invalid() {
+fn test_track_repo_dry_run() {
+    logger::init(log::Level::Debug);
+
     let tmp = tempfile::tempdir().unwrap();
     let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
     let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let carol = MockSigner::default();
     let acme = bob.project("acme", "");
-    let repo = bob.storage.repository_mut(acme).unwrap();
-    let (_, head) = repo.head().unwrap();
-    let id = repo.identity_head().unwrap();
-
-    // Create some unsigned refs for Carol in Bob's storage.
-    repo.raw()
-        .reference(
-            &git::qualified!("refs/heads/carol").with_namespace(carol.public_key().into()),
-            *head,
-            true,
-            &String::default(),
-        )
-        .unwrap();
-    repo.raw()
-        .reference(
-            &git::refs::storage::id(carol.public_key()),
-            id.into(),
-            true,
-            &String::default(),
-        )
-        .unwrap();
 
     let mut alice = alice.spawn();
     let bob = bob.spawn();
 
+    // Without any connected seeds, only the routing table is consulted.
+    let summary = alice.handle.track_repo_dry_run(acme, Scope::All).unwrap();
+    assert_eq!(summary.seeds, vec![]);
+    assert_eq!(summary.seed, None);
+    assert_eq!(summary.namespaces, None);
+    assert!(summary.note.is_some());
+
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    alice.handle.track_node(*carol.public_key(), None).unwrap();
-    alice.handle.track_repo(acme, Scope::Trusted).unwrap();
-    let result = alice.handle.fetch(acme, bob.id).unwrap();
-
-    // Fetch is successful despite not fetching Carol's refs, since she isn't a delegate.
-    assert!(result.is_success());
-
-    let repo = alice.storage.repository(acme).unwrap();
-    let mut remotes = repo.remote_ids().unwrap();
-
-    assert_eq!(remotes.next().unwrap().unwrap(), bob.id);
-    assert!(remotes.next().is_none());
-
-    repo.validate().unwrap();
+    let refs = bob
+        .storage
+        .repository(acme)
+        .unwrap()
+        .references()
+        .unwrap()
+        .filter(|r| r.as_ref().unwrap().namespace == Some(bob.id))
+        .count();
+    let summary = alice.handle.track_repo_dry_run(acme, Scope::All).unwrap();
+
+    assert_eq!(summary.rid, acme);
+    assert_eq!(summary.seed, Some(bob.id));
+    assert_eq!(summary.namespaces, Some(1));
+    assert_eq!(summary.refs, Some(refs));
+    assert_eq!(summary.note, None);
+    assert!(summary.seeds.iter().any(|s| s.nid == bob.id));
+
+    // Nothing was tracked or fetched.
+    let tracking = tracking::Config::reader(alice.home.node().join(TRACKING_DB_FILE)).unwrap();
+    assert_eq!(tracking.repo_policy(&acme).unwrap(), None);
+    assert!(alice.storage.inventory().unwrap().is_empty());
 }
 
 #[test]
-fn test_migrated_clone() {
+fn test_track_repo_fetch() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
-    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
-    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let acme = alice.project("acme", "");
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
 
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
+    let bob = bob.spawn();
+    let events = alice.handle.events();
 
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    let tracked = bob.handle.track_repo(acme, Scope::All).unwrap();
-    assert!(tracked);
-
-    let result = bob.handle.fetch(acme, alice.id).unwrap();
-    assert!(result.is_success());
-
-    log::debug!(target: "test", "Fetch complete with {}", alice.id);
-
-    // Simulate alice deleting the project and cloning it again
-    {
-        let path = alice.storage.path().join(acme.canonical());
-        std::fs::remove_dir_all(path).unwrap();
-    }
-    assert!(!alice.storage.contains(&acme).unwrap());
-    let result = alice.handle.fetch(acme, bob.id).unwrap();
-    assert!(result.is_success());
+    assert!(alice.storage.inventory().unwrap().is_empty());
 
-    let alice_repo = alice.storage.repository(acme).unwrap();
-    let bob_repo = bob.storage.repository(acme).unwrap();
+    let tracked = alice.handle.track_repo_fetch(acme, Scope::All).unwrap();
+    assert!(tracked.updated);
+    assert_eq!(tracked.fetch, TrackFetch::Started { seed: bob.id });
 
-    let alice_refs = alice_repo
-        .references()
-        .unwrap()
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
-    let bob_refs = bob_repo
-        .references()
-        .unwrap()
-        .collect::<Result<Vec<_>, _>>()
+    // The repository is fetched right away, without waiting for the next sync.
+    events
+        .wait(
+            |e| {
+                matches!(e, service::Event::RefsFetched { rid, updated, .. } if *rid == acme && !updated.is_empty())
+                    .then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
         .unwrap();
 
-    assert_eq!(alice_refs, bob_refs);
+    assert_eq!(alice.storage.inventory().unwrap(), vec![acme]);
     assert_matches!(alice.storage.repository(acme).unwrap().validate(), Ok(()));
 }
 
 #[test]
-fn test_dont_fetch_owned_refs() {
+fn test_repo_origin() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
-    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
-    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let acme = alice.project("acme", "");
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
 
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
+    let bob = bob.spawn();
+    let origin = |node: &NodeHandle<MockSigner>| {
+        node.handle
+            .export_policies()
+            .unwrap()
+            .repos
+            .into_iter()
+            .find(|r| r.id == acme)
+            .map(|r| r.origin)
+    };
+    // Bob's node tracks his project on startup, and knows he is its delegate.
+    assert_eq!(origin(&bob), Some(service::tracking::Origin::Delegate));
 
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
-
-    let result = bob.handle.fetch(acme, alice.id).unwrap();
-    assert!(result.is_success());
-
-    log::debug!(target: "test", "Fetch complete with {}", bob.id);
-
-    alice.issue(acme, "Don't fetch self", "Use ^");
-    let result = alice.handle.fetch(acme, bob.id).unwrap();
-    assert!(result.is_success())
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    assert!(alice.handle.fetch(acme, bob.id).unwrap().is_success());
+    assert_eq!(origin(&alice), Some(service::tracking::Origin::Tracked));
 }
 
 #[test]
-fn test_fetch_trusted_remotes() {
+fn test_mirror() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
-    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
-    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let acme = alice.project("acme", "");
-    let mut signers = Vec::with_capacity(5);
-    {
-        for _ in 0..5 {
-            let signer = MockSigner::default();
-            rad::fork_remote(acme, &alice.id, &signer, &alice.storage).unwrap();
-            signers.push(signer);
-        }
-    }
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(
+        tmp.path(),
+        Config {
+            mirror: vec![alice.id],
+            ..Config::test(Alias::new("bob"))
+        },
+    );
 
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
+    let bob = bob.spawn();
+    let events = bob.handle.events();
 
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    let trusted = signers
-        .iter()
-        .map(|s| *s.public_key())
-        .take(2)
-        .collect::<HashSet<_>>();
-
-    assert!(
-        trusted.len() < signers.len(),
-        "Bob is only trusting a subset of peers"
-    );
-    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
-    for nid in &trusted {
-        assert!(bob.handle.track_node(*nid, None).unwrap());
-    }
-
-    let result = bob.handle.fetch(acme, alice.id).unwrap();
-    assert!(result.is_success());
-
-    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+    // Alice creates a repository once connected to bob, and announces it.
+    let (repo, _) = fixtures::repository(tmp.path().join("acme"));
+    let (acme, _, _) = rad::init(
+        &repo,
+        "acme",
+        "",
+        git::refname!("master"),
+        &alice.signer,
+        &alice.storage,
+    )
+    .unwrap();
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    alice.handle.announce_inventory().unwrap();
 
-    let bob_repo = bob.storage.repository(acme).unwrap();
-    let bob_remotes = bob_repo
-        .remote_ids()
-        .unwrap()
-        .collect::<Result<HashSet<_>, _>>()
+    // Bob tracks and fetches it, without being told to.
+    let origin = events
+        .wait(
+            |e| match e {
+                Event::RepoTracked { rid, origin, .. } if rid == &acme => Some(*origin),
+                _ => None,
+            },
+            time::Duration::from_secs(6),
+        )
         .unwrap();
+    assert_eq!(origin, service::tracking::Origin::Mirror);
 
-    assert!(bob_remotes.len() == trusted.len() + 1);
-    assert!(bob_remotes.is_superset(&trusted));
-    assert!(bob_remotes.contains(&alice.id));
+    events
+        .wait(
+            |e| matches!(e, Event::RefsFetched { rid, .. } if rid == &acme).then_some(()),
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+    assert!(bob.storage.contains(&acme).unwrap());
+    assert_matches!(bob.storage.repository(acme).unwrap().validate(), Ok(()));
 }
 
 #[test]
-fn test_missing_remote() {
+fn test_tracking_events() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
-    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
-    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let acme = alice.project("acme", "");
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
 
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
-    let carol = MockSigner::default();
+    let bob = bob.spawn();
 
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
-    assert!(bob.handle.track_node(*carol.public_key(), None).unwrap());
-    let result = bob.handle.fetch(acme, alice.id).unwrap();
-    assert!(result.is_success());
-    log::debug!(target: "test", "Fetch complete with {}", bob.id);
-    rad::fork_remote(acme, &alice.id, &carol, &bob.storage).unwrap();
+    // Subscribe through the control socket, as a client would.
+    let mut events = radicle::Node::new(alice.home.socket())
+        .subscribe(time::Duration::from_secs(6))
+        .unwrap()
+        .map(|e| e.unwrap())
+        .filter(|e| match e {
+            Event::RefsFetched { rid, .. }
+            | Event::RefsSynced { rid, .. }
+            | Event::SeedDiscovered { rid, .. }
+            | Event::RepoTracked { rid, .. }
+            | Event::RepoUntracked { rid } => *rid == acme,
+            Event::NodeTracked { .. } | Event::NodeUntracked { .. } => true,
+            _ => false,
+        });
+
+    // The policy change is emitted before any of the activity that follows from it,
+    // eg. fetching from seeds that learn about our new subscription filter.
+    assert!(alice.handle.track_repo(acme, Scope::All).unwrap());
+    assert_matches!(
+        events.next(),
+        Some(Event::RepoTracked { rid, scope: Scope::All, .. }) if rid == acme
+    );
+    // Changing the scope is a policy change too.
+    assert!(alice.handle.track_repo(acme, Scope::Trusted).unwrap());
+    assert_matches!(
+        events.find(|e| matches!(e, Event::RepoTracked { .. })),
+        Some(Event::RepoTracked { rid, scope: Scope::Trusted, .. }) if rid == acme
+    );
+    // Nothing is emitted when the policy is unchanged.
+    assert!(!alice.handle.track_repo(acme, Scope::Trusted).unwrap());
+    assert!(alice.handle.untrack_repo(acme).unwrap());
+    assert_matches!(
+        events.find(|e| matches!(e, Event::RepoTracked { .. } | Event::RepoUntracked { .. })),
+        Some(Event::RepoUntracked { rid }) if rid == acme
+    );
 
-    alice.issue(acme, "Missing Remote", "Fixing the missing remote issue");
-    let result = bob.handle.fetch(acme, alice.id).unwrap();
-    assert!(result.is_success());
-    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+    assert!(alice
+        .handle
+        .track_node(bob.id, Some(Alias::new("bob")))
+        .unwrap());
+    assert_matches!(
+        events.find(|e| matches!(e, Event::NodeTracked { .. } | Event::NodeUntracked { .. })),
+        Some(Event::NodeTracked { nid, alias: Some(alias) }) if nid == bob.id && alias == Alias::new("bob")
+    );
+    assert!(alice.handle.untrack_node(bob.id).unwrap());
+    assert_matches!(
+        events.find(|e| matches!(e, Event::NodeTracked { .. } | Event::NodeUntracked { .. })),
+        Some(Event::NodeUntracked { nid }) if nid == bob.id
+    );
 }
 
 #[test]
-fn test_fetch_preserve_owned_refs() {
+fn test_sync_all() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
-    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
-    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let acme = alice.project("acme", "");
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
+    let bells = bob.project("bells", "");
+
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
+    let bob = bob.spawn();
 
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
-    assert!(bob.handle.track_node(alice.id, None).unwrap());
-
-    let result = bob.handle.fetch(acme, alice.id).unwrap();
-    assert!(result.is_success());
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    alice.handle.track_repo(bells, Scope::All).unwrap();
+
+    // Go through the control socket, as a client would.
+    let progress = radicle::Node::new(alice.home.socket())
+        .sync_all(SyncAllOptions {
+            fetch: true,
+            announce: false,
+            parallelism: 2,
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
 
-    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+    assert_eq!(progress.len(), 3, "{progress:#?}");
 
-    alice.issue(acme, "Bug", "Bugs, bugs, bugs");
+    let mut fetched = progress[..2]
+        .iter()
+        .map(|p| match p {
+            SyncProgress::Repo {
+                rid,
+                action: SyncAction::Fetch,
+                result: SyncResult::Success { seed, .. },
+            } => {
+                assert_eq!(*seed, Some(bob.id));
+                *rid
+            }
+            other => panic!("unexpected progress {other:?}"),
+        })
+        .collect::<Vec<_>>();
+    fetched.sort();
 
-    let before = alice
-        .storage
-        .repository(acme)
-        .unwrap()
-        .references_of(&alice.id)
-        .unwrap();
+    let mut expected = vec![acme, bells];
+    expected.sort();
 
-    // Fetch shouldn't prune any of our own refs.
-    let result = alice.handle.fetch(acme, bob.id).unwrap();
-    let (updated, _) = result.success().unwrap();
-    assert_eq!(updated, vec![]);
+    assert_eq!(fetched, expected);
+    assert_eq!(
+        progress[2],
+        SyncProgress::Summary {
+            repos: 2,
+            succeeded: 2,
+            skipped: 0,
+            failed: 0,
+        }
+    );
 
-    let after = alice
-        .storage
-        .repository(acme)
-        .unwrap()
-        .references_of(&alice.id)
-        .unwrap();
+    let mut inventory = alice.storage.inventory().unwrap();
+    inventory.sort();
 
-    assert_eq!(before, after);
+    assert_eq!(inventory, expected);
 }
 
 #[test]
-fn test_clone() {
+fn test_inspect() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
@@ -502,62 +564,1735 @@ fn test_clone() {
     let acme = bob.project("acme", "");
 
     let mut alice = alice.spawn();
-    let bob = bob.spawn();
+    let mut bob = bob.spawn();
+
+    // Without any connected seeds, the repository can't be found.
+    let err = alice.handle.inspect(acme).unwrap_err();
+    assert!(err.to_string().contains("not found"), "{err}");
 
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    transport::local::register(alice.storage.clone());
+    // Go through the control socket, as a client would.
+    let inspection = radicle::Node::new(alice.home.socket())
+        .inspect(acme)
+        .unwrap();
+    let identity = bob.storage.repository(acme).unwrap().identity().unwrap();
 
-    let _ = alice.handle.track_repo(acme, Scope::All).unwrap();
-    let seeds = alice.handle.seeds(acme).unwrap();
-    assert!(seeds.is_connected(&bob.id));
+    assert_eq!(inspection.rid, acme);
+    assert_eq!(inspection.source, InspectSource::Seed { nid: bob.id });
+    assert_eq!(inspection.head, identity.head);
+    assert_eq!(inspection.doc, identity.doc);
+    assert!(inspection.doc.is_delegate(&bob.id));
 
-    let result = alice.handle.fetch(acme, bob.id).unwrap();
-    assert!(result.is_success());
+    // Nothing was tracked or stored.
+    let tracking = tracking::Config::reader(alice.home.node().join(TRACKING_DB_FILE)).unwrap();
+    assert_eq!(tracking.repo_policy(&acme).unwrap(), None);
+    assert!(!alice.storage.contains(&acme).unwrap());
 
-    rad::fork(acme, &alice.signer, &alice.storage).unwrap();
+    // Repositories in storage are inspected locally.
+    let inspection = bob.handle.inspect(acme).unwrap();
+    assert_eq!(inspection.source, InspectSource::Local);
+    assert_eq!(inspection.doc, identity.doc);
+}
 
-    let working = rad::checkout(
-        acme,
-        alice.signer.public_key(),
+#[test]
+//
+//     alice -- eve -- bob
+//
+fn test_untrack_announces_inventory() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let eve = Node::init(
+        tmp.path(),
+        Config {
+            // Don't relay alice's announcements, so that bob only knows about eve.
+            relay: false,
+            ..Config::test(Alias::new("eve"))
+        },
+    );
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+
+    let alice = alice.spawn();
+    let mut eve = eve.spawn();
+    let mut bob = bob.spawn();
+
+    bob.connect(&eve);
+
+    // Eve seeds alice's repository.
+    eve.connect(&alice);
+    converge([&alice, &eve]);
+    eve.handle.track_repo(acme, Scope::All).unwrap();
+    assert!(eve.handle.fetch(acme, alice.id).unwrap().is_success());
+
+    // Bob only knows about eve.
+    bob.routes_to(&[(acme, eve.id)]);
+
+    let bob_events = bob.handle.events();
+    let eve_events = eve.handle.events();
+
+    assert!(eve.handle.untrack_repo(acme).unwrap());
+    eve_events
+        .wait(
+            |e| {
+                matches!(e, Event::InventoryRemoved { rid, peers: 2 } if rid == &acme).then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+    bob_events
+        .wait(
+            |e| {
+                matches!(e, Event::SeedDropped { rid, nid } if rid == &acme && nid == &eve.id)
+                    .then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+    assert_eq!(bob.routing().next(), None);
+
+    // Once bob learns about alice, his fetches go to her instead of eve.
+    bob.connect(&alice);
+    bob.routes_to(&[(acme, alice.id)]);
+    bob.handle.track_repo(acme, Scope::All).unwrap();
+
+    let seeds = bob.handle.seeds(acme).unwrap();
+    let connected = seeds.connected().map(|s| s.nid).collect::<Vec<_>>();
+    assert_eq!(connected, vec![alice.id]);
+    assert!(bob.handle.fetch(acme, connected[0]).unwrap().is_success());
+    assert!(bob.storage.contains(&acme).unwrap());
+}
+
+#[test]
+fn test_watch_repo() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let events = bob.handle.events();
+
+    assert!(bob.handle.watch_repo(acme).unwrap());
+    assert!(!bob.handle.watch_repo(acme).unwrap());
+
+    bob.connect(&alice);
+    events
+        .wait(
+            |e| {
+                matches!(e, Event::SeedDiscovered { rid, nid } if rid == &acme && nid == &alice.id)
+                    .then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+
+    alice.handle.announce_refs(acme).unwrap();
+    events
+        .wait(
+            |e| {
+                matches!(e, Event::RefsAnnounced { rid, remote, .. } if rid == &acme && remote == &alice.id)
+                    .then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+
+    // Watched repositories are never fetched, and aren't part of our inventory.
+    assert!(!bob.storage.contains(&acme).unwrap());
+    assert!(bob
+        .handle
+        .seeds(acme)
+        .unwrap()
+        .connected()
+        .any(|s| s.nid == alice.id));
+
+    assert!(bob.handle.unwatch_repo(acme).unwrap());
+    assert!(!bob.handle.unwatch_repo(acme).unwrap());
+}
+
+#[test]
+//
+//     alice -- bob
+//
+// Nodes discover each other on the local network, without being told about each other.
+fn test_local_discovery() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let discovery = Discovery {
+        enabled: true,
+        group: net::SocketAddrV4::new(
+            net::Ipv4Addr::new(239, 255, 77, 76),
+            fastrand::u16(20000..30000),
+        ),
+        interface: net::Ipv4Addr::LOCALHOST,
+        interval: LocalDuration::from_secs(1),
+    };
+    let mut alice = Node::init(
+        tmp.path(),
+        Config {
+            discovery: discovery.clone(),
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let bob = Node::init(
+        tmp.path(),
+        Config {
+            discovery,
+            ..Config::test(Alias::new("bob"))
+        },
+    );
+    let acme = alice.project("acme", "");
+
+    let alice = alice.spawn();
+    let bob = bob.spawn();
+    let connected =
+        |node: &NodeHandle<MockSigner>, peer: &NodeId| {
+            node.handle.sessions().unwrap().iter().any(|s| {
+                &s.nid == peer && matches!(s.state, radicle::node::State::Connected { .. })
+            })
+        };
+    let deadline = time::Instant::now() + time::Duration::from_secs(10);
+
+    while !(connected(&alice, &bob.id) && connected(&bob, &alice.id)) {
+        assert!(
+            time::Instant::now() < deadline,
+            "nodes didn't discover each other in time"
+        );
+        thread::sleep(time::Duration::from_millis(100));
+    }
+    let routes = converge_within([&alice, &bob], time::Duration::from_secs(6)).unwrap();
+    assert!(routes.contains(&(acme, alice.id)));
+}
+
+#[test]
+fn test_validate_repo() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let acme = alice.project("acme", "");
+    let other = alice.project("other", "");
+
+    // Delete one of the signed refs.
+    alice
+        .storage
+        .repository_mut(acme)
+        .unwrap()
+        .reference(&alice.id, &git::qualified!("refs/heads/master"))
+        .unwrap()
+        .delete()
+        .unwrap();
+
+    let alice = alice.spawn();
+    let expected = vec![Finding::error(
+        acme,
+        Some(alice.id),
+        "signed reference `refs/heads/master` is missing",
+    )];
+
+    let findings = alice
+        .handle
+        .validate(Some(acme))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(findings, expected);
+
+    let findings = alice
+        .handle
+        .validate(Some(other))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(findings, vec![]);
+
+    let findings = alice
+        .handle
+        .validate(None)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(findings, expected);
+
+    let unknown = crate::test::arbitrary::gen::<crate::identity::Id>(1);
+    assert!(alice.handle.validate(Some(unknown)).is_err());
+}
+
+#[test]
+fn test_replication_invalid() {
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let carol = MockSigner::default();
+    let acme = bob.project("acme", "");
+    let repo = bob.storage.repository_mut(acme).unwrap();
+    let (_, head) = repo.head().unwrap();
+    let id = repo.identity_head().unwrap();
+
+    // Create some unsigned refs for Carol in Bob's storage.
+    repo.raw()
+        .reference(
+            &git::qualified!("refs/heads/carol").with_namespace(carol.public_key().into()),
+            *head,
+            true,
+            &String::default(),
+        )
+        .unwrap();
+    repo.raw()
+        .reference(
+            &git::refs::storage::id(carol.public_key()),
+            id.into(),
+            true,
+            &String::default(),
+        )
+        .unwrap();
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    alice.handle.track_node(*carol.public_key(), None).unwrap();
+    alice.handle.track_repo(acme, Scope::Trusted).unwrap();
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+
+    // Fetch is successful despite not fetching Carol's refs, since she isn't a delegate.
+    assert!(result.is_success());
+
+    let repo = alice.storage.repository(acme).unwrap();
+    let mut remotes = repo.remote_ids().unwrap();
+
+    assert_eq!(remotes.next().unwrap().unwrap(), bob.id);
+    assert!(remotes.next().is_none());
+
+    repo.validate().unwrap();
+}
+
+#[test]
+fn test_migrated_clone() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    let tracked = bob.handle.track_repo(acme, Scope::All).unwrap();
+    assert!(tracked);
+
+    let result = bob.handle.fetch(acme, alice.id).unwrap();
+    assert!(result.is_success());
+
+    log::debug!(target: "test", "Fetch complete with {}", alice.id);
+
+    // Simulate alice deleting the project and cloning it again
+    {
+        let path = alice.storage.path().join(acme.canonical());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+    assert!(!alice.storage.contains(&acme).unwrap());
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success());
+
+    let alice_repo = alice.storage.repository(acme).unwrap();
+    let bob_repo = bob.storage.repository(acme).unwrap();
+
+    let alice_refs = alice_repo
+        .references()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let bob_refs = bob_repo
+        .references()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(alice_refs, bob_refs);
+    assert_matches!(alice.storage.repository(acme).unwrap().validate(), Ok(()));
+}
+
+#[test]
+fn test_dont_fetch_owned_refs() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
+
+    let result = bob.handle.fetch(acme, alice.id).unwrap();
+    assert!(result.is_success());
+
+    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+
+    alice.issue(acme, "Don't fetch self", "Use ^");
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success())
+}
+
+#[test]
+fn test_fetch_trusted_remotes() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+    let mut signers = Vec::with_capacity(5);
+    {
+        for _ in 0..5 {
+            let signer = MockSigner::default();
+            rad::fork_remote(acme, &alice.id, &signer, &alice.storage).unwrap();
+            signers.push(signer);
+        }
+    }
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    let trusted = signers
+        .iter()
+        .map(|s| *s.public_key())
+        .take(2)
+        .collect::<HashSet<_>>();
+    // Carol is tracked, but Alice doesn't have her fork.
+    let carol = MockSigner::default();
+
+    assert!(
+        trusted.len() < signers.len(),
+        "Bob is only trusting a subset of peers"
+    );
+    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
+    for nid in &trusted {
+        assert!(bob.handle.track_node(*nid, None).unwrap());
+    }
+    assert!(bob.handle.track_node(*carol.public_key(), None).unwrap());
+
+    let result = bob.handle.fetch(acme, alice.id).unwrap();
+    assert_matches!(
+        &result,
+        FetchResult::Success { skipped, .. }
+        if skipped == &[(*carol.public_key(), SkipReason::Absent)]
+    );
+
+    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+
+    let bob_repo = bob.storage.repository(acme).unwrap();
+    let bob_remotes = bob_repo
+        .remote_ids()
+        .unwrap()
+        .collect::<Result<HashSet<_>, _>>()
+        .unwrap();
+
+    assert!(bob_remotes.len() == trusted.len() + 1);
+    assert!(bob_remotes.is_superset(&trusted));
+    assert!(bob_remotes.contains(&alice.id));
+
+    // Fetched namespaces are reported with the number of signed refs fetched.
+    let FetchResult::Success { fetched, .. } = result else {
+        panic!("Fetch from {} failed", alice.id);
+    };
+    assert_eq!(
+        fetched.iter().map(|(nid, _)| *nid).collect::<HashSet<_>>(),
+        bob_remotes
+    );
+    for (nid, refs) in fetched {
+        assert_eq!(refs, bob_repo.remote(&nid).unwrap().refs.len());
+    }
+}
+
+#[test]
+fn test_missing_remote() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let carol = MockSigner::default();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
+    assert!(bob.handle.track_node(*carol.public_key(), None).unwrap());
+    let result = bob.handle.fetch(acme, alice.id).unwrap();
+    assert!(result.is_success());
+    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+    rad::fork_remote(acme, &alice.id, &carol, &bob.storage).unwrap();
+
+    alice.issue(acme, "Missing Remote", "Fixing the missing remote issue");
+    let result = bob.handle.fetch(acme, alice.id).unwrap();
+    assert!(result.is_success());
+    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+}
+
+#[test]
+fn test_fetch_preserve_owned_refs() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    assert!(bob.handle.track_repo(acme, Scope::Trusted).unwrap());
+    assert!(bob.handle.track_node(alice.id, None).unwrap());
+
+    let result = bob.handle.fetch(acme, alice.id).unwrap();
+    assert!(result.is_success());
+
+    log::debug!(target: "test", "Fetch complete with {}", bob.id);
+
+    alice.issue(acme, "Bug", "Bugs, bugs, bugs");
+
+    let before = alice
+        .storage
+        .repository(acme)
+        .unwrap()
+        .references_of(&alice.id)
+        .unwrap();
+
+    // Fetch shouldn't prune any of our own refs.
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    let (updated, _) = result.success().unwrap();
+    assert_eq!(updated, vec![]);
+
+    let after = alice
+        .storage
+        .repository(acme)
+        .unwrap()
+        .references_of(&alice.id)
+        .unwrap();
+
+    assert_eq!(before, after);
+}
+
+#[test]
+fn test_clone() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    transport::local::register(alice.storage.clone());
+
+    let _ = alice.handle.track_repo(acme, Scope::All).unwrap();
+    let seeds = alice.handle.seeds(acme).unwrap();
+    assert!(seeds.is_connected(&bob.id));
+
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success());
+
+    rad::fork(acme, &alice.signer, &alice.storage).unwrap();
+
+    let working = rad::checkout(
+        acme,
+        alice.signer.public_key(),
         tmp.path().join("clone"),
         &alice.storage,
     )
     .unwrap();
 
-    // Makes test finish faster.
-    drop(alice);
+    // Makes test finish faster.
+    drop(alice);
+
+    let head = working.head().unwrap();
+    let oid = head.target().unwrap();
+
+    let (_, canonical) = bob
+        .storage
+        .repository(acme)
+        .unwrap()
+        .canonical_head()
+        .unwrap();
+
+    assert_eq!(oid, *canonical);
+
+    // Make sure that bob has refs/rad/id set
+    assert!(bob
+        .storage
+        .repository(acme)
+        .unwrap()
+        .identity_head()
+        .is_ok());
+}
+
+#[test]
+fn test_fetch_up_to_date() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    transport::local::register(alice.storage.clone());
+
+    let _ = alice.handle.track_repo(acme, Scope::All).unwrap();
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success());
+
+    // Fetch again! This time, everything's up to date.
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert_eq!(
+        result.success(),
+        Some((vec![], HashSet::from_iter([bob.id])))
+    );
+}
+
+#[test]
+fn test_large_fetch() {
+    logger::init(log::Level::Debug);
+
+    let env = Environment::new();
+    let scale = env.scale();
+    let mut alice = Node::init(&env.tmp(), Config::test(Alias::new("alice")));
+    let bob = Node::init(&env.tmp(), Config::test(Alias::new("bob")));
+
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = fixtures::populated(tmp.path(), scale.max(3));
+
+    let rid = alice.project_from("acme", "", &repo);
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let bob_events = bob.handle.events();
+
+    bob.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&bob);
+
+    bob_events
+        .wait(
+            |e| {
+                matches!(e, service::Event::RefsFetched { updated, .. } if !updated.is_empty())
+                    .then_some(())
+            },
+            time::Duration::from_secs(9 * scale as u64),
+        )
+        .unwrap();
+
+    let (_, doc) = bob.storage.repository(rid).unwrap().identity_doc().unwrap();
+    let proj = doc.verified().unwrap().project().unwrap();
+
+    assert_eq!(proj.name(), "acme");
+
+    // Bob only has Alice's namespace.
+    let stats = bob.handle.repo_size(Some(rid), false).unwrap();
+    let [stats] = stats.as_slice() else {
+        panic!("expected statistics of a single repository, got {stats:?}");
+    };
+    assert_eq!(stats.rid, rid);
+    assert_eq!(stats.namespaces.len(), 1);
+    assert!(stats.disk > 0);
+    assert!(stats.refs > 0);
+    assert!(stats.loose + stats.packed > 0);
+    assert!(stats.fetched.is_some());
+
+    // Statistics are cached, unless a refresh is requested.
+    let cached = bob.handle.repo_size(None, false).unwrap();
+    assert_eq!(cached.len(), 1);
+    assert_eq!(cached[0].computed_at, stats.computed_at);
+
+    let refreshed = bob.handle.repo_size(Some(rid), true).unwrap();
+    assert!(refreshed[0].computed_at >= stats.computed_at);
+}
+
+#[test]
+fn test_fetch_minimal() {
+    use radicle::node::watermark::Store as _;
+    use radicle::node::{watermark, WATERMARK_DB_FILE};
+
+    logger::init(log::Level::Debug);
+
+    let env = Environment::new();
+    let scale = env.scale();
+    let alice = Node::init(&env.tmp(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(&env.tmp(), Config::test(Alias::new("bob")));
+    let eve = Node::init(&env.tmp(), Config::test(Alias::new("eve")));
+
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = fixtures::populated(tmp.path(), scale.max(3));
+    let rid = bob.project_from("acme", "", &repo);
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    // Eve has Bob's repository, and a fork of it.
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    eve.connect(&bob);
+    converge([&bob, &eve]);
+    assert!(eve.handle.fetch(rid, bob.id).unwrap().is_success());
+    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+
+    alice.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&eve);
+    converge([&alice, &eve]);
+
+    // Only the delegate's namespace is fetched, which is enough to get the canonical head.
+    let result = alice.handle.fetch_minimal(rid, eve.id).unwrap();
+    assert_eq!(
+        result.success().map(|(_, namespaces)| namespaces),
+        Some(HashSet::from_iter([bob.id]))
+    );
+    let partial = || {
+        watermark::Table::reader(alice.home.node().join(WATERMARK_DB_FILE))
+            .unwrap()
+            .is_partial(&rid)
+            .unwrap()
+    };
+    let stored = alice.storage.repository(rid).unwrap();
+    let (_, head) = stored.canonical_head().unwrap();
+    let (_, expected) = bob
+        .storage
+        .repository(rid)
+        .unwrap()
+        .canonical_head()
+        .unwrap();
+
+    assert_eq!(head, expected);
+    assert!(stored.remote(&bob.id).is_ok());
+    assert!(stored.remote(&eve.id).is_err());
+    assert!(partial());
+
+    // A regular fetch fetches the remaining namespaces.
+    assert!(alice.handle.fetch(rid, eve.id).unwrap().is_success());
+    let stored = alice.storage.repository(rid).unwrap();
+
+    assert!(stored.remote(&eve.id).is_ok());
+    assert!(!partial());
+}
+
+#[test]
+fn test_concurrent_fetches() {
+    logger::init(log::Level::Debug);
+
+    let env = Environment::new();
+    let scale = env.scale();
+    let repos = scale.max(4);
+    let limits = Limits {
+        // Have one fetch be queued.
+        fetch_concurrency: repos - 1,
+        ..Limits::default()
+    };
+    let mut bob_repos = HashSet::new();
+    let mut alice_repos = HashSet::new();
+    let mut alice = Node::init(
+        &env.tmp(),
+        service::Config {
+            limits: limits.clone(),
+            ..service::Config::test(Alias::new("alice"))
+        },
+    );
+    let mut bob = Node::init(
+        &env.tmp(),
+        service::Config {
+            limits,
+            ..service::Config::test(Alias::new("bob"))
+        },
+    );
+
+    for i in 0..repos {
+        // Create a repo for Alice.
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = fixtures::populated(tmp.path(), scale);
+
+        let rid = alice.project_from(&format!("alice-{i}"), "", &repo);
+        alice_repos.insert(rid);
+
+        // Create a repo for Bob.
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = fixtures::populated(tmp.path(), scale);
+
+        let rid = bob.project_from(&format!("bob-{i}"), "", &repo);
+        bob_repos.insert(rid);
+    }
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+
+    let alice_events = alice.handle.events();
+    let bob_events = bob.handle.events();
+
+    for rid in &bob_repos {
+        alice.handle.track_repo(*rid, Scope::All).unwrap();
+    }
+    for rid in &alice_repos {
+        bob.handle.track_repo(*rid, Scope::All).unwrap();
+    }
+    alice.connect(&bob);
+
+    while !bob_repos.is_empty() {
+        match alice_events.recv().unwrap() {
+            service::Event::RefsFetched { rid, updated, .. } if !updated.is_empty() => {
+                bob_repos.remove(&rid);
+                log::debug!(target: "test", "{} fetched {rid} ({} left)",alice.id, bob_repos.len());
+            }
+            _ => {}
+        }
+    }
+
+    while !alice_repos.is_empty() {
+        match bob_events.recv().unwrap() {
+            service::Event::RefsFetched { rid, updated, .. } if !updated.is_empty() => {
+                alice_repos.remove(&rid);
+                log::debug!(target: "test", "{} fetched {rid} ({} left)", bob.id, alice_repos.len());
+            }
+            _ => {}
+        }
+    }
+
+    for rid in &bob_repos {
+        let (_, doc) = alice
+            .storage
+            .repository(*rid)
+            .unwrap()
+            .identity_doc()
+            .unwrap();
+        let proj = doc.verified().unwrap().project().unwrap();
+
+        assert!(proj.name().starts_with("bob"));
+    }
+    for rid in &alice_repos {
+        let (_, doc) = bob
+            .storage
+            .repository(*rid)
+            .unwrap()
+            .identity_doc()
+            .unwrap();
+        let proj = doc.verified().unwrap().project().unwrap();
+
+        assert!(proj.name().starts_with("alice"));
+    }
+}
+
+#[test]
+#[ignore = "failing"]
+#[should_panic]
+// TODO: This test currently passes but the behavior is wrong. The test should not panic.
+// We should figure out why we end up with no sessions established.
+fn test_connection_crossing() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+
+    alice
+        .handle
+        .connect(bob.id, bob.addr.into(), ConnectOptions::default())
+        .unwrap();
+    bob.handle
+        .connect(alice.id, alice.addr.into(), ConnectOptions::default())
+        .unwrap();
+
+    thread::sleep(time::Duration::from_secs(1));
+
+    let s1 = alice
+        .handle
+        .sessions()
+        .unwrap()
+        .iter()
+        .any(|s| s.nid == bob.id);
+    let s2 = bob
+        .handle
+        .sessions()
+        .unwrap()
+        .iter()
+        .any(|s| s.nid == alice.id);
+
+    assert!(s1 ^ s2, "Exactly one session should be established");
+}
+
+#[test]
+/// Alice is going to try to fetch outdated refs of Bob, from Eve. This is a non-fastfoward fetch
+/// on the sigrefs branch.
+fn test_non_fastforward_sigrefs() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+
+    let rid = bob.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    alice.handle.track_repo(rid, Scope::All).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+
+    alice.connect(&bob);
+    alice.connect(&eve);
+    eve.connect(&bob);
+
+    converge([&alice, &bob, &eve]);
+
+    // Eve fetches the inital project from Bob.
+    eve.handle.fetch(rid, bob.id).unwrap();
+    // Alice fetches it too.
+    alice.handle.fetch(rid, bob.id).unwrap();
+
+    // Now Eve disconnects from Bob so she doesn't fetch his update.
+    eve.handle
+        .command(service::Command::Disconnect(bob.id))
+        .unwrap();
+
+    // Bob updates his refs.
+    bob.issue(
+        rid,
+        "Updated Sigrefs",
+        "Updated sigrefs are harshing my vibes",
+    );
+    // Alice fetches from Bob.
+    alice.handle.fetch(rid, bob.id).unwrap();
+
+    // Now Alice has the latest, and when she tries to fetch from Eve, it breaks because
+    // Eve has old refs.
+    assert_matches!(
+        alice.handle.fetch(rid, eve.id).unwrap(),
+        FetchResult::Success { .. }
+    );
+}
+
+#[test]
+fn test_partition_heal() {
+    logger::init(log::Level::Debug);
+
+    let mut env = Environment::new();
+    let mut alice = Node::init(&env.tmp(), Config::test(Alias::new("alice")));
+    let bob = Node::init(&env.tmp(), Config::test(Alias::new("bob")));
+    let eve = Node::init(&env.tmp(), Config::test(Alias::new("eve")));
+
+    let rid = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    alice.handle.track_repo(rid, Scope::All).unwrap();
+    bob.handle.track_repo(rid, Scope::All).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&bob);
+    alice.connect(&eve);
+    converge([&alice, &bob, &eve]);
+
+    bob.handle.fetch(rid, alice.id).unwrap();
+    eve.handle.fetch(rid, alice.id).unwrap();
+    // Connect Bob and Eve once both have the repository, to avoid racing fetches.
+    bob.connect(&eve);
+    rad::fork(rid, &bob.signer, &bob.storage).unwrap();
+
+    // Alice is cut off from Bob and Eve.
+    env.partition([&alice], [&bob, &eve]);
+
+    assert!(!alice
+        .handle
+        .sessions()
+        .unwrap()
+        .iter()
+        .any(|s| s.state.is_connected()));
+    assert!(bob
+        .handle
+        .sessions()
+        .unwrap()
+        .iter()
+        .any(|s| s.nid == eve.id && s.state.is_connected()));
+
+    // Reconnecting across the partition fails.
+    assert_matches!(
+        alice
+            .handle
+            .connect(
+                bob.id,
+                bob.addr.into(),
+                ConnectOptions {
+                    persistent: false,
+                    timeout: time::Duration::from_secs(3),
+                }
+            )
+            .unwrap(),
+        ConnectResult::Disconnected { .. }
+    );
+
+    // Both sides of the partition diverge.
+    alice.issue(rid, "Alice's issue", "Created during the partition");
+    alice.handle.announce_refs(rid).unwrap();
+    bob.issue(rid, "Bob's issue", "Created during the partition");
+    bob.handle.announce_refs(rid).unwrap();
+
+    // Bob's update only reaches his side of the partition.
+    eve.has_refs_of(&rid, &bob);
+    let repo = alice.storage.repository(rid).unwrap();
+    let latest = bob
+        .storage
+        .repository(rid)
+        .unwrap()
+        .remote(&bob.id)
+        .unwrap();
+    assert!(repo
+        .remote(&bob.id)
+        .map_or(true, |r| r.refs.signature != latest.refs.signature));
+
+    // Once healed, announcements reach the other side again.
+    env.heal();
+    alice.handle.announce_refs(rid).unwrap();
+    bob.handle.announce_refs(rid).unwrap();
+
+    alice.has_refs_of(&rid, &bob);
+    bob.has_refs_of(&rid, &alice);
+    eve.has_refs_of(&rid, &alice);
+
+    converge_within([&alice, &bob, &eve], time::Duration::from_secs(6)).unwrap();
+}
+
+#[test]
+fn test_outdated_sigrefs() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+
+    let rid = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.handle.track_repo(rid, Scope::All).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&bob);
+    bob.connect(&eve);
+    eve.connect(&alice);
+    converge([&alice, &bob, &eve]);
+
+    bob.handle.fetch(rid, alice.id).unwrap();
+    assert!(bob.storage.contains(&rid).unwrap());
+    rad::fork(rid, &bob.signer, &bob.storage).unwrap();
+
+    eve.handle.fetch(rid, alice.id).unwrap();
+    assert!(eve.storage.contains(&rid).unwrap());
+    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+
+    alice
+        .handle
+        .track_node(eve.id, Some(Alias::new("eve")))
+        .unwrap();
+    alice.handle.fetch(rid, eve.id).unwrap();
+    let repo = alice.storage.repository(rid).unwrap();
+    assert!(repo.remote(&eve.id).is_ok());
+
+    assert_matches!(
+        bob.handle.fetch(rid, eve.id).unwrap(),
+        FetchResult::Success { .. }
+    );
+    let repo = bob.storage.repository(rid).unwrap();
+    let eve_remote = repo.remote(&eve.id).unwrap();
+    let old_refs = eve_remote.refs;
+
+    // At this stage, Alice and Bob have Eve's fork and Eve does not
+    // have Bob's fork
+
+    eve.issue(
+        rid,
+        "Outdated Sigrefs",
+        "Outdated sigrefs are harshing my vibes",
+    );
+    let repo = eve.storage.repository(rid).unwrap();
+    let eves_refs = repo.remote(&eve.id).unwrap().refs;
+
+    // Get the current state of eve's refs in alice's storage
+    log::debug!(target: "test", "Alice fetches from Eve..");
+    assert_matches!(
+        alice.handle.fetch(rid, eve.id).unwrap(),
+        FetchResult::Success { .. }
+    );
+    let repo = alice.storage.repository(rid).unwrap();
+    let eve_remote = repo.remote(&eve.id).unwrap();
+    let eves_refs_expected = eve_remote.refs;
+    assert_ne!(eves_refs_expected, old_refs);
+    assert_eq!(eves_refs_expected, eves_refs);
+
+    log::debug!(target: "test", "Alice fetches from Bob..");
+
+    alice
+        .handle
+        .track_node(bob.id, Some(Alias::new("bob")))
+        .unwrap();
+    assert_matches!(
+        alice.handle.fetch(rid, bob.id).unwrap(),
+        FetchResult::Success { .. }
+    );
+
+    // Ensure that Eve's refs have not changed after fetching the old refs from Bob.
+    let repo = alice.storage.repository(rid).unwrap();
+    let eve_remote = repo.remote(&eve.id).unwrap();
+    let eves_refs = eve_remote.refs;
+
+    assert_ne!(eves_refs, old_refs);
+    assert_eq!(eves_refs_expected, eves_refs);
+}
+
+#[test]
+fn test_delegates_changed() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+    let rid = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.handle.track_repo(rid, Scope::Trusted).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&bob);
+    alice.connect(&eve);
+    converge([&alice, &bob, &eve]);
+
+    // Eve forks the repository, and Alice fetches her fork.
+    eve.handle.fetch(rid, alice.id).unwrap();
+    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+    alice
+        .handle
+        .track_node(eve.id, Some(Alias::new("eve")))
+        .unwrap();
+    alice.handle.fetch(rid, eve.id).unwrap();
+
+    // Bob only tracks the delegates, so he doesn't get Eve's fork.
+    bob.handle.fetch(rid, alice.id).unwrap();
+    assert!(bob
+        .storage
+        .repository(rid)
+        .unwrap()
+        .remote(&eve.id)
+        .is_err());
+
+    // Alice makes Eve a delegate.
+    {
+        let repo = alice.storage.repository_mut(rid).unwrap();
+        let (_, doc) = repo.identity_doc().unwrap();
+        let mut doc = doc.verified().unwrap();
+
+        assert!(doc.delegate(&eve.id));
+        doc.sign(&alice.signer)
+            .and_then(|(_, sig)| doc.update(&alice.id, "Add eve", &[(&alice.id, sig)], repo.raw()))
+            .unwrap();
+        repo.sign_refs(&alice.signer).unwrap();
+        repo.set_identity_head().unwrap();
+    }
+
+    // When Bob fetches the new identity, he fetches Eve's namespace right away, since
+    // it's now in scope.
+    let events = bob.handle.events();
+    bob.handle.fetch(rid, alice.id).unwrap();
+    events
+        .wait(
+            |e| match e {
+                Event::DelegatesChanged {
+                    rid: r,
+                    added,
+                    removed,
+                } if *r == rid => {
+                    assert_eq!(added, &vec![eve.id]);
+                    assert!(removed.is_empty());
+                    Some(())
+                }
+                _ => None,
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+    events
+        .wait(
+            |e| {
+                matches!(e, Event::RefsFetched { rid: r, updated, .. }
+                if *r == rid && updated.iter().any(|u| match u {
+                    RefUpdate::Created { name, .. } => name.as_str().contains(&eve.id.to_string()),
+                    _ => false,
+                }))
+                .then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
+
+    let repo = bob.storage.repository(rid).unwrap();
+    assert!(repo.remote(&eve.id).is_ok());
+    assert!(repo.delegates().unwrap().contains(&eve.id.into()));
+}
+
+#[test]
+fn test_fetch_oversized_cob_entry() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+    let rid = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.handle.track_repo(rid, Scope::All).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&eve);
+    bob.connect(&eve);
+    converge([&alice, &bob, &eve]);
+
+    eve.handle.fetch(rid, alice.id).unwrap();
+    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+    let issue = eve.issue(rid, "Oversized", "This issue is about to grow");
+
+    // Bob gets Eve's namespace while it's valid. Nb. Only the delegates' namespaces are
+    // fetched when cloning.
+    bob.handle.fetch(rid, eve.id).unwrap();
+    let (_, namespaces) = bob.handle.fetch(rid, eve.id).unwrap().success().unwrap();
+    assert!(namespaces.contains(&eve.id));
+    let sigrefs = bob
+        .storage
+        .repository(rid)
+        .unwrap()
+        .reference_oid(&eve.id, &git::refs::storage::SIGREFS_BRANCH)
+        .unwrap();
+
+    // Alice opens an issue, which Eve fetches.
+    let acme = alice.issue(rid, "Normal", "This issue is of a reasonable size");
+    eve.handle.fetch(rid, alice.id).unwrap();
+
+    // Eve adds an entry with too many blobs to her issue, bypassing the checks done when
+    // entries are created.
+    {
+        let repo = eve.storage.repository(rid).unwrap();
+        let refname = git::refs::storage::cob(&eve.id, &issue::TYPENAME, &issue);
+        let tip = repo.backend.refname_to_id(refname.as_str()).unwrap();
+        let commit = repo.backend.find_commit(tip).unwrap();
+        let mut tree = repo
+            .backend
+            .treebuilder(Some(&commit.tree().unwrap()))
+            .unwrap();
+        let blob = repo.backend.blob(b"{}").unwrap();
+
+        for ix in 0..=radicle::cob::change::store::MAX_ENTRY_BLOBS {
+            tree.insert(ix.to_string(), blob, git::raw::FileMode::Blob.into())
+                .unwrap();
+        }
+        let tree = repo.backend.find_tree(tree.write().unwrap()).unwrap();
+        let author = commit.author();
+        let oversized = repo
+            .backend
+            .commit(
+                None,
+                &author,
+                &author,
+                commit.message().unwrap(),
+                &tree,
+                &[&commit],
+            )
+            .unwrap();
+
+        repo.backend
+            .reference(refname.as_str(), oversized, true, "Oversized entry")
+            .unwrap();
+        repo.sign_refs(&eve.signer).unwrap();
+    }
+
+    // Eve's namespace is skipped, but Alice's new issue is fetched.
+    let (_, namespaces) = bob.handle.fetch(rid, eve.id).unwrap().success().unwrap();
+    assert!(namespaces.contains(&alice.id));
+    assert!(!namespaces.contains(&eve.id));
+
+    let repo = bob.storage.repository(rid).unwrap();
+    let issues = issue::Issues::open(&repo).unwrap();
+    assert!(issues.get(&acme).unwrap().is_some());
+    assert_eq!(
+        repo.reference_oid(&eve.id, &git::refs::storage::SIGREFS_BRANCH)
+            .unwrap(),
+        sigrefs
+    );
+}
+
+#[test]
+fn test_fetch_shape_limit() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(
+        tmp.path(),
+        Config {
+            limits: Limits {
+                fetch_shape: ShapeLimits {
+                    max_refs: 16,
+                    ..ShapeLimits::default()
+                },
+                ..Limits::default()
+            },
+            ..Config::test(Alias::new("bob"))
+        },
+    );
+    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+    let rid = alice.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.handle.track_repo(rid, Scope::All).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&eve);
+    bob.connect(&eve);
+    converge([&alice, &bob, &eve]);
+
+    eve.handle.fetch(rid, alice.id).unwrap();
+    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+
+    // Bob gets Eve's namespace while it's within the limits.
+    bob.handle.fetch(rid, eve.id).unwrap();
+    let (_, namespaces) = bob.handle.fetch(rid, eve.id).unwrap().success().unwrap();
+    assert!(namespaces.contains(&eve.id));
+    let sigrefs = bob
+        .storage
+        .repository(rid)
+        .unwrap()
+        .reference_oid(&eve.id, &git::refs::storage::SIGREFS_BRANCH)
+        .unwrap();
+
+    // Alice opens an issue, which Eve fetches.
+    let acme = alice.issue(rid, "Normal", "This issue is of a reasonable size");
+    eve.handle.fetch(rid, alice.id).unwrap();
+
+    // Eve creates thousands of branches, way over Bob's limit.
+    {
+        let repo = eve.storage.repository(rid).unwrap();
+        let (_, head) = repo.head().unwrap();
+
+        for ix in 0..2048 {
+            let refname = git::refs::storage::branch_of(
+                &eve.id,
+                &git::RefString::try_from(format!("branch-{ix}")).unwrap(),
+            );
+            repo.backend
+                .reference(refname.as_str(), head.into(), true, "Generated branch")
+                .unwrap();
+        }
+        repo.sign_refs(&eve.signer).unwrap();
+    }
+
+    // Eve's namespace is quarantined, but Alice's new issue is fetched.
+    let result = bob.handle.fetch(rid, eve.id).unwrap();
+    assert_matches!(
+        &result,
+        FetchResult::Success { skipped, .. }
+            if matches!(skipped.as_slice(), [(nid, SkipReason::Quarantined { .. })] if *nid == eve.id)
+    );
+    let history = bob.handle.fetch_history(rid).unwrap();
+    assert_matches!(
+        history.last(),
+        Some(FetchRecord {
+            outcome: FetchOutcome::Success,
+            ..
+        })
+    );
+
+    let repo = bob.storage.repository(rid).unwrap();
+    let issues = issue::Issues::open(&repo).unwrap();
+    assert!(issues.get(&acme).unwrap().is_some());
+
+    // None of Eve's new refs were transferred.
+    assert_eq!(
+        repo.reference_oid(&eve.id, &git::refs::storage::SIGREFS_BRANCH)
+            .unwrap(),
+        sigrefs
+    );
+    assert!(repo
+        .reference_oid(&eve.id, &git::qualified!("refs/heads/branch-0"))
+        .is_err());
+}
+
+#[test]
+fn test_fetch_negotiation() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+    let repo = fixtures::populated(tmp.path().join("acme"), 1);
+    let rid = alice.project_from("acme", "", &repo);
+
+    let mut alice = alice.spawn();
+    let mut bob = bob.spawn();
+    let mut eve = eve.spawn();
+
+    bob.handle.track_repo(rid, Scope::All).unwrap();
+    eve.handle.track_repo(rid, Scope::All).unwrap();
+    alice.connect(&bob);
+    alice.connect(&eve);
+    bob.connect(&eve);
+    converge([&alice, &bob, &eve]);
+
+    // Eve forks the repository, so that her namespace shares its history with Alice's.
+    eve.handle.fetch(rid, alice.id).unwrap();
+    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+
+    // Bob clones from Alice, and then fetches Eve's namespace from Eve.
+    assert!(bob.handle.fetch(rid, alice.id).unwrap().is_success());
+    let (_, namespaces) = bob.handle.fetch(rid, eve.id).unwrap().success().unwrap();
+    assert!(namespaces.contains(&eve.id));
+
+    // Nb. Bob may have fetched from Alice more than once, since he tracks the repository.
+    let history = bob.handle.fetch_history(rid).unwrap();
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        panic!("unexpected fetch history: {history:?}");
+    };
+    assert_eq!(first.seed, alice.id);
+    assert_eq!(last.seed, eve.id);
+
+    let (first, second) = (first.received.unwrap(), last.received.unwrap());
+    log::debug!(target: "test", "Received {first} byte(s) from Alice, and {second} byte(s) from Eve");
+
+    // Only the objects that are new in Eve's namespace are sent.
+    assert!(
+        second * 10 < first,
+        "{second} is not a small fraction of {first}"
+    );
+}
+
+/// Make an HTTP request to the given address, and return the status code and JSON body.
+fn http(addr: net::SocketAddr, method: &str, path: &str) -> (u16, serde_json::Value) {
+    let mut stream = net::TcpStream::connect(addr).unwrap();
+    let mut response = String::new();
+
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n"
+    )
+    .unwrap();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
 
-    let head = working.head().unwrap();
-    let oid = head.target().unwrap();
+    (status, serde_json::from_str(body).unwrap_or_default())
+}
 
-    let (_, canonical) = bob
-        .storage
-        .repository(acme)
-        .unwrap()
-        .canonical_head()
+#[test]
+fn test_http_disabled_by_default() {
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let alice = alice.spawn();
+
+    assert!(Config::new(Alias::new("alice")).http.listen.is_none());
+    assert!(alice.http.is_none());
+}
+
+#[test]
+fn test_http_endpoints() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Http {
+        listen: Some(([127, 0, 0, 1], 0).into()),
+        ..Http::default()
+    };
+    let alice = Node::init(
+        tmp.path(),
+        Config {
+            http: config.clone(),
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let mut bob = Node::init(
+        tmp.path(),
+        Config {
+            http: Http {
+                list_peers: true,
+                ..config
+            },
+            ..Config::test(Alias::new("bob"))
+        },
+    );
+    let acme = bob.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+    let (alice_http, bob_http) = (alice.http.unwrap(), bob.http.unwrap());
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    transport::local::register(alice.storage.clone());
+
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    assert!(alice.handle.fetch(acme, bob.id).unwrap().is_success());
+
+    let (status, body) = http(alice_http, "GET", "/status");
+    assert_eq!(status, 200);
+    assert_eq!(body["nid"], alice.id.to_string());
+    assert_eq!(body["peers"], 1);
+
+    let (status, body) = http(alice_http, "GET", &format!("/repos/{acme}"));
+    assert_eq!(status, 200);
+    assert_eq!(body["rid"], acme.to_string());
+    assert_eq!(body["seeding"], true);
+    assert_eq!(body["seeds"], 1);
+    assert!(body["lastFetch"].is_u64());
+
+    // Peers are only counted, unless listing them is enabled.
+    let (status, body) = http(alice_http, "GET", "/peers");
+    assert_eq!(status, 200);
+    assert_eq!(body, serde_json::json!({ "count": 1 }));
+
+    let (status, body) = http(bob_http, "GET", "/peers");
+    assert_eq!(status, 200);
+    assert_eq!(body["count"], 1);
+    assert_eq!(body["peers"][0]["nid"], alice.id.to_string());
+
+    let (status, body) = http(bob_http, "GET", &format!("/repos/{acme}"));
+    assert_eq!(status, 200);
+    assert_eq!(body["seeding"], true);
+    assert!(body["lastFetch"].is_null());
+
+    let (status, _) = http(alice_http, "GET", "/repos/acme");
+    assert_eq!(status, 400);
+    let (status, _) = http(alice_http, "GET", "/repos");
+    assert_eq!(status, 404);
+    let (status, _) = http(alice_http, "POST", "/status");
+    assert_eq!(status, 405);
+    let (status, _) = http(alice_http, "DELETE", &format!("/repos/{acme}"));
+    assert_eq!(status, 405);
+}
+
+#[test]
+fn test_http_rate_limit() {
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(
+        tmp.path(),
+        Config {
+            http: Http {
+                listen: Some(([127, 0, 0, 1], 0).into()),
+                rate_limit: 3,
+                ..Http::default()
+            },
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let alice = alice.spawn();
+    let addr = alice.http.unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(http(addr, "GET", "/status").0, 200);
+    }
+    let (status, body) = http(addr, "GET", "/status");
+    assert_eq!(status, 429);
+    assert_eq!(body["error"], "rate limit exceeded");
+}
+
+#[test]
+fn test_http_slow_client() {
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(
+        tmp.path(),
+        Config {
+            http: Http {
+                listen: Some(([127, 0, 0, 1], 0).into()),
+                ..Http::default()
+            },
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let alice = alice.spawn();
+    let addr = alice.http.unwrap();
+
+    // A client that never sends its request doesn't hold up other clients.
+    let mut slow = net::TcpStream::connect(addr).unwrap();
+    assert_eq!(http(addr, "GET", "/status").0, 200);
+
+    // It is disconnected once the read timeout expires.
+    let mut buf = Vec::new();
+    slow.set_read_timeout(Some(time::Duration::from_secs(10)))
         .unwrap();
+    slow.read_to_end(&mut buf).unwrap();
+    assert!(buf.is_empty());
+}
 
-    assert_eq!(oid, *canonical);
+#[test]
+fn test_http_events() {
+    logger::init(log::Level::Debug);
 
-    // Make sure that bob has refs/rad/id set
-    assert!(bob
-        .storage
-        .repository(acme)
-        .unwrap()
-        .identity_head()
-        .is_ok());
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(
+        tmp.path(),
+        Config {
+            http: Http {
+                listen: Some(([127, 0, 0, 1], 0).into()),
+                ..Http::default()
+            },
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+    let addr = alice.http.unwrap();
+
+    let (status, body) = http(addr, "GET", "/status");
+    assert_eq!(status, 200);
+    assert_eq!(body["nid"], alice.id.to_string());
+
+    let mut stream = net::TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let mut lines = std::io::BufReader::new(stream).lines();
+    let status = lines.next().unwrap().unwrap();
+    assert_eq!(status, "HTTP/1.1 200 OK");
+
+    let head = lines
+        .by_ref()
+        .map(|line| line.unwrap())
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    assert!(head.contains(&String::from("Content-Type: text/event-stream")));
+
+    alice.connect(&bob);
+
+    let event = lines
+        .map(|line| line.unwrap())
+        .filter_map(|line| {
+            line.strip_prefix("data: ")
+                .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        })
+        .find(|event| event["type"] == "peerConnected")
+        .unwrap();
+    assert_eq!(event["nid"], bob.id.to_string());
 }
 
 #[test]
-fn test_fetch_up_to_date() {
+fn test_http_events_closed() {
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(
+        tmp.path(),
+        Config {
+            http: Http {
+                listen: Some(([127, 0, 0, 1], 0).into()),
+                ..Http::default()
+            },
+            limits: Limits {
+                subscribe_heartbeat: LocalDuration::from_millis(100),
+                ..Limits::default()
+            },
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let alice = alice.spawn();
+    let addr = alice.http.unwrap();
+    let subscribe = || {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        write!(stream, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let mut status = String::new();
+        std::io::BufReader::new(&stream)
+            .read_line(&mut status)
+            .unwrap();
+        (status, stream)
+    };
+
+    // Use up all event streams, then close them.
+    let streams = (0..8)
+        .map(|_| {
+            let (status, stream) = subscribe();
+            assert!(status.starts_with("HTTP/1.1 200"), "{status}");
+            stream
+        })
+        .collect::<Vec<_>>();
+    assert!(subscribe().0.starts_with("HTTP/1.1 503"));
+    drop(streams);
+
+    // Once the heartbeats fail to be written, the streams are dropped, and new clients
+    // can subscribe again.
+    let mut attempts = 0;
+    while !subscribe().0.starts_with("HTTP/1.1 200") {
+        attempts += 1;
+        assert!(attempts < 50, "event streams were not dropped");
+        thread::sleep(time::Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_fetch_history() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
     let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
     let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
     let acme = bob.project("acme", "");
+    let unknown = radicle::test::arbitrary::gen::<radicle::prelude::Id>(1);
 
     let mut alice = alice.spawn();
     let bob = bob.spawn();
@@ -565,344 +2300,630 @@ fn test_fetch_up_to_date() {
     alice.connect(&bob);
     converge([&alice, &bob]);
 
-    transport::local::register(alice.storage.clone());
+    alice.handle.track_repo(unknown, Scope::All).unwrap();
+    let result = alice.handle.fetch(unknown, bob.id).unwrap();
+    assert!(!result.is_success());
 
-    let _ = alice.handle.track_repo(acme, Scope::All).unwrap();
+    alice.handle.track_repo(acme, Scope::All).unwrap();
     let result = alice.handle.fetch(acme, bob.id).unwrap();
     assert!(result.is_success());
 
-    // Fetch again! This time, everything's up to date.
-    let result = alice.handle.fetch(acme, bob.id).unwrap();
-    assert_eq!(
-        result.success(),
-        Some((vec![], HashSet::from_iter([bob.id])))
+    let history = alice.handle.fetch_history(unknown).unwrap();
+    assert_matches!(
+        history.as_slice(),
+        [FetchRecord {
+            seed,
+            outcome: FetchOutcome::Failed {
+                failure: FetchFailure::NotFound,
+                ..
+            },
+            ..
+        }] if *seed == bob.id
+    );
+
+    let history = alice.handle.fetch_history(acme).unwrap();
+    assert_matches!(
+        history.as_slice(),
+        [FetchRecord {
+            seed,
+            duration: Some(_),
+            outcome: FetchOutcome::Success,
+            ..
+        }] if *seed == bob.id
     );
 }
 
 #[test]
-fn test_large_fetch() {
+fn test_fetch_size_limit() {
     logger::init(log::Level::Debug);
 
-    let env = Environment::new();
-    let scale = env.scale();
-    let mut alice = Node::init(&env.tmp(), Config::test(Alias::new("alice")));
-    let bob = Node::init(&env.tmp(), Config::test(Alias::new("bob")));
-
     let tmp = tempfile::tempdir().unwrap();
-    let (repo, _) = fixtures::repository(tmp.path());
-    fixtures::populate(&repo, scale.max(3));
-
-    let rid = alice.project_from("acme", "", &repo);
+    let alice = Node::init(
+        tmp.path(),
+        Config {
+            limits: Limits {
+                fetch_pack_max_bytes: FetchPackLimit {
+                    clone: 1024,
+                    ..FetchPackLimit::default()
+                },
+                ..Limits::default()
+            },
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let repo = fixtures::populated(tmp.path().join("acme"), 3);
+    let acme = bob.project_from("acme", "", &repo);
 
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
-    let bob_events = bob.handle.events();
+    let bob = bob.spawn();
 
-    bob.handle.track_repo(rid, Scope::All).unwrap();
     alice.connect(&bob);
+    converge([&alice, &bob]);
 
-    bob_events
+    // The clone goes over the limit, and is aborted.
+    let events = alice.handle.events();
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(!result.is_success());
+
+    let history = alice.handle.fetch_history(acme).unwrap();
+    assert_matches!(
+        history.as_slice(),
+        [FetchRecord {
+            seed,
+            received: Some(received),
+            outcome: FetchOutcome::Failed {
+                failure: FetchFailure::SizeLimit,
+                ..
+            },
+            ..
+        }] if *seed == bob.id && *received > 1024
+    );
+    // Nothing was stored.
+    assert!(!alice.storage.contains(&acme).unwrap());
+    assert!(alice.storage.inventory().unwrap().is_empty());
+
+    // Bob was disconnected, since he went over the limit.
+    events
         .wait(
             |e| {
-                matches!(e, service::Event::RefsFetched { updated, .. } if !updated.is_empty())
+                matches!(e, service::Event::PeerDisconnected { nid, .. } if *nid == bob.id)
                     .then_some(())
             },
-            time::Duration::from_secs(9 * scale as u64),
+            time::Duration::from_secs(6),
         )
         .unwrap();
 
-    let (_, doc) = bob.storage.repository(rid).unwrap().identity_doc().unwrap();
-    let proj = doc.verified().unwrap().project().unwrap();
+    // The limit of the repository's tracking policy takes precedence.
+    let mut tracking = tracking::Config::open(alice.home.node().join(TRACKING_DB_FILE)).unwrap();
+    assert!(tracking
+        .set_repo_fetch_limit(&acme, Some(u64::MAX))
+        .unwrap());
 
-    assert_eq!(proj.name(), "acme");
+    alice.connect(&bob);
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success());
+    assert!(alice.storage.contains(&acme).unwrap());
 }
 
 #[test]
-fn test_concurrent_fetches() {
+fn test_fetch_ref_filter() {
     logger::init(log::Level::Debug);
 
-    let env = Environment::new();
-    let scale = env.scale();
-    let repos = scale.max(4);
-    let limits = Limits {
-        // Have one fetch be queued.
-        fetch_concurrency: repos - 1,
-        ..Limits::default()
-    };
-    let mut bob_repos = HashSet::new();
-    let mut alice_repos = HashSet::new();
-    let mut alice = Node::init(
-        &env.tmp(),
-        service::Config {
-            limits: limits.clone(),
-            ..service::Config::test(Alias::new("alice"))
-        },
-    );
-    let mut bob = Node::init(
-        &env.tmp(),
-        service::Config {
-            limits,
-            ..service::Config::test(Alias::new("bob"))
-        },
-    );
-
-    for i in 0..repos {
-        // Create a repo for Alice.
-        let tmp = tempfile::tempdir().unwrap();
-        let (repo, _) = fixtures::repository(tmp.path());
-        fixtures::populate(&repo, scale);
-
-        let rid = alice.project_from(&format!("alice-{i}"), "", &repo);
-        alice_repos.insert(rid);
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
 
-        // Create a repo for Bob.
-        let tmp = tempfile::tempdir().unwrap();
-        let (repo, _) = fixtures::repository(tmp.path());
-        fixtures::populate(&repo, scale);
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
 
-        let rid = bob.project_from(&format!("bob-{i}"), "", &repo);
-        bob_repos.insert(rid);
-    }
+    // Bob pushes a build artifact to a new branch, and signs it.
+    let push_artifact = |name: &git::Qualified| {
+        let repo = bob.storage.repository_mut(acme).unwrap();
+        let (_, head) = repo.head().unwrap();
+        let parent = repo.raw().find_commit(*head).unwrap();
+        let mut tree = repo
+            .raw()
+            .treebuilder(Some(&parent.tree().unwrap()))
+            .unwrap();
+        let blob = repo.raw().blob(&[0xff; 4096]).unwrap();
+        tree.insert("artifact.bin", blob, git::raw::FileMode::Blob.into())
+            .unwrap();
+        let tree = repo.raw().find_tree(tree.write().unwrap()).unwrap();
+        let author = parent.author();
+        let artifact = repo
+            .raw()
+            .commit(None, &author, &author, "Build", &tree, &[&parent])
+            .unwrap();
 
-    let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
+        repo.raw()
+            .reference(
+                &name.with_namespace((&bob.id).into()),
+                artifact,
+                true,
+                "Build artifact",
+            )
+            .unwrap();
+        repo.sign_refs(&bob.signer).unwrap();
 
-    let alice_events = alice.handle.events();
-    let bob_events = bob.handle.events();
+        artifact
+    };
+    let build = git::qualified!("refs/heads/artifacts/build-1");
+    let artifact = push_artifact(&build);
 
-    for rid in &bob_repos {
-        alice.handle.track_repo(*rid, Scope::All).unwrap();
-    }
-    for rid in &alice_repos {
-        bob.handle.track_repo(*rid, Scope::All).unwrap();
-    }
     alice.connect(&bob);
+    converge([&alice, &bob]);
 
-    while !bob_repos.is_empty() {
-        match alice_events.recv().unwrap() {
-            service::Event::RefsFetched { rid, updated, .. } if !updated.is_empty() => {
-                bob_repos.remove(&rid);
-                log::debug!(target: "test", "{} fetched {rid} ({} left)",alice.id, bob_repos.len());
-            }
-            _ => {}
-        }
-    }
+    let filter = RefFilter {
+        include: vec![],
+        exclude: vec![git::refspec::pattern!("refs/heads/artifacts/*")],
+    };
+    assert!(alice
+        .handle
+        .track_repo_filter(acme, Scope::All, filter)
+        .unwrap());
+
+    // Bob's remote is verified, even though its excluded branch is missing.
+    let (updated, namespaces) = alice.handle.fetch(acme, bob.id).unwrap().success().unwrap();
+    assert!(namespaces.contains(&bob.id));
+    assert!(updated.iter().any(|u| matches!(
+        u,
+        RefUpdate::Skipped { name, oid }
+            if name.as_str().ends_with(build.as_str()) && **oid == artifact
+    )));
 
-    while !alice_repos.is_empty() {
-        match bob_events.recv().unwrap() {
-            service::Event::RefsFetched { rid, updated, .. } if !updated.is_empty() => {
-                alice_repos.remove(&rid);
-                log::debug!(target: "test", "{} fetched {rid} ({} left)", bob.id, alice_repos.len());
-            }
-            _ => {}
-        }
-    }
+    let repo = alice.storage.repository(acme).unwrap();
+    assert!(repo
+        .reference_oid(&bob.id, &git::qualified!("refs/heads/master"))
+        .is_ok());
+    assert!(repo.reference_oid(&bob.id, &build).is_err());
+    // The excluded branch's objects were never transferred.
+    assert!(repo.raw().find_commit(artifact).is_err());
 
-    for rid in &bob_repos {
-        let (_, doc) = alice
-            .storage
-            .repository(*rid)
-            .unwrap()
-            .identity_doc()
-            .unwrap();
-        let proj = doc.verified().unwrap().project().unwrap();
+    // Excluded branches are also skipped when fetching updates.
+    let build = git::qualified!("refs/heads/artifacts/build-2");
+    let artifact = push_artifact(&build);
 
-        assert!(proj.name().starts_with("bob"));
-    }
-    for rid in &alice_repos {
-        let (_, doc) = bob
-            .storage
-            .repository(*rid)
-            .unwrap()
-            .identity_doc()
-            .unwrap();
-        let proj = doc.verified().unwrap().project().unwrap();
+    let (_, namespaces) = alice.handle.fetch(acme, bob.id).unwrap().success().unwrap();
+    assert!(namespaces.contains(&bob.id));
 
-        assert!(proj.name().starts_with("alice"));
-    }
+    let repo = alice.storage.repository(acme).unwrap();
+    assert!(repo.reference_oid(&bob.id, &build).is_err());
+    assert!(repo.raw().find_commit(artifact).is_err());
+
+    // Bob's remote is missing some of its signed refs, so it isn't served to others.
+    let hidden = |repo: &radicle::storage::git::Repository| {
+        let config = repo.raw().config().unwrap();
+        let entries = config.multivar("uploadpack.hideRefs", None).unwrap();
+        let hidden = (&entries)
+            .map(|e| e.unwrap().value().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        hidden
+    };
+    assert_eq!(hidden(&repo), vec![format!("refs/namespaces/{}", bob.id)]);
+
+    // Once all refs are fetched again, it is served again.
+    alice
+        .handle
+        .track_repo_filter(acme, Scope::All, RefFilter::default())
+        .unwrap();
+    push_artifact(&git::qualified!("refs/heads/artifacts/build-3"));
+    alice.handle.fetch(acme, bob.id).unwrap().success().unwrap();
+
+    let repo = alice.storage.repository(acme).unwrap();
+    assert!(repo.reference_oid(&bob.id, &build).is_ok());
+    assert!(hidden(&repo).is_empty());
+    repo.validate().unwrap();
 }
 
 #[test]
-#[ignore = "failing"]
-#[should_panic]
-// TODO: This test currently passes but the behavior is wrong. The test should not panic.
-// We should figure out why we end up with no sessions established.
-fn test_connection_crossing() {
+fn test_export_import_policies() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
     let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
     let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let rids = radicle::test::arbitrary::vec::<radicle::prelude::Id>(2);
+    let eve = radicle::test::arbitrary::gen::<radicle::node::NodeId>(1);
 
     let mut alice = alice.spawn();
-    let mut bob = bob.spawn();
+    let bob = bob.spawn();
 
+    alice.handle.track_repo(rids[0], Scope::All).unwrap();
+    alice.handle.track_repo(rids[1], Scope::Trusted).unwrap();
     alice
         .handle
-        .connect(bob.id, bob.addr.into(), ConnectOptions::default())
+        .track_node(eve, Some(Alias::new("eve")))
         .unwrap();
-    bob.handle
-        .connect(alice.id, alice.addr.into(), ConnectOptions::default())
+
+    // Go through the control socket, as a client would.
+    let policies = radicle::Node::new(alice.home.socket())
+        .export_policies()
+        .unwrap();
+    let summary = radicle::Node::new(bob.home.socket())
+        .import_policies(policies.clone())
         .unwrap();
 
-    thread::sleep(time::Duration::from_secs(1));
+    assert_eq!(summary.added, 3);
+    assert_eq!(bob.handle.export_policies().unwrap(), policies);
+}
 
-    let s1 = alice
-        .handle
-        .sessions()
-        .unwrap()
-        .iter()
-        .any(|s| s.nid == bob.id);
-    let s2 = bob
-        .handle
-        .sessions()
-        .unwrap()
-        .iter()
-        .any(|s| s.nid == alice.id);
+#[test]
+fn test_event_log() {
+    logger::init(log::Level::Debug);
 
-    assert!(s1 ^ s2, "Exactly one session should be established");
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        event_log: EventLog {
+            events: [EventKind::RefsFetched].into(),
+            ..EventLog::default()
+        },
+        ..Config::test(Alias::new("alice"))
+    };
+    let alice = Node::init(tmp.path(), config.clone());
+    let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = bob.project("acme", "");
+
+    let mut alice = alice.spawn();
+    let bob = bob.spawn();
+
+    alice.connect(&bob);
+    converge([&alice, &bob]);
+
+    // Events are logged without anyone subscribed.
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success());
+
+    // Bob is shut down first, so that Alice doesn't fetch from him once restarted.
+    let bob_id = bob.id;
+    drop(bob);
+    let alice = alice.restart(config);
+
+    // Read the log from the start, through the control socket, as a consumer would.
+    let node = radicle::Node::new(alice.home.socket());
+    let mut seq = 0;
+    let mut records = Vec::new();
+    loop {
+        let page = node.events_since(seq).unwrap();
+        records.extend(page.events.iter().cloned());
+
+        match page.next() {
+            Some(next) => seq = next,
+            None => break,
+        }
+    }
+    assert!(!records.is_empty());
+    assert!(records.windows(2).all(|w| w[0].seq < w[1].seq));
+    assert!(records.iter().all(|r| matches!(
+        r.event,
+        Event::RefsFetched { rid, remote, .. } if rid == acme && remote == bob_id
+    )));
+
+    // Asking for events following the last one returns nothing, since nothing else
+    // was logged.
+    let last = records.last().unwrap().seq;
+    let page = node.events_since(last).unwrap();
+    assert!(page.events.is_empty());
+    assert!(!page.more);
 }
 
 #[test]
-/// Alice is going to try to fetch outdated refs of Bob, from Eve. This is a non-fastfoward fetch
-/// on the sigrefs branch.
-fn test_non_fastforward_sigrefs() {
+fn test_external_address_validation() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
+    let private = Address::from(net::SocketAddr::from(([192, 168, 1, 10], 8776)));
+    let public = Address::from(net::SocketAddr::from(([1, 1, 1, 1], 8776)));
+    let config = Config {
+        network: Network::Main,
+        external_addresses: vec![private.clone(), public.clone()],
+        ..Config::new(Alias::new("alice"))
+    };
+    let validation = Validation {
+        strict: false,
+        // Nb. We listen on an ephemeral port.
+        allow_port_mismatch: true,
+    };
+    assert_eq!(
+        config.validate(None, validation, |_, _| true),
+        vec![Warning::Private(private.clone())]
+    );
+
+    for (strict, expected) in [
+        (false, vec![private.clone(), public.clone()]),
+        (true, vec![public.clone()]),
+    ] {
+        let node = Node::init(tmp.path(), config.clone());
+        let (_, signals) = crossbeam_channel::bounded(1);
+        let rt = Runtime::init(
+            node.home.clone(),
+            node.config,
+            vec![([127, 0, 0, 1], 0).into()],
+            Validation {
+                strict,
+                ..validation
+            },
+            ([127, 0, 0, 1], 9050).into(),
+            ([127, 0, 0, 1], 0).into(),
+            signals,
+            node.signer,
+        )
+        .unwrap();
+
+        assert_eq!(rt.external_addrs, expected, "strict = {strict}");
+    }
+}
+
+#[test]
+fn test_activity_log() {
+    logger::init(log::Level::Debug);
 
+    let tmp = tempfile::tempdir().unwrap();
     let alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
     let mut bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
-
-    let rid = bob.project("acme", "");
+    let acme = bob.project("acme", "");
 
     let mut alice = alice.spawn();
     let bob = bob.spawn();
-    let mut eve = eve.spawn();
-
-    alice.handle.track_repo(rid, Scope::All).unwrap();
-    eve.handle.track_repo(rid, Scope::All).unwrap();
 
     alice.connect(&bob);
-    alice.connect(&eve);
-    eve.connect(&bob);
+    converge([&alice, &bob]);
 
-    converge([&alice, &bob, &eve]);
+    alice.handle.track_repo(acme, Scope::All).unwrap();
+    let result = alice.handle.fetch(acme, bob.id).unwrap();
+    assert!(result.is_success());
 
-    // Eve fetches the inital project from Bob.
-    eve.handle.fetch(rid, bob.id).unwrap();
-    // Alice fetches it too.
-    alice.handle.fetch(rid, bob.id).unwrap();
+    let entries = alice.handle.activity(None, None).unwrap();
+    let position = |kind: &str| {
+        entries
+            .iter()
+            .position(|e| e.activity.as_str() == kind)
+            .unwrap_or_else(|| panic!("no {kind} entry in activity log: {entries:?}"))
+    };
+    let connected = position("connected");
+    let started = position("fetchStarted");
+    let completed = position("fetchCompleted");
 
-    // Now Eve disconnects from Bob so she doesn't fetch his update.
-    eve.handle
-        .command(service::Command::Disconnect(bob.id))
-        .unwrap();
+    assert!(connected < started);
+    assert!(started < completed);
+    assert!(entries.windows(2).all(|w| w[0].time <= w[1].time));
 
-    // Bob updates his refs.
-    bob.issue(
-        rid,
-        "Updated Sigrefs",
-        "Updated sigrefs are harshing my vibes",
-    );
-    // Alice fetches from Bob.
-    alice.handle.fetch(rid, bob.id).unwrap();
+    let last = alice.handle.activity(None, Some(1)).unwrap();
+    assert_eq!(last.len(), 1);
+}
 
-    // Now Alice has the latest, and when she tries to fetch from Eve, it breaks because
-    // Eve has old refs.
-    assert_matches!(
-        alice.handle.fetch(rid, eve.id).unwrap(),
-        FetchResult::Success { .. }
+#[test]
+fn test_unclean_shutdown_recovery() {
+    use radicle::node::routing::Store as _;
+    use radicle::node::watermark::Store as _;
+    use radicle::node::{
+        routing, watermark, NODE_RUNNING_FILE, ROUTING_DB_FILE, WATERMARK_DB_FILE,
+    };
+
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config::test(Alias::new("alice"));
+    let mut alice = Node::init(tmp.path(), config.clone());
+    let acme = alice.project("acme", "");
+    let alice = alice.spawn();
+    let nid = alice.id;
+    let phantom = radicle::test::arbitrary::gen::<radicle::prelude::Id>(1);
+
+    assert_eq!(alice.handle.recovery().unwrap(), None, "first start");
+
+    // The node loses power: it doesn't get to remove its marker, and its routing table
+    // is out of date with storage.
+    let alice = alice.shutdown(config.clone());
+    let dir = alice.home.node();
+    let mut routing = routing::Table::open(dir.join(ROUTING_DB_FILE)).unwrap();
+    let mut watermarks = watermark::Table::open(dir.join(WATERMARK_DB_FILE)).unwrap();
+    let signature = radicle::crypto::Signature::from([1; 64]);
+
+    std::fs::write(dir.join(NODE_RUNNING_FILE), []).unwrap();
+    routing.remove(&acme, &nid).unwrap();
+    routing.insert([&phantom], nid, 0).unwrap();
+    watermarks.fetched(&acme, &nid, &signature).unwrap();
+    drop((routing, watermarks));
+
+    // Repositories are checked in the background.
+    let alice = alice.spawn();
+    let recovery = loop {
+        let recovery = alice.handle.recovery().unwrap().expect("recovery ran");
+        if recovery.is_complete() {
+            break recovery;
+        }
+        thread::sleep(time::Duration::from_millis(100));
+    };
+
+    assert_eq!(recovery.routes_added, 1);
+    assert_eq!(recovery.routes_removed, 1);
+    assert_eq!(recovery.checked, 1);
+    assert!(recovery.invalid.is_empty());
+    assert_eq!(alice.routing().collect::<Vec<_>>(), vec![(acme, nid)]);
+
+    // The repository is valid, so its sync watermarks are kept.
+    assert_eq!(recovery.watermarks_cleared, 0);
+    assert_eq!(
+        watermark::Table::reader(dir.join(WATERMARK_DB_FILE))
+            .unwrap()
+            .get(&acme, &nid)
+            .unwrap()
+            .and_then(|w| w.fetched),
+        Some(signature)
     );
+
+    // Once shut down cleanly, there's nothing to recover from.
+    let alice = alice.restart(config);
+    assert_eq!(alice.handle.recovery().unwrap(), None);
 }
 
 #[test]
-fn test_outdated_sigrefs() {
+fn test_add_peer_persisted() {
     logger::init(log::Level::Debug);
 
     let tmp = tempfile::tempdir().unwrap();
+    let config = Config::test(Alias::new("alice"));
+    let alice = Node::init(tmp.path(), config.clone());
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob"))).spawn();
 
-    let mut alice = Node::init(tmp.path(), Config::test(Alias::new("alice")));
-    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
-    let eve = Node::init(tmp.path(), Config::test(Alias::new("eve")));
+    radicle::profile::Config {
+        node: config,
+        cli: Default::default(),
+    }
+    .write(&alice.home.config())
+    .unwrap();
 
-    let rid = alice.project("acme", "");
+    let alice = alice.spawn();
+    let bob_events = bob.handle.events();
+    let peer = PeerEntry {
+        id: bob.id,
+        address: bob.addr.into(),
+        alias: Some(Alias::new("bob")),
+        persistent: true,
+    };
+    // Go through the control socket, as `rad node connect --persist` would.
+    let added = radicle::Node::new(alice.home.socket())
+        .add_peer(peer.clone())
+        .unwrap();
+    assert!(added);
 
+    bob_events
+        .iter()
+        .find(|e| matches!(e, Event::PeerConnected { nid } if nid == &alice.id))
+        .unwrap();
+
+    // The peer is in the configuration file, and is connected to once restarted with it.
+    let config = radicle::profile::Config::load(&alice.home.config())
+        .unwrap()
+        .node;
+    assert_eq!(config.peer_entries, vec![peer]);
+
+    let bob_events = bob.handle.events();
+    let alice = alice.restart(config);
+
+    bob_events
+        .iter()
+        .find(|e| matches!(e, Event::PeerConnected { nid } if nid == &alice.id))
+        .unwrap();
+    assert!(alice.handle.config().unwrap().is_persistent(&bob.id));
+}
+
+#[test]
+//
+//     alice -- bob
+//
+fn test_cob_update() {
+    logger::init(log::Level::Debug);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let mut alice = Node::init(
+        tmp.path(),
+        Config {
+            control_permission: Permission::Operator,
+            ..Config::test(Alias::new("alice"))
+        },
+    );
+    let bob = Node::init(tmp.path(), Config::test(Alias::new("bob")));
+    let acme = alice.project("acme", "");
     let mut alice = alice.spawn();
     let mut bob = bob.spawn();
-    let mut eve = eve.spawn();
 
-    bob.handle.track_repo(rid, Scope::All).unwrap();
-    eve.handle.track_repo(rid, Scope::All).unwrap();
     alice.connect(&bob);
-    bob.connect(&eve);
-    eve.connect(&alice);
-    converge([&alice, &bob, &eve]);
-
-    bob.handle.fetch(rid, alice.id).unwrap();
-    assert!(bob.storage.contains(&rid).unwrap());
-    rad::fork(rid, &bob.signer, &bob.storage).unwrap();
-
-    eve.handle.fetch(rid, alice.id).unwrap();
-    assert!(eve.storage.contains(&rid).unwrap());
-    rad::fork(rid, &eve.signer, &eve.storage).unwrap();
+    converge([&alice, &bob]);
 
-    alice
-        .handle
-        .track_node(eve.id, Some(Alias::new("eve")))
-        .unwrap();
-    alice.handle.fetch(rid, eve.id).unwrap();
-    let repo = alice.storage.repository(rid).unwrap();
-    assert!(repo.remote(&eve.id).is_ok());
+    bob.handle.track_repo(acme, Scope::All).unwrap();
+    assert!(bob.handle.fetch(acme, alice.id).unwrap().is_success());
+
+    let id = alice.issue(acme, "Bug", "Bugs, bugs, bugs");
+    let events = bob.handle.events();
+    let token = OperatorToken::read(&alice.home.control_operator()).unwrap();
+    let mut node = radicle::Node::new(alice.home.socket()).with_operator_token(token);
+    let update = CobUpdate {
+        rid: acme,
+        type_name: issue::TYPENAME.clone(),
+        object: CobObject::Existing(id),
+        message: String::from("Triage"),
+        actions: vec![serde_json::json!({ "type": "comment", "body": "Duplicate of #1" })],
+    };
 
+    // Clients without the operator token can't update objects.
+    let err = radicle::Node::new(alice.home.socket())
+        .cob_update(update.clone())
+        .unwrap_err();
     assert_matches!(
-        bob.handle.fetch(rid, eve.id).unwrap(),
-        FetchResult::Success { .. }
+        err,
+        radicle::node::Error::Node {
+            code: ErrorCode::Unauthorized,
+            ..
+        }
     );
-    let repo = bob.storage.repository(rid).unwrap();
-    let eve_remote = repo.remote(&eve.id).unwrap();
-    let old_refs = eve_remote.refs;
-
-    // At this stage, Alice and Bob have Eve's fork and Eve does not
-    // have Bob's fork
 
-    eve.issue(
-        rid,
-        "Outdated Sigrefs",
-        "Outdated sigrefs are harshing my vibes",
+    // Objects of repositories we don't have aren't found.
+    let err = node
+        .cob_update(CobUpdate {
+            rid: radicle::test::arbitrary::gen::<radicle::prelude::Id>(1),
+            ..update.clone()
+        })
+        .unwrap_err();
+    assert_matches!(
+        err,
+        radicle::node::Error::Node {
+            code: ErrorCode::NotFound,
+            ..
+        }
     );
-    let repo = eve.storage.repository(rid).unwrap();
-    let eves_refs = repo.remote(&eve.id).unwrap().refs;
 
-    // Get the current state of eve's refs in alice's storage
-    log::debug!(target: "test", "Alice fetches from Eve..");
+    // Actions that don't match the object type's schema are rejected.
+    let err = node
+        .cob_update(CobUpdate {
+            actions: vec![serde_json::json!({ "type": "comment", "text": "Duplicate" })],
+            ..update.clone()
+        })
+        .unwrap_err();
     assert_matches!(
-        alice.handle.fetch(rid, eve.id).unwrap(),
-        FetchResult::Success { .. }
+        err,
+        radicle::node::Error::Node {
+            code: ErrorCode::InvalidArgument,
+            ..
+        }
     );
-    let repo = alice.storage.repository(rid).unwrap();
-    let eve_remote = repo.remote(&eve.id).unwrap();
-    let eves_refs_expected = eve_remote.refs;
-    assert_ne!(eves_refs_expected, old_refs);
-    assert_eq!(eves_refs_expected, eves_refs);
 
-    log::debug!(target: "test", "Alice fetches from Bob..");
+    // Go through the control socket, as a bot would.
+    let updated = node.cob_update(update).unwrap();
+    assert_eq!(updated.object, id);
 
-    alice
-        .handle
-        .track_node(bob.id, Some(Alias::new("bob")))
+    let repo = alice.storage.repository(acme).unwrap();
+    let issues = issue::Issues::open(&repo).unwrap();
+    let issue = issues.get(&id).unwrap().unwrap();
+    let (_, comment) = issue
+        .comments()
+        .find(|(entry, _)| **entry == updated.entry)
         .unwrap();
-    assert_matches!(
-        alice.handle.fetch(rid, bob.id).unwrap(),
-        FetchResult::Success { .. }
+    assert_eq!(comment.body(), "Duplicate of #1");
+    assert_eq!(comment.author(), alice.id);
+    assert_eq!(
+        issue.comments().count(),
+        2,
+        "the invalid comment wasn't added"
     );
 
-    // Ensure that Eve's refs have not changed after fetching the old refs from Bob.
-    let repo = alice.storage.repository(rid).unwrap();
-    let eve_remote = repo.remote(&eve.id).unwrap();
-    let eves_refs = eve_remote.refs;
+    // Our refs are announced, and fetched by peers.
+    events
+        .wait(
+            |e| {
+                matches!(e, Event::RefsFetched { rid, remote, .. } if *rid == acme && *remote == alice.id)
+                    .then_some(())
+            },
+            time::Duration::from_secs(6),
+        )
+        .unwrap();
 
-    assert_ne!(eves_refs, old_refs);
-    assert_eq!(eves_refs_expected, eves_refs);
+    let repo = bob.storage.repository(acme).unwrap();
+    let issues = issue::Issues::open(&repo).unwrap();
+    let issue = issues.get(&id).unwrap().unwrap();
+    assert!(issue.comments().any(|(entry, _)| *entry == updated.entry));
 }
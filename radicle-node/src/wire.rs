@@ -3,7 +3,7 @@ mod message;
 mod protocol;
 mod varint;
 
-pub use frame::StreamId;
+pub use frame::{StreamId, Version, PROTOCOL_VERSION};
 pub use message::{AddressType, MessageType};
 pub use protocol::{Control, Wire, WireReader, WireSession, WireWriter};
 
@@ -15,6 +15,7 @@ use std::string::FromUtf8Error;
 use std::{io, mem};
 
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use radicle::cob::TypeName;
 
 use crate::crypto::{PublicKey, Signature, Unverified};
 use crate::git;
@@ -51,14 +52,24 @@ pub enum Error {
     InvalidRefName(#[from] fmt::Error),
     #[error(transparent)]
     InvalidAlias(#[from] node::AliasError),
+    #[error("invalid collaborative object type name `{0}`")]
+    InvalidTypeName(String),
     #[error("invalid control message with type `{0}`")]
     InvalidControlMessage(u8),
     #[error("invalid protocol version header `{0:x?}`")]
     InvalidProtocolVersion([u8; 4]),
     #[error("unknown address type `{0}`")]
     UnknownAddressType(u8),
+    #[error("unsupported address type `{0}`")]
+    UnsupportedAddressType(u8),
+    #[error("message exceeds the maximum size of {0} bytes")]
+    MessageTooLarge(usize),
+    #[error("unexpected end of message")]
+    UnexpectedEnd,
     #[error("unknown message type `{0}`")]
     UnknownMessageType(u16),
+    #[error("invalid announcement response with message type `{0}`")]
+    InvalidAnnouncementResponse(u16),
     #[error("unexpected bytes")]
     UnexpectedBytes,
 }
@@ -71,6 +82,47 @@ impl Error {
     }
 }
 
+/// A reader that fails once more than a given number of bytes were read from it.
+///
+/// Used to stop decoding messages that go over the maximum message size, instead of
+/// waiting for, and buffering, data that a well-behaved peer would never send.
+struct Limited<'a, R: ?Sized> {
+    inner: &'a mut R,
+    remaining: usize,
+    exceeded: bool,
+}
+
+impl<'a, R: io::Read + ?Sized> Limited<'a, R> {
+    fn new(inner: &'a mut R, limit: usize) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            exceeded: false,
+        }
+    }
+}
+
+impl<'a, R: io::Read + ?Sized> io::Read for Limited<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            self.exceeded = true;
+
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "read limit exceeded",
+            ));
+        }
+        let len = buf.len().min(self.remaining);
+        let n = self.inner.read(&mut buf[..len])?;
+        self.remaining -= n;
+
+        Ok(n)
+    }
+}
+
 /// Things that can be encoded as binary.
 pub trait Encode {
     fn encode<W: io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, io::Error>;
@@ -235,6 +287,12 @@ impl Encode for Alias {
     }
 }
 
+impl Encode for TypeName {
+    fn encode<W: io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        self.as_str().encode(writer)
+    }
+}
+
 impl<A, B> Encode for (A, B)
 where
     A: Encode,
@@ -306,6 +364,13 @@ impl Decode for Alias {
     }
 }
 
+impl Decode for TypeName {
+    fn decode<R: io::Read + ?Sized>(reader: &mut R) -> Result<Self, Error> {
+        let name = String::decode(reader)?;
+        TypeName::from_str(&name).map_err(|_| Error::InvalidTypeName(name))
+    }
+}
+
 impl<A, B> Decode for (A, B)
 where
     A: Decode,
@@ -16,14 +16,21 @@ const CONTROL_CLOSE: u8 = 1;
 const CONTROL_EOF: u8 = 2;
 
 /// Protocol version.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Version([u8; 4]);
 
+impl Version {
+    /// The version number, following the magic sequence.
+    pub const fn number(&self) -> u8 {
+        self.0[3]
+    }
+}
+
 impl wire::Encode for Version {
     fn encode<W: io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, io::Error> {
-        writer.write_all(&PROTOCOL_VERSION.0)?;
+        writer.write_all(&self.0)?;
 
-        Ok(PROTOCOL_VERSION.0.len())
+        Ok(self.0.len())
     }
 }
 
@@ -32,7 +39,9 @@ impl wire::Decode for Version {
         let mut version = [0u8; 4];
         reader.read_exact(&mut version[..])?;
 
-        if version != PROTOCOL_VERSION.0 {
+        // Nb. Frames of newer protocol versions are accepted, as long as they are framed
+        // the same way. It's up to the session to decide what to do with their contents.
+        if version[..3] != PROTOCOL_VERSION.0[..3] || version[3] == 0 {
             return Err(wire::Error::InvalidProtocolVersion(version));
         }
         Ok(Self(version))
@@ -234,6 +243,9 @@ pub enum FrameData {
     Control(Control),
     /// Gossip frame payload.
     Gossip(Message),
+    /// Gossip frame payload with a message type we don't know, eg. because it was
+    /// introduced in a newer protocol version. The rest of the payload is dropped.
+    Unknown(u16),
     /// Git frame payload. May contain packet-lines as well as packfile data.
     Git(Vec<u8>),
 }
@@ -321,11 +333,19 @@ impl wire::Decode for Frame {
             Ok(StreamKind::Gossip) => {
                 let data = varint::payload::decode(reader)?;
                 let mut cursor = io::Cursor::new(data);
-                let msg = Message::decode(&mut cursor)?;
+                let data = match Message::decode(&mut cursor) {
+                    Ok(msg) => FrameData::Gossip(msg),
+                    // Nb. The payload was read in full, so the frame itself is valid.
+                    Err(wire::Error::UnknownMessageType(t)) => FrameData::Unknown(t),
+                    // Nb. The payload was read in full, so running out of data means the
+                    // message is truncated, and not that we should wait for more data.
+                    Err(e) if e.is_eof() => return Err(wire::Error::UnexpectedEnd),
+                    Err(e) => return Err(e),
+                };
                 let frame = Frame {
                     version,
                     stream,
-                    data: FrameData::Gossip(msg),
+                    data,
                 };
 
                 // Nb. If there is data after the `Message` that is not decoded,
@@ -352,6 +372,7 @@ impl wire::Encode for Frame {
             FrameData::Control(ctrl) => ctrl.encode(writer)?,
             FrameData::Git(data) => varint::payload::encode(data, writer)?,
             FrameData::Gossip(msg) => varint::payload::encode(&wire::serialize(msg), writer)?,
+            FrameData::Unknown(t) => varint::payload::encode(&wire::serialize(t), writer)?,
         };
 
         Ok(n)
@@ -380,4 +401,36 @@ mod test {
         assert_eq!(StreamId::control(Link::Inbound), StreamId(VarInt(0b001)));
         assert_eq!(StreamId::gossip(Link::Inbound), StreamId(VarInt(0b011)));
     }
+
+    #[test]
+    fn test_frame_version() {
+        use wire::{Decode as _, Encode as _};
+
+        let frame = |version: [u8; 4], payload: &[u8]| {
+            let mut bytes = version.to_vec();
+            StreamId::gossip(Link::Outbound).encode(&mut bytes).unwrap();
+            varint::payload::encode(payload, &mut bytes).unwrap();
+            Frame::decode(&mut io::Cursor::new(bytes))
+        };
+        let mut unknown = wire::serialize(&0xfffeu16);
+        unknown.extend([1, 2, 3]);
+
+        // Messages of unknown types are skipped over, in frames of any version.
+        let decoded = frame(*b"rad\x02", &unknown).unwrap();
+        assert_eq!(decoded.version.number(), 2);
+        assert_eq!(decoded.data, FrameData::Unknown(0xfffe));
+
+        let decoded = frame(*b"rad\x01", &unknown).unwrap();
+        assert_eq!(decoded.version, PROTOCOL_VERSION);
+        assert_eq!(decoded.data, FrameData::Unknown(0xfffe));
+
+        assert!(matches!(
+            frame(*b"rad\x00", &unknown),
+            Err(wire::Error::InvalidProtocolVersion(_))
+        ));
+        assert!(matches!(
+            frame(*b"git\x01", &unknown),
+            Err(wire::Error::InvalidProtocolVersion(_))
+        ));
+    }
 }
@@ -19,6 +19,9 @@ pub enum MessageType {
     Subscribe = 8,
     Ping = 10,
     Pong = 12,
+    CobsAnnouncement = 14,
+    AnnouncementRequest = 16,
+    AnnouncementResponse = 18,
 }
 
 impl From<MessageType> for u16 {
@@ -27,6 +30,17 @@ impl From<MessageType> for u16 {
     }
 }
 
+impl From<&AnnouncementMessage> for MessageType {
+    fn from(message: &AnnouncementMessage) -> Self {
+        match message {
+            AnnouncementMessage::Node(_) => MessageType::NodeAnnouncement,
+            AnnouncementMessage::Inventory(_) => MessageType::InventoryAnnouncement,
+            AnnouncementMessage::Refs(_) => MessageType::RefsAnnouncement,
+            AnnouncementMessage::Cobs(_) => MessageType::CobsAnnouncement,
+        }
+    }
+}
+
 impl TryFrom<u16> for MessageType {
     type Error = u16;
 
@@ -38,6 +52,9 @@ impl TryFrom<u16> for MessageType {
             8 => Ok(MessageType::Subscribe),
             10 => Ok(MessageType::Ping),
             12 => Ok(MessageType::Pong),
+            14 => Ok(MessageType::CobsAnnouncement),
+            16 => Ok(MessageType::AnnouncementRequest),
+            18 => Ok(MessageType::AnnouncementResponse),
             _ => Err(other),
         }
     }
@@ -51,11 +68,9 @@ impl Message {
     pub fn type_id(&self) -> u16 {
         match self {
             Self::Subscribe { .. } => MessageType::Subscribe,
-            Self::Announcement(Announcement { message, .. }) => match message {
-                AnnouncementMessage::Node(_) => MessageType::NodeAnnouncement,
-                AnnouncementMessage::Inventory(_) => MessageType::InventoryAnnouncement,
-                AnnouncementMessage::Refs(_) => MessageType::RefsAnnouncement,
-            },
+            Self::Announcement(Announcement { message, .. }) => MessageType::from(message),
+            Self::AnnouncementRequest { .. } => MessageType::AnnouncementRequest,
+            Self::AnnouncementResponse { .. } => MessageType::AnnouncementResponse,
             Self::Ping { .. } => MessageType::Ping,
             Self::Pong { .. } => MessageType::Pong,
         }
@@ -69,7 +84,7 @@ impl netservices::Frame for Message {
     fn unmarshall(mut reader: impl io::Read) -> Result<Option<Self>, Self::Error> {
         match Message::decode(&mut reader) {
             Ok(msg) => Ok(Some(msg)),
-            Err(wire::Error::Io(_)) => Ok(None),
+            Err(err) if err.is_eof() => Ok(None),
             Err(err) => Err(err),
         }
     }
@@ -127,6 +142,7 @@ impl wire::Encode for AnnouncementMessage {
             Self::Node(ann) => ann.encode(writer),
             Self::Inventory(ann) => ann.encode(writer),
             Self::Refs(ann) => ann.encode(writer),
+            Self::Cobs(ann) => ann.encode(writer),
         }
     }
 }
@@ -180,6 +196,26 @@ impl wire::Decode for InventoryAnnouncement {
     }
 }
 
+impl wire::Encode for CobsAnnouncement {
+    fn encode<W: io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        let mut n = 0;
+
+        n += self.cobs.encode(writer)?;
+        n += self.timestamp.encode(writer)?;
+
+        Ok(n)
+    }
+}
+
+impl wire::Decode for CobsAnnouncement {
+    fn decode<R: std::io::Read + ?Sized>(reader: &mut R) -> Result<Self, wire::Error> {
+        let cobs = BoundedVec::decode(reader)?;
+        let timestamp = Timestamp::decode(reader)?;
+
+        Ok(Self { cobs, timestamp })
+    }
+}
+
 impl wire::Encode for Message {
     fn encode<W: std::io::Write + ?Sized>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
         let mut n = self.type_id().encode(writer)?;
@@ -203,6 +239,21 @@ impl wire::Encode for Message {
                 n += message.encode(writer)?;
                 n += signature.encode(writer)?;
             }
+            Self::AnnouncementRequest(AnnouncementRequest { rid, kinds }) => {
+                n += rid.encode(writer)?;
+                n += u8::from(*kinds).encode(writer)?;
+            }
+            Self::AnnouncementResponse(Announcement {
+                node,
+                message,
+                signature,
+            }) => {
+                // The announcement is encoded as it would be on its own, type included.
+                n += u16::from(MessageType::from(message)).encode(writer)?;
+                n += node.encode(writer)?;
+                n += message.encode(writer)?;
+                n += signature.encode(writer)?;
+            }
             Self::Ping(Ping { ponglen, zeroes }) => {
                 n += ponglen.encode(writer)?;
                 n += zeroes.encode(writer)?;
@@ -224,6 +275,19 @@ impl wire::Encode for Message {
 
 impl wire::Decode for Message {
     fn decode<R: std::io::Read + ?Sized>(reader: &mut R) -> Result<Self, wire::Error> {
+        let max = wire::Size::MAX as usize;
+        let mut reader = wire::Limited::new(reader, max);
+
+        match Self::decode_unlimited(&mut reader) {
+            Err(_) if reader.exceeded => Err(wire::Error::MessageTooLarge(max)),
+            result => result,
+        }
+    }
+}
+
+impl Message {
+    /// Decode a message, without checking that it is within the maximum message size.
+    fn decode_unlimited<R: std::io::Read + ?Sized>(reader: &mut R) -> Result<Self, wire::Error> {
         let type_id = reader.read_u16::<NetworkEndian>()?;
 
         match MessageType::try_from(type_id) {
@@ -274,13 +338,45 @@ impl wire::Decode for Message {
                 }
                 .into())
             }
+            Ok(MessageType::CobsAnnouncement) => {
+                let node = NodeId::decode(reader)?;
+                let message = CobsAnnouncement::decode(reader)?.into();
+                let signature = Signature::decode(reader)?;
+
+                Ok(Announcement {
+                    node,
+                    message,
+                    signature,
+                }
+                .into())
+            }
+            Ok(MessageType::AnnouncementRequest) => {
+                let rid = Id::decode(reader)?;
+                // Unknown kinds are kept, and ignored when answering the request.
+                let kinds = AnnouncementKinds::from(u8::decode(reader)?);
+
+                Ok(Self::AnnouncementRequest(AnnouncementRequest {
+                    rid,
+                    kinds,
+                }))
+            }
+            Ok(MessageType::AnnouncementResponse) => match Self::decode_unlimited(reader)? {
+                Self::Announcement(ann) => Ok(Self::AnnouncementResponse(ann)),
+                other => Err(wire::Error::InvalidAnnouncementResponse(other.type_id())),
+            },
             Ok(MessageType::Ping) => {
                 let ponglen = u16::decode(reader)?;
-                let zeroes = ZeroBytes::decode(reader)?;
+                if ponglen > Ping::MAX_PONG_ZEROES {
+                    return Err(wire::Error::InvalidSize {
+                        expected: Ping::MAX_PONG_ZEROES as usize,
+                        actual: ponglen as usize,
+                    });
+                }
+                let zeroes = ZeroBytes::decode_max(reader, Ping::MAX_PING_ZEROES)?;
                 Ok(Self::Ping(Ping { ponglen, zeroes }))
             }
             Ok(MessageType::Pong) => {
-                let zeroes = ZeroBytes::decode(reader)?;
+                let zeroes = ZeroBytes::decode_max(reader, Ping::MAX_PONG_ZEROES)?;
                 Ok(Self::Pong { zeroes })
             }
             Err(other) => Err(wire::Error::UnknownMessageType(other)),
@@ -337,7 +433,7 @@ impl wire::Decode for Address {
                 HostName::Dns(dns)
             }
             Ok(AddressType::Onion) => {
-                todo!();
+                return Err(wire::Error::UnsupportedAddressType(addrtype));
             }
             Err(other) => return Err(wire::Error::UnknownAddressType(other)),
         };
@@ -359,7 +455,23 @@ impl wire::Encode for ZeroBytes {
 
 impl wire::Decode for ZeroBytes {
     fn decode<R: std::io::Read + ?Sized>(reader: &mut R) -> Result<Self, wire::Error> {
+        Self::decode_max(reader, wire::Size::MAX)
+    }
+}
+
+impl ZeroBytes {
+    /// Decode zero bytes, failing if there are more than `max` of them.
+    fn decode_max<R: std::io::Read + ?Sized>(
+        reader: &mut R,
+        max: wire::Size,
+    ) -> Result<Self, wire::Error> {
         let zeroes = u16::decode(reader)?;
+        if zeroes > max {
+            return Err(wire::Error::InvalidSize {
+                expected: max as usize,
+                actual: zeroes as usize,
+            });
+        }
         for _ in 0..zeroes {
             _ = u8::decode(reader)?;
         }
@@ -372,8 +484,19 @@ mod tests {
     use super::*;
     use qcheck_macros::quickcheck;
 
+    use std::fs;
+    use std::path::Path;
+
+    use radicle::cob::TypeName;
+
+    use crate::crypto::test::signer::MockSigner;
     use crate::deserializer::Deserializer;
+    use crate::test::arbitrary;
+    use crate::test::assert_matches;
+    use crate::wire::frame::{Frame, FrameData, StreamId, PROTOCOL_VERSION};
+    use crate::wire::varint::{self, VarInt};
     use crate::wire::{self, Encode};
+    use crate::Link;
 
     #[test]
     fn test_pingpong_encode_max_size() {
@@ -413,6 +536,24 @@ mod tests {
             .expect_err("pong should exceed max message size");
     }
 
+    #[test]
+    fn test_announcement_response_decode_invalid() {
+        let mut buf = Vec::new();
+        u16::from(MessageType::AnnouncementResponse)
+            .encode(&mut buf)
+            .unwrap();
+        Message::Pong {
+            zeroes: ZeroBytes::new(0),
+        }
+        .encode(&mut buf)
+        .unwrap();
+
+        assert_matches!(
+            wire::deserialize::<Message>(&buf),
+            Err(wire::Error::InvalidAnnouncementResponse(12))
+        );
+    }
+
     #[quickcheck]
     fn prop_message_encode_decode(message: Message) {
         assert_eq!(
@@ -447,6 +588,170 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pingpong_decode_limits() {
+        let ping = |ponglen: u16, zeroes: u16| {
+            let mut buf = Vec::new();
+            u16::from(MessageType::Ping).encode(&mut buf).unwrap();
+            ponglen.encode(&mut buf).unwrap();
+            ZeroBytes::new(zeroes).encode(&mut buf).unwrap();
+            buf
+        };
+        assert!(wire::deserialize::<Message>(&ping(Ping::MAX_PONG_ZEROES, 0)).is_ok());
+        assert!(wire::deserialize::<Message>(&ping(0, Ping::MAX_PING_ZEROES)).is_ok());
+        assert_matches!(
+            wire::deserialize::<Message>(&ping(Ping::MAX_PONG_ZEROES + 1, 0)),
+            Err(wire::Error::InvalidSize { .. })
+        );
+        assert_matches!(
+            wire::deserialize::<Message>(&ping(0, Ping::MAX_PING_ZEROES + 1)),
+            Err(wire::Error::InvalidSize { .. })
+        );
+
+        let mut pong = Vec::new();
+        u16::from(MessageType::Pong).encode(&mut pong).unwrap();
+        ZeroBytes::new(Ping::MAX_PONG_ZEROES + 1)
+            .encode(&mut pong)
+            .unwrap();
+        assert_matches!(
+            wire::deserialize::<Message>(&pong),
+            Err(wire::Error::InvalidSize { .. })
+        );
+    }
+
+    #[test]
+    fn test_message_decode_max_size() {
+        let nid = arbitrary::gen::<NodeId>(1);
+        let rid = arbitrary::gen::<Id>(1);
+        let oid = arbitrary::oid();
+        let refs = 3000u16;
+
+        // A refs announcement with too many refs to fit in a message.
+        let mut buf = Vec::new();
+        u16::from(MessageType::RefsAnnouncement)
+            .encode(&mut buf)
+            .unwrap();
+        nid.encode(&mut buf).unwrap();
+        rid.encode(&mut buf).unwrap();
+        1u16.encode(&mut buf).unwrap();
+        nid.encode(&mut buf).unwrap();
+        refs.encode(&mut buf).unwrap();
+        for i in 0..refs {
+            format!("refs/heads/{i}").encode(&mut buf).unwrap();
+            oid.encode(&mut buf).unwrap();
+        }
+        assert!(buf.len() > wire::Size::MAX as usize);
+
+        assert_matches!(
+            wire::deserialize::<Message>(&buf),
+            Err(wire::Error::MessageTooLarge(_))
+        );
+        // The decoder doesn't wait for more data.
+        let mut decoder = Deserializer::<Message>::new(buf.len());
+        decoder.input(&buf);
+        assert_matches!(
+            decoder.deserialize_next(),
+            Err(wire::Error::MessageTooLarge(_))
+        );
+    }
+
+    #[test]
+    fn test_cobs_announcement_encode_decode() {
+        let signer = arbitrary::gen::<MockSigner>(1);
+        let cobs = ["xyz.radicle.issue", "xyz.radicle.patch"]
+            .into_iter()
+            .map(|n| n.parse().unwrap())
+            .collect::<Vec<TypeName>>();
+        let ann = CobsAnnouncement {
+            cobs: cobs.try_into().unwrap(),
+            timestamp: 42,
+        };
+        let msg = Message::cobs(ann.clone(), &signer);
+        let decoded = wire::deserialize::<Message>(&wire::serialize(&msg)).unwrap();
+
+        assert_eq!(decoded, msg);
+        assert_matches!(
+            decoded,
+            Message::Announcement(Announcement { message: AnnouncementMessage::Cobs(a), .. }) if a == ann
+        );
+
+        // Invalid type names are rejected.
+        let mut buf = Vec::new();
+        1u16.encode(&mut buf).unwrap();
+        "xyz..issue".encode(&mut buf).unwrap();
+        42u64.encode(&mut buf).unwrap();
+        assert_matches!(
+            wire::deserialize::<CobsAnnouncement>(&buf),
+            Err(wire::Error::InvalidTypeName(_))
+        );
+
+        // So are announcements with too many types.
+        let mut buf = Vec::new();
+        (COB_LIMIT as u16 + 1).encode(&mut buf).unwrap();
+        for i in 0..=COB_LIMIT {
+            format!("xyz.example.t{i}").encode(&mut buf).unwrap();
+        }
+        42u64.encode(&mut buf).unwrap();
+        assert!(wire::deserialize::<CobsAnnouncement>(&buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/wire/corpus");
+        let mut count = 0;
+
+        for entry in fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let bytes = fs::read(&path).unwrap();
+            let name = path.display();
+
+            let err = wire::deserialize::<Message>(&bytes).unwrap_err();
+
+            // When received in a gossip frame, the message is complete, so it can't be
+            // mistaken for a message we're still receiving.
+            let mut frame = Vec::new();
+            PROTOCOL_VERSION.encode(&mut frame).unwrap();
+            StreamId::gossip(Link::Inbound).encode(&mut frame).unwrap();
+            varint::payload::encode(&bytes, &mut frame).unwrap();
+
+            let mut decoder = Deserializer::<Frame>::new(frame.len());
+            decoder.input(&frame);
+            let result = decoder.deserialize_next();
+
+            if let wire::Error::UnknownMessageType(t) = err {
+                // Messages of unknown types are well-framed, and left to the session to handle.
+                assert_matches!(
+                    result,
+                    Ok(Some(Frame { data: FrameData::Unknown(u), .. })) if u == t,
+                    "{name} should decode as an unknown message in a frame"
+                );
+            } else {
+                assert!(result.is_err(), "{name} should fail to decode in a frame");
+            }
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_payload_max_size() {
+        let mut frame = Vec::new();
+        PROTOCOL_VERSION.encode(&mut frame).unwrap();
+        StreamId::git(Link::Inbound).encode(&mut frame).unwrap();
+        VarInt::new(varint::payload::MAX_SIZE as u64 + 1)
+            .unwrap()
+            .encode(&mut frame)
+            .unwrap();
+
+        // The payload isn't allocated, nor waited for.
+        let mut decoder = Deserializer::<Frame>::new(frame.len());
+        decoder.input(&frame);
+        assert_matches!(
+            decoder.deserialize_next(),
+            Err(wire::Error::InvalidSize { .. })
+        );
+    }
+
     #[quickcheck]
     fn prop_addr(addr: Address) {
         assert_eq!(
@@ -326,6 +326,9 @@ pub struct Wire<R, S, W, G: Signer + Ecdh> {
     peers: Peers,
     /// SOCKS5 proxy address.
     proxy: net::SocketAddr,
+    /// Nodes we don't establish sessions with, to simulate network partitions.
+    #[cfg(any(test, feature = "test"))]
+    blocklist: crate::test::environment::Blocklist,
 }
 
 impl<R, S, W, G> Wire<R, S, W, G>
@@ -353,9 +356,31 @@ where
             proxy,
             actions: VecDeque::new(),
             peers: Peers(RandomMap::default()),
+            #[cfg(any(test, feature = "test"))]
+            blocklist: Default::default(),
         }
     }
 
+    /// Get the nodes we don't establish sessions with. Changes to the returned list
+    /// apply to this instance.
+    #[cfg(any(test, feature = "test"))]
+    pub fn blocklist(&self) -> crate::test::environment::Blocklist {
+        self.blocklist.clone()
+    }
+
+    /// Check whether sessions with the given node are refused.
+    #[cfg(any(test, feature = "test"))]
+    fn is_blocked(&self, nid: &NodeId) -> bool {
+        self.blocklist.contains(nid)
+    }
+
+    /// Check whether sessions with the given node are refused. Never the case outside
+    /// of tests.
+    #[cfg(not(any(test, feature = "test")))]
+    fn is_blocked(&self, _nid: &NodeId) -> bool {
+        false
+    }
+
     pub fn listen(&mut self, socket: NetAccept<WireSession<G>>) {
         self.actions.push_back(Action::RegisterListener(socket));
     }
@@ -394,11 +419,22 @@ where
             log::warn!(target: "wire", "Peer {nid} is not connected; ignoring fetch result");
             return;
         };
+        self.service.transfer_finished(nid);
 
         // Only call into the service if we initiated this fetch.
         match task.result {
-            FetchResult::Initiator { rid, result } => {
-                self.service.fetched(rid, *nid, result);
+            FetchResult::Initiator {
+                rid,
+                result,
+                received,
+            } => {
+                self.service.fetched(rid, *nid, result, received);
+            }
+            FetchResult::LsRefs { rid, result } => {
+                self.service.listed(rid, *nid, result);
+            }
+            FetchResult::Identity { rid, result } => {
+                self.service.inspected(rid, *nid, result);
             }
             FetchResult::Responder { .. } => {
                 // We don't do anything with upload results for now.
@@ -418,6 +454,46 @@ where
         }
     }
 
+    /// Open a stream to the task's remote, and send the task to the worker pool.
+    fn task(&mut self, fetch: FetchRequest) {
+        let remote = fetch.remote();
+        let Some((fd, Peer::Connected { link, streams,  .. })) =
+            self.peers.lookup_mut(&remote) else {
+                // Nb. It's possible that a peer is disconnected while an `Io::Fetch`
+                // is in the service's i/o buffer. Since the service may not purge the
+                // buffer on disconnect, we should just ignore i/o actions that don't
+                // have a connected peer.
+                log::error!(target: "wire", "Peer {remote} is not connected: dropping fetch");
+                return;
+            };
+        let (stream, channels) = streams.open();
+
+        log::debug!(target: "wire", "Opened new stream with id={stream} for remote={remote}: {fetch:?}");
+
+        let link = *link;
+        let task = Task {
+            fetch,
+            stream,
+            channels,
+        };
+
+        if !self.worker.is_empty() {
+            log::warn!(
+                target: "wire",
+                "Worker pool is busy: {} tasks pending, fetch requests may be delayed", self.worker.len()
+            );
+        }
+        if self.worker.send(task).is_err() {
+            log::error!(target: "wire", "Worker pool is disconnected; cannot send fetch request");
+        } else {
+            self.service.transfer_started(&remote);
+        }
+        self.actions.push_back(Action::Send(
+            fd,
+            Frame::control(link, frame::Control::Open { stream }).to_bytes(),
+        ));
+    }
+
     fn flush(&mut self, remote: NodeId, stream: StreamId) {
         let Some((fd, peer)) = self.peers.lookup(&remote) else {
             log::warn!(target: "wire", "Peer {remote} is not known; ignoring flush");
@@ -536,6 +612,16 @@ where
                     );
                 }
 
+                if self.is_blocked(&id) {
+                    log::debug!(target: "wire", "Closing session with blocked peer {id} (fd={fd})");
+                    self.disconnect(
+                        fd,
+                        DisconnectReason::Dial(Arc::new(io::Error::from(
+                            io::ErrorKind::ConnectionRefused,
+                        ))),
+                    );
+                    return;
+                }
                 let Some(peer) = self.peers.get_mut(&fd) else {
                     log::error!(target: "wire", "Session not found for fd {fd}");
                     return;
@@ -555,6 +641,8 @@ where
                     inbox.input(&data);
 
                     loop {
+                        let unparsed = inbox.len();
+
                         match inbox.deserialize_next() {
                             Ok(Some(Frame {
                                 data: FrameData::Control(frame::Control::Open { stream }),
@@ -574,6 +662,8 @@ where
                                 };
                                 if self.worker.send(task).is_err() {
                                     log::error!(target: "wire", "Worker pool is disconnected; cannot send task");
+                                } else {
+                                    self.service.transfer_started(nid);
                                 }
                             }
                             Ok(Some(Frame {
@@ -601,11 +691,26 @@ where
                                 }
                             }
                             Ok(Some(Frame {
+                                version,
                                 data: FrameData::Gossip(msg),
                                 ..
                             })) => {
+                                self.service.received_version(nid, version.number());
+                                self.service.bytes_received(
+                                    nid,
+                                    msg.kind(),
+                                    unparsed - inbox.len(),
+                                );
                                 self.service.received_message(*nid, msg);
                             }
+                            Ok(Some(Frame {
+                                version,
+                                data: FrameData::Unknown(kind),
+                                ..
+                            })) => {
+                                self.service.received_version(nid, version.number());
+                                self.service.received_unknown(*nid, kind);
+                            }
                             Ok(Some(Frame {
                                 stream,
                                 data: FrameData::Git(data),
@@ -768,9 +873,13 @@ where
 
                     let mut data = Vec::new();
                     for msg in msgs {
+                        let kind = msg.kind();
+                        let len = data.len();
+
                         Frame::gossip(link, msg)
                             .encode(&mut data)
                             .expect("in-memory writes never fail");
+                        self.service.bytes_sent(&node_id, kind, data.len() - len);
                     }
                     self.actions.push_back(reactor::Action::Send(fd, data));
                 }
@@ -782,6 +891,17 @@ where
                         );
                         break;
                     }
+                    if self.is_blocked(&node_id) {
+                        log::debug!(target: "wire", "Refusing to connect to blocked peer {node_id}");
+
+                        self.service.disconnected(
+                            node_id,
+                            &DisconnectReason::Dial(Arc::new(io::Error::from(
+                                io::ErrorKind::ConnectionRefused,
+                            ))),
+                        );
+                        continue;
+                    }
 
                     match dial::<G>(
                         addr.to_inner(),
@@ -828,46 +948,36 @@ where
                     rid,
                     remote,
                     namespaces,
+                    limit,
+                    filter,
                 } => {
                     log::trace!(target: "wire", "Processing fetch for {rid} from {remote}..");
 
-                    let Some((fd, Peer::Connected { link, streams,  .. })) =
-                        self.peers.lookup_mut(&remote) else {
-                            // Nb. It's possible that a peer is disconnected while an `Io::Fetch`
-                            // is in the service's i/o buffer. Since the service may not purge the
-                            // buffer on disconnect, we should just ignore i/o actions that don't
-                            // have a connected peer.
-                            log::error!(target: "wire", "Peer {remote} is not connected: dropping fetch");
-                            continue;
-                        };
-                    let (stream, channels) = streams.open();
-
-                    log::debug!(target: "wire", "Opened new stream with id={stream} for rid={rid} remote={remote}");
-
-                    let link = *link;
-                    let task = Task {
-                        fetch: FetchRequest::Initiator {
-                            rid,
-                            namespaces,
-                            remote,
-                        },
-                        stream,
-                        channels,
-                    };
+                    self.task(FetchRequest::Initiator {
+                        rid,
+                        namespaces,
+                        remote,
+                        limit,
+                        filter,
+                    });
+                }
+                Io::LsRefs {
+                    rid,
+                    remote,
+                    namespaces,
+                } => {
+                    log::trace!(target: "wire", "Processing ls-refs for {rid} from {remote}..");
 
-                    if !self.worker.is_empty() {
-                        log::warn!(
-                            target: "wire",
-                            "Worker pool is busy: {} tasks pending, fetch requests may be delayed", self.worker.len()
-                        );
-                    }
-                    if self.worker.send(task).is_err() {
-                        log::error!(target: "wire", "Worker pool is disconnected; cannot send fetch request");
-                    }
-                    self.actions.push_back(Action::Send(
-                        fd,
-                        Frame::control(link, frame::Control::Open { stream }).to_bytes(),
-                    ));
+                    self.task(FetchRequest::LsRefs {
+                        rid,
+                        namespaces,
+                        remote,
+                    });
+                }
+                Io::FetchIdentity { rid, remote } => {
+                    log::trace!(target: "wire", "Processing identity fetch for {rid} from {remote}..");
+
+                    self.task(FetchRequest::Identity { rid, remote });
                 }
             }
         }
@@ -150,6 +150,10 @@ impl Encode for VarInt {
 pub mod payload {
     use super::*;
 
+    /// Maximum size of a data payload. Gossip messages are limited to 64KiB, and
+    /// Git data is sent in chunks of at most 64KiB, so this leaves plenty of room.
+    pub const MAX_SIZE: usize = 1024 * 1024;
+
     /// Encode varint-prefixed data payload.
     pub fn encode<W: io::Write + ?Sized>(payload: &[u8], writer: &mut W) -> io::Result<usize> {
         let mut n = 0;
@@ -168,6 +172,12 @@ pub mod payload {
     /// Decode varint-prefixed data payload.
     pub fn decode<R: io::Read + ?Sized>(reader: &mut R) -> Result<Vec<u8>, wire::Error> {
         let size = VarInt::decode(reader)?;
+        if *size > MAX_SIZE as u64 {
+            return Err(wire::Error::InvalidSize {
+                expected: MAX_SIZE,
+                actual: *size as usize,
+            });
+        }
         let mut data = vec![0; *size as usize];
         reader.read_exact(&mut data[..])?;
 
@@ -2,16 +2,20 @@ mod channels;
 mod fetch;
 mod tunnel;
 
-use std::collections::{BTreeSet, HashSet};
+use std::collections::BTreeSet;
 use std::io::{prelude::*, BufReader};
 use std::ops::ControlFlow;
+use std::path::Path;
 use std::{env, io, net, process, time};
 
 use crossbeam_channel as chan;
 
-use radicle::identity::Id;
+use radicle::identity::{Id, Identity, IdentityError};
+use radicle::node::config::ShapeLimits;
+use radicle::node::tracking::RefFilter;
+use radicle::node::FetchFailure;
 use radicle::prelude::NodeId;
-use radicle::storage::{Namespaces, ReadRepository, RefUpdate};
+use radicle::storage::{Namespaces, ReadRepository};
 use radicle::{git, storage, Storage};
 
 use crate::runtime::{thread, Handle};
@@ -20,6 +24,7 @@ use channels::{ChannelReader, ChannelWriter};
 use tunnel::Tunnel;
 
 pub use channels::{ChannelEvent, Channels};
+pub use fetch::Transferred;
 
 /// Worker pool configuration.
 pub struct Config {
@@ -33,6 +38,8 @@ pub struct Config {
     pub daemon: net::SocketAddr,
     /// Git storage.
     pub storage: Storage,
+    /// Limits on the shape of fetched namespaces.
+    pub shape: ShapeLimits,
 }
 
 /// Error returned by fetch.
@@ -40,6 +47,8 @@ pub struct Config {
 pub enum FetchError {
     #[error("the 'git fetch' command failed with exit code '{code}'")]
     CommandFailed { code: i32 },
+    #[error("the remote doesn't have the repository")]
+    NotFound,
     #[error(transparent)]
     Io(#[from] io::Error),
     #[error(transparent)]
@@ -48,6 +57,10 @@ pub enum FetchError {
     StagingTransition(#[from] fetch::error::Transition),
     #[error(transparent)]
     StagingTransfer(#[from] fetch::error::Transfer),
+    #[error("identity invalid: {0}")]
+    InvalidIdentity(#[from] IdentityError),
+    #[error("fetch aborted after exceeding the size limit of {limit} byte(s)")]
+    SizeLimitExceeded { limit: u64 },
 }
 
 impl FetchError {
@@ -55,6 +68,26 @@ impl FetchError {
     pub fn is_timeout(&self) -> bool {
         matches!(self, FetchError::Io(e) if e.kind() == io::ErrorKind::TimedOut)
     }
+
+    /// Get the kind of failure, for the fetch history.
+    pub fn failure(&self) -> FetchFailure {
+        match self {
+            Self::Io(e) if e.kind() == io::ErrorKind::TimedOut => FetchFailure::Timeout,
+            Self::Io(_) => FetchFailure::Connection,
+            Self::NotFound => FetchFailure::NotFound,
+            Self::SizeLimitExceeded { .. } => FetchFailure::SizeLimit,
+            Self::CommandFailed { .. } => FetchFailure::Git,
+            Self::StagingTransfer(fetch::error::Transfer::NoDelegates) => FetchFailure::NoDelegates,
+            Self::StagingTransition(_) | Self::InvalidIdentity(_) => FetchFailure::Verification,
+            Self::StagingInit(_) | Self::StagingTransfer(_) => FetchFailure::Other,
+        }
+    }
+
+    /// Check if the error is transient, eg. the connection to the remote was interrupted,
+    /// in which case the fetch can be retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, FetchError::Io(_))
+    }
 }
 
 /// Error returned by fetch responder.
@@ -87,6 +120,28 @@ pub enum FetchRequest {
         namespaces: Namespaces,
         /// Remote peer we are interacting with.
         remote: NodeId,
+        /// Maximum number of bytes that can be received before the fetch is aborted.
+        limit: u64,
+        /// Which refs are fetched from the remotes.
+        filter: RefFilter,
+    },
+    /// Client is listing the refs the remote has for the specified
+    /// [`Namespaces`], without fetching anything.
+    LsRefs {
+        /// Repo to list refs of.
+        rid: Id,
+        /// Namespaces to list.
+        namespaces: Namespaces,
+        /// Remote peer we are interacting with.
+        remote: NodeId,
+    },
+    /// Client is fetching only the identity branch of a repository, to verify
+    /// its identity, without storing anything.
+    Identity {
+        /// Repo to fetch the identity of.
+        rid: Id,
+        /// Remote peer we are interacting with.
+        remote: NodeId,
     },
     /// Server is responding to a fetch request by uploading the
     /// specified `refspecs` sent by the client.
@@ -99,7 +154,10 @@ pub enum FetchRequest {
 impl FetchRequest {
     pub fn remote(&self) -> NodeId {
         match self {
-            Self::Initiator { remote, .. } | Self::Responder { remote } => *remote,
+            Self::Initiator { remote, .. }
+            | Self::LsRefs { remote, .. }
+            | Self::Identity { remote, .. }
+            | Self::Responder { remote } => *remote,
         }
     }
 }
@@ -111,7 +169,21 @@ pub enum FetchResult {
         /// Repo fetched.
         rid: Id,
         /// Fetch result, including remotes fetched.
-        result: Result<(Vec<RefUpdate>, HashSet<NodeId>), FetchError>,
+        result: Result<Transferred, FetchError>,
+        /// Number of bytes received from the remote.
+        received: u64,
+    },
+    LsRefs {
+        /// Repo listed.
+        rid: Id,
+        /// Refs found on the remote.
+        result: Result<BTreeSet<git::Namespaced<'static>>, FetchError>,
+    },
+    Identity {
+        /// Repo whose identity was fetched.
+        rid: Id,
+        /// The verified identity.
+        result: Result<Identity<Id>, FetchError>,
     },
     Responder {
         /// Upload result.
@@ -144,6 +216,7 @@ struct Worker {
     timeout: time::Duration,
     handle: Handle,
     atomic: bool,
+    shape: ShapeLimits,
 }
 
 impl Worker {
@@ -191,11 +264,53 @@ impl Worker {
                 rid,
                 namespaces,
                 remote,
+                limit,
+                filter,
             } => {
                 log::debug!(target: "worker", "Worker processing outgoing fetch for {}", rid);
-                let result = self.fetch(rid, remote, stream, &namespaces, channels);
+                channels.set_limit(limit);
+
+                let result = self
+                    .fetch(rid, remote, stream, &namespaces, filter, &mut channels)
+                    .map_err(|e| {
+                        if channels.is_limit_exceeded() {
+                            FetchError::SizeLimitExceeded { limit }
+                        } else {
+                            e
+                        }
+                    });
+                let received = channels.received();
+
+                log::debug!(target: "worker", "Received {received} byte(s) from {remote} fetching {rid}");
+
+                FetchResult::Initiator {
+                    rid,
+                    result,
+                    received,
+                }
+            }
+            FetchRequest::LsRefs {
+                rid,
+                namespaces,
+                remote,
+            } => {
+                log::debug!(target: "worker", "Worker processing outgoing ls-refs for {}", rid);
+                let result = self.ls_refs(
+                    rid,
+                    self.storage.path(),
+                    fetch::ls_remote_patterns(&namespaces),
+                    remote,
+                    stream,
+                    &mut channels,
+                );
+
+                FetchResult::LsRefs { rid, result }
+            }
+            FetchRequest::Identity { rid, remote } => {
+                log::debug!(target: "worker", "Worker processing outgoing identity fetch for {}", rid);
+                let result = self.fetch_identity(rid, remote, stream, channels);
 
-                FetchResult::Initiator { rid, result }
+                FetchResult::Identity { rid, result }
             }
             FetchRequest::Responder { remote } => {
                 log::debug!(target: "worker", "Worker processing incoming fetch..");
@@ -223,18 +338,26 @@ impl Worker {
         remote: NodeId,
         stream: StreamId,
         namespaces: &Namespaces,
-        mut channels: Channels,
-    ) -> Result<(Vec<RefUpdate>, HashSet<NodeId>), FetchError> {
-        let staging =
-            fetch::StagingPhaseInitial::new(&self.storage, rid, self.nid, namespaces.clone())?;
+        filter: RefFilter,
+        channels: &mut Channels,
+    ) -> Result<Transferred, FetchError> {
+        let staging = fetch::StagingPhaseInitial::new(
+            &self.storage,
+            rid,
+            self.nid,
+            namespaces.clone(),
+            filter,
+            self.shape,
+        )?;
         let refs = if staging.repo.is_cloning() {
             match self._fetch(
                 &staging.repo,
                 staging.repo.is_cloning(),
                 remote,
                 staging.refspecs(),
+                &[],
                 stream,
-                &mut channels,
+                channels,
             ) {
                 Ok(_) => {
                     log::debug!(target: "worker", "Initial fetch for {rid} exited successfully")
@@ -256,11 +379,12 @@ impl Worker {
             BTreeSet::new()
         } else {
             self.ls_refs(
-                &staging.repo,
+                rid,
+                staging.repo.path(),
                 staging.ls_remote_refs(),
                 remote,
                 stream,
-                &mut channels,
+                channels,
             )?
         };
 
@@ -271,8 +395,9 @@ impl Worker {
             staging.repo.is_cloning(),
             remote,
             staging.refspecs(),
+            &staging.exclusions(),
             stream,
-            &mut channels,
+            channels,
         ) {
             Ok(()) => log::debug!(target: "worker", "Final fetch for {rid} exited successfully"),
             Err(e) => {
@@ -284,6 +409,28 @@ impl Worker {
         staging.transfer().map_err(FetchError::from)
     }
 
+    /// Fetch the identity branch of a repository into a quarantine, and verify it.
+    fn fetch_identity(
+        &mut self,
+        rid: Id,
+        remote: NodeId,
+        stream: StreamId,
+        mut channels: Channels,
+    ) -> Result<Identity<Id>, FetchError> {
+        let quarantine = fetch::Quarantine::new(rid)?;
+
+        self._fetch(
+            &quarantine.repo,
+            true,
+            remote,
+            quarantine.refspecs(),
+            &[],
+            stream,
+            &mut channels,
+        )?;
+        quarantine.verify().map_err(FetchError::from)
+    }
+
     fn upload_pack(
         &mut self,
         remote: NodeId,
@@ -391,9 +538,11 @@ impl Worker {
         })
     }
 
+    /// List the remote's refs of the given repo, running `git ls-remote` from `cwd`.
     fn ls_refs(
         &self,
-        repo: &fetch::StagedRepository,
+        rid: Id,
+        cwd: &Path,
         namespaces: impl IntoIterator<Item = git::PatternString>,
         remote: NodeId,
         stream: StreamId,
@@ -402,13 +551,13 @@ impl Worker {
         let tunnel = Tunnel::with(channels, stream, self.nid, remote, self.handle.clone())?;
         let tunnel_addr = tunnel.local_addr();
         let mut cmd = process::Command::new("git");
-        cmd.current_dir(repo.path())
+        cmd.current_dir(cwd)
             .env_clear()
             .envs(env::vars().filter(|(k, _)| k == "PATH" || k.starts_with("GIT_TRACE")))
             .envs(git::env::GIT_DEFAULT_CONFIG)
             .args(["-c", "protocol.version=2"])
             .arg("ls-remote")
-            .arg(format!("git://{tunnel_addr}/{}", repo.id.canonical()));
+            .arg(format!("git://{tunnel_addr}/{}", rid.canonical()));
 
         for ns in namespaces.into_iter() {
             cmd.arg(ns.as_str());
@@ -481,6 +630,7 @@ impl Worker {
         is_cloning: bool,
         remote: NodeId,
         specs: S,
+        exclude: &[git::PatternString],
         stream: StreamId,
         channels: &mut Channels,
     ) -> Result<(), FetchError>
@@ -511,9 +661,16 @@ impl Worker {
             .map(|spec| spec.to_string())
             .collect::<Vec<_>>();
 
+        // Refs matching these patterns are never transferred.
+        fetchspecs.extend(exclude.iter().map(|pattern| format!("^{pattern}")));
+
         if !is_cloning {
             // Make sure we don't fetch our own refs via a glob pattern.
             fetchspecs.push(format!("^refs/namespaces/{}/*", self.nid));
+
+            for tip in fetch::negotiation_tips(repo) {
+                cmd.arg(format!("--negotiation-tip={tip}"));
+            }
         }
 
         cmd.arg(format!("git://{tunnel_addr}/{}", repo.id.canonical()))
@@ -527,10 +684,15 @@ impl Worker {
         let mut child = cmd.spawn()?;
         let stderr = child.stderr.take().unwrap();
 
-        thread::spawn(&self.nid, "fetch", || {
+        // Returns whether the remote told us it doesn't have the repository.
+        let stderr = thread::spawn(&self.nid, "fetch", || {
+            let mut not_found = false;
             for line in BufReader::new(stderr).lines().flatten() {
                 log::debug!(target: "worker", "Git: {}", line);
+
+                not_found |= line.contains("remote error: no such repository");
             }
+            not_found
         });
 
         tunnel.run(self.timeout)?;
@@ -538,6 +700,8 @@ impl Worker {
         let result = child.wait()?;
         if result.success() {
             Ok(())
+        } else if stderr.join().unwrap_or_default() {
+            Err(FetchError::NotFound)
         } else {
             Err(FetchError::CommandFailed {
                 code: result.code().unwrap_or(1),
@@ -582,6 +746,7 @@ impl Pool {
                 daemon: config.daemon,
                 timeout: config.timeout,
                 atomic: config.atomic,
+                shape: config.shape,
             };
             let thread = thread::spawn(&nid, format!("worker#{i}"), || worker.run());
 
@@ -74,6 +74,22 @@ impl<T: AsRef<[u8]>> Channels<T> {
     pub fn close(self) -> Result<(), chan::SendError<ChannelEvent<T>>> {
         self.sender.close()
     }
+
+    /// Number of bytes of git protocol data received so far.
+    pub fn received(&self) -> u64 {
+        self.receiver.received
+    }
+
+    /// Set the maximum number of bytes of data that can be received. Reads fail once the
+    /// limit is exceeded.
+    pub fn set_limit(&mut self, limit: u64) {
+        self.receiver.limit = Some(limit);
+    }
+
+    /// Check whether more data was received than the limit allows.
+    pub fn is_limit_exceeded(&self) -> bool {
+        self.receiver.is_limit_exceeded()
+    }
 }
 
 /// Wraps a [`chan::Receiver`] and provides it with [`io::Read`].
@@ -82,6 +98,10 @@ pub struct ChannelReader<T = Vec<u8>> {
     buffer: io::Cursor<Vec<u8>>,
     receiver: chan::Receiver<ChannelEvent<T>>,
     timeout: time::Duration,
+    /// Number of bytes of data received.
+    received: u64,
+    /// Maximum number of bytes of data that can be received, if any.
+    limit: Option<u64>,
 }
 
 impl<T> Deref for ChannelReader<T> {
@@ -98,13 +118,36 @@ impl<T: AsRef<[u8]>> ChannelReader<T> {
             buffer: io::Cursor::new(Vec::new()),
             receiver,
             timeout,
+            received: 0,
+            limit: None,
         }
     }
 
+    fn is_limit_exceeded(&self) -> bool {
+        self.limit.map_or(false, |limit| self.received > limit)
+    }
+
+    /// Account for received data, failing if the limit is exceeded.
+    fn receive(&mut self, len: usize) -> io::Result<()> {
+        self.received += len as u64;
+
+        if self.is_limit_exceeded() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "error reading from stream: size limit exceeded",
+            ));
+        }
+        Ok(())
+    }
+
     pub fn pipe<W: io::Write>(&mut self, mut writer: W) -> io::Result<()> {
         loop {
             match self.receiver.recv_timeout(self.timeout) {
-                Ok(ChannelEvent::Data(data)) => writer.write_all(data.as_ref())?,
+                Ok(ChannelEvent::Data(data)) => {
+                    let data = data.as_ref();
+                    self.receive(data.len())?;
+                    writer.write_all(data)?
+                }
                 Ok(ChannelEvent::Eof) => return Ok(()),
                 Ok(ChannelEvent::Close) => return Err(io::ErrorKind::ConnectionReset.into()),
                 Err(chan::RecvTimeoutError::Timeout) => {
@@ -133,6 +176,7 @@ impl Read for ChannelReader<Vec<u8>> {
 
         match self.receiver.recv_timeout(self.timeout) {
             Ok(ChannelEvent::Data(data)) => {
+                self.receive(data.len())?;
                 self.buffer = io::Cursor::new(data);
                 self.buffer.read(buf)
             }
@@ -3,12 +3,18 @@ pub use refspecs::SpecialRefs;
 
 pub mod error;
 
+use std::cmp::Reverse;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::ops::Deref;
 
+use radicle::cob::change::Limits;
 use radicle::crypto::{PublicKey, Unverified, Verified};
 use radicle::git::refspec;
 use radicle::git::{url, Namespaced};
+use radicle::identity::{Identity, IdentityError};
+use radicle::node::config::ShapeLimits;
+use radicle::node::tracking::RefFilter;
+use radicle::node::SkipReason;
 use radicle::prelude::{Doc, Id, NodeId};
 use radicle::storage::git::Repository;
 use radicle::storage::refs::IDENTITY_BRANCH;
@@ -18,6 +24,58 @@ use radicle::{git, Storage};
 
 pub type Refspec = refspec::Refspec<git::PatternString, git::PatternString>;
 
+/// A verified remote's identity document, unsigned refs, and signed refs skipped
+/// because of the ref filter.
+type VerifiedRefs = (
+    Doc<Verified>,
+    Vec<git::RefString>,
+    Vec<(git::RefString, git::Oid)>,
+);
+
+/// The result of transferring a fetch into storage: the ref updates, the remotes fetched,
+/// the number of signed refs fetched of each of them, and the remotes that weren't
+/// fetched, with the reason why.
+pub type Transferred = (
+    Vec<RefUpdate>,
+    HashSet<NodeId>,
+    Vec<(NodeId, usize)>,
+    Vec<(NodeId, SkipReason)>,
+);
+
+/// Maximum number of namespaces whose tips are advertised when negotiating a fetch.
+pub const MAX_NEGOTIATION_NAMESPACES: usize = 32;
+
+/// A temporary repository that only the remote's identity branch is fetched into,
+/// to verify the identity of a repository we don't have. Nothing is transferred into
+/// storage: the fetched objects are discarded when the quarantine is dropped.
+pub struct Quarantine {
+    /// The inner [`Repository`] to fetch the identity branch into.
+    pub(super) repo: Repository,
+    _tmp: tempfile::TempDir,
+}
+
+impl Quarantine {
+    /// Construct a [`Quarantine`] in a new, temporary directory.
+    pub fn new(rid: Id) -> Result<Self, error::Init> {
+        let tmp = tempfile::TempDir::new()?;
+        log::debug!(target: "worker", "Quarantining identity of {rid} in {:?}", tmp.path());
+        let staging = Storage::open(tmp.path())?;
+        let repo = staging.create(rid).map_err(error::Setup::from)?;
+
+        Ok(Self { repo, _tmp: tmp })
+    }
+
+    /// Return the fetch refspecs for fetching the identity branch.
+    pub fn refspecs(&self) -> Vec<Refspec> {
+        identity_refspecs()
+    }
+
+    /// Verify the fetched identity branch against the repository id, and discard it.
+    pub fn verify(self) -> Result<Identity<Id>, IdentityError> {
+        self.repo.identity()?.verified(self.repo.id)
+    }
+}
+
 /// The initial phase of staging a fetch from a remote.
 ///
 /// The [`StagingPhaseInitial::refpsecs`] generated are to fetch the
@@ -35,6 +93,10 @@ pub struct StagingPhaseInitial<'a> {
     nid: NodeId,
     /// The `Namespaces` passed by the fetching caller.
     pub(super) namespaces: Namespaces,
+    /// Which refs are fetched from the remotes.
+    filter: RefFilter,
+    /// Limits on the shape of the fetched namespaces.
+    shape: ShapeLimits,
     _tmp: tempfile::TempDir,
 }
 
@@ -70,6 +132,8 @@ pub enum FinalStagedRepository {
     Fetching {
         repo: Repository,
         refs: BTreeSet<Namespaced<'static>>,
+        /// Trusted remotes that the seed doesn't have.
+        absent: Vec<NodeId>,
     },
 }
 
@@ -108,12 +172,16 @@ pub struct StagingPhaseFinal<'a> {
     production: &'a Storage,
     /// The local Node ID.
     nid: NodeId,
+    /// Which refs are fetched from the remotes.
+    filter: RefFilter,
+    /// Limits on the shape of the fetched namespaces.
+    shape: ShapeLimits,
     _tmp: tempfile::TempDir,
 }
 
 enum VerifiedRemote {
     Failed {
-        reason: String,
+        reason: error::Verify,
     },
     Success {
         // Nb. unused but we want to ensure that we verify the identity
@@ -121,8 +189,14 @@ enum VerifiedRemote {
         remote: Remote<Verified>,
         /// Unsigned refs.
         unsigned: Vec<git::RefString>,
+        /// Signed refs that weren't fetched, since they are excluded by the ref filter.
+        skipped: Vec<(git::RefString, git::Oid)>,
+    },
+    UpToDate {
+        /// Number of signed refs we have of the remote, that aren't excluded by the ref
+        /// filter.
+        refs: usize,
     },
-    UpToDate,
 }
 
 impl<'a> StagingPhaseInitial<'a> {
@@ -133,6 +207,8 @@ impl<'a> StagingPhaseInitial<'a> {
         rid: Id,
         nid: NodeId,
         namespaces: Namespaces,
+        filter: RefFilter,
+        shape: ShapeLimits,
     ) -> Result<Self, error::Init> {
         let tmp = tempfile::TempDir::new()?;
         log::debug!(target: "worker", "Staging fetch in {:?}", tmp.path());
@@ -143,6 +219,8 @@ impl<'a> StagingPhaseInitial<'a> {
             nid,
             production,
             namespaces,
+            filter,
+            shape,
             _tmp: tmp,
         })
     }
@@ -150,31 +228,14 @@ impl<'a> StagingPhaseInitial<'a> {
     /// Return the fetch refspecs for fetching the necessary `rad`
     /// references.
     pub fn refspecs(&self) -> Vec<Refspec> {
-        let id = git::PatternString::from(IDENTITY_BRANCH.clone().into_refstring());
         match self.repo {
-            StagedRepository::Cloning(_) => vec![Refspec {
-                src: id.clone(),
-                dst: id,
-                force: false,
-            }],
+            StagedRepository::Cloning(_) => identity_refspecs(),
             StagedRepository::Fetching(_) => SpecialRefs(self.namespaces.clone()).into_refspecs(),
         }
     }
 
     pub fn ls_remote_refs(&self) -> Vec<git::PatternString> {
-        match &self.namespaces {
-            Namespaces::All => {
-                vec![git::refspec::pattern!("refs/namespaces/*")]
-            }
-            Namespaces::Trusted(trusted) => trusted
-                .iter()
-                .map(|ns| {
-                    git::refname!("refs/namespaces")
-                        .join(git::Component::from(ns))
-                        .with_pattern(git::refspec::STAR)
-                })
-                .collect::<Vec<_>>(),
-        }
+        ls_remote_patterns(&self.namespaces)
     }
 
     /// Convert the [`StagingPhaseInitial`] into [`StagingPhaseFinal`] to continue
@@ -198,13 +259,39 @@ impl<'a> StagingPhaseInitial<'a> {
                 trusted.extend(delegates);
                 FinalStagedRepository::Cloning { repo, trusted }
             }
-            StagedRepository::Fetching(repo) => FinalStagedRepository::Fetching { repo, refs },
+            StagedRepository::Fetching(repo) => {
+                let (refs, excluded): (BTreeSet<_>, BTreeSet<_>) = refs
+                    .into_iter()
+                    .partition(|r| !self.filter.is_excluded(r.strip_namespace().as_str()));
+
+                for r in excluded {
+                    log::debug!(target: "worker", "Skipping {r}: excluded by ref filter");
+                }
+                let absent = match &self.namespaces {
+                    Namespaces::All => vec![],
+                    Namespaces::Trusted(trusted) => {
+                        let present = refs
+                            .iter()
+                            .filter_map(|r| NodeId::from_namespaced(r).ok())
+                            .collect::<HashSet<_>>();
+
+                        trusted
+                            .iter()
+                            .filter(|nid| **nid != self.nid && !present.contains(nid))
+                            .copied()
+                            .collect()
+                    }
+                };
+                FinalStagedRepository::Fetching { repo, refs, absent }
+            }
         };
 
         Ok(StagingPhaseFinal {
             repo,
             nid: self.nid,
             production: self.production,
+            filter: self.filter,
+            shape: self.shape,
             _tmp: self._tmp,
         })
     }
@@ -260,9 +347,27 @@ impl<'a> StagingPhaseFinal<'a> {
     /// references.
     pub fn refspecs(&self) -> Vec<Refspec> {
         match &self.repo {
-            FinalStagedRepository::Cloning { trusted, .. } => {
+            FinalStagedRepository::Cloning { trusted, .. } if self.filter.include.is_empty() => {
                 Namespaces::Trusted(trusted.clone()).to_refspecs()
             }
+            FinalStagedRepository::Cloning { trusted, .. } => trusted
+                .iter()
+                .flat_map(|remote| {
+                    // The `rad` refs are needed to verify the remote.
+                    let rad = git::refspec::pattern!("refs/rad/*");
+
+                    [&rad]
+                        .into_iter()
+                        .chain(&self.filter.include)
+                        .map(|pattern| namespaced_pattern(remote, pattern))
+                        .collect::<Vec<_>>()
+                })
+                .map(|pattern| Refspec {
+                    src: pattern.clone(),
+                    dst: pattern,
+                    force: true,
+                })
+                .collect(),
             FinalStagedRepository::Fetching { refs, .. } => refs
                 .iter()
                 .map(|r| Refspec {
@@ -274,12 +379,33 @@ impl<'a> StagingPhaseFinal<'a> {
         }
     }
 
+    /// Return the patterns of the refs that must not be fetched, in addition to the
+    /// [`StagingPhaseFinal::refspecs`]. When fetching into an existing repository,
+    /// excluded refs are already left out of the refspecs.
+    pub fn exclusions(&self) -> Vec<git::PatternString> {
+        match &self.repo {
+            FinalStagedRepository::Cloning { trusted, .. } => trusted
+                .iter()
+                .flat_map(|remote| {
+                    self.filter
+                        .exclude
+                        .iter()
+                        .map(move |pattern| namespaced_pattern(remote, pattern))
+                })
+                .collect(),
+            FinalStagedRepository::Fetching { .. } => vec![],
+        }
+    }
+
     /// Finalise the fetching process via the following steps.
     ///
     /// Verify all `rad/id` and `rad/sigrefs` from fetched
     /// remotes. Any remotes that fail will be ignored and not fetched
     /// into the production repository.
     ///
+    /// Remotes whose namespace goes over the [`ShapeLimits`] are quarantined: none of
+    /// their refs are transferred, and they are returned as skipped.
+    ///
     /// For each remote that verifies, fetch from the staging storage
     /// into the production storage using the refspec:
     ///
@@ -288,8 +414,9 @@ impl<'a> StagingPhaseFinal<'a> {
     /// ```
     ///
     /// All references that were updated are returned as a
-    /// [`RefUpdate`].
-    pub fn transfer(self) -> Result<(Vec<RefUpdate>, HashSet<NodeId>), error::Transfer> {
+    /// [`RefUpdate`], along with the number of refs fetched of each remote, and the
+    /// remotes that were skipped.
+    pub fn transfer(self) -> Result<Transferred, error::Transfer> {
         // Nb. we have to verify in a different order when fetching vs. cloning, due to needing
         // access to the existing repository in the fetching case.
         let (production, verifications) = match &self.repo {
@@ -311,33 +438,87 @@ impl<'a> StagingPhaseFinal<'a> {
         let mut updates = Vec::new();
         let mut delete = HashSet::new();
         let mut skipped = HashSet::new();
+        let mut excluded = Vec::new();
+        let mut partial = Vec::new();
+        let mut fetched = Vec::new();
+        let mut not_fetched = self
+            .absent()
+            .into_iter()
+            .map(|nid| (nid, SkipReason::Absent))
+            .collect::<Vec<_>>();
 
         let callbacks = ref_updates(&mut updates);
         let mut remotes = {
             let specs = verifications
                 .into_iter()
                 .flat_map(|(remote, verified)| match verified {
-                    VerifiedRemote::UpToDate => {
+                    VerifiedRemote::UpToDate { refs } => {
                         log::debug!(target: "worker", "{remote} is up-to-date");
                         skipped.insert(remote);
+                        fetched.push((remote, refs));
 
                         vec![]
                     }
+                    VerifiedRemote::Failed {
+                        reason: error::Verify::Shape(reason),
+                    } => {
+                        log::warn!(
+                            target: "worker",
+                            "{remote} went over the shape limits, quarantining its refs: {reason}",
+                        );
+                        not_fetched.push((
+                            remote,
+                            SkipReason::Quarantined {
+                                reason: reason.to_string(),
+                            },
+                        ));
+                        vec![]
+                    }
                     VerifiedRemote::Failed { reason } => {
-                        // TODO: We should include the skipped remotes in the fetch result,
-                        // with the reason why they're skipped.
                         log::warn!(
                             target: "worker",
                             "{remote} failed to verify, ignoring ref updates: {reason}",
                         );
+                        not_fetched.push((
+                            remote,
+                            SkipReason::Invalid {
+                                reason: reason.to_string(),
+                            },
+                        ));
                         vec![]
                     }
                     VerifiedRemote::Success {
-                        remote, unsigned, ..
+                        remote,
+                        unsigned,
+                        skipped: filtered,
+                        ..
                     } => {
                         let ns = remote.id.to_namespace();
                         let mut refspecs = vec![];
 
+                        // Remotes whose refs are all excluded are only fetched to verify them.
+                        let signed = remote
+                            .refs
+                            .refs
+                            .keys()
+                            .filter(|name| !name.as_str().starts_with("refs/rad/"))
+                            .count();
+                        if !filtered.is_empty() && filtered.len() == signed {
+                            not_fetched.push((remote.id, SkipReason::Filtered));
+                        } else {
+                            fetched.push((remote.id, remote.refs.refs.len() - filtered.len()));
+                        }
+                        partial.push((remote.id, !filtered.is_empty()));
+
+                        // Excluded refs are recorded as skipped, so that it's clear that
+                        // they weren't fetched on purpose.
+                        for (name, oid) in filtered {
+                            let name = ns.join(name);
+
+                            log::debug!(target: "worker", "Skipped {name}: excluded by ref filter");
+                            excluded.push(RefUpdate::Skipped { name, oid });
+                        }
+
                         // Unsigned refs should be deleted.
                         delete.insert((remote.id, unsigned));
 
@@ -413,6 +594,12 @@ impl<'a> StagingPhaseFinal<'a> {
             // Fetch into production copy.
             remote.fetch(&specs, Some(&mut opts), None)?;
 
+            // Remotes with excluded refs are missing some of their signed refs, so they
+            // are hidden from the nodes fetching from us, who would fail to verify them.
+            for (remote, hidden) in partial {
+                set_hidden(&production.backend, &remote, hidden)?;
+            }
+
             // Delete unsigned refs.
             for (namespace, unsigned) in delete {
                 for refstr in unsigned {
@@ -436,19 +623,41 @@ impl<'a> StagingPhaseFinal<'a> {
 
         #[cfg(test)]
         // N.b. This is to prevent us from shooting ourselves in the
-        // foot with storage inconsistencies.
-        radicle::debug_assert_matches!(
-            production.validate(),
-            Ok(()),
-            "repository {} is not valid",
-            production.id,
-        );
+        // foot with storage inconsistencies. Hidden remotes are missing signed refs on
+        // purpose, and aren't served.
+        for (id, remote) in production.remotes().expect("remotes can be listed") {
+            if !is_hidden(&production.backend, &id).unwrap_or_default() {
+                radicle::debug_assert_matches!(
+                    production.validate_remote(&remote),
+                    Ok(_),
+                    "remote {id} of repository {} is not valid",
+                    production.id,
+                );
+            }
+        }
 
         // Extend the list of remotes we attempted to fetch from with the skipped remotes.
         // This confirms to the user that the remote was indeed tried.
         remotes.extend(skipped);
+        updates.extend(excluded);
 
-        Ok((updates, remotes))
+        Ok((updates, remotes, fetched, not_fetched))
+    }
+
+    /// Get the trusted remotes that the seed doesn't have.
+    fn absent(&self) -> Vec<NodeId> {
+        match &self.repo {
+            FinalStagedRepository::Cloning { repo, trusted } => trusted
+                .iter()
+                .filter(|nid| **nid != self.nid)
+                .filter(|nid| {
+                    repo.reference_oid(nid, &git::refs::storage::SIGREFS_BRANCH)
+                        .is_err()
+                })
+                .copied()
+                .collect(),
+            FinalStagedRepository::Fetching { absent, .. } => absent.clone(),
+        }
     }
 
     fn remotes(&self) -> Result<Box<dyn Iterator<Item = Remote> + '_>, git::raw::Error> {
@@ -458,7 +667,7 @@ impl<'a> StagingPhaseFinal<'a> {
                     .iter()
                     .filter_map(|remote| self.repo.remote(remote).ok()),
             )),
-            FinalStagedRepository::Fetching { repo, refs } => {
+            FinalStagedRepository::Fetching { repo, refs, .. } => {
                 // Only verify remotes we're fetching refs from.
                 let remotes = refs
                     .iter()
@@ -503,37 +712,38 @@ impl<'a> StagingPhaseFinal<'a> {
                                     return (
                                         remote_id,
                                         VerifiedRemote::Failed {
-                                            reason: "signed refs have diverged".to_owned()
+                                            reason: error::Verify::Diverged,
                                         }
                                     );
                                 }
                                 Err(e) => {
                                     return (
                                         remote_id,
-                                        VerifiedRemote::Failed { reason: e.to_string() },
+                                        VerifiedRemote::Failed { reason: e.into() },
                                     );
                                 }
                             }
                         } else {
-                            return (remote_id, VerifiedRemote::UpToDate);
+                            let refs = remote
+                                .refs
+                                .refs
+                                .keys()
+                                .filter(|name| !self.filter.is_excluded(name.as_str()))
+                                .count();
+
+                            return (remote_id, VerifiedRemote::UpToDate { refs });
                         }
                     }
                 }
 
-                let verification = match self.repo.identity_doc_of(&remote_id) {
-                    Ok(doc) => match self.repo.validate_remote(&remote) {
-                        Ok(unsigned) => VerifiedRemote::Success {
-                            _doc: doc,
-                            remote,
-                            unsigned,
-                        },
-                        Err(e) => VerifiedRemote::Failed {
-                            reason: e.to_string(),
-                        },
-                    },
-                    Err(e) => VerifiedRemote::Failed {
-                        reason: e.to_string(),
+                let verification = match self.verify_remote(&remote, local) {
+                    Ok((doc, unsigned, skipped)) => VerifiedRemote::Success {
+                        _doc: doc,
+                        remote,
+                        unsigned,
+                        skipped,
                     },
+                    Err(reason) => VerifiedRemote::Failed { reason },
                 };
                 (remote_id, verification)
             })
@@ -541,6 +751,204 @@ impl<'a> StagingPhaseFinal<'a> {
 
         Ok(result)
     }
+
+    /// Verify the identity, signed refs and COBs of a remote, returning its identity
+    /// document, unsigned refs, and the signed refs skipped because of the ref filter.
+    /// Only the COB entries we don't already have locally are checked against the entry
+    /// limits.
+    fn verify_remote<R: ReadRepository>(
+        &self,
+        remote: &Remote<Verified>,
+        local: Option<&R>,
+    ) -> Result<VerifiedRefs, error::Verify> {
+        let doc = self.repo.identity_doc_of(&remote.id)?;
+
+        // Excluded refs were never fetched, so they are left out of the signed refs that
+        // are checked.
+        let mut remote = remote.clone();
+        let skipped = remote
+            .refs
+            .refs
+            .iter()
+            .filter(|(name, _)| self.filter.is_excluded(name.as_str()))
+            .map(|(name, oid)| (name.clone(), *oid))
+            .collect::<Vec<_>>();
+        for (name, _) in &skipped {
+            remote.refs.refs.remove(name);
+        }
+        self.check_shape(&remote)?;
+
+        // Excluded refs we already have aren't unsigned, they just weren't updated.
+        let unsigned = self
+            .repo
+            .validate_remote(&remote)?
+            .into_iter()
+            .filter(|name| !self.filter.is_excluded(name.as_str()))
+            .collect();
+
+        self.repo
+            .check_cob_limits(&remote, &Limits::default(), |refname| {
+                let refname = git::Qualified::from_refstr(refname)?;
+                local?.reference_oid(&remote.id, &refname).ok()
+            })?;
+
+        Ok((doc, unsigned, skipped))
+    }
+
+    /// Check that the namespace of a remote doesn't go over the [`ShapeLimits`], and that
+    /// all its signed refs point to objects we have.
+    fn check_shape(&self, remote: &Remote<Verified>) -> Result<(), error::Verify> {
+        let prefix = format!("refs/namespaces/{}/", remote.id);
+        let mut count = 0;
+
+        for r in self
+            .repo
+            .backend
+            .references_glob(format!("{prefix}*").as_str())?
+        {
+            let r = r?;
+            let name = String::from_utf8_lossy(r.name_bytes());
+            let name = name.strip_prefix(prefix.as_str()).unwrap_or(&name);
+
+            count += 1;
+            if count > self.shape.max_refs {
+                return Err(error::Shape::TooManyRefs {
+                    limit: self.shape.max_refs,
+                }
+                .into());
+            }
+            if name.len() > self.shape.max_ref_name_len {
+                return Err(error::Shape::RefNameTooLong {
+                    name: name.to_owned(),
+                    limit: self.shape.max_ref_name_len,
+                }
+                .into());
+            }
+        }
+
+        let odb = self.repo.backend.odb()?;
+        for (name, oid) in remote.refs.refs.iter() {
+            if !odb.exists((*oid).into()) {
+                return Err(error::Shape::MissingObject {
+                    name: name.clone(),
+                    oid: *oid,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Git configuration key of the refs hidden from the nodes fetching from us.
+const HIDE_REFS: &str = "uploadpack.hideRefs";
+
+/// Hide or unhide the namespace of a remote from the nodes fetching from us.
+fn set_hidden(
+    repo: &git::raw::Repository,
+    remote: &RemoteId,
+    hidden: bool,
+) -> Result<(), git::raw::Error> {
+    if hidden == is_hidden(repo, remote)? {
+        return Ok(());
+    }
+    let mut config = repo.config()?;
+    let namespace = format!("refs/namespaces/{remote}");
+
+    if hidden {
+        // Nb. The regular expression doesn't match any existing value, so a value is added.
+        config.set_multivar(HIDE_REFS, "^$", &namespace)
+    } else {
+        config.remove_multivar(HIDE_REFS, &format!("^{namespace}$"))
+    }
+}
+
+/// Check whether the namespace of a remote is hidden from the nodes fetching from us.
+fn is_hidden(repo: &git::raw::Repository, remote: &RemoteId) -> Result<bool, git::raw::Error> {
+    let namespace = format!("refs/namespaces/{remote}");
+    let config = repo.config()?;
+    let entries = config.multivar(HIDE_REFS, None)?;
+
+    for entry in &entries {
+        if entry?.value() == Some(namespace.as_str()) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Prefix a ref pattern with the namespace of the given remote.
+fn namespaced_pattern(remote: &RemoteId, pattern: &git::PatternStr) -> git::PatternString {
+    git::PatternString::try_from(format!("{}/{}", remote.to_namespace(), pattern.as_str()))
+        .expect("namespaced_pattern: a pattern under a namespace is a valid pattern")
+}
+
+/// The refspecs for fetching the canonical identity branch, ie. `refs/rad/id`.
+pub fn identity_refspecs() -> Vec<Refspec> {
+    let id = git::PatternString::from(IDENTITY_BRANCH.clone().into_refstring());
+
+    vec![Refspec {
+        src: id.clone(),
+        dst: id,
+        force: false,
+    }]
+}
+
+/// Get the ref patterns whose tips are advertised as "haves" when fetching into the given
+/// repository, so that the remote doesn't send objects we already have under another
+/// namespace, eg. when fetching a fork of history we have.
+///
+/// Up to [`MAX_NEGOTIATION_NAMESPACES`] namespaces are advertised: the delegates' first,
+/// then the most recently updated ones. Nb. Only object ids are sent to the remote, never
+/// ref names.
+pub fn negotiation_tips(repo: &Repository) -> Vec<git::PatternString> {
+    let delegates = repo
+        .delegates()
+        .map(|ds| ds.into_iter().map(PublicKey::from).collect::<HashSet<_>>())
+        .unwrap_or_default();
+    let Ok(remotes) = repo.remote_ids() else {
+        return vec![];
+    };
+    let mut namespaces = remotes
+        .filter_map(Result::ok)
+        .map(|nid| {
+            let updated = repo
+                .reference_oid(&nid, &git::refs::storage::SIGREFS_BRANCH)
+                .and_then(|oid| repo.commit(oid))
+                .map(|c| c.time().seconds())
+                .unwrap_or_default();
+
+            (!delegates.contains(&nid), Reverse(updated), nid)
+        })
+        .collect::<Vec<_>>();
+
+    namespaces.sort();
+    namespaces
+        .into_iter()
+        .take(MAX_NEGOTIATION_NAMESPACES)
+        .map(|(_, _, nid)| {
+            git::refname!("refs/namespaces")
+                .join(git::Component::from(&nid))
+                .with_pattern(git::refspec::STAR)
+        })
+        .collect()
+}
+
+/// The `ls-remote` patterns matching the given namespaces.
+pub fn ls_remote_patterns(namespaces: &Namespaces) -> Vec<git::PatternString> {
+    match namespaces {
+        Namespaces::All => {
+            vec![git::refspec::pattern!("refs/namespaces/*")]
+        }
+        Namespaces::Trusted(trusted) => trusted
+            .iter()
+            .map(|ns| {
+                git::refname!("refs/namespaces")
+                    .join(git::Component::from(ns))
+                    .with_pattern(git::refspec::STAR)
+            })
+            .collect::<Vec<_>>(),
+    }
 }
 
 fn ref_updates(updates: &mut Vec<RefUpdate>) -> git::raw::RemoteCallbacks<'_> {
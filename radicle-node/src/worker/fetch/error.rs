@@ -34,6 +34,34 @@ pub enum Transfer {
     NoDelegates,
 }
 
+/// A remote failed verification, and its refs are not transferred into storage.
+#[derive(Debug, Error)]
+pub enum Verify {
+    #[error("signed refs have diverged")]
+    Diverged,
+    #[error(transparent)]
+    Git(#[from] git::raw::Error),
+    #[error(transparent)]
+    Identity(#[from] identity::IdentityError),
+    #[error(transparent)]
+    Remote(#[from] storage::git::VerifyError),
+    #[error(transparent)]
+    CobLimits(#[from] storage::git::cob::LimitsError),
+    #[error(transparent)]
+    Shape(#[from] Shape),
+}
+
+/// A remote's namespace went over the configured shape limits.
+#[derive(Debug, Error)]
+pub enum Shape {
+    #[error("namespace has more than {limit} refs")]
+    TooManyRefs { limit: usize },
+    #[error("ref name `{name}` is longer than the limit of {limit} byte(s)")]
+    RefNameTooLong { name: String, limit: usize },
+    #[error("signed ref `{name}` points to missing object {oid}")]
+    MissingObject { name: git::RefString, oid: git::Oid },
+}
+
 #[derive(Debug, Error)]
 pub enum Transition {
     #[error(transparent)]
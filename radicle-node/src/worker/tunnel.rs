@@ -54,8 +54,15 @@ impl<'a> Tunnel<'a> {
         let stream_id = self.stream;
 
         thread::scope(|s| {
-            let remote_to_local =
-                thread::spawn_scoped(&self.local, "tunnel", s, || remote_r.pipe(local_w));
+            let remote_to_local = thread::spawn_scoped(&self.local, "tunnel", s, || {
+                let result = remote_r.pipe(&local_w);
+                if result.is_err() {
+                    // Close the local connection, so that the git process doesn't wait
+                    // for data that will never come.
+                    local_w.shutdown(net::Shutdown::Both).ok();
+                }
+                result
+            });
 
             let local_to_remote = thread::spawn_scoped(&self.local, "tunnel", s, || {
                 let mut buffer = [0; u16::MAX as usize + 1];
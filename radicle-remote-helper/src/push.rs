@@ -224,11 +224,11 @@ pub fn run(
         stored.sign_refs(&signer)?;
         stored.set_head()?;
 
-        if !opts.no_sync {
+        if !opts.no_sync && !radicle::profile::env::offline() {
             // Connect to local node and announce refs to the network.
-            // If our node is not running, we simply skip this step, as the
-            // refs will be announced eventually, when the node restarts.
-            let node = radicle::Node::new(profile.socket());
+            // If our node is not running, or we're offline, we simply skip this step,
+            // as the refs will be announced eventually, when the node restarts.
+            let node = radicle::Node::from_profile(profile);
             if node.is_running() {
                 // Nb. allow this to fail. The push to local storage was still successful.
                 sync(stored.id, node).ok();
@@ -19,7 +19,7 @@ fn main() -> anyhow::Result<()> {
     let sigrefs = project.sign_refs(&signer)?;
     let head = project.set_head()?;
 
-    radicle::Node::new(profile.socket()).announce_refs(id)?;
+    radicle::Node::from_profile(&profile).announce_refs(id)?;
 
     println!("head: {head}");
     println!("ok: {}", sigrefs.signature);
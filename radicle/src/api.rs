@@ -0,0 +1,140 @@
+//! Stable API for tools built on top of radicle, eg. bots, bridges or editors.
+//!
+//! The rest of this crate is organized around the needs of the node and the CLI, and its
+//! module paths change as these evolve. This module gathers what is needed for the most
+//! common tasks, under paths that don't change:
+//!
+//! * Opening a profile: [`profile`].
+//! * Reading storage: [`Storage`], [`Repository`], [`ReadStorage`] and [`ReadRepository`].
+//! * Resolving a repository and its identity: [`working`] and [`project`].
+//! * Tracking repositories and subscribing to events via a running node: [`node`].
+//! * Working with patches: [`patch`].
+//!
+//! # Semver policy
+//!
+//! Items reachable from this module are only removed or changed in incompatible ways in a
+//! breaking release of the crate. When an item is moved within the crate, its re-export
+//! here stays where it is. Everything else, including the [`internal`] escape hatch, can
+//! change in any release.
+use std::path::Path;
+
+use crate::rad;
+
+pub use crate::crypto::{PublicKey, Signer, Verified};
+pub use crate::identity::{project::Project, Did, Doc, Id};
+pub use crate::node::tracking::{Policy, Scope};
+pub use crate::node::{
+    Alias, AnnounceEvent, AnnounceResult, ConnectOptions, ConnectResult, ErrorCode, Event,
+    FetchFailure, FetchOutcome, FetchRecord, FetchResult, FetchResults, Handle, Node, NodeId,
+    Seeds, SyncAllOptions, SyncProgress, SyncResult,
+};
+pub use crate::profile::{Home, Profile};
+pub use crate::storage::git::{Repository, Storage};
+pub use crate::storage::{
+    BranchName, ReadRepository, ReadStorage, RemoteId, WriteRepository, WriteStorage,
+};
+
+/// Errors returned by the functions of this module, and the types it re-exports.
+pub mod error {
+    pub use crate::identity::IdentityError as Identity;
+    pub use crate::node::Error as Node;
+    pub use crate::profile::Error as Profile;
+    pub use crate::rad::RemoteError as Remote;
+    pub use crate::storage::Error as Storage;
+}
+
+/// Git types and helpers, for working copies and storage repositories.
+pub mod git {
+    pub use crate::git::refs::workdir;
+    pub use crate::git::url;
+    pub use crate::git::{is_not_found_err, raw, refname, Oid, Qualified, RefStr, RefString};
+}
+
+/// Patches, ie. proposed changes to a repository.
+pub mod patch {
+    pub use crate::cob::patch::{Patch, PatchId, Patches, Revision, RevisionId, State};
+    pub use crate::cob::store::Error;
+    pub use crate::rad::setup_patch_upstream as setup_upstream;
+}
+
+/// The rest of the crate, without any stability guarantee.
+#[doc(hidden)]
+pub mod internal {
+    pub use crate::{cob, git, identity, node, profile, rad, storage};
+}
+
+/// Open the profile of the current user, from `RAD_HOME` or the default location.
+///
+/// ```no_run
+/// # fn main() -> Result<(), radicle::api::error::Profile> {
+/// let profile = radicle::api::profile()?;
+///
+/// println!("{}", profile.id());
+/// # Ok(())
+/// # }
+/// ```
+pub fn profile() -> Result<Profile, error::Profile> {
+    Profile::load()
+}
+
+/// Open the working copy at the given path, and get the id of the repository it was
+/// checked out from.
+///
+/// ```no_run
+/// # fn main() -> Result<(), radicle::api::error::Remote> {
+/// let (working, rid) = radicle::api::working(".")?;
+///
+/// println!("{rid} is checked out in {:?}", working.workdir());
+/// # Ok(())
+/// # }
+/// ```
+pub fn working(path: impl AsRef<Path>) -> Result<(git::raw::Repository, Id), error::Remote> {
+    rad::repo(path)
+}
+
+/// Get the verified identity document of a repository in storage, and its project payload.
+///
+/// ```no_run
+/// use radicle::api::ReadStorage as _;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = radicle::api::profile()?;
+/// let (_, rid) = radicle::api::working(".")?;
+/// let repo = profile.storage.repository(rid)?;
+/// let (doc, project) = radicle::api::project(&repo)?;
+///
+/// println!("{} has {} delegate(s)", project.name(), doc.delegates.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn project(repo: &Repository) -> Result<(Doc<Verified>, Project), error::Identity> {
+    let (_, doc) = repo.identity_doc()?;
+    let doc = doc.verified()?;
+    let project = doc.project()?;
+
+    Ok((doc, project))
+}
+
+/// Get a handle to the node of the given profile, to send it commands or subscribe to
+/// its events. The node is expected to be running.
+///
+/// ```no_run
+/// use std::time;
+/// use radicle::api::{Handle as _, Scope};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = radicle::api::profile()?;
+/// let (_, rid) = radicle::api::working(".")?;
+/// let mut node = radicle::api::node(&profile);
+///
+/// node.track_repo(rid, Scope::All)?;
+///
+/// for event in node.subscribe(time::Duration::from_secs(60))? {
+///     println!("{:?}", event?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn node(profile: &Profile) -> Node {
+    Node::from_profile(profile)
+}
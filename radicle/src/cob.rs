@@ -11,11 +11,38 @@ pub mod thread;
 pub mod test;
 
 pub use cob::{
-    change, history::EntryId, object, object::collaboration::error, CollaborativeObject, Contents,
-    Create, Embed, Entry, History, Manifest, ObjectId, Store, TypeName, Update, Updated, Version,
+    change, history::DiffError, history::EntryId, object, object::collaboration::error,
+    CollaborativeObject, Contents, Create, Embed, Entry, History, Manifest, ObjectId, Rejected,
+    Store, TypeName, Update, Updated, Verification, Version,
 };
 pub use cob::{create, get, list, remove, update};
 pub use common::*;
 pub use op::{ActorId, Op};
 
 use radicle_cob as cob;
+
+use crate::crypto::Signer;
+use crate::storage::{ReadRepository, SignRepository};
+
+/// Create an object of the given type, or update an existing one, from actions encoded
+/// as JSON. Only issues and patches are supported. See [`store::update_json`].
+pub fn update_json<R, G>(
+    repo: &R,
+    type_name: &TypeName,
+    object: Option<ObjectId>,
+    message: &str,
+    actions: Vec<serde_json::Value>,
+    signer: &G,
+) -> Result<(ObjectId, EntryId), store::Error>
+where
+    R: ReadRepository + SignRepository + Store,
+    G: Signer,
+{
+    if type_name == &*issue::TYPENAME {
+        store::update_json::<issue::Issue, _, _>(repo, object, message, actions, signer)
+    } else if type_name == &*patch::TYPENAME {
+        store::update_json::<patch::Patch, _, _>(repo, object, message, actions, signer)
+    } else {
+        Err(store::Error::UnsupportedType(type_name.clone()))
+    }
+}
@@ -513,6 +513,23 @@ where
         self.raw.get(id)
     }
 
+    /// Get the comments added to an issue since the given history entry, oldest first.
+    pub fn comments_since(
+        &self,
+        id: &ObjectId,
+        since: &EntryId,
+    ) -> Result<Vec<(CommentId, thread::Comment)>, store::Error> {
+        let (issue, entries) = self
+            .raw
+            .since(id, since)?
+            .ok_or_else(|| store::Error::NotFound(TYPENAME.clone(), *id))?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|e| issue.thread.comment(&e).map(|c| (e, c.clone())))
+            .collect())
+    }
+
     /// Get an issue mutably.
     pub fn get_mut<'g>(&'g mut self, id: &ObjectId) -> Result<IssueMut<'a, 'g, R>, store::Error> {
         let issue = self
@@ -555,7 +572,7 @@ where
 
     /// Issues count by state.
     pub fn counts(&self) -> Result<IssueCounts, Error> {
-        let all = self.all()?;
+        let all = self.all_unverified()?;
         let state_groups =
             all.filter_map(|s| s.ok())
                 .fold(IssueCounts::default(), |mut state, (_, p)| {
@@ -643,6 +660,7 @@ mod test {
 
     use super::*;
     use crate::cob::{ActorId, Reaction};
+    use crate::crypto::test::signer::{Forger, MockSigner};
     use crate::git::Oid;
     use crate::test;
     use crate::test::arbitrary;
@@ -1111,6 +1129,84 @@ mod test {
         assert_eq!(c2.author(), author);
     }
 
+    #[test]
+    fn test_issue_forged_comment() {
+        let test::setup::NodeWithRepo { node, repo, .. } = test::setup::NodeWithRepo::default();
+        let forger = Forger::new(*node.signer.public_key(), MockSigner::default());
+        let mut issues = Issues::open(&*repo).unwrap();
+        let mut issue = issues
+            .create(
+                "My first issue",
+                "Blah blah blah.",
+                &[],
+                &[],
+                [],
+                &node.signer,
+            )
+            .unwrap();
+        let (c0, _) = issue.root();
+        let c0 = *c0;
+        let forged = issue.comment("Ho ho ho.", c0, vec![], &forger).unwrap();
+
+        let id = issue.id;
+        let (issue, rejected) = issues.get_verified(&id).unwrap().unwrap();
+        assert_eq!(issue.comments().count(), 1);
+        assert_eq!(
+            rejected,
+            vec![cob::Rejected {
+                id: forged,
+                author: *node.signer.public_key(),
+            }]
+        );
+        // Counts don't verify entries.
+        assert_eq!(issues.counts().unwrap().open, 1);
+    }
+
+    #[test]
+    fn test_issue_comments_since() {
+        let test::setup::NodeWithRepo { node, repo, .. } = test::setup::NodeWithRepo::default();
+        let mut issues = Issues::open(&*repo).unwrap();
+        let mut issue = issues
+            .create(
+                "My first issue",
+                "Blah blah blah.",
+                &[],
+                &[],
+                [],
+                &node.signer,
+            )
+            .unwrap();
+        let id = issue.id;
+        let (c0, _) = issue.root();
+        let c0 = *c0;
+
+        let c1 = issue
+            .comment("Ho ho ho.", c0, vec![], &node.signer)
+            .unwrap();
+        let e1 = issue.edit("My first edited issue", &node.signer).unwrap();
+        let c2 = issue
+            .comment("Ha ha ha.", c1, vec![], &node.signer)
+            .unwrap();
+
+        let comments = issues.comments_since(&id, &c0).unwrap();
+        assert_eq!(
+            comments.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![c1, c2]
+        );
+        assert_eq!(comments[0].1.body(), "Ho ho ho.");
+
+        let comments = issues.comments_since(&id, &e1).unwrap();
+        assert_eq!(
+            comments.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![c2]
+        );
+        assert!(issues.comments_since(&id, &c2).unwrap().is_empty());
+        assert!(matches!(
+            issues.comments_since(&id, &arbitrary::oid()),
+            Err(store::Error::History(cob::DiffError::NotFound(_)))
+        ));
+    }
+
     #[test]
     fn test_issue_state_serde() {
         assert_eq!(
@@ -1619,7 +1619,7 @@ where
 
     /// Patches count by state.
     pub fn counts(&self) -> Result<PatchCounts, store::Error> {
-        let all = self.all()?;
+        let all = self.all_unverified()?;
         let state_groups =
             all.filter_map(|s| s.ok())
                 .fold(PatchCounts::default(), |mut state, (_, p)| {
@@ -1658,6 +1658,23 @@ where
         self.raw.get(id)
     }
 
+    /// Get the revisions added to a patch since the given history entry, oldest first.
+    pub fn revisions_since(
+        &self,
+        id: &ObjectId,
+        since: &EntryId,
+    ) -> Result<Vec<(RevisionId, Revision)>, store::Error> {
+        let (patch, entries) = self
+            .raw
+            .since(id, since)?
+            .ok_or_else(|| store::Error::NotFound(TYPENAME.clone(), *id))?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|e| patch.revision(&e).map(|r| (e, r.clone())))
+            .collect())
+    }
+
     /// Get proposed patches.
     pub fn proposed(&self) -> Result<impl Iterator<Item = (PatchId, Patch)> + '_, Error> {
         let all = self.all()?;
@@ -2193,6 +2210,46 @@ mod test {
         assert_eq!(revision.description(), "I've made changes.");
     }
 
+    #[test]
+    fn test_patch_revisions_since() {
+        let alice = test::setup::NodeWithRepo::default();
+        let checkout = alice.repo.checkout();
+        let branch = checkout.branch_with([("README", b"Hello World!")]);
+        let mut patches = Patches::open(&*alice.repo).unwrap();
+        let mut patch = patches
+            .create(
+                "My first patch",
+                "Blah blah blah.",
+                MergeTarget::Delegates,
+                branch.base,
+                branch.oid,
+                &[],
+                &alice.signer,
+            )
+            .unwrap();
+        let id = patch.id;
+        let r0 = RevisionId::from(*id);
+
+        let update = checkout.branch_with([("README", b"Hello Radicle!")]);
+        let r1 = patch
+            .update("I've made changes.", branch.base, update.oid, &alice.signer)
+            .unwrap();
+        let update = checkout.branch_with([("README", b"Hello Radicle, again!")]);
+        let r2 = patch
+            .update("More changes.", branch.base, update.oid, &alice.signer)
+            .unwrap();
+
+        let revisions = patches.revisions_since(&id, &r0).unwrap();
+        assert_eq!(
+            revisions.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![r1, r2]
+        );
+        assert_eq!(revisions[1].1.description(), "More changes.");
+
+        let revisions = patches.revisions_since(&id, &r2).unwrap();
+        assert!(revisions.is_empty());
+    }
+
     #[test]
     fn test_patch_redact() {
         let alice = test::setup::Node::default();
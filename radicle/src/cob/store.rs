@@ -11,7 +11,8 @@ use serde::{Deserialize, Serialize};
 use crate::cob::common::Timestamp;
 use crate::cob::op::Op;
 use crate::cob::{
-    ActorId, Create, Embed, EntryId, History, ObjectId, TypeName, Update, Updated, Version,
+    ActorId, Create, Embed, EntryId, History, ObjectId, Rejected, TypeName, Update, Updated,
+    Verification, Version,
 };
 use crate::git;
 use crate::prelude::*;
@@ -109,8 +110,16 @@ pub enum Error {
     Identity(#[from] identity::IdentityError),
     #[error(transparent)]
     Serialize(#[from] serde_json::Error),
+    #[error("invalid action: {0}")]
+    InvalidAction(serde_json::Error),
+    #[error("at least one action is required")]
+    NoActions,
+    #[error("objects of type `{0}` can't be updated from actions")]
+    UnsupportedType(TypeName),
     #[error("object `{1}` of type `{0}` was not found")]
     NotFound(TypeName, ObjectId),
+    #[error("history: {0}")]
+    History(#[from] cob::DiffError),
     #[error("apply: {0}")]
     Apply(Arc<dyn std::error::Error + Sync + Send + 'static>),
     #[error("signed refs: {0}")]
@@ -253,22 +262,65 @@ where
     T: FromHistory,
     T::Action: Serialize,
 {
-    /// Get an object.
+    /// Get an object. History entries that aren't signed by their author are excluded.
     pub fn get(&self, id: &ObjectId) -> Result<Option<T>, Error> {
-        let cob = cob::get(self.repo, T::type_name(), id)?;
+        Ok(self.get_verified(id)?.map(|(obj, _)| obj))
+    }
+
+    /// Get an object, along with the history entries that were excluded from it because
+    /// they aren't signed by their author.
+    pub fn get_verified(&self, id: &ObjectId) -> Result<Option<(T, Vec<Rejected>)>, Error> {
+        let cob = cob::get(self.repo, T::type_name(), id, Verification::Verify)?;
 
         if let Some(cob) = cob {
             let obj = T::from_history(cob.history(), self.repo).map_err(Error::apply)?;
 
-            Ok(Some(obj))
+            Ok(Some((obj, cob.rejected().to_vec())))
         } else {
             Ok(None)
         }
     }
 
+    /// Get an object, along with the ids of the history entries added since the given entry,
+    /// in topological order. See [`History::since`].
+    pub fn since(
+        &self,
+        id: &ObjectId,
+        entry: &EntryId,
+    ) -> Result<Option<(T, Vec<EntryId>)>, Error> {
+        let Some(cob) = cob::get(self.repo, T::type_name(), id, Verification::Verify)? else {
+            return Ok(None);
+        };
+        let obj = T::from_history(cob.history(), self.repo).map_err(Error::apply)?;
+        let entries = cob
+            .history()
+            .since(entry)?
+            .into_iter()
+            .map(|e| *e.id())
+            .collect();
+
+        Ok(Some((obj, entries)))
+    }
+
     /// Return all objects.
     pub fn all(&self) -> Result<impl Iterator<Item = Result<(ObjectId, T), Error>> + 'a, Error> {
-        let raw = cob::list(self.repo, T::type_name())?;
+        self.list(Verification::Verify)
+    }
+
+    /// Return all objects, without verifying that history entries are signed by their
+    /// author. This is faster, but should only be used for listings where forged entries
+    /// don't matter.
+    pub fn all_unverified(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(ObjectId, T), Error>> + 'a, Error> {
+        self.list(Verification::Skip)
+    }
+
+    fn list(
+        &self,
+        verification: Verification,
+    ) -> Result<impl Iterator<Item = Result<(ObjectId, T), Error>> + 'a, Error> {
+        let raw = cob::list(self.repo, T::type_name(), verification)?;
 
         Ok(raw.into_iter().map(|o| {
             let obj = T::from_history(o.history(), self.repo).map_err(Error::apply)?;
@@ -283,7 +335,7 @@ where
 
     /// Return objects count.
     pub fn count(&self) -> Result<usize, Error> {
-        let raw = cob::list(self.repo, T::type_name())?;
+        let raw = cob::list(self.repo, T::type_name(), Verification::Skip)?;
 
         Ok(raw.len())
     }
@@ -392,7 +444,7 @@ pub fn ops<R: cob::Store>(
     type_name: &TypeName,
     repo: &R,
 ) -> Result<Vec<Op<Vec<u8>>>, Error> {
-    let cob = cob::get(repo, type_name, id)?;
+    let cob = cob::get(repo, type_name, id, Verification::Verify)?;
 
     if let Some(cob) = cob {
         let ops = cob.history().traverse(Vec::new(), |mut ops, _, entry| {
@@ -405,6 +457,68 @@ pub fn ops<R: cob::Store>(
     }
 }
 
+/// Create an object, or update an existing one, from actions encoded as JSON, eg. by a
+/// client that doesn't know the concrete object type. Actions are decoded, and applied to
+/// the object in memory, before anything is written, so that actions that don't match the
+/// schema of `T`, or that can't be applied to the object, are rejected.
+///
+/// Returns the object id and the id of the new entry.
+pub fn update_json<T, R, G>(
+    repo: &R,
+    object: Option<ObjectId>,
+    message: &str,
+    actions: Vec<serde_json::Value>,
+    signer: &G,
+) -> Result<(ObjectId, EntryId), Error>
+where
+    T: FromHistory,
+    T::Action: Clone,
+    R: ReadRepository + SignRepository + cob::Store,
+    G: Signer,
+{
+    let actions = actions
+        .into_iter()
+        .map(serde_json::from_value::<T::Action>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::InvalidAction)?;
+    let actions = NonEmpty::from_vec(actions).ok_or(Error::NoActions)?;
+    let store = Store::<T, R>::open(repo)?;
+    let mut state = match object {
+        Some(id) => store
+            .get(&id)?
+            .ok_or_else(|| Error::NotFound(T::type_name().clone(), id))?,
+        None => T::default(),
+    };
+    // Nb. The entry id isn't known until the entry is written, so a placeholder is used
+    // to try the actions out.
+    let op = Op {
+        id: git::raw::Oid::zero().into(),
+        actions,
+        author: *signer.public_key(),
+        timestamp: Timestamp::now(),
+        parents: Vec::new(),
+        identity: store.identity,
+        manifest: cob::Manifest::new(T::type_name().clone(), Version::default()),
+    };
+    let actions = op.actions.clone();
+
+    state.apply(op, repo).map_err(Error::apply)?;
+    state.validate().map_err(Error::apply)?;
+
+    match object {
+        Some(id) => {
+            let updated = store.update(id, message, actions, Vec::new(), signer)?;
+
+            Ok((id, updated.head))
+        }
+        None => {
+            let (id, _) = store.create(message, actions, Vec::new(), signer)?;
+
+            Ok((id, *id))
+        }
+    }
+}
+
 pub mod encoding {
     use serde::Serialize;
 
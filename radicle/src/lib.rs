@@ -8,6 +8,7 @@ pub extern crate radicle_crypto as crypto;
 extern crate amplify;
 extern crate radicle_git_ext as git_ext;
 
+pub mod api;
 mod canonical;
 pub mod cob;
 pub mod collections;
@@ -1,18 +1,23 @@
 mod features;
 
+pub mod activity;
 pub mod address;
 pub mod config;
+pub mod control;
 pub mod events;
 pub mod routing;
+#[cfg(any(test, feature = "test"))]
+pub mod test;
 pub mod tracking;
+pub mod watermark;
 
-use std::collections::{BTreeSet, HashMap, HashSet};
-use std::io::{BufRead, BufReader};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write as _};
+use std::iter;
 use std::ops::Deref;
-use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::{fmt, io, net, thread, time};
+use std::{fmt, io, mem, net, thread, time};
 
 use amplify::WrapperMut;
 use cyphernet::addr::NetAddr;
@@ -21,10 +26,14 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json as json;
 
-use crate::crypto::PublicKey;
-use crate::identity::Id;
+use crate::cob::object::ParseObjectId;
+use crate::cob::{EntryId, ObjectId, TypeName};
+use crate::crypto::{PublicKey, Verified};
+use crate::git::Oid;
+use crate::identity::{Doc, Id};
 use crate::profile;
-use crate::storage::RefUpdate;
+use crate::storage::git::stats::RepoStats;
+use crate::storage::{Finding, RefUpdate};
 
 pub use address::KnownAddress;
 pub use config::Config;
@@ -38,6 +47,14 @@ pub const DEFAULT_SOCKET_NAME: &str = "control.sock";
 pub const DEFAULT_PORT: u16 = 8776;
 /// Default timeout when waiting for the node to respond with data.
 pub const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(9);
+/// Maximum time to wait for the next finding when validating repositories.
+pub const VALIDATE_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+/// Maximum time to wait for the node to compute repository statistics.
+pub const REPO_SIZE_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+/// Maximum time to wait for the next progress line when syncing all repositories.
+pub const SYNC_ALL_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+/// Default number of repositories synced in parallel by [`Handle::sync_all`].
+pub const DEFAULT_SYNC_PARALLELISM: usize = 4;
 /// Maximum length in bytes of a node alias.
 pub const MAX_ALIAS_LENGTH: usize = 32;
 /// Filename of routing table database under the node directory.
@@ -46,6 +63,15 @@ pub const ROUTING_DB_FILE: &str = "routing.db";
 pub const ADDRESS_DB_FILE: &str = "addresses.db";
 /// Filename of tracking table database under the node directory.
 pub const TRACKING_DB_FILE: &str = "tracking.db";
+/// Filename of the event log database under the node directory.
+pub const EVENTS_DB_FILE: &str = "events.db";
+/// Filename of the sync watermark database under the node directory.
+pub const WATERMARK_DB_FILE: &str = "watermarks.db";
+/// Filename of the marker written once the node directory is fully initialized.
+pub const NODE_INITIALIZED_FILE: &str = "initialized";
+/// Filename of the marker present while the node is running. If it's found on startup,
+/// the node didn't shut down cleanly.
+pub const NODE_RUNNING_FILE: &str = "running";
 /// Filename of last node announcement, when running in debug mode.
 #[cfg(debug_assertions)]
 pub const NODE_ANNOUNCEMENT_FILE: &str = "announcement.wire.debug";
@@ -120,7 +146,12 @@ impl fmt::Display for State {
 }
 
 /// Node alias.
-#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+///
+/// Aliases are validated when parsed from user input. When decoded, eg. from an existing
+/// configuration file, invalid aliases are made valid with [`Alias::from_lossy`] instead,
+/// so that aliases accepted by earlier versions keep working.
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize)]
+#[serde(into = "String")]
 pub struct Alias(String);
 
 impl Alias {
@@ -133,6 +164,48 @@ impl Alias {
             Err(e) => panic!("Alias::new: {e}"),
         }
     }
+
+    /// Create an alias from a string that may not be a valid alias, by dropping any
+    /// whitespace or control characters, and truncating it to the maximum alias length.
+    /// Returns `None` if nothing is left of the string.
+    pub fn from_lossy(s: &str) -> Option<Self> {
+        let mut alias = String::new();
+
+        for c in s.chars().filter(|c| !c.is_control() && !c.is_whitespace()) {
+            if alias.len() + c.len_utf8() > MAX_ALIAS_LENGTH {
+                break;
+            }
+            alias.push(c);
+        }
+        Self::from_str(&alias).ok()
+    }
+}
+
+impl<'de> Deserialize<'de> for Alias {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+
+        match Self::from_str(&s) {
+            Ok(alias) => Ok(alias),
+            Err(err) => {
+                let alias = Self::from_lossy(&s).ok_or_else(|| serde::de::Error::custom(&err))?;
+                log::warn!(target: "node", "Invalid alias {s:?} ({err}), using {alias:?} instead");
+
+                Ok(alias)
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Alias {
+    type Error = AliasError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_str(&value)
+    }
 }
 
 impl From<Alias> for String {
@@ -167,12 +240,19 @@ impl AsRef<str> for Alias {
     }
 }
 
-impl From<&Alias> for [u8; 32] {
-    fn from(input: &Alias) -> [u8; 32] {
+impl TryFrom<&Alias> for [u8; 32] {
+    type Error = AliasError;
+
+    fn try_from(input: &Alias) -> Result<[u8; 32], Self::Error> {
         let mut alias = [0u8; 32];
+        let bytes = input.as_bytes();
 
-        alias[..input.len()].copy_from_slice(input.as_bytes());
         alias
+            .get_mut(..bytes.len())
+            .ok_or(AliasError::MaxBytesExceeded)?
+            .copy_from_slice(bytes);
+
+        Ok(alias)
     }
 }
 
@@ -204,7 +284,7 @@ impl FromStr for Alias {
 }
 
 /// Options passed to the "connect" node command.
-#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct ConnectOptions {
     /// Establish a persistent connection.
     pub persistent: bool,
@@ -227,9 +307,69 @@ pub enum CommandResult {
     Error {
         /// The reason for the error.
         reason: String,
+        /// The kind of error, for clients to act on.
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        code: ErrorCode,
     },
 }
 
+/// Error code of a failed command, on the node control socket.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCode {
+    /// The command failed while being carried out.
+    #[default]
+    Internal,
+    /// The request is not a valid command.
+    InvalidRequest,
+    /// The command is not known to the node.
+    UnknownCommand,
+    /// A command argument is missing, unexpected or invalid.
+    InvalidArgument,
+    /// A repository id argument is invalid.
+    InvalidRid,
+    /// A node id argument is invalid.
+    InvalidNid,
+    /// A tracking scope argument is invalid.
+    InvalidScope,
+    /// A node address argument is invalid.
+    InvalidAddress,
+    /// A node alias argument is invalid.
+    InvalidAlias,
+    /// The repository was not found in storage.
+    NotFound,
+    /// The client is not allowed to issue commands.
+    Unauthorized,
+    /// There is nothing to announce, eg. because we have no refs in the repository.
+    NothingToAnnounce,
+    /// Too many fetches are in progress to accept another one.
+    TooManyFetches,
+    /// The repository is not tracked, eg. when asked to fetch it.
+    NotTracked,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            Self::Internal => "internal",
+            Self::InvalidRequest => "invalid-request",
+            Self::UnknownCommand => "unknown-command",
+            Self::InvalidArgument => "invalid-argument",
+            Self::InvalidRid => "invalid-rid",
+            Self::InvalidNid => "invalid-nid",
+            Self::InvalidScope => "invalid-scope",
+            Self::InvalidAddress => "invalid-address",
+            Self::InvalidAlias => "invalid-alias",
+            Self::NotFound => "not-found",
+            Self::Unauthorized => "unauthorized",
+            Self::NothingToAnnounce => "nothing-to-announce",
+            Self::TooManyFetches => "too-many-fetches",
+            Self::NotTracked => "not-tracked",
+        };
+        f.write_str(code)
+    }
+}
+
 impl CommandResult {
     /// Create an "updated" response.
     pub fn updated() -> Self {
@@ -245,6 +385,15 @@ impl CommandResult {
     pub fn error(err: impl std::error::Error) -> Self {
         Self::Error {
             reason: err.to_string(),
+            code: ErrorCode::default(),
+        }
+    }
+
+    /// Create an error result with the given code.
+    pub fn error_code(code: ErrorCode, err: impl std::error::Error) -> Self {
+        Self::Error {
+            reason: err.to_string(),
+            code,
         }
     }
 
@@ -259,7 +408,7 @@ impl From<CommandResult> for Result<bool, Error> {
     fn from(value: CommandResult) -> Self {
         match value {
             CommandResult::Okay { updated } => Ok(updated),
-            CommandResult::Error { reason } => Err(Error::Node(reason)),
+            CommandResult::Error { reason, code } => Err(Error::Node { code, reason }),
         }
     }
 }
@@ -295,6 +444,35 @@ impl Address {
             _ => true,
         }
     }
+
+    /// Check whether this address can be dialed. Addresses that can never be reached,
+    /// eg. multicast addresses, are rejected, as are our own addresses. Loopback and
+    /// private addresses are only rejected if the context says so.
+    pub fn validate(&self, ctx: &address::Context) -> Result<(), address::AddressError> {
+        use address::AddressError;
+
+        if self.0.port == 0 {
+            return Err(AddressError::PortZero(self.clone()));
+        }
+        if ctx.own.contains(self) {
+            return Err(AddressError::Own(self.clone()));
+        }
+        if let HostName::Ip(ip) = self.0.host {
+            if ip.is_unspecified() {
+                return Err(AddressError::Unspecified(self.clone()));
+            }
+            if ip.is_multicast() {
+                return Err(AddressError::Multicast(self.clone()));
+            }
+            if matches!(ip, net::IpAddr::V4(ip) if ip.is_broadcast()) {
+                return Err(AddressError::Broadcast(self.clone()));
+            }
+            if ctx.reject_local && (ip.is_loopback() || address::is_local(&ip)) {
+                return Err(AddressError::Local(self.clone()));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl cyphernet::addr::Host for Address {
@@ -324,8 +502,13 @@ impl From<Address> for HostName {
     }
 }
 
-/// Command name.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Command sent to the node, on the control socket.
+///
+/// Commands are sent as JSON objects tagged with the command `type`, eg.
+/// `{"type":"fetch","rid":"rad:...","nid":"z6Mk..."}`. For backwards compatibility, the node
+/// also accepts the legacy positional form, eg. `{"cmd":"fetch","args":["rad:...","z6Mk..."]}`.
+/// See [`Command::parse`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Command {
     /// Announce repository references for given repository to peers.
@@ -343,6 +526,7 @@ pub enum Command {
     #[serde(rename_all = "camelCase")]
     Connect {
         addr: config::ConnectAddress,
+        #[serde(default)]
         opts: ConnectOptions,
     },
 
@@ -353,18 +537,51 @@ pub enum Command {
     /// Get the current peer sessions.
     Sessions,
 
-    /// Fetch the given repository from the network.
+    /// Fetch the given repository from the network. In `minimal` mode, only the
+    /// identity branch and the namespaces of the delegates are fetched, which is enough
+    /// to compute the canonical head. The repository is then marked as partially
+    /// replicated, and the next regular fetch completes it.
     #[serde(rename_all = "camelCase")]
-    Fetch { rid: Id, nid: NodeId },
+    Fetch {
+        rid: Id,
+        nid: NodeId,
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        minimal: bool,
+    },
 
-    /// Track the given repository.
+    /// Track the given repository. In dry-run mode, the tracking policy isn't changed,
+    /// and a [`TrackDryRun`] summary of what would be fetched is returned instead.
+    /// With `fetch`, the repository is fetched from known seeds right away, and a
+    /// [`Tracked`] result is returned. The `origin` records why the repository is in our
+    /// inventory, eg. because it was created locally. If a `filter` is given, it replaces
+    /// the repository's ref filter, before anything is fetched.
     #[serde(rename_all = "camelCase")]
-    TrackRepo { rid: Id, scope: tracking::Scope },
+    TrackRepo {
+        rid: Id,
+        #[serde(default)]
+        scope: tracking::Scope,
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        dry_run: bool,
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        fetch: bool,
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        origin: tracking::Origin,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<tracking::RefFilter>,
+    },
 
     /// Untrack the given repository.
     #[serde(rename_all = "camelCase")]
     UntrackRepo { rid: Id },
 
+    /// Watch the given repository, ie. subscribe to its announcements without fetching it.
+    #[serde(rename_all = "camelCase")]
+    WatchRepo { rid: Id },
+
+    /// Stop watching the given repository.
+    #[serde(rename_all = "camelCase")]
+    UnwatchRepo { rid: Id },
+
     /// Track the given node.
     #[serde(rename_all = "camelCase")]
     TrackNode { nid: NodeId, alias: Option<Alias> },
@@ -373,6 +590,13 @@ pub enum Command {
     #[serde(rename_all = "camelCase")]
     UntrackNode { nid: NodeId },
 
+    /// Get all tracking policies.
+    ExportPolicies,
+
+    /// Import the given tracking policies.
+    #[serde(rename_all = "camelCase")]
+    ImportPolicies { policies: tracking::Policies },
+
     /// Get the node's status.
     Status,
 
@@ -384,6 +608,93 @@ pub enum Command {
 
     /// Subscribe to events.
     Subscribe,
+
+    /// Validate the given repository in storage, or all repositories if none is given.
+    #[serde(rename_all = "camelCase")]
+    ValidateRepo { rid: Option<Id> },
+
+    /// Get the size and object statistics of the given repository, or of all repositories
+    /// if none is given. Statistics are cached, and may be stale, unless a refresh is
+    /// requested.
+    #[serde(rename_all = "camelCase")]
+    RepoSize {
+        rid: Option<Id>,
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        refresh: bool,
+    },
+
+    /// Get the size of the node's gossip store.
+    GossipStats,
+
+    /// Get the node's service metrics, as a flat map of counters.
+    Metrics,
+
+    /// Get the repairs made on startup, after an unclean shutdown or database corruption.
+    Recovery,
+
+    /// Explain why the routing table says the given node seeds the given repository.
+    #[serde(rename_all = "camelCase")]
+    Explain { rid: Id, nid: NodeId },
+
+    /// Get the recent fetches of the given repository.
+    #[serde(rename_all = "camelCase")]
+    FetchHistory { rid: Id },
+
+    /// Get the logged events following the given sequence number.
+    #[serde(rename_all = "camelCase")]
+    EventsSince {
+        #[serde(default)]
+        seq: u64,
+    },
+
+    /// Get the node's recent activity, recorded at or after the given time, if any.
+    /// An [`activity::Entry`] line is returned per activity, oldest first. If a limit is
+    /// given, only the most recent entries are returned.
+    #[serde(rename_all = "camelCase")]
+    Activity {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<Timestamp>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<usize>,
+    },
+
+    /// Get the verified identity document of the given repository, from storage if we
+    /// have it, or from a connected seed otherwise.
+    #[serde(rename_all = "camelCase")]
+    Inspect { rid: Id },
+
+    /// Get the node's effective configuration.
+    GetConfig,
+
+    /// Change one of the [`config::Limits::RUNTIME`] limits. The new value applies to
+    /// sessions established from then on.
+    #[serde(rename_all = "camelCase")]
+    SetLimit { name: String, value: json::Value },
+
+    /// Add a peer to the node's configuration, in memory and in the configuration file,
+    /// replacing its previous entry.
+    #[serde(rename_all = "camelCase")]
+    AddPeer { peer: config::PeerEntry },
+
+    /// Remove a peer from the node's configuration, in memory and in the configuration file.
+    #[serde(rename_all = "camelCase")]
+    RemovePeer { nid: NodeId },
+
+    /// Announce and/or fetch all tracked repositories. A [`SyncProgress`] line is
+    /// returned per repository and action, followed by a summary.
+    #[serde(rename_all = "camelCase")]
+    SyncAll {
+        #[serde(flatten)]
+        options: SyncAllOptions,
+    },
+
+    /// Create or update a collaborative object, signing the change with the node's key,
+    /// and announce our refs. Requires [`control::Permission::Operator`].
+    #[serde(rename_all = "camelCase")]
+    CobUpdate {
+        #[serde(flatten)]
+        update: CobUpdate,
+    },
 }
 
 impl Command {
@@ -392,6 +703,214 @@ impl Command {
         json::to_writer(&mut w, self).map_err(|_| io::ErrorKind::InvalidInput)?;
         w.write_all(b"\n")
     }
+
+    /// Parse a command received on the control socket, in either the tagged JSON form,
+    /// or the legacy positional form.
+    pub fn parse(input: &str) -> Result<Self, RequestError> {
+        let value = json::from_str::<json::Value>(input)
+            .map_err(|e| RequestError::new(ErrorCode::InvalidRequest, e))?;
+        let value = match json::from_value::<LegacyCommand>(value.clone()) {
+            Ok(legacy) => legacy.into_tagged()?,
+            Err(_) => value,
+        };
+        let Some(kind) = value.get("type").and_then(|t| t.as_str()) else {
+            return Err(RequestError::new(
+                ErrorCode::InvalidRequest,
+                "missing command `type`",
+            ));
+        };
+        if Self::params(kind).is_none() {
+            return Err(RequestError::new(
+                ErrorCode::UnknownCommand,
+                format!("unknown command `{kind}`"),
+            ));
+        }
+        // Aliases are decoded leniently, but new ones must be valid.
+        if let Some(alias) = value.get("alias").and_then(|a| a.as_str()) {
+            Alias::from_str(alias).map_err(|e| RequestError::new(ErrorCode::InvalidAlias, e))?;
+        }
+        let cmd: Self = json::from_value(value.clone()).map_err(|e| {
+            // Find out which argument is invalid, if any, to return a more specific code.
+            let code = [
+                ("rid", ErrorCode::InvalidRid),
+                ("nid", ErrorCode::InvalidNid),
+                ("scope", ErrorCode::InvalidScope),
+                ("addr", ErrorCode::InvalidAddress),
+            ]
+            .into_iter()
+            .find_map(|(param, code)| {
+                let arg = value.get(param).filter(|v| !v.is_null())?.clone();
+                let valid = match param {
+                    "rid" => json::from_value::<Id>(arg).is_ok(),
+                    "nid" => json::from_value::<NodeId>(arg).is_ok(),
+                    "scope" => json::from_value::<tracking::Scope>(arg).is_ok(),
+                    _ => json::from_value::<config::ConnectAddress>(arg).is_ok(),
+                };
+                (!valid).then_some(code)
+            })
+            .unwrap_or(ErrorCode::InvalidArgument);
+
+            RequestError::new(code, e)
+        })?;
+        // Addresses that can never be dialed are rejected here. Those that depend on the
+        // node's configuration, eg. its own addresses, are rejected by the node.
+        if let Self::Connect { addr, .. } = &cmd {
+            addr.addr
+                .validate(&address::Context::default())
+                .map_err(|e| RequestError::new(ErrorCode::InvalidAddress, e))?;
+        }
+        if let Self::RepoSize {
+            rid: None,
+            refresh: true,
+        } = &cmd
+        {
+            return Err(RequestError::new(
+                ErrorCode::InvalidArgument,
+                "a refresh can only be requested for a single repository",
+            ));
+        }
+        Ok(cmd)
+    }
+
+    /// The permission level a control client needs to issue this command.
+    pub fn permission(&self) -> control::Permission {
+        match self {
+            Self::CobUpdate { .. } => control::Permission::Operator,
+            _ => control::Permission::User,
+        }
+    }
+
+    /// Check that a control client with the given permission level can issue this command.
+    pub fn authorize(&self, granted: control::Permission) -> Result<(), RequestError> {
+        let required = self.permission();
+        if granted < required {
+            return Err(RequestError::new(
+                ErrorCode::Unauthorized,
+                format!("{required} permission is required, but only {granted} was granted"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The positional parameters of the command of the given type, as accepted in the
+    /// legacy command form. Returns `None` if the command is unknown.
+    fn params(kind: &str) -> Option<&'static [&'static str]> {
+        let params: &[&str] = match kind {
+            "announceRefs" => &["rid"],
+            "announceInventory" => &[],
+            "syncInventory" => &[],
+            "connect" => &["addr"],
+            "seeds" => &["rid"],
+            "sessions" => &[],
+            "fetch" => &["rid", "nid"],
+            "trackRepo" => &["rid", "scope"],
+            "untrackRepo" => &["rid"],
+            "watchRepo" => &["rid"],
+            "unwatchRepo" => &["rid"],
+            "trackNode" => &["nid", "alias"],
+            "untrackNode" => &["nid"],
+            "exportPolicies" => &[],
+            "importPolicies" => &[],
+            "status" => &[],
+            "nodeId" => &[],
+            "shutdown" => &[],
+            "subscribe" => &[],
+            "validateRepo" => &["rid"],
+            "repoSize" => &["rid"],
+            "gossipStats" => &[],
+            "metrics" => &[],
+            "recovery" => &[],
+            "explain" => &["rid", "nid"],
+            "fetchHistory" => &["rid"],
+            "eventsSince" => &["seq"],
+            "activity" => &["since", "limit"],
+            "inspect" => &["rid"],
+            "getConfig" => &[],
+            "setLimit" => &["name", "value"],
+            "addPeer" => &[],
+            "removePeer" => &["nid"],
+            "syncAll" => &[],
+            "cobUpdate" => &[],
+            _ => return None,
+        };
+        Some(params)
+    }
+}
+
+/// Command in the legacy form, with a kebab-case command name, and positional arguments,
+/// eg. `{"cmd":"track-repo","args":["rad:...","all"]}`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegacyCommand {
+    cmd: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl LegacyCommand {
+    /// Convert into the tagged command form.
+    fn into_tagged(self) -> Result<json::Value, RequestError> {
+        // Eg. `track-repo` becomes `trackRepo`.
+        let mut words = self.cmd.split('-');
+        let mut kind = words.next().unwrap_or_default().to_owned();
+        for word in words {
+            let mut chars = word.chars();
+            kind.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+            kind.extend(chars);
+        }
+        let Some(params) = Command::params(&kind) else {
+            return Err(RequestError::new(
+                ErrorCode::UnknownCommand,
+                format!("unknown command `{}`", self.cmd),
+            ));
+        };
+        if self.args.len() > params.len() {
+            return Err(RequestError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "command `{}` takes at most {} argument(s), {} given",
+                    self.cmd,
+                    params.len(),
+                    self.args.len()
+                ),
+            ));
+        }
+        let mut object = json::Map::new();
+        object.insert("type".to_owned(), json::Value::String(kind));
+
+        for (param, arg) in params.iter().zip(self.args) {
+            object.insert((*param).to_owned(), json::Value::String(arg));
+        }
+        Ok(json::Value::Object(object))
+    }
+}
+
+/// Error parsing a command received on the control socket.
+#[derive(thiserror::Error, Debug)]
+#[error("{reason}")]
+pub struct RequestError {
+    /// Error code.
+    pub code: ErrorCode,
+    /// The reason for the error.
+    pub reason: String,
+}
+
+impl RequestError {
+    fn new(code: ErrorCode, reason: impl ToString) -> Self {
+        Self {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl From<RequestError> for CommandResult {
+    fn from(err: RequestError) -> Self {
+        Self::Error {
+            reason: err.reason,
+            code: err.code,
+        }
+    }
 }
 
 /// An established network connection with a peer.
@@ -400,6 +919,36 @@ pub struct Session {
     pub nid: NodeId,
     pub addr: Address,
     pub state: State,
+    /// Protocol version spoken by the peer, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    /// Queued fetches, in the order they will be carried out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queue: Vec<Id>,
+    /// Gossip bytes sent to the peer during this session.
+    #[serde(default)]
+    pub sent: u64,
+    /// Gossip bytes received from the peer during this session.
+    #[serde(default)]
+    pub received: u64,
+    /// Round-trip time to the peer, in milliseconds, as measured by pings, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt: Option<u64>,
+    /// How long the peer has to answer a ping before it is disconnected, in milliseconds.
+    /// Only set for connected peers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Whether the peer was disconnected by the user, in which case it isn't reconnected
+    /// to automatically until the disconnect cool-off has passed.
+    #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+    pub user_disconnected: bool,
+    /// Connection attempts since the peer was last connected.
+    #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+    pub attempts: usize,
+    /// If we gave up reconnecting to the peer after too many attempts, the time until
+    /// which we don't try again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gave_up_until: Option<LocalTime>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -408,6 +957,20 @@ pub struct Seed {
     pub nid: NodeId,
     pub addrs: Vec<KnownAddress>,
     pub state: Option<State>,
+    /// Timestamp of the seed's latest refs announcement for the repository, if we have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announced: Option<Timestamp>,
+    /// Whether the seed's latest refs announcement has refs we don't have locally,
+    /// for the namespaces we track.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fresh: Option<bool>,
+    /// Preference rank of the seed, as computed by the node. Seeds with a lower rank are
+    /// returned first, and seeds without a rank are returned last, in random order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    /// Collaborative object types the seed advertized it replicates and serves.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cobs: Vec<TypeName>,
 }
 
 impl Seed {
@@ -416,138 +979,751 @@ impl Seed {
         matches!(self.state, Some(State::Connected { .. }))
     }
 
-    pub fn new(nid: NodeId, addrs: Vec<KnownAddress>, state: Option<State>) -> Self {
-        Self { nid, addrs, state }
+    /// Check if this seed announced refs we don't have.
+    pub fn is_fresh(&self) -> bool {
+        self.fresh == Some(true)
     }
-}
-
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-/// Represents a set of seeds with associated metadata. Uses an RNG
-/// underneath, so every iteration returns a different ordering.
-pub struct Seeds(address::AddressBook<NodeId, Seed>);
 
-impl Seeds {
-    /// Create a new seeds list from an RNG.
-    pub fn new(rng: fastrand::Rng) -> Self {
-        Self(address::AddressBook::new(rng))
+    pub fn new(nid: NodeId, addrs: Vec<KnownAddress>, state: Option<State>) -> Self {
+        Self {
+            nid,
+            addrs,
+            state,
+            announced: None,
+            fresh: None,
+            rank: None,
+            cobs: vec![],
+        }
     }
 
-    /// Insert a seed.
-    pub fn insert(&mut self, seed: Seed) {
-        self.0.insert(seed.nid, seed);
+    /// Check if the seed advertized the given collaborative object type.
+    pub fn supports(&self, cob: &TypeName) -> bool {
+        self.cobs.contains(cob)
     }
 
-    /// Partitions the list of seeds into connected and disconnected seeds.
-    /// Note that the disconnected seeds may be in a "connecting" state.
-    pub fn partition(&self) -> (Vec<Seed>, Vec<Seed>) {
-        self.0
-            .shuffled()
-            .map(|(_, v)| v)
-            .cloned()
-            .partition(|s| s.is_connected())
+    /// Set the collaborative object types the seed advertized.
+    pub fn with_cobs(mut self, cobs: Vec<TypeName>) -> Self {
+        self.cobs = cobs;
+        self
     }
 
-    /// Return connected seeds.
-    pub fn connected(&self) -> impl Iterator<Item = &Seed> {
-        self.0
-            .shuffled()
-            .map(|(_, v)| v)
-            .filter(|s| s.is_connected())
+    /// Set the seed's latest refs announcement timestamp, and whether it is fresh.
+    pub fn with_announced(mut self, timestamp: Timestamp, fresh: bool) -> Self {
+        self.announced = Some(timestamp);
+        self.fresh = Some(fresh);
+        self
     }
 
-    /// Check if a seed is connected.
-    pub fn is_connected(&self, nid: &NodeId) -> bool {
-        self.0.get(nid).map_or(false, |s| s.is_connected())
+    /// Set the seed's preference rank.
+    pub fn with_rank(mut self, rank: usize) -> Self {
+        self.rank = Some(rank);
+        self
     }
+}
 
-    /// Return a new seeds object with the given RNG.
-    pub fn with(self, rng: fastrand::Rng) -> Self {
-        Self(self.0.with(rng))
+/// Summary of what would be fetched if a repository was tracked.
+/// Returned by [`Handle::track_repo_dry_run`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackDryRun {
+    /// The repository.
+    pub rid: Id,
+    /// Seeds of the repository, according to our routing table.
+    pub seeds: Vec<Seed>,
+    /// The connected seed that was asked for its refs, if any.
+    pub seed: Option<NodeId>,
+    /// Number of namespaces that would be fetched, if known.
+    pub namespaces: Option<usize>,
+    /// Number of refs that would be fetched, if known.
+    pub refs: Option<usize>,
+    /// Why the summary is incomplete or approximate, if it is.
+    pub note: Option<String>,
+}
+
+/// Outcome of the fetch started when tracking a repository.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum TrackFetch {
+    /// The repository is being fetched from a connected seed.
+    Started { seed: NodeId },
+    /// The fetch will start once the seed has a free fetch slot, or once we're connected to it.
+    Queued { seed: NodeId },
+    /// No seeds are known for the repository.
+    NoSeeds,
+}
+
+/// Result of tracking a repository and fetching it. Returned by [`Handle::track_repo_fetch`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tracked {
+    /// Whether the tracking policy was updated.
+    pub updated: bool,
+    /// Outcome of the fetch.
+    pub fetch: TrackFetch,
+}
+
+/// Options of [`Handle::sync_all`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SyncAllOptions {
+    /// Fetch every tracked repository from its best connected seed.
+    pub fetch: bool,
+    /// Announce our refs of every tracked repository we have in storage.
+    pub announce: bool,
+    /// Maximum number of repositories synced at the same time. Fetches are also subject
+    /// to the node's per-connection fetch concurrency limit.
+    pub parallelism: usize,
+}
+
+impl Default for SyncAllOptions {
+    fn default() -> Self {
+        Self {
+            fetch: true,
+            announce: true,
+            parallelism: DEFAULT_SYNC_PARALLELISM,
+        }
     }
 }
 
-/// Announcement result returned by [`Node::announce`].
-#[derive(Debug)]
-pub struct AnnounceResult {
-    /// Nodes that timed out.
-    pub timeout: Vec<NodeId>,
-    /// Nodes that synced.
-    pub synced: Vec<NodeId>,
+/// What was done to a repository, when syncing all repositories.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncAction {
+    /// Our refs were announced.
+    Announce,
+    /// The repository was fetched.
+    Fetch,
 }
 
-/// A sync event, emitted by [`Node::announce`].
-#[derive(Debug)]
-pub enum AnnounceEvent {
-    /// Refs were synced with the given node.
-    RefsSynced { remote: NodeId },
-    /// Refs were announced to all given nodes.
-    Announced,
+impl fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Announce => f.write_str("announce"),
+            Self::Fetch => f.write_str("fetch"),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "status", rename_all = "camelCase")]
-pub enum FetchResult {
+/// Result of a [`SyncAction`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum SyncResult {
+    /// The action was carried out. For fetches, the seed fetched from and the number
+    /// of refs updated are included.
     Success {
-        updated: Vec<RefUpdate>,
-        namespaces: HashSet<NodeId>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seed: Option<NodeId>,
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        updated: usize,
     },
-    // TODO: Create enum for reason.
-    Failed {
-        reason: String,
+    /// The action couldn't be carried out, eg. because there are no connected seeds.
+    Skipped { reason: String },
+    /// The action failed.
+    Failed { reason: String },
+}
+
+/// Progress of [`Handle::sync_all`]. One line is streamed per repository and action,
+/// as they complete, followed by a summary.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SyncProgress {
+    /// An action was carried out on a repository.
+    Repo {
+        rid: Id,
+        action: SyncAction,
+        result: SyncResult,
+    },
+    /// All tracked repositories were synced. This is always the last line.
+    Summary {
+        /// Number of tracked repositories.
+        repos: usize,
+        /// Repositories with at least one successful action, and no failed ones.
+        succeeded: usize,
+        /// Repositories for which all actions were skipped.
+        skipped: usize,
+        /// Repositories with at least one failed action.
+        failed: usize,
     },
 }
 
-impl FetchResult {
-    pub fn is_success(&self) -> bool {
-        matches!(self, FetchResult::Success { .. })
+/// Object targeted by a [`CobUpdate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CobObject {
+    /// A new object is created.
+    New,
+    /// An existing object is updated.
+    Existing(ObjectId),
+}
+
+impl From<CobObject> for Option<ObjectId> {
+    fn from(object: CobObject) -> Self {
+        match object {
+            CobObject::New => None,
+            CobObject::Existing(id) => Some(id),
+        }
     }
+}
 
-    pub fn success(self) -> Option<(Vec<RefUpdate>, HashSet<NodeId>)> {
+impl fmt::Display for CobObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Success {
-                updated,
-                namespaces,
-            } => Some((updated, namespaces)),
-            _ => None,
+            Self::New => write!(f, "new"),
+            Self::Existing(id) => write!(f, "{id}"),
         }
     }
 }
 
-impl<S: ToString> From<Result<(Vec<RefUpdate>, HashSet<NodeId>), S>> for FetchResult {
-    fn from(value: Result<(Vec<RefUpdate>, HashSet<NodeId>), S>) -> Self {
-        match value {
-            Ok((updated, namespaces)) => Self::Success {
-                updated,
-                namespaces,
-            },
-            Err(err) => Self::Failed {
-                reason: err.to_string(),
-            },
+impl FromStr for CobObject {
+    type Err = ParseObjectId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(Self::New),
+            _ => s.parse().map(Self::Existing),
         }
     }
 }
 
-/// Holds multiple fetch results.
-#[derive(Debug, Default)]
-pub struct FetchResults(Vec<(NodeId, FetchResult)>);
+/// Change to a collaborative object, applied by the node. See [`Handle::cob_update`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CobUpdate {
+    /// Repository of the object.
+    pub rid: Id,
+    /// Object type, eg. `xyz.radicle.issue`.
+    pub type_name: TypeName,
+    /// Object to update, or `new` to create one.
+    #[serde(with = "crate::serde_ext::string")]
+    pub object: CobObject,
+    /// Message of the change.
+    pub message: String,
+    /// Actions of the change, in the JSON encoding of the object type's actions.
+    pub actions: Vec<json::Value>,
+}
 
-impl FetchResults {
-    /// Push a fetch result.
-    pub fn push(&mut self, nid: NodeId, result: FetchResult) {
-        self.0.push((nid, result));
-    }
+/// Result of a [`CobUpdate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CobUpdated {
+    /// The object id. For new objects, this is the id of the first entry.
+    pub object: ObjectId,
+    /// The id of the new entry.
+    pub entry: EntryId,
+}
 
-    /// Iterate over all fetch results.
-    pub fn iter(&self) -> impl Iterator<Item = (&NodeId, &FetchResult)> {
-        self.0.iter().map(|(nid, r)| (nid, r))
-    }
+/// Where an inspected identity document was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum InspectSource {
+    /// The repository is in local storage.
+    Local,
+    /// The identity branch was fetched from a connected seed, and discarded once verified.
+    Seed { nid: NodeId },
+}
 
-    /// Iterate over successful fetches.
-    pub fn success(&self) -> impl Iterator<Item = (&NodeId, &[RefUpdate], HashSet<NodeId>)> {
+/// The verified identity of a repository. Returned by [`Handle::inspect`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inspection {
+    /// The repository.
+    pub rid: Id,
+    /// Where the identity was read from.
+    pub source: InspectSource,
+    /// Head of the identity branch.
+    pub head: Oid,
+    /// The identity document at the head of the identity branch.
+    pub doc: Doc<Verified>,
+}
+
+/// Size of the node's gossip store, and gossip traffic.
+/// Returned by [`Handle::gossip_stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GossipStats {
+    /// Number of announcers we keep announcements of.
+    pub nodes: usize,
+    /// Number of refs announcements kept, across all announcers.
+    pub refs: usize,
+    /// Number of third-party announcements that weren't relayed, to stay within the
+    /// relay rate.
+    #[serde(default)]
+    pub relays_throttled: usize,
+    /// Gossip traffic of the last hours, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bandwidth: Vec<Bandwidth>,
+}
+
+/// Service metrics, as a flat map from metric name to value, eg. `fetches.succeeded`.
+/// Counters only ever increase while the node is running; they are reset on restart.
+/// Returned by [`Handle::metrics`].
+pub type Metrics = BTreeMap<String, u64>;
+
+/// Repairs made on startup, because the node didn't shut down cleanly the last time it
+/// ran, or because some of its databases were corrupt. Returned by [`Handle::recovery`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recovery {
+    /// Whether the node didn't shut down cleanly. Stores are only checked against
+    /// storage if so.
+    #[serde(default)]
+    pub unclean: bool,
+    /// Corrupt databases that were moved aside, and replaced with empty ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub databases: Vec<RecoveredDatabase>,
+    /// Routing entries added for repositories we have in storage.
+    pub routes_added: usize,
+    /// Routing entries removed for repositories we don't have in storage.
+    pub routes_removed: usize,
+    /// Sync watermarks cleared for repositories that failed to validate, as they may
+    /// record fetches that didn't reach the disk.
+    pub watermarks_cleared: usize,
+    /// Repositories in storage that were checked.
+    pub checked: usize,
+    /// Repositories in storage that failed to validate. They are left to inventory
+    /// handling, which skips repositories that don't open.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid: Vec<Id>,
+    /// Repositories in storage left to check. They are checked in the background.
+    pub pending: usize,
+}
+
+/// A corrupt database that was replaced with an empty one on startup.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredDatabase {
+    /// Path of the database.
+    pub path: PathBuf,
+    /// Path the corrupt database was moved to.
+    pub backup: PathBuf,
+}
+
+impl Recovery {
+    /// Whether all repositories were checked.
+    pub fn is_complete(&self) -> bool {
+        self.pending == 0
+    }
+}
+
+/// Kind of gossip message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageKind {
+    NodeAnnouncement,
+    InventoryAnnouncement,
+    RefsAnnouncement,
+    CobsAnnouncement,
+    Subscribe,
+    AnnouncementRequest,
+    Ping,
+    Pong,
+}
+
+impl MessageKind {
+    /// Get the message kind name, as it is serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NodeAnnouncement => "nodeAnnouncement",
+            Self::InventoryAnnouncement => "inventoryAnnouncement",
+            Self::RefsAnnouncement => "refsAnnouncement",
+            Self::CobsAnnouncement => "cobsAnnouncement",
+            Self::Subscribe => "subscribe",
+            Self::AnnouncementRequest => "announcementRequest",
+            Self::Ping => "ping",
+            Self::Pong => "pong",
+        }
+    }
+
+    /// Whether this is an announcement message.
+    pub fn is_announcement(&self) -> bool {
+        matches!(
+            self,
+            Self::NodeAnnouncement
+                | Self::InventoryAnnouncement
+                | Self::RefsAnnouncement
+                | Self::CobsAnnouncement
+        )
+    }
+}
+
+/// Gossip bytes exchanged with peers over an hour, per message kind.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bandwidth {
+    /// Start of the hour.
+    pub hour: Timestamp,
+    /// Bytes sent, per message kind.
+    pub sent: BTreeMap<MessageKind, u64>,
+    /// Bytes received, per message kind.
+    pub received: BTreeMap<MessageKind, u64>,
+}
+
+impl Bandwidth {
+    /// Total number of bytes sent.
+    pub fn total_sent(&self) -> u64 {
+        self.sent.values().sum()
+    }
+
+    /// Total number of bytes received.
+    pub fn total_received(&self) -> u64 {
+        self.received.values().sum()
+    }
+}
+
+/// Why the node believes a node seeds a repository, for debugging the routing table.
+/// Returned by [`Handle::explain`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explanation {
+    /// The repository.
+    pub rid: Id,
+    /// The node said to seed the repository.
+    pub nid: NodeId,
+    /// Timestamp of the routing entry, if there is one.
+    pub time: Option<Timestamp>,
+    /// Where the routing entry was learned from, if known. Entries learned before
+    /// provenance was recorded don't have one.
+    pub provenance: Option<routing::Provenance>,
+    /// Whether the entry is old enough to be pruned at the next prune run, should the
+    /// routing table be over capacity.
+    pub prunable: bool,
+    /// The last fetch of the repository from the node, if any.
+    pub fetch: Option<FetchAttempt>,
+}
+
+/// A fetch of a repository from a node, and its outcome.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchAttempt {
+    /// Local time at which the fetch completed.
+    pub time: Timestamp,
+    /// Why the fetch failed, if it did.
+    pub error: Option<String>,
+    /// Number of failed attempts, if a retry is scheduled.
+    pub attempts: usize,
+    /// Local time at which the fetch is retried, if a retry is scheduled.
+    pub retry_at: Option<Timestamp>,
+}
+
+/// A past fetch of a repository. Returned by [`Handle::fetch_history`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchRecord {
+    /// Local time at which the fetch completed.
+    pub time: Timestamp,
+    /// The seed fetched from.
+    pub seed: NodeId,
+    /// How long the fetch took, in milliseconds, if known.
+    pub duration: Option<u64>,
+    /// Number of bytes received from the seed, if known.
+    #[serde(default)]
+    pub received: Option<u64>,
+    /// Outcome of the fetch.
+    pub outcome: FetchOutcome,
+}
+
+/// Outcome of a fetch, as recorded in the fetch history.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum FetchOutcome {
+    /// The fetch succeeded.
+    Success,
+    /// The fetch failed.
+    #[serde(rename_all = "camelCase")]
+    Failed {
+        /// What kind of failure it was.
+        failure: FetchFailure,
+        /// The error message.
+        reason: String,
+    },
+}
+
+/// Kind of fetch failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FetchFailure {
+    /// The fetch timed out.
+    Timeout,
+    /// The connection with the seed failed, eg. the seed disconnected.
+    Connection,
+    /// The seed doesn't have the repository.
+    NotFound,
+    /// None of the repository delegates' refs could be fetched.
+    NoDelegates,
+    /// The fetched refs or identity failed verification.
+    Verification,
+    /// The `git fetch` command failed.
+    Git,
+    /// The seed sent more data than the fetch size limit allows.
+    SizeLimit,
+    /// Any other failure, eg. a local storage error.
+    Other,
+}
+
+impl fmt::Display for FetchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timeout"),
+            Self::Connection => write!(f, "connection"),
+            Self::NotFound => write!(f, "not found"),
+            Self::NoDelegates => write!(f, "no delegates"),
+            Self::Verification => write!(f, "verification"),
+            Self::Git => write!(f, "git"),
+            Self::SizeLimit => write!(f, "size limit"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+/// Represents a set of seeds with associated metadata. Seeds are returned in order of
+/// preference: ranked seeds first, by rank, followed by unranked seeds. Seeds with the same
+/// rank are returned in random order, which is different on every iteration.
+pub struct Seeds(address::AddressBook<NodeId, Seed>);
+
+impl Seeds {
+    /// Create a new seeds list from an RNG.
+    pub fn new(rng: fastrand::Rng) -> Self {
+        Self(address::AddressBook::new(rng))
+    }
+
+    /// Insert a seed.
+    pub fn insert(&mut self, seed: Seed) {
+        self.0.insert(seed.nid, seed);
+    }
+
+    /// Rank the seeds by the given key, lowest first. Seeds for which no key is returned
+    /// are left unranked, and seeds with equal keys get the same rank.
+    pub fn rank_by_key<K: Ord>(&mut self, key: impl Fn(&Seed) -> Option<K>) {
+        let mut keyed = self
+            .0
+            .values_mut()
+            .filter_map(|s| {
+                s.rank = None;
+                key(s).map(|k| (k, s))
+            })
+            .collect::<Vec<_>>();
+        keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut rank = 0;
+        for i in 0..keyed.len() {
+            if i > 0 && keyed[i - 1].0 != keyed[i].0 {
+                rank = i;
+            }
+            keyed[i].1.rank = Some(rank);
+        }
+    }
+
+    /// Prefer the seeds that advertized the given collaborative object type, eg. for
+    /// operations that need that type's data. This is a hint only: seeds that didn't
+    /// advertize the type may still serve it. The existing ranks are otherwise preserved.
+    pub fn prefer(&mut self, cob: &TypeName) {
+        if !self.0.values().any(|s| s.supports(cob)) {
+            return;
+        }
+        let ranks = self
+            .0
+            .iter()
+            .map(|(nid, s)| (*nid, s.rank))
+            .collect::<HashMap<_, _>>();
+
+        self.rank_by_key(|s| {
+            let rank = ranks.get(&s.nid).copied().flatten();
+            Some((!s.supports(cob), rank.is_none(), rank))
+        });
+    }
+
+    /// Iterate over the seeds in order of preference.
+    fn ordered(&self) -> impl Iterator<Item = &Seed> {
+        let mut seeds = self.0.shuffled().map(|(_, v)| v).collect::<Vec<_>>();
+        // Nb. The sort is stable, so seeds with the same rank remain in random order.
+        seeds.sort_by_key(|s| (s.rank.is_none(), s.rank));
+        seeds.into_iter()
+    }
+
+    /// Partitions the list of seeds into connected and disconnected seeds.
+    /// Note that the disconnected seeds may be in a "connecting" state.
+    pub fn partition(&self) -> (Vec<Seed>, Vec<Seed>) {
+        self.ordered().cloned().partition(|s| s.is_connected())
+    }
+
+    /// Return connected seeds.
+    pub fn connected(&self) -> impl Iterator<Item = &Seed> {
+        self.ordered().filter(|s| s.is_connected())
+    }
+
+    /// Pick up to `n` connected seeds to fetch from.
+    ///
+    /// The goal is to spread the load of serving a repository across all of its seeds:
+    /// if seeds were picked in key order, every node on the network would fetch a
+    /// popular repository from the seed with the lowest node id. Hence seeds are picked
+    /// preferring the ones that announced refs we don't have, then the ones we have the
+    /// fewest ongoing fetches with, and then by rank, or at random among equals.
+    pub fn select(&self, n: usize) -> Vec<&Seed> {
+        let mut connected = self.connected().collect::<Vec<_>>();
+        // Nb. The sort is stable, so seeds with the same key remain in random order.
+        connected.sort_by_key(|s| {
+            let load = match &s.state {
+                Some(State::Connected { fetching, .. }) => fetching.len(),
+                _ => 0,
+            };
+            (!s.is_fresh(), load)
+        });
+        connected.truncate(n);
+        connected
+    }
+
+    /// Check if a seed is connected.
+    pub fn is_connected(&self, nid: &NodeId) -> bool {
+        self.0.get(nid).map_or(false, |s| s.is_connected())
+    }
+
+    /// Return a new seeds object with the given RNG.
+    pub fn with(self, rng: fastrand::Rng) -> Self {
+        Self(self.0.with(rng))
+    }
+}
+
+/// Announcement result returned by [`Node::announce`].
+#[derive(Debug)]
+pub struct AnnounceResult {
+    /// Nodes that timed out.
+    pub timeout: Vec<NodeId>,
+    /// Nodes that synced.
+    pub synced: Vec<NodeId>,
+}
+
+/// A sync event, emitted by [`Node::announce`].
+#[derive(Debug)]
+pub enum AnnounceEvent {
+    /// Refs were synced with the given node.
+    RefsSynced { remote: NodeId },
+    /// Refs were announced to all given nodes.
+    Announced,
+}
+
+/// Why a namespace wasn't fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SkipReason {
+    /// The seed doesn't have the namespace.
+    Absent,
+    /// All of the namespace's refs, besides its identity and signed refs, are excluded by
+    /// the ref filter.
+    Filtered,
+    /// The namespace failed verification.
+    Invalid { reason: String },
+    /// The namespace went over the shape limits, eg. it had too many refs, and none of its
+    /// refs were transferred.
+    Quarantined { reason: String },
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Absent => write!(f, "not found on seed"),
+            Self::Filtered => write!(f, "excluded by ref filter"),
+            Self::Invalid { reason } => write!(f, "failed verification: {reason}"),
+            Self::Quarantined { reason } => write!(f, "quarantined: {reason}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum FetchResult {
+    Success {
+        updated: Vec<RefUpdate>,
+        namespaces: HashSet<NodeId>,
+        /// Namespaces fetched, with the number of their signed refs that were fetched.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fetched: Vec<(NodeId, usize)>,
+        /// Namespaces we expected, or tried to fetch, that weren't fetched.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        skipped: Vec<(NodeId, SkipReason)>,
+    },
+    // TODO: Create enum for reason.
+    Failed {
+        reason: String,
+    },
+}
+
+impl FetchResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self, FetchResult::Success { .. })
+    }
+
+    pub fn success(self) -> Option<(Vec<RefUpdate>, HashSet<NodeId>)> {
+        match self {
+            Self::Success {
+                updated,
+                namespaces,
+                ..
+            } => Some((updated, namespaces)),
+            _ => None,
+        }
+    }
+}
+
+impl<S: ToString>
+    From<
+        Result<
+            (
+                Vec<RefUpdate>,
+                HashSet<NodeId>,
+                Vec<(NodeId, usize)>,
+                Vec<(NodeId, SkipReason)>,
+            ),
+            S,
+        >,
+    > for FetchResult
+{
+    fn from(
+        value: Result<
+            (
+                Vec<RefUpdate>,
+                HashSet<NodeId>,
+                Vec<(NodeId, usize)>,
+                Vec<(NodeId, SkipReason)>,
+            ),
+            S,
+        >,
+    ) -> Self {
+        match value {
+            Ok((updated, namespaces, fetched, skipped)) => Self::Success {
+                updated,
+                namespaces,
+                fetched,
+                skipped,
+            },
+            Err(err) => Self::Failed {
+                reason: err.to_string(),
+            },
+        }
+    }
+}
+
+/// Holds multiple fetch results.
+#[derive(Debug, Default)]
+pub struct FetchResults(Vec<(NodeId, FetchResult)>);
+
+impl FetchResults {
+    /// Push a fetch result.
+    pub fn push(&mut self, nid: NodeId, result: FetchResult) {
+        self.0.push((nid, result));
+    }
+
+    /// Iterate over all fetch results.
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeId, &FetchResult)> {
+        self.0.iter().map(|(nid, r)| (nid, r))
+    }
+
+    /// Iterate over successful fetches.
+    pub fn success(&self) -> impl Iterator<Item = (&NodeId, &[RefUpdate], HashSet<NodeId>)> {
         self.0.iter().filter_map(|(nid, r)| {
             if let FetchResult::Success {
                 updated,
                 namespaces,
+                ..
             } = r
             {
                 Some((nid, updated.as_slice(), namespaces.clone()))
@@ -567,6 +1743,28 @@ impl FetchResults {
             }
         })
     }
+
+    /// Get the namespaces that weren't fetched from any of the seeds, with the reason
+    /// given by the first seed that skipped them.
+    pub fn skipped(&self) -> Vec<(NodeId, &SkipReason)> {
+        let fetched = self
+            .success()
+            .flat_map(|(_, _, namespaces)| namespaces)
+            .collect::<HashSet<_>>();
+        let mut skipped: Vec<(NodeId, &SkipReason)> = Vec::new();
+
+        for (_, r) in &self.0 {
+            let FetchResult::Success { skipped: s, .. } = r else {
+                continue;
+            };
+            for (nid, reason) in s {
+                if !fetched.contains(nid) && !skipped.iter().any(|(n, _)| n == nid) {
+                    skipped.push((*nid, reason));
+                }
+            }
+        }
+        skipped
+    }
 }
 
 impl From<Vec<(NodeId, FetchResult)>> for FetchResults {
@@ -599,8 +1797,8 @@ pub enum Error {
     Connect(#[from] io::Error),
     #[error("failed to call node: {0}")]
     Call(#[from] CallError),
-    #[error("node: {0}")]
-    Node(String),
+    #[error("node: {reason}")]
+    Node { code: ErrorCode, reason: String },
     #[error("received empty response for command")]
     EmptyResponse,
 }
@@ -622,6 +1820,31 @@ pub enum CallError {
         response: String,
         error: json::Error,
     },
+    #[error("node closed the connection in the middle of a response")]
+    Truncated,
+}
+
+impl CallError {
+    /// Convert into an I/O error, for commands that stream their response.
+    fn into_io(self) -> io::Error {
+        match self {
+            Self::Io(e) => e,
+            Self::Truncated => io::Error::new(io::ErrorKind::UnexpectedEof, self.to_string()),
+            // The node responds with an error result if the command couldn't be started.
+            Self::InvalidJson { ref response, .. } => {
+                match json::from_str::<CommandResult>(response) {
+                    Ok(CommandResult::Error { reason, code }) => {
+                        let kind = match code {
+                            ErrorCode::NotFound => io::ErrorKind::NotFound,
+                            _ => io::ErrorKind::Other,
+                        };
+                        io::Error::new(kind, reason)
+                    }
+                    _ => io::Error::new(io::ErrorKind::InvalidInput, self.to_string()),
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -653,15 +1876,54 @@ pub trait Handle: Clone + Sync + Send {
     fn seeds(&mut self, id: Id) -> Result<Seeds, Self::Error>;
     /// Fetch a repository from the network.
     fn fetch(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Self::Error>;
+    /// Fetch only the identity branch and delegate namespaces of a repository, to
+    /// bootstrap it quickly. See [`Command::Fetch`].
+    fn fetch_minimal(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Self::Error>;
     /// Start tracking the given project. Doesn't do anything if the project is already
     /// tracked.
     fn track_repo(&mut self, id: Id, scope: tracking::Scope) -> Result<bool, Self::Error>;
+    /// Start tracking the given project, and record why it is in our inventory.
+    fn track_repo_origin(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+        origin: tracking::Origin,
+    ) -> Result<bool, Self::Error>;
+    /// Start tracking the given project, and only fetch the refs matched by the given
+    /// filter from its remotes. Replaces any filter the project had.
+    fn track_repo_filter(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+        filter: tracking::RefFilter,
+    ) -> Result<bool, Self::Error>;
+    /// Start tracking the given project, and fetch it from known seeds right away.
+    fn track_repo_fetch(&mut self, id: Id, scope: tracking::Scope) -> Result<Tracked, Self::Error>;
+    /// Find out what would be fetched if the given project was tracked with the given
+    /// scope, without changing the tracking policy.
+    fn track_repo_dry_run(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+    ) -> Result<TrackDryRun, Self::Error>;
     /// Start tracking the given node.
     fn track_node(&mut self, id: NodeId, alias: Option<Alias>) -> Result<bool, Self::Error>;
     /// Untrack the given project and delete it from storage.
     fn untrack_repo(&mut self, id: Id) -> Result<bool, Self::Error>;
+    /// Start watching the given project: its announcements are received and relayed, but
+    /// it is never fetched. Doesn't do anything if the project is already tracked.
+    fn watch_repo(&mut self, id: Id) -> Result<bool, Self::Error>;
+    /// Stop watching the given project. Doesn't do anything if the project is tracked.
+    fn unwatch_repo(&mut self, id: Id) -> Result<bool, Self::Error>;
     /// Untrack the given node.
     fn untrack_node(&mut self, id: NodeId) -> Result<bool, Self::Error>;
+    /// Get all tracking policies, eg. to back them up.
+    fn export_policies(&self) -> Result<tracking::Policies, Self::Error>;
+    /// Import tracking policies, eg. from a backup. Either all policies are imported, or none.
+    fn import_policies(
+        &mut self,
+        policies: tracking::Policies,
+    ) -> Result<tracking::ImportSummary, Self::Error>;
     /// Notify the service that a project has been updated, and announce local refs.
     fn announce_refs(&mut self, id: Id) -> Result<(), Self::Error>;
     /// Announce local inventory.
@@ -672,11 +1934,66 @@ pub trait Handle: Clone + Sync + Send {
     fn shutdown(self) -> Result<(), Self::Error>;
     /// Query the peer session state.
     fn sessions(&self) -> Result<Self::Sessions, Self::Error>;
-    /// Subscribe to node events.
+    /// Subscribe to node events. Yields an error of kind [`io::ErrorKind::TimedOut`] if
+    /// no event is received within the given timeout, and ends if the node goes away.
     fn subscribe(
         &self,
         timeout: time::Duration,
     ) -> Result<Box<dyn Iterator<Item = Result<Event, io::Error>>>, Self::Error>;
+    /// Validate the given repository in storage, or all repositories if `None` is given.
+    /// Findings are returned as they are found.
+    fn validate(
+        &self,
+        rid: Option<Id>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Finding, io::Error>>>, Self::Error>;
+    /// Get the size and object statistics of a repository in storage, or of all repositories
+    /// if `None` is given. Cached statistics are returned even if stale, unless `refresh` is
+    /// set, in which case the statistics of the given repository are brought up to date.
+    fn repo_size(&self, rid: Option<Id>, refresh: bool) -> Result<Vec<RepoStats>, Self::Error>;
+    /// Get the size of the gossip store.
+    fn gossip_stats(&self) -> Result<GossipStats, Self::Error>;
+    /// Get the node's service metrics. Empty if metrics are disabled in the configuration.
+    fn metrics(&self) -> Result<Metrics, Self::Error>;
+    /// Get the repairs made on startup, if the node didn't shut down cleanly the last time
+    /// it ran, or some of its databases were corrupt.
+    fn recovery(&self) -> Result<Option<Recovery>, Self::Error>;
+    /// Explain why the routing table says the given node seeds the given repository.
+    fn explain(&self, rid: Id, nid: NodeId) -> Result<Explanation, Self::Error>;
+    /// Get the recent fetches of the given repository, oldest first.
+    fn fetch_history(&self, rid: Id) -> Result<Vec<FetchRecord>, Self::Error>;
+    /// Get a page of the logged events following the given sequence number. Events are
+    /// only logged if their kind is configured in [`config::EventLog`].
+    fn events_since(&self, seq: u64) -> Result<events::Page, Self::Error>;
+    /// Get the node's recent activity, recorded at or after the given time, oldest first.
+    /// If a limit is given, only the most recent entries are returned.
+    fn activity(
+        &self,
+        since: Option<Timestamp>,
+        limit: Option<usize>,
+    ) -> Result<Vec<activity::Entry>, Self::Error>;
+    /// Get the verified identity of a repository, without tracking it. If the repository
+    /// isn't in storage, its identity branch is fetched from a connected seed.
+    fn inspect(&mut self, rid: Id) -> Result<Inspection, Self::Error>;
+    /// Get the node's effective configuration, including limits changed at runtime.
+    fn config(&self) -> Result<config::Config, Self::Error>;
+    /// Change one of the [`config::Limits::RUNTIME`] limits, without restarting the node.
+    /// Existing sessions keep the limits they were established with.
+    fn set_limit(&mut self, name: &str, value: json::Value) -> Result<(), Self::Error>;
+    /// Add a peer to the node's configuration, and to the configuration file. Persistent
+    /// peers are connected to right away. Returns `true` if the peer wasn't configured.
+    fn add_peer(&mut self, peer: config::PeerEntry) -> Result<bool, Self::Error>;
+    /// Remove a peer from the node's configuration, and from the configuration file.
+    /// Returns `true` if the peer was configured.
+    fn remove_peer(&mut self, nid: NodeId) -> Result<bool, Self::Error>;
+    /// Announce and/or fetch all tracked repositories, a few at a time. Progress is
+    /// returned as each repository is synced, followed by a summary.
+    fn sync_all(
+        &mut self,
+        options: SyncAllOptions,
+    ) -> Result<Box<dyn Iterator<Item = Result<SyncProgress, io::Error>>>, Self::Error>;
+    /// Create or update a collaborative object, signing the change with the node's key,
+    /// and announce our refs of the repository.
+    fn cob_update(&mut self, update: CobUpdate) -> Result<CobUpdated, Self::Error>;
 }
 
 /// Public node & device identifier.
@@ -685,48 +2002,137 @@ pub type NodeId = PublicKey;
 /// Node controller.
 #[derive(Debug, Clone)]
 pub struct Node {
-    socket: PathBuf,
+    addr: control::Address,
+    /// Token sent with the commands that require [`control::Permission::Operator`].
+    operator: Option<control::OperatorToken>,
 }
 
 impl Node {
     /// Connect to the node, via the socket at the given path.
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_address(control::Address::Unix(path.as_ref().to_path_buf()))
+    }
+
+    /// Connect to the node, via the given control address.
+    pub fn with_address(addr: control::Address) -> Self {
         Self {
-            socket: path.as_ref().to_path_buf(),
+            addr,
+            operator: None,
         }
     }
 
+    /// Connect to the node of the given profile, using the control transport
+    /// configured for it. The node's operator token is used if there is one.
+    pub fn from_profile(profile: &profile::Profile) -> Self {
+        Self {
+            operator: control::OperatorToken::read(&profile.home.control_operator()).ok(),
+            ..Self::with_address(profile.control())
+        }
+    }
+
+    /// Authenticate as an operator with the given token, for the commands that require it.
+    pub fn with_operator_token(mut self, token: control::OperatorToken) -> Self {
+        self.operator = Some(token);
+        self
+    }
+
+    /// Get the control address of the node.
+    pub fn address(&self) -> &control::Address {
+        &self.addr
+    }
+
     /// Call a command on the node.
+    ///
+    /// Each response is waited for up to the given timeout, after which a
+    /// [`io::ErrorKind::TimedOut`] error is returned.
     pub fn call<T: DeserializeOwned>(
         &self,
         cmd: Command,
         timeout: time::Duration,
     ) -> Result<impl Iterator<Item = Result<T, CallError>>, io::Error> {
-        let stream = UnixStream::connect(&self.socket)?;
-        cmd.to_writer(&stream)?;
-
-        stream.set_read_timeout(Some(timeout))?;
-
-        Ok(BufReader::new(stream).lines().map(move |l| {
-            let l = l.map_err(|e| {
-                if e.kind() == io::ErrorKind::WouldBlock {
-                    io::Error::new(
-                        io::ErrorKind::TimedOut,
-                        "timed out reading from control socket",
-                    )
-                } else {
-                    e
-                }
-            })?;
-            let v = json::from_str(&l).map_err(|e| CallError::InvalidJson {
-                response: l,
-                error: e,
-            })?;
+        let mut stream = self.addr.connect()?;
+        if cmd.permission() == control::Permission::Operator {
+            if let Some(token) = &self.operator {
+                writeln!(stream, "{}", token.as_str())?;
+            }
+        }
+        cmd.to_writer(&mut stream)?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        let timed_out = || {
+            Some(Err(CallError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out reading from control socket",
+            ))))
+        };
+
+        Ok(iter::from_fn(move || {
+            // Nb. Heartbeats don't extend the time we wait for a response, otherwise
+            // a node that stays idle would keep us waiting forever.
+            let deadline = time::Instant::now() + timeout;
 
-            Ok(v)
+            loop {
+                let remaining = deadline.saturating_duration_since(time::Instant::now());
+                if remaining.is_zero() {
+                    return timed_out();
+                }
+                if let Err(e) = reader.get_ref().set_read_timeout(Some(remaining)) {
+                    return Some(Err(CallError::Io(e)));
+                }
+                // Nb. If the read times out in the middle of a line, what was read so far
+                // is kept in the buffer, and the line is completed on the next call.
+                let eof = match reader.read_line(&mut line) {
+                    Ok(_) => !line.ends_with('\n'),
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        return timed_out();
+                    }
+                    Err(e) => return Some(Err(CallError::Io(e))),
+                };
+                let response = mem::take(&mut line);
+                let response = response.trim_end();
+
+                if response.is_empty() {
+                    if eof {
+                        return None;
+                    }
+                    // Empty lines are heartbeats, sent by the node on idle streams.
+                    continue;
+                }
+                return Some(json::from_str(response).map_err(|error| {
+                    // Responses don't always end with a newline, so we only consider a
+                    // response truncated if it's also incomplete.
+                    if eof && error.is_eof() {
+                        CallError::Truncated
+                    } else {
+                        CallError::InvalidJson {
+                            response: response.to_owned(),
+                            error,
+                        }
+                    }
+                }));
+            }
         }))
     }
 
+    /// Parse a command response, which is either of the expected type, or an error result.
+    fn parse_response<T: DeserializeOwned>(response: json::Value) -> Result<T, Error> {
+        if let Ok(CommandResult::Error { reason, code }) = CommandResult::deserialize(&response) {
+            return Err(Error::Node { code, reason });
+        }
+        json::from_value(response.clone()).map_err(|error| {
+            Error::Call(CallError::InvalidJson {
+                response: response.to_string(),
+                error,
+            })
+        })
+    }
+
     /// Announce refs of the given `rid` to the given seeds.
     /// Waits for the seeds to acknowledge the refs or times out if no acknowledgments are received
     /// within the given time.
@@ -735,40 +2141,72 @@ impl Node {
         rid: Id,
         seeds: impl IntoIterator<Item = NodeId>,
         timeout: time::Duration,
-        mut callback: impl FnMut(AnnounceEvent),
+        callback: impl FnMut(AnnounceEvent),
     ) -> Result<AnnounceResult, Error> {
-        let events = self.subscribe(timeout)?;
-        let mut seeds = seeds.into_iter().collect::<BTreeSet<_>>();
-
-        self.announce_refs(rid)?;
-
-        callback(AnnounceEvent::Announced);
-
-        let mut synced = Vec::new();
-        let mut timeout: Vec<NodeId> = Vec::new();
-
-        for e in events {
-            match e {
-                Ok(Event::RefsSynced { remote, rid: rid_ }) if rid == rid_ => {
-                    seeds.remove(&remote);
-                    synced.push(remote);
-
-                    callback(AnnounceEvent::RefsSynced { remote });
-                }
-                Ok(_) => {}
+        self::announce(self, rid, seeds, timeout, callback)
+    }
+}
 
-                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
-                    timeout.extend(seeds.iter());
-                    break;
-                }
-                Err(e) => return Err(e.into()),
+/// Announce refs of the given `rid` to the given seeds, via any node handle.
+/// See [`Node::announce`].
+pub fn announce<H>(
+    handle: &mut H,
+    rid: Id,
+    seeds: impl IntoIterator<Item = NodeId>,
+    timeout: time::Duration,
+    mut callback: impl FnMut(AnnounceEvent),
+) -> Result<AnnounceResult, H::Error>
+where
+    H: Handle,
+    H::Error: From<io::Error>,
+{
+    // Nb. Other events may keep the subscription busy, so we also keep track of the
+    // overall time we've been waiting for.
+    let deadline = time::Instant::now() + timeout;
+    let events = handle.subscribe(timeout)?;
+    let mut seeds = seeds.into_iter().collect::<BTreeSet<_>>();
+
+    handle.announce_refs(rid)?;
+
+    callback(AnnounceEvent::Announced);
+
+    let mut synced = Vec::new();
+    let mut timeout: Vec<NodeId> = Vec::new();
+
+    for e in events {
+        match e {
+            Ok(Event::RefsSynced { remote, rid: rid_ }) if rid == rid_ => {
+                seeds.remove(&remote);
+                synced.push(remote);
+
+                callback(AnnounceEvent::RefsSynced { remote });
             }
-            if seeds.is_empty() {
+            Ok(_) => {}
+
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                timeout.extend(seeds.iter());
                 break;
             }
+            Err(e) => return Err(e.into()),
+        }
+        if seeds.is_empty() {
+            break;
         }
-        Ok(AnnounceResult { timeout, synced })
+        if time::Instant::now() >= deadline {
+            timeout.extend(seeds.iter());
+            break;
+        }
+    }
+    // The event stream only ends if the node went away, which is different from seeds not
+    // syncing in time.
+    if !seeds.is_empty() && timeout.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "node closed the event stream before all seeds were synced",
+        )
+        .into());
     }
+    Ok(AnnounceResult { timeout, synced })
 }
 
 // TODO(finto): repo_policies, node_policies, and routing should all
@@ -826,7 +2264,30 @@ impl Handle for Node {
 
     fn fetch(&mut self, rid: Id, from: NodeId) -> Result<FetchResult, Error> {
         let result = self
-            .call(Command::Fetch { rid, nid: from }, DEFAULT_TIMEOUT)?
+            .call(
+                Command::Fetch {
+                    rid,
+                    nid: from,
+                    minimal: false,
+                },
+                DEFAULT_TIMEOUT,
+            )?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Ok(result)
+    }
+
+    fn fetch_minimal(&mut self, rid: Id, from: NodeId) -> Result<FetchResult, Error> {
+        let result = self
+            .call(
+                Command::Fetch {
+                    rid,
+                    nid: from,
+                    minimal: true,
+                },
+                DEFAULT_TIMEOUT,
+            )?
             .next()
             .ok_or(Error::EmptyResponse)??;
 
@@ -841,12 +2302,95 @@ impl Handle for Node {
     }
 
     fn track_repo(&mut self, rid: Id, scope: tracking::Scope) -> Result<bool, Error> {
-        let mut line = self.call(Command::TrackRepo { rid, scope }, DEFAULT_TIMEOUT)?;
+        self.track_repo_origin(rid, scope, tracking::Origin::default())
+    }
+
+    fn track_repo_origin(
+        &mut self,
+        rid: Id,
+        scope: tracking::Scope,
+        origin: tracking::Origin,
+    ) -> Result<bool, Error> {
+        let mut line = self.call(
+            Command::TrackRepo {
+                rid,
+                scope,
+                dry_run: false,
+                fetch: false,
+                origin,
+                filter: None,
+            },
+            DEFAULT_TIMEOUT,
+        )?;
         let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
 
         response.into()
     }
 
+    fn track_repo_filter(
+        &mut self,
+        rid: Id,
+        scope: tracking::Scope,
+        filter: tracking::RefFilter,
+    ) -> Result<bool, Error> {
+        let mut line = self.call(
+            Command::TrackRepo {
+                rid,
+                scope,
+                dry_run: false,
+                fetch: false,
+                origin: tracking::Origin::default(),
+                filter: Some(filter),
+            },
+            DEFAULT_TIMEOUT,
+        )?;
+        let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
+
+        response.into()
+    }
+
+    fn track_repo_fetch(&mut self, rid: Id, scope: tracking::Scope) -> Result<Tracked, Error> {
+        let line = self
+            .call::<json::Value>(
+                Command::TrackRepo {
+                    rid,
+                    scope,
+                    dry_run: false,
+                    fetch: true,
+                    origin: tracking::Origin::default(),
+                    filter: None,
+                },
+                DEFAULT_TIMEOUT,
+            )?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn track_repo_dry_run(
+        &mut self,
+        rid: Id,
+        scope: tracking::Scope,
+    ) -> Result<TrackDryRun, Error> {
+        let line = self
+            .call::<json::Value>(
+                Command::TrackRepo {
+                    rid,
+                    scope,
+                    dry_run: true,
+                    fetch: false,
+                    origin: tracking::Origin::default(),
+                    filter: None,
+                },
+                DEFAULT_TIMEOUT,
+            )?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
     fn untrack_node(&mut self, nid: NodeId) -> Result<bool, Error> {
         let mut line = self.call(Command::UntrackNode { nid }, DEFAULT_TIMEOUT)?;
         let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
@@ -861,9 +2405,44 @@ impl Handle for Node {
         response.into()
     }
 
+    fn watch_repo(&mut self, rid: Id) -> Result<bool, Error> {
+        let mut line = self.call(Command::WatchRepo { rid }, DEFAULT_TIMEOUT)?;
+        let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
+
+        response.into()
+    }
+
+    fn unwatch_repo(&mut self, rid: Id) -> Result<bool, Error> {
+        let mut line = self.call(Command::UnwatchRepo { rid }, DEFAULT_TIMEOUT)?;
+        let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
+
+        response.into()
+    }
+
+    fn export_policies(&self) -> Result<tracking::Policies, Error> {
+        let line = self
+            .call::<json::Value>(Command::ExportPolicies, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn import_policies(
+        &mut self,
+        policies: tracking::Policies,
+    ) -> Result<tracking::ImportSummary, Error> {
+        let line = self
+            .call::<json::Value>(Command::ImportPolicies { policies }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
     fn announce_refs(&mut self, rid: Id) -> Result<(), Error> {
         for line in self.call::<CommandResult>(Command::AnnounceRefs { rid }, DEFAULT_TIMEOUT)? {
-            line?;
+            Result::<bool, Error>::from(line?)?;
         }
         Ok(())
     }
@@ -894,10 +2473,153 @@ impl Handle for Node {
                 CallError::InvalidJson { .. } => {
                     io::Error::new(io::ErrorKind::InvalidInput, err.to_string())
                 }
+                CallError::Truncated => err.into_io(),
             })
         })))
     }
 
+    fn validate(
+        &self,
+        rid: Option<Id>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Finding, io::Error>>>, Error> {
+        let findings = self.call(Command::ValidateRepo { rid }, VALIDATE_TIMEOUT)?;
+
+        Ok(Box::new(findings.map(|f| f.map_err(CallError::into_io))))
+    }
+
+    fn repo_size(&self, rid: Option<Id>, refresh: bool) -> Result<Vec<RepoStats>, Error> {
+        let line = self
+            .call::<json::Value>(Command::RepoSize { rid, refresh }, REPO_SIZE_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn gossip_stats(&self) -> Result<GossipStats, Error> {
+        let line = self
+            .call::<json::Value>(Command::GossipStats, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn metrics(&self) -> Result<Metrics, Error> {
+        let line = self
+            .call::<json::Value>(Command::Metrics, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn recovery(&self) -> Result<Option<Recovery>, Error> {
+        let line = self
+            .call::<json::Value>(Command::Recovery, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn explain(&self, rid: Id, nid: NodeId) -> Result<Explanation, Error> {
+        let line = self
+            .call::<json::Value>(Command::Explain { rid, nid }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn fetch_history(&self, rid: Id) -> Result<Vec<FetchRecord>, Error> {
+        let line = self
+            .call::<json::Value>(Command::FetchHistory { rid }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn events_since(&self, seq: u64) -> Result<events::Page, Error> {
+        let line = self
+            .call::<json::Value>(Command::EventsSince { seq }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn activity(
+        &self,
+        since: Option<Timestamp>,
+        limit: Option<usize>,
+    ) -> Result<Vec<activity::Entry>, Error> {
+        self.call::<json::Value>(Command::Activity { since, limit }, DEFAULT_TIMEOUT)?
+            .map(|line| Self::parse_response(line?))
+            .collect()
+    }
+
+    fn inspect(&mut self, rid: Id) -> Result<Inspection, Error> {
+        let line = self
+            .call::<json::Value>(Command::Inspect { rid }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn config(&self) -> Result<config::Config, Error> {
+        let line = self
+            .call::<json::Value>(Command::GetConfig, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
+    fn set_limit(&mut self, name: &str, value: json::Value) -> Result<(), Error> {
+        let name = name.to_owned();
+        let line = self
+            .call::<json::Value>(Command::SetLimit { name, value }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response::<CommandResult>(line).map(|_| ())
+    }
+
+    fn add_peer(&mut self, peer: config::PeerEntry) -> Result<bool, Error> {
+        let mut line = self.call(Command::AddPeer { peer }, DEFAULT_TIMEOUT)?;
+        let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
+
+        response.into()
+    }
+
+    fn remove_peer(&mut self, nid: NodeId) -> Result<bool, Error> {
+        let mut line = self.call(Command::RemovePeer { nid }, DEFAULT_TIMEOUT)?;
+        let response: CommandResult = line.next().ok_or(Error::EmptyResponse)??;
+
+        response.into()
+    }
+
+    fn sync_all(
+        &mut self,
+        options: SyncAllOptions,
+    ) -> Result<Box<dyn Iterator<Item = Result<SyncProgress, io::Error>>>, Error> {
+        let progress = self.call(Command::SyncAll { options }, SYNC_ALL_TIMEOUT)?;
+
+        Ok(Box::new(progress.map(|p| p.map_err(CallError::into_io))))
+    }
+
+    fn cob_update(&mut self, update: CobUpdate) -> Result<CobUpdated, Error> {
+        let line = self
+            .call::<json::Value>(Command::CobUpdate { update }, DEFAULT_TIMEOUT)?
+            .next()
+            .ok_or(Error::EmptyResponse)??;
+
+        Self::parse_response(line)
+    }
+
     fn sessions(&self) -> Result<Self::Sessions, Error> {
         let sessions = self
             .call::<Vec<Session>>(Command::Sessions, DEFAULT_TIMEOUT)?
@@ -944,8 +2666,402 @@ impl AliasStore for HashMap<NodeId, Alias> {
 }
 
 #[cfg(test)]
-mod test {
+mod tests {
     use super::*;
+    use crate::assert_matches;
+    use crate::test::arbitrary;
+
+    #[test]
+    fn test_command_roundtrip() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let addr = config::ConnectAddress::from((nid, arbitrary::gen::<Address>(1)));
+        let cmds = [
+            Command::AnnounceRefs { rid },
+            Command::AnnounceInventory,
+            Command::SyncInventory,
+            Command::Connect {
+                addr,
+                opts: ConnectOptions {
+                    persistent: true,
+                    timeout: time::Duration::from_secs(3),
+                },
+            },
+            Command::Seeds { rid },
+            Command::Sessions,
+            Command::Fetch {
+                rid,
+                nid,
+                minimal: false,
+            },
+            Command::Fetch {
+                rid,
+                nid,
+                minimal: true,
+            },
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::Trusted,
+                dry_run: false,
+                fetch: false,
+                origin: tracking::Origin::default(),
+                filter: None,
+            },
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::All,
+                dry_run: true,
+                fetch: false,
+                origin: tracking::Origin::default(),
+                filter: None,
+            },
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::All,
+                dry_run: false,
+                fetch: true,
+                origin: tracking::Origin::default(),
+                filter: None,
+            },
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::Trusted,
+                dry_run: false,
+                fetch: false,
+                origin: tracking::Origin::Created,
+                filter: None,
+            },
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::All,
+                dry_run: false,
+                fetch: true,
+                origin: tracking::Origin::default(),
+                filter: Some(tracking::RefFilter {
+                    include: vec![],
+                    exclude: vec![crate::git::refspec::pattern!("refs/heads/artifacts/*")],
+                }),
+            },
+            Command::UntrackRepo { rid },
+            Command::WatchRepo { rid },
+            Command::UnwatchRepo { rid },
+            Command::TrackNode {
+                nid,
+                alias: Some(Alias::new("alice")),
+            },
+            Command::TrackNode { nid, alias: None },
+            Command::UntrackNode { nid },
+            Command::ExportPolicies,
+            Command::ImportPolicies {
+                policies: tracking::Policies {
+                    repos: vec![tracking::Repo {
+                        id: rid,
+                        scope: tracking::Scope::All,
+                        policy: tracking::Policy::Track,
+                        fetch_limit: None,
+                        origin: tracking::Origin::default(),
+                        ref_filter: tracking::RefFilter::default(),
+                    }],
+                    nodes: vec![tracking::Node {
+                        id: nid,
+                        alias: Some(Alias::new("alice")),
+                        policy: tracking::Policy::Block,
+                    }],
+                },
+            },
+            Command::Status,
+            Command::NodeId,
+            Command::Shutdown,
+            Command::Subscribe,
+            Command::ValidateRepo { rid: Some(rid) },
+            Command::ValidateRepo { rid: None },
+            Command::RepoSize {
+                rid: Some(rid),
+                refresh: true,
+            },
+            Command::RepoSize {
+                rid: None,
+                refresh: false,
+            },
+            Command::GossipStats,
+            Command::Metrics,
+            Command::Recovery,
+            Command::Explain { rid, nid },
+            Command::FetchHistory { rid },
+            Command::EventsSince { seq: 42 },
+            Command::Activity {
+                since: None,
+                limit: None,
+            },
+            Command::Activity {
+                since: Some(1700000000000),
+                limit: Some(10),
+            },
+            Command::Inspect { rid },
+            Command::GetConfig,
+            Command::SetLimit {
+                name: String::from("fetchConcurrency"),
+                value: json::json!(4),
+            },
+            Command::AddPeer {
+                peer: config::PeerEntry {
+                    id: nid,
+                    address: arbitrary::gen::<Address>(1),
+                    alias: Some(Alias::new("bob")),
+                    persistent: true,
+                },
+            },
+            Command::RemovePeer { nid },
+            Command::SyncAll {
+                options: SyncAllOptions::default(),
+            },
+            Command::SyncAll {
+                options: SyncAllOptions {
+                    fetch: true,
+                    announce: false,
+                    parallelism: 2,
+                },
+            },
+            Command::CobUpdate {
+                update: CobUpdate {
+                    rid,
+                    type_name: TypeName::from_str("xyz.radicle.issue").unwrap(),
+                    object: CobObject::New,
+                    message: String::from("Open issue"),
+                    actions: vec![json::json!({ "type": "edit", "title": "Bug" })],
+                },
+            },
+            Command::CobUpdate {
+                update: CobUpdate {
+                    rid,
+                    type_name: TypeName::from_str("xyz.radicle.issue").unwrap(),
+                    object: CobObject::Existing(arbitrary::oid().into()),
+                    message: String::from("Comment"),
+                    actions: vec![json::json!({ "type": "comment", "body": "Hi" })],
+                },
+            },
+        ];
+
+        for cmd in cmds {
+            let mut line = Vec::new();
+            cmd.to_writer(&mut line).unwrap();
+
+            let input = String::from_utf8(line).unwrap();
+            assert_eq!(Command::parse(input.trim_end()).unwrap(), cmd, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_command_legacy() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+
+        let cmd = json::json!({ "cmd": "track-repo", "args": [rid.urn(), "all"] }).to_string();
+        assert_eq!(
+            Command::parse(&cmd).unwrap(),
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::All,
+                dry_run: false,
+                fetch: false,
+                origin: tracking::Origin::default(),
+                filter: None,
+            }
+        );
+        let cmd = json::json!({ "cmd": "track-repo", "args": [rid.urn()] }).to_string();
+        assert_eq!(
+            Command::parse(&cmd).unwrap(),
+            Command::TrackRepo {
+                rid,
+                scope: tracking::Scope::default(),
+                dry_run: false,
+                fetch: false,
+                origin: tracking::Origin::default(),
+                filter: None,
+            }
+        );
+        let cmd = json::json!({ "cmd": "fetch", "args": [rid.urn(), nid.to_string()] }).to_string();
+        assert_eq!(
+            Command::parse(&cmd).unwrap(),
+            Command::Fetch {
+                rid,
+                nid,
+                minimal: false,
+            }
+        );
+
+        let cmd = json::json!({ "cmd": "node-id", "args": [] }).to_string();
+        assert_eq!(Command::parse(&cmd).unwrap(), Command::NodeId);
+
+        let cmd = json::json!({ "cmd": "shutdown" }).to_string();
+        assert_eq!(Command::parse(&cmd).unwrap(), Command::Shutdown);
+
+        // Options that aren't given take their default value.
+        let cmd = json::json!({ "cmd": "sync-all" }).to_string();
+        assert_eq!(
+            Command::parse(&cmd).unwrap(),
+            Command::SyncAll {
+                options: SyncAllOptions::default(),
+            }
+        );
+        let cmd = json::json!({ "type": "syncAll", "announce": false }).to_string();
+        assert_eq!(
+            Command::parse(&cmd).unwrap(),
+            Command::SyncAll {
+                options: SyncAllOptions {
+                    announce: false,
+                    ..SyncAllOptions::default()
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_address_validate() {
+        use address::AddressError;
+
+        let addr = |s: &str| Address::from_str(s).unwrap();
+        let own = [addr("203.0.113.7:8776")];
+        let ctx = address::Context {
+            own: &own,
+            reject_local: true,
+        };
+
+        assert_eq!(addr("198.51.100.1:8776").validate(&ctx), Ok(()));
+        assert_eq!(addr("seed.radicle.xyz:8776").validate(&ctx), Ok(()));
+        assert_matches!(
+            addr("0.0.0.0:8776").validate(&ctx),
+            Err(AddressError::Unspecified(_))
+        );
+        assert_matches!(
+            addr("[::]:8776").validate(&ctx),
+            Err(AddressError::Unspecified(_))
+        );
+        assert_matches!(
+            addr("224.0.0.251:5353").validate(&ctx),
+            Err(AddressError::Multicast(_))
+        );
+        assert_matches!(
+            addr("[ff02::1]:8776").validate(&ctx),
+            Err(AddressError::Multicast(_))
+        );
+        assert_matches!(
+            addr("255.255.255.255:8776").validate(&ctx),
+            Err(AddressError::Broadcast(_))
+        );
+        assert_matches!(
+            addr("198.51.100.1:0").validate(&ctx),
+            Err(AddressError::PortZero(_))
+        );
+        assert_matches!(
+            addr("203.0.113.7:8776").validate(&ctx),
+            Err(AddressError::Own(_))
+        );
+        assert_matches!(
+            addr("127.0.0.1:8776").validate(&ctx),
+            Err(AddressError::Local(_))
+        );
+        assert_matches!(
+            addr("192.168.1.2:8776").validate(&ctx),
+            Err(AddressError::Local(_))
+        );
+        // Our own address on another port is someone else's.
+        assert_eq!(addr("203.0.113.7:8777").validate(&ctx), Ok(()));
+    }
+
+    #[test]
+    fn test_address_validate_local() {
+        let cfg = config::Config::test(Alias::new("alice"));
+        let ctx = cfg.address_context();
+
+        for local in ["127.0.0.1:8776", "[::1]:8776", "10.0.0.2:8776"] {
+            assert_eq!(Address::from_str(local).unwrap().validate(&ctx), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_command_errors() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let code = |input: json::Value| Command::parse(&input.to_string()).unwrap_err().code;
+
+        assert_eq!(
+            Command::parse("{\"type\":").unwrap_err().code,
+            ErrorCode::InvalidRequest
+        );
+        assert_eq!(code(json::json!({ "rid": rid })), ErrorCode::InvalidRequest);
+        assert_eq!(
+            code(json::json!({ "type": "frobnicate" })),
+            ErrorCode::UnknownCommand
+        );
+        assert_eq!(
+            code(json::json!({ "cmd": "frobnicate", "args": [] })),
+            ErrorCode::UnknownCommand
+        );
+        assert_eq!(
+            code(json::json!({ "type": "seeds", "rid": "rad:invalid" })),
+            ErrorCode::InvalidRid
+        );
+        assert_eq!(
+            code(json::json!({ "cmd": "fetch", "args": [rid.urn(), "z6Mk"] })),
+            ErrorCode::InvalidNid
+        );
+        assert_eq!(
+            code(json::json!({ "type": "trackRepo", "rid": rid, "scope": "everyone" })),
+            ErrorCode::InvalidScope
+        );
+        assert_eq!(
+            code(json::json!({ "type": "connect", "addr": "localhost" })),
+            ErrorCode::InvalidAddress
+        );
+        assert_eq!(
+            code(json::json!({ "type": "connect", "addr": format!("{nid}@0.0.0.0:8776") })),
+            ErrorCode::InvalidAddress
+        );
+        assert_eq!(
+            code(json::json!({ "type": "repoSize", "refresh": true })),
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            code(json::json!({ "type": "trackNode", "nid": nid, "alias": "cloud head" })),
+            ErrorCode::InvalidAlias
+        );
+        assert_eq!(
+            code(json::json!({ "cmd": "track-node", "args": [nid, "a".repeat(200)] })),
+            ErrorCode::InvalidAlias
+        );
+        assert_eq!(
+            code(json::json!({ "type": "fetch", "rid": rid })),
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            code(json::json!({ "cmd": "seeds", "args": [rid.urn(), "all"] })),
+            ErrorCode::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_command_authorize() {
+        let rid = arbitrary::gen::<Id>(1);
+        let cmd = Command::CobUpdate {
+            update: CobUpdate {
+                rid,
+                type_name: TypeName::from_str("xyz.radicle.issue").unwrap(),
+                object: CobObject::New,
+                message: String::from("Open issue"),
+                actions: vec![],
+            },
+        };
+
+        assert_eq!(
+            cmd.authorize(control::Permission::User).unwrap_err().code,
+            ErrorCode::Unauthorized
+        );
+        assert!(cmd.authorize(control::Permission::Operator).is_ok());
+        assert!(Command::Seeds { rid }
+            .authorize(control::Permission::User)
+            .is_ok());
+    }
 
     #[test]
     fn test_alias() {
@@ -960,5 +3076,315 @@ mod test {
         assert!(Alias::from_str("cloud\0head").is_err());
         assert!(Alias::from_str("cloud head").is_err());
         assert!(Alias::from_str("cloudhead\n").is_err());
+
+        // Invalid aliases are made valid when decoded, eg. from existing configurations.
+        assert!(json::from_str::<Alias>("\"cloudhead\"").is_ok());
+        assert_eq!(
+            json::from_str::<Alias>("\"cloud head\"").unwrap(),
+            Alias::new("cloudhead")
+        );
+        assert_eq!(
+            json::from_str::<Alias>(&format!("\"{}\"", "a".repeat(40))).unwrap(),
+            Alias::new("a".repeat(MAX_ALIAS_LENGTH))
+        );
+        assert!(json::from_str::<Alias>("\"\"").is_err());
+
+        assert_eq!(
+            Alias::from_lossy(" cloud head\n"),
+            Some(Alias::new("cloudhead"))
+        );
+        assert_eq!(
+            Alias::from_lossy(&"è".repeat(20)),
+            Some(Alias::new("è".repeat(16)))
+        );
+        assert_eq!(Alias::from_lossy(" \n"), None);
+
+        let bytes = <[u8; 32]>::try_from(&Alias::new("cloudhead")).unwrap();
+        assert_eq!(&bytes[..9], b"cloudhead");
+        assert!(bytes[9..].iter().all(|b| *b == 0));
+        assert!(<[u8; 32]>::try_from(&Alias::from(&arbitrary::gen::<NodeId>(1))).is_err());
+    }
+    #[test]
+    fn test_seeds_select() {
+        let rid = arbitrary::gen::<Id>(1);
+        let connected = |fetching: HashSet<Id>| {
+            Some(State::Connected {
+                since: LocalTime::now(),
+                ping: PingState::default(),
+                fetching,
+            })
+        };
+        let nids = (0..5)
+            .map(|_| arbitrary::gen::<NodeId>(1))
+            .collect::<BTreeSet<_>>();
+        let mut seeds = Seeds::new(fastrand::Rng::with_seed(42));
+
+        for nid in &nids {
+            seeds.insert(Seed::new(*nid, vec![], connected(HashSet::new())));
+        }
+        // Disconnected seeds are never selected.
+        seeds.insert(Seed::new(arbitrary::gen::<NodeId>(1), vec![], None));
+
+        let mut counts = HashMap::<NodeId, usize>::new();
+        for _ in 0..1000 {
+            for seed in seeds.select(1) {
+                *counts.entry(seed.nid).or_default() += 1;
+            }
+        }
+        // Selection is spread across all connected seeds, instead of always choosing the
+        // seed with the smallest key.
+        assert_eq!(counts.len(), nids.len());
+        for (nid, count) in &counts {
+            assert!(*count > 100, "{nid} was only selected {count} time(s)");
+        }
+
+        // Seeds we're fetching from are selected last.
+        let busy = *nids.first().unwrap();
+        seeds.insert(Seed::new(busy, vec![], connected(HashSet::from([rid]))));
+
+        for _ in 0..100 {
+            let selected = seeds.select(nids.len() - 1);
+
+            assert_eq!(selected.len(), nids.len() - 1);
+            assert!(selected.iter().all(|s| s.nid != busy));
+            assert_eq!(seeds.select(nids.len()).last().unwrap().nid, busy);
+        }
+
+        // Seeds that announced refs we don't have are selected first, even if busy.
+        seeds.insert(
+            Seed::new(busy, vec![], connected(HashSet::from([rid]))).with_announced(1, true),
+        );
+
+        for _ in 0..100 {
+            assert_eq!(seeds.select(1).first().unwrap().nid, busy);
+        }
+    }
+
+    #[test]
+    fn test_seeds_order() {
+        let connected = Some(State::Connected {
+            since: LocalTime::now(),
+            ping: PingState::default(),
+            fetching: HashSet::new(),
+        });
+        let nids = (0..5)
+            .map(|_| arbitrary::gen::<NodeId>(1))
+            .collect::<BTreeSet<_>>();
+        let mut seeds = Seeds::new(fastrand::Rng::with_seed(42));
+
+        for nid in &nids {
+            seeds.insert(Seed::new(*nid, vec![], connected.clone()));
+        }
+        // Without ranks, queries don't all return the same seed first.
+        let first = (0..100)
+            .filter_map(|_| seeds.connected().next().map(|s| s.nid))
+            .collect::<HashSet<_>>();
+        assert!(first.len() > 1);
+
+        // With ranks, seeds are returned by rank, and unranked seeds last.
+        let mut ranked = nids.iter().rev().copied().collect::<Vec<_>>();
+        let unranked = ranked.pop().unwrap();
+        seeds.rank_by_key(|s| ranked.iter().position(|nid| *nid == s.nid));
+
+        for _ in 0..100 {
+            let order = seeds.connected().map(|s| s.nid).collect::<Vec<_>>();
+            assert_eq!(order[..ranked.len()], ranked);
+            assert_eq!(order.last(), Some(&unranked));
+
+            let (connected, _) = seeds.partition();
+            assert_eq!(connected.first().map(|s| s.nid), ranked.first().copied());
+        }
+
+        // Seeds with equal keys share a rank, and are returned in random order.
+        seeds.rank_by_key(|s| (s.nid != unranked).then_some(0));
+        assert_eq!(seeds.connected().last().map(|s| s.nid), Some(unranked));
+        assert!(seeds
+            .connected()
+            .filter(|s| s.nid != unranked)
+            .all(|s| s.rank == Some(0)));
+    }
+
+    #[test]
+    fn test_seeds_prefer() {
+        let issue = TypeName::from_str("xyz.radicle.issue").unwrap();
+        let nids = (0..3)
+            .map(|_| arbitrary::gen::<NodeId>(1))
+            .collect::<Vec<_>>();
+        let mut seeds = Seeds::new(fastrand::Rng::with_seed(42));
+
+        for nid in &nids {
+            let cobs = if *nid == nids[2] {
+                vec![issue.clone()]
+            } else {
+                vec![]
+            };
+            seeds.insert(Seed::new(*nid, vec![], None).with_cobs(cobs));
+        }
+        seeds.rank_by_key(|s| nids.iter().position(|nid| *nid == s.nid));
+        seeds.prefer(&issue);
+
+        let (_, order) = seeds.partition();
+        let order = order.into_iter().map(|s| s.nid).collect::<Vec<_>>();
+        assert_eq!(order, vec![nids[2], nids[0], nids[1]]);
+    }
+
+    #[test]
+    fn test_announce() {
+        use test::{Call, MockHandle};
+
+        let rid = arbitrary::gen::<Id>(1);
+        let other = arbitrary::gen::<Id>(1);
+        let alice = arbitrary::gen::<NodeId>(1);
+        let bob = arbitrary::gen::<NodeId>(1);
+        let timeout = time::Duration::from_millis(10);
+        let mut node = MockHandle::default();
+
+        // Events for other repositories are ignored.
+        node.emit(Event::RefsSynced {
+            remote: alice,
+            rid: other,
+        });
+        node.emit(Event::RefsSynced { remote: alice, rid });
+        node.emit(Event::RefsSynced { remote: bob, rid });
+
+        let mut events = Vec::new();
+        let result = announce(&mut node, rid, [alice, bob], timeout, |e| events.push(e)).unwrap();
+
+        assert_eq!(result.synced, vec![alice, bob]);
+        assert!(result.timeout.is_empty());
+        assert_eq!(events.len(), 3);
+        assert_eq!(node.calls(), vec![Call::Subscribe, Call::AnnounceRefs(rid)]);
+
+        // Seeds that don't sync in time are reported.
+        node.emit(Event::RefsSynced { remote: alice, rid });
+
+        let result = announce(&mut node, rid, [alice, bob], timeout, |_| {}).unwrap();
+        assert_eq!(result.synced, vec![alice]);
+        assert_eq!(result.timeout, vec![bob]);
+
+        // Errors from the node are returned.
+        node.fail(
+            |call| matches!(call, Call::AnnounceRefs(_)),
+            io::ErrorKind::ConnectionRefused,
+        );
+        let err = announce(&mut node, rid, [alice], timeout, |_| {}).unwrap_err();
+        assert!(matches!(err, Error::Connect(e) if e.kind() == io::ErrorKind::ConnectionRefused));
+    }
+
+    /// Serve scripted responses on a unix socket, one connection at a time. Each response
+    /// is written in chunks, each after a delay in milliseconds, before closing the connection.
+    #[cfg(unix)]
+    fn serve(socket: &Path, responses: Vec<Vec<(u64, String)>>) -> thread::JoinHandle<()> {
+        use std::io::Write as _;
+        use std::os::unix::net::UnixListener;
+
+        let listener = UnixListener::bind(socket).unwrap();
+
+        thread::spawn(move || {
+            for chunks in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut cmd = String::new();
+                BufReader::new(&stream).read_line(&mut cmd).unwrap();
+
+                for (delay, chunk) in chunks {
+                    thread::sleep(time::Duration::from_millis(delay));
+                    stream.write_all(chunk.as_bytes()).unwrap();
+                }
+            }
+        })
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_call_heartbeats() {
+        let tmp = tempfile::tempdir().unwrap();
+        let socket = tmp.path().join("node.sock");
+        let rid = arbitrary::gen::<Id>(1);
+        let remote = arbitrary::gen::<NodeId>(1);
+        let event = json::to_string(&Event::RefsSynced { remote, rid }).unwrap();
+
+        // Heartbeats are skipped while waiting for an event.
+        let mut script = vec![(50, String::from("\n")); 3];
+        script.push((50, format!("{event}\n")));
+        // Heartbeats don't extend the time we wait for the next event.
+        script.extend(vec![(100, String::from("\n")); 8]);
+
+        let server = serve(&socket, vec![script]);
+        let node = Node::new(&socket);
+        let mut events = node.subscribe(time::Duration::from_millis(500)).unwrap();
+
+        assert_matches!(
+            events.next(),
+            Some(Ok(Event::RefsSynced { remote: r, rid: id })) if r == remote && id == rid
+        );
+        assert_matches!(
+            events.next(),
+            Some(Err(e)) if e.kind() == io::ErrorKind::TimedOut
+        );
+        assert!(events.next().is_none());
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_call_partial_lines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let socket = tmp.path().join("node.sock");
+        let response = json::json!({ "status": "ok" }).to_string();
+        let (head, tail) = response.split_at(response.len() / 2);
+
+        // A line is completed after the client timed out, and the node goes away
+        // in the middle of the next one.
+        let server = serve(
+            &socket,
+            vec![vec![
+                (0, head.to_owned()),
+                (300, format!("{tail}\n")),
+                (0, head.to_owned()),
+            ]],
+        );
+        let node = Node::new(&socket);
+        let mut lines = node
+            .call::<CommandResult>(Command::Status, time::Duration::from_millis(100))
+            .unwrap();
+
+        let result = loop {
+            match lines.next() {
+                Some(Err(CallError::Io(e))) if e.kind() == io::ErrorKind::TimedOut => continue,
+                other => break other,
+            }
+        };
+        assert_matches!(result, Some(Ok(CommandResult::Okay { .. })));
+        assert_matches!(lines.next(), Some(Err(CallError::Truncated)));
+        assert!(lines.next().is_none());
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_announce_node_gone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let socket = tmp.path().join("node.sock");
+        let rid = arbitrary::gen::<Id>(1);
+        let seed = arbitrary::gen::<NodeId>(1);
+
+        // The event stream is closed right after a heartbeat, before the seed synced.
+        let server = serve(
+            &socket,
+            vec![
+                vec![(0, String::from("\n"))],
+                vec![(0, format!("{}\n", json::json!({ "status": "ok" })))],
+            ],
+        );
+        let mut node = Node::new(&socket);
+        let err = node
+            .announce(rid, [seed], time::Duration::from_secs(3), |_| {})
+            .unwrap_err();
+
+        assert_matches!(err, Error::Connect(e) if e.kind() == io::ErrorKind::UnexpectedEof);
+
+        server.join().unwrap();
     }
 }
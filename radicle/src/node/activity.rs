@@ -0,0 +1,182 @@
+//! In-memory log of the node's recent activity.
+//!
+//! Unlike the [`super::events`] log, the activity log isn't persisted, and summarizes what
+//! the node did, for operators who don't have access to the node's logs.
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::identity::Id;
+use crate::node::{Address, NodeId, Timestamp};
+
+/// Something the node did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Activity {
+    /// A peer connection was established.
+    Connected {
+        nid: NodeId,
+        addr: Address,
+        inbound: bool,
+    },
+    /// A peer connection was closed.
+    Disconnected { nid: NodeId, reason: String },
+    /// A fetch was started.
+    FetchStarted { rid: Id, nid: NodeId },
+    /// A fetch completed, updating the given number of refs.
+    FetchCompleted {
+        rid: Id,
+        nid: NodeId,
+        updated: usize,
+    },
+    /// A fetch failed.
+    FetchFailed {
+        rid: Id,
+        nid: NodeId,
+        reason: String,
+    },
+    /// Gossip announcements sent and received since the previous such entry.
+    Announcements { sent: usize, received: usize },
+    /// Routing table entries and fetch history records were pruned.
+    Pruned { routes: usize, fetches: usize },
+}
+
+impl Activity {
+    /// Get the activity type, as it appears in the `type` field of entries.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connected { .. } => "connected",
+            Self::Disconnected { .. } => "disconnected",
+            Self::FetchStarted { .. } => "fetchStarted",
+            Self::FetchCompleted { .. } => "fetchCompleted",
+            Self::FetchFailed { .. } => "fetchFailed",
+            Self::Announcements { .. } => "announcements",
+            Self::Pruned { .. } => "pruned",
+        }
+    }
+}
+
+/// An entry of the activity log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    /// Local time at which the activity was recorded.
+    pub time: Timestamp,
+    /// What the node did.
+    #[serde(flatten)]
+    pub activity: Activity,
+}
+
+/// Activity log, holding a bounded number of entries. Once full, the oldest entries are
+/// dropped. Nothing is recorded if the capacity is zero.
+#[derive(Debug, Clone, Default)]
+pub struct Log {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl Log {
+    /// Create a new log, holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Record an activity at the given time.
+    pub fn record(&mut self, time: Timestamp, activity: Activity) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry { time, activity });
+    }
+
+    /// Get the entries recorded at or after the given time, oldest first. If a limit is
+    /// given, only the most recent entries within the limit are returned.
+    pub fn since(&self, since: Option<Timestamp>, limit: Option<usize>) -> Vec<Entry> {
+        let since = since.unwrap_or_default();
+        let start = self.entries.partition_point(|e| e.time < since);
+        let start = match limit {
+            Some(limit) => start.max(self.entries.len().saturating_sub(limit)),
+            None => start,
+        };
+        self.entries.range(start..).cloned().collect()
+    }
+
+    /// Number of entries in the log.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the log is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::arbitrary;
+
+    #[test]
+    fn test_log() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let mut log = Log::new(3);
+
+        for time in 1..=4 {
+            log.record(time, Activity::FetchStarted { rid, nid });
+        }
+        assert_eq!(log.len(), 3, "the oldest entry is dropped");
+        assert_eq!(
+            log.since(None, None)
+                .iter()
+                .map(|e| e.time)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            log.since(Some(3), None)
+                .iter()
+                .map(|e| e.time)
+                .collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        assert_eq!(
+            log.since(None, Some(1))
+                .iter()
+                .map(|e| e.time)
+                .collect::<Vec<_>>(),
+            vec![4]
+        );
+        assert!(log.since(Some(5), None).is_empty());
+
+        let mut log = Log::new(0);
+        log.record(1, Activity::FetchStarted { rid, nid });
+        assert!(log.is_empty(), "nothing is recorded when disabled");
+    }
+
+    #[test]
+    fn test_entry_json() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let entry = Entry {
+            time: 42,
+            activity: Activity::FetchCompleted {
+                rid,
+                nid,
+                updated: 3,
+            },
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+
+        assert_eq!(json["type"], entry.activity.as_str());
+        assert_eq!(json["time"], 42);
+        assert_eq!(serde_json::from_value::<Entry>(json).unwrap(), entry);
+    }
+}
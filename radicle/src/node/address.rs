@@ -68,6 +68,39 @@ pub fn is_local(addr: &net::IpAddr) -> bool {
     }
 }
 
+/// What an address is checked against before it is dialed. See [`Address::validate`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Context<'a> {
+    /// Our own announced addresses, which we never dial.
+    pub own: &'a [Address],
+    /// Whether loopback and private addresses are rejected. They are legitimate on local
+    /// networks and in test setups, so they are allowed by default.
+    pub reject_local: bool,
+}
+
+/// Reason an address can't be dialed.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    /// The address IP is unspecified, eg. `0.0.0.0`.
+    #[error("`{0}` is an unspecified address")]
+    Unspecified(Address),
+    /// The address IP is in a multicast range.
+    #[error("`{0}` is a multicast address")]
+    Multicast(Address),
+    /// The address IP is the IPv4 broadcast address.
+    #[error("`{0}` is a broadcast address")]
+    Broadcast(Address),
+    /// The address port is zero.
+    #[error("`{0}` has port zero")]
+    PortZero(Address),
+    /// The address is one we announce for ourselves.
+    #[error("`{0}` is one of our own addresses")]
+    Own(Address),
+    /// The address is in a loopback or private range, and these are rejected.
+    #[error("`{0}` is in a private or loopback range")]
+    Local(Address),
+}
+
 /// Check whether an IPv4 address is globally routable.
 ///
 /// This code is adapted from the Rust standard library's `net::Ipv4Addr::is_global`. It can be
@@ -0,0 +1,2 @@
+-- Whether we aren't able to dial an address, eg. because it requires a proxy.
+alter table "addresses" add column "unsupported" integer default 0;
@@ -37,3 +37,15 @@ create table if not exists "addresses" (
   unique ("node", "type", "value")
   --
 ) strict;
+
+create table if not exists "cobs" (
+  -- Node ID.
+  "node"               text      not null references "nodes" ("id"),
+  -- Type name of a collaborative object the node replicates and serves.
+  "type"               text      not null,
+  --
+  unique ("node", "type")
+  --
+) strict;
+
+-- Columns added later are added by the migrations in `migrations/`.
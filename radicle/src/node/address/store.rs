@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::{fmt, io};
 
@@ -6,6 +6,7 @@ use localtime::LocalTime;
 use sqlite as sql;
 use thiserror::Error;
 
+use crate::cob::TypeName;
 use crate::node;
 use crate::node::address::{KnownAddress, Source};
 use crate::node::{Address, Alias, AliasError, AliasStore, NodeId};
@@ -38,6 +39,9 @@ impl fmt::Debug for Book {
     }
 }
 
+/// Schema migrations, in order. See [`crate::sql::migrate`].
+const MIGRATIONS: &[&str] = &[include_str!("migrations/1.sql")];
+
 impl Book {
     const SCHEMA: &str = include_str!("schema.sql");
 
@@ -46,10 +50,29 @@ impl Book {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let db = sql::Connection::open(path)?;
         db.execute(Self::SCHEMA)?;
+        crate::sql::migrate(&db, MIGRATIONS)?;
 
         Ok(Self { db })
     }
 
+    /// Same as [`Self::open`], but if the address book is found to be corrupt, it is
+    /// moved aside and a fresh one is created in its place. Returns the path the
+    /// corrupt address book was moved to, if any.
+    pub fn open_or_recover<P: AsRef<Path>>(path: P) -> Result<(Self, Option<PathBuf>), Error> {
+        let path = path.as_ref();
+
+        match Self::open(path) {
+            Ok(book) => Ok((book, None)),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e) => {
+                let backup = crate::sql::quarantine(path)?;
+                let book = Self::open(path)?;
+
+                Ok((book, Some(backup)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Same as [`Self::open`], but in read-only mode. This is useful to have multiple
     /// open databases, as no locking is required.
     pub fn reader<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
@@ -63,6 +86,7 @@ impl Book {
     pub fn memory() -> Result<Self, Error> {
         let db = sql::Connection::open(":memory:")?;
         db.execute(Self::SCHEMA)?;
+        crate::sql::migrate(&db, MIGRATIONS)?;
 
         Ok(Self { db })
     }
@@ -99,15 +123,31 @@ impl Store for Book {
                     source,
                     last_success: None,
                     last_attempt: None,
+                    unsupported: false,
                 });
             }
 
+            let mut cobs = Vec::new();
+            let mut stmt = self.db.prepare("SELECT type FROM cobs WHERE node = ?")?;
+            stmt.bind((1, node))?;
+
+            for row in stmt.into_iter() {
+                let row = row?;
+                let name = row.read::<&str, _>("type");
+
+                match TypeName::from_str(name) {
+                    Ok(name) => cobs.push(name),
+                    Err(e) => log::warn!(target: "db", "Skipping invalid COB type of {node}: {e}"),
+                }
+            }
+
             Ok(Some(types::Node {
                 features,
                 alias,
                 pow,
                 timestamp,
                 addrs,
+                cobs,
             }))
         } else {
             Ok(None)
@@ -184,6 +224,11 @@ impl Store for Book {
                 .bind(&[node][..])?
                 .next();
 
+            db.prepare("DELETE FROM cobs WHERE node = ?")?
+                .into_iter()
+                .bind(&[node][..])?
+                .next();
+
             Ok(db.change_count() > 0)
         })
         .map_err(Error::from)
@@ -192,7 +237,10 @@ impl Store for Book {
     fn entries(&self) -> Result<Box<dyn Iterator<Item = (NodeId, KnownAddress)>>, Error> {
         let mut stmt = self
             .db
-            .prepare("SELECT node, type, value, source, last_success, last_attempt FROM addresses ORDER BY node")?
+            .prepare(
+                "SELECT node, type, value, source, last_success, last_attempt, unsupported
+                 FROM addresses ORDER BY node",
+            )?
             .into_iter();
         let mut entries = Vec::new();
 
@@ -205,6 +253,7 @@ impl Store for Book {
             let last_attempt = row.read::<Option<i64>, _>("last_attempt");
             let last_success = last_success.map(|t| LocalTime::from_millis(t as u128));
             let last_attempt = last_attempt.map(|t| LocalTime::from_millis(t as u128));
+            let unsupported = row.read::<i64, _>("unsupported") != 0;
 
             entries.push((
                 node,
@@ -213,16 +262,42 @@ impl Store for Book {
                     source,
                     last_success,
                     last_attempt,
+                    unsupported,
                 },
             ));
         }
         Ok(Box::new(entries.into_iter()))
     }
 
+    fn set_cobs(
+        &mut self,
+        node: &NodeId,
+        cobs: impl IntoIterator<Item = TypeName>,
+    ) -> Result<(), Error> {
+        transaction(&self.db, move |db| {
+            db.prepare("DELETE FROM cobs WHERE node = ?")?
+                .into_iter()
+                .bind(&[node][..])?
+                .next();
+
+            for cob in cobs {
+                let mut stmt = db.prepare(
+                    "INSERT INTO cobs (node, type) VALUES (?1, ?2)
+                     ON CONFLICT DO NOTHING",
+                )?;
+                stmt.bind((1, node))?;
+                stmt.bind((2, cob.as_str()))?;
+                stmt.next()?;
+            }
+            Ok(())
+        })
+        .map_err(Error::from)
+    }
+
     fn attempted(&self, nid: &NodeId, addr: &Address, time: Timestamp) -> Result<(), Error> {
         let mut stmt = self.db.prepare(
             "UPDATE `addresses`
-             SET last_attempt = ?1
+             SET last_attempt = ?1, unsupported = 0
              WHERE node = ?2
              AND type = ?3
              AND value = ?4",
@@ -254,6 +329,23 @@ impl Store for Book {
 
         Ok(())
     }
+
+    fn unsupported(&self, nid: &NodeId, addr: &Address) -> Result<(), Error> {
+        let mut stmt = self.db.prepare(
+            "UPDATE `addresses`
+             SET unsupported = 1
+             WHERE node = ?1
+             AND type = ?2
+             AND value = ?3",
+        )?;
+
+        stmt.bind((1, nid))?;
+        stmt.bind((2, AddressType::from(addr)))?;
+        stmt.bind((3, addr))?;
+        stmt.next()?;
+
+        Ok(())
+    }
 }
 
 impl AliasStore for Book {
@@ -294,10 +386,19 @@ pub trait Store {
     }
     /// Get the address entries in the store.
     fn entries(&self) -> Result<Box<dyn Iterator<Item = (NodeId, KnownAddress)>>, Error>;
+    /// Replace the collaborative object types advertized by a node.
+    fn set_cobs(
+        &mut self,
+        node: &NodeId,
+        cobs: impl IntoIterator<Item = TypeName>,
+    ) -> Result<(), Error>;
     /// Mark a node as attempted at a certain time.
     fn attempted(&self, nid: &NodeId, addr: &Address, time: Timestamp) -> Result<(), Error>;
     /// Mark a node as successfully connected at a certain time.
     fn connected(&self, nid: &NodeId, addr: &Address, time: Timestamp) -> Result<(), Error>;
+    /// Mark an address as unsupported, ie. we aren't able to dial it. This is cleared
+    /// once the address is attempted.
+    fn unsupported(&self, nid: &NodeId, addr: &Address) -> Result<(), Error>;
 }
 
 impl TryFrom<&sql::Value> for Source {
@@ -313,6 +414,7 @@ impl TryFrom<&sql::Value> for Source {
                 "bootstrap" => Ok(Source::Bootstrap),
                 "peer" => Ok(Source::Peer),
                 "imported" => Ok(Source::Imported),
+                "local" => Ok(Source::Local),
                 _ => Err(err),
             },
             _ => Err(err),
@@ -326,6 +428,7 @@ impl sql::BindableWithIndex for Source {
             Self::Bootstrap => "bootstrap".bind(stmt, i),
             Self::Peer => "peer".bind(stmt, i),
             Self::Imported => "imported".bind(stmt, i),
+            Self::Local => "local".bind(stmt, i),
         }
     }
 }
@@ -429,6 +532,7 @@ mod test {
             source: Source::Peer,
             last_success: None,
             last_attempt: None,
+            unsupported: false,
         };
         let inserted = cache
             .insert(
@@ -464,6 +568,7 @@ mod test {
             source: Source::Peer,
             last_success: None,
             last_attempt: None,
+            unsupported: false,
         };
         let inserted = cache
             .insert(&alice, features, alias.clone(), 0, timestamp, [ka.clone()])
@@ -491,6 +596,7 @@ mod test {
             source: Source::Peer,
             last_success: None,
             last_attempt: None,
+            unsupported: false,
         };
 
         let updated = cache
@@ -551,6 +657,7 @@ mod test {
                 source: Source::Peer,
                 last_success: None,
                 last_attempt: None,
+                unsupported: false,
             };
             cache
                 .insert(
@@ -596,6 +703,7 @@ mod test {
                 // TODO: Test times as well.
                 last_success: None,
                 last_attempt: None,
+                unsupported: false,
             };
             expected.push((id, ka.clone()));
             cache
@@ -611,4 +719,118 @@ mod test {
         assert_eq!(cache.len().unwrap(), actual.len());
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_unsupported() {
+        let alice = arbitrary::gen::<NodeId>(1);
+        let mut cache = Book::memory().unwrap();
+        let timestamp = LocalTime::now().as_millis();
+        let addr: Address = net::SocketAddr::from(([4, 4, 4, 4], 8776)).into();
+        let ka = KnownAddress::new(addr.clone(), Source::Peer);
+        let entry = |cache: &Book| cache.entries().unwrap().next().unwrap().1;
+
+        cache
+            .insert(
+                &alice,
+                node::Features::SEED,
+                Alias::new("alice"),
+                0,
+                timestamp,
+                [ka],
+            )
+            .unwrap();
+        assert!(!entry(&cache).unsupported);
+
+        // Marking an address as unsupported isn't an attempt.
+        cache.unsupported(&alice, &addr).unwrap();
+        assert!(entry(&cache).unsupported);
+        assert_eq!(entry(&cache).last_attempt, None);
+
+        cache.attempted(&alice, &addr, timestamp).unwrap();
+        assert!(!entry(&cache).unsupported);
+        assert!(entry(&cache).last_attempt.is_some());
+    }
+
+    #[test]
+    fn test_cobs() {
+        let alice = arbitrary::gen::<NodeId>(1);
+        let mut cache = Book::memory().unwrap();
+        let timestamp = LocalTime::now().as_millis();
+        let issue = TypeName::from_str("xyz.radicle.issue").unwrap();
+        let patch = TypeName::from_str("xyz.radicle.patch").unwrap();
+
+        cache
+            .insert(
+                &alice,
+                node::Features::SEED,
+                Alias::new("alice"),
+                0,
+                timestamp,
+                [],
+            )
+            .unwrap();
+        assert!(cache.get(&alice).unwrap().unwrap().cobs.is_empty());
+
+        cache
+            .set_cobs(&alice, [issue.clone(), patch.clone()])
+            .unwrap();
+        let mut cobs = cache.get(&alice).unwrap().unwrap().cobs;
+        cobs.sort();
+        assert_eq!(cobs, vec![issue.clone(), patch]);
+
+        // Advertized types are replaced, and not added to.
+        cache.set_cobs(&alice, [issue.clone()]).unwrap();
+        assert_eq!(cache.get(&alice).unwrap().unwrap().cobs, vec![issue]);
+
+        cache.remove(&alice).unwrap();
+        cache
+            .insert(
+                &alice,
+                node::Features::SEED,
+                Alias::new("alice"),
+                0,
+                timestamp,
+                [],
+            )
+            .unwrap();
+        assert!(cache.get(&alice).unwrap().unwrap().cobs.is_empty());
+    }
+
+    #[test]
+    fn test_migration() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("addresses.db");
+        let alice = arbitrary::gen::<NodeId>(1);
+        let timestamp = LocalTime::now().as_millis();
+
+        // An address book created before any migration.
+        {
+            let db = sql::Connection::open(&path).unwrap();
+            db.execute(Book::SCHEMA).unwrap();
+        }
+        let mut cache = Book::open(&path).unwrap();
+        let addr: Address = net::SocketAddr::from(([4, 4, 4, 4], 8776)).into();
+        let ka = KnownAddress::new(addr.clone(), Source::Peer);
+
+        cache
+            .insert(
+                &alice,
+                node::Features::SEED,
+                Alias::new("alice"),
+                0,
+                timestamp,
+                [ka],
+            )
+            .unwrap();
+        cache.unsupported(&alice, &addr).unwrap();
+
+        let (_, entry) = cache.entries().unwrap().next().unwrap();
+        assert!(entry.unsupported);
+        drop(cache);
+
+        // Migrations are only applied once.
+        let cache = Book::open(&path).unwrap();
+        let (_, entry) = cache.entries().unwrap().next().unwrap();
+        assert!(entry.unsupported);
+    }
 }
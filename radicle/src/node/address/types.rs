@@ -5,6 +5,7 @@ use std::ops::{Deref, DerefMut};
 use localtime::LocalTime;
 use nonempty::NonEmpty;
 
+use crate::cob::TypeName;
 use crate::collections::RandomMap;
 use crate::node;
 use crate::node::{Address, Alias};
@@ -102,6 +103,8 @@ pub struct Node {
     pub pow: u32,
     /// When this data was published.
     pub timestamp: Timestamp,
+    /// Advertized collaborative object types, if any.
+    pub cobs: Vec<TypeName>,
 }
 
 /// A known address.
@@ -116,6 +119,11 @@ pub struct KnownAddress {
     pub last_success: Option<LocalTime>,
     /// Last time this address was tried.
     pub last_attempt: Option<LocalTime>,
+    /// Whether we skipped this address because we aren't able to dial it, eg. because it
+    /// requires a proxy and none is configured. Unlike a failed attempt, this doesn't
+    /// delay further attempts once we are able to dial it.
+    #[serde(default)]
+    pub unsupported: bool,
 }
 
 impl KnownAddress {
@@ -126,6 +134,7 @@ impl KnownAddress {
             source,
             last_success: None,
             last_attempt: None,
+            unsupported: false,
         }
     }
 }
@@ -141,6 +150,8 @@ pub enum Source {
     /// An address that came from some source external to the system, eg.
     /// specified by the user or added directly to the address manager.
     Imported,
+    /// An address that was discovered on the local network.
+    Local,
 }
 
 impl std::fmt::Display for Source {
@@ -149,6 +160,7 @@ impl std::fmt::Display for Source {
             Self::Peer => write!(f, "Peer"),
             Self::Bootstrap => write!(f, "Bootstrap"),
             Self::Imported => write!(f, "Imported"),
+            Self::Local => write!(f, "Local"),
         }
     }
 }
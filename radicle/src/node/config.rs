@@ -1,10 +1,17 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::net;
 use std::ops::Deref;
 
-use cyphernet::addr::PeerAddr;
+use cyphernet::addr::{Addr as _, HostName, PeerAddr};
 use localtime::LocalDuration;
+use thiserror::Error;
 
+use crate::cob;
+use crate::cob::TypeName;
 use crate::node;
+use crate::node::address;
+use crate::node::control;
+use crate::node::events::EventKind;
 use crate::node::tracking::{Policy, Scope};
 use crate::node::{Address, Alias, NodeId};
 
@@ -48,7 +55,7 @@ impl Network {
 
 /// Configuration parameters defining attributes of minima and maxima.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(default, rename_all = "camelCase")]
 pub struct Limits {
     /// Number of routing table entries before we start pruning.
     pub routing_max_size: usize,
@@ -57,6 +64,56 @@ pub struct Limits {
     pub routing_max_age: LocalDuration,
     /// Maximum number of concurrent fetches per per connection.
     pub fetch_concurrency: usize,
+    /// Order in which the queued fetches of a connection are carried out.
+    pub fetch_order: FetchOrder,
+    /// How long a queued fetch can wait before it is carried out ahead of smaller ones,
+    /// when fetches are carried out in [`FetchOrder::Fair`] order.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub fetch_max_wait: LocalDuration,
+    /// Maximum number of outstanding fetches requested by users, eg. via the control
+    /// socket. Further requests are rejected until some complete.
+    pub fetch_max_requests: usize,
+    /// Maximum number of missing repositories fetched each time the inventory is synced.
+    /// The others are fetched in later syncs.
+    pub fetch_missing_max: usize,
+    /// Number of announcers kept in the gossip store before we start evicting.
+    pub gossip_max_nodes: usize,
+    /// Number of refs announcements kept per announcer before we start evicting.
+    pub gossip_max_refs: usize,
+    /// Maximum number of bytes per second spent relaying third-party announcements.
+    /// Our own announcements and fetches are not counted. Unlimited if not set.
+    pub relay_rate: Option<usize>,
+    /// How often our node announcement is refreshed and re-announced to connected peers,
+    /// so that it isn't pruned by nodes which have been online for longer.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub node_announce_interval: LocalDuration,
+    /// Maximum number of bytes received from a seed in a single fetch. Fetches that go over
+    /// the limit are aborted, and the seed is disconnected. Can be overridden per repository,
+    /// in the repository's tracking policy.
+    pub fetch_pack_max_bytes: FetchPackLimit,
+    /// Limits on the shape of the namespaces fetched from seeds. Namespaces that go over
+    /// these limits are not transferred into storage.
+    pub fetch_shape: ShapeLimits,
+    /// How long an event subscription on the control socket can be idle before the node
+    /// sends a heartbeat, so that clients can tell a quiet node from one that went away.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub subscribe_heartbeat: LocalDuration,
+    /// How long a connected peer can go without answering us, or transferring data with us,
+    /// before it is pinged.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub keep_alive: LocalDuration,
+    /// How long a peer has to answer our ping before it is disconnected. On slow links,
+    /// the timeout is raised according to the round-trip time measured for the peer.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub stale_timeout: LocalDuration,
+    /// How long a peer disconnected by the user isn't automatically reconnected to,
+    /// unless the user connects to it again.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub disconnect_cooloff: LocalDuration,
+    /// How long the session of a disconnected peer is kept, unless the peer is persistent.
+    /// Persistent peers we gave up reconnecting to are only retried once this long.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub session_retention: LocalDuration,
 }
 
 impl Default for Limits {
@@ -65,10 +122,148 @@ impl Default for Limits {
             routing_max_size: 1000,
             routing_max_age: LocalDuration::from_mins(7 * 24 * 60),
             fetch_concurrency: 1,
+            fetch_order: FetchOrder::default(),
+            fetch_max_wait: LocalDuration::from_mins(5),
+            fetch_max_requests: 256,
+            fetch_missing_max: 8,
+            gossip_max_nodes: 1000,
+            gossip_max_refs: 1000,
+            relay_rate: None,
+            node_announce_interval: LocalDuration::from_mins(24 * 60),
+            fetch_pack_max_bytes: FetchPackLimit::default(),
+            fetch_shape: ShapeLimits::default(),
+            subscribe_heartbeat: LocalDuration::from_secs(5),
+            keep_alive: LocalDuration::from_mins(1),
+            stale_timeout: LocalDuration::from_mins(2),
+            disconnect_cooloff: LocalDuration::from_mins(60),
+            session_retention: LocalDuration::from_mins(24 * 60),
+        }
+    }
+}
+
+/// Error setting a limit at runtime.
+#[derive(Error, Debug)]
+pub enum LimitError {
+    /// The limit doesn't exist, or can't be changed at runtime.
+    #[error("unknown or read-only limit `{0}`")]
+    Unknown(String),
+    /// The value isn't valid for this limit.
+    #[error("invalid value for limit `{name}`: {reason}")]
+    Invalid { name: String, reason: String },
+}
+
+impl Limits {
+    /// Limits that can be changed at runtime, by their configuration name.
+    pub const RUNTIME: &'static [&'static str] = &[
+        "routingMaxSize",
+        "routingMaxAge",
+        "fetchConcurrency",
+        "fetchMaxWait",
+        "gossipMaxNodes",
+        "gossipMaxRefs",
+        "relayRate",
+        "keepAlive",
+        "staleTimeout",
+    ];
+
+    /// Set one of the [`Limits::RUNTIME`] limits, given its configuration name and value.
+    /// String values are parsed as JSON first, so that values from the command line can be
+    /// passed as-is.
+    pub fn set(&mut self, name: &str, value: serde_json::Value) -> Result<(), LimitError> {
+        if !Self::RUNTIME.contains(&name) {
+            return Err(LimitError::Unknown(name.to_owned()));
+        }
+        let invalid = |reason: String| LimitError::Invalid {
+            name: name.to_owned(),
+            reason,
+        };
+        let value = match value {
+            serde_json::Value::String(s) => {
+                serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s))
+            }
+            other => other,
+        };
+        let mut limits = serde_json::to_value(&*self).map_err(|e| invalid(e.to_string()))?;
+        limits[name] = value;
+
+        let limits: Self = serde_json::from_value(limits).map_err(|e| invalid(e.to_string()))?;
+        if limits.fetch_concurrency == 0 {
+            return Err(invalid(String::from("must be at least 1")));
+        }
+        if limits.relay_rate == Some(0) {
+            return Err(invalid(String::from("must be at least 1, or `null`")));
+        }
+        if limits.keep_alive.as_secs() == 0 || limits.stale_timeout.as_secs() == 0 {
+            return Err(invalid(String::from("must be at least 1 second")));
+        }
+        *self = limits;
+
+        Ok(())
+    }
+}
+
+/// Maximum size of a fetch, in bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FetchPackLimit {
+    /// Limit when cloning a repository we don't have yet, possibly from an untrusted seed.
+    pub clone: u64,
+    /// Limit when fetching updates to a repository we already have.
+    pub update: u64,
+}
+
+impl FetchPackLimit {
+    /// Get the limit of a fetch, depending on whether we're cloning.
+    pub fn get(&self, cloning: bool) -> u64 {
+        if cloning {
+            self.clone
+        } else {
+            self.update
+        }
+    }
+}
+
+impl Default for FetchPackLimit {
+    fn default() -> Self {
+        Self {
+            clone: 1024 * 1024 * 1024,
+            update: 8 * 1024 * 1024 * 1024,
         }
     }
 }
 
+/// Limits on the shape of a fetched namespace, checked before its refs are transferred
+/// into storage.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ShapeLimits {
+    /// Maximum number of refs in a namespace.
+    pub max_refs: usize,
+    /// Maximum length of a ref name, in bytes, not counting the namespace prefix.
+    pub max_ref_name_len: usize,
+}
+
+impl Default for ShapeLimits {
+    fn default() -> Self {
+        Self {
+            max_refs: 100_000,
+            max_ref_name_len: 1024,
+        }
+    }
+}
+
+/// Order in which queued fetches are carried out.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FetchOrder {
+    /// In the order they were queued.
+    Fifo,
+    /// Shortest estimated fetch first, based on previous fetches of the same repository.
+    /// Fetches that waited for too long are carried out first, in the order they were queued.
+    #[default]
+    Fair,
+}
+
 /// Full address used to connect to a remote node.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Hash)]
 #[serde(transparent)]
@@ -106,6 +301,23 @@ impl Deref for ConnectAddress {
     }
 }
 
+/// Peer configured by the operator, under the `peer` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerEntry {
+    /// Node ID of the peer.
+    pub id: NodeId,
+    /// Address of the peer.
+    pub address: Address,
+    /// Alias of the peer, recorded in the address book.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<Alias>,
+    /// Whether the connection to the peer is maintained. Other peers are connected to on
+    /// startup, but not reconnected to.
+    #[serde(default)]
+    pub persistent: bool,
+}
+
 /// Peer configuration.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase", tag = "type")]
@@ -124,6 +336,132 @@ impl Default for PeerConfig {
     }
 }
 
+/// Read-only HTTP interface configuration. This interface allows anyone who can reach it
+/// to query the node's status, and what it knows about repositories.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Http {
+    /// Address to listen on. The interface is disabled if not set.
+    pub listen: Option<net::SocketAddr>,
+    /// Whether connected peers are listed, instead of only counted.
+    pub list_peers: bool,
+    /// Number of requests a client IP can make per minute.
+    pub rate_limit: usize,
+}
+
+impl Default for Http {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            list_peers: false,
+            rate_limit: 60,
+        }
+    }
+}
+
+/// Local network peer discovery configuration. When enabled, the node periodically
+/// multicasts a signed beacon with its node id and listen port, and adds the nodes it
+/// hears from to its address book, from where they are dialed like any other peer.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Discovery {
+    /// Whether discovery is enabled. Disabled by default, on all networks.
+    pub enabled: bool,
+    /// Multicast group and port beacons are sent to and received on.
+    pub group: net::SocketAddrV4,
+    /// Local interface used to join the multicast group. Unspecified means the
+    /// system's default interface.
+    pub interface: net::Ipv4Addr,
+    /// How often our beacon is sent.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub interval: LocalDuration,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            group: net::SocketAddrV4::new(net::Ipv4Addr::new(239, 255, 77, 76), 8777),
+            interface: net::Ipv4Addr::UNSPECIFIED,
+            interval: LocalDuration::from_secs(30),
+        }
+    }
+}
+
+/// Which of our peers we subscribe to gossip from.
+///
+/// Subscribing to a peer means it will send us its gossip backlog, and relay gossip to us
+/// from then on. Seeds with many inbound connections should use
+/// [`SubscribePolicy::OutboundOnly`].
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscribePolicy {
+    /// Subscribe to all peers.
+    #[default]
+    All,
+    /// Subscribe to the peers we connect to. When tracking selectively, we also subscribe
+    /// to the peers that connect to us, to the repositories we track only, so that we keep
+    /// receiving their updates.
+    OutboundOnly,
+    /// Don't subscribe to any peer.
+    None,
+}
+
+/// Durable event log configuration. Logged events are kept across restarts, and can be
+/// read back by sequence number, including those emitted while no one was subscribed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EventLog {
+    /// Kinds of events to log. Nothing is logged if empty.
+    pub events: BTreeSet<EventKind>,
+    /// How long to keep a logged event before it is pruned.
+    #[serde(with = "crate::serde_ext::localtime::duration")]
+    pub max_age: LocalDuration,
+    /// Number of logged events kept before the oldest are pruned.
+    pub max_size: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            events: BTreeSet::new(),
+            max_age: LocalDuration::from_mins(7 * 24 * 60),
+            max_size: 100_000,
+        }
+    }
+}
+
+/// Activity log configuration. The activity log keeps the node's recent activity in
+/// memory, for operators to query over the control socket.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ActivityLog {
+    /// Whether activity is recorded.
+    pub enabled: bool,
+    /// Number of entries kept before the oldest are dropped.
+    pub max_size: usize,
+}
+
+impl Default for ActivityLog {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size: 4096,
+        }
+    }
+}
+
+impl ActivityLog {
+    /// Number of entries kept, taking into account whether the log is enabled.
+    pub fn capacity(&self) -> usize {
+        if self.enabled {
+            self.max_size
+        } else {
+            0
+        }
+    }
+}
+
 /// Service configuration.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -137,6 +475,10 @@ pub struct Config {
     /// Connections to these peers will be maintained.
     #[serde(default)]
     pub connect: HashSet<ConnectAddress>,
+    /// Peers to connect to on startup, with an alias, and whether connections to them
+    /// are maintained.
+    #[serde(default, rename = "peer", skip_serializing_if = "Vec::is_empty")]
+    pub peer_entries: Vec<PeerEntry>,
     /// Specify the node's public addresses
     #[serde(default)]
     pub external_addresses: Vec<Address>,
@@ -155,6 +497,66 @@ pub struct Config {
     /// Default tracking scope.
     #[serde(default)]
     pub scope: Scope,
+    /// Which peers to subscribe to gossip from.
+    #[serde(default)]
+    pub subscribe: SubscribePolicy,
+    /// Transport used by the control interface.
+    #[serde(default)]
+    pub control: control::Transport,
+    /// Highest permission level that control clients can be granted. Commands that have the
+    /// node sign changes on the client's behalf, such as COB updates, require operator
+    /// permission. If it can be granted, the node writes an operator token to its directory
+    /// on startup, and grants operator permission to the clients that send it.
+    #[serde(default)]
+    pub control_permission: control::Permission,
+    /// Read-only HTTP interface.
+    #[serde(default)]
+    pub http: Http,
+    /// Local network peer discovery.
+    #[serde(default)]
+    pub discovery: Discovery,
+    /// Durable event log.
+    #[serde(default)]
+    pub event_log: EventLog,
+    /// In-memory activity log.
+    #[serde(default)]
+    pub activity_log: ActivityLog,
+    /// Whether service metrics are collected. Metrics are counters of what the node did
+    /// since it started, queried over the control socket.
+    #[serde(default)]
+    pub metrics: bool,
+    /// Collaborative object types this node replicates and serves, advertized to peers
+    /// so that they can prefer it for operations that need these types.
+    #[serde(default = "default_cobs")]
+    pub cobs: Vec<TypeName>,
+    /// Nodes to mirror. Repositories announced by these nodes are tracked and fetched
+    /// automatically. Announcements they merely relay are not taken into account.
+    #[serde(default)]
+    pub mirror: Vec<NodeId>,
+    /// Tracking scope of mirrored repositories.
+    #[serde(default = "default_mirror_scope")]
+    pub mirror_scope: Scope,
+    /// How long a mirrored repository can be missing from the inventories of the nodes we
+    /// mirror before it is untracked. Mirrored repositories are never untracked if not set.
+    #[serde(
+        default,
+        with = "crate::serde_ext::localtime::duration::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mirror_expiry: Option<LocalDuration>,
+    /// Whether corrupt databases found on startup should be moved aside and replaced
+    /// with empty ones, instead of failing to start. Recovered databases are reported
+    /// by the `recovery` command.
+    #[serde(default = "crate::serde_ext::bool::yes")]
+    pub recover_databases: bool,
+    /// SOCKS5 proxy used to connect to addresses that require one, eg. Tor onion
+    /// addresses. Such addresses are skipped if not set.
+    #[serde(default)]
+    pub proxy: Option<net::SocketAddr>,
+    /// Whether loopback and private addresses can be dialed. If so, dialing them only
+    /// logs a warning.
+    #[serde(default = "crate::serde_ext::bool::yes")]
+    pub allow_local_addresses: bool,
 }
 
 impl Config {
@@ -170,22 +572,86 @@ impl Config {
             alias,
             peers: PeerConfig::default(),
             connect: HashSet::default(),
+            peer_entries: Vec::new(),
             external_addresses: vec![],
             network: Network::default(),
             relay: true,
             limits: Limits::default(),
             policy: Policy::default(),
             scope: Scope::default(),
+            subscribe: SubscribePolicy::default(),
+            control: control::Transport::default(),
+            control_permission: control::Permission::default(),
+            http: Http::default(),
+            discovery: Discovery::default(),
+            event_log: EventLog::default(),
+            activity_log: ActivityLog::default(),
+            metrics: false,
+            cobs: default_cobs(),
+            mirror: Vec::new(),
+            mirror_scope: default_mirror_scope(),
+            mirror_expiry: None,
+            recover_databases: true,
+            proxy: None,
+            allow_local_addresses: true,
         }
     }
 }
 
+/// Collaborative object types advertized by default.
+pub fn default_cobs() -> Vec<TypeName> {
+    vec![cob::issue::TYPENAME.clone(), cob::patch::TYPENAME.clone()]
+}
+
+/// Tracking scope of mirrored repositories, by default.
+pub fn default_mirror_scope() -> Scope {
+    Scope::All
+}
+
 impl Config {
+    /// Get the address of a persistent peer.
     pub fn peer(&self, id: &NodeId) -> Option<&Address> {
         self.connect
             .iter()
             .find(|ca| &ca.id == id)
             .map(|ca| &ca.addr)
+            .or_else(|| {
+                self.peer_entries
+                    .iter()
+                    .find(|p| &p.id == id && p.persistent)
+                    .map(|p| &p.address)
+            })
+    }
+
+    /// Peers to connect to on startup, whether persistent or not.
+    pub fn startup_peers(&self) -> impl Iterator<Item = (NodeId, Address)> + '_ {
+        self.connect
+            .iter()
+            .map(|ca| (ca.id, ca.addr.clone()))
+            .chain(self.peer_entries.iter().map(|p| (p.id, p.address.clone())))
+    }
+
+    /// Add a peer entry, replacing the entry of the same peer if any.
+    /// Returns `true` if the peer wasn't configured.
+    pub fn add_peer(&mut self, peer: PeerEntry) -> bool {
+        if let Some(entry) = self.peer_entries.iter_mut().find(|p| p.id == peer.id) {
+            *entry = peer;
+            return false;
+        }
+        self.peer_entries.push(peer);
+
+        true
+    }
+
+    /// Remove a peer, whether it was configured with a peer entry or a connect address.
+    /// Returns `true` if the peer was configured.
+    pub fn remove_peer(&mut self, id: &NodeId) -> bool {
+        let (entries, connect) = (self.peer_entries.len(), self.connect.len());
+
+        self.peer_entries.retain(|p| &p.id != id);
+        self.connect.retain(|ca| &ca.id != id);
+
+        entries != self.peer_entries.len() || connect != self.connect.len()
     }
 
     pub fn is_persistent(&self, id: &NodeId) -> bool {
@@ -193,6 +659,225 @@ impl Config {
     }
 
     pub fn features(&self) -> node::Features {
-        node::Features::SEED
+        node::Features::SEED.with(node::Features::PULL)
+    }
+
+    /// Get the context addresses are validated in before they are dialed.
+    pub fn address_context(&self) -> address::Context<'_> {
+        address::Context {
+            own: &self.external_addresses,
+            reject_local: !self.allow_local_addresses,
+        }
+    }
+}
+
+/// External address validation options.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Validation {
+    /// Exclude external addresses that fail validation, instead of only warning about them.
+    pub strict: bool,
+    /// Allow external addresses with a port that differs from the listening ports, eg.
+    /// when a different port is forwarded to the node.
+    pub allow_port_mismatch: bool,
+}
+
+/// Problem found with a configured external address.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The address is in a private or loopback range, and isn't reachable from the network.
+    #[error("external address `{0}` is in a private or loopback range")]
+    Private(Address),
+    /// The address port doesn't match any of the listening ports.
+    #[error("external address `{0}` port doesn't match any of the listening ports")]
+    PortMismatch(Address),
+    /// The address host name doesn't resolve.
+    #[error("external address `{0}` does not resolve")]
+    Unresolved(Address),
+}
+
+impl Warning {
+    /// The external address this warning is about.
+    pub fn address(&self) -> &Address {
+        match self {
+            Self::Private(addr) | Self::PortMismatch(addr) | Self::Unresolved(addr) => addr,
+        }
+    }
+}
+
+impl Config {
+    /// Validate the configured external addresses.
+    ///
+    /// If `listen` is given, external address ports are checked against the listening
+    /// ports. Host names are resolved with `resolve`, which should return whether the
+    /// given host and port resolve to at least one address.
+    pub fn validate(
+        &self,
+        listen: Option<&[net::SocketAddr]>,
+        validation: Validation,
+        resolve: impl Fn(&str, u16) -> bool,
+    ) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+
+        for addr in &self.external_addresses {
+            match &addr.host {
+                HostName::Ip(ip) if self.network == Network::Main => {
+                    if ip.is_loopback() || ip.is_unspecified() || !address::is_routable(ip) {
+                        warnings.push(Warning::Private(addr.clone()));
+                    }
+                }
+                HostName::Dns(host) => {
+                    if !resolve(host, addr.port()) {
+                        warnings.push(Warning::Unresolved(addr.clone()));
+                    }
+                }
+                _ => {}
+            }
+            if let Some(listen) = listen {
+                if !validation.allow_port_mismatch
+                    && !listen.iter().any(|l| l.port() == addr.port())
+                {
+                    warnings.push(Warning::PortMismatch(addr.clone()));
+                }
+            }
+        }
+        warnings
+    }
+}
+
+/// Resolve a host name using the system resolver. Returns whether at least one address
+/// was found.
+pub fn resolve(host: &str, port: u16) -> bool {
+    use std::net::ToSocketAddrs as _;
+
+    (host, port)
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.next().is_some())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn config(network: Network, addrs: &[&str]) -> Config {
+        Config {
+            network,
+            external_addresses: addrs
+                .iter()
+                .map(|a| Address::from_str(a).unwrap())
+                .collect(),
+            ..Config::new(Alias::new("alice"))
+        }
+    }
+
+    fn addr(s: &str) -> Address {
+        Address::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_validate_private() {
+        let cfg = config(
+            Network::Main,
+            &[
+                "192.168.1.2:8776",
+                "127.0.0.1:8776",
+                "10.0.0.1:8776",
+                "1.1.1.1:8776",
+            ],
+        );
+        let warnings = cfg.validate(None, Validation::default(), |_, _| true);
+
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::Private(addr("192.168.1.2:8776")),
+                Warning::Private(addr("127.0.0.1:8776")),
+                Warning::Private(addr("10.0.0.1:8776")),
+            ]
+        );
+        // Private addresses are fine on other networks.
+        let cfg = Config {
+            network: Network::Test,
+            ..cfg
+        };
+        assert!(cfg
+            .validate(None, Validation::default(), |_, _| true)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_port() {
+        let cfg = config(Network::Main, &["1.1.1.1:8776", "1.1.1.1:443"]);
+        let listen = [net::SocketAddr::from(([0, 0, 0, 0], 8776))];
+
+        assert_eq!(
+            cfg.validate(Some(&listen), Validation::default(), |_, _| true),
+            vec![Warning::PortMismatch(addr("1.1.1.1:443"))]
+        );
+        assert!(cfg
+            .validate(
+                Some(&listen),
+                Validation {
+                    allow_port_mismatch: true,
+                    ..Validation::default()
+                },
+                |_, _| true
+            )
+            .is_empty());
+        // Without listeners to compare to, ports aren't checked.
+        assert!(cfg
+            .validate(None, Validation::default(), |_, _| true)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_dns() {
+        let cfg = config(
+            Network::Main,
+            &["seed.example.com:8776", "seed.example.org:8776"],
+        );
+        let warnings = cfg.validate(None, Validation::default(), |host, port| {
+            host == "seed.example.com" && port == 8776
+        });
+
+        assert_eq!(
+            warnings,
+            vec![Warning::Unresolved(addr("seed.example.org:8776"))]
+        );
+    }
+
+    #[test]
+    fn test_limits_set() {
+        let mut limits = Limits::default();
+
+        limits.set("fetchConcurrency", json!(4)).unwrap();
+        limits.set("fetchMaxWait", json!("60")).unwrap();
+        limits.set("relayRate", json!(1024)).unwrap();
+        assert_eq!(limits.fetch_concurrency, 4);
+        assert_eq!(limits.fetch_max_wait, LocalDuration::from_secs(60));
+        assert_eq!(limits.relay_rate, Some(1024));
+
+        limits.set("relayRate", json!(null)).unwrap();
+        assert_eq!(limits.relay_rate, None);
+
+        assert!(matches!(
+            limits.set("fetchConcurrency", json!(0)),
+            Err(LimitError::Invalid { .. })
+        ));
+        assert!(matches!(
+            limits.set("fetchConcurrency", json!("many")),
+            Err(LimitError::Invalid { .. })
+        ));
+        assert!(matches!(
+            limits.set("staleTimeout", json!(0)),
+            Err(LimitError::Invalid { .. })
+        ));
+        assert!(matches!(
+            limits.set("announceBuckets", json!(1)),
+            Err(LimitError::Unknown(_))
+        ));
+        assert_eq!(limits.fetch_concurrency, 4);
     }
 }
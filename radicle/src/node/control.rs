@@ -0,0 +1,337 @@
+//! Transports for the node control interface.
+//!
+//! On Unix-like systems, the node listens for commands on a Unix domain socket. Where Unix
+//! sockets aren't available, the node listens on a TCP port bound to localhost instead.
+//! Since any local user can connect to such a port, clients must first authenticate with a
+//! secret token. The listener address and token are written to a file under the node
+//! directory, which clients read to connect.
+//!
+//! Whatever the transport, clients are granted [`Permission::User`], unless they send the
+//! [`OperatorToken`] on its own line before their command.
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io, net, time};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto;
+
+/// Default name of the file holding the address and token of the TCP control listener.
+pub const DEFAULT_ENDPOINT_NAME: &str = "control.json";
+/// Default name of the file holding the operator token.
+pub const DEFAULT_OPERATOR_TOKEN_NAME: &str = "control.operator";
+
+/// Control transport selection.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Transport {
+    /// Choose the transport based on the platform.
+    #[default]
+    Auto,
+    /// Unix domain socket.
+    Unix,
+    /// TCP listener bound to localhost, with token authentication.
+    Tcp,
+}
+
+impl Transport {
+    /// Resolve [`Transport::Auto`] to the transport supported by the current platform.
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto if cfg!(unix) => Self::Unix,
+            Self::Auto => Self::Tcp,
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Unix => write!(f, "unix"),
+            Self::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// Permission level granted to control clients. Levels are ordered, each granting the
+/// commands of the levels below it.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    /// Query the node, and manage its connections and tracking policies.
+    #[default]
+    User,
+    /// Also have the node write to storage on the client's behalf, signing with the
+    /// node's key.
+    Operator,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::Operator => write!(f, "operator"),
+        }
+    }
+}
+
+/// Address and authentication token of a TCP control listener.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    /// Address the control listener is bound to.
+    pub addr: net::SocketAddr,
+    /// Token clients must send before any command.
+    pub token: String,
+}
+
+impl Endpoint {
+    /// Create a new endpoint for the given address, with a freshly generated token.
+    pub fn new(addr: net::SocketAddr) -> Self {
+        Self {
+            addr,
+            token: generate_token(),
+        }
+    }
+
+    /// Read an endpoint from a file.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let endpoint = serde_json::from_reader(file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(endpoint)
+    }
+
+    /// Write this endpoint to a file, readable only by the current user.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut file = create_private(path)?;
+
+        serde_json::to_writer(&mut file, self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        file.write_all(b"\n")?;
+        file.sync_all()
+    }
+}
+
+/// Token granting [`Permission::Operator`] to the control clients that send it. It is only
+/// created by nodes configured to grant operator permission.
+#[derive(Clone, PartialEq, Eq)]
+pub struct OperatorToken(String);
+
+impl OperatorToken {
+    /// Generate a new token.
+    pub fn generate() -> Self {
+        Self(generate_token())
+    }
+
+    /// Read a token from a file.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let token = fs::read_to_string(path)?;
+
+        Ok(Self(token.trim_end().to_owned()))
+    }
+
+    /// Write this token to a file, readable only by the current user.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut file = create_private(path)?;
+
+        writeln!(file, "{}", self.0)?;
+        file.sync_all()
+    }
+
+    /// Get the token as a string, to send it.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Debug for OperatorToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OperatorToken(..)")
+    }
+}
+
+/// Generate a random token, hex-encoded.
+fn generate_token() -> String {
+    crypto::Seed::generate()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Create a file for writing, readable only by the current user. Truncates any existing file.
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let file = opts.open(path)?;
+
+    // Nb. The mode is only applied when the file is created, so the permissions of an
+    // existing file are set explicitly, before anything is written to it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
+/// A bidirectional stream to or from the node control interface.
+pub trait Stream: Read + Write + Send {
+    /// Set the read timeout of the stream.
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> io::Result<()>;
+    /// Create a new handle to the same stream.
+    fn try_clone(&self) -> io::Result<Box<dyn Stream>>;
+    /// Shut down both halves of the stream.
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Stream>> {
+        UnixStream::try_clone(self).map(|s| Box::new(s) as Box<dyn Stream>)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        UnixStream::shutdown(self, net::Shutdown::Both)
+    }
+}
+
+impl Stream for net::TcpStream {
+    fn set_read_timeout(&self, timeout: Option<time::Duration>) -> io::Result<()> {
+        net::TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Stream>> {
+        net::TcpStream::try_clone(self).map(|s| Box::new(s) as Box<dyn Stream>)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        net::TcpStream::shutdown(self, net::Shutdown::Both)
+    }
+}
+
+/// Location of the node control interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// Path to a Unix domain socket.
+    Unix(PathBuf),
+    /// Path to an [`Endpoint`] file describing a TCP listener.
+    Tcp(PathBuf),
+}
+
+impl Address {
+    /// Resolve the control address for the given transport, under the node directory.
+    pub fn resolve(transport: Transport, socket: PathBuf, endpoint: PathBuf) -> Self {
+        match transport.resolve() {
+            Transport::Tcp => Self::Tcp(endpoint),
+            Transport::Unix | Transport::Auto => Self::Unix(socket),
+        }
+    }
+
+    /// Path of the socket or endpoint file.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Unix(path) | Self::Tcp(path) => path.as_path(),
+        }
+    }
+
+    /// Connect to the control interface, authenticating if necessary.
+    pub fn connect(&self) -> io::Result<Box<dyn Stream>> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(path) => UnixStream::connect(path).map(|s| Box::new(s) as Box<dyn Stream>),
+            #[cfg(not(unix))]
+            Self::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix sockets are not supported on this platform",
+            )),
+            Self::Tcp(path) => {
+                let endpoint = Endpoint::read(path)?;
+                let mut stream = net::TcpStream::connect(endpoint.addr)?;
+
+                writeln!(stream, "{}", endpoint.token)?;
+
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path().display())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(DEFAULT_ENDPOINT_NAME);
+        let endpoint = Endpoint::new(([127, 0, 0, 1], 8080).into());
+
+        endpoint.write(&path).unwrap();
+
+        assert_eq!(endpoint.token.len(), 64);
+        assert_eq!(Endpoint::read(&path).unwrap(), endpoint);
+        assert_ne!(Endpoint::new(endpoint.addr).token, endpoint.token);
+    }
+
+    #[test]
+    fn test_operator_token_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(DEFAULT_OPERATOR_TOKEN_NAME);
+        let token = OperatorToken::generate();
+
+        token.write(&path).unwrap();
+
+        assert_eq!(token.as_str().len(), 64);
+        assert_eq!(OperatorToken::read(&path).unwrap(), token);
+        assert_ne!(OperatorToken::generate(), token);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_existing_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(DEFAULT_OPERATOR_TOKEN_NAME);
+
+        fs::write(&path, "stale\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let token = OperatorToken::generate();
+        token.write(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(OperatorToken::read(&path).unwrap(), token);
+    }
+
+    #[test]
+    fn test_transport_resolve() {
+        assert_eq!(Transport::Tcp.resolve(), Transport::Tcp);
+        assert_eq!(Transport::Unix.resolve(), Transport::Unix);
+        assert_ne!(Transport::Auto.resolve(), Transport::Auto);
+    }
+}
@@ -1,11 +1,21 @@
+pub mod store;
+
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::time;
 
 use crossbeam_channel as chan;
+use serde::{Deserialize, Serialize};
 
+use crate::node::{tracking, Alias};
 use crate::prelude::*;
 use crate::storage::RefUpdate;
 
+pub use store::Log;
+
+/// Maximum number of logged events returned in a [`Page`].
+pub const PAGE_SIZE: usize = 256;
+
 /// A service event.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase", tag = "type")]
@@ -19,6 +29,13 @@ pub enum Event {
         remote: NodeId,
         rid: Id,
     },
+    /// A peer announced new refs of a watched repository. Since watched repositories are
+    /// never fetched, this is emitted instead of [`Event::RefsFetched`].
+    RefsAnnounced {
+        remote: NodeId,
+        rid: Id,
+        timestamp: Timestamp,
+    },
     SeedDiscovered {
         rid: Id,
         nid: NodeId,
@@ -27,6 +44,10 @@ pub enum Event {
         rid: Id,
         nid: NodeId,
     },
+    InventoryRemoved {
+        rid: Id,
+        peers: usize,
+    },
     PeerConnected {
         nid: NodeId,
     },
@@ -34,6 +55,173 @@ pub enum Event {
         nid: NodeId,
         reason: String,
     },
+    /// Relaying of third-party announcements was paused, to stay within the relay rate,
+    /// in bytes per second.
+    BandwidthThrottled {
+        rate: usize,
+    },
+    /// A limit was changed at runtime, via the control socket.
+    ConfigChanged {
+        name: String,
+        value: serde_json::Value,
+    },
+    /// A repository tracking policy was added, or its scope changed.
+    /// Emitted once the policy is stored.
+    RepoTracked {
+        rid: Id,
+        scope: tracking::Scope,
+        /// Why the repository was tracked.
+        #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+        origin: tracking::Origin,
+    },
+    /// A repository tracking policy was removed. Emitted once the policy is stored.
+    RepoUntracked {
+        rid: Id,
+    },
+    /// A node tracking policy was added, or its alias changed.
+    /// Emitted once the policy is stored.
+    NodeTracked {
+        nid: NodeId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alias: Option<Alias>,
+    },
+    /// A node tracking policy was removed. Emitted once the policy is stored.
+    NodeUntracked {
+        nid: NodeId,
+    },
+    /// The delegates of a repository changed, after fetching a new revision of its
+    /// identity document.
+    DelegatesChanged {
+        rid: Id,
+        added: Vec<NodeId>,
+        removed: Vec<NodeId>,
+    },
+    /// A corrupt database was found on startup. It was moved to `backup`, and an
+    /// empty database was created in its place.
+    DatabaseRecovered {
+        path: PathBuf,
+        backup: PathBuf,
+    },
+    /// A repository in storage could not be opened, or is invalid. It is left out of
+    /// our inventory until repaired. Emitted once per repository.
+    RepoCorrupted {
+        rid: Id,
+        error: String,
+    },
+    /// A database operation failed while handling a message from a peer. The message
+    /// was dropped.
+    StoreError {
+        remote: NodeId,
+        error: String,
+    },
+}
+
+impl Event {
+    /// Get the kind of event this is.
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::RefsFetched { .. } => EventKind::RefsFetched,
+            Self::RefsSynced { .. } => EventKind::RefsSynced,
+            Self::RefsAnnounced { .. } => EventKind::RefsAnnounced,
+            Self::SeedDiscovered { .. } => EventKind::SeedDiscovered,
+            Self::SeedDropped { .. } => EventKind::SeedDropped,
+            Self::InventoryRemoved { .. } => EventKind::InventoryRemoved,
+            Self::PeerConnected { .. } => EventKind::PeerConnected,
+            Self::PeerDisconnected { .. } => EventKind::PeerDisconnected,
+            Self::BandwidthThrottled { .. } => EventKind::BandwidthThrottled,
+            Self::ConfigChanged { .. } => EventKind::ConfigChanged,
+            Self::RepoTracked { .. } => EventKind::RepoTracked,
+            Self::RepoUntracked { .. } => EventKind::RepoUntracked,
+            Self::NodeTracked { .. } => EventKind::NodeTracked,
+            Self::NodeUntracked { .. } => EventKind::NodeUntracked,
+            Self::DelegatesChanged { .. } => EventKind::DelegatesChanged,
+            Self::DatabaseRecovered { .. } => EventKind::DatabaseRecovered,
+            Self::RepoCorrupted { .. } => EventKind::RepoCorrupted,
+            Self::StoreError { .. } => EventKind::StoreError,
+        }
+    }
+}
+
+/// Kind of service event. Used to choose which events are logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventKind {
+    RefsFetched,
+    RefsSynced,
+    RefsAnnounced,
+    SeedDiscovered,
+    SeedDropped,
+    InventoryRemoved,
+    PeerConnected,
+    PeerDisconnected,
+    BandwidthThrottled,
+    ConfigChanged,
+    RepoTracked,
+    RepoUntracked,
+    NodeTracked,
+    NodeUntracked,
+    DelegatesChanged,
+    DatabaseRecovered,
+    RepoCorrupted,
+    StoreError,
+}
+
+impl EventKind {
+    /// Get the event kind as a string, as it appears in the `type` field of events.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RefsFetched => "refsFetched",
+            Self::RefsSynced => "refsSynced",
+            Self::RefsAnnounced => "refsAnnounced",
+            Self::SeedDiscovered => "seedDiscovered",
+            Self::SeedDropped => "seedDropped",
+            Self::InventoryRemoved => "inventoryRemoved",
+            Self::PeerConnected => "peerConnected",
+            Self::PeerDisconnected => "peerDisconnected",
+            Self::BandwidthThrottled => "bandwidthThrottled",
+            Self::ConfigChanged => "configChanged",
+            Self::RepoTracked => "repoTracked",
+            Self::RepoUntracked => "repoUntracked",
+            Self::NodeTracked => "nodeTracked",
+            Self::NodeUntracked => "nodeUntracked",
+            Self::DelegatesChanged => "delegatesChanged",
+            Self::DatabaseRecovered => "databaseRecovered",
+            Self::RepoCorrupted => "repoCorrupted",
+            Self::StoreError => "storeError",
+        }
+    }
+}
+
+/// An event appended to the durable event log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    /// Sequence number of the event. Sequence numbers are increasing, and never reused,
+    /// even once events are pruned.
+    pub seq: u64,
+    /// Local time at which the event was logged.
+    pub time: Timestamp,
+    /// The event.
+    pub event: Event,
+}
+
+/// Logged events following a sequence number. Returned by [`super::Handle::events_since`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page {
+    /// Logged events, in sequence order.
+    pub events: Vec<Record>,
+    /// Whether there are more events following the last one of this page.
+    pub more: bool,
+}
+
+impl Page {
+    /// Sequence number to ask for to get the next page, or `None` if this is the last page.
+    pub fn next(&self) -> Option<u64> {
+        self.more
+            .then(|| self.events.last().map(|r| r.seq))
+            .flatten()
+    }
 }
 
 /// Events feed.
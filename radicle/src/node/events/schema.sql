@@ -0,0 +1,15 @@
+--
+-- Event log SQL schema.
+--
+create table if not exists "events" (
+  -- Sequence number of the event. Never reused, even once the event is pruned.
+  "seq"          integer   primary key autoincrement,
+  -- Kind of event, eg. `refsFetched`.
+  "kind"         text      not null,
+  -- Local UNIX time at which the event was logged.
+  "time"         integer   not null,
+  -- The event, encoded as JSON.
+  "event"        text      not null
+);
+
+create index if not exists "events_time" on "events" ("time");
@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+use std::{fmt, io, time};
+
+use serde_json as json;
+use sqlite as sql;
+use thiserror::Error;
+
+use crate::node::Timestamp;
+
+use super::{Event, Page, Record};
+
+/// How long to wait for the database lock to be released before failing a write.
+const DB_WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(6);
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// I/O error.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// An Internal error.
+    #[error("internal error: {0}")]
+    Internal(#[from] sql::Error),
+    /// An event could not be encoded or decoded.
+    #[error("invalid event: {0}")]
+    Json(#[from] json::Error),
+    /// Internal unit overflow.
+    #[error("the unit overflowed")]
+    UnitOverflow,
+}
+
+/// Durable log of service events. Unlike the live event stream, logged events survive
+/// node restarts, and can be read back by sequence number.
+pub struct Log {
+    db: sql::Connection,
+}
+
+impl fmt::Debug for Log {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Log(..)")
+    }
+}
+
+impl Log {
+    const SCHEMA: &str = include_str!("schema.sql");
+
+    /// Open an event log at the given path. Creates a new empty log if an existing
+    /// one isn't found.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut db = sql::Connection::open(path)?;
+        db.set_busy_timeout(DB_WRITE_TIMEOUT.as_millis() as usize)?;
+        db.execute(Self::SCHEMA)?;
+
+        Ok(Self { db })
+    }
+
+    /// Same as [`Self::open`], but if the log is found to be corrupt, it is moved
+    /// aside and a fresh, empty log is created in its place. Returns the path the
+    /// corrupt log was moved to, if any.
+    pub fn open_or_recover<P: AsRef<Path>>(path: P) -> Result<(Self, Option<PathBuf>), Error> {
+        let path = path.as_ref();
+
+        match Self::open(path) {
+            Ok(log) => Ok((log, None)),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e) => {
+                let backup = crate::sql::quarantine(path)?;
+                let log = Self::open(path)?;
+
+                Ok((log, Some(backup)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a new in-memory event log.
+    pub fn memory() -> Result<Self, Error> {
+        let db = sql::Connection::open(":memory:")?;
+        db.execute(Self::SCHEMA)?;
+
+        Ok(Self { db })
+    }
+
+    /// Append an event to the log. Returns its sequence number.
+    pub fn append(&mut self, event: &Event, time: Timestamp) -> Result<u64, Error> {
+        let time: i64 = time.try_into().map_err(|_| Error::UnitOverflow)?;
+        let mut stmt = self
+            .db
+            .prepare("INSERT INTO events (kind, time, event) VALUES (?, ?, ?)")?;
+
+        stmt.bind((1, event.kind().as_str()))?;
+        stmt.bind((2, time))?;
+        stmt.bind((3, json::to_string(event)?.as_str()))?;
+        stmt.next()?;
+
+        let mut stmt = self.db.prepare("SELECT last_insert_rowid()")?;
+        stmt.next()?;
+
+        Ok(stmt.read::<i64, _>(0)? as u64)
+    }
+
+    /// Get up to `limit` events following the given sequence number, in sequence order.
+    pub fn since(&self, seq: u64, limit: usize) -> Result<Page, Error> {
+        let seq: i64 = seq.try_into().map_err(|_| Error::UnitOverflow)?;
+        let mut stmt = self
+            .db
+            .prepare("SELECT seq, time, event FROM events WHERE seq > ? ORDER BY seq LIMIT ?")?;
+
+        stmt.bind((1, seq))?;
+        // Nb. We ask for one more event than the limit, to know whether there are more.
+        stmt.bind((2, limit as i64 + 1))?;
+
+        let mut page = Page::default();
+        for row in stmt.into_iter() {
+            let row = row?;
+
+            if page.events.len() == limit {
+                page.more = true;
+                break;
+            }
+            page.events.push(Record {
+                seq: row.read::<i64, _>("seq") as u64,
+                time: row.read::<i64, _>("time") as Timestamp,
+                event: json::from_str(row.read::<&str, _>("event"))?,
+            });
+        }
+        Ok(page)
+    }
+
+    /// Prune events logged before `oldest`, and the oldest events in excess of `max`.
+    /// Returns the number of events pruned.
+    pub fn prune(&mut self, oldest: Timestamp, max: usize) -> Result<usize, Error> {
+        let oldest: i64 = oldest.try_into().map_err(|_| Error::UnitOverflow)?;
+        let mut stmt = self.db.prepare("DELETE FROM events WHERE time < ?")?;
+
+        stmt.bind((1, oldest))?;
+        stmt.next()?;
+
+        let mut pruned = self.db.change_count();
+        let mut stmt = self.db.prepare(
+            "DELETE FROM events WHERE seq NOT IN
+            (SELECT seq FROM events ORDER BY seq DESC LIMIT ?)",
+        )?;
+
+        stmt.bind((1, max as i64))?;
+        stmt.next()?;
+        pruned += self.db.change_count();
+
+        Ok(pruned)
+    }
+
+    /// Get the number of events in the log.
+    pub fn len(&self) -> Result<usize, Error> {
+        let mut stmt = self.db.prepare("SELECT COUNT(*) FROM events")?;
+        stmt.next()?;
+
+        Ok(stmt.read::<i64, _>(0)? as usize)
+    }
+
+    /// Check whether the log is empty.
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::arbitrary;
+
+    fn event() -> Event {
+        Event::PeerConnected {
+            nid: arbitrary::gen(1),
+        }
+    }
+
+    #[test]
+    fn test_append_since() {
+        let mut log = Log::memory().unwrap();
+        let mut seqs = Vec::new();
+
+        for t in 0..10 {
+            seqs.push(log.append(&event(), t).unwrap());
+        }
+        assert_eq!(seqs, (1..=10).collect::<Vec<_>>());
+
+        let mut seq = 0;
+        let mut received = Vec::new();
+        let mut pages = 0;
+        loop {
+            let page = log.since(seq, 3).unwrap();
+            pages += 1;
+            received.extend(page.events.iter().map(|r| r.seq));
+
+            match page.next() {
+                Some(next) => seq = next,
+                None => break,
+            }
+        }
+        assert_eq!(received, seqs);
+        assert_eq!(pages, 4);
+
+        // Exactly one page is enough when the events fit.
+        let page = log.since(7, 3).unwrap();
+        assert_eq!(page.events.len(), 3);
+        assert!(!page.more);
+        assert!(log.since(10, 3).unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut log = Log::memory().unwrap();
+
+        for t in 0..10 {
+            log.append(&event(), t).unwrap();
+        }
+        // Prune by age.
+        assert_eq!(log.prune(3, 100).unwrap(), 3);
+        assert_eq!(log.len().unwrap(), 7);
+        // Prune by size.
+        assert_eq!(log.prune(0, 5).unwrap(), 2);
+        assert_eq!(log.since(0, 10).unwrap().events[0].seq, 6);
+
+        // Sequence numbers are not reused once pruned.
+        log.prune(0, 0).unwrap();
+        assert!(log.is_empty().unwrap());
+        assert_eq!(log.append(&event(), 10).unwrap(), 11);
+    }
+}
@@ -1,20 +1,28 @@
 use std::collections::HashSet;
-use std::path::Path;
-use std::{fmt, time};
+use std::path::{Path, PathBuf};
+use std::{fmt, io, time};
 
+use serde::{Deserialize, Serialize};
+use serde_json as json;
 use sqlite as sql;
 use thiserror::Error;
 
 use crate::{
+    node::{FetchFailure, FetchOutcome, FetchRecord},
     prelude::Timestamp,
     prelude::{Id, NodeId},
     sql::transaction,
 };
 
+/// Number of fetches kept in the fetch history of each resource.
+pub const FETCH_HISTORY_SIZE: usize = 20;
+
 /// How long to wait for the database lock to be released before failing a read.
 const DB_READ_TIMEOUT: time::Duration = time::Duration::from_secs(3);
 /// How long to wait for the database lock to be released before failing a write.
 const DB_WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(6);
+/// Schema migrations, in order. See [`crate::sql::migrate`].
+const MIGRATIONS: &[&str] = &[include_str!("routing/migrations/1.sql")];
 
 /// Result of inserting into the routing table.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -27,12 +35,34 @@ pub enum InsertResult {
     SeedAdded,
 }
 
+/// Where a routing entry was learned from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    /// The node that announced it seeds the resource. Since announcements are signed,
+    /// this is always the seed of the routing entry.
+    pub announcer: NodeId,
+    /// Timestamp of the latest announcement of the entry.
+    pub timestamp: Timestamp,
+    /// The peer who first relayed the entry to us.
+    pub first_relayer: NodeId,
+    /// Local time at which the entry was first received.
+    pub first_received: Timestamp,
+    /// The peer who last relayed the entry to us.
+    pub relayer: NodeId,
+    /// Local time at which the entry was last received.
+    pub received: Timestamp,
+}
+
 /// An error occuring in peer-to-peer networking code.
 #[derive(Error, Debug)]
 pub enum Error {
     /// An Internal error.
     #[error("internal error: {0}")]
     Internal(#[from] sql::Error),
+    /// I/O error.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
     /// Internal unit overflow.
     #[error("the unit overflowed")]
     UnitOverflow,
@@ -58,10 +88,29 @@ impl Table {
         let mut db = sql::Connection::open(path)?;
         db.set_busy_timeout(DB_WRITE_TIMEOUT.as_millis() as usize)?;
         db.execute(Self::SCHEMA)?;
+        crate::sql::migrate(&db, MIGRATIONS)?;
 
         Ok(Self { db })
     }
 
+    /// Same as [`Self::open`], but if the store is found to be corrupt, it is moved
+    /// aside and a fresh, empty store is created in its place. Returns the path the
+    /// corrupt store was moved to, if any.
+    pub fn open_or_recover<P: AsRef<Path>>(path: P) -> Result<(Self, Option<PathBuf>), Error> {
+        let path = path.as_ref();
+
+        match Self::open(path) {
+            Ok(table) => Ok((table, None)),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e) => {
+                let backup = crate::sql::quarantine(path)?;
+                let table = Self::open(path)?;
+
+                Ok((table, Some(backup)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Same as [`Self::open`], but in read-only mode. This is useful to have multiple
     /// open databases, as no locking is required.
     pub fn reader<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
@@ -77,6 +126,7 @@ impl Table {
     pub fn memory() -> Result<Self, Error> {
         let db = sql::Connection::open(":memory:")?;
         db.execute(Self::SCHEMA)?;
+        crate::sql::migrate(&db, MIGRATIONS)?;
 
         Ok(Self { db })
     }
@@ -101,6 +151,26 @@ pub trait Store {
         node: NodeId,
         time: Timestamp,
     ) -> Result<Vec<(Id, InsertResult)>, Error>;
+    /// Record that the given peer relayed an announcement of the given node seeding the
+    /// given ids. The last relayer is only updated if the announcement is not older than
+    /// the last one recorded.
+    fn relayed<'a>(
+        &mut self,
+        ids: impl IntoIterator<Item = &'a Id>,
+        node: NodeId,
+        relayer: NodeId,
+        timestamp: Timestamp,
+        received: Timestamp,
+    ) -> Result<(), Error>;
+    /// Get where a specific entry was learned from, if known.
+    fn provenance(&self, id: &Id, node: &NodeId) -> Result<Option<Provenance>, Error>;
+    /// Record a fetch of the given id in its fetch history. Only the last
+    /// [`FETCH_HISTORY_SIZE`] fetches of each id are kept.
+    fn fetched(&mut self, id: &Id, record: &FetchRecord) -> Result<(), Error>;
+    /// Get the fetch history of the given id, oldest first.
+    fn fetch_history(&self, id: &Id) -> Result<Vec<FetchRecord>, Error>;
+    /// Prune fetch history entries older than the given timestamp.
+    fn prune_fetches(&mut self, oldest: Timestamp) -> Result<usize, Error>;
     /// Remove a node for the given id.
     fn remove(&mut self, id: &Id, node: &NodeId) -> Result<bool, Error>;
     /// Iterate over all entries in the routing table.
@@ -197,6 +267,154 @@ impl Store for Table {
         .map_err(Error::from)
     }
 
+    fn relayed<'a>(
+        &mut self,
+        ids: impl IntoIterator<Item = &'a Id>,
+        node: NodeId,
+        relayer: NodeId,
+        timestamp: Timestamp,
+        received: Timestamp,
+    ) -> Result<(), Error> {
+        let timestamp: i64 = timestamp.try_into().map_err(|_| Error::UnitOverflow)?;
+        let received: i64 = received.try_into().map_err(|_| Error::UnitOverflow)?;
+
+        transaction(&self.db, |db| {
+            for id in ids.into_iter() {
+                let mut stmt = db.prepare(
+                    "INSERT INTO provenance
+                     (resource, node, timestamp, first_relayer, first_received, relayer, received)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?4, ?5)
+                     ON CONFLICT DO UPDATE
+                     SET timestamp = ?3, relayer = ?4, received = ?5
+                     WHERE timestamp <= ?3",
+                )?;
+
+                stmt.bind((1, id))?;
+                stmt.bind((2, &node))?;
+                stmt.bind((3, timestamp))?;
+                stmt.bind((4, &relayer))?;
+                stmt.bind((5, received))?;
+                stmt.next()?;
+            }
+            Ok(())
+        })
+        .map_err(Error::from)
+    }
+
+    fn provenance(&self, id: &Id, node: &NodeId) -> Result<Option<Provenance>, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT timestamp, first_relayer, first_received, relayer, received
+             FROM provenance WHERE resource = ? AND node = ?",
+        )?;
+
+        stmt.bind((1, id))?;
+        stmt.bind((2, node))?;
+
+        if let Some(Ok(row)) = stmt.into_iter().next() {
+            return Ok(Some(Provenance {
+                announcer: *node,
+                timestamp: row.read::<i64, _>("timestamp") as Timestamp,
+                first_relayer: row.read::<NodeId, _>("first_relayer"),
+                first_received: row.read::<i64, _>("first_received") as Timestamp,
+                relayer: row.read::<NodeId, _>("relayer"),
+                received: row.read::<i64, _>("received") as Timestamp,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn fetched(&mut self, id: &Id, record: &FetchRecord) -> Result<(), Error> {
+        let time: i64 = record.time.try_into().map_err(|_| Error::UnitOverflow)?;
+        let duration: Option<i64> = record
+            .duration
+            .map(|d| d.try_into().map_err(|_| Error::UnitOverflow))
+            .transpose()?;
+        let received: Option<i64> = record
+            .received
+            .map(|r| r.try_into().map_err(|_| Error::UnitOverflow))
+            .transpose()?;
+        let (failure, reason) = match &record.outcome {
+            FetchOutcome::Success => (None, None),
+            FetchOutcome::Failed { failure, reason } => (
+                json::to_value(failure)
+                    .ok()
+                    .and_then(|v| v.as_str().map(ToOwned::to_owned)),
+                Some(reason.as_str()),
+            ),
+        };
+
+        transaction(&self.db, |db| {
+            let mut stmt = db.prepare(
+                "INSERT INTO fetches (resource, seed, time, duration, received, failure, reason)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, &record.seed))?;
+            stmt.bind((3, time))?;
+            stmt.bind((4, duration))?;
+            stmt.bind((5, received))?;
+            stmt.bind((6, failure.as_deref()))?;
+            stmt.bind((7, reason))?;
+            stmt.next()?;
+
+            let mut stmt = db.prepare(
+                "DELETE FROM fetches WHERE resource = ?1 AND rowid NOT IN
+                (SELECT rowid FROM fetches WHERE resource = ?1 ORDER BY rowid DESC LIMIT ?2)",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, FETCH_HISTORY_SIZE as i64))?;
+            stmt.next()?;
+
+            Ok(())
+        })
+        .map_err(Error::from)
+    }
+
+    fn fetch_history(&self, id: &Id) -> Result<Vec<FetchRecord>, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT seed, time, duration, received, failure, reason
+             FROM fetches WHERE resource = ? ORDER BY rowid",
+        )?;
+        stmt.bind((1, id))?;
+
+        let mut history = Vec::new();
+        for row in stmt.into_iter() {
+            let row = row?;
+            let outcome = match row.read::<Option<&str>, _>("failure") {
+                None => FetchOutcome::Success,
+                Some(failure) => FetchOutcome::Failed {
+                    // Failures we don't know of were recorded by a newer version.
+                    failure: json::from_value(json::Value::from(failure))
+                        .unwrap_or(FetchFailure::Other),
+                    reason: row
+                        .read::<Option<&str>, _>("reason")
+                        .unwrap_or_default()
+                        .to_owned(),
+                },
+            };
+            history.push(FetchRecord {
+                seed: row.read::<NodeId, _>("seed"),
+                time: row.read::<i64, _>("time") as Timestamp,
+                duration: row.read::<Option<i64>, _>("duration").map(|d| d as u64),
+                received: row.read::<Option<i64>, _>("received").map(|r| r as u64),
+                outcome,
+            });
+        }
+        Ok(history)
+    }
+
+    fn prune_fetches(&mut self, oldest: Timestamp) -> Result<usize, Error> {
+        let oldest: i64 = oldest.try_into().map_err(|_| Error::UnitOverflow)?;
+        let mut stmt = self.db.prepare("DELETE FROM fetches WHERE time < ?")?;
+
+        stmt.bind((1, oldest))?;
+        stmt.next()?;
+
+        Ok(self.db.change_count())
+    }
+
     fn entries(&self) -> Result<Box<dyn Iterator<Item = (Id, NodeId)>>, Error> {
         let mut stmt = self
             .db
@@ -484,6 +702,114 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_provenance() {
+        let id = arbitrary::gen::<Id>(1);
+        let node = arbitrary::gen::<NodeId>(1);
+        let bob = arbitrary::gen::<NodeId>(1);
+        let eve = arbitrary::gen::<NodeId>(1);
+        let mut db = Table::open(":memory:").unwrap();
+
+        db.insert([&id], node, 1).unwrap();
+        db.relayed([&id], node, bob, 1, 10).unwrap();
+        db.relayed([&id], node, eve, 2, 20).unwrap();
+
+        let p = db.provenance(&id, &node).unwrap().unwrap();
+        assert_eq!(p.announcer, node);
+        assert_eq!((p.first_relayer, p.first_received), (bob, 10));
+        assert_eq!((p.relayer, p.received, p.timestamp), (eve, 20, 2));
+
+        // Older announcements don't change the provenance.
+        db.relayed([&id], node, bob, 1, 30).unwrap();
+        assert_eq!(db.provenance(&id, &node).unwrap(), Some(p));
+
+        // Provenance is removed with the routing entry.
+        db.remove(&id, &node).unwrap();
+        assert_eq!(db.provenance(&id, &node).unwrap(), None);
+    }
+
+    #[test]
+    fn test_fetch_history() {
+        let id = arbitrary::gen::<Id>(1);
+        let seed = arbitrary::gen::<NodeId>(1);
+        let mut db = Table::open(":memory:").unwrap();
+        let record = |time| FetchRecord {
+            time,
+            seed,
+            duration: Some(time),
+            received: (time % 3 != 0).then_some(time * 1024),
+            outcome: if time % 2 == 0 {
+                FetchOutcome::Success
+            } else {
+                FetchOutcome::Failed {
+                    failure: FetchFailure::Timeout,
+                    reason: String::from("timed out"),
+                }
+            },
+        };
+
+        for time in 0..FETCH_HISTORY_SIZE as u64 + 5 {
+            db.fetched(&id, &record(time)).unwrap();
+        }
+        db.fetched(&arbitrary::gen::<Id>(1), &record(0)).unwrap();
+
+        // Only the last fetches are kept, oldest first.
+        let history = db.fetch_history(&id).unwrap();
+        assert_eq!(history.len(), FETCH_HISTORY_SIZE);
+        assert_eq!(history.first(), Some(&record(5)));
+        assert_eq!(history.last(), Some(&record(FETCH_HISTORY_SIZE as u64 + 4)));
+
+        // The fetch of the other id is pruned too.
+        assert_eq!(db.prune_fetches(10).unwrap(), 6);
+        assert_eq!(db.fetch_history(&id).unwrap().first(), Some(&record(10)));
+    }
+
+    #[test]
+    fn test_fetch_history_migration() {
+        let id = arbitrary::gen::<Id>(1);
+        let old = arbitrary::gen::<Id>(1);
+        let seed = arbitrary::gen::<NodeId>(1);
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("routing.db");
+
+        // A table created before any migration.
+        {
+            let db = sql::Connection::open(&path).unwrap();
+            db.execute(Table::SCHEMA).unwrap();
+
+            let mut stmt = db
+                .prepare("INSERT INTO fetches VALUES (?, ?, 1, 2, NULL, NULL)")
+                .unwrap();
+            stmt.bind((1, &old)).unwrap();
+            stmt.bind((2, &seed)).unwrap();
+            stmt.next().unwrap();
+        }
+
+        let mut db = Table::open(&path).unwrap();
+        let history = db.fetch_history(&old).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].duration, Some(2));
+        assert_eq!(history[0].received, None);
+
+        let record = FetchRecord {
+            time: 3,
+            seed,
+            duration: None,
+            received: Some(1024),
+            outcome: FetchOutcome::Success,
+        };
+        db.fetched(&id, &record).unwrap();
+
+        assert_eq!(db.fetch_history(&id).unwrap(), vec![record.clone()]);
+        drop(db);
+
+        // Migrations are only applied once.
+        let db = Table::open(&path).unwrap();
+        assert_eq!(db.fetch_history(&id).unwrap(), vec![record]);
+        let reader = Table::reader(&path).unwrap();
+        assert_eq!(reader.fetch_history(&old).unwrap()[0].received, None);
+    }
+
     #[test]
     fn test_count() {
         let id = arbitrary::gen::<Id>(1);
@@ -495,4 +821,36 @@ mod test {
         }
         assert_eq!(db.count(&id).unwrap(), nodes.len());
     }
+
+    #[test]
+    fn test_open_or_recover() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("routing.db");
+
+        std::fs::write(&path, [0xff; 4096]).unwrap();
+        assert!(matches!(
+            Table::open(&path),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e)
+        ));
+
+        let (mut db, backup) = Table::open_or_recover(&path).unwrap();
+        let backup = backup.unwrap();
+
+        assert!(db.is_empty().unwrap());
+        assert_eq!(std::fs::read(&backup).unwrap(), [0xff; 4096]);
+        assert!(backup
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("routing.db.corrupt."));
+
+        // The fresh table is usable, and isn't recovered again when re-opened.
+        let id = arbitrary::gen::<Id>(1);
+        db.insert([&id], arbitrary::gen::<NodeId>(1), 0).unwrap();
+        drop(db);
+
+        let (db, backup) = Table::open_or_recover(&path).unwrap();
+        assert!(backup.is_none());
+        assert_eq!(db.len().unwrap(), 1);
+    }
 }
@@ -0,0 +1,2 @@
+-- Number of bytes received from the seed during a fetch, if known.
+alter table "fetches" add column "received" integer;
@@ -11,3 +11,50 @@ create table if not exists "routing" (
 
   primary key ("resource", "node")
 );
+
+-- Where routing entries were learned from.
+create table if not exists "provenance" (
+  -- Resource being seeded.
+  "resource"       text      not null,
+  -- Node ID of the seed, ie. the announcer.
+  "node"           text      not null,
+  -- Timestamp of the latest announcement of this entry.
+  "timestamp"      integer   not null,
+  -- Node ID of the peer who first relayed this entry to us.
+  "first_relayer"  text      not null,
+  -- Local UNIX time at which this entry was first received.
+  "first_received" integer   not null,
+  -- Node ID of the peer who last relayed this entry to us.
+  "relayer"        text      not null,
+  -- Local UNIX time at which this entry was last received.
+  "received"       integer   not null,
+
+  primary key ("resource", "node")
+);
+
+-- Provenance is only kept for as long as the routing entry it is about.
+create trigger if not exists "routing_provenance_delete"
+after delete on "routing"
+begin
+  delete from "provenance" where resource = old.resource and node = old.node;
+end;
+
+-- Recent fetches of resources, for diagnostics.
+create table if not exists "fetches" (
+  -- Resource fetched.
+  "resource"     text      not null,
+  -- Node ID of the seed fetched from.
+  "seed"         text      not null,
+  -- Local UNIX time at which the fetch completed.
+  "time"         integer   not null,
+  -- How long the fetch took, in milliseconds, if known.
+  "duration"     integer,
+  -- Kind of failure, or null if the fetch succeeded.
+  "failure"      text,
+  -- Error message, if the fetch failed.
+  "reason"       text
+);
+
+create index if not exists "fetches_resource" on "fetches" ("resource");
+
+-- Columns added later are added by the migrations in `migrations/`.
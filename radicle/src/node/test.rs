@@ -0,0 +1,545 @@
+//! Test utilities for code built on top of a node [`Handle`].
+//!
+//! [`MockHandle`] implements [`Handle`] over in-memory state, so that tools talking to a node
+//! can be tested without running one. Responses are scripted ahead of time, calls are recorded
+//! for later assertions, errors can be injected per call, and events can be pushed to
+//! subscribers.
+//!
+//! ```
+//! use std::{io, time};
+//!
+//! use radicle::node::{Event, FetchResult, Handle as _};
+//! use radicle::node::test::{Call, MockHandle};
+//! use radicle::test::arbitrary;
+//! use radicle::prelude::{Id, NodeId};
+//!
+//! let rid = arbitrary::gen::<Id>(1);
+//! let seed = arbitrary::gen::<NodeId>(1);
+//! let mut node = MockHandle::default();
+//!
+//! // Script the outcome of a fetch, and make announcements fail.
+//! node.push_fetch(rid, seed, FetchResult::Failed { reason: String::from("timeout") });
+//! node.fail(|call| matches!(call, Call::AnnounceRefs(_)), io::ErrorKind::ConnectionRefused);
+//!
+//! assert!(!node.fetch(rid, seed).unwrap().is_success());
+//! assert!(node.announce_refs(rid).is_err());
+//! assert_eq!(node.calls(), vec![Call::Fetch(rid, seed), Call::AnnounceRefs(rid)]);
+//!
+//! // Events pushed by the test are received by subscribers.
+//! node.emit(Event::RefsSynced { remote: seed, rid });
+//!
+//! let mut events = node.subscribe(time::Duration::from_millis(10)).unwrap();
+//! assert!(matches!(events.next(), Some(Ok(Event::RefsSynced { .. }))));
+//! assert!(matches!(events.next(), Some(Err(e)) if e.kind() == io::ErrorKind::TimedOut));
+//! ```
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::{io, iter, time};
+
+use crossbeam_channel as chan;
+use serde_json as json;
+
+use crate::cob::ObjectId;
+use crate::crypto::PublicKey;
+use crate::git;
+use crate::identity::Id;
+use crate::node::{
+    activity, config, events, tracking, Address, Alias, CobUpdate, CobUpdated, ConnectOptions,
+    ConnectResult, Error, ErrorCode, Event, Explanation, FetchRecord, FetchResult, GossipStats,
+    Handle, Inspection, Metrics, NodeId, Recovery, Seeds, Session, SyncAllOptions, SyncProgress,
+    Timestamp, TrackDryRun, TrackFetch, Tracked,
+};
+use crate::storage::git::stats::RepoStats;
+use crate::storage::Finding;
+
+/// A call made to a [`MockHandle`], with its arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Nid,
+    Connect(NodeId, Address),
+    Seeds(Id),
+    Fetch(Id, NodeId),
+    FetchMinimal(Id, NodeId),
+    TrackRepo(Id, tracking::Scope),
+    TrackRepoOrigin(Id, tracking::Scope, tracking::Origin),
+    TrackRepoFilter(Id, tracking::Scope, tracking::RefFilter),
+    TrackRepoFetch(Id, tracking::Scope),
+    TrackRepoDryRun(Id, tracking::Scope),
+    TrackNode(NodeId, Option<Alias>),
+    UntrackRepo(Id),
+    WatchRepo(Id),
+    UnwatchRepo(Id),
+    UntrackNode(NodeId),
+    ExportPolicies,
+    ImportPolicies,
+    AnnounceRefs(Id),
+    AnnounceInventory,
+    SyncInventory,
+    Shutdown,
+    Sessions,
+    Subscribe,
+    Validate(Option<Id>),
+    RepoSize(Option<Id>, bool),
+    GossipStats,
+    Metrics,
+    Recovery,
+    Explain(Id, NodeId),
+    FetchHistory(Id),
+    EventsSince(u64),
+    Activity(Option<Timestamp>, Option<usize>),
+    Inspect(Id),
+    Config,
+    SetLimit(String, json::Value),
+    AddPeer(config::PeerEntry),
+    RemovePeer(NodeId),
+    SyncAll(SyncAllOptions),
+    CobUpdate(CobUpdate),
+}
+
+/// Predicate selecting the calls that should fail.
+type Failure = (Box<dyn Fn(&Call) -> bool + Send + Sync>, io::ErrorKind);
+
+/// State shared by all clones of a [`MockHandle`].
+#[derive(Default)]
+struct State {
+    running: bool,
+    calls: Vec<Call>,
+    failures: Vec<Failure>,
+    seeds: HashMap<Id, Seeds>,
+    fetches: HashMap<(Id, NodeId), VecDeque<FetchResult>>,
+    sessions: Vec<Session>,
+    tracked_repos: HashSet<Id>,
+    watched_repos: HashSet<Id>,
+    tracked_nodes: HashSet<NodeId>,
+    config: Option<config::Config>,
+}
+
+/// An in-memory node handle. Clones share the same state.
+///
+/// Calls for which no response was scripted return an empty or default response, or an
+/// [`Error::Node`] error if there is no sensible default.
+#[derive(Clone)]
+pub struct MockHandle {
+    nid: NodeId,
+    state: Arc<Mutex<State>>,
+    events: (chan::Sender<Event>, chan::Receiver<Event>),
+}
+
+impl Default for MockHandle {
+    fn default() -> Self {
+        Self::new(PublicKey::from([0xff; 32]))
+    }
+}
+
+impl MockHandle {
+    /// Create a new handle to a running node with the given id.
+    pub fn new(nid: NodeId) -> Self {
+        Self {
+            nid,
+            state: Arc::new(Mutex::new(State {
+                running: true,
+                ..State::default()
+            })),
+            events: chan::unbounded(),
+        }
+    }
+
+    /// Set whether the node is running.
+    pub fn set_running(&self, running: bool) {
+        self.state().running = running;
+    }
+
+    /// Set the seeds returned for a repository.
+    pub fn set_seeds(&self, rid: Id, seeds: Seeds) {
+        self.state().seeds.insert(rid, seeds);
+    }
+
+    /// Queue the result of the next fetch of a repository from a node.
+    pub fn push_fetch(&self, rid: Id, from: NodeId, result: FetchResult) {
+        self.state()
+            .fetches
+            .entry((rid, from))
+            .or_default()
+            .push_back(result);
+    }
+
+    /// Set the sessions returned by [`Handle::sessions`].
+    pub fn set_sessions(&self, sessions: Vec<Session>) {
+        self.state().sessions = sessions;
+    }
+
+    /// Set the configuration returned by [`Handle::config`], and changed by
+    /// [`Handle::set_limit`].
+    pub fn set_config(&self, config: config::Config) {
+        self.state().config = Some(config);
+    }
+
+    /// Make the calls matching the predicate fail with an I/O error of the given kind,
+    /// as if the node couldn't be reached.
+    pub fn fail(
+        &self,
+        predicate: impl Fn(&Call) -> bool + Send + Sync + 'static,
+        kind: io::ErrorKind,
+    ) {
+        self.state().failures.push((Box::new(predicate), kind));
+    }
+
+    /// Push an event to subscribers. Events pushed before anyone subscribed are received by
+    /// the next subscriber. With multiple subscribers, each event is only received once.
+    pub fn emit(&self, event: Event) {
+        self.events
+            .0
+            .send(event)
+            .expect("MockHandle::emit: the channel is never disconnected");
+    }
+
+    /// Get the calls made so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state().calls.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("MockHandle: state lock is poisoned")
+    }
+
+    /// Record a call, and return the state if it should succeed.
+    fn call(&self, call: Call) -> Result<MutexGuard<'_, State>, Error> {
+        let mut state = self.state();
+        let failure = state
+            .failures
+            .iter()
+            .find(|(predicate, _)| predicate(&call))
+            .map(|(_, kind)| *kind);
+
+        state.calls.push(call);
+
+        match failure {
+            Some(kind) => Err(Error::Connect(io::Error::from(kind))),
+            None => Ok(state),
+        }
+    }
+
+    /// Record a call whose response doesn't depend on the state.
+    fn record(&self, call: Call) -> Result<(), Error> {
+        self.call(call).map(drop)
+    }
+
+    fn unscripted(call: &str) -> Error {
+        Error::Node {
+            code: ErrorCode::NotFound,
+            reason: format!("MockHandle: no response scripted for `{call}`"),
+        }
+    }
+}
+
+impl Handle for MockHandle {
+    type Sessions = Vec<Session>;
+    type Error = Error;
+
+    fn nid(&self) -> Result<NodeId, Error> {
+        self.record(Call::Nid)?;
+        Ok(self.nid)
+    }
+
+    fn is_running(&self) -> bool {
+        self.state().running
+    }
+
+    fn connect(
+        &mut self,
+        node: NodeId,
+        addr: Address,
+        _opts: ConnectOptions,
+    ) -> Result<ConnectResult, Error> {
+        self.record(Call::Connect(node, addr))?;
+        Ok(ConnectResult::Connected)
+    }
+
+    fn seeds(&mut self, id: Id) -> Result<Seeds, Error> {
+        let state = self.call(Call::Seeds(id))?;
+
+        Ok(state
+            .seeds
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| Seeds::new(fastrand::Rng::new())))
+    }
+
+    fn fetch(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Error> {
+        let mut state = self.call(Call::Fetch(id, from))?;
+
+        Ok(state
+            .fetches
+            .get_mut(&(id, from))
+            .and_then(|q| q.pop_front())
+            .unwrap_or_else(|| FetchResult::Failed {
+                reason: String::from("MockHandle: no fetch result scripted"),
+            }))
+    }
+
+    fn fetch_minimal(&mut self, id: Id, from: NodeId) -> Result<FetchResult, Error> {
+        let mut state = self.call(Call::FetchMinimal(id, from))?;
+
+        Ok(state
+            .fetches
+            .get_mut(&(id, from))
+            .and_then(|q| q.pop_front())
+            .unwrap_or_else(|| FetchResult::Failed {
+                reason: String::from("MockHandle: no fetch result scripted"),
+            }))
+    }
+
+    fn track_repo(&mut self, id: Id, scope: tracking::Scope) -> Result<bool, Error> {
+        let mut state = self.call(Call::TrackRepo(id, scope))?;
+        Ok(state.tracked_repos.insert(id))
+    }
+
+    fn track_repo_origin(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+        origin: tracking::Origin,
+    ) -> Result<bool, Error> {
+        let mut state = self.call(Call::TrackRepoOrigin(id, scope, origin))?;
+        Ok(state.tracked_repos.insert(id))
+    }
+
+    fn track_repo_filter(
+        &mut self,
+        id: Id,
+        scope: tracking::Scope,
+        filter: tracking::RefFilter,
+    ) -> Result<bool, Error> {
+        let mut state = self.call(Call::TrackRepoFilter(id, scope, filter))?;
+        Ok(state.tracked_repos.insert(id))
+    }
+
+    fn track_repo_fetch(&mut self, id: Id, scope: tracking::Scope) -> Result<Tracked, Error> {
+        let mut state = self.call(Call::TrackRepoFetch(id, scope))?;
+        let updated = state.tracked_repos.insert(id);
+        let fetch = match state.seeds.get(&id).map(|s| s.partition()) {
+            Some((connected, _)) if !connected.is_empty() => TrackFetch::Started {
+                seed: connected[0].nid,
+            },
+            Some((_, disconnected)) if !disconnected.is_empty() => TrackFetch::Queued {
+                seed: disconnected[0].nid,
+            },
+            _ => TrackFetch::NoSeeds,
+        };
+        Ok(Tracked { updated, fetch })
+    }
+
+    fn track_repo_dry_run(&mut self, id: Id, scope: tracking::Scope) -> Result<TrackDryRun, Error> {
+        self.record(Call::TrackRepoDryRun(id, scope))?;
+        Err(Self::unscripted("track_repo_dry_run"))
+    }
+
+    fn track_node(&mut self, id: NodeId, alias: Option<Alias>) -> Result<bool, Error> {
+        let mut state = self.call(Call::TrackNode(id, alias))?;
+        Ok(state.tracked_nodes.insert(id))
+    }
+
+    fn untrack_repo(&mut self, id: Id) -> Result<bool, Error> {
+        let mut state = self.call(Call::UntrackRepo(id))?;
+        Ok(state.tracked_repos.remove(&id))
+    }
+
+    fn watch_repo(&mut self, id: Id) -> Result<bool, Error> {
+        let mut state = self.call(Call::WatchRepo(id))?;
+        if state.tracked_repos.contains(&id) {
+            return Ok(false);
+        }
+        Ok(state.watched_repos.insert(id))
+    }
+
+    fn unwatch_repo(&mut self, id: Id) -> Result<bool, Error> {
+        let mut state = self.call(Call::UnwatchRepo(id))?;
+        Ok(state.watched_repos.remove(&id))
+    }
+
+    fn untrack_node(&mut self, id: NodeId) -> Result<bool, Error> {
+        let mut state = self.call(Call::UntrackNode(id))?;
+        Ok(state.tracked_nodes.remove(&id))
+    }
+
+    fn export_policies(&self) -> Result<tracking::Policies, Error> {
+        self.record(Call::ExportPolicies)?;
+        Ok(tracking::Policies::default())
+    }
+
+    fn import_policies(
+        &mut self,
+        _policies: tracking::Policies,
+    ) -> Result<tracking::ImportSummary, Error> {
+        self.record(Call::ImportPolicies)?;
+        Ok(tracking::ImportSummary::default())
+    }
+
+    fn announce_refs(&mut self, id: Id) -> Result<(), Error> {
+        self.record(Call::AnnounceRefs(id))?;
+        Ok(())
+    }
+
+    fn announce_inventory(&mut self) -> Result<(), Error> {
+        self.record(Call::AnnounceInventory)?;
+        Ok(())
+    }
+
+    fn sync_inventory(&mut self) -> Result<bool, Error> {
+        self.record(Call::SyncInventory)?;
+        Ok(false)
+    }
+
+    fn shutdown(self) -> Result<(), Error> {
+        self.call(Call::Shutdown)?.running = false;
+        Ok(())
+    }
+
+    fn sessions(&self) -> Result<Vec<Session>, Error> {
+        let state = self.call(Call::Sessions)?;
+        Ok(state.sessions.clone())
+    }
+
+    fn subscribe(
+        &self,
+        timeout: time::Duration,
+    ) -> Result<Box<dyn Iterator<Item = Result<Event, io::Error>>>, Error> {
+        self.record(Call::Subscribe)?;
+
+        let events = self.events.1.clone();
+        Ok(Box::new(iter::from_fn(move || {
+            match events.recv_timeout(timeout) {
+                Ok(event) => Some(Ok(event)),
+                Err(chan::RecvTimeoutError::Timeout) => {
+                    Some(Err(io::Error::from(io::ErrorKind::TimedOut)))
+                }
+                Err(chan::RecvTimeoutError::Disconnected) => None,
+            }
+        })))
+    }
+
+    fn validate(
+        &self,
+        rid: Option<Id>,
+    ) -> Result<Box<dyn Iterator<Item = Result<Finding, io::Error>>>, Error> {
+        self.record(Call::Validate(rid))?;
+        Ok(Box::new(iter::empty()))
+    }
+
+    fn repo_size(&self, rid: Option<Id>, refresh: bool) -> Result<Vec<RepoStats>, Error> {
+        self.record(Call::RepoSize(rid, refresh))?;
+        Err(Self::unscripted("repo_size"))
+    }
+
+    fn gossip_stats(&self) -> Result<GossipStats, Error> {
+        self.record(Call::GossipStats)?;
+        Ok(GossipStats::default())
+    }
+
+    fn metrics(&self) -> Result<Metrics, Error> {
+        self.record(Call::Metrics)?;
+        Ok(Metrics::default())
+    }
+
+    fn recovery(&self) -> Result<Option<Recovery>, Error> {
+        self.record(Call::Recovery)?;
+        Ok(None)
+    }
+
+    fn explain(&self, rid: Id, nid: NodeId) -> Result<Explanation, Error> {
+        self.record(Call::Explain(rid, nid))?;
+        Err(Self::unscripted("explain"))
+    }
+
+    fn fetch_history(&self, rid: Id) -> Result<Vec<FetchRecord>, Error> {
+        self.record(Call::FetchHistory(rid))?;
+        Ok(vec![])
+    }
+
+    fn events_since(&self, seq: u64) -> Result<events::Page, Error> {
+        self.record(Call::EventsSince(seq))?;
+        Ok(events::Page::default())
+    }
+
+    fn activity(
+        &self,
+        since: Option<Timestamp>,
+        limit: Option<usize>,
+    ) -> Result<Vec<activity::Entry>, Error> {
+        self.record(Call::Activity(since, limit))?;
+        Ok(vec![])
+    }
+
+    fn inspect(&mut self, rid: Id) -> Result<Inspection, Error> {
+        self.record(Call::Inspect(rid))?;
+        Err(Self::unscripted("inspect"))
+    }
+
+    fn config(&self) -> Result<config::Config, Error> {
+        self.call(Call::Config)?
+            .config
+            .clone()
+            .ok_or_else(|| Self::unscripted("config"))
+    }
+
+    fn set_limit(&mut self, name: &str, value: json::Value) -> Result<(), Error> {
+        let mut state = self.call(Call::SetLimit(name.to_owned(), value.clone()))?;
+        let mut limits = state
+            .config
+            .as_ref()
+            .map(|c| c.limits.clone())
+            .unwrap_or_default();
+
+        limits.set(name, value).map_err(|e| Error::Node {
+            code: ErrorCode::InvalidArgument,
+            reason: e.to_string(),
+        })?;
+        if let Some(config) = &mut state.config {
+            config.limits = limits;
+        }
+        Ok(())
+    }
+
+    fn add_peer(&mut self, peer: config::PeerEntry) -> Result<bool, Error> {
+        let mut state = self.call(Call::AddPeer(peer.clone()))?;
+        let config = state
+            .config
+            .get_or_insert_with(|| config::Config::new(Alias::new("mock")));
+
+        Ok(config.add_peer(peer))
+    }
+
+    fn remove_peer(&mut self, nid: NodeId) -> Result<bool, Error> {
+        let mut state = self.call(Call::RemovePeer(nid))?;
+
+        Ok(state
+            .config
+            .as_mut()
+            .map_or(false, |config| config.remove_peer(&nid)))
+    }
+
+    fn sync_all(
+        &mut self,
+        options: SyncAllOptions,
+    ) -> Result<Box<dyn Iterator<Item = Result<SyncProgress, io::Error>>>, Error> {
+        self.record(Call::SyncAll(options))?;
+        Ok(Box::new(iter::once(Ok(SyncProgress::Summary {
+            repos: 0,
+            succeeded: 0,
+            skipped: 0,
+            failed: 0,
+        }))))
+    }
+
+    fn cob_update(&mut self, update: CobUpdate) -> Result<CobUpdated, Error> {
+        self.record(Call::CobUpdate(update.clone()))?;
+
+        // Nb. Nothing is written, so there is no new entry to return.
+        let entry = git::Oid::from(git::raw::Oid::zero());
+        let object = Option::from(update.object).unwrap_or_else(|| ObjectId::from(entry));
+
+        Ok(CobUpdated { object, entry })
+    }
+}
@@ -6,15 +6,27 @@ use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::git;
 use crate::prelude::Id;
 
 pub use super::{Alias, NodeId};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Repo {
     pub id: Id,
     pub scope: Scope,
     pub policy: Policy,
+    /// Maximum number of bytes received in a single fetch of this repository, overriding
+    /// the node's configured limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_limit: Option<u64>,
+    /// Why the repository is in our inventory.
+    #[serde(default)]
+    pub origin: Origin,
+    /// Which refs are fetched from the repository's remotes.
+    #[serde(default, skip_serializing_if = "RefFilter::is_empty")]
+    pub ref_filter: RefFilter,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,12 +36,41 @@ pub struct Node {
     pub policy: Policy,
 }
 
+/// All tracking policies of a node, as exported for backup, or for migrating to
+/// another node.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Policies {
+    /// Repository policies, including blocked repositories.
+    pub repos: Vec<Repo>,
+    /// Node policies, including blocked nodes.
+    pub nodes: Vec<Node>,
+}
+
+/// Outcome of importing tracking policies.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    /// Policies that didn't exist, and were added.
+    pub added: usize,
+    /// Policies that existed, and were changed.
+    pub updated: usize,
+    /// Policies that already existed as imported, and were left as-is.
+    pub skipped: usize,
+    /// Repositories that would have been blocked, but were left as-is because they are
+    /// ours. See [`Origin::is_local`].
+    #[serde(default)]
+    pub protected: usize,
+}
+
 /// Tracking policy.
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Policy {
     /// The resource is tracked.
     Track,
+    /// The repository is watched: we subscribe to its announcements, but never fetch it.
+    Watch,
     /// The resource is blocked.
     #[default]
     Block,
@@ -39,6 +80,7 @@ impl fmt::Display for Policy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Track => write!(f, "track"),
+            Self::Watch => write!(f, "watch"),
             Self::Block => write!(f, "block"),
         }
     }
@@ -50,6 +92,7 @@ impl FromStr for Policy {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "track" => Ok(Self::Track),
+            "watch" => Ok(Self::Watch),
             "block" => Ok(Self::Block),
             _ => Err(s.to_owned()),
         }
@@ -64,6 +107,7 @@ impl sqlite::BindableWithIndex for Policy {
     ) -> sqlite::Result<()> {
         match self {
             Self::Track => "track",
+            Self::Watch => "watch",
             Self::Block => "block",
         }
         .bind(stmt, i)
@@ -78,6 +122,7 @@ impl TryFrom<&sqlite::Value> for Policy {
 
         match value {
             sqlite::Value::String(s) if s == "track" => Ok(Policy::Track),
+            sqlite::Value::String(s) if s == "watch" => Ok(Policy::Watch),
             sqlite::Value::String(s) if s == "block" => Ok(Policy::Block),
             _ => Err(sqlite::Error {
                 code: None,
@@ -155,3 +200,202 @@ impl TryFrom<&sqlite::Value> for Scope {
         }
     }
 }
+
+/// The reason a repository is in our inventory.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Origin {
+    /// The repository was tracked, eg. to seed it or to contribute to it.
+    #[default]
+    Tracked,
+    /// We are a delegate of the repository.
+    Delegate,
+    /// The repository was created on this node.
+    Created,
+    /// The repository was tracked automatically, because a node we mirror announced it.
+    Mirror,
+}
+
+impl Origin {
+    /// Whether the repository is ours, ie. it was created locally or we are one of its
+    /// delegates. Such repositories shouldn't be removed in bulk, as there may be no other
+    /// copy of them.
+    pub fn is_local(&self) -> bool {
+        matches!(self, Self::Delegate | Self::Created)
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tracked => f.write_str("tracked"),
+            Self::Delegate => f.write_str("delegate"),
+            Self::Created => f.write_str("created"),
+            Self::Mirror => f.write_str("mirror"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid repository origin: {0:?}")]
+pub struct ParseOriginError(String);
+
+impl FromStr for Origin {
+    type Err = ParseOriginError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tracked" => Ok(Self::Tracked),
+            "delegate" => Ok(Self::Delegate),
+            "created" => Ok(Self::Created),
+            "mirror" => Ok(Self::Mirror),
+            _ => Err(ParseOriginError(s.to_string())),
+        }
+    }
+}
+
+impl sqlite::BindableWithIndex for Origin {
+    fn bind<I: sqlite::ParameterIndex>(
+        self,
+        stmt: &mut sqlite::Statement<'_>,
+        i: I,
+    ) -> sqlite::Result<()> {
+        let s = match self {
+            Self::Tracked => "tracked",
+            Self::Delegate => "delegate",
+            Self::Created => "created",
+            Self::Mirror => "mirror",
+        };
+        s.bind(stmt, i)
+    }
+}
+
+impl TryFrom<&sqlite::Value> for Origin {
+    type Error = sqlite::Error;
+
+    fn try_from(value: &sqlite::Value) -> Result<Self, Self::Error> {
+        let message = Some("invalid repository origin".to_owned());
+
+        match value {
+            sqlite::Value::String(origin) => Origin::from_str(origin).map_err(|_| sqlite::Error {
+                code: None,
+                message,
+            }),
+            _ => Err(sqlite::Error {
+                code: None,
+                message,
+            }),
+        }
+    }
+}
+
+/// Filter over the refs fetched from the remotes of a repository, eg. to skip large
+/// artifact branches. Patterns are matched against ref names relative to a remote's
+/// namespace, eg. `refs/heads/artifacts/*`, where `*` matches any sequence of characters.
+///
+/// The identity and signed refs branches are always fetched, since a remote can't be
+/// verified without them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefFilter {
+    /// If not empty, only refs matching one of these patterns are fetched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<git::PatternString>,
+    /// Refs matching one of these patterns are not fetched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<git::PatternString>,
+}
+
+impl RefFilter {
+    /// Whether the filter lets all refs through.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Check whether a ref, named relative to its namespace, is excluded by the filter.
+    pub fn is_excluded(&self, refname: &str) -> bool {
+        if refname.starts_with("refs/rad/") {
+            return false;
+        }
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|p| glob_matches(p.as_str(), refname));
+
+        !included
+            || self
+                .exclude
+                .iter()
+                .any(|p| glob_matches(p.as_str(), refname))
+    }
+}
+
+impl fmt::Display for RefFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("all");
+        }
+        let include = self.include.iter().map(|p| format!("+{p}"));
+        let exclude = self.exclude.iter().map(|p| format!("-{p}"));
+
+        f.write_str(&include.chain(exclude).collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// Match a name against a pattern, where `*` matches any sequence of characters.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return false;
+    };
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // There is no wildcard in the pattern.
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ref_filter() {
+        let filter = RefFilter {
+            include: vec![],
+            exclude: vec![git::refspec::pattern!("refs/heads/artifacts/*")],
+        };
+        assert!(filter.is_excluded("refs/heads/artifacts/build-1"));
+        assert!(filter.is_excluded("refs/heads/artifacts/nightly/build-1"));
+        assert!(!filter.is_excluded("refs/heads/master"));
+        assert!(!filter.is_excluded("refs/heads/artifacts"));
+        assert!(!filter.is_excluded("refs/rad/sigrefs"));
+
+        let filter = RefFilter {
+            include: vec![
+                git::refspec::pattern!("refs/heads/master"),
+                git::refspec::pattern!("refs/cobs/*"),
+            ],
+            exclude: vec![git::refspec::pattern!("refs/cobs/xyz.radicle.issue/*")],
+        };
+        assert!(!filter.is_excluded("refs/heads/master"));
+        assert!(!filter.is_excluded("refs/cobs/xyz.radicle.patch/abc"));
+        assert!(filter.is_excluded("refs/cobs/xyz.radicle.issue/abc"));
+        assert!(filter.is_excluded("refs/heads/feature"));
+        assert!(!filter.is_excluded("refs/rad/id"));
+
+        assert!(RefFilter::default().is_empty());
+        assert!(!RefFilter::default().is_excluded("refs/heads/master"));
+    }
+}
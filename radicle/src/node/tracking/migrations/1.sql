@@ -0,0 +1,3 @@
+-- Maximum number of bytes received in a single fetch of a repository.
+-- Overrides the node's configured limit if set.
+alter table "repo-policies" add column "fetch_limit" integer default null;
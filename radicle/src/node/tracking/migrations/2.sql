@@ -0,0 +1,9 @@
+-- Why a repository is in our inventory.
+--
+-- Valid values are:
+--
+-- "tracked"         the repository was tracked.
+-- "delegate"        we are a delegate of the repository.
+-- "created"         the repository was created on this node.
+--
+alter table "repo-policies" add column "origin" text default 'tracked';
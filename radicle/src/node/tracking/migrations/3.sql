@@ -0,0 +1,3 @@
+-- Which refs are fetched from a repository's remotes, as JSON.
+-- All refs are fetched if not set.
+alter table "repo-policies" add column "ref_filter" text default null;
@@ -27,6 +27,15 @@ create table if not exists "repo-policies" (
   --
   "scope"              text      default 'trusted',
   -- Tracking policy for this repository.
+  --
+  -- Valid values are:
+  --
+  -- "track"           fetch and seed the repository.
+  -- "watch"           subscribe to announcements of the repository, without fetching it.
+  -- "block"           ignore the repository.
+  --
   "policy"             text      default 'track'
   --
 ) strict;
+
+-- Columns added later are added by the migrations in `migrations/`.
@@ -1,20 +1,30 @@
 #![allow(clippy::type_complexity)]
 use std::marker::PhantomData;
-use std::path::Path;
-use std::{fmt, io, ops::Not as _, str::FromStr, time};
+use std::path::{Path, PathBuf};
+use std::{fmt, io, str::FromStr, time};
 
 use sqlite as sql;
 use thiserror::Error;
 
 use crate::node::{Alias, AliasStore};
 use crate::prelude::{Id, NodeId};
+use crate::sql::transaction;
 
-use super::{Node, Policy, Repo, Scope};
+use super::{ImportSummary, Node, Origin, Policies, Policy, RefFilter, Repo, Scope};
 
 /// How long to wait for the database lock to be released before failing a read.
 const DB_READ_TIMEOUT: time::Duration = time::Duration::from_secs(3);
 /// How long to wait for the database lock to be released before failing a write.
 const DB_WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(6);
+/// Result code returned by SQLite when the database is locked.
+const SQLITE_BUSY: isize = 5;
+/// Schema migrations, in order. The database's `user_version` is the number of
+/// migrations that were applied to it.
+const MIGRATIONS: &[&str] = &[
+    include_str!("migrations/1.sql"),
+    include_str!("migrations/2.sql"),
+    include_str!("migrations/3.sql"),
+];
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -87,6 +97,7 @@ impl Config<Write> {
         let mut db = sql::Connection::open(path)?;
         db.set_busy_timeout(DB_WRITE_TIMEOUT.as_millis() as usize)?;
         db.execute(Self::SCHEMA)?;
+        crate::sql::migrate(&db, MIGRATIONS)?;
 
         Ok(Self {
             db,
@@ -94,10 +105,29 @@ impl Config<Write> {
         })
     }
 
+    /// Same as [`Self::open`], but if the store is found to be corrupt, it is moved
+    /// aside and a fresh, empty store is created in its place. Returns the path the
+    /// corrupt store was moved to, if any.
+    pub fn open_or_recover<P: AsRef<Path>>(path: P) -> Result<(Self, Option<PathBuf>), Error> {
+        let path = path.as_ref();
+
+        match Self::open(path) {
+            Ok(store) => Ok((store, None)),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e) => {
+                let backup = crate::sql::quarantine(path)?;
+                let store = Self::open(path)?;
+
+                Ok((store, Some(backup)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Create a new in-memory address book.
     pub fn memory() -> Result<Self, Error> {
         let db = sql::Connection::open(":memory:")?;
         db.execute(Self::SCHEMA)?;
+        crate::sql::migrate(&db, MIGRATIONS)?;
 
         Ok(Self {
             db,
@@ -105,6 +135,31 @@ impl Config<Write> {
         })
     }
 
+    /// Create a new in-memory store on which all writes fail, as if the disk was full.
+    #[cfg(any(test, feature = "test"))]
+    pub fn faulty() -> Result<Self, Error> {
+        let store = Self::memory()?;
+
+        for table in ["repo-policies", "node-policies"] {
+            for op in ["INSERT", "UPDATE", "DELETE"] {
+                store.db.execute(format!(
+                    "CREATE TRIGGER `{table}-{op}` BEFORE {op} ON `{table}`
+                     BEGIN SELECT RAISE(ABORT, 'database or disk is full'); END"
+                ))?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Make all further queries on this store fail, as if the database was corrupted.
+    #[cfg(any(test, feature = "test"))]
+    pub fn corrupt(&self) -> Result<(), Error> {
+        self.db
+            .execute("DROP TABLE `repo-policies`; DROP TABLE `node-policies`;")?;
+
+        Ok(())
+    }
+
     /// Get a read-only version of this store.
     pub fn read_only(self) -> ConfigReader {
         Config {
@@ -114,91 +169,309 @@ impl Config<Write> {
     }
 
     /// Track a node.
-    pub fn track_node(&mut self, id: &NodeId, alias: Option<&str>) -> Result<bool, Error> {
-        let mut stmt = self.db.prepare(
-            "INSERT INTO `node-policies` (id, alias)
-             VALUES (?1, ?2)
-             ON CONFLICT DO UPDATE
-             SET alias = ?2 WHERE alias != ?2",
-        )?;
+    pub fn track_node(&mut self, id: &NodeId, alias: Option<&Alias>) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "INSERT INTO `node-policies` (id, alias)
+                 VALUES (?1, ?2)
+                 ON CONFLICT DO UPDATE
+                 SET alias = ?2 WHERE alias != ?2",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, alias.map(|a| a.as_ref()).unwrap_or_default()))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
+    }
 
-        stmt.bind((1, id))?;
-        stmt.bind((2, alias.unwrap_or_default()))?;
-        stmt.next()?;
+    /// Track a repository. A watched repository becomes tracked.
+    pub fn track_repo(&mut self, id: &Id, scope: Scope) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "INSERT INTO `repo-policies` (id, scope)
+                 VALUES (?1, ?2)
+                 ON CONFLICT DO UPDATE
+                 SET scope = ?2, policy = iif(policy = ?3, ?4, policy)
+                 WHERE scope != ?2 OR policy = ?3",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, scope))?;
+            stmt.bind((3, Policy::Watch))?;
+            stmt.bind((4, Policy::Track))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
+    }
 
-        Ok(self.db.change_count() > 0)
+    /// Set a node's tracking policy.
+    pub fn set_node_policy(&mut self, id: &NodeId, policy: Policy) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "INSERT INTO `node-policies` (id, policy)
+                 VALUES (?1, ?2)
+                 ON CONFLICT DO UPDATE
+                 SET policy = ?2 WHERE policy != ?2",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, policy))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
     }
 
-    /// Track a repository.
-    pub fn track_repo(&mut self, id: &Id, scope: Scope) -> Result<bool, Error> {
-        let mut stmt = self.db.prepare(
-            "INSERT INTO `repo-policies` (id, scope)
-             VALUES (?1, ?2)
-             ON CONFLICT DO UPDATE
-             SET scope = ?2 WHERE scope != ?2",
-        )?;
+    /// Set a repository's tracking policy.
+    pub fn set_repo_policy(&mut self, id: &Id, policy: Policy) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "INSERT INTO `repo-policies` (id, policy)
+                 VALUES (?1, ?2)
+                 ON CONFLICT DO UPDATE
+                 SET policy = ?2 WHERE policy != ?2",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, policy))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
+    }
 
-        stmt.bind((1, id))?;
-        stmt.bind((2, scope))?;
-        stmt.next()?;
+    /// Watch a repository, ie. subscribe to its announcements without fetching it. Has no
+    /// effect if the repository is already tracked.
+    pub fn watch_repo(&mut self, id: &Id) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "INSERT INTO `repo-policies` (id, policy)
+                 VALUES (?1, ?2)
+                 ON CONFLICT DO UPDATE
+                 SET policy = ?2 WHERE policy = ?3",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, Policy::Watch))?;
+            stmt.bind((3, Policy::Block))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
+    }
 
-        Ok(self.db.change_count() > 0)
+    /// Stop watching a repository. Has no effect if the repository is tracked.
+    pub fn unwatch_repo(&mut self, id: &Id) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt =
+                db.prepare("DELETE FROM `repo-policies` WHERE id = ?1 AND policy = ?2")?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, Policy::Watch))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
     }
 
-    /// Set a node's tracking policy.
-    pub fn set_node_policy(&mut self, id: &NodeId, policy: Policy) -> Result<bool, Error> {
-        let mut stmt = self.db.prepare(
-            "INSERT INTO `node-policies` (id, policy)
-             VALUES (?1, ?2)
-             ON CONFLICT DO UPDATE
-             SET policy = ?2 WHERE policy != ?2",
-        )?;
+    /// Set the maximum number of bytes received in a single fetch of a repository,
+    /// overriding the node's configured limit. Has no effect if the repository has no policy.
+    pub fn set_repo_fetch_limit(&mut self, id: &Id, limit: Option<u64>) -> Result<bool, Error> {
+        let limit = limit.map(|l| l.min(i64::MAX as u64) as i64);
 
-        stmt.bind((1, id))?;
-        stmt.bind((2, policy))?;
-        stmt.next()?;
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "UPDATE `repo-policies`
+                 SET fetch_limit = ?2
+                 WHERE id = ?1 AND fetch_limit IS NOT ?2",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, limit))?;
+            stmt.next()?;
 
-        Ok(self.db.change_count() > 0)
+            Ok(db.change_count() > 0)
+        })
     }
 
-    /// Set a repository's tracking policy.
-    pub fn set_repo_policy(&mut self, id: &Id, policy: Policy) -> Result<bool, Error> {
-        let mut stmt = self.db.prepare(
-            "INSERT INTO `repo-policies` (id, policy)
-             VALUES (?1, ?2)
-             ON CONFLICT DO UPDATE
-             SET policy = ?2 WHERE policy != ?2",
-        )?;
+    /// Set which refs are fetched from the remotes of a repository. Has no effect if the
+    /// repository has no policy.
+    pub fn set_repo_ref_filter(&mut self, id: &Id, filter: &RefFilter) -> Result<bool, Error> {
+        let filter = write_ref_filter(filter);
 
-        stmt.bind((1, id))?;
-        stmt.bind((2, policy))?;
-        stmt.next()?;
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "UPDATE `repo-policies`
+                 SET ref_filter = ?2
+                 WHERE id = ?1 AND ref_filter IS NOT ?2",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, filter.as_deref()))?;
+            stmt.next()?;
 
-        Ok(self.db.change_count() > 0)
+            Ok(db.change_count() > 0)
+        })
+    }
+
+    /// Record why a repository is in our inventory. Has no effect if the repository has
+    /// no policy.
+    pub fn set_repo_origin(&mut self, id: &Id, origin: Origin) -> Result<bool, Error> {
+        self.write(|db| {
+            let mut stmt = db.prepare(
+                "UPDATE `repo-policies`
+                 SET origin = ?2
+                 WHERE id = ?1 AND origin IS NOT ?2",
+            )?;
+
+            stmt.bind((1, id))?;
+            stmt.bind((2, origin))?;
+            stmt.next()?;
+
+            Ok(db.change_count() > 0)
+        })
     }
 
     /// Untrack a node.
     pub fn untrack_node(&mut self, id: &NodeId) -> Result<bool, Error> {
-        let mut stmt = self
-            .db
-            .prepare("DELETE FROM `node-policies` WHERE id = ?")?;
+        self.write(|db| {
+            let mut stmt = db.prepare("DELETE FROM `node-policies` WHERE id = ?")?;
 
-        stmt.bind((1, id))?;
-        stmt.next()?;
+            stmt.bind((1, id))?;
+            stmt.next()?;
 
-        Ok(self.db.change_count() > 0)
+            Ok(db.change_count() > 0)
+        })
     }
 
     /// Untrack a repository.
     pub fn untrack_repo(&mut self, id: &Id) -> Result<bool, Error> {
-        let mut stmt = self
-            .db
-            .prepare("DELETE FROM `repo-policies` WHERE id = ?")?;
+        self.write(|db| {
+            let mut stmt = db.prepare("DELETE FROM `repo-policies` WHERE id = ?")?;
 
-        stmt.bind((1, id))?;
-        stmt.next()?;
+            stmt.bind((1, id))?;
+            stmt.next()?;
 
-        Ok(self.db.change_count() > 0)
+            Ok(db.change_count() > 0)
+        })
+    }
+
+    /// Import tracking policies, eg. from an export of another node. Existing policies that
+    /// aren't part of the import are left untouched, and so are repositories of ours that
+    /// the import would block.
+    ///
+    /// The import is carried out in a single transaction: if any policy fails to be imported,
+    /// none are.
+    pub fn import(&mut self, policies: &Policies) -> Result<ImportSummary, Error> {
+        self.write(|db| {
+            transaction(db, |db| {
+                let mut summary = ImportSummary::default();
+
+                for repo in &policies.repos {
+                    let fetch_limit = repo.fetch_limit.map(|l| l.min(i64::MAX as u64) as i64);
+                    let ref_filter = write_ref_filter(&repo.ref_filter);
+                    let mut stmt = db.prepare(
+                        "SELECT scope, policy, fetch_limit, ref_filter, origin
+                         FROM `repo-policies` WHERE id = ?",
+                    )?;
+                    stmt.bind((1, &repo.id))?;
+
+                    let row = stmt.into_iter().next().transpose()?;
+                    let origin = row.as_ref().map(|row| row.read::<Origin, _>("origin"));
+                    let existing = row.map(|row| {
+                        (
+                            row.read::<Scope, _>("scope"),
+                            row.read::<Policy, _>("policy"),
+                            row.read::<Option<i64>, _>("fetch_limit"),
+                            row.read::<Option<&str>, _>("ref_filter").map(str::to_owned),
+                        )
+                    });
+                    if existing == Some((repo.scope, repo.policy, fetch_limit, ref_filter.clone()))
+                    {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    // There may be no other copy of our own repositories, so they aren't
+                    // blocked in bulk.
+                    if repo.policy == Policy::Block && origin.map_or(false, |o| o.is_local()) {
+                        log::warn!(target: "tracking", "Not blocking repository {}: it is ours", repo.id);
+                        summary.protected += 1;
+                        continue;
+                    }
+                    let mut stmt = db.prepare(
+                        "INSERT INTO `repo-policies` (id, scope, policy, fetch_limit, ref_filter)
+                         VALUES (?1, ?2, ?3, ?4, ?5)
+                         ON CONFLICT DO UPDATE
+                         SET scope = ?2, policy = ?3, fetch_limit = ?4, ref_filter = ?5",
+                    )?;
+                    stmt.bind((1, &repo.id))?;
+                    stmt.bind((2, repo.scope))?;
+                    stmt.bind((3, repo.policy))?;
+                    stmt.bind((4, fetch_limit))?;
+                    stmt.bind((5, ref_filter.as_deref()))?;
+                    stmt.next()?;
+
+                    if existing.is_some() {
+                        summary.updated += 1;
+                    } else {
+                        summary.added += 1;
+                    }
+                }
+
+                for node in &policies.nodes {
+                    let alias = node.alias.as_deref().unwrap_or_default();
+                    let mut stmt =
+                        db.prepare("SELECT alias, policy FROM `node-policies` WHERE id = ?")?;
+                    stmt.bind((1, &node.id))?;
+
+                    let existing = stmt.into_iter().next().transpose()?.map(|row| {
+                        (
+                            row.read::<&str, _>("alias").to_owned(),
+                            row.read::<Policy, _>("policy"),
+                        )
+                    });
+                    if existing == Some((alias.to_owned(), node.policy)) {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    let mut stmt = db.prepare(
+                        "INSERT INTO `node-policies` (id, alias, policy)
+                         VALUES (?1, ?2, ?3)
+                         ON CONFLICT DO UPDATE
+                         SET alias = ?2, policy = ?3",
+                    )?;
+                    stmt.bind((1, &node.id))?;
+                    stmt.bind((2, alias))?;
+                    stmt.bind((3, node.policy))?;
+                    stmt.next()?;
+
+                    if existing.is_some() {
+                        summary.updated += 1;
+                    } else {
+                        summary.added += 1;
+                    }
+                }
+                Ok(summary)
+            })
+        })
+    }
+
+    /// Run a write query. If the database is locked, SQLite already waits for up to
+    /// [`DB_WRITE_TIMEOUT`] for the lock to be released, so the query isn't retried:
+    /// this runs on the service thread, which shouldn't be blocked any longer.
+    fn write<T>(
+        &mut self,
+        query: impl FnOnce(&sql::Connection) -> Result<T, sql::Error>,
+    ) -> Result<T, Error> {
+        query(&self.db).map_err(|err| {
+            if err.code == Some(SQLITE_BUSY) {
+                log::warn!(target: "tracking", "Tracking database is still locked after {DB_WRITE_TIMEOUT:?}");
+            }
+            Error::from(err)
+        })
     }
 }
 
@@ -227,6 +500,17 @@ impl<T> Config<T> {
         ))
     }
 
+    /// Check if a repository is watched.
+    pub fn is_repo_watched(&self, id: &Id) -> Result<bool, Error> {
+        Ok(matches!(
+            self.repo_policy(id)?,
+            Some(Repo {
+                policy: Policy::Watch,
+                ..
+            })
+        ))
+    }
+
     /// Get a node's tracking policy.
     pub fn node_policy(&self, id: &NodeId) -> Result<Option<Node>, Error> {
         let mut stmt = self
@@ -236,12 +520,7 @@ impl<T> Config<T> {
         stmt.bind((1, id))?;
 
         if let Some(Ok(row)) = stmt.into_iter().next() {
-            let alias = row.read::<&str, _>("alias");
-            let alias = alias
-                .is_empty()
-                .not()
-                .then_some(alias.to_owned())
-                .and_then(|s| Alias::from_str(&s).ok());
+            let alias = read_alias(id, row.read::<&str, _>("alias"));
             let policy = row.read::<Policy, _>("policy");
 
             return Ok(Some(Node {
@@ -255,9 +534,7 @@ impl<T> Config<T> {
 
     /// Get a repository's tracking policy.
     pub fn repo_policy(&self, id: &Id) -> Result<Option<Repo>, Error> {
-        let mut stmt = self
-            .db
-            .prepare("SELECT scope, policy FROM `repo-policies` WHERE id = ?")?;
+        let mut stmt = self.repo_policies_query("WHERE id = ?")?;
 
         stmt.bind((1, id))?;
 
@@ -266,6 +543,9 @@ impl<T> Config<T> {
                 id: *id,
                 scope: row.read::<Scope, _>("scope"),
                 policy: row.read::<Policy, _>("policy"),
+                fetch_limit: read_fetch_limit(&row),
+                origin: row.read::<Origin, _>("origin"),
+                ref_filter: read_ref_filter(id, &row),
             }));
         }
         Ok(None)
@@ -281,12 +561,7 @@ impl<T> Config<T> {
 
         while let Some(Ok(row)) = stmt.next() {
             let id = row.read("id");
-            let alias = row.read::<&str, _>("alias").to_owned();
-            let alias = alias
-                .is_empty()
-                .not()
-                .then_some(alias.to_owned())
-                .and_then(|s| Alias::from_str(&s).ok());
+            let alias = read_alias(&id, row.read::<&str, _>("alias"));
             let policy = row.read::<Policy, _>("policy");
 
             entries.push(Node { id, alias, policy });
@@ -297,21 +572,89 @@ impl<T> Config<T> {
     // TODO: see if sql can return iterator directly
     /// Get repository tracking policies.
     pub fn repo_policies(&self) -> Result<Box<dyn Iterator<Item = Repo>>, Error> {
-        let mut stmt = self
-            .db
-            .prepare("SELECT id, scope, policy FROM `repo-policies`")?
-            .into_iter();
+        let mut stmt = self.repo_policies_query("")?.into_iter();
         let mut entries = Vec::new();
 
         while let Some(Ok(row)) = stmt.next() {
             let id = row.read("id");
             let scope = row.read("scope");
             let policy = row.read::<Policy, _>("policy");
+            let fetch_limit = read_fetch_limit(&row);
+            let origin = row.read::<Origin, _>("origin");
+            let ref_filter = read_ref_filter(&id, &row);
 
-            entries.push(Repo { id, scope, policy });
+            entries.push(Repo {
+                id,
+                scope,
+                policy,
+                fetch_limit,
+                origin,
+                ref_filter,
+            });
         }
         Ok(Box::new(entries.into_iter()))
     }
+
+    /// Prepare a query of repository policies, with the given filter.
+    fn repo_policies_query(&self, filter: &str) -> Result<sql::Statement<'_>, sql::Error> {
+        self.db.prepare(format!(
+            "SELECT id, scope, policy, fetch_limit, origin, ref_filter
+             FROM `repo-policies` {filter}"
+        ))
+    }
+
+    /// Export all tracking policies, eg. to back them up, or import them on another node.
+    pub fn export(&self) -> Result<Policies, Error> {
+        Ok(Policies {
+            repos: self.repo_policies()?.collect(),
+            nodes: self.node_policies()?.collect(),
+        })
+    }
+}
+
+/// Read a stored fetch limit.
+fn read_fetch_limit(row: &sql::Row) -> Option<u64> {
+    row.read::<Option<i64>, _>("fetch_limit").map(|l| l as u64)
+}
+
+/// Serialize a ref filter for storage. Empty filters aren't stored.
+fn write_ref_filter(filter: &RefFilter) -> Option<String> {
+    if filter.is_empty() {
+        return None;
+    }
+    serde_json::to_string(filter).ok()
+}
+
+/// Read a stored ref filter. Filters that can't be parsed are ignored, so that all refs
+/// are fetched, rather than none.
+fn read_ref_filter(rid: &Id, row: &sql::Row) -> RefFilter {
+    let Some(filter) = row.read::<Option<&str>, _>("ref_filter") else {
+        return RefFilter::default();
+    };
+    serde_json::from_str(filter).unwrap_or_else(|e| {
+        log::warn!(target: "tracking", "Invalid ref filter {filter:?} stored for {rid}: {e}");
+        RefFilter::default()
+    })
+}
+
+/// Read a stored alias. Aliases weren't always validated before being stored, so invalid
+/// aliases are normalized rather than rejected.
+fn read_alias(nid: &NodeId, alias: &str) -> Option<Alias> {
+    if alias.is_empty() {
+        return None;
+    }
+    match Alias::from_str(alias) {
+        Ok(alias) => Some(alias),
+        Err(e) => {
+            let normalized = Alias::from_lossy(alias);
+            log::warn!(
+                target: "tracking",
+                "Invalid alias {alias:?} stored for {nid} ({e}), normalized to {:?}",
+                normalized.as_deref().unwrap_or_default()
+            );
+            normalized
+        }
+    }
 }
 
 impl<T> AliasStore for Config<T> {
@@ -336,9 +679,9 @@ mod test {
         let id = arbitrary::gen::<NodeId>(1);
         let mut db = Config::open(":memory:").unwrap();
 
-        assert!(db.track_node(&id, Some("eve")).unwrap());
+        assert!(db.track_node(&id, Some(&Alias::new("eve"))).unwrap());
         assert!(db.is_node_tracked(&id).unwrap());
-        assert!(!db.track_node(&id, Some("eve")).unwrap());
+        assert!(!db.track_node(&id, Some(&Alias::new("eve"))).unwrap());
         assert!(db.untrack_node(&id).unwrap());
         assert!(!db.is_node_tracked(&id).unwrap());
     }
@@ -355,6 +698,34 @@ mod test {
         assert!(!db.is_repo_tracked(&id).unwrap());
     }
 
+    #[test]
+    fn test_watch_and_unwatch_repo() {
+        let id = arbitrary::gen::<Id>(1);
+        let mut db = Config::open(":memory:").unwrap();
+
+        assert!(db.watch_repo(&id).unwrap());
+        assert!(db.is_repo_watched(&id).unwrap());
+        assert!(!db.is_repo_tracked(&id).unwrap());
+        assert!(!db.watch_repo(&id).unwrap());
+        assert!(db.unwatch_repo(&id).unwrap());
+        assert!(!db.is_repo_watched(&id).unwrap());
+        assert_eq!(db.repo_policy(&id).unwrap(), None);
+
+        // Watching a blocked repository unblocks it.
+        assert!(db.set_repo_policy(&id, Policy::Block).unwrap());
+        assert!(db.watch_repo(&id).unwrap());
+        assert!(db.is_repo_watched(&id).unwrap());
+
+        // Tracking a watched repository tracks it.
+        assert!(db.track_repo(&id, Scope::Trusted).unwrap());
+        assert!(db.is_repo_tracked(&id).unwrap());
+
+        // Tracked repositories are neither watched nor unwatched.
+        assert!(!db.watch_repo(&id).unwrap());
+        assert!(!db.unwatch_repo(&id).unwrap());
+        assert!(db.is_repo_tracked(&id).unwrap());
+    }
+
     #[test]
     fn test_node_policies() {
         let ids = arbitrary::vec::<NodeId>(3);
@@ -388,7 +759,7 @@ mod test {
         let id = arbitrary::gen::<NodeId>(1);
         let mut db = Config::open(":memory:").unwrap();
 
-        assert!(db.track_node(&id, Some("eve")).unwrap());
+        assert!(db.track_node(&id, Some(&Alias::new("eve"))).unwrap());
         assert_eq!(
             db.node_policy(&id).unwrap().unwrap().alias,
             Some(Alias::from_str("eve").unwrap())
@@ -396,7 +767,7 @@ mod test {
         assert!(db.track_node(&id, None).unwrap());
         assert_eq!(db.node_policy(&id).unwrap().unwrap().alias, None);
         assert!(!db.track_node(&id, None).unwrap());
-        assert!(db.track_node(&id, Some("alice")).unwrap());
+        assert!(db.track_node(&id, Some(&Alias::new("alice"))).unwrap());
         assert_eq!(
             db.node_policy(&id).unwrap().unwrap().alias,
             Some(Alias::new("alice"))
@@ -425,6 +796,286 @@ mod test {
         assert_eq!(db.repo_policy(&id).unwrap().unwrap().policy, Policy::Block);
     }
 
+    #[test]
+    fn test_repo_fetch_limit() {
+        let id = arbitrary::gen::<Id>(1);
+        let mut db = Config::open(":memory:").unwrap();
+
+        // Repositories without a policy aren't affected.
+        assert!(!db.set_repo_fetch_limit(&id, Some(1024)).unwrap());
+        assert_eq!(db.repo_policy(&id).unwrap(), None);
+
+        assert!(db.track_repo(&id, Scope::All).unwrap());
+        assert_eq!(db.repo_policy(&id).unwrap().unwrap().fetch_limit, None);
+        assert!(db.set_repo_fetch_limit(&id, Some(1024)).unwrap());
+        assert!(!db.set_repo_fetch_limit(&id, Some(1024)).unwrap());
+        assert_eq!(
+            db.repo_policy(&id).unwrap().unwrap().fetch_limit,
+            Some(1024)
+        );
+        assert_eq!(
+            db.repo_policies().unwrap().next().unwrap().fetch_limit,
+            Some(1024)
+        );
+
+        // Changing the scope leaves the limit untouched.
+        assert!(db.track_repo(&id, Scope::Trusted).unwrap());
+        assert_eq!(
+            db.repo_policy(&id).unwrap().unwrap().fetch_limit,
+            Some(1024)
+        );
+
+        assert!(db.set_repo_fetch_limit(&id, None).unwrap());
+        assert_eq!(db.repo_policy(&id).unwrap().unwrap().fetch_limit, None);
+    }
+
+    #[test]
+    fn test_repo_ref_filter() {
+        let id = arbitrary::gen::<Id>(1);
+        let mut db = Config::open(":memory:").unwrap();
+        let filter = RefFilter {
+            include: vec![],
+            exclude: vec![crate::git::refspec::pattern!("refs/heads/artifacts/*")],
+        };
+
+        // Repositories without a policy aren't affected.
+        assert!(!db.set_repo_ref_filter(&id, &filter).unwrap());
+
+        assert!(db.track_repo(&id, Scope::All).unwrap());
+        assert!(db.repo_policy(&id).unwrap().unwrap().ref_filter.is_empty());
+        assert!(db.set_repo_ref_filter(&id, &filter).unwrap());
+        assert!(!db.set_repo_ref_filter(&id, &filter).unwrap());
+        assert_eq!(db.repo_policy(&id).unwrap().unwrap().ref_filter, filter);
+        assert_eq!(
+            db.repo_policies().unwrap().next().unwrap().ref_filter,
+            filter
+        );
+
+        // Changing the scope leaves the filter untouched.
+        assert!(db.track_repo(&id, Scope::Trusted).unwrap());
+        assert_eq!(db.repo_policy(&id).unwrap().unwrap().ref_filter, filter);
+
+        assert!(db.set_repo_ref_filter(&id, &RefFilter::default()).unwrap());
+        assert!(db.repo_policy(&id).unwrap().unwrap().ref_filter.is_empty());
+    }
+
+    #[test]
+    fn test_migrations() {
+        let id = arbitrary::gen::<Id>(1);
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("tracking.db");
+
+        // A store created before any migration.
+        {
+            let db = sql::Connection::open(&path).unwrap();
+            db.execute(Config::<Write>::SCHEMA).unwrap();
+
+            let mut stmt = db
+                .prepare("INSERT INTO `repo-policies` (id) VALUES (?)")
+                .unwrap();
+            stmt.bind((1, &id)).unwrap();
+            stmt.next().unwrap();
+        }
+
+        let mut db = Config::open(&path).unwrap();
+        let repo = db.repo_policy(&id).unwrap().unwrap();
+        assert_eq!(repo.fetch_limit, None);
+        assert_eq!(repo.origin, Origin::Tracked);
+        assert!(repo.ref_filter.is_empty());
+        assert!(db.set_repo_fetch_limit(&id, Some(1024)).unwrap());
+        drop(db);
+
+        // Migrations are only applied once.
+        let db = Config::open(&path).unwrap();
+        assert_eq!(
+            db.repo_policy(&id).unwrap().unwrap().fetch_limit,
+            Some(1024)
+        );
+        let reader = Config::reader(&path).unwrap();
+        assert_eq!(
+            reader.repo_policy(&id).unwrap().unwrap().fetch_limit,
+            Some(1024)
+        );
+    }
+
+    #[test]
+    fn test_repo_origin() {
+        let id = arbitrary::gen::<Id>(1);
+        let mut db = Config::open(":memory:").unwrap();
+
+        assert!(!db.set_repo_origin(&id, Origin::Created).unwrap());
+        assert!(db.track_repo(&id, Scope::All).unwrap());
+        assert_eq!(
+            db.repo_policy(&id).unwrap().unwrap().origin,
+            Origin::Tracked
+        );
+        assert!(db.set_repo_origin(&id, Origin::Created).unwrap());
+        assert!(!db.set_repo_origin(&id, Origin::Created).unwrap());
+        // Changing the scope doesn't reset the origin.
+        assert!(db.track_repo(&id, Scope::Trusted).unwrap());
+        assert_eq!(
+            db.repo_policy(&id).unwrap().unwrap().origin,
+            Origin::Created
+        );
+    }
+
+    #[test]
+    fn test_export_import() {
+        let rids = arbitrary::vec::<Id>(3);
+        let nids = arbitrary::vec::<NodeId>(3);
+        let mut db = Config::open(":memory:").unwrap();
+
+        db.track_repo(&rids[0], Scope::All).unwrap();
+        db.track_repo(&rids[1], Scope::Trusted).unwrap();
+        db.set_repo_policy(&rids[2], Policy::Block).unwrap();
+        db.set_repo_fetch_limit(&rids[1], Some(1024)).unwrap();
+        db.track_node(&nids[0], Some(&Alias::new("alice"))).unwrap();
+        db.track_node(&nids[1], None).unwrap();
+        db.set_node_policy(&nids[2], Policy::Block).unwrap();
+
+        let policies = db.export().unwrap();
+        assert_eq!(policies.repos.len(), 3);
+        assert_eq!(policies.nodes.len(), 3);
+
+        let mut other = Config::open(":memory:").unwrap();
+        let summary = other.import(&policies).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 6,
+                updated: 0,
+                skipped: 0,
+                protected: 0,
+            }
+        );
+        assert_eq!(other.export().unwrap(), policies);
+
+        // Importing the same policies again doesn't change anything.
+        let summary = other.import(&policies).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 0,
+                updated: 0,
+                skipped: 6,
+                protected: 0,
+            }
+        );
+        assert_eq!(other.export().unwrap(), policies);
+    }
+
+    #[test]
+    fn test_import_update() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let mut db = Config::open(":memory:").unwrap();
+
+        db.track_repo(&rid, Scope::Trusted).unwrap();
+        db.track_node(&nid, Some(&Alias::new("alice"))).unwrap();
+
+        let summary = db
+            .import(&Policies {
+                repos: vec![Repo {
+                    id: rid,
+                    scope: Scope::All,
+                    policy: Policy::Track,
+                    fetch_limit: None,
+                    origin: Origin::default(),
+                    ref_filter: RefFilter::default(),
+                }],
+                nodes: vec![Node {
+                    id: nid,
+                    alias: Some(Alias::new("alice")),
+                    policy: Policy::Block,
+                }],
+            })
+            .unwrap();
+
+        assert_eq!(summary.updated, 2);
+        assert_eq!(db.repo_policy(&rid).unwrap().unwrap().scope, Scope::All);
+        assert_eq!(db.node_policy(&nid).unwrap().unwrap().policy, Policy::Block);
+    }
+
+    #[test]
+    fn test_import_protected() {
+        let rids = arbitrary::vec::<Id>(3);
+        let mut db = Config::open(":memory:").unwrap();
+
+        for rid in &rids {
+            db.track_repo(rid, Scope::All).unwrap();
+        }
+        db.set_repo_origin(&rids[0], Origin::Created).unwrap();
+        db.set_repo_origin(&rids[1], Origin::Delegate).unwrap();
+
+        let summary = db
+            .import(&Policies {
+                repos: rids
+                    .iter()
+                    .map(|rid| Repo {
+                        id: *rid,
+                        scope: Scope::All,
+                        policy: Policy::Block,
+                        fetch_limit: None,
+                        origin: Origin::default(),
+                        ref_filter: RefFilter::default(),
+                    })
+                    .collect(),
+                nodes: vec![],
+            })
+            .unwrap();
+
+        assert_eq!(summary.protected, 2);
+        assert_eq!(summary.updated, 1);
+        assert!(db.is_repo_tracked(&rids[0]).unwrap());
+        assert!(db.is_repo_tracked(&rids[1]).unwrap());
+        assert!(!db.is_repo_tracked(&rids[2]).unwrap());
+    }
+
+    #[test]
+    fn test_import_atomic() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let mut db = Config::open(":memory:").unwrap();
+
+        // Fail any write to the node policies, after the repository policies are written.
+        db.db
+            .execute(
+                "CREATE TRIGGER `fail` BEFORE INSERT ON `node-policies`
+                 BEGIN SELECT RAISE(ABORT, 'failed'); END",
+            )
+            .unwrap();
+
+        let policies = Policies {
+            repos: vec![Repo {
+                id: rid,
+                scope: Scope::All,
+                policy: Policy::Track,
+                fetch_limit: None,
+                origin: Origin::default(),
+                ref_filter: RefFilter::default(),
+            }],
+            nodes: vec![Node {
+                id: nid,
+                alias: None,
+                policy: Policy::Track,
+            }],
+        };
+        assert!(db.import(&policies).is_err());
+        assert_eq!(db.repo_policy(&rid).unwrap(), None);
+        assert_eq!(db.export().unwrap(), Policies::default());
+    }
+
+    #[test]
+    fn test_faulty() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let mut db = Config::faulty().unwrap();
+
+        assert!(db.track_repo(&rid, Scope::All).is_err());
+        assert!(db.track_node(&nid, None).is_err());
+        assert!(!db.is_repo_tracked(&rid).unwrap());
+    }
+
     #[test]
     fn test_node_policy() {
         let id = arbitrary::gen::<NodeId>(1);
@@ -435,4 +1086,54 @@ mod test {
         assert!(db.set_node_policy(&id, Policy::Block).unwrap());
         assert_eq!(db.node_policy(&id).unwrap().unwrap().policy, Policy::Block);
     }
+
+    #[test]
+    fn test_invalid_alias() {
+        let id = arbitrary::gen::<NodeId>(1);
+        let db = Config::open(":memory:").unwrap();
+
+        // Aliases stored before they were validated.
+        let mut stmt = db
+            .db
+            .prepare("INSERT INTO `node-policies` (id, alias, policy) VALUES (?1, ?2, 'track')")
+            .unwrap();
+        stmt.bind((1, &id)).unwrap();
+        stmt.bind((2, "cloud head")).unwrap();
+        stmt.next().unwrap();
+
+        let node = db.node_policy(&id).unwrap().unwrap();
+        assert_eq!(node.alias, Some(Alias::new("cloudhead")));
+
+        let nodes = db.node_policies().unwrap().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].alias, Some(Alias::new("cloudhead")));
+        assert_eq!(db.alias(&id), Some(Alias::new("cloudhead")));
+    }
+
+    #[test]
+    fn test_open_or_recover() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("tracking.db");
+
+        std::fs::write(&path, [0xff; 4096]).unwrap();
+        assert!(matches!(
+            Config::open(&path),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e)
+        ));
+
+        let (mut db, backup) = Config::open_or_recover(&path).unwrap();
+        let backup = backup.unwrap();
+
+        assert_eq!(db.export().unwrap(), Policies::default());
+        assert_eq!(std::fs::read(&backup).unwrap(), [0xff; 4096]);
+
+        // The fresh store is usable, and isn't recovered again when re-opened.
+        let rid = arbitrary::gen::<Id>(1);
+        assert!(db.track_repo(&rid, Scope::All).unwrap());
+        drop(db);
+
+        let (db, backup) = Config::open_or_recover(&path).unwrap();
+        assert!(backup.is_none());
+        assert!(db.is_repo_tracked(&rid).unwrap());
+    }
 }
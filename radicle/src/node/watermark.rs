@@ -0,0 +1,355 @@
+//! Sync watermarks.
+//!
+//! For each namespace of a repository, we keep the signed refs we last fetched and the ones
+//! we last announced. Since they are persisted, we can tell which refs we already have or
+//! already announced after a restart.
+//!
+//! We also keep track of the repositories that are only partially replicated, because they
+//! were bootstrapped with a minimal fetch.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{fmt, io, time};
+
+use serde::{Deserialize, Serialize};
+use sqlite as sql;
+use thiserror::Error;
+
+use crate::crypto::Signature;
+use crate::prelude::{Id, NodeId};
+
+/// How long to wait for the database lock to be released before failing a read.
+const DB_READ_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+/// How long to wait for the database lock to be released before failing a write.
+const DB_WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(6);
+
+/// Watermark of a namespace. Signed refs are identified by their signature.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watermark {
+    /// Signature of the refs we last fetched successfully.
+    pub fetched: Option<Signature>,
+    /// Signature of the refs we last announced.
+    pub announced: Option<Signature>,
+}
+
+/// An error occuring in the watermark store.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// I/O error.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// An Internal error.
+    #[error("internal error: {0}")]
+    Internal(#[from] sql::Error),
+}
+
+/// Persistent file storage for sync watermarks.
+pub struct Table {
+    db: sql::Connection,
+}
+
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Table(..)")
+    }
+}
+
+impl Table {
+    const SCHEMA: &str = include_str!("watermark/schema.sql");
+
+    /// Open a watermark store at the given path. Creates a new empty store
+    /// if an existing store isn't found.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut db = sql::Connection::open(path)?;
+        db.set_busy_timeout(DB_WRITE_TIMEOUT.as_millis() as usize)?;
+        db.execute(Self::SCHEMA)?;
+
+        Ok(Self { db })
+    }
+
+    /// Same as [`Self::open`], but if the store is found to be corrupt, it is moved
+    /// aside and a fresh, empty store is created in its place. Returns the path the
+    /// corrupt store was moved to, if any.
+    pub fn open_or_recover<P: AsRef<Path>>(path: P) -> Result<(Self, Option<PathBuf>), Error> {
+        let path = path.as_ref();
+
+        match Self::open(path) {
+            Ok(table) => Ok((table, None)),
+            Err(Error::Internal(e)) if crate::sql::is_corrupt(&e) => {
+                let backup = crate::sql::quarantine(path)?;
+                let table = Self::open(path)?;
+
+                Ok((table, Some(backup)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Same as [`Self::open`], but in read-only mode. This is useful to have multiple
+    /// open databases, as no locking is required.
+    pub fn reader<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut db =
+            sql::Connection::open_with_flags(path, sqlite::OpenFlags::new().set_read_only())?;
+        db.set_busy_timeout(DB_READ_TIMEOUT.as_millis() as usize)?;
+        db.execute(Self::SCHEMA)?;
+
+        Ok(Self { db })
+    }
+
+    /// Create a new in-memory watermark store.
+    pub fn memory() -> Result<Self, Error> {
+        let db = sql::Connection::open(":memory:")?;
+        db.execute(Self::SCHEMA)?;
+
+        Ok(Self { db })
+    }
+
+    /// Set one of the watermark columns of a namespace.
+    fn set(
+        &mut self,
+        column: &str,
+        rid: &Id,
+        namespace: &NodeId,
+        refs: &Signature,
+    ) -> Result<bool, Error> {
+        let mut stmt = self.db.prepare(format!(
+            "INSERT INTO watermarks (repo, namespace, {column})
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT (repo, namespace) DO UPDATE
+             SET {column} = ?3 WHERE {column} IS NOT ?3"
+        ))?;
+
+        stmt.bind((1, rid))?;
+        stmt.bind((2, namespace))?;
+        stmt.bind((3, refs.to_string().as_str()))?;
+        stmt.next()?;
+
+        Ok(self.db.change_count() > 0)
+    }
+}
+
+/// Backing store for sync watermarks.
+pub trait Store {
+    /// Get the watermark of a namespace of a repository.
+    fn get(&self, rid: &Id, namespace: &NodeId) -> Result<Option<Watermark>, Error>;
+    /// Get the watermarks of all namespaces of a repository.
+    fn watermarks(&self, rid: &Id) -> Result<Vec<(NodeId, Watermark)>, Error>;
+    /// Record the signed refs of a namespace we fetched.
+    /// Returns whether the watermark changed.
+    fn fetched(&mut self, rid: &Id, namespace: &NodeId, refs: &Signature) -> Result<bool, Error>;
+    /// Record the signed refs of a namespace we announced.
+    /// Returns whether the watermark changed.
+    fn announced(&mut self, rid: &Id, namespace: &NodeId, refs: &Signature) -> Result<bool, Error>;
+    /// Get the repositories we have watermarks of.
+    fn repos(&self) -> Result<HashSet<Id>, Error>;
+    /// Remove the watermarks of a repository. Returns the number of namespaces removed.
+    fn remove(&mut self, rid: &Id) -> Result<usize, Error>;
+    /// Mark a repository as partially replicated, or not. Returns whether the mark changed.
+    /// Unlike watermarks, the mark isn't removed by [`Store::remove`], since it describes
+    /// what is in storage rather than what was synced.
+    fn set_partial(&mut self, rid: &Id, partial: bool) -> Result<bool, Error>;
+    /// Check whether a repository is partially replicated, ie. only its identity branch
+    /// and the namespaces of its delegates were fetched.
+    fn is_partial(&self, rid: &Id) -> Result<bool, Error>;
+}
+
+impl Store for Table {
+    fn get(&self, rid: &Id, namespace: &NodeId) -> Result<Option<Watermark>, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT fetched, announced FROM watermarks WHERE repo = ? AND namespace = ?",
+        )?;
+
+        stmt.bind((1, rid))?;
+        stmt.bind((2, namespace))?;
+
+        match stmt.into_iter().next() {
+            Some(row) => Ok(Some(read_watermark(&row?))),
+            None => Ok(None),
+        }
+    }
+
+    fn watermarks(&self, rid: &Id) -> Result<Vec<(NodeId, Watermark)>, Error> {
+        let mut stmt = self.db.prepare(
+            "SELECT namespace, fetched, announced FROM watermarks
+             WHERE repo = ? ORDER BY namespace",
+        )?;
+        stmt.bind((1, rid))?;
+
+        let mut watermarks = Vec::new();
+        for row in stmt.into_iter() {
+            let row = row?;
+            watermarks.push((row.read::<NodeId, _>("namespace"), read_watermark(&row)));
+        }
+        Ok(watermarks)
+    }
+
+    fn fetched(&mut self, rid: &Id, namespace: &NodeId, refs: &Signature) -> Result<bool, Error> {
+        self.set("fetched", rid, namespace, refs)
+    }
+
+    fn announced(&mut self, rid: &Id, namespace: &NodeId, refs: &Signature) -> Result<bool, Error> {
+        self.set("announced", rid, namespace, refs)
+    }
+
+    fn repos(&self) -> Result<HashSet<Id>, Error> {
+        let mut repos = HashSet::new();
+        for row in self
+            .db
+            .prepare("SELECT DISTINCT repo FROM watermarks")?
+            .into_iter()
+        {
+            repos.insert(row?.read::<Id, _>("repo"));
+        }
+        Ok(repos)
+    }
+
+    fn remove(&mut self, rid: &Id) -> Result<usize, Error> {
+        let mut stmt = self.db.prepare("DELETE FROM watermarks WHERE repo = ?")?;
+
+        stmt.bind((1, rid))?;
+        stmt.next()?;
+
+        Ok(self.db.change_count())
+    }
+
+    fn set_partial(&mut self, rid: &Id, partial: bool) -> Result<bool, Error> {
+        let mut stmt = if partial {
+            self.db
+                .prepare("INSERT INTO partial (repo) VALUES (?) ON CONFLICT DO NOTHING")?
+        } else {
+            self.db.prepare("DELETE FROM partial WHERE repo = ?")?
+        };
+
+        stmt.bind((1, rid))?;
+        stmt.next()?;
+
+        Ok(self.db.change_count() > 0)
+    }
+
+    fn is_partial(&self, rid: &Id) -> Result<bool, Error> {
+        let mut stmt = self.db.prepare("SELECT 1 FROM partial WHERE repo = ?")?;
+
+        stmt.bind((1, rid))?;
+
+        Ok(stmt.into_iter().next().transpose()?.is_some())
+    }
+}
+
+/// Read a watermark from a row. Signatures that can't be parsed are ignored.
+fn read_watermark(row: &sql::Row) -> Watermark {
+    let signature = |column: &str| {
+        row.read::<Option<&str>, _>(column)
+            .and_then(|s| Signature::from_str(s).ok())
+    };
+    Watermark {
+        fetched: signature("fetched"),
+        announced: signature("announced"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::arbitrary;
+
+    #[test]
+    fn test_fetched_announced() {
+        let rid = arbitrary::gen::<Id>(1);
+        let alice = arbitrary::gen::<NodeId>(1);
+        let [a, b] = [Signature::from([1; 64]), Signature::from([2; 64])];
+        let mut db = Table::memory().unwrap();
+
+        assert_eq!(db.get(&rid, &alice).unwrap(), None);
+        assert!(db.fetched(&rid, &alice, &a).unwrap());
+        assert!(!db.fetched(&rid, &alice, &a).unwrap());
+        assert_eq!(
+            db.get(&rid, &alice).unwrap(),
+            Some(Watermark {
+                fetched: Some(a),
+                announced: None
+            })
+        );
+
+        assert!(db.announced(&rid, &alice, &b).unwrap());
+        assert!(!db.announced(&rid, &alice, &b).unwrap());
+        assert!(db.fetched(&rid, &alice, &b).unwrap());
+        assert_eq!(
+            db.get(&rid, &alice).unwrap(),
+            Some(Watermark {
+                fetched: Some(b),
+                announced: Some(b)
+            })
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let rids = arbitrary::vec::<Id>(2);
+        let nids = arbitrary::vec::<NodeId>(2);
+        let sig = Signature::from([1; 64]);
+        let mut db = Table::memory().unwrap();
+
+        for rid in &rids {
+            for nid in &nids {
+                db.fetched(rid, nid, &sig).unwrap();
+            }
+        }
+        assert_eq!(db.repos().unwrap(), rids.iter().copied().collect());
+        assert_eq!(db.watermarks(&rids[0]).unwrap().len(), 2);
+
+        assert_eq!(db.remove(&rids[0]).unwrap(), 2);
+        assert_eq!(db.remove(&rids[0]).unwrap(), 0);
+        assert!(db.watermarks(&rids[0]).unwrap().is_empty());
+        assert_eq!(db.repos().unwrap(), HashSet::from([rids[1]]));
+    }
+
+    #[test]
+    fn test_partial() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let sig = Signature::from([1; 64]);
+        let mut db = Table::memory().unwrap();
+
+        assert!(!db.is_partial(&rid).unwrap());
+        assert!(db.set_partial(&rid, true).unwrap());
+        assert!(!db.set_partial(&rid, true).unwrap());
+        assert!(db.is_partial(&rid).unwrap());
+
+        db.fetched(&rid, &nid, &sig).unwrap();
+        db.remove(&rid).unwrap();
+        assert!(db.is_partial(&rid).unwrap());
+
+        assert!(db.set_partial(&rid, false).unwrap());
+        assert!(!db.set_partial(&rid, false).unwrap());
+        assert!(!db.is_partial(&rid).unwrap());
+    }
+
+    #[test]
+    fn test_persistence() {
+        let rid = arbitrary::gen::<Id>(1);
+        let nid = arbitrary::gen::<NodeId>(1);
+        let sig = Signature::from([1; 64]);
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("watermarks.db");
+
+        {
+            let mut db = Table::open(&path).unwrap();
+            db.fetched(&rid, &nid, &sig).unwrap();
+            db.announced(&rid, &nid, &sig).unwrap();
+        }
+
+        let expected = Watermark {
+            fetched: Some(sig),
+            announced: Some(sig),
+        };
+        assert_eq!(
+            Table::reader(&path).unwrap().get(&rid, &nid).unwrap(),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            Table::open(&path).unwrap().watermarks(&rid).unwrap(),
+            vec![(nid, expected)]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+--
+-- Sync watermarks SQL schema.
+--
+-- Signed refs are identified by their signature, which is deterministic over the refs.
+-- Unlike the `rad/sigrefs` commit, it can be compared with the refs of an announcement.
+--
+create table if not exists "watermarks" (
+  -- Repository ID.
+  "repo"         text      not null,
+  -- Namespace, ie. the Node ID of the remote.
+  "namespace"    text      not null,
+  -- Signature of the refs of the namespace we last fetched successfully.
+  "fetched"      text,
+  -- Signature of the refs of the namespace we last announced.
+  "announced"    text,
+
+  primary key ("repo", "namespace")
+);
+
+-- Repositories of which only the identity branch and the delegates' namespaces were
+-- fetched. The next regular fetch fetches the other namespaces.
+create table if not exists "partial" (
+  -- Repository ID.
+  "repo"         text      primary key not null
+);
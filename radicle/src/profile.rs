@@ -9,9 +9,13 @@
 //!       radicle.pub                            # Public key (PKCS 8)
 //!     node/
 //!       control.sock                           # Node control socket
+//!       control.json                           # Node control address & token (TCP transport)
+//!       control.operator                       # Node control operator token (if enabled)
 //!
+use std::collections::BTreeSet;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::{fs, io, str::FromStr};
 
 use serde::Serialize;
@@ -21,7 +25,7 @@ use crate::crypto::ssh::agent::Agent;
 use crate::crypto::ssh::{keystore, Keystore, Passphrase};
 use crate::crypto::{PublicKey, Signer};
 use crate::node;
-use crate::node::{address, routing, tracking, Alias, AliasStore};
+use crate::node::{address, routing, tracking, watermark, Alias, AliasStore};
 use crate::prelude::Did;
 use crate::prelude::NodeId;
 use crate::storage::git::transport;
@@ -39,6 +43,8 @@ pub mod env {
     pub const RAD_PASSPHRASE: &str = "RAD_PASSPHRASE";
     /// RNG seed. Must be convertible to a `u64`.
     pub const RAD_RNG_SEED: &str = "RAD_RNG_SEED";
+    /// Set to `1` or `true` to have tools work without connecting to the node.
+    pub const RAD_OFFLINE: &str = "RAD_OFFLINE";
 
     /// Get the radicle passphrase from the environment.
     pub fn passphrase() -> Option<super::Passphrase> {
@@ -48,6 +54,11 @@ pub mod env {
         Some(super::Passphrase::from(passphrase))
     }
 
+    /// Check whether we're offline, according to the environment.
+    pub fn offline() -> bool {
+        matches!(std::env::var(RAD_OFFLINE).as_deref(), Ok("1" | "true"))
+    }
+
     /// Get a random number generator from the environment.
     pub fn rng() -> fastrand::Rng {
         if let Ok(seed) = std::env::var(RAD_RNG_SEED) {
@@ -88,6 +99,10 @@ pub enum ConfigError {
     Io(PathBuf, io::Error),
     #[error("failed to decode node configuration from {0}: {1}")]
     Json(PathBuf, serde_json::Error),
+    #[error("failed to write node configuration to {0}: {1}")]
+    Write(PathBuf, io::Error),
+    #[error(transparent)]
+    Limit(#[from] node::config::LimitError),
 }
 
 /// Local radicle configuration.
@@ -95,6 +110,27 @@ pub enum ConfigError {
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     pub node: node::Config,
+    /// Command-line interface configuration.
+    #[serde(default, skip_serializing_if = "crate::serde_ext::is_default")]
+    pub cli: CliConfig,
+}
+
+/// Command-line interface configuration.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CliConfig {
+    /// Have `rad remote sync` add working copy remotes for all tracked nodes.
+    pub add_remotes: bool,
+    /// Tracked nodes `rad remote sync` adds working copy remotes for, even if
+    /// `addRemotes` is off. Nodes are added with `rad track <nid> --add-remotes`.
+    pub remotes: BTreeSet<NodeId>,
+}
+
+impl CliConfig {
+    /// Check whether `rad remote sync` should add working copy remotes for the given node.
+    pub fn adds_remotes_for(&self, nid: &NodeId) -> bool {
+        self.add_remotes || self.remotes.contains(nid)
+    }
 }
 
 impl Config {
@@ -102,21 +138,104 @@ impl Config {
     pub fn init(alias: Alias, path: &Path) -> io::Result<Self> {
         let cfg = Self {
             node: node::Config::new(alias),
+            cli: CliConfig::default(),
         };
-        let mut file = fs::OpenOptions::new()
+        let file = fs::OpenOptions::new()
             .create_new(true)
             .write(true)
             .open(path)?;
-        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"  ");
-        let mut serializer = serde_json::Serializer::with_formatter(&file, formatter);
-
-        cfg.serialize(&mut serializer)?;
-        file.write_all(b"\n")?;
-        file.sync_all()?;
+        to_writer(&cfg, &file)?;
 
         Ok(cfg)
     }
 
+    /// Write the configuration to the given path, atomically replacing the existing
+    /// configuration.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let _lock = UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        replace(path, self)
+    }
+
+    /// Update the peers of the node configuration at the given path, with a function that
+    /// is passed the node configuration. Only the `connect` and `peer` fields are written
+    /// back, leaving the rest of the file as it was, including fields unknown to this
+    /// version. The file is replaced atomically, and concurrent updates from this process
+    /// are serialized, so that none of them are lost.
+    pub fn update_peers<T>(
+        path: &Path,
+        f: impl FnOnce(&mut node::Config) -> T,
+    ) -> Result<T, ConfigError> {
+        Self::update_node(path, |config, node| {
+            let json = |e| ConfigError::Json(path.to_path_buf(), e);
+            let result = f(config);
+
+            node.insert(
+                String::from("connect"),
+                serde_json::to_value(&config.connect).map_err(json)?,
+            );
+            if config.peer_entries.is_empty() {
+                node.remove("peer");
+            } else {
+                node.insert(
+                    String::from("peer"),
+                    serde_json::to_value(&config.peer_entries).map_err(json)?,
+                );
+            }
+            Ok(result)
+        })
+    }
+
+    /// Set one of the limits of the node configuration at the given path, see
+    /// [`node::config::Limits::set`]. Only that limit is written back, the same way as
+    /// with [`Config::update_peers`].
+    pub fn set_limit(path: &Path, name: &str, value: serde_json::Value) -> Result<(), ConfigError> {
+        Self::update_node(path, |config, node| {
+            config.limits.set(name, value)?;
+
+            // Nb. The limit is written as it is encoded, eg. after string values were parsed.
+            let value = serde_json::to_value(&config.limits)
+                .map_err(|e| ConfigError::Json(path.to_path_buf(), e))?
+                .get(name)
+                .cloned()
+                .unwrap_or_default();
+            let limits = node
+                .entry("limits")
+                .or_insert_with(|| serde_json::Value::Object(Default::default()));
+
+            if let Some(limits) = limits.as_object_mut() {
+                limits.insert(name.to_owned(), value);
+            }
+            Ok(())
+        })
+    }
+
+    /// Update the node configuration at the given path. The function is passed the decoded
+    /// node configuration, and the node configuration as it is in the file, which is what
+    /// is written back.
+    fn update_node<T>(
+        path: &Path,
+        f: impl FnOnce(
+            &mut node::Config,
+            &mut serde_json::Map<String, serde_json::Value>,
+        ) -> Result<T, ConfigError>,
+    ) -> Result<T, ConfigError> {
+        let _lock = UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let json = |e| ConfigError::Json(path.to_path_buf(), e);
+        let file = fs::File::open(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        let mut value: serde_json::Value = serde_json::from_reader(file).map_err(json)?;
+        let mut config: Config = serde_json::from_value(value.clone()).map_err(json)?;
+
+        let Some(node) = value.get_mut("node").and_then(|n| n.as_object_mut()) else {
+            unreachable!("Config::update_node: node configuration was decoded");
+        };
+        let result = f(&mut config.node, node)?;
+
+        replace(path, &value).map_err(|e| ConfigError::Write(path.to_path_buf(), e))?;
+
+        Ok(result)
+    }
+
     /// Load a configuration from the given path.
     pub fn load(path: &Path) -> Result<Self, ConfigError> {
         match fs::File::open(path) {
@@ -132,6 +251,7 @@ impl Config {
                 };
                 Ok(Config {
                     node: node::Config::new(alias),
+                    cli: CliConfig::default(),
                 })
             }
         }
@@ -143,6 +263,39 @@ impl Config {
     }
 }
 
+/// Serializes updates of configuration files made by this process.
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Replace the file at the given path with a value, as pretty-printed JSON. The value is
+/// written to a uniquely named file in the same directory, which is renamed over the
+/// original, and the directory is synced so that the rename survives a crash.
+fn replace(path: &Path, value: &impl Serialize) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = tempfile::Builder::new()
+        .prefix(&format!(".{name}."))
+        .suffix(".tmp")
+        .tempfile_in(dir)?;
+
+    to_writer(value, tmp.as_file())?;
+    tmp.persist(path).map_err(|e| e.error)?;
+
+    fs::File::open(dir)?.sync_all()
+}
+
+/// Write a value as pretty-printed JSON to a file, and sync it to disk.
+fn to_writer(value: &impl Serialize, mut file: &fs::File) -> io::Result<()> {
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"  ");
+    let mut serializer = serde_json::Serializer::with_formatter(file, formatter);
+
+    value.serialize(&mut serializer)?;
+    file.write_all(b"\n")?;
+    file.sync_all()
+}
+
 #[derive(Debug, Clone)]
 pub struct Profile {
     pub home: Home,
@@ -246,6 +399,14 @@ impl Profile {
         Ok(addresses)
     }
 
+    /// Return a read-only handle to the sync watermarks database of the node.
+    pub fn watermarks(&self) -> Result<watermark::Table, watermark::Error> {
+        let path = self.home.node().join(node::WATERMARK_DB_FILE);
+        let watermarks = watermark::Table::reader(path)?;
+
+        Ok(watermarks)
+    }
+
     /// Return a multi-source store for aliases.
     pub fn aliases(&self) -> Aliases {
         let tracking = self.tracking().ok();
@@ -271,6 +432,11 @@ impl Profile {
     pub fn socket(&self) -> PathBuf {
         self.home.socket()
     }
+
+    /// Get the address of the node control interface.
+    pub fn control(&self) -> node::control::Address {
+        self.home.control(self.config.node.control)
+    }
 }
 
 /// Holds multiple alias stores, and will try
@@ -374,14 +540,34 @@ impl Home {
             .map(PathBuf::from)
             .unwrap_or_else(|| self.node().join(node::DEFAULT_SOCKET_NAME))
     }
+
+    /// Path to the file holding the TCP control listener address and token.
+    pub fn control_endpoint(&self) -> PathBuf {
+        self.node().join(node::control::DEFAULT_ENDPOINT_NAME)
+    }
+
+    /// Path to the file holding the control operator token.
+    pub fn control_operator(&self) -> PathBuf {
+        self.node().join(node::control::DEFAULT_OPERATOR_TOKEN_NAME)
+    }
+
+    /// Get the address of the node control interface, for the given transport.
+    pub fn control(&self, transport: node::control::Transport) -> node::control::Address {
+        node::control::Address::resolve(transport, self.socket(), self.control_endpoint())
+    }
 }
 
 #[cfg(test)]
 #[cfg(not(target_os = "macos"))]
 mod test {
     use std::fs;
+    use std::str::FromStr;
 
-    use super::Home;
+    use super::{Config, Home};
+    use crate::node::config::PeerEntry;
+    use crate::node::Alias;
+    use crate::prelude::NodeId;
+    use crate::test::arbitrary;
 
     // Checks that if we have:
     // '/run/user/1000/.tmpqfK6ih/../.tmpqfK6ih/Radicle/Home'
@@ -406,4 +592,108 @@ mod test {
 
         assert_eq!(home.path, path);
     }
+
+    #[test]
+    fn test_update_peers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        let peer = PeerEntry {
+            id: arbitrary::gen(1),
+            address: std::net::SocketAddr::from(([1, 1, 1, 1], 8776)).into(),
+            alias: Some(Alias::from_str("bob").unwrap()),
+            persistent: true,
+        };
+        Config::init(Alias::from_str("alice").unwrap(), &path).unwrap();
+
+        // Add a field unknown to this version.
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        value["node"]["unknown"] = serde_json::json!(42);
+        fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+        assert!(Config::update_peers(&path, |c| c.add_peer(peer.clone())).unwrap());
+        assert!(!Config::update_peers(&path, |c| c.add_peer(peer.clone())).unwrap());
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.node.peer_entries, vec![peer.clone()]);
+        assert_eq!(config.node.peer(&peer.id), Some(&peer.address));
+
+        let value: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(value["node"]["unknown"], 42);
+
+        assert!(Config::update_peers(&path, |c| c.remove_peer(&peer.id)).unwrap());
+        assert!(Config::load(&path).unwrap().node.peer_entries.is_empty());
+        assert_eq!(
+            fs::read_dir(tmp.path()).unwrap().count(),
+            1,
+            "no temporary files left"
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        let nid = arbitrary::gen::<NodeId>(1);
+        let mut config = Config::init(Alias::from_str("alice").unwrap(), &path).unwrap();
+
+        config.cli.remotes.insert(nid);
+        config.write(&path).unwrap();
+
+        assert!(Config::load(&path).unwrap().cli.remotes.contains(&nid));
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_set_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        Config::init(Alias::from_str("alice").unwrap(), &path).unwrap();
+
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        value["node"]["unknown"] = serde_json::json!(42);
+        fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+        Config::set_limit(&path, "staleTimeout", serde_json::json!("300")).unwrap();
+        assert!(Config::set_limit(&path, "staleTimeout", serde_json::json!(0)).is_err());
+
+        let value: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(value["node"]["limits"]["staleTimeout"], 300);
+        assert_eq!(value["node"]["unknown"], 42);
+        assert_eq!(
+            Config::load(&path)
+                .unwrap()
+                .node
+                .limits
+                .stale_timeout
+                .as_secs(),
+            300
+        );
+    }
+
+    #[test]
+    fn test_update_peers_concurrent() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        let peers = arbitrary::vec::<NodeId>(8);
+        Config::init(Alias::from_str("alice").unwrap(), &path).unwrap();
+
+        std::thread::scope(|s| {
+            for id in &peers {
+                let path = &path;
+                s.spawn(move || {
+                    let peer = PeerEntry {
+                        id: *id,
+                        address: std::net::SocketAddr::from(([1, 1, 1, 1], 8776)).into(),
+                        alias: None,
+                        persistent: true,
+                    };
+                    Config::update_peers(path, |c| c.add_peer(peer)).unwrap();
+                });
+            }
+        });
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.node.peer_entries.len(), peers.len());
+    }
 }
@@ -1,7 +1,9 @@
 #![allow(clippy::let_unit_value)]
-use std::io;
-use std::path::Path;
+use std::collections::HashSet;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::{fs, io};
 
 use once_cell::sync::Lazy;
 use thiserror::Error;
@@ -198,6 +200,8 @@ pub enum CheckoutError {
     Fetch(#[source] git2::Error),
     #[error("git: {0}")]
     Git(#[from] git2::Error),
+    #[error("i/o: {0}")]
+    Io(#[from] io::Error),
     #[error("storage: {0}")]
     Storage(#[from] storage::Error),
     #[error("payload: {0}")]
@@ -206,6 +210,31 @@ pub enum CheckoutError {
     NotFound(Id),
     #[error("project error: {0}")]
     Identity(#[from] IdentityError),
+    #[error("path {0:?} already exists and is not an empty directory")]
+    AlreadyExists(PathBuf),
+    #[error("path {0:?} was not found in the project's default branch")]
+    SparsePathNotFound(PathBuf),
+}
+
+/// Working copy checkout progress.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CheckoutProgress {
+    /// Number of files written so far.
+    pub written: usize,
+    /// Total number of files to write.
+    pub total: usize,
+}
+
+/// Options for [`checkout_with`].
+#[derive(Default)]
+pub struct CheckoutOptions<'a> {
+    /// Only check out the files under this path of the project tree.
+    pub sparse: Option<&'a Path>,
+    /// Continue a previously interrupted checkout, and keep the partial checkout around
+    /// if this one fails too, so that it can be resumed later.
+    pub resume: bool,
+    /// Called every time a file is written. Returning an error aborts the checkout.
+    pub progress: Option<&'a mut dyn FnMut(CheckoutProgress) -> io::Result<()>>,
 }
 
 /// Checkout a project from storage as a working copy.
@@ -216,6 +245,67 @@ pub fn checkout<P: AsRef<Path>, S: storage::ReadStorage>(
     path: P,
     storage: &S,
 ) -> Result<git2::Repository, CheckoutError> {
+    checkout_with(proj, remote, path, storage, CheckoutOptions::default())
+}
+
+/// Checkout a project from storage as a working copy, with the given options.
+///
+/// The working copy is created in a temporary directory next to `path`, which is renamed
+/// into place once the checkout is complete. An interrupted checkout can be resumed with
+/// [`CheckoutOptions::resume`], in which case the objects already fetched are re-used,
+/// and the files already checked out are not written again.
+pub fn checkout_with<P: AsRef<Path>, S: storage::ReadStorage>(
+    proj: Id,
+    remote: &RemoteId,
+    path: P,
+    storage: &S,
+    mut options: CheckoutOptions,
+) -> Result<git2::Repository, CheckoutError> {
+    let path = path.as_ref();
+    let Some(name) = path.file_name() else {
+        return Err(CheckoutError::AlreadyExists(path.to_path_buf()));
+    };
+    if path.exists() && (!path.is_dir() || path.read_dir()?.next().is_some()) {
+        return Err(CheckoutError::AlreadyExists(path.to_path_buf()));
+    }
+    let mut tmp = std::ffi::OsString::from(".");
+    tmp.push(name);
+    tmp.push(".checkout");
+    let tmp = path.with_file_name(tmp);
+
+    if tmp.exists() && !options.resume {
+        // A stale partial checkout that we aren't resuming.
+        fs::remove_dir_all(&tmp)?;
+    }
+
+    match checkout_into(proj, remote, &tmp, storage, &mut options) {
+        Ok(()) => {
+            if path.exists() {
+                fs::remove_dir(path)?;
+            }
+            fs::rename(&tmp, path)?;
+
+            Ok(git2::Repository::open(path)?)
+        }
+        Err(e) => {
+            if !options.resume {
+                fs::remove_dir_all(&tmp).ok();
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Name of the file tracking the paths checked out so far, under the `.git` directory.
+const CHECKOUT_MANIFEST: &str = "RAD_CHECKOUT";
+
+fn checkout_into<S: storage::ReadStorage>(
+    proj: Id,
+    remote: &RemoteId,
+    path: &Path,
+    storage: &S,
+    options: &mut CheckoutOptions,
+) -> Result<(), CheckoutError> {
     // TODO: Decide on whether we can use `clone_local`
     // TODO: Look into sharing object databases.
     let doc = storage
@@ -223,50 +313,172 @@ pub fn checkout<P: AsRef<Path>, S: storage::ReadStorage>(
         .ok_or(CheckoutError::NotFound(proj))?;
     let project = doc.project()?;
 
-    let mut opts = git2::RepositoryInitOptions::new();
-    opts.no_reinit(true).description(project.description());
+    let repo = match git2::Repository::open(path) {
+        Ok(repo) if options.resume => repo,
+        _ => {
+            let mut opts = git2::RepositoryInitOptions::new();
+            opts.no_reinit(true).description(project.description());
 
-    let repo = git2::Repository::init_opts(path.as_ref(), &opts)?;
+            git2::Repository::init_opts(path, &opts)?
+        }
+    };
     let url = git::Url::from(proj);
 
     // Configure and fetch all refs from remote.
-    git::configure_remote(
-        &repo,
-        &REMOTE_NAME,
-        &url,
-        &url.clone().with_namespace(*remote),
-    )?;
+    if repo.find_remote(&REMOTE_NAME).is_err() {
+        git::configure_remote(
+            &repo,
+            &REMOTE_NAME,
+            &url,
+            &url.clone().with_namespace(*remote),
+        )?;
+    }
     git::fetch(&repo, &REMOTE_NAME).map_err(CheckoutError::Fetch)?;
 
-    {
-        // Setup default branch.
-        let remote_head_ref =
-            git::refs::workdir::remote_branch(&REMOTE_NAME, project.default_branch());
+    // Setup default branch.
+    let remote_head_ref = git::refs::workdir::remote_branch(&REMOTE_NAME, project.default_branch());
+    let remote_head_commit = repo.find_reference(&remote_head_ref)?.peel_to_commit()?;
+    let branch_ref = git::refs::workdir::branch(project.default_branch());
+
+    // Nb. When resuming, the branch may already exist and be checked out.
+    repo.reference(
+        &branch_ref,
+        remote_head_commit.id(),
+        true,
+        "checkout: setup default branch",
+    )?;
+    repo.set_head(&branch_ref)?;
+
+    // Check out the working copy, only considering the sparse path if one was given.
+    let tree = remote_head_commit.tree()?;
+    let prefix = match options.sparse {
+        Some(sparse) => {
+            let entry = tree
+                .get_path(sparse)
+                .map_err(|_| CheckoutError::SparsePathNotFound(sparse.to_path_buf()))?;
+            if entry.kind() != Some(git2::ObjectType::Tree) {
+                return Err(CheckoutError::SparsePathNotFound(sparse.to_path_buf()));
+            }
+            let prefix = format!("{}/", sparse.to_string_lossy().trim_end_matches('/'));
+
+            Some(prefix)
+        }
+        None => None,
+    };
+    checkout_files(&repo, &tree, prefix.as_deref().unwrap_or_default(), options)?;
+
+    // Mark files outside of the sparse path as not checked out.
+    if let Some(prefix) = prefix {
+        let mut index = repo.index()?;
+        index.read_tree(&tree)?;
+
+        let skipped = index
+            .iter()
+            .filter(|e| !e.path.starts_with(prefix.as_bytes()))
+            .collect::<Vec<_>>();
+        for mut entry in skipped {
+            entry.flags_extended |= git2::IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+        }
+        index.write()?;
+
+        repo.config()?.set_bool("core.sparseCheckout", true)?;
+        fs::create_dir_all(repo.path().join("info"))?;
+        fs::write(
+            repo.path().join("info").join("sparse-checkout"),
+            format!("/{prefix}\n"),
+        )?;
+    }
+
+    // Setup remote tracking for default branch.
+    git::set_upstream(&repo, &*REMOTE_NAME, project.default_branch(), &branch_ref)?;
+
+    Ok(())
+}
 
-        let remote_head_commit = repo.find_reference(&remote_head_ref)?.peel_to_commit()?;
-        let branch = repo
-            .branch(project.default_branch(), &remote_head_commit, true)?
-            .into_reference();
-        let branch_ref = branch
-            .name()
-            .expect("checkout: default branch name is valid UTF-8");
+/// Check out the files of the given tree under the given path prefix, one by one. Each
+/// path is recorded in a manifest, so that a resumed checkout doesn't write it again.
+fn checkout_files(
+    repo: &git2::Repository,
+    tree: &git2::Tree,
+    prefix: &str,
+    options: &mut CheckoutOptions,
+) -> Result<(), CheckoutError> {
+    let manifest = repo.path().join(CHECKOUT_MANIFEST);
+    let done = match fs::read(&manifest) {
+        Ok(bytes) => bytes
+            .split(|b| *b == 0)
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_vec())
+            .collect::<HashSet<_>>(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+        Err(e) => return Err(e.into()),
+    };
 
-        repo.set_head(branch_ref)?;
-        repo.checkout_head(None)?;
+    let mut files = Vec::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() != Some(git2::ObjectType::Tree) {
+            let mut path = dir.as_bytes().to_vec();
+            path.extend_from_slice(entry.name_bytes());
 
-        // Setup remote tracking for default branch.
-        git::set_upstream(&repo, &*REMOTE_NAME, project.default_branch(), branch_ref)?;
+            if path.starts_with(prefix.as_bytes()) {
+                files.push(path);
+            }
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+
+    let total = files.len();
+    let mut written = files.iter().filter(|p| done.contains(*p)).count();
+    let mut log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&manifest)?;
+
+    if let Some(progress) = &mut options.progress {
+        progress(CheckoutProgress { written, total })?;
+    }
+    for path in files {
+        if done.contains(&path) {
+            continue;
+        }
+        // Nb. Pathspecs are glob patterns, so special characters are escaped to only
+        // match the path itself.
+        let mut pathspec = Vec::with_capacity(path.len());
+        for b in &path {
+            if matches!(b, b'*' | b'?' | b'[' | b'\\') {
+                pathspec.push(b'\\');
+            }
+            pathspec.push(*b);
+        }
+        let mut builder = git2::build::CheckoutBuilder::new();
+        builder.force().path(pathspec);
+        repo.checkout_tree(tree.as_object(), Some(&mut builder))?;
+
+        log.write_all(&path)?;
+        log.write_all(&[0])?;
+        written += 1;
+
+        if let Some(progress) = &mut options.progress {
+            progress(CheckoutProgress { written, total })?;
+        }
     }
+    drop(log);
+    fs::remove_file(manifest)?;
 
-    Ok(repo)
+    Ok(())
 }
 
 #[derive(Error, Debug)]
 pub enum RemoteError {
     #[error("git: {0}")]
     Git(#[from] git2::Error),
-    #[error("invalid remote url: {0}")]
-    Url(#[from] transport::local::UrlError),
+    #[error("invalid remote url '{url}': {err}")]
+    Url {
+        url: String,
+        #[source]
+        err: transport::local::UrlError,
+    },
     #[error("invalid utf-8 string")]
     InvalidUtf8,
     #[error("remote `{0}` not found")]
@@ -285,7 +497,10 @@ pub fn remote(repo: &git2::Repository) -> Result<(git2::Remote<'_>, Id), RemoteE
         }
     })?;
     let url = remote.url().ok_or(RemoteError::InvalidUtf8)?;
-    let url = git::Url::from_str(url)?;
+    let url = git::Url::from_str(url).map_err(|err| RemoteError::Url {
+        url: url.to_owned(),
+        err,
+    })?;
 
     Ok((remote, url.repo))
 }
@@ -360,10 +575,11 @@ pub fn setup_patch_upstream<'a>(
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     use radicle_crypto::test::signer::MockSigner;
 
+    use crate::assert_matches;
     use crate::git::{name::component, qualified};
     use crate::identity::Did;
     use crate::storage::git::transport;
@@ -512,4 +728,225 @@ mod tests {
                 .collect::<Vec<_>>(),
         );
     }
+
+    /// Create a project with a few files, in nested directories.
+    fn project_with_files(tempdir: &Path, signer: &MockSigner, storage: &Storage) -> Id {
+        let (original, _) = fixtures::repository(tempdir.join("original"));
+        let workdir = original.workdir().unwrap();
+
+        for (path, content) in [
+            ("src/main.rs", "fn main() {}\n"),
+            ("src/lib.rs", "pub mod util;\n"),
+            ("src/util/mod.rs", "pub fn util() {}\n"),
+            ("docs/guide.md", "# Guide\n"),
+            ("LICENSE", "MIT\n"),
+        ] {
+            let path = workdir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let mut index = original.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+
+        let tree = original.find_tree(index.write_tree().unwrap()).unwrap();
+        let head = original.head().unwrap().peel_to_commit().unwrap();
+        let sig = head.author();
+        original
+            .commit(Some("HEAD"), &sig, &sig, "Add files", &tree, &[&head])
+            .unwrap();
+
+        let (id, _, _) = init(
+            &original,
+            "acme",
+            "Acme's repo",
+            git::refname!("master"),
+            signer,
+            storage,
+        )
+        .unwrap();
+        id
+    }
+
+    /// Read all the files of a working copy, excluding the `.git` directory.
+    fn files(root: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        fn visit(root: &Path, dir: &Path, files: &mut BTreeMap<PathBuf, Vec<u8>>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.file_name().unwrap() == ".git" {
+                    continue;
+                }
+                if path.is_dir() {
+                    visit(root, &path, files);
+                } else {
+                    let content = fs::read(&path).unwrap();
+                    files.insert(path.strip_prefix(root).unwrap().to_path_buf(), content);
+                }
+            }
+        }
+        let mut files = BTreeMap::new();
+        visit(root, root, &mut files);
+        files
+    }
+
+    #[test]
+    fn test_checkout_resume() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let signer = MockSigner::default();
+        let remote_id = signer.public_key();
+        let storage = Storage::open(tempdir.path().join("storage")).unwrap();
+
+        transport::local::register(storage.clone());
+
+        let id = project_with_files(tempdir.path(), &signer, &storage);
+        let fresh = checkout(id, remote_id, tempdir.path().join("fresh"), &storage).unwrap();
+        let path = tempdir.path().join("copy");
+
+        // Interrupt the checkout after a few files were written.
+        let mut interrupt = |p: CheckoutProgress| {
+            if p.written == 3 {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            Ok(())
+        };
+        let Err(err) = checkout_with(
+            id,
+            remote_id,
+            &path,
+            &storage,
+            CheckoutOptions {
+                resume: true,
+                progress: Some(&mut interrupt),
+                ..CheckoutOptions::default()
+            },
+        ) else {
+            panic!("checkout should have failed");
+        };
+        assert_matches!(err, CheckoutError::Io(e) if e.kind() == io::ErrorKind::Interrupted);
+        assert!(!path.exists());
+
+        // The partial checkout is kept around, with the files written so far.
+        let partial = tempdir.path().join(".copy.checkout");
+        assert_eq!(files(&partial).len(), 3);
+        assert!(partial.join(".git").join(CHECKOUT_MANIFEST).exists());
+
+        // Resume the checkout. Only the remaining files are written.
+        let mut progress = Vec::new();
+        let mut record = |p: CheckoutProgress| {
+            progress.push(p.written);
+            Ok(())
+        };
+        let copy = checkout_with(
+            id,
+            remote_id,
+            &path,
+            &storage,
+            CheckoutOptions {
+                resume: true,
+                progress: Some(&mut record),
+                ..CheckoutOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(progress, vec![3, 4, 5, 6]);
+        assert!(!partial.exists());
+        assert!(!path.join(".git").join(CHECKOUT_MANIFEST).exists());
+
+        assert_eq!(files(&path), files(fresh.workdir().unwrap()));
+        assert_eq!(files(&path).len(), 6);
+        assert!(copy.statuses(None).unwrap().is_empty());
+        assert_eq!(
+            copy.head().unwrap().target(),
+            fresh.head().unwrap().target()
+        );
+    }
+
+    #[test]
+    fn test_checkout_failure_cleanup() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let signer = MockSigner::default();
+        let remote_id = signer.public_key();
+        let storage = Storage::open(tempdir.path().join("storage")).unwrap();
+
+        transport::local::register(storage.clone());
+
+        let id = project_with_files(tempdir.path(), &signer, &storage);
+        let path = tempdir.path().join("copy");
+
+        assert!(checkout_with(
+            id,
+            remote_id,
+            &path,
+            &storage,
+            CheckoutOptions {
+                sparse: Some(Path::new("nope")),
+                ..CheckoutOptions::default()
+            },
+        )
+        .is_err());
+
+        // Without resuming, nothing is left behind.
+        assert!(!path.exists());
+        assert!(!tempdir.path().join(".copy.checkout").exists());
+    }
+
+    #[test]
+    fn test_checkout_sparse() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let signer = MockSigner::default();
+        let remote_id = signer.public_key();
+        let storage = Storage::open(tempdir.path().join("storage")).unwrap();
+
+        transport::local::register(storage.clone());
+
+        let id = project_with_files(tempdir.path(), &signer, &storage);
+        let path = tempdir.path().join("copy");
+        let copy = checkout_with(
+            id,
+            remote_id,
+            &path,
+            &storage,
+            CheckoutOptions {
+                sparse: Some(Path::new("src")),
+                ..CheckoutOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            files(&path).into_keys().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("src/util/mod.rs"),
+            ]
+        );
+        // Files outside of the sparse path are not considered deleted.
+        // Nb. `libgit2` doesn't support sparse checkouts, so we ask `git` directly.
+        assert_eq!(
+            git::run::<_, _, &str, &str>(&path, ["status", "--porcelain"], []).unwrap(),
+            ""
+        );
+        assert!(copy
+            .config()
+            .unwrap()
+            .get_bool("core.sparseCheckout")
+            .unwrap());
+
+        assert!(matches!(
+            checkout_with(
+                id,
+                remote_id,
+                tempdir.path().join("other"),
+                &storage,
+                CheckoutOptions {
+                    sparse: Some(Path::new("nope")),
+                    ..CheckoutOptions::default()
+                },
+            ),
+            Err(CheckoutError::SparsePathNotFound(_))
+        ));
+    }
 }
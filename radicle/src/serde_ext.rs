@@ -74,6 +74,30 @@ pub mod localtime {
 
             Ok(LocalDuration::from_secs(seconds))
         }
+
+        pub mod option {
+            use localtime::LocalDuration;
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<S>(
+                value: &Option<LocalDuration>,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                value.map(|d| d.as_secs()).serialize(serializer)
+            }
+
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<LocalDuration>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let seconds = Option::<u64>::deserialize(deserializer)?;
+
+                Ok(seconds.map(LocalDuration::from_secs))
+            }
+        }
     }
 }
 
@@ -1,5 +1,9 @@
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::{fs, io};
+
+use localtime::LocalTime;
 
 use sqlite as sql;
 use sqlite::Value;
@@ -28,6 +32,63 @@ pub fn transaction<T>(
     }
 }
 
+/// Apply the schema migrations that weren't applied to a database yet. The database's
+/// `user_version` is the number of migrations that were applied to it. Each migration is
+/// applied in its own transaction, along with the version update.
+pub fn migrate(db: &sql::Connection, migrations: &[&str]) -> Result<(), sql::Error> {
+    let version = db
+        .prepare("PRAGMA user_version")?
+        .into_iter()
+        .next()
+        .transpose()?
+        .map_or(0, |row| row.read::<i64, _>("user_version")) as usize;
+
+    for (i, migration) in migrations.iter().enumerate().skip(version) {
+        transaction(db, |db| {
+            db.execute(migration)?;
+            db.execute(format!("PRAGMA user_version = {}", i + 1))
+        })?;
+    }
+    Ok(())
+}
+
+/// SQLite result code returned when the database disk image is malformed.
+const SQLITE_CORRUPT: isize = 11;
+/// SQLite result code returned when a file is not a database.
+const SQLITE_NOTADB: isize = 26;
+
+/// Check whether an error indicates that the database file is corrupt or
+/// isn't a database at all. Extended result codes are masked to their primary code.
+pub fn is_corrupt(err: &sql::Error) -> bool {
+    matches!(
+        err.code.map(|c| c & 0xff),
+        Some(SQLITE_CORRUPT | SQLITE_NOTADB)
+    )
+}
+
+/// Move a corrupt database file out of the way, to `<name>.corrupt.<timestamp>`,
+/// so that a fresh database can be created in its place. Any journal files that
+/// belong to it are moved along with it. Returns the path of the moved file.
+pub fn quarantine(path: &Path) -> io::Result<PathBuf> {
+    let timestamp = LocalTime::now().as_secs();
+    let suffixed = |path: &Path, suffix: &str| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    let backup = suffixed(path, &format!(".corrupt.{timestamp}"));
+
+    fs::rename(path, &backup)?;
+
+    for journal in ["-wal", "-shm", "-journal"] {
+        let from = suffixed(path, journal);
+        if from.exists() {
+            fs::rename(&from, suffixed(&backup, journal))?;
+        }
+    }
+    Ok(backup)
+}
+
 impl TryFrom<&Value> for Id {
     type Error = sql::Error;
 
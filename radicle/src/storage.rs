@@ -29,6 +29,15 @@ use self::refs::SignedRefs;
 pub type BranchName = git::RefString;
 pub type Inventory = Vec<Id>;
 
+/// The repositories found in storage.
+#[derive(Debug, Default)]
+pub struct InventoryReport {
+    /// Repositories that are valid.
+    pub inventory: Inventory,
+    /// Repositories that were skipped, because they could not be opened, or are invalid.
+    pub skipped: Vec<(Id, IdentityError)>,
+}
+
 /// Describes one or more namespaces.
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub enum Namespaces {
@@ -166,6 +175,72 @@ impl fmt::Display for RefUpdate {
     }
 }
 
+/// Severity of a validation [`Finding`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// Something unexpected, that doesn't prevent the repository from being replicated.
+    Warning,
+    /// The repository, or one of its namespaces, is corrupted.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while validating a stored repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    /// Repository the problem was found in.
+    pub rid: Id,
+    /// Namespace the problem was found in, if the problem is specific to one.
+    pub namespace: Option<RemoteId>,
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl Finding {
+    /// Create a new finding with [`Severity::Error`].
+    pub fn error(rid: Id, namespace: Option<RemoteId>, message: impl ToString) -> Self {
+        Self {
+            rid,
+            namespace,
+            severity: Severity::Error,
+            message: message.to_string(),
+        }
+    }
+
+    /// Create a new finding with [`Severity::Warning`].
+    pub fn warning(rid: Id, namespace: Option<RemoteId>, message: impl ToString) -> Self {
+        Self {
+            rid,
+            namespace,
+            severity: Severity::Warning,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.severity, self.rid)?;
+
+        if let Some(namespace) = &self.namespace {
+            write!(f, " {namespace}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
 /// Project remotes. Tracks the git state of a project.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Remotes<V>(RandomMap<RemoteId, Remote<V>>);
@@ -303,7 +378,13 @@ pub trait ReadStorage {
     /// Check whether storage contains a repository.
     fn contains(&self, rid: &Id) -> Result<bool, IdentityError>;
     /// Get the inventory of repositories hosted under this storage.
-    fn inventory(&self) -> Result<Inventory, Error>;
+    /// Invalid repositories are skipped.
+    fn inventory(&self) -> Result<Inventory, Error> {
+        self.inventory_report().map(|report| report.inventory)
+    }
+    /// Get the inventory of repositories hosted under this storage, along with the
+    /// repositories that were skipped because they are invalid.
+    fn inventory_report(&self) -> Result<InventoryReport, Error>;
     /// Open or create a read-only repository.
     fn repository(&self, rid: Id) -> Result<Self::Repository, Error>;
 }
@@ -488,8 +569,8 @@ where
         self.deref().contains(rid)
     }
 
-    fn inventory(&self) -> Result<Inventory, Error> {
-        self.deref().inventory()
+    fn inventory_report(&self) -> Result<InventoryReport, Error> {
+        self.deref().inventory_report()
     }
 
     fn get(
@@ -1,7 +1,8 @@
 pub mod cob;
+pub mod stats;
 pub mod transport;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
@@ -16,8 +17,8 @@ use crate::identity::{Identity, IdentityError, Project};
 use crate::storage::refs;
 use crate::storage::refs::{Refs, SignedRefs};
 use crate::storage::{
-    Inventory, ReadRepository, ReadStorage, Remote, Remotes, SignRepository, WriteRepository,
-    WriteStorage,
+    Finding, Inventory, InventoryReport, ReadRepository, ReadStorage, Remote, Remotes,
+    SignRepository, WriteRepository, WriteStorage,
 };
 
 pub use crate::git::*;
@@ -104,8 +105,25 @@ impl ReadStorage for Storage {
         }
     }
 
-    fn inventory(&self) -> Result<Inventory, Error> {
-        self.repositories()
+    fn inventory_report(&self) -> Result<InventoryReport, Error> {
+        let mut report = InventoryReport::default();
+
+        for rid in self.ids()? {
+            // For performance reasons, we don't do a full repository check here.
+            let result = self
+                .repository(rid)
+                .map_err(IdentityError::from)
+                .and_then(|repo| repo.head());
+
+            match result {
+                Ok(_) => report.inventory.push(rid),
+                Err(e) => {
+                    log::warn!(target: "storage", "Repository {rid} is invalid: {e}");
+                    report.skipped.push((rid, e));
+                }
+            }
+        }
+        Ok(report)
     }
 
     fn repository(&self, rid: Id) -> Result<Self::Repository, Error> {
@@ -143,8 +161,14 @@ impl Storage {
         self.path.as_path()
     }
 
+    /// Get the ids of all valid repositories in storage.
     pub fn repositories(&self) -> Result<Vec<Id>, Error> {
-        let mut repos = Vec::new();
+        self.inventory()
+    }
+
+    /// Get the ids of all repositories in storage, without checking that they are valid.
+    pub fn ids(&self) -> Result<Vec<Id>, Error> {
+        let mut ids = Vec::new();
 
         for result in fs::read_dir(&self.path)? {
             let path = result?;
@@ -159,16 +183,10 @@ impl Storage {
             }
             let rid =
                 Id::try_from(path.file_name()).map_err(|_| Error::InvalidId(path.file_name()))?;
-            let repo = self.repository(rid)?;
 
-            // For performance reasons, we don't do a full repository check here.
-            if let Err(e) = repo.head() {
-                log::warn!(target: "storage", "Repository {rid} is invalid: looking up head: {e}");
-                continue;
-            }
-            repos.push(rid);
+            ids.push(rid);
         }
-        Ok(repos)
+        Ok(ids)
     }
 
     pub fn inspect(&self) -> Result<(), Error> {
@@ -342,6 +360,108 @@ impl Repository {
                 });
         Ok(remotes)
     }
+
+    /// Validate the repository, reporting all problems found instead of stopping at the first.
+    ///
+    /// On top of what [`ReadRepository::validate`] checks, this makes sure that every namespace
+    /// has signed refs, and that the repository identity head can be resolved.
+    pub fn findings(&self) -> Result<Vec<Finding>, Error> {
+        let mut findings = Vec::new();
+        let mut signed = HashSet::new();
+
+        for id in self.remote_ids()? {
+            let id = id?;
+
+            match self.remote(&id) {
+                Ok(remote) => findings.extend(self.remote_findings(&remote)?),
+                Err(e) => findings.push(Finding::error(
+                    self.id,
+                    Some(id),
+                    format!("invalid signed refs: {e}"),
+                )),
+            }
+            signed.insert(id);
+        }
+
+        // Namespaces that have references, but no signed refs.
+        let mut dangling = Vec::new();
+        for r in self.backend.references_glob(NAMESPACES_GLOB.as_str())? {
+            let r = r?;
+            let name = r.name().ok_or(Error::InvalidRef)?;
+
+            match git::parse_ref_namespaced::<RemoteId>(name) {
+                Ok((id, _)) if !signed.contains(&id) && !dangling.contains(&id) => {
+                    dangling.push(id);
+                }
+                Ok(_) => {}
+                Err(e) => findings.push(Finding::warning(
+                    self.id,
+                    None,
+                    format!("invalid reference `{name}`: {e}"),
+                )),
+            }
+        }
+        for id in dangling {
+            findings.push(Finding::error(
+                self.id,
+                Some(id),
+                "namespace has no signed refs",
+            ));
+        }
+
+        if let Err(e) = self.identity_head() {
+            findings.push(Finding::error(
+                self.id,
+                None,
+                format!("missing identity head: {e}"),
+            ));
+        }
+        Ok(findings)
+    }
+
+    /// Validate a remote like [`ReadRepository::validate_remote`], reporting all problems found.
+    fn remote_findings(&self, remote: &Remote<Verified>) -> Result<Vec<Finding>, Error> {
+        let mut findings = Vec::new();
+        let mut signed = BTreeMap::from((*remote.refs).clone());
+
+        for (refname, oid) in self.references_of(&remote.id)? {
+            if refname == refs::SIGREFS_BRANCH.to_ref_string() {
+                continue;
+            }
+            match signed.remove(&refname) {
+                Some(signed_oid) if signed_oid != oid => findings.push(Finding::error(
+                    self.id,
+                    Some(remote.id),
+                    format!("reference `{refname}` points to {oid}, but is signed as {signed_oid}"),
+                )),
+                Some(_) => {}
+                None => findings.push(Finding::warning(
+                    self.id,
+                    Some(remote.id),
+                    format!("reference `{refname}` is not signed"),
+                )),
+            }
+        }
+        for (refname, _) in signed {
+            findings.push(Finding::error(
+                self.id,
+                Some(remote.id),
+                format!("signed reference `{refname}` is missing"),
+            ));
+        }
+
+        let identity = self
+            .identity_of(&remote.id)
+            .and_then(|identity| identity.verified(self.id));
+        if let Err(e) = identity {
+            findings.push(Finding::error(
+                self.id,
+                Some(remote.id),
+                format!("invalid identity: {e}"),
+            ));
+        }
+        Ok(findings)
+    }
 }
 
 impl ReadRepository for Repository {
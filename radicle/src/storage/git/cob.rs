@@ -42,6 +42,46 @@ pub enum TypesError {
     RefFormat(#[from] git::fmt::Error),
 }
 
+/// A COB of a remote exceeds the COB entry limits.
+#[derive(Error, Debug)]
+#[error("cob `{refname}` of remote `{remote}` is invalid: {err}")]
+pub struct LimitsError {
+    pub remote: RemoteId,
+    pub refname: RefString,
+    #[source]
+    pub err: cob::git::change::error::Check,
+}
+
+impl Repository {
+    /// Check the COBs of the given remote against the given entry limits.
+    ///
+    /// `known` returns the tip we already have of a remote's COB reference, if any.
+    /// Those tips, and the entries they depend on, aren't checked.
+    pub fn check_cob_limits(
+        &self,
+        remote: &Remote<Verified>,
+        limits: &change::Limits,
+        known: impl Fn(&RefString) -> Option<Oid>,
+    ) -> Result<(), LimitsError> {
+        let cobs = remote
+            .refs
+            .iter()
+            .filter(|(refname, _)| refname.starts_with("refs/cobs/"));
+
+        for (refname, oid) in cobs {
+            let tip = known(refname);
+
+            cob::git::change::check_limits(&self.backend, *oid, limits, |id| Some(*id) == tip)
+                .map_err(|err| LimitsError {
+                    remote: remote.id,
+                    refname: refname.clone(),
+                    err,
+                })?;
+        }
+        Ok(())
+    }
+}
+
 impl cob::Store for Repository {}
 
 impl change::Storage for Repository {
@@ -0,0 +1,684 @@
+//! Repository size and object statistics.
+//!
+//! Objects in a repository are shared between namespaces: if two remotes have the same
+//! commit, it's only stored once. Hence, each object is accounted for exactly once, in
+//! one of the following buckets:
+//!
+//! * The *exclusive* usage of a namespace, if it is only reachable from that namespace.
+//! * The *shared* usage, if it is reachable from more than one namespace.
+//! * The *unreachable* usage, if it isn't reachable from any namespace, eg. objects
+//!   awaiting garbage collection.
+//!
+//! The sum of these buckets is the total usage of the repository.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::identity::Id;
+use crate::node::Timestamp;
+use crate::storage::git::{Error, Repository};
+use crate::storage::{Oid, RemoteId};
+
+/// Number of largest objects reported in [`RepoStats`].
+pub const LARGEST_OBJECTS: usize = 10;
+
+/// Git file mode of submodule entries. These point to commits in other repositories.
+const GITLINK_FILEMODE: i32 = 0o160000;
+
+/// Number of objects and their total size.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    /// Number of objects.
+    pub objects: usize,
+    /// Total uncompressed size of the objects, in bytes.
+    pub size: u64,
+}
+
+impl Usage {
+    fn add(&mut self, size: u64) {
+        self.objects += 1;
+        self.size += size;
+    }
+}
+
+/// Statistics of a single namespace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespaceStats {
+    /// The namespace.
+    pub namespace: RemoteId,
+    /// Objects reachable from the namespace references.
+    pub reachable: Usage,
+    /// Objects reachable from this namespace only.
+    pub exclusive: Usage,
+}
+
+/// A single object in the repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectStats {
+    /// Object id.
+    pub oid: Oid,
+    /// Object type, eg. "blob".
+    pub kind: String,
+    /// Uncompressed size of the object, in bytes.
+    pub size: u64,
+    /// Example path of the object in one of the reachable trees, if any.
+    pub path: Option<String>,
+}
+
+/// Size and object statistics of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoStats {
+    /// Repository id.
+    pub rid: Id,
+    /// Size of the object database on disk, in bytes. Since objects are compressed and
+    /// delta-encoded on disk, this is usually much smaller than the total object size.
+    pub disk: u64,
+    /// Number of references, across all namespaces.
+    pub refs: usize,
+    /// Number of loose objects.
+    pub loose: usize,
+    /// Number of packed objects. Objects present in more than one pack are counted
+    /// more than once.
+    pub packed: usize,
+    /// All objects in the repository.
+    pub total: Usage,
+    /// Per-namespace breakdown.
+    pub namespaces: Vec<NamespaceStats>,
+    /// Objects reachable from more than one namespace.
+    pub shared: Usage,
+    /// Objects not reachable from any namespace.
+    pub unreachable: Usage,
+    /// The largest objects in the repository, largest first.
+    pub largest: Vec<ObjectStats>,
+    /// Last time the repository was fetched, if known. Only known to the node.
+    pub fetched: Option<Timestamp>,
+    /// Last time refs of the repository were announced, if known. Only known to the node.
+    pub announced: Option<Timestamp>,
+    /// When the statistics were computed.
+    pub computed_at: Timestamp,
+}
+
+/// Owner of an object, ie. the namespaces that can reach it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Owner {
+    /// Reachable from a single namespace.
+    One(RemoteId),
+    /// Reachable from multiple namespaces.
+    Many,
+}
+
+/// Compute the statistics of a repository.
+///
+/// Object sizes are read from the object headers, without loading the objects themselves.
+/// Only commits, trees and tags are loaded, to compute reachability. Since fetch and
+/// announcement times aren't known to storage, they are left unset.
+pub fn stats(repo: &Repository, now: Timestamp) -> Result<RepoStats, Error> {
+    let odb = repo.backend.odb()?;
+    let mut sizes = HashMap::new();
+    let mut total = Usage::default();
+    let mut error = None;
+
+    odb.foreach(|oid| match odb.read_header(*oid) {
+        Ok((size, _)) => {
+            // Nb. Objects can be listed more than once, eg. if they are both packed and loose.
+            if sizes.insert(*oid, size as u64).is_none() {
+                total.add(size as u64);
+            }
+            true
+        }
+        Err(e) => {
+            error = Some(e);
+            false
+        }
+    })
+    .map_err(|e| error.take().unwrap_or(e))?;
+
+    let mut largest = sizes
+        .iter()
+        .map(|(oid, size)| (*oid, *size))
+        .collect::<Vec<_>>();
+    largest.sort_by(|(a_oid, a_size), (b_oid, b_size)| b_size.cmp(a_size).then(a_oid.cmp(b_oid)));
+    largest.truncate(LARGEST_OBJECTS);
+
+    // Group namespace reference targets by namespace.
+    let mut tips: Vec<(RemoteId, Vec<git2::Oid>)> = Vec::new();
+    let mut refs = 0;
+    for r in repo.references()? {
+        let r = r?;
+        refs += 1;
+
+        let Some(namespace) = r.namespace else {
+            continue;
+        };
+        match tips.iter_mut().find(|(ns, _)| *ns == namespace) {
+            Some((_, oids)) => oids.push(r.oid.into()),
+            None => tips.push((namespace, vec![r.oid.into()])),
+        }
+    }
+
+    let wanted = largest.iter().map(|(oid, _)| *oid).collect::<HashSet<_>>();
+    let mut paths = HashMap::new();
+    let mut owners: HashMap<git2::Oid, Owner> = HashMap::new();
+    let mut namespaces = Vec::with_capacity(tips.len());
+
+    for (namespace, oids) in tips {
+        let mut walk = Walk {
+            repo: &repo.backend,
+            seen: HashSet::new(),
+            wanted: &wanted,
+            paths: &mut paths,
+        };
+        for oid in oids {
+            let (_, kind) = odb.read_header(oid)?;
+            walk.visit(oid, kind)?;
+        }
+
+        let mut reachable = Usage::default();
+        for oid in walk.seen {
+            reachable.add(sizes.get(&oid).copied().unwrap_or_default());
+            owners
+                .entry(oid)
+                .and_modify(|owner| {
+                    if *owner != Owner::One(namespace) {
+                        *owner = Owner::Many;
+                    }
+                })
+                .or_insert(Owner::One(namespace));
+        }
+        namespaces.push(NamespaceStats {
+            namespace,
+            reachable,
+            exclusive: Usage::default(),
+        });
+    }
+
+    let mut shared = Usage::default();
+    let mut unreachable = Usage::default();
+    for (oid, size) in &sizes {
+        match owners.get(oid) {
+            Some(Owner::One(namespace)) => {
+                if let Some(ns) = namespaces.iter_mut().find(|ns| ns.namespace == *namespace) {
+                    ns.exclusive.add(*size);
+                }
+            }
+            Some(Owner::Many) => shared.add(*size),
+            None => unreachable.add(*size),
+        }
+    }
+
+    let largest = largest
+        .into_iter()
+        .map(|(oid, size)| {
+            let (_, kind) = odb.read_header(oid)?;
+
+            Ok(ObjectStats {
+                oid: oid.into(),
+                kind: kind.str().to_owned(),
+                size,
+                path: paths.remove(&oid),
+            })
+        })
+        .collect::<Result<Vec<_>, git2::Error>>()?;
+
+    let objects = repo.backend.path().join("objects");
+    let (loose, packed) = object_counts(&objects)?;
+
+    Ok(RepoStats {
+        rid: repo.id,
+        disk: disk_usage(&objects)?,
+        refs,
+        loose,
+        packed,
+        total,
+        namespaces,
+        shared,
+        unreachable,
+        largest,
+        fetched: None,
+        announced: None,
+        computed_at: now,
+    })
+}
+
+/// Cache of repository statistics, since they are expensive to compute.
+///
+/// Entries are invalidated when the repository references or objects change, which is the
+/// case after a fetch or a garbage collection. The cache can be shared between threads:
+/// statistics are computed without holding its lock.
+#[derive(Debug, Default)]
+pub struct Cache {
+    entries: Mutex<HashMap<Id, Entry>>,
+}
+
+/// A cache entry.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Fingerprint of the repository the statistics were computed for.
+    fingerprint: u64,
+    /// Last time the statistics were found to be up to date.
+    checked_at: Timestamp,
+    /// The cached statistics.
+    stats: RepoStats,
+}
+
+impl Cache {
+    /// Get the statistics of a repository, computing them if they aren't cached or
+    /// are outdated.
+    pub fn get(&self, repo: &Repository, now: Timestamp) -> Result<RepoStats, Error> {
+        let fingerprint = fingerprint(repo)?;
+
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&repo.id) {
+            if entry.fingerprint == fingerprint {
+                entry.checked_at = now;
+
+                return Ok(entry.stats.clone());
+            }
+        }
+        let stats = stats(repo, now)?;
+        self.entries.lock().unwrap().insert(
+            repo.id,
+            Entry {
+                fingerprint,
+                checked_at: now,
+                stats: stats.clone(),
+            },
+        );
+
+        Ok(stats)
+    }
+
+    /// Get the cached statistics of a repository, without checking whether they are
+    /// outdated, along with the last time they were found to be up to date.
+    pub fn cached(&self, rid: &Id) -> Option<(RepoStats, Timestamp)> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(rid)
+            .map(|e| (e.stats.clone(), e.checked_at))
+    }
+
+    /// Evict the statistics of repositories for which the predicate returns `false`, eg.
+    /// because they were removed from storage.
+    pub fn retain(&self, f: impl Fn(&Id) -> bool) {
+        self.entries.lock().unwrap().retain(|rid, _| f(rid));
+    }
+}
+
+/// Reachability walk over the object graph of a namespace.
+struct Walk<'a> {
+    repo: &'a git2::Repository,
+    /// Objects reachable from the tips visited so far.
+    seen: HashSet<git2::Oid>,
+    /// Objects we'd like to find a path for.
+    wanted: &'a HashSet<git2::Oid>,
+    /// Paths found for wanted objects.
+    paths: &'a mut HashMap<git2::Oid, String>,
+}
+
+impl<'a> Walk<'a> {
+    fn visit(&mut self, oid: git2::Oid, kind: git2::ObjectType) -> Result<(), git2::Error> {
+        let mut stack = vec![(oid, kind, String::new())];
+
+        while let Some((oid, kind, path)) = stack.pop() {
+            if !self.seen.insert(oid) {
+                continue;
+            }
+            if !path.is_empty() && self.wanted.contains(&oid) {
+                self.paths.entry(oid).or_insert_with(|| path.clone());
+            }
+            match kind {
+                git2::ObjectType::Commit => {
+                    let commit = self.repo.find_commit(oid)?;
+
+                    stack.push((commit.tree_id(), git2::ObjectType::Tree, String::new()));
+                    stack.extend(
+                        commit
+                            .parent_ids()
+                            .map(|p| (p, git2::ObjectType::Commit, String::new())),
+                    );
+                }
+                git2::ObjectType::Tree => {
+                    let tree = self.repo.find_tree(oid)?;
+
+                    for entry in tree.iter() {
+                        if entry.filemode() == GITLINK_FILEMODE {
+                            continue;
+                        }
+                        let Some(kind) = entry.kind() else {
+                            continue;
+                        };
+                        let name = String::from_utf8_lossy(entry.name_bytes());
+                        let path = if path.is_empty() {
+                            name.into_owned()
+                        } else {
+                            format!("{path}/{name}")
+                        };
+                        stack.push((entry.id(), kind, path));
+                    }
+                }
+                git2::ObjectType::Tag => {
+                    let tag = self.repo.find_tag(oid)?;
+                    let kind = tag.target_type().unwrap_or(git2::ObjectType::Any);
+
+                    stack.push((tag.target_id(), kind, String::new()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compute a fingerprint of the repository state that changes when objects are
+/// added or removed, eg. by a fetch or a garbage collection.
+fn fingerprint(repo: &Repository) -> Result<u64, Error> {
+    let mut hasher = DefaultHasher::new();
+
+    for r in repo.backend.references()? {
+        let r = r?;
+        r.name_bytes().hash(&mut hasher);
+        r.target().hash(&mut hasher);
+    }
+
+    let objects = repo.backend.path().join("objects");
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(&objects)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_loose = {
+            let name = name.to_string_lossy();
+            name.len() == 2 && name.bytes().all(|b| b.is_ascii_hexdigit())
+        };
+        // Loose object directories change whenever an object is added to or removed
+        // from them, which is reflected in their number of entries.
+        if is_loose {
+            let count = fs::read_dir(entry.path())?.count();
+            entries.push((name, count as u64, entry.metadata()?.modified().ok()));
+        }
+    }
+    match fs::read_dir(objects.join("pack")) {
+        Ok(packs) => {
+            for entry in packs {
+                let entry = entry?;
+                let meta = entry.metadata()?;
+
+                entries.push((entry.file_name(), meta.len(), meta.modified().ok()));
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    entries.sort();
+    entries.hash(&mut hasher);
+
+    Ok(hasher.finish())
+}
+
+/// Count the loose and packed objects of an object database, like `git count-objects`.
+/// Packed objects are counted from the pack index headers, without reading the packs.
+fn object_counts(objects: &std::path::Path) -> Result<(usize, usize), io::Error> {
+    let mut loose = 0;
+    let mut packed = 0;
+
+    for entry in fs::read_dir(objects)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+
+        // Loose objects are stored under a directory named after the first byte of their id.
+        if name.len() == 2 && name.bytes().all(|b| b.is_ascii_hexdigit()) {
+            loose += fs::read_dir(entry.path())?.count();
+        }
+    }
+    let packs = match fs::read_dir(objects.join("pack")) {
+        Ok(packs) => packs,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((loose, 0)),
+        Err(e) => return Err(e),
+    };
+    for entry in packs {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "idx") {
+            packed += pack_objects(&fs::read(path)?)?;
+        }
+    }
+    Ok((loose, packed))
+}
+
+/// Get the number of objects in a pack, given its index. The last entry of the fan-out
+/// table holds the object count.
+fn pack_objects(idx: &[u8]) -> Result<usize, io::Error> {
+    /// Magic number of version 2 pack indexes. Version 1 indexes have no header.
+    const MAGIC: &[u8] = b"\xfftOc";
+    const FANOUT_SIZE: usize = 256 * 4;
+
+    let fanout = if idx.starts_with(MAGIC) {
+        idx.get(8..8 + FANOUT_SIZE)
+    } else {
+        idx.get(..FANOUT_SIZE)
+    }
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated pack index"))?;
+    let mut count = [0; 4];
+    count.copy_from_slice(&fanout[FANOUT_SIZE - 4..]);
+
+    Ok(u32::from_be_bytes(count) as usize)
+}
+
+/// Compute the disk usage of a directory, like `du`.
+fn disk_usage(path: &std::path::Path) -> Result<u64, io::Error> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+
+        if meta.is_dir() {
+            size += disk_usage(&entry.path())?;
+        } else {
+            size += meta.len();
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto::test::signer::MockSigner;
+    use crypto::Signer as _;
+
+    use super::*;
+    use crate::storage::{ReadStorage, WriteStorage};
+    use crate::test::arbitrary;
+    use crate::test::fixtures;
+
+    /// Size of the large, incompressible blob added to the fixture.
+    const BLOB_SIZE: usize = 256 * 1024;
+
+    #[test]
+    fn test_stats() {
+        let tmp = tempfile::tempdir().unwrap();
+        let signer = MockSigner::default();
+        let storage = fixtures::storage(tmp.path(), &signer).unwrap();
+        let rid = *storage.inventory().unwrap().first().unwrap();
+        let repo = storage.repository_mut(rid).unwrap();
+        let raw = &repo.backend;
+        let alice = *signer.public_key();
+        let bob = arbitrary::gen::<RemoteId>(1);
+
+        // Give bob a copy of all of alice's refs, so that her objects are all shared.
+        for r in repo.references().unwrap() {
+            let r = r.unwrap();
+            if r.namespace == Some(alice) {
+                let name = format!("refs/namespaces/{bob}/{}", r.name);
+                raw.reference(&name, r.oid.into(), false, "").unwrap();
+            }
+        }
+        // Add a commit with a large file under bob's namespace only.
+        let data = (0..BLOB_SIZE).map(|_| fastrand::u8(..)).collect::<Vec<_>>();
+        let blob = raw.blob(&data).unwrap();
+        let mut assets = raw.treebuilder(None).unwrap();
+        assets.insert("big.bin", blob, 0o100644).unwrap();
+        let assets = assets.write().unwrap();
+        let mut root = raw.treebuilder(None).unwrap();
+        root.insert("assets", assets, 0o040000).unwrap();
+        let root = raw.find_tree(root.write().unwrap()).unwrap();
+        let sig = git2::Signature::now("bob", "bob@radicle.xyz").unwrap();
+        raw.commit(
+            Some(&format!("refs/namespaces/{bob}/refs/heads/big")),
+            &sig,
+            &sig,
+            "Add big file",
+            &root,
+            &[],
+        )
+        .unwrap();
+
+        let stats = stats(&repo, 42).unwrap();
+        let mut objects = HashSet::new();
+        raw.odb()
+            .unwrap()
+            .foreach(|oid| {
+                objects.insert(*oid);
+                true
+            })
+            .unwrap();
+
+        assert_eq!(stats.rid, rid);
+        assert_eq!(stats.refs, repo.references().unwrap().count());
+        assert_eq!(stats.computed_at, 42);
+        assert_eq!(stats.total.objects, objects.len());
+        assert!(stats.total.size >= BLOB_SIZE as u64);
+        // The blob is incompressible, so it takes at least as much space on disk.
+        assert!(stats.disk >= BLOB_SIZE as u64);
+
+        // Each object is accounted for exactly once.
+        let mut sum = Usage::default();
+        for ns in &stats.namespaces {
+            sum.objects += ns.exclusive.objects;
+            sum.size += ns.exclusive.size;
+        }
+        assert_eq!(
+            sum.objects + stats.shared.objects + stats.unreachable.objects,
+            stats.total.objects
+        );
+        assert_eq!(
+            sum.size + stats.shared.size + stats.unreachable.size,
+            stats.total.size
+        );
+
+        let alice = stats
+            .namespaces
+            .iter()
+            .find(|ns| ns.namespace == alice)
+            .unwrap();
+        let bob = stats
+            .namespaces
+            .iter()
+            .find(|ns| ns.namespace == bob)
+            .unwrap();
+        assert_eq!(stats.namespaces.len(), 2);
+        assert_eq!(alice.exclusive, Usage::default());
+        assert_eq!(alice.reachable, stats.shared);
+        // The commit, two trees and the blob.
+        assert_eq!(bob.exclusive.objects, 4);
+        assert!(bob.exclusive.size > BLOB_SIZE as u64);
+
+        let largest = stats.largest.first().unwrap();
+        assert_eq!(largest.oid, blob.into());
+        assert_eq!(largest.kind, "blob");
+        assert_eq!(largest.size, BLOB_SIZE as u64);
+        assert_eq!(largest.path.as_deref(), Some("assets/big.bin"));
+
+        // Objects that aren't reachable from any namespace.
+        raw.blob(b"dangling").unwrap();
+        assert_eq!(
+            super::stats(&repo, 42).unwrap().unreachable.objects,
+            stats.unreachable.objects + 1
+        );
+    }
+
+    #[test]
+    fn test_object_counts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let signer = MockSigner::default();
+        let storage = fixtures::storage(tmp.path(), &signer).unwrap();
+        let rid = *storage.inventory().unwrap().first().unwrap();
+        let repo = storage.repository_mut(rid).unwrap();
+        let raw = &repo.backend;
+        let mut objects = Vec::new();
+        raw.odb()
+            .unwrap()
+            .foreach(|oid| {
+                objects.push(*oid);
+                true
+            })
+            .unwrap();
+
+        let stats = stats(&repo, 42).unwrap();
+        assert_eq!(stats.loose, objects.len());
+        assert_eq!(stats.packed, 0);
+
+        // Pack all objects.
+        let mut builder = raw.packbuilder().unwrap();
+        for oid in &objects {
+            builder.insert_object(*oid, None).unwrap();
+        }
+        builder
+            .write(&raw.path().join("objects").join("pack"), 0o644)
+            .unwrap();
+
+        let stats = super::stats(&repo, 42).unwrap();
+        assert_eq!(stats.loose, objects.len());
+        assert_eq!(stats.packed, objects.len());
+    }
+
+    #[test]
+    fn test_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let signer = MockSigner::default();
+        let storage = fixtures::storage(tmp.path(), &signer).unwrap();
+        let rid = *storage.inventory().unwrap().first().unwrap();
+        let repo = storage.repository_mut(rid).unwrap();
+        let cache = Cache::default();
+
+        let before = cache.get(&repo, 1).unwrap();
+        // Up to date entries are returned as they are, but are marked as checked.
+        assert_eq!(cache.get(&repo, 2).unwrap(), before);
+        assert_eq!(cache.cached(&rid), Some((before.clone(), 2)));
+
+        // Updating references, eg. after a fetch, invalidates the entry.
+        let blob = repo.backend.blob(b"hello").unwrap();
+        let nid = arbitrary::gen::<RemoteId>(1);
+        repo.backend
+            .reference(
+                &format!("refs/namespaces/{nid}/refs/heads/x"),
+                blob,
+                false,
+                "",
+            )
+            .unwrap();
+
+        let after = cache.get(&repo, 3).unwrap();
+        assert_ne!(after, before);
+        assert_eq!(after, stats(&repo, 3).unwrap());
+
+        // So does adding loose objects, eg. when a fetch is unpacked.
+        repo.backend.blob(&[0xff; 1024]).unwrap();
+
+        let loose = cache.get(&repo, 4).unwrap();
+        assert_ne!(loose, after);
+        assert_eq!(loose, stats(&repo, 4).unwrap());
+
+        // Entries of repositories removed from storage can be evicted.
+        cache.retain(|id| *id != rid);
+        assert_eq!(cache.cached(&rid), None);
+    }
+}
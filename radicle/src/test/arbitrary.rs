@@ -246,10 +246,15 @@ impl Arbitrary for Address {
             AddressType::Onion => todo!(),
         };
 
-        Address::from(cyphernet::addr::NetAddr {
+        let addr = Address::from(cyphernet::addr::NetAddr {
             host,
             port: u16::arbitrary(g),
-        })
+        });
+        // Only generate addresses that can be dialed.
+        if addr.validate(&Default::default()).is_err() {
+            return Self::arbitrary(g);
+        }
+        addr
     }
 }
 
@@ -1,4 +1,6 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{env, fs};
 
 use crate::crypto::{Signer, Verified};
 use crate::git;
@@ -11,6 +13,10 @@ use crate::storage::refs::SignedRefs;
 /// The birth of the radicle project, January 1st, 2018.
 const RADICLE_EPOCH: i64 = 1514817556;
 
+/// Version of the fixture generation code. This is part of the fixture cache key, and must
+/// be bumped whenever [`repository`] or [`populate`] change what they generate.
+pub const FIXTURES_VERSION: u32 = 1;
+
 /// Create a new storage with a project.
 pub fn storage<P: AsRef<Path>, G: Signer>(path: P, signer: &G) -> Result<Storage, rad::InitError> {
     let path = path.as_ref();
@@ -132,6 +138,84 @@ pub fn populate(repo: &git2::Repository, scale: usize) -> Vec<git::Qualified> {
     refs
 }
 
+/// Create a repository at the given path, populated with [`populate`] at the given scale.
+///
+/// The populated repository is only generated once per scale, and cached in a shared
+/// temporary directory. Subsequent calls clone the cached repository, hardlinking its
+/// objects when the filesystem allows it. This makes it cheap to use large fixtures in
+/// many tests.
+pub fn populated<P: AsRef<Path>>(path: P, scale: usize) -> git2::Repository {
+    let path = path.as_ref();
+    let template = template(scale);
+    let url = template
+        .to_str()
+        .expect("fixtures::populated: invalid template path");
+    let repo = git2::build::RepoBuilder::new()
+        .clone_local(git2::build::CloneLocal::Local)
+        .clone(url, path)
+        .or_else(|_| {
+            // Hardlinking is not supported everywhere, eg. across devices.
+            fs::remove_dir_all(path).ok();
+            git2::build::RepoBuilder::new()
+                .clone_local(git2::build::CloneLocal::NoLinks)
+                .clone(url, path)
+        })
+        .unwrap();
+
+    // Turn the remote branches into local branches, so that the repository looks exactly
+    // like a freshly populated one.
+    {
+        let branches = repo
+            .branches(Some(git2::BranchType::Remote))
+            .unwrap()
+            .map(|b| b.unwrap().0)
+            .collect::<Vec<_>>();
+
+        for branch in branches {
+            let name = branch.name().unwrap().unwrap();
+            let Some(name) = name.strip_prefix("origin/") else {
+                continue;
+            };
+            if name == "HEAD" || repo.find_branch(name, git2::BranchType::Local).is_ok() {
+                continue;
+            }
+            let commit = branch.get().peel_to_commit().unwrap();
+            repo.branch(name, &commit, false).unwrap();
+        }
+    }
+    repo.remote_delete("origin").unwrap();
+    repo
+}
+
+/// Get the path to a cached populated repository of the given scale, generating it if
+/// necessary.
+fn template(scale: usize) -> PathBuf {
+    // Serializes fixture generation between tests running in the same process.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root = env::temp_dir().join("radicle-fixtures");
+    let path = root.join(format!("populated-v{FIXTURES_VERSION}-{scale}"));
+
+    if path.exists() {
+        return path;
+    }
+    fs::create_dir_all(&root).unwrap();
+
+    // Generate the repository in a staging directory, and move it in place atomically.
+    // If another process got there first, the rename fails and we use theirs.
+    let staging = tempfile::tempdir_in(&root).unwrap();
+    {
+        let (repo, _) = repository(staging.path());
+        populate(&repo, scale);
+    }
+    let staging = staging.into_path();
+    if fs::rename(&staging, &path).is_err() {
+        fs::remove_dir_all(&staging).ok();
+    }
+    path
+}
+
 /// Generate random fixtures.
 pub mod gen {
     use super::*;
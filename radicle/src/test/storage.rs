@@ -72,8 +72,11 @@ impl ReadStorage for MockStorage {
         Ok(self.inventory.get(&proj).cloned())
     }
 
-    fn inventory(&self) -> Result<Inventory, Error> {
-        Ok(self.inventory.keys().cloned().collect::<Vec<_>>())
+    fn inventory_report(&self) -> Result<InventoryReport, Error> {
+        Ok(InventoryReport {
+            inventory: self.inventory.keys().cloned().collect::<Vec<_>>(),
+            skipped: vec![],
+        })
     }
 
     fn repository(&self, rid: Id) -> Result<Self::Repository, Error> {
@@ -155,7 +158,14 @@ impl ReadRepository for MockRepository {
         self.remotes
             .get(id)
             .map(|refs| Remote { refs: refs.clone() })
-            .ok_or(refs::Error::InvalidRef)
+            .ok_or_else(|| {
+                // Mimic the error returned by git storage for a missing namespace.
+                refs::Error::GitExt(git_ext::Error::Git(git2::Error::new(
+                    git2::ErrorCode::NotFound,
+                    git2::ErrorClass::Reference,
+                    format!("remote {id} not found"),
+                )))
+            })
     }
 
     fn remotes(&self) -> Result<Remotes<Verified>, refs::Error> {